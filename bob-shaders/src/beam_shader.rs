@@ -0,0 +1,91 @@
+use gl::types::GLint;
+use gl_thin::gl_helper::{GLErrorWrapper, Program};
+use gl_thin::linear::XrMatrix4x4f;
+
+/// A shaft of light through dusty air: a mesh drawn in one color, added onto what is behind
+/// it, brightest where each vertex's `a_fade` is 1 and gone where it is 0.  It tests depth but
+/// does not write it, so it belongs with the other transparent draws, after the opaque ones.
+pub struct BeamShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sal_fade: u32,
+    pub sul_matrix: u32,
+    pub sul_color: u32,
+}
+
+impl BeamShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src())?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sal_fade = program.get_attribute_location("a_fade")?;
+        let sul_matrix = program.get_uniform_location("matrix")?;
+        let sul_color = program.get_uniform_location("u_color")?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sal_fade,
+            sul_matrix,
+            sul_color,
+        })
+    }
+
+    /// `color`'s alpha scales how much is added.  Call before binding the buffers; undo with
+    /// [BeamShader::restore] after drawing.
+    pub fn set_params(
+        &self,
+        matrix: &XrMatrix4x4f,
+        color: &[f32; 4],
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+        self.program
+            .set_mat4u(self.sul_matrix as GLint, matrix.slice())?;
+        self.program
+            .set_uniform_4fv(self.sul_color as GLint, color)?;
+        unsafe {
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE);
+            gl::DepthMask(gl::FALSE);
+        }
+        Ok(())
+    }
+
+    /// back to the blending and depth writes the rest of the scene draws with
+    pub fn restore(&self) {
+        unsafe {
+            gl::DepthMask(gl::TRUE);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "
+attribute vec3 a_position;
+attribute float a_fade;
+
+uniform mat4 matrix;
+
+varying float v_fade;
+
+void main()
+{
+    gl_Position = matrix * vec4(a_position, 1.0);
+    v_fade = a_fade;
+}
+"
+}
+
+fn shader_f_src() -> &'static str {
+    "#ifdef GL_ES
+precision mediump float;
+#endif
+uniform vec4 u_color;
+
+varying float v_fade;
+
+void main()
+{
+    gl_FragColor = vec4(u_color.rgb, u_color.a * v_fade * v_fade);
+}"
+}
@@ -0,0 +1,49 @@
+//! GLSL for shaders that also have to run where gl_thin::gl_caps finds a GLES2-class context.
+
+use gl::types::GLint;
+use gl_thin::gl_caps::GlCaps;
+use gl_thin::gl_helper::Program;
+
+/// Put this first in a vertex shader written in GLSL ES 1.00 that calls `instance_id()`, and
+/// draw it with gl_thin::gl_fancy::BoundBuffers::draw_elements_instanced.  With instancing
+/// the shader is compiled as GLSL ES 3.00 and reads `gl_InstanceID`; without, every instance
+/// is its own draw and the id comes from `u_instance_id`.
+pub fn instance_id_preamble(caps: &GlCaps) -> &'static str {
+    if caps.instancing {
+        "#version 300 es
+#define attribute in
+#define varying out
+int instance_id()
+{
+    return gl_InstanceID;
+}
+"
+    } else {
+        "
+uniform int u_instance_id;
+int instance_id()
+{
+    return u_instance_id;
+}
+"
+    }
+}
+
+/// Where [instance_id_preamble]'s `u_instance_id` is, or -1 (which glUniform ignores) for the
+/// instanced version, which has none.
+pub fn instance_id_location(program: &Program) -> GLint {
+    program
+        .get_uniform_location("u_instance_id")
+        .map_or(-1, |location| location as GLint)
+}
+
+/// Paste into a fragment shader and call `encode_srgb(color)` on the linear result, for a
+/// destination that stores sRGB-encoded values without the GPU encoding them (an RGBA8
+/// swapchain on a device without sRGB framebuffers).  A power of 1/2.2 is close enough to the
+/// real curve everywhere but the darkest shades.
+pub const SRGB_ENCODE_GLSL: &str = "
+vec3 encode_srgb(vec3 linear)
+{
+    return pow(clamp(linear, 0.0, 1.0), vec3(1.0 / 2.2));
+}
+";
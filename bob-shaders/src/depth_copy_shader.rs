@@ -0,0 +1,82 @@
+use crate::GeometryBuffer;
+use gl::types::{GLint, GLsizei};
+use gl_thin::gl_fancy::{ActiveTextureUnit, GPUState};
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program, Texture};
+
+/// Writes a depth texture's values into the bound framebuffer's depth buffer, which may be
+/// a different size or format, e.g. an eye's depth into XR_FB_space_warp's smaller depth
+/// image.  Depth writes need GL_DEPTH_TEST on (with GL_ALWAYS).  Needs GLSL ES 3.00.  Draw a
+/// quad covering clip space from -1 to 1.
+pub struct DepthCopyShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sul_depth: u32,
+}
+
+impl DepthCopyShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src())?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sul_depth = program.get_uniform_location("depth")?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sul_depth,
+        })
+    }
+
+    /// `depth` is a TEXTURE_2D with NEAREST filtering
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        depth: &Texture,
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+
+        let unit = ActiveTextureUnit(0);
+        gpu_state.set_active_texture(unit)?;
+        depth.bind(gl::TEXTURE_2D)?;
+        self.program
+            .set_uniform_1i(self.sul_depth as GLint, unit.0 as GLint)?;
+
+        let bindings = buffers.activate(gpu_state);
+
+        bindings.draw_elements(gl::TRIANGLE_STRIP, n_indices, 0)?;
+
+        buffers.deactivate(bindings);
+        unsafe {
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+
+        Ok(())
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "#version 300 es
+in vec4 a_position;
+out vec2 v_uv;
+
+void main()
+{
+    v_uv = a_position.xy * 0.5 + 0.5;
+    gl_Position = vec4(a_position.xy, 0.0, 1.0);
+}
+"
+}
+
+fn shader_f_src() -> &'static str {
+    "#version 300 es
+precision highp float;
+uniform highp sampler2D depth;
+in vec2 v_uv;
+
+void main()
+{
+    gl_FragDepth = texture(depth, v_uv).r;
+}"
+}
@@ -0,0 +1,102 @@
+use crate::GeometryBuffer;
+use gl::types::{GLint, GLsizei};
+use gl_thin::gl_fancy::{ActiveTextureUnit, GPUState};
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program, Texture};
+use gl_thin::linear::XrMatrix4x4f;
+
+/// Wraps an equirectangular (360°) image around the viewer.  Like
+/// [crate::sky_shader::SkyShader], the color only depends on the direction from the origin
+/// of the mesh's local coordinates, so a cube centered on the eye is enough.  The middle
+/// of the image faces -Z and its top is +Y.
+pub struct EquirectShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sul_matrix: u32,
+    pub sul_texture: u32,
+}
+
+impl EquirectShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src())?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sul_matrix = program.get_uniform_location("u_matrix")?;
+        let sul_texture = program.get_uniform_location("tex")?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sul_matrix,
+            sul_texture,
+        })
+    }
+
+    /// `texture` is a TEXTURE_2D; `matrix` carries the mesh to clip space and should be
+    /// centered on the eye.
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        matrix: &XrMatrix4x4f,
+        texture: &Texture,
+        texture_unit: ActiveTextureUnit,
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+
+        gpu_state.set_active_texture(texture_unit)?;
+        texture.bind(gl::TEXTURE_2D)?;
+        self.program
+            .set_mat4u(self.sul_matrix as GLint, matrix.slice())?;
+        self.program
+            .set_uniform_1i(self.sul_texture as GLint, texture_unit.0 as GLint)?;
+
+        let bindings = buffers.activate(gpu_state);
+
+        bindings.draw_elements(gl::TRIANGLES, n_indices, 0)?;
+
+        buffers.deactivate(bindings);
+        unsafe {
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+
+        Ok(())
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "
+attribute vec4 a_position;
+
+uniform mat4 u_matrix;
+
+varying vec3 v_direction;
+
+void main()
+{
+    v_direction = a_position.xyz;
+    gl_Position = u_matrix * a_position;
+}
+"
+}
+
+fn shader_f_src() -> &'static str {
+    "#ifdef GL_ES
+precision highp float;
+#endif
+varying vec3 v_direction;
+
+uniform sampler2D tex;
+
+const float PI = 3.14159265;
+
+void main()
+{
+    vec3 dir = normalize(v_direction);
+    // forward (-Z) is the middle of the image, and turning right moves toward its right edge
+    float u = 0.5 + atan(dir.x, -dir.z) / (2.0 * PI);
+    float v = 0.5 + asin(clamp(dir.y, -1.0, 1.0)) / PI;
+    gl_FragColor = texture2D(tex, vec2(u, v));
+}
+"
+}
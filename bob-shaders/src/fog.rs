@@ -0,0 +1,95 @@
+use gl::types::GLint;
+use gl_thin::gl_helper::{GLErrorWrapper, Program};
+
+/// How quickly things fade into the fog color with view-space depth (meters)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FogMode {
+    Off,
+    /// untouched before `start`, fully fogged past `end`
+    Linear {
+        start: f32,
+        end: f32,
+    },
+    /// `exp(-density * depth)`
+    Exponential {
+        density: f32,
+    },
+    /// `exp(-(density * depth)²)`, which keeps the near field clearer
+    ExponentialSquared {
+        density: f32,
+    },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fog {
+    pub color: [f32; 3],
+    pub mode: FogMode,
+}
+
+impl Fog {
+    pub fn none() -> Self {
+        Self {
+            color: [0.0; 3],
+            mode: FogMode::Off,
+        }
+    }
+
+    /// `[kind, density, start, end]` the way [FOG_GLSL] wants them
+    fn parameters(&self) -> [f32; 4] {
+        match self.mode {
+            FogMode::Off => [0.0, 0.0, 0.0, 0.0],
+            FogMode::Linear { start, end } => [1.0, 0.0, start, end.max(start + 1e-3)],
+            FogMode::Exponential { density } => [2.0, density, 0.0, 0.0],
+            FogMode::ExponentialSquared { density } => [3.0, density, 0.0, 0.0],
+        }
+    }
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// Paste into a fragment shader and call `apply_fog(color, depth)`, with `depth` being the
+/// view-space distance in front of the eye (`gl_Position.w` from the vertex shader works).
+pub const FOG_GLSL: &str = "
+uniform vec3 u_fog_color;
+// kind, density, start, end
+uniform vec4 u_fog;
+
+vec3 apply_fog(vec3 color, float depth)
+{
+    float visible = 1.0;
+    if (u_fog.x > 2.5) {
+        float d = u_fog.y * depth;
+        visible = exp(-d * d);
+    } else if (u_fog.x > 1.5) {
+        visible = exp(-u_fog.y * depth);
+    } else if (u_fog.x > 0.5) {
+        visible = (u_fog.w - depth) / (u_fog.w - u_fog.z);
+    }
+    return mix(u_fog_color, color, clamp(visible, 0.0, 1.0));
+}
+";
+
+/// the locations of the uniforms declared by [FOG_GLSL]
+pub struct FogUniforms {
+    pub sul_fog_color: u32,
+    pub sul_fog: u32,
+}
+
+impl FogUniforms {
+    pub fn new(program: &Program) -> Result<Self, GLErrorWrapper> {
+        Ok(Self {
+            sul_fog_color: program.get_uniform_location("u_fog_color")?,
+            sul_fog: program.get_uniform_location("u_fog")?,
+        })
+    }
+
+    /// `program` must be in use
+    pub fn set(&self, program: &Program, fog: &Fog) -> Result<(), GLErrorWrapper> {
+        program.set_uniform_3fv(self.sul_fog_color as GLint, &fog.color)?;
+        program.set_uniform_4fv(self.sul_fog as GLint, &fog.parameters())
+    }
+}
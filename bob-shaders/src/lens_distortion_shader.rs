@@ -0,0 +1,172 @@
+use crate::GeometryBuffer;
+use gl::types::{GLint, GLsizei};
+use gl_thin::gl_fancy::{ActiveTextureUnit, GPUState};
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program, Texture};
+
+/// A radial (Brown–Conrady) model of a headset lens.  Radii are measured from `center`,
+/// with 1.0 at the top and bottom edges of the image.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LensDistortionParameters {
+    /// `r' = r (1 + k1 r² + k2 r⁴)`; positive values give barrel distortion
+    pub k1: f32,
+    pub k2: f32,
+    /// Extra scale for the red, green and blue channels.  Lenses bend blue more than red,
+    /// which shows up as colored fringes toward the edges.
+    pub channel_scale: [f32; 3],
+    /// in texture coordinates
+    pub center: [f32; 2],
+    /// width / height of the image, so the distortion stays circular
+    pub aspect_ratio: f32,
+    /// >1 enlarges the result, trimming the black corners the distortion leaves
+    pub zoom: f32,
+}
+
+impl Default for LensDistortionParameters {
+    /// roughly a current fresnel-lens headset
+    fn default() -> Self {
+        Self {
+            k1: 0.22,
+            k2: 0.24,
+            channel_scale: [0.994, 1.0, 1.012],
+            center: [0.5, 0.5],
+            aspect_ratio: 1.0,
+            zoom: 1.0,
+        }
+    }
+}
+
+/// Resamples a texture the way [LensDistortionParameters] describes, for previews of what
+/// the user sees through the lenses.  Draw a quad covering clip space from -1 to 1;
+/// the texture coordinates are derived from the positions.
+pub struct LensDistortionShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sul_texture: u32,
+    pub sul_k: u32,
+    pub sul_channel_scale: u32,
+    pub sul_center: u32,
+    pub sul_aspect_ratio: u32,
+}
+
+impl LensDistortionShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src())?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sul_texture = program.get_uniform_location("tex")?;
+        let sul_k = program.get_uniform_location("u_k")?;
+        let sul_channel_scale = program.get_uniform_location("u_channel_scale")?;
+        let sul_center = program.get_uniform_location("u_center")?;
+        let sul_aspect_ratio = program.get_uniform_location("u_aspect_ratio")?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sul_texture,
+            sul_k,
+            sul_channel_scale,
+            sul_center,
+            sul_aspect_ratio,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        texture: &Texture,
+        texture_unit: ActiveTextureUnit,
+        parameters: &LensDistortionParameters,
+        draw_mode: gl::types::GLenum,
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+
+        gpu_state.set_active_texture(texture_unit)?;
+        texture.bind(gl::TEXTURE_2D)?;
+        self.set_parameters(texture_unit, parameters)?;
+
+        let bindings = buffers.activate(gpu_state);
+
+        bindings.draw_elements(draw_mode, n_indices, 0)?;
+
+        buffers.deactivate(bindings);
+        unsafe {
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+
+        Ok(())
+    }
+
+    pub fn set_parameters(
+        &self,
+        texture_unit: ActiveTextureUnit,
+        parameters: &LensDistortionParameters,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program
+            .set_uniform_1i(self.sul_texture as GLint, texture_unit.0 as GLint)?;
+        self.program.set_uniform_3fv(
+            self.sul_k as GLint,
+            &[parameters.k1, parameters.k2, 1.0 / parameters.zoom],
+        )?;
+        self.program
+            .set_uniform_3fv(self.sul_channel_scale as GLint, &parameters.channel_scale)?;
+        self.program
+            .set_uniform_2fv(self.sul_center as GLint, &parameters.center)?;
+        self.program
+            .set_uniform_1f(self.sul_aspect_ratio as GLint, parameters.aspect_ratio)
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "
+attribute vec4 a_position;
+
+varying vec2 v_uv;
+
+void main()
+{
+    v_uv = a_position.xy * 0.5 + 0.5;
+    gl_Position = vec4(a_position.xy, 0.0, 1.0);
+}
+"
+}
+
+fn shader_f_src() -> &'static str {
+    "#ifdef GL_ES
+precision highp float;
+#endif
+varying vec2 v_uv;
+uniform sampler2D tex;
+uniform vec3 u_k; // k1, k2, 1/zoom
+uniform vec3 u_channel_scale;
+uniform vec2 u_center;
+uniform float u_aspect_ratio;
+
+// where the lens takes the light that ends up at v_uv from
+vec2 distort(float channel_scale)
+{
+    vec2 aspect = vec2(u_aspect_ratio, 1.0);
+    vec2 p = (v_uv - u_center) * 2.0 * aspect;
+    float r2 = dot(p, p);
+    float f = (1.0 + u_k.x * r2 + u_k.y * r2 * r2) * channel_scale * u_k.z;
+    return u_center + p * f / (2.0 * aspect);
+}
+
+// the texture, or black outside it
+float channel(vec2 uv, int c)
+{
+    vec2 inside = step(vec2(0.0), uv) * step(uv, vec2(1.0));
+    vec4 color = texture2D(tex, uv) * inside.x * inside.y;
+    return c == 0 ? color.r : c == 1 ? color.g : color.b;
+}
+
+void main()
+{
+    gl_FragColor = vec4(channel(distort(u_channel_scale.r), 0),
+                        channel(distort(u_channel_scale.g), 1),
+                        channel(distort(u_channel_scale.b), 2),
+                        1.0);
+}"
+}
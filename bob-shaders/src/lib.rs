@@ -1,10 +1,33 @@
 use gl_thin::gl_fancy::{BoundBuffers, GPUState, VertexBufferBundle};
 
+pub mod beam_shader;
+pub mod compat;
+pub mod depth_copy_shader;
+pub mod equirect_shader;
 pub mod flat_color_shader;
+pub mod fog;
 pub mod geometry;
+pub mod lens_distortion_shader;
+pub mod lightmap_shader;
+pub mod lights;
+pub mod mask_shader;
 pub mod masked_solid_shader;
+pub mod material;
+pub mod mirror_shader;
+pub mod motion_vector_shader;
+pub mod msaa_resolve_shader;
+pub mod outline_shader;
 pub mod raw_texture_shader;
+pub mod screen_space_texture_shader;
+pub mod sdf_shape_shader;
+pub mod skinned_mask_shader;
+pub mod sky_shader;
+pub mod spherical_harmonics;
+pub mod srgb_encode_shader;
 pub mod sun_phong_shader;
+pub mod temporal_aa_shader;
+pub mod tonemap_shader;
+pub mod yuv_shader;
 
 pub trait GeometryBuffer<AT, IT> {
     fn activate<'a>(&'a self, gpu_state: &'a mut GPUState) -> BoundBuffers<'a, AT, IT>;
@@ -0,0 +1,173 @@
+use crate::fog::{Fog, FogUniforms, FOG_GLSL};
+use crate::GeometryBuffer;
+use gl::types::{GLint, GLsizei, GLuint};
+use gl_thin::gl_fancy::{ActiveTextureUnit, GPUState};
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program, Texture};
+use gl_thin::linear::XrMatrix4x4f;
+
+/// Floats per vertex for [LightmapShader]: XYZ, the base color UV, then the lightmap UV
+pub const XYZ_UV_UV2_STRIDE: GLsizei = 7;
+
+/// Pack separate position, base color UV and lightmap UV arrays into rows of
+/// [XYZ_UV_UV2_STRIDE] floats.  Stops at the shortest of the three.
+pub fn interleave_xyz_uv_uv2(
+    positions: &[[f32; 3]],
+    uvs: &[[f32; 2]],
+    lightmap_uvs: &[[f32; 2]],
+) -> Vec<f32> {
+    let mut rval = Vec::with_capacity(positions.len() * XYZ_UV_UV2_STRIDE as usize);
+    for ((xyz, uv), uv2) in positions.iter().zip(uvs).zip(lightmap_uvs) {
+        rval.extend_from_slice(xyz);
+        rval.extend_from_slice(uv);
+        rval.extend_from_slice(uv2);
+    }
+    rval
+}
+
+/// For static geometry whose lighting was baked by an external tool: the base color texture
+/// is sampled with the first UV set, the lightmap with the second (usually a unique, non-overlapping
+/// unwrap), and the two are multiplied.  No lighting is computed at runtime.
+pub struct LightmapShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sal_tex_coord: u32,
+    pub sal_lightmap_coord: u32,
+    pub sul_m_matrix: u32,
+    pub sul_pv_matrix: u32,
+    pub sul_tint: u32,
+    pub sul_base_color: u32,
+    pub sul_lightmap: u32,
+    pub fog_uniforms: FogUniforms,
+}
+
+impl LightmapShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src())?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sal_tex_coord = program.get_attribute_location("a_texcoord")?;
+        let sal_lightmap_coord = program.get_attribute_location("a_texcoord2")?;
+        let sul_m_matrix = program.get_uniform_location("m_matrix")?;
+        let sul_pv_matrix = program.get_uniform_location("pv_matrix")?;
+        let sul_tint = program.get_uniform_location("tint")?;
+        let sul_base_color = program.get_uniform_location("base_color")?;
+        let sul_lightmap = program.get_uniform_location("lightmap")?;
+        let fog_uniforms = FogUniforms::new(&program)?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sal_tex_coord,
+            sal_lightmap_coord,
+            sul_m_matrix,
+            sul_pv_matrix,
+            sul_tint,
+            sul_base_color,
+            sul_lightmap,
+            fog_uniforms,
+        })
+    }
+
+    /// `(location, width, offset)` for a [gl_thin::gl_fancy::VertexBufferBundle] of
+    /// [XYZ_UV_UV2_STRIDE] floats per vertex
+    pub fn attributes(&self) -> [(GLuint, GLint, GLsizei); 3] {
+        [
+            (self.sal_position, 3, 0),
+            (self.sal_tex_coord, 2, 3),
+            (self.sal_lightmap_coord, 2, 5),
+        ]
+    }
+
+    /// `tint` multiplies the result, and may go past 1 to brighten a lightmap baked dim.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        m_matrix: &XrMatrix4x4f,
+        pv_matrix: &XrMatrix4x4f,
+        base_color: &Texture,
+        lightmap: &Texture,
+        tint: &[f32; 3],
+        fog: &Fog,
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+
+        let base_unit = ActiveTextureUnit(0);
+        gpu_state.set_active_texture(base_unit)?;
+        base_color.bind(gl::TEXTURE_2D)?;
+        let lightmap_unit = ActiveTextureUnit(1);
+        gpu_state.set_active_texture(lightmap_unit)?;
+        lightmap.bind(gl::TEXTURE_2D)?;
+
+        self.program
+            .set_mat4u(self.sul_m_matrix as GLint, m_matrix.slice())?;
+        self.program
+            .set_mat4u(self.sul_pv_matrix as GLint, pv_matrix.slice())?;
+        self.program.set_uniform_3fv(self.sul_tint as GLint, tint)?;
+        self.program
+            .set_uniform_1i(self.sul_base_color as GLint, base_unit.0 as GLint)?;
+        self.program
+            .set_uniform_1i(self.sul_lightmap as GLint, lightmap_unit.0 as GLint)?;
+        self.fog_uniforms.set(&self.program, fog)?;
+
+        let bindings = buffers.activate(gpu_state);
+
+        bindings.draw_elements(gl::TRIANGLES, n_indices, 0)?;
+
+        buffers.deactivate(bindings);
+        unsafe {
+            gl::DisableVertexAttribArray(self.sal_lightmap_coord);
+            gl::DisableVertexAttribArray(self.sal_tex_coord);
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+        gpu_state.set_active_texture(base_unit)?;
+
+        Ok(())
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "
+attribute vec4 a_position;
+attribute vec2 a_texcoord;
+attribute vec2 a_texcoord2;
+
+varying vec2 v_texcoord;
+varying vec2 v_texcoord2;
+varying float v_depth;
+
+uniform mat4 m_matrix;
+uniform mat4 pv_matrix;
+
+void main()
+{
+    gl_Position = pv_matrix * m_matrix * a_position;
+    v_depth = gl_Position.w;
+    v_texcoord = a_texcoord;
+    v_texcoord2 = a_texcoord2;
+}
+"
+}
+
+fn shader_f_src() -> String {
+    format!(
+        "#ifdef GL_ES
+precision mediump float;
+#endif
+varying vec2 v_texcoord;
+varying vec2 v_texcoord2;
+varying float v_depth;
+uniform sampler2D base_color;
+uniform sampler2D lightmap;
+uniform vec3 tint;
+{FOG_GLSL}
+void main()
+{{
+    vec4 base = texture2D(base_color, v_texcoord);
+    vec3 light = texture2D(lightmap, v_texcoord2).rgb;
+    gl_FragColor = vec4(apply_fog(base.rgb * light * tint, v_depth), base.a);
+}}"
+    )
+}
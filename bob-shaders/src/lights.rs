@@ -0,0 +1,330 @@
+use crate::spherical_harmonics::ShIrradiance;
+use gl::types::{GLint, GLuint};
+use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper, Program};
+use gl_thin::linear::XrMatrix4x4f;
+
+/// how many lights [LIGHTS_GLSL] loops over; the rest of a [LightManager]'s are ignored
+pub const MAX_LIGHTS: usize = 8;
+
+/// the texture unit a [ShadowMap] is bound to while lit shaders draw
+pub const SHADOW_MAP_UNIT: u32 = 7;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightKind {
+    /// like the sun; `direction` points toward the light
+    Directional { direction: [f32; 3] },
+    /// fades to nothing at `range` meters
+    Point { position: [f32; 3], range: f32 },
+    /// A point light limited to a cone around `direction` (which points away from the
+    /// light), full strength inside `inner_angle` and fading out by `outer_angle`, both
+    /// half-angles in radians.
+    Spot {
+        position: [f32; 3],
+        direction: [f32; 3],
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+impl Light {
+    pub fn new(kind: LightKind, color: [f32; 3], intensity: f32) -> Self {
+        Self {
+            kind,
+            color,
+            intensity,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LightId(u64);
+
+/// What one light can see, as a depth texture rendered from it, so [LIGHTS_GLSL] leaves the
+/// surfaces hidden from it unlit.  Only a light among the first [MAX_LIGHTS] casts shadows.
+#[derive(Copy, Clone, Debug)]
+pub struct ShadowMap {
+    pub light: LightId,
+    /// from world space to the map's texture coordinates (xy) and depth (z), all 0..1
+    pub matrix: XrMatrix4x4f,
+    /// a GL_TEXTURE_2D of depth, sampled with GL_NEAREST
+    pub texture: GLuint,
+    /// how far behind the map's depth a surface has to be to count as shadowed, against
+    /// surfaces shadowing themselves
+    pub bias: f32,
+}
+
+/// The dynamic lights on top of the sun, and the ambient light.  Lit shaders see the first
+/// [MAX_LIGHTS] of them, in the order they were added.
+#[derive(Default)]
+pub struct LightManager {
+    /// e.g. [ShIrradiance::from_equirect_rgba8] of the environment map when it is loaded
+    pub ambient: ShIrradiance,
+    /// at most one light casts shadows
+    pub shadow: Option<ShadowMap>,
+    lights: Vec<(LightId, Light)>,
+    next_id: u64,
+}
+
+impl LightManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, light: Light) -> LightId {
+        let id = LightId(self.next_id);
+        self.next_id += 1;
+        self.lights.push((id, light));
+        id
+    }
+
+    pub fn remove(&mut self, id: LightId) -> Option<Light> {
+        let index = self.lights.iter().position(|(i, _)| *i == id)?;
+        Some(self.lights.remove(index).1)
+    }
+
+    /// move or dim a light from frame to frame
+    pub fn get_mut(&mut self, id: LightId) -> Option<&mut Light> {
+        self.lights
+            .iter_mut()
+            .find(|(i, _)| *i == id)
+            .map(|(_, light)| light)
+    }
+
+    pub fn clear(&mut self) {
+        self.lights.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Light> {
+        self.lights.iter().map(|(_, light)| light)
+    }
+
+    /// the uniform arrays [LIGHTS_GLSL] declares
+    fn packed(&self) -> PackedLights {
+        let mut packed = PackedLights::default();
+        for (i, light) in self.iter().take(MAX_LIGHTS).enumerate() {
+            let [r, g, b] = light.color;
+            let k = light.intensity;
+            packed.color[i] = [r * k, g * k, b * k, 0.0];
+            match light.kind {
+                LightKind::Directional {
+                    direction: [x, y, z],
+                } => {
+                    packed.position[i] = [x, y, z, 0.0];
+                }
+                LightKind::Point {
+                    position: [x, y, z],
+                    range,
+                } => {
+                    packed.position[i] = [x, y, z, 1.0];
+                    packed.params[i] = [range, -1.0, -1.0, 0.0];
+                }
+                LightKind::Spot {
+                    position: [x, y, z],
+                    direction: [dx, dy, dz],
+                    range,
+                    inner_angle,
+                    outer_angle,
+                } => {
+                    packed.position[i] = [x, y, z, 2.0];
+                    packed.direction[i] = [dx, dy, dz, 0.0];
+                    let outer = outer_angle.max(inner_angle + 1e-3);
+                    packed.params[i] = [range, inner_angle.cos(), outer.cos(), 0.0];
+                }
+            }
+            packed.count += 1;
+        }
+        packed.shadow = self.shadow.and_then(|shadow| {
+            let index = self
+                .lights
+                .iter()
+                .take(MAX_LIGHTS)
+                .position(|(id, _)| *id == shadow.light)?;
+            Some((index, shadow))
+        });
+        packed
+    }
+}
+
+#[derive(Default)]
+struct PackedLights {
+    count: usize,
+    /// xyz, then the kind: 0 directional, 1 point, 2 spot
+    position: [[f32; 4]; MAX_LIGHTS],
+    direction: [[f32; 4]; MAX_LIGHTS],
+    /// premultiplied by the intensity
+    color: [[f32; 4]; MAX_LIGHTS],
+    /// range, cos(inner angle), cos(outer angle)
+    params: [[f32; 4]; MAX_LIGHTS],
+    /// and the index of the light casting it
+    shadow: Option<(usize, ShadowMap)>,
+}
+
+/// Paste into a fragment shader and call `apply_lights(normal, world_position)` for the
+/// diffuse light the [LightManager]'s lights add at a point, and `ambient_irradiance(normal)`
+/// for its ambient light.  `normal` must be normalized.  The [ShadowMap] sampler is
+/// `u_shadow_map`, on [SHADOW_MAP_UNIT].
+pub const LIGHTS_GLSL: &str = "
+// the same as lights::MAX_LIGHTS
+#define MAX_LIGHTS 8
+uniform int u_light_count;
+// xyz, kind: 0 directional, 1 point, 2 spot
+uniform vec4 u_light_position[MAX_LIGHTS];
+uniform vec4 u_light_direction[MAX_LIGHTS];
+uniform vec4 u_light_color[MAX_LIGHTS];
+// range, cos inner, cos outer
+uniform vec4 u_light_params[MAX_LIGHTS];
+// ShIrradiance::coefficients
+uniform vec3 u_sh[9];
+// the light casting the ShadowMap, -1 for none
+uniform int u_shadow_light;
+uniform mat4 u_shadow_matrix;
+uniform float u_shadow_bias;
+uniform sampler2D u_shadow_map;
+
+// 0 where the shadow-casting light can not see world_position, 1 where it can
+float shadow_visibility(vec3 world_position)
+{
+    vec4 p = u_shadow_matrix * vec4(world_position, 1.0);
+    vec3 q = p.xyz / p.w;
+    // sampled either way, so it is not inside non-uniform control flow
+    float nearest = texture2D(u_shadow_map, q.xy).r;
+    bool inside = p.w > 0.0 && all(greaterThanEqual(q.xy, vec2(0.0))) && all(lessThanEqual(q.xy, vec2(1.0)));
+    return inside && q.z - u_shadow_bias > nearest ? 0.0 : 1.0;
+}
+
+vec3 ambient_irradiance(vec3 n)
+{
+    vec3 e = u_sh[0] * 0.282095
+        + u_sh[1] * (0.488603 * n.y)
+        + u_sh[2] * (0.488603 * n.z)
+        + u_sh[3] * (0.488603 * n.x)
+        + u_sh[4] * (1.092548 * n.x * n.y)
+        + u_sh[5] * (1.092548 * n.y * n.z)
+        + u_sh[6] * (0.315392 * (3.0 * n.z * n.z - 1.0))
+        + u_sh[7] * (1.092548 * n.x * n.z)
+        + u_sh[8] * (0.546274 * (n.x * n.x - n.y * n.y));
+    return max(e, vec3(0.0));
+}
+
+vec3 apply_lights(vec3 normal, vec3 world_position)
+{
+    vec3 sum = vec3(0.0);
+    float shadow = 1.0;
+    if (u_shadow_light >= 0) {
+        shadow = shadow_visibility(world_position);
+    }
+    for (int i = 0; i < MAX_LIGHTS; i++) {
+        if (i >= u_light_count) {
+            break;
+        }
+        vec4 p = u_light_position[i];
+        vec3 L;
+        float attenuation = 1.0;
+        if (p.w < 0.5) {
+            L = normalize(p.xyz);
+        } else {
+            vec3 to_light = p.xyz - world_position;
+            float d = length(to_light);
+            L = to_light / max(d, 1e-4);
+            // inverse square, windowed to reach zero at the range
+            float r = d / u_light_params[i].x;
+            float window = clamp(1.0 - r * r * r * r, 0.0, 1.0);
+            attenuation = window * window / (1.0 + d * d);
+            if (p.w > 1.5) {
+                float c = dot(-L, normalize(u_light_direction[i].xyz));
+                attenuation *= smoothstep(u_light_params[i].z, u_light_params[i].y, c);
+            }
+        }
+        if (i == u_shadow_light) {
+            attenuation *= shadow;
+        }
+        sum += u_light_color[i].rgb * attenuation * max(0.0, dot(normal, L));
+    }
+    return sum;
+}
+";
+
+/// the locations of the uniforms declared by [LIGHTS_GLSL]
+pub struct LightUniforms {
+    pub sul_count: u32,
+    pub sul_position: u32,
+    pub sul_direction: u32,
+    pub sul_color: u32,
+    pub sul_params: u32,
+    pub sul_sh: u32,
+    pub sul_shadow_light: u32,
+    pub sul_shadow_matrix: u32,
+    pub sul_shadow_bias: u32,
+    pub sul_shadow_map: u32,
+}
+
+impl LightUniforms {
+    pub fn new(program: &Program) -> Result<Self, GLErrorWrapper> {
+        Ok(Self {
+            sul_count: program.get_uniform_location("u_light_count")?,
+            sul_position: program.get_uniform_location("u_light_position")?,
+            sul_direction: program.get_uniform_location("u_light_direction")?,
+            sul_color: program.get_uniform_location("u_light_color")?,
+            sul_params: program.get_uniform_location("u_light_params")?,
+            sul_sh: program.get_uniform_location("u_sh")?,
+            sul_shadow_light: program.get_uniform_location("u_shadow_light")?,
+            sul_shadow_matrix: program.get_uniform_location("u_shadow_matrix")?,
+            sul_shadow_bias: program.get_uniform_location("u_shadow_bias")?,
+            sul_shadow_map: program.get_uniform_location("u_shadow_map")?,
+        })
+    }
+
+    /// `program` must be in use
+    pub fn set(&self, program: &Program, lights: &LightManager) -> Result<(), GLErrorWrapper> {
+        program.set_uniform_3fv_array(self.sul_sh as GLint, &lights.ambient.coefficients)?;
+        let packed = lights.packed();
+        self.set_shadow(program, packed.shadow)?;
+        program.set_uniform_1i(self.sul_count as GLint, packed.count as GLint)?;
+        if packed.count == 0 {
+            return Ok(());
+        }
+        program.set_uniform_4fv_array(self.sul_position as GLint, &packed.position)?;
+        program.set_uniform_4fv_array(self.sul_direction as GLint, &packed.direction)?;
+        program.set_uniform_4fv_array(self.sul_color as GLint, &packed.color)?;
+        program.set_uniform_4fv_array(self.sul_params as GLint, &packed.params)
+    }
+
+    fn set_shadow(
+        &self,
+        program: &Program,
+        shadow: Option<(usize, ShadowMap)>,
+    ) -> Result<(), GLErrorWrapper> {
+        let Some((index, shadow)) = shadow else {
+            return program.set_uniform_1i(self.sul_shadow_light as GLint, -1);
+        };
+        program.set_uniform_1i(self.sul_shadow_light as GLint, index as GLint)?;
+        program.set_mat4u(self.sul_shadow_matrix as GLint, shadow.matrix.slice())?;
+        program.set_uniform_1f(self.sul_shadow_bias as GLint, shadow.bias)?;
+        program.set_uniform_1i(self.sul_shadow_map as GLint, SHADOW_MAP_UNIT as GLint)?;
+        // a unit of its own, put back the way the caller left it
+        unsafe {
+            let mut active = 0;
+            gl::GetIntegerv(gl::ACTIVE_TEXTURE, &mut active);
+            gl::ActiveTexture(gl::TEXTURE0 + SHADOW_MAP_UNIT);
+            gl::BindTexture(gl::TEXTURE_2D, shadow.texture);
+            gl::ActiveTexture(active as u32);
+        }
+        explode_if_gl_error()
+    }
+}
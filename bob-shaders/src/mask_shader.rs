@@ -0,0 +1,76 @@
+use crate::GeometryBuffer;
+use gl::types::{GLenum, GLint, GLsizei};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program};
+use gl_thin::linear::XrMatrix4x4f;
+
+/// Draws nothing but the shape of the geometry, for filling the stencil (or depth) buffer.
+/// Turn off color writes with gl::ColorMask around [MaskShader::draw]; the fragments are
+/// transparent black.
+pub struct MaskShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sul_matrix: u32,
+}
+
+impl MaskShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src())?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sul_matrix = program.get_uniform_location("u_matrix")?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sul_matrix,
+        })
+    }
+
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        matrix: &XrMatrix4x4f,
+        draw_mode: GLenum,
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+        self.program
+            .set_mat4u(self.sul_matrix as GLint, matrix.slice())?;
+
+        let bindings = buffers.activate(gpu_state);
+
+        bindings.draw_elements(draw_mode, n_indices, 0)?;
+
+        buffers.deactivate(bindings);
+        unsafe {
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+
+        Ok(())
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "
+attribute vec4 a_position;
+
+uniform mat4 u_matrix;
+
+void main()
+{
+    gl_Position = u_matrix * a_position;
+}
+"
+}
+
+fn shader_f_src() -> &'static str {
+    "#ifdef GL_ES
+precision mediump float;
+#endif
+void main()
+{
+    gl_FragColor = vec4(0.0);
+}"
+}
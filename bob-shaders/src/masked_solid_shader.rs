@@ -1,3 +1,4 @@
+use crate::material::{ClipMode, Material, MaterialUniforms};
 use crate::GeometryBuffer;
 use gl::types::{GLenum, GLint, GLsizei};
 use gl_thin::gl_fancy::GPUState;
@@ -16,11 +17,13 @@ pub struct MaskedSolidShader {
     pub sul_tex: u32,
     pub sul_color_fg: u32,
     pub sul_color_bg: u32,
+    pub material_uniforms: MaterialUniforms,
 }
 
 impl MaskedSolidShader {
     pub fn new() -> Result<Self, GLErrorWrapper> {
-        let program = Program::compile(shader_v_src(), shader_f_src())?;
+        let clip_mode = ClipMode::detect();
+        let program = Program::compile(shader_v_src(clip_mode), shader_f_src(clip_mode))?;
 
         let sal_position = program.get_attribute_location("a_position")?;
         let sal_tex_coord = program.get_attribute_location("a_texCoord")?;
@@ -29,6 +32,7 @@ impl MaskedSolidShader {
         let sul_tex = program.get_uniform_location("tex")?;
         let sul_color_fg = program.get_uniform_location("color_fg")?;
         let sul_color_bg = program.get_uniform_location("color_bg")?;
+        let material_uniforms = MaterialUniforms::new(&program, clip_mode)?;
 
         debug!(
             "attribute, uniform locations {} {}  {} {} ",
@@ -43,6 +47,7 @@ impl MaskedSolidShader {
             sul_tex,
             sul_color_fg,
             sul_color_bg,
+            material_uniforms,
         })
     }
 
@@ -53,6 +58,7 @@ impl MaskedSolidShader {
         mask: &TextureWithTarget,
         color_fg: &[f32; 4],
         color_bg: Option<&[f32; 4]>,
+        material: &Material,
         draw_mode: GLenum,
         buffers: &dyn GeometryBuffer<AT, IT>,
         n_indices: GLsizei,
@@ -73,6 +79,7 @@ impl MaskedSolidShader {
             color_bg.unwrap_or(&[0.0; 4]),
             matrix,
         )?;
+        self.material_uniforms.apply(&self.program, material)?;
 
         let bindings = buffers.activate(gpu_state);
 
@@ -86,7 +93,7 @@ impl MaskedSolidShader {
             gl::DisableVertexAttribArray(self.sal_position);
         }
 
-        Ok(())
+        self.material_uniforms.finish(material)
     }
 
     pub fn set_parameters(
@@ -136,8 +143,9 @@ impl MaskedSolidShader {
     }
 }
 
-fn shader_v_src() -> &'static str {
-    "
+fn shader_v_src(clip_mode: ClipMode) -> String {
+    clip_mode.vertex_preamble().to_string()
+        + "
 attribute vec4 a_position;
 attribute vec2 a_texCoord;
 
@@ -149,21 +157,22 @@ void main()
 {
     gl_Position = u_matrix * a_position;
     v_texCoord = a_texCoord;
+    clip_vertex(gl_Position);
 }
 "
 }
 
-fn shader_f_src() -> &'static str {
-    "#ifdef GL_ES
-precision highp float;
-#endif
+fn shader_f_src(clip_mode: ClipMode) -> String {
+    clip_mode.fragment_preamble(&[])
+        + "
 varying vec2 v_texCoord;
 uniform sampler2D tex;
 uniform vec4 color_fg;
 uniform vec4 color_bg;
 void main()
-{{
+{
+    clip_fragment();
     float alpha = texture2D(tex, v_texCoord).r;
     gl_FragColor = mix(color_bg, color_fg, alpha);
-}}"
+}"
 }
@@ -0,0 +1,316 @@
+use gl::types::GLint;
+use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper, Program};
+use gl_thin::linear::{xr_matrix4x4f_invert, XrMatrix4x4f};
+use gl_thin::render_queue::RenderLayer;
+use std::ffi::CStr;
+
+pub const MAX_CLIP_PLANES: usize = 4;
+
+/// Half-spaces that limit where a draw may put fragments, e.g. the bounds of a scrolling panel.
+///
+/// The planes are kept in clip space, because that is what both `gl_ClipDistance` and the
+/// discard fallback compare against.  That ties them to one view: build them again for every eye.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ClipPlanes {
+    planes: [[f32; 4]; MAX_CLIP_PLANES],
+    count: usize,
+}
+
+impl ClipPlanes {
+    pub fn none() -> Self {
+        Self {
+            planes: [[0.0, 0.0, 0.0, 1.0]; MAX_CLIP_PLANES],
+            count: 0,
+        }
+    }
+
+    /// Each `(a, b, c, d)` keeps the world-space points where `ax + by + cz + d >= 0`.
+    /// Planes past [MAX_CLIP_PLANES] are ignored.
+    pub fn world(matrix_pv: &XrMatrix4x4f, planes: &[[f32; 4]]) -> Self {
+        let inverse = xr_matrix4x4f_invert(matrix_pv);
+        let mut rval = Self::none();
+        for plane in planes.iter().take(MAX_CLIP_PLANES) {
+            rval.planes[rval.count] = transform_plane(&inverse, plane);
+            rval.count += 1;
+        }
+        rval
+    }
+
+    /// Keep what projects inside the `2*half_width` × `2*half_height` rectangle centered on the
+    /// origin of `model`'s XY plane, the way a panel with that model matrix would.
+    pub fn rectangle(
+        matrix_pv: &XrMatrix4x4f,
+        model: &XrMatrix4x4f,
+        half_width: f32,
+        half_height: f32,
+    ) -> Self {
+        let inverse = xr_matrix4x4f_invert(&(*matrix_pv * *model));
+        let local = [
+            [1.0, 0.0, 0.0, half_width],
+            [-1.0, 0.0, 0.0, half_width],
+            [0.0, 1.0, 0.0, half_height],
+            [0.0, -1.0, 0.0, half_height],
+        ];
+        let mut rval = Self::none();
+        for (dst, plane) in rval.planes.iter_mut().zip(&local) {
+            *dst = transform_plane(&inverse, plane);
+        }
+        rval.count = local.len();
+        rval
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// in clip space
+    pub fn planes(&self) -> &[[f32; 4]] {
+        &self.planes[..self.count]
+    }
+}
+
+impl Default for ClipPlanes {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// Carry a plane through the transform whose inverse is `inverse`.
+fn transform_plane(inverse: &XrMatrix4x4f, plane: &[f32; 4]) -> [f32; 4] {
+    let m = &inverse.m;
+    let mut rval = [0.0; 4];
+    for (j, dst) in rval.iter_mut().enumerate() {
+        *dst = (0..4).map(|i| plane[i] * m[4 * j + i]).sum();
+    }
+    rval
+}
+
+//
+
+/// How a draw treats the depth buffer.  UI and pointers usually want to show over the
+/// world they float in, without poking through a hand or a prop that is in front of them.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum DepthMode {
+    /// tested and written like any other geometry
+    #[default]
+    World,
+    /// Pulled toward the viewer by `glPolygonOffset`, so it wins against a surface it lies
+    /// on (a cursor on a panel) but still hides behind anything nearer.  `slope` scales with
+    /// how steeply the polygon is seen, `units` are steps of the depth buffer.
+    Soft { slope: f32, units: f32 },
+    /// no depth test or write; queue it in [RenderLayer::Overlay] so it is drawn last
+    AlwaysOnTop,
+}
+
+impl DepthMode {
+    /// enough to settle a cursor lying on a panel
+    pub const SOFT: DepthMode = DepthMode::Soft {
+        slope: 1.0,
+        units: 4.0,
+    };
+
+    /// where a drawable with this mode belongs in a [gl_thin::render_queue::RenderQueue]
+    pub fn render_layer(self) -> RenderLayer {
+        match self {
+            DepthMode::World | DepthMode::Soft { .. } => RenderLayer::Transparent,
+            DepthMode::AlwaysOnTop => RenderLayer::Overlay,
+        }
+    }
+}
+
+/// Per-draw state for the shaders that take one (see [MaterialUniforms]),
+/// on top of each shader's own parameters.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Material {
+    pub clip_planes: ClipPlanes,
+    pub depth: DepthMode,
+}
+
+impl Material {
+    pub fn clipped(clip_planes: ClipPlanes) -> Self {
+        Self {
+            clip_planes,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_depth(mut self, depth: DepthMode) -> Self {
+        self.depth = depth;
+        self
+    }
+}
+
+/// How a shader that supports [Material] implements its clip planes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ClipMode {
+    /// compiled as GLSL ES 3.00 and writes `gl_ClipDistance`
+    Hardware,
+    /// GLSL ES 1.00 with a varying per plane and a `discard` in the fragment shader
+    Discard,
+}
+
+impl ClipMode {
+    /// [ClipMode::Hardware] if the current context is GLES 3 with GL_EXT_clip_cull_distance
+    pub fn detect() -> Self {
+        let version = gl_string(gl::VERSION);
+        let extensions = gl_string(gl::EXTENSIONS);
+        let gles3 = version.starts_with("OpenGL ES 3");
+        if gles3
+            && extensions
+                .split_whitespace()
+                .any(|e| e == "GL_EXT_clip_cull_distance")
+        {
+            ClipMode::Hardware
+        } else {
+            ClipMode::Discard
+        }
+    }
+
+    /// Put this before the rest of the vertex shader, which calls `clip_vertex(gl_Position)`
+    /// after computing `gl_Position`.
+    pub fn vertex_preamble(self) -> &'static str {
+        match self {
+            ClipMode::Hardware => {
+                "#version 300 es
+#extension GL_EXT_clip_cull_distance : require
+#define attribute in
+#define varying out
+uniform vec4 u_clip_planes[4];
+void clip_vertex(vec4 position)
+{
+    gl_ClipDistance[0] = dot(u_clip_planes[0], position);
+    gl_ClipDistance[1] = dot(u_clip_planes[1], position);
+    gl_ClipDistance[2] = dot(u_clip_planes[2], position);
+    gl_ClipDistance[3] = dot(u_clip_planes[3], position);
+}
+"
+            }
+            ClipMode::Discard => {
+                "
+uniform vec4 u_clip_planes[4];
+varying vec4 v_clip_distance;
+void clip_vertex(vec4 position)
+{
+    v_clip_distance = vec4(dot(u_clip_planes[0], position), dot(u_clip_planes[1], position),
+                           dot(u_clip_planes[2], position), dot(u_clip_planes[3], position));
+}
+"
+            }
+        }
+    }
+
+    /// Put this before the rest of the fragment shader, which calls `clip_fragment()` first thing.
+    /// `es2_extensions` are only enabled for [ClipMode::Discard]; GLSL ES 3.00 has the usual
+    /// suspects (e.g. GL_OES_standard_derivatives) built in.
+    pub fn fragment_preamble(self, es2_extensions: &[&str]) -> String {
+        match self {
+            ClipMode::Hardware => "#version 300 es
+precision highp float;
+#define varying in
+#define texture2D texture
+out vec4 o_frag_color;
+#define gl_FragColor o_frag_color
+void clip_fragment()
+{
+}
+"
+            .to_string(),
+            ClipMode::Discard => {
+                let mut rval = String::new();
+                for extension in es2_extensions {
+                    rval.push_str(&format!("#extension {} : enable\n", extension));
+                }
+                rval.push_str(
+                    "#ifdef GL_ES
+precision highp float;
+#endif
+varying vec4 v_clip_distance;
+void clip_fragment()
+{
+    if (any(lessThan(v_clip_distance, vec4(0.0)))) {
+        discard;
+    }
+}
+",
+                );
+                rval
+            }
+        }
+    }
+}
+
+fn gl_string(name: gl::types::GLenum) -> String {
+    let ptr = unsafe { gl::GetString(name) };
+    if ptr.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(ptr as *const _) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The uniforms declared by [ClipMode::vertex_preamble]
+pub struct MaterialUniforms {
+    pub clip_mode: ClipMode,
+    pub sul_clip_planes: [u32; MAX_CLIP_PLANES],
+}
+
+impl MaterialUniforms {
+    pub fn new(program: &Program, clip_mode: ClipMode) -> Result<Self, GLErrorWrapper> {
+        let mut sul_clip_planes = [0; MAX_CLIP_PLANES];
+        for (i, sul) in sul_clip_planes.iter_mut().enumerate() {
+            *sul = program.get_uniform_location(&format!("u_clip_planes[{}]", i))?;
+        }
+        Ok(Self {
+            clip_mode,
+            sul_clip_planes,
+        })
+    }
+
+    /// Set the uniforms (`program` must be in use), enable the clip distances and set up the
+    /// [DepthMode].  Call [MaterialUniforms::finish] after drawing.
+    pub fn apply(&self, program: &Program, material: &Material) -> Result<(), GLErrorWrapper> {
+        let clip = &material.clip_planes;
+        for (sul, plane) in self.sul_clip_planes.iter().zip(&clip.planes) {
+            program.set_uniform_4fv(*sul as GLint, plane)?;
+        }
+        if self.clip_mode == ClipMode::Hardware {
+            for i in 0..clip.count {
+                unsafe { gl::Enable(gl::CLIP_DISTANCE0 + i as u32) };
+            }
+            explode_if_gl_error()?;
+        }
+        match material.depth {
+            DepthMode::World => {}
+            DepthMode::Soft { slope, units } => unsafe {
+                gl::Enable(gl::POLYGON_OFFSET_FILL);
+                gl::PolygonOffset(-slope, -units);
+            },
+            DepthMode::AlwaysOnTop => unsafe {
+                gl::Disable(gl::DEPTH_TEST);
+                gl::DepthMask(gl::FALSE);
+            },
+        }
+        explode_if_gl_error()
+    }
+
+    /// Undo the global state [MaterialUniforms::apply] changed.  The depth test is left
+    /// enabled, the way the scene draws.
+    pub fn finish(&self, material: &Material) -> Result<(), GLErrorWrapper> {
+        if self.clip_mode == ClipMode::Hardware {
+            for i in 0..MAX_CLIP_PLANES {
+                unsafe { gl::Disable(gl::CLIP_DISTANCE0 + i as u32) };
+            }
+            explode_if_gl_error()?;
+        }
+        match material.depth {
+            DepthMode::World => {}
+            DepthMode::Soft { .. } => unsafe { gl::Disable(gl::POLYGON_OFFSET_FILL) },
+            DepthMode::AlwaysOnTop => unsafe {
+                gl::Enable(gl::DEPTH_TEST);
+                gl::DepthMask(gl::TRUE);
+            },
+        }
+        explode_if_gl_error()
+    }
+}
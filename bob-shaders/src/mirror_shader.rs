@@ -0,0 +1,141 @@
+use crate::GeometryBuffer;
+use gl::types::{GLenum, GLint, GLsizei};
+use gl_thin::gl_fancy::{ActiveTextureUnit, GPUState};
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program, Texture};
+use gl_thin::linear::{XrMatrix4x4f, XrVector3f};
+
+/// Composites a reflection (rendered from the mirrored viewpoint into a texture) onto a flat surface.
+/// The texture is looked up by screen position like
+/// [ScreenSpaceTextureShader](crate::screen_space_texture_shader::ScreenSpaceTextureShader),
+/// and faded with Schlick's Fresnel approximation so the surface is a faint reflection when you
+/// look down at it and a strong one at grazing angles.
+pub struct MirrorShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sul_matrix_pv: u32,
+    pub sul_model: u32,
+    pub sul_eye_position: u32,
+    pub sul_normal: u32,
+    pub sul_reflectance: u32,
+    pub sul_tint: u32,
+    pub sul_texture: u32,
+}
+
+impl MirrorShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src())?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sul_matrix_pv = program.get_uniform_location("u_matrix_pv")?;
+        let sul_model = program.get_uniform_location("u_model")?;
+        let sul_eye_position = program.get_uniform_location("eye_position")?;
+        let sul_normal = program.get_uniform_location("normal")?;
+        let sul_reflectance = program.get_uniform_location("reflectance")?;
+        let sul_tint = program.get_uniform_location("tint")?;
+        let sul_texture = program.get_uniform_location("tex")?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sul_matrix_pv,
+            sul_model,
+            sul_eye_position,
+            sul_normal,
+            sul_reflectance,
+            sul_tint,
+            sul_texture,
+        })
+    }
+
+    /// `reflectance` is how much is reflected when looking straight at the surface (0.04 for most
+    /// dielectrics, higher for polished floors).  `normal` is in world space.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        model: &XrMatrix4x4f,
+        eye_position: &XrVector3f,
+        normal: &XrVector3f,
+        reflectance: f32,
+        tint: &[f32; 3],
+        texture: &Texture,
+        draw_mode: GLenum,
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+
+        let texture_unit = ActiveTextureUnit(0);
+        gpu_state.set_active_texture(texture_unit)?;
+        texture.bind(gl::TEXTURE_2D)?;
+
+        self.program
+            .set_mat4u(self.sul_matrix_pv as GLint, matrix_pv.slice())?;
+        self.program
+            .set_mat4u(self.sul_model as GLint, model.slice())?;
+        self.program.set_uniform_3fv(
+            self.sul_eye_position as GLint,
+            &[eye_position.x, eye_position.y, eye_position.z],
+        )?;
+        self.program
+            .set_uniform_3fv(self.sul_normal as GLint, &[normal.x, normal.y, normal.z])?;
+        self.program
+            .set_uniform_1f(self.sul_reflectance as GLint, reflectance)?;
+        self.program.set_uniform_3fv(self.sul_tint as GLint, tint)?;
+        self.program
+            .set_uniform_1i(self.sul_texture as GLint, texture_unit.0 as GLint)?;
+
+        let bindings = buffers.activate(gpu_state);
+
+        bindings.draw_elements(draw_mode, n_indices, 0)?;
+
+        buffers.deactivate(bindings);
+        unsafe {
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+
+        Ok(())
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "
+attribute vec4 a_position;
+
+varying vec4 v_clip;
+varying vec3 v_world;
+
+uniform mat4 u_matrix_pv;
+uniform mat4 u_model;
+
+void main()
+{
+    vec4 world = u_model * a_position;
+    v_world = world.xyz;
+    gl_Position = u_matrix_pv * world;
+    v_clip = gl_Position;
+}
+"
+}
+
+fn shader_f_src() -> &'static str {
+    "#ifdef GL_ES
+precision highp float;
+#endif
+varying vec4 v_clip;
+varying vec3 v_world;
+uniform sampler2D tex;
+uniform vec3 eye_position;
+uniform vec3 normal;
+uniform float reflectance;
+uniform vec3 tint;
+void main()
+{
+    vec2 uv = v_clip.xy / v_clip.w * 0.5 + 0.5;
+    vec3 to_eye = normalize(eye_position - v_world);
+    float cos_theta = clamp(dot(to_eye, normal), 0.0, 1.0);
+    float fresnel = reflectance + (1.0 - reflectance) * pow(1.0 - cos_theta, 5.0);
+    gl_FragColor = vec4(texture2D(tex, uv).rgb * tint, fresnel);
+}"
+}
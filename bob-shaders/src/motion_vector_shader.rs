@@ -0,0 +1,95 @@
+use crate::GeometryBuffer;
+use gl::types::{GLint, GLsizei};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program};
+use gl_thin::linear::XrMatrix4x4f;
+
+/// Writes how far each pixel of a mesh moved since the previous frame: the difference
+/// between its normalized device coordinates under `matrix` and under `previous_matrix`
+/// goes into RGB.  Meant for a half-float target; values are usually well under 1.
+pub struct MotionVectorShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sul_matrix: u32,
+    pub sul_previous_matrix: u32,
+}
+
+impl MotionVectorShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src())?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sul_matrix = program.get_uniform_location("matrix")?;
+        let sul_previous_matrix = program.get_uniform_location("previous_matrix")?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sul_matrix,
+            sul_previous_matrix,
+        })
+    }
+
+    /// `matrix` and `previous_matrix` are this frame's and the previous frame's
+    /// projection * view * model
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        matrix: &XrMatrix4x4f,
+        previous_matrix: &XrMatrix4x4f,
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+
+        self.program
+            .set_mat4u(self.sul_matrix as GLint, matrix.slice())?;
+        self.program
+            .set_mat4u(self.sul_previous_matrix as GLint, previous_matrix.slice())?;
+
+        let bindings = buffers.activate(gpu_state);
+
+        bindings.draw_elements(gl::TRIANGLES, n_indices, 0)?;
+
+        buffers.deactivate(bindings);
+        unsafe {
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+
+        Ok(())
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "
+attribute vec4 a_position;
+uniform mat4 matrix;
+uniform mat4 previous_matrix;
+
+varying vec4 v_current;
+varying vec4 v_previous;
+
+void main()
+{
+    v_current = matrix * a_position;
+    v_previous = previous_matrix * a_position;
+    gl_Position = v_current;
+}
+"
+}
+
+fn shader_f_src() -> &'static str {
+    "#ifdef GL_ES
+precision highp float;
+#endif
+varying vec4 v_current;
+varying vec4 v_previous;
+
+void main()
+{
+    // divide per pixel; interpolating after the divide would be wrong under perspective
+    vec3 current = v_current.xyz / v_current.w;
+    vec3 previous = v_previous.xyz / v_previous.w;
+    gl_FragColor = vec4(current - previous, 1.0);
+}"
+}
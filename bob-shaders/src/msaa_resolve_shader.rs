@@ -0,0 +1,93 @@
+use crate::GeometryBuffer;
+use gl::types::{GLint, GLsizei};
+use gl_thin::gl_fancy::{ActiveTextureUnit, GPUState};
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program, Texture};
+
+/// Averages every sample of a multisampled texture into one pixel, for
+/// gl_thin::msaa::ResolveMethod::Shader.  texelFetch decodes an sRGB texture, so the average
+/// is always of linear values; the destination encodes again if it is sRGB.
+/// Needs GLSL ES 3.10.  Draw a quad covering clip space from -1 to 1.
+pub struct MsaaResolveShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sul_color: u32,
+    pub sul_samples: u32,
+}
+
+impl MsaaResolveShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src())?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sul_color = program.get_uniform_location("color")?;
+        let sul_samples = program.get_uniform_location("samples")?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sul_color,
+            sul_samples,
+        })
+    }
+
+    /// `color` is the TEXTURE_2D_MULTISAMPLE with `samples` samples per pixel
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        color: &Texture,
+        samples: GLint,
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+
+        let unit = ActiveTextureUnit(0);
+        gpu_state.set_active_texture(unit)?;
+        color.bind(gl::TEXTURE_2D_MULTISAMPLE)?;
+        self.program
+            .set_uniform_1i(self.sul_color as GLint, unit.0 as GLint)?;
+        self.program
+            .set_uniform_1i(self.sul_samples as GLint, samples)?;
+
+        let bindings = buffers.activate(gpu_state);
+
+        bindings.draw_elements(gl::TRIANGLE_STRIP, n_indices, 0)?;
+
+        buffers.deactivate(bindings);
+        unsafe {
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+
+        Ok(())
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "#version 310 es
+in vec4 a_position;
+
+void main()
+{
+    gl_Position = vec4(a_position.xy, 0.0, 1.0);
+}
+"
+}
+
+fn shader_f_src() -> &'static str {
+    "#version 310 es
+precision highp float;
+precision highp sampler2DMS;
+uniform sampler2DMS color;
+uniform int samples;
+out vec4 o_frag_color;
+
+void main()
+{
+    ivec2 texel = ivec2(gl_FragCoord.xy);
+    vec4 sum = vec4(0.0);
+    for (int i = 0; i < samples; i++) {
+        sum += texelFetch(color, texel, i);
+    }
+    o_frag_color = sum / float(samples);
+}"
+}
@@ -0,0 +1,129 @@
+use crate::GeometryBuffer;
+use gl::types::{GLint, GLsizei};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program};
+use gl_thin::linear::XrMatrix4x4f;
+
+/// The color and thickness of an [OutlineShader] rim
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OutlineStyle {
+    pub color: [f32; 4],
+    /// in meters, measured out from the surface
+    pub width: f32,
+}
+
+impl Default for OutlineStyle {
+    fn default() -> Self {
+        Self {
+            color: [1.0, 0.85, 0.2, 1.0],
+            width: 0.006,
+        }
+    }
+}
+
+/// Highlights a mesh with a rim: draws it again puffed out along its normals in a solid
+/// color with the front faces culled, so only the shell's far side shows around the edges
+/// of the mesh drawn before it (the "inverted hull").  Needs smooth normals, or the shell
+/// cracks at the creases, and counter-clockwise front faces.
+pub struct OutlineShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sal_normal: u32,
+    pub sul_m_matrix: u32,
+    pub sul_pv_matrix: u32,
+    pub sul_width: u32,
+    pub sul_color: u32,
+}
+
+impl OutlineShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src())?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sal_normal = program.get_attribute_location("a_normal")?;
+        let sul_m_matrix = program.get_uniform_location("m_matrix")?;
+        let sul_pv_matrix = program.get_uniform_location("pv_matrix")?;
+        let sul_width = program.get_uniform_location("u_width")?;
+        let sul_color = program.get_uniform_location("u_color")?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sal_normal,
+            sul_m_matrix,
+            sul_pv_matrix,
+            sul_width,
+            sul_color,
+        })
+    }
+
+    /// Draw after the mesh itself, with the same `m_matrix`.  Leaves face culling off.
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        m_matrix: &XrMatrix4x4f,
+        pv_matrix: &XrMatrix4x4f,
+        style: &OutlineStyle,
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+        self.program
+            .set_mat4u(self.sul_m_matrix as GLint, m_matrix.slice())?;
+        self.program
+            .set_mat4u(self.sul_pv_matrix as GLint, pv_matrix.slice())?;
+        self.program
+            .set_uniform_1f(self.sul_width as GLint, style.width)?;
+        self.program
+            .set_uniform_4fv(self.sul_color as GLint, &style.color)?;
+
+        unsafe {
+            gl::Enable(gl::CULL_FACE);
+            gl::CullFace(gl::FRONT);
+        }
+
+        let bindings = buffers.activate(gpu_state);
+        let drawn = bindings.draw_elements(gl::TRIANGLES, n_indices, 0);
+        buffers.deactivate(bindings);
+
+        unsafe {
+            gl::CullFace(gl::BACK);
+            gl::Disable(gl::CULL_FACE);
+            gl::DisableVertexAttribArray(self.sal_normal);
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+
+        drawn
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "
+attribute vec3 a_position;
+attribute vec3 a_normal;
+
+uniform mat4 m_matrix;
+uniform mat4 pv_matrix;
+uniform float u_width;
+
+void main()
+{
+    vec4 world = m_matrix * vec4(a_position, 1.0);
+    // assumes m_matrix scales uniformly
+    vec3 normal = normalize((m_matrix * vec4(a_normal, 0.0)).xyz);
+    gl_Position = pv_matrix * vec4(world.xyz + u_width * normal, 1.0);
+}
+"
+}
+
+fn shader_f_src() -> &'static str {
+    "#ifdef GL_ES
+precision mediump float;
+#endif
+uniform vec4 u_color;
+
+void main()
+{
+    gl_FragColor = u_color;
+}"
+}
@@ -0,0 +1,103 @@
+use crate::GeometryBuffer;
+use gl::types::{GLenum, GLint, GLsizei};
+use gl_thin::gl_fancy::{ActiveTextureUnit, GPUState};
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program, Texture};
+use gl_thin::linear::XrMatrix4x4f;
+
+/// Paints geometry with a texture that is looked up by the fragment's position on screen
+/// instead of by texture coordinates.
+/// This is how you show something that was rendered from a matching viewpoint into an
+/// offscreen target, like the view through a portal or a mirror.
+pub struct ScreenSpaceTextureShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sul_matrix: u32,
+    pub sul_texture: u32,
+}
+
+impl ScreenSpaceTextureShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src())?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sul_matrix = program.get_uniform_location("u_matrix")?;
+        let sul_texture = program.get_uniform_location("tex")?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sul_matrix,
+            sul_texture,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        matrix: &XrMatrix4x4f,
+        texture: &Texture,
+        texture_unit: ActiveTextureUnit,
+        draw_mode: GLenum,
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+
+        gpu_state.set_active_texture(texture_unit)?;
+        texture.bind(gl::TEXTURE_2D)?;
+        self.set_parameters(matrix, texture_unit)?;
+
+        let bindings = buffers.activate(gpu_state);
+
+        bindings.draw_elements(draw_mode, n_indices, 0)?;
+
+        buffers.deactivate(bindings);
+        unsafe {
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+
+        Ok(())
+    }
+
+    pub fn set_parameters(
+        &self,
+        matrix: &XrMatrix4x4f,
+        texture_unit: ActiveTextureUnit,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program
+            .set_mat4u(self.sul_matrix as GLint, matrix.slice())?;
+        self.program
+            .set_uniform_1i(self.sul_texture as GLint, texture_unit.0 as GLint)
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "
+attribute vec4 a_position;
+
+varying vec4 v_clip;
+
+uniform mat4 u_matrix;
+
+void main()
+{
+    gl_Position = u_matrix * a_position;
+    v_clip = gl_Position;
+}
+"
+}
+
+fn shader_f_src() -> &'static str {
+    "#ifdef GL_ES
+precision highp float;
+#endif
+varying vec4 v_clip;
+uniform sampler2D tex;
+void main()
+{
+    // divide per-fragment, interpolating the quotient would not be perspective-correct
+    vec2 uv = v_clip.xy / v_clip.w * 0.5 + 0.5;
+    gl_FragColor = texture2D(tex, uv);
+}"
+}
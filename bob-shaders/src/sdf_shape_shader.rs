@@ -0,0 +1,290 @@
+use crate::material::{ClipMode, Material, MaterialUniforms};
+use crate::GeometryBuffer;
+use gl::types::{GLenum, GLint, GLsizei};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program};
+use gl_thin::linear::XrMatrix4x4f;
+
+/// The outline of an [SdfShapeShader] shape, centered on the origin of its local coordinates.
+#[derive(Copy, Clone, Debug)]
+pub enum SdfShape {
+    RoundedRect {
+        half_width: f32,
+        half_height: f32,
+        corner_radius: f32,
+    },
+    Circle {
+        radius: f32,
+    },
+    /// a circle of `radius` stroked `thickness` wide
+    Ring {
+        radius: f32,
+        thickness: f32,
+    },
+}
+
+impl SdfShape {
+    fn kind(&self) -> GLint {
+        match self {
+            SdfShape::RoundedRect { .. } => 0,
+            SdfShape::Circle { .. } => 1,
+            SdfShape::Ring { .. } => 2,
+        }
+    }
+
+    fn parameters(&self) -> [f32; 4] {
+        match *self {
+            SdfShape::RoundedRect {
+                half_width,
+                half_height,
+                corner_radius,
+            } => {
+                let corner_radius = corner_radius.min(half_width).min(half_height);
+                [half_width, half_height, corner_radius, 0.0]
+            }
+            SdfShape::Circle { radius } => [radius, 0.0, 0.0, 0.0],
+            SdfShape::Ring { radius, thickness } => [radius, thickness, 0.0, 0.0],
+        }
+    }
+
+    /// half the width and height of the shape's bounding box
+    pub fn half_extent(&self) -> [f32; 2] {
+        match *self {
+            SdfShape::RoundedRect {
+                half_width,
+                half_height,
+                ..
+            } => [half_width, half_height],
+            SdfShape::Circle { radius } => [radius, radius],
+            SdfShape::Ring { radius, thickness } => {
+                let r = radius + thickness * 0.5;
+                [r, r]
+            }
+        }
+    }
+}
+
+/// Colors are non-premultiplied RGBA.  Distances are in the shape's local units.
+#[derive(Copy, Clone, Debug)]
+pub struct SdfShapeStyle {
+    pub fill: [f32; 4],
+    pub border_color: [f32; 4],
+    /// measured inward from the outline
+    pub border_width: f32,
+    pub shadow_color: [f32; 4],
+    pub shadow_offset: [f32; 2],
+    /// how far the shadow's edge fades out on either side of the offset outline
+    pub shadow_softness: f32,
+}
+
+impl SdfShapeStyle {
+    pub fn solid(fill: [f32; 4]) -> Self {
+        Self {
+            fill,
+            border_color: fill,
+            border_width: 0.0,
+            shadow_color: [0.0; 4],
+            shadow_offset: [0.0; 2],
+            shadow_softness: 0.0,
+        }
+    }
+
+    /// how far past the shape's own outline the shadow can reach
+    pub fn shadow_margin(&self) -> f32 {
+        if self.shadow_color[3] <= 0.0 {
+            return 0.0;
+        }
+        self.shadow_offset[0].abs().max(self.shadow_offset[1].abs()) + self.shadow_softness
+    }
+}
+
+/// Draws rounded rectangles, circles and rings from a signed distance function evaluated per
+/// fragment, with an optional border and soft drop shadow.
+/// The edges are antialiased using screen-space derivatives, so they stay crisp at any distance.
+///
+/// The geometry is a quad with corners at (±1,±1) in `a_position.xy`; the shader stretches it to
+/// cover the shape and its shadow, then `u_matrix` carries the shape's local coordinates to clip space.
+pub struct SdfShapeShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sul_matrix: u32,
+    pub sul_extent: u32,
+    pub sul_kind: u32,
+    pub sul_shape: u32,
+    pub sul_fill: u32,
+    pub sul_border_color: u32,
+    pub sul_border_width: u32,
+    pub sul_shadow_color: u32,
+    pub sul_shadow: u32,
+    pub material_uniforms: MaterialUniforms,
+}
+
+impl SdfShapeShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let clip_mode = ClipMode::detect();
+        let program = Program::compile(shader_v_src(clip_mode), shader_f_src(clip_mode))?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sul_matrix = program.get_uniform_location("u_matrix")?;
+        let sul_extent = program.get_uniform_location("u_extent")?;
+        let sul_kind = program.get_uniform_location("u_kind")?;
+        let sul_shape = program.get_uniform_location("u_shape")?;
+        let sul_fill = program.get_uniform_location("u_fill")?;
+        let sul_border_color = program.get_uniform_location("u_border_color")?;
+        let sul_border_width = program.get_uniform_location("u_border_width")?;
+        let sul_shadow_color = program.get_uniform_location("u_shadow_color")?;
+        let sul_shadow = program.get_uniform_location("u_shadow")?;
+        let material_uniforms = MaterialUniforms::new(&program, clip_mode)?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sul_matrix,
+            sul_extent,
+            sul_kind,
+            sul_shape,
+            sul_fill,
+            sul_border_color,
+            sul_border_width,
+            sul_shadow_color,
+            sul_shadow,
+            material_uniforms,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        matrix: &XrMatrix4x4f,
+        shape: &SdfShape,
+        style: &SdfShapeStyle,
+        material: &Material,
+        draw_mode: GLenum,
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+
+        self.set_parameters(matrix, shape, style)?;
+        self.material_uniforms.apply(&self.program, material)?;
+
+        let bindings = buffers.activate(gpu_state);
+
+        bindings.draw_elements(draw_mode, n_indices, 0)?;
+
+        buffers.deactivate(bindings);
+        unsafe {
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+
+        self.material_uniforms.finish(material)
+    }
+
+    pub fn set_parameters(
+        &self,
+        matrix: &XrMatrix4x4f,
+        shape: &SdfShape,
+        style: &SdfShapeStyle,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program
+            .set_mat4u(self.sul_matrix as GLint, matrix.slice())?;
+
+        // a little extra so the antialiased edge is not cut off
+        let [hw, hh] = shape.half_extent();
+        let margin = style.shadow_margin() + 0.02 * hw.max(hh);
+        self.program
+            .set_uniform_2f(self.sul_extent as GLint, hw + margin, hh + margin)?;
+
+        self.program
+            .set_uniform_1i(self.sul_kind as GLint, shape.kind())?;
+        self.program
+            .set_uniform_4fv(self.sul_shape as GLint, &shape.parameters())?;
+        self.program
+            .set_uniform_4fv(self.sul_fill as GLint, &style.fill)?;
+        self.program
+            .set_uniform_4fv(self.sul_border_color as GLint, &style.border_color)?;
+        self.program
+            .set_uniform_1f(self.sul_border_width as GLint, style.border_width)?;
+        self.program
+            .set_uniform_4fv(self.sul_shadow_color as GLint, &style.shadow_color)?;
+        self.program.set_uniform_3fv(
+            self.sul_shadow as GLint,
+            &[
+                style.shadow_offset[0],
+                style.shadow_offset[1],
+                style.shadow_softness,
+            ],
+        )
+    }
+}
+
+fn shader_v_src(clip_mode: ClipMode) -> String {
+    clip_mode.vertex_preamble().to_string()
+        + "
+attribute vec4 a_position;
+
+uniform mat4 u_matrix;
+uniform vec2 u_extent;
+
+varying vec2 v_local;
+
+void main()
+{
+    v_local = a_position.xy * u_extent;
+    gl_Position = u_matrix * vec4(v_local, 0.0, 1.0);
+    clip_vertex(gl_Position);
+}
+"
+}
+
+fn shader_f_src(clip_mode: ClipMode) -> String {
+    clip_mode.fragment_preamble(&["GL_OES_standard_derivatives"])
+        + "
+varying vec2 v_local;
+
+uniform int u_kind;
+uniform vec4 u_shape;
+uniform vec4 u_fill;
+uniform vec4 u_border_color;
+uniform float u_border_width;
+uniform vec4 u_shadow_color;
+// offset x, offset y, softness
+uniform vec3 u_shadow;
+
+// negative inside, positive outside
+float shape_distance(vec2 p)
+{
+    if (u_kind == 0) {
+        vec2 q = abs(p) - u_shape.xy + u_shape.z;
+        return length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - u_shape.z;
+    } else if (u_kind == 1) {
+        return length(p) - u_shape.x;
+    } else {
+        return abs(length(p) - u_shape.x) - 0.5 * u_shape.y;
+    }
+}
+
+void main()
+{
+    clip_fragment();
+    float d = shape_distance(v_local);
+    // the size of one pixel in local units
+    float aa = max(fwidth(d), 1e-6);
+
+    float outside = clamp(0.5 - d / aa, 0.0, 1.0);
+    float inside_border = clamp(0.5 - (d + u_border_width) / aa, 0.0, 1.0);
+    vec4 body = mix(u_border_color, u_fill, inside_border);
+    body.a *= outside;
+
+    float softness = max(u_shadow.z, aa);
+    float shadow_distance = shape_distance(v_local - u_shadow.xy);
+    float shadow_alpha = u_shadow_color.a * (1.0 - smoothstep(-softness, softness, shadow_distance));
+
+    // body over shadow
+    float alpha = body.a + shadow_alpha * (1.0 - body.a);
+    vec3 rgb = body.rgb * body.a + u_shadow_color.rgb * shadow_alpha * (1.0 - body.a);
+    gl_FragColor = vec4(rgb / max(alpha, 1e-6), alpha);
+}
+"
+}
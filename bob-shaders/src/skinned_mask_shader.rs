@@ -0,0 +1,119 @@
+use crate::GeometryBuffer;
+use gl::types::{GLint, GLsizei, GLuint};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program};
+use gl_thin::linear::XrMatrix4x4f;
+
+/// as many joints as an XR_EXT_hand_tracking hand has
+pub const MAX_SKIN_JOINTS: usize = 26;
+
+/// floats per vertex: position, then four joint indices, then their four weights
+pub const SKINNED_VERTEX_STRIDE: GLsizei = 3 + 4 + 4;
+
+/// Like [crate::mask_shader::MaskShader] for a skinned mesh: each vertex follows up to four
+/// joints.  Turn off color writes with gl::ColorMask around [SkinnedMaskShader::draw] to lay
+/// down only depth, e.g. so real hands seen through passthrough hide what is behind them.
+pub struct SkinnedMaskShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sal_joints: u32,
+    pub sal_weights: u32,
+    pub sul_matrix: u32,
+    pub sul_joint_matrices: u32,
+}
+
+impl SkinnedMaskShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src())?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sal_joints = program.get_attribute_location("a_joints")?;
+        let sal_weights = program.get_attribute_location("a_weights")?;
+        let sul_matrix = program.get_uniform_location("u_matrix")?;
+        let sul_joint_matrices = program.get_uniform_location("u_joint_matrices")?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sal_joints,
+            sal_weights,
+            sul_matrix,
+            sul_joint_matrices,
+        })
+    }
+
+    /// `(location, width, offset)` for a [gl_thin::gl_fancy::VertexBufferBundle] of
+    /// [SKINNED_VERTEX_STRIDE] floats per vertex
+    pub fn attributes(&self) -> [(GLuint, GLint, GLsizei); 3] {
+        [
+            (self.sal_position, 3, 0),
+            (self.sal_joints, 4, 3),
+            (self.sal_weights, 4, 7),
+        ]
+    }
+
+    /// `joint_matrices` take each joint from where the mesh was modeled to where it is now;
+    /// at most [MAX_SKIN_JOINTS] of them are used
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        matrix: &XrMatrix4x4f,
+        joint_matrices: &[XrMatrix4x4f],
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+        self.program
+            .set_mat4u(self.sul_matrix as GLint, matrix.slice())?;
+        let joints: Vec<[f32; 16]> = joint_matrices
+            .iter()
+            .take(MAX_SKIN_JOINTS)
+            .map(|m| *m.slice())
+            .collect();
+        self.program
+            .set_mat4u_array(self.sul_joint_matrices as GLint, &joints)?;
+
+        let bindings = buffers.activate(gpu_state);
+
+        bindings.draw_elements(gl::TRIANGLES, n_indices, 0)?;
+
+        buffers.deactivate(bindings);
+        unsafe {
+            gl::DisableVertexAttribArray(self.sal_weights);
+            gl::DisableVertexAttribArray(self.sal_joints);
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+
+        Ok(())
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "
+attribute vec3 a_position;
+attribute vec4 a_joints;
+attribute vec4 a_weights;
+
+uniform mat4 u_matrix;
+uniform mat4 u_joint_matrices[26];
+
+void main()
+{
+    mat4 skin = a_weights.x * u_joint_matrices[int(a_joints.x)]
+        + a_weights.y * u_joint_matrices[int(a_joints.y)]
+        + a_weights.z * u_joint_matrices[int(a_joints.z)]
+        + a_weights.w * u_joint_matrices[int(a_joints.w)];
+    gl_Position = u_matrix * skin * vec4(a_position, 1.0);
+}
+"
+}
+
+fn shader_f_src() -> &'static str {
+    "#ifdef GL_ES
+precision mediump float;
+#endif
+void main()
+{
+    gl_FragColor = vec4(0.0);
+}"
+}
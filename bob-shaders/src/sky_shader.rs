@@ -0,0 +1,193 @@
+use crate::GeometryBuffer;
+use gl::types::{GLint, GLsizei};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program};
+use gl_thin::linear::XrMatrix4x4f;
+
+/// Everything the sky needs besides the geometry.
+/// `sun_direction` points toward the sun, the same vector [crate::sun_phong_shader::SunPhongShader] lights with.
+#[derive(Copy, Clone, Debug)]
+pub struct SkyParameters {
+    pub sun_direction: [f32; 3],
+    pub zenith_color: [f32; 3],
+    pub horizon_color: [f32; 3],
+    pub ground_color: [f32; 3],
+    pub sun_color: [f32; 3],
+    /// angular radius of the sun disc, radians
+    pub sun_radius: f32,
+}
+
+impl SkyParameters {
+    /// What the sky shader paints toward the unit vector `direction`, minus the sun disc,
+    /// which lit shaders already get as a directional light.  For
+    /// [crate::spherical_harmonics::ShIrradiance::from_directions].
+    pub fn sky_radiance(&self, direction: [f32; 3]) -> [f32; 3] {
+        let [x, y, z] = direction;
+        let mix = |a: [f32; 3], b: [f32; 3], t: f32| {
+            [
+                a[0] + (b[0] - a[0]) * t,
+                a[1] + (b[1] - a[1]) * t,
+                a[2] + (b[2] - a[2]) * t,
+            ]
+        };
+        let color = if y >= 0.0 {
+            mix(self.horizon_color, self.zenith_color, y.sqrt())
+        } else {
+            mix(self.horizon_color, self.ground_color, (-y * 8.0).min(1.0))
+        };
+
+        let [sx, sy, sz] = self.sun_direction;
+        let length = (sx * sx + sy * sy + sz * sz).sqrt().max(f32::EPSILON);
+        let cos_angle = (x * sx + y * sy + z * sz) / length;
+        let glow = cos_angle.max(0.0).powi(64) * 0.5;
+        let t = ((y + 0.02) / 0.02).clamp(0.0, 1.0);
+        let above = t * t * (3.0 - 2.0 * t);
+        let k = glow * above;
+        [
+            color[0] + self.sun_color[0] * k,
+            color[1] + self.sun_color[1] * k,
+            color[2] + self.sun_color[2] * k,
+        ]
+    }
+}
+
+/// An analytic sky: a gradient from the ground through the horizon to the zenith, with a sun disc
+/// and a glow around it.  The color only depends on the direction from the origin of
+/// the mesh's local coordinates, so any mesh surrounding the eye works (a cube is plenty).
+pub struct SkyShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sul_matrix: u32,
+    pub sul_sun_direction: u32,
+    pub sul_zenith_color: u32,
+    pub sul_horizon_color: u32,
+    pub sul_ground_color: u32,
+    pub sul_sun_color: u32,
+    pub sul_sun_radius: u32,
+}
+
+impl SkyShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src())?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sul_matrix = program.get_uniform_location("u_matrix")?;
+        let sul_sun_direction = program.get_uniform_location("u_sun_direction")?;
+        let sul_zenith_color = program.get_uniform_location("u_zenith_color")?;
+        let sul_horizon_color = program.get_uniform_location("u_horizon_color")?;
+        let sul_ground_color = program.get_uniform_location("u_ground_color")?;
+        let sul_sun_color = program.get_uniform_location("u_sun_color")?;
+        let sul_sun_radius = program.get_uniform_location("u_sun_radius")?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sul_matrix,
+            sul_sun_direction,
+            sul_zenith_color,
+            sul_horizon_color,
+            sul_ground_color,
+            sul_sun_color,
+            sul_sun_radius,
+        })
+    }
+
+    /// `matrix` carries the mesh to clip space; it should be centered on the eye.
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        matrix: &XrMatrix4x4f,
+        parameters: &SkyParameters,
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+
+        self.set_parameters(matrix, parameters)?;
+
+        let bindings = buffers.activate(gpu_state);
+
+        bindings.draw_elements(gl::TRIANGLES, n_indices, 0)?;
+
+        buffers.deactivate(bindings);
+        unsafe {
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+
+        Ok(())
+    }
+
+    pub fn set_parameters(
+        &self,
+        matrix: &XrMatrix4x4f,
+        parameters: &SkyParameters,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program
+            .set_mat4u(self.sul_matrix as GLint, matrix.slice())?;
+        self.program
+            .set_uniform_3fv(self.sul_sun_direction as GLint, &parameters.sun_direction)?;
+        self.program
+            .set_uniform_3fv(self.sul_zenith_color as GLint, &parameters.zenith_color)?;
+        self.program
+            .set_uniform_3fv(self.sul_horizon_color as GLint, &parameters.horizon_color)?;
+        self.program
+            .set_uniform_3fv(self.sul_ground_color as GLint, &parameters.ground_color)?;
+        self.program
+            .set_uniform_3fv(self.sul_sun_color as GLint, &parameters.sun_color)?;
+        self.program
+            .set_uniform_1f(self.sul_sun_radius as GLint, parameters.sun_radius)
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "
+attribute vec4 a_position;
+
+uniform mat4 u_matrix;
+
+varying vec3 v_direction;
+
+void main()
+{
+    v_direction = a_position.xyz;
+    gl_Position = u_matrix * a_position;
+}
+"
+}
+
+fn shader_f_src() -> &'static str {
+    "#ifdef GL_ES
+precision highp float;
+#endif
+varying vec3 v_direction;
+
+uniform vec3 u_sun_direction;
+uniform vec3 u_zenith_color;
+uniform vec3 u_horizon_color;
+uniform vec3 u_ground_color;
+uniform vec3 u_sun_color;
+uniform float u_sun_radius;
+
+void main()
+{
+    vec3 dir = normalize(v_direction);
+    vec3 sun = normalize(u_sun_direction);
+
+    // the horizon band is thin; the sky eases into the zenith color
+    vec3 sky = mix(u_horizon_color, u_zenith_color, pow(max(dir.y, 0.0), 0.5));
+    vec3 ground = mix(u_horizon_color, u_ground_color, min(-dir.y * 8.0, 1.0));
+    vec3 color = dir.y >= 0.0 ? sky : ground;
+
+    float cos_angle = dot(dir, sun);
+    float cos_radius = cos(u_sun_radius);
+    // a soft edge about a tenth of the radius wide
+    float disc = smoothstep(cos_radius - 0.1 * u_sun_radius * u_sun_radius, cos_radius, cos_angle);
+    float glow = pow(max(cos_angle, 0.0), 64.0) * 0.5;
+    // the ground hides the sun once it sets
+    float above = smoothstep(-0.02, 0.0, dir.y);
+
+    color += u_sun_color * (disc + glow) * above;
+    gl_FragColor = vec4(color, 1.0);
+}
+"
+}
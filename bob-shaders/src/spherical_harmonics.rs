@@ -0,0 +1,149 @@
+use std::f32::consts::PI;
+
+/// The nine real spherical harmonics of bands 0..=2 at the unit vector `(x, y, z)`
+fn sh9_basis(x: f32, y: f32, z: f32) -> [f32; 9] {
+    [
+        0.282095,
+        0.488603 * y,
+        0.488603 * z,
+        0.488603 * x,
+        1.092548 * x * y,
+        1.092548 * y * z,
+        0.315392 * (3.0 * z * z - 1.0),
+        1.092548 * x * z,
+        0.546274 * (x * x - y * y),
+    ]
+}
+
+/// How much each band survives the cosine lobe, divided by π so the result is the
+/// outgoing light of a white diffuse surface (Ramamoorthi & Hanrahan).
+const BAND_SCALE: [f32; 9] = [
+    1.0,
+    2.0 / 3.0,
+    2.0 / 3.0,
+    2.0 / 3.0,
+    0.25,
+    0.25,
+    0.25,
+    0.25,
+    0.25,
+];
+
+/// Diffuse light arriving from all around, squeezed into nine RGB coefficients.  What
+/// [crate::lights::LIGHTS_GLSL]'s `ambient_irradiance` evaluates for a surface normal, in place
+/// of a single ambient constant.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShIrradiance {
+    /// already convolved with the cosine lobe, in the order of `u_sh` in the shader
+    pub coefficients: [[f32; 3]; 9],
+}
+
+impl ShIrradiance {
+    /// the same light from every direction
+    pub fn constant(rgb: [f32; 3]) -> Self {
+        let mut coefficients = [[0.0; 3]; 9];
+        // cancels sh9_basis's band 0 constant
+        let k = 2.0 * PI.sqrt();
+        coefficients[0] = rgb.map(|c| c * k);
+        Self { coefficients }
+    }
+
+    /// Project an equirectangular environment map, laid out the way
+    /// [crate::equirect_shader::EquirectShader] samples it but with the top row (straight up)
+    /// first, like decoded image files.  `radiance(column, row)` is linear RGB.
+    pub fn from_equirect(width: u32, height: u32, radiance: impl Fn(u32, u32) -> [f32; 3]) -> Self {
+        Self::project(width, height, |column, row, _| radiance(column, row))
+    }
+
+    /// Project light described by direction, e.g. an analytic sky.  `radiance(direction)`
+    /// is linear RGB arriving from the unit vector `direction`; it is sampled on a grid
+    /// about 3° apart, so features smaller than that (a sun disc) come and go.
+    pub fn from_directions(radiance: impl Fn([f32; 3]) -> [f32; 3]) -> Self {
+        Self::project(128, 64, |_, _, direction| radiance(direction))
+    }
+
+    fn project(width: u32, height: u32, radiance: impl Fn(u32, u32, [f32; 3]) -> [f32; 3]) -> Self {
+        let mut sums = [[0.0f32; 3]; 9];
+        if width == 0 || height == 0 {
+            return Self { coefficients: sums };
+        }
+        let d_longitude = 2.0 * PI / width as f32;
+        let d_latitude = PI / height as f32;
+        for row in 0..height {
+            let latitude = PI / 2.0 - (row as f32 + 0.5) * d_latitude;
+            let (sin_lat, cos_lat) = latitude.sin_cos();
+            let solid_angle = cos_lat * d_latitude * d_longitude;
+            for column in 0..width {
+                let longitude = ((column as f32 + 0.5) / width as f32 - 0.5) * 2.0 * PI;
+                let (sin_lon, cos_lon) = longitude.sin_cos();
+                let direction = [sin_lon * cos_lat, sin_lat, -cos_lon * cos_lat];
+                let basis = sh9_basis(direction[0], direction[1], direction[2]);
+                let rgb = radiance(column, row, direction);
+                for (sum, y) in sums.iter_mut().zip(basis) {
+                    for (s, c) in sum.iter_mut().zip(rgb) {
+                        *s += c * y * solid_angle;
+                    }
+                }
+            }
+        }
+        for (sum, scale) in sums.iter_mut().zip(BAND_SCALE) {
+            for s in sum.iter_mut() {
+                *s *= scale;
+            }
+        }
+        Self { coefficients: sums }
+    }
+
+    /// like [ShIrradiance::from_equirect] for sRGB-encoded RGBA8 pixels, e.g. a decoded PNG
+    pub fn from_equirect_rgba8(width: u32, height: u32, pixels: &[u8]) -> Self {
+        let lut: Vec<f32> = (0..=255u8).map(srgb_to_linear).collect();
+        let row_bytes = width as usize * 4;
+        let height = height.min((pixels.len() / row_bytes.max(1)) as u32);
+        Self::from_equirect(width, height, |column, row| {
+            let i = row as usize * row_bytes + column as usize * 4;
+            [
+                lut[pixels[i] as usize],
+                lut[pixels[i + 1] as usize],
+                lut[pixels[i + 2] as usize],
+            ]
+        })
+    }
+
+    /// the diffuse light on a white surface facing `normal` (unit length)
+    pub fn evaluate(&self, normal: [f32; 3]) -> [f32; 3] {
+        let basis = sh9_basis(normal[0], normal[1], normal[2]);
+        let mut rval = [0.0; 3];
+        for (coefficient, y) in self.coefficients.iter().zip(basis) {
+            for (r, c) in rval.iter_mut().zip(coefficient) {
+                *r += c * y;
+            }
+        }
+        rval.map(|c| c.max(0.0))
+    }
+
+    /// brighter or dimmer by `factor`
+    pub fn scaled(mut self, factor: f32) -> Self {
+        for coefficient in self.coefficients.iter_mut() {
+            for c in coefficient.iter_mut() {
+                *c *= factor;
+            }
+        }
+        self
+    }
+}
+
+impl Default for ShIrradiance {
+    /// the dim grey the lit shaders used before they had an environment
+    fn default() -> Self {
+        Self::constant([0.1; 3])
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let c = value as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
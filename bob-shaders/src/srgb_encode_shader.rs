@@ -0,0 +1,87 @@
+use crate::compat::SRGB_ENCODE_GLSL;
+use crate::GeometryBuffer;
+use gl::types::{GLint, GLsizei};
+use gl_thin::gl_fancy::{ActiveTextureUnit, GPUState};
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program, Texture};
+
+/// Copies a linear image into a destination that expects sRGB-encoded values but will not
+/// encode them itself, with [SRGB_ENCODE_GLSL]'s approximation.  The last pass on devices
+/// without sRGB framebuffers.  Draw a quad covering clip space from -1 to 1.
+pub struct SrgbEncodeShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sul_color: u32,
+}
+
+impl SrgbEncodeShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src())?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sul_color = program.get_uniform_location("color")?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sul_color,
+        })
+    }
+
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        color: &Texture,
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+
+        let unit = ActiveTextureUnit(0);
+        gpu_state.set_active_texture(unit)?;
+        color.bind(gl::TEXTURE_2D)?;
+        self.program
+            .set_uniform_1i(self.sul_color as GLint, unit.0 as GLint)?;
+
+        let bindings = buffers.activate(gpu_state);
+
+        bindings.draw_elements(gl::TRIANGLE_STRIP, n_indices, 0)?;
+
+        buffers.deactivate(bindings);
+        unsafe {
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+
+        Ok(())
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "
+attribute vec4 a_position;
+
+varying vec2 v_uv;
+
+void main()
+{
+    v_uv = a_position.xy * 0.5 + 0.5;
+    gl_Position = vec4(a_position.xy, 0.0, 1.0);
+}
+"
+}
+
+fn shader_f_src() -> String {
+    format!(
+        "#ifdef GL_ES
+precision mediump float;
+#endif
+varying vec2 v_uv;
+uniform sampler2D color;
+{}
+void main()
+{{
+    vec4 linear = texture2D(color, v_uv);
+    gl_FragColor = vec4(encode_srgb(linear.rgb), linear.a);
+}}",
+        SRGB_ENCODE_GLSL
+    )
+}
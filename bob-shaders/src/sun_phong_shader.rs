@@ -1,8 +1,12 @@
+use crate::fog::{Fog, FogUniforms, FOG_GLSL};
+use crate::lights::{LightManager, LightUniforms, LIGHTS_GLSL};
 use crate::GeometryBuffer;
 use gl::types::{GLint, GLsizei};
 use gl_thin::gl_fancy::{BoundBuffers, GPUState};
 use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program};
 use gl_thin::linear::XrMatrix4x4f;
+use gl_thin::uniform_batch::UniformBatch;
+use std::cell::RefCell;
 
 //
 
@@ -12,6 +16,12 @@ pub struct SunPhongShader {
     pub sal_normal: u32,
     pub sul_m_matrix: u32,
     pub sul_pv_matrix: u32,
+    pub sul_sun_direction: u32,
+    pub sul_color: u32,
+    pub fog_uniforms: FogUniforms,
+    pub light_uniforms: LightUniforms,
+    /// the matrices, sun and color, which mostly repeat from one draw to the next
+    uniforms: RefCell<UniformBatch>,
 }
 
 impl SunPhongShader {
@@ -23,6 +33,10 @@ impl SunPhongShader {
 
         let sul_m_matrix = program.get_uniform_location("m_matrix")?;
         let sul_pv_matrix = program.get_uniform_location("pv_matrix")?;
+        let sul_sun_direction = program.get_uniform_location("sun_direction")?;
+        let sul_color = program.get_uniform_location("color")?;
+        let fog_uniforms = FogUniforms::new(&program)?;
+        let light_uniforms = LightUniforms::new(&program)?;
 
         log::debug!(
             "attribute, uniform locations {} {}  {} {}",
@@ -38,6 +52,11 @@ impl SunPhongShader {
             sal_normal,
             sul_m_matrix,
             sul_pv_matrix,
+            sul_sun_direction,
+            sul_color,
+            fog_uniforms,
+            light_uniforms,
+            uniforms: RefCell::new(UniformBatch::new()),
         })
     }
 
@@ -48,13 +67,15 @@ impl SunPhongShader {
         pv_matrix: &XrMatrix4x4f,
         sun_direction: &[f32; 3],
         color: &[f32; 3],
+        fog: &Fog,
+        lights: &LightManager,
         buffers: &dyn GeometryBuffer<AT, IT>,
         n_indices: GLsizei,
         gpu_state: &mut GPUState,
     ) -> Result<(), GLErrorWrapper> {
         self.program.use_()?;
 
-        self.set_parameters(m_matrix, pv_matrix, sun_direction, color)?;
+        self.set_parameters(m_matrix, pv_matrix, sun_direction, color, fog, lights)?;
 
         let bindings = buffers.activate(gpu_state);
 
@@ -77,29 +98,21 @@ impl SunPhongShader {
         pv_matrix: &XrMatrix4x4f,
         sun_direction: &[f32; 3],
         color: &[f32; 3],
+        fog: &Fog,
+        lights: &LightManager,
     ) -> Result<(), GLErrorWrapper> {
-        self.set_m_matrix(m_matrix)?;
-        self.set_pv_matrix(pv_matrix)?;
-
-        self.set_sun_direction(sun_direction)?;
-        self.set_color(color)?;
+        self.uniforms
+            .borrow_mut()
+            .set_mat4u(self.sul_m_matrix as GLint, m_matrix.slice())
+            .set_mat4u(self.sul_pv_matrix as GLint, pv_matrix.slice())
+            .set_3fv(self.sul_sun_direction as GLint, sun_direction)
+            .set_3fv(self.sul_color as GLint, color)
+            .flush_to(&self.program)?;
+        self.fog_uniforms.set(&self.program, fog)?;
+        self.light_uniforms.set(&self.program, lights)?;
         Ok(())
     }
 
-    fn set_color(&self, color: &[f32; 3]) -> Result<(), GLErrorWrapper> {
-        self.program
-            .set_uniform_3f("color", color[0], color[1], color[2])
-    }
-
-    fn set_sun_direction(&self, sun_direction: &[f32; 3]) -> Result<(), GLErrorWrapper> {
-        self.program.set_uniform_3f(
-            "sun_direction",
-            sun_direction[0],
-            sun_direction[1],
-            sun_direction[2],
-        )
-    }
-
     pub fn rig_attribute_arrays<AT: GLBufferType, IT: GLBufferType>(
         &self,
         binding: &BoundBuffers<AT, IT>,
@@ -111,16 +124,6 @@ impl SunPhongShader {
         // Renderer::rig_one_va(&self.program, "a_normal", 3, 6, 3)?;
         Ok(())
     }
-
-    fn set_m_matrix(&self, projection_matrix: &XrMatrix4x4f) -> Result<(), GLErrorWrapper> {
-        self.program
-            .set_mat4u(self.sul_m_matrix as GLint, projection_matrix.slice())
-    }
-
-    fn set_pv_matrix(&self, projection_matrix: &XrMatrix4x4f) -> Result<(), GLErrorWrapper> {
-        self.program
-            .set_mat4u(self.sul_pv_matrix as GLint, projection_matrix.slice())
-    }
 }
 
 fn shader_v_src() -> &'static str {
@@ -129,32 +132,43 @@ attribute vec4 a_position;
 attribute vec3 a_normal;
 
 varying vec3 v_normal;
+varying vec3 v_world;
+varying float v_depth;
 
 uniform mat4 m_matrix;
 uniform mat4 pv_matrix;
 
 void main()
 {
-    gl_Position = pv_matrix * m_matrix * a_position;
+    vec4 world = m_matrix * a_position;
+    gl_Position = pv_matrix * world;
+    v_world = world.xyz;
+    v_depth = gl_Position.w;
     v_normal = mat3(m_matrix) * a_normal;
 }
 "
 }
 
-fn shader_f_src() -> &'static str {
-    "#ifdef GL_ES
+fn shader_f_src() -> String {
+    format!(
+        "#ifdef GL_ES
 precision highp float;
 #endif
 varying vec3 v_normal;
+varying vec3 v_world;
+varying float v_depth;
 uniform vec3 sun_direction;
 uniform vec3 color;
+{FOG_GLSL}
+{LIGHTS_GLSL}
 void main()
 {{
     vec3 N = normalize(v_normal);
     vec3 SD = normalize(sun_direction);
-    float ambient=0.1;
+    vec3 ambient = ambient_irradiance(N);
 
-    float lum = ambient+max(0.0, dot(N,SD));
-    gl_FragColor = vec4(color*lum, 1.0);
+    vec3 lum = ambient + max(0.0, dot(N,SD)) + apply_lights(N, v_world);
+    gl_FragColor = vec4(apply_fog(color*lum, v_depth), 1.0);
 }}"
+    )
 }
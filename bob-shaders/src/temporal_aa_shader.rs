@@ -0,0 +1,168 @@
+use crate::GeometryBuffer;
+use gl::types::{GLint, GLsizei};
+use gl_thin::gl_fancy::{ActiveTextureUnit, GPUState};
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program, Texture};
+use gl_thin::linear::XrMatrix4x4f;
+
+/// What [TemporalAaShader] needs besides the textures
+#[derive(Copy, Clone, Debug)]
+pub struct TemporalAaParameters {
+    /// from this frame's clip space to the previous frame's, i.e. previous PV * inverse(current PV)
+    pub reprojection: XrMatrix4x4f,
+    /// one over the image's width and height
+    pub texel_size: [f32; 2],
+    /// how much of this frame goes into the result; lower is smoother but smears more
+    pub blend: f32,
+    /// false on the first frame (or after a gap), when the history holds nothing useful
+    pub history_valid: bool,
+}
+
+/// Blends the current frame into an accumulated history to calm the shimmer of thin,
+/// high-contrast geometry.  Each pixel finds where it was last frame from the depth buffer
+/// and the camera's motion, and the history found there is clamped to the colors around the
+/// pixel now, so what moved or was uncovered does not leave ghosts.  Only the camera's motion
+/// is known; moving objects lean on the clamp.
+/// Draw a quad covering clip space from -1 to 1.
+pub struct TemporalAaShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sul_current: u32,
+    pub sul_depth: u32,
+    pub sul_history: u32,
+    pub sul_reprojection: u32,
+    pub sul_texel_size: u32,
+    pub sul_blend: u32,
+}
+
+impl TemporalAaShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src())?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sul_current = program.get_uniform_location("current")?;
+        let sul_depth = program.get_uniform_location("depth")?;
+        let sul_history = program.get_uniform_location("history")?;
+        let sul_reprojection = program.get_uniform_location("reprojection")?;
+        let sul_texel_size = program.get_uniform_location("texel_size")?;
+        let sul_blend = program.get_uniform_location("blend")?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sul_current,
+            sul_depth,
+            sul_history,
+            sul_reprojection,
+            sul_texel_size,
+            sul_blend,
+        })
+    }
+
+    /// `depth` must sample with NEAREST filtering
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        current: &Texture,
+        depth: &Texture,
+        history: &Texture,
+        parameters: &TemporalAaParameters,
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+
+        let units = [
+            ActiveTextureUnit(0),
+            ActiveTextureUnit(1),
+            ActiveTextureUnit(2),
+        ];
+        let textures = [current, depth, history];
+        let locations = [self.sul_current, self.sul_depth, self.sul_history];
+        for ((unit, texture), location) in units.iter().zip(textures).zip(locations) {
+            gpu_state.set_active_texture(*unit)?;
+            texture.bind(gl::TEXTURE_2D)?;
+            self.program
+                .set_uniform_1i(location as GLint, unit.0 as GLint)?;
+        }
+
+        self.program.set_mat4u(
+            self.sul_reprojection as GLint,
+            parameters.reprojection.slice(),
+        )?;
+        self.program
+            .set_uniform_2fv(self.sul_texel_size as GLint, &parameters.texel_size)?;
+        // with no history, take all of the current frame
+        let blend = if parameters.history_valid {
+            parameters.blend
+        } else {
+            1.0
+        };
+        self.program
+            .set_uniform_1f(self.sul_blend as GLint, blend)?;
+
+        let bindings = buffers.activate(gpu_state);
+
+        bindings.draw_elements(gl::TRIANGLE_STRIP, n_indices, 0)?;
+
+        buffers.deactivate(bindings);
+        unsafe {
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+        gpu_state.set_active_texture(units[0])?;
+
+        Ok(())
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "
+attribute vec4 a_position;
+
+varying vec2 v_uv;
+
+void main()
+{
+    v_uv = a_position.xy * 0.5 + 0.5;
+    gl_Position = vec4(a_position.xy, 0.0, 1.0);
+}
+"
+}
+
+fn shader_f_src() -> &'static str {
+    "#ifdef GL_ES
+precision highp float;
+#endif
+varying vec2 v_uv;
+uniform sampler2D current;
+uniform sampler2D depth;
+uniform sampler2D history;
+uniform mat4 reprojection;
+uniform vec2 texel_size;
+uniform float blend;
+
+void main()
+{
+    vec4 now = texture2D(current, v_uv);
+    vec3 color = now.rgb;
+
+    // the range of colors around this pixel now; history outside it is stale
+    vec3 n = texture2D(current, v_uv + vec2(0.0, texel_size.y)).rgb;
+    vec3 s = texture2D(current, v_uv - vec2(0.0, texel_size.y)).rgb;
+    vec3 e = texture2D(current, v_uv + vec2(texel_size.x, 0.0)).rgb;
+    vec3 w = texture2D(current, v_uv - vec2(texel_size.x, 0.0)).rgb;
+    vec3 lo = min(color, min(min(n, s), min(e, w)));
+    vec3 hi = max(color, max(max(n, s), max(e, w)));
+
+    // where this pixel was in the previous frame
+    float z = texture2D(depth, v_uv).r * 2.0 - 1.0;
+    vec4 previous = reprojection * vec4(v_uv * 2.0 - 1.0, z, 1.0);
+    vec2 history_uv = previous.xy / previous.w * 0.5 + 0.5;
+    vec2 inside = step(vec2(0.0), history_uv) * step(history_uv, vec2(1.0));
+
+    vec3 remembered = clamp(texture2D(history, history_uv).rgb, lo, hi);
+    float weight = mix(1.0, blend, inside.x * inside.y);
+    // the current alpha, so the layers under the eyes still show through
+    gl_FragColor = vec4(mix(remembered, color, weight), now.a);
+}"
+}
@@ -0,0 +1,94 @@
+use crate::GeometryBuffer;
+use gl::types::{GLint, GLsizei};
+use gl_thin::gl_fancy::{ActiveTextureUnit, GPUState};
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program, Texture};
+
+/// Brings an HDR image (values past 1.0) into the displayable range, for the last pass
+/// out of a half-float render target.  Scales by `exposure`, then rolls highlights off
+/// with the ACES filmic curve (Narkowicz's fit) instead of clipping them.
+/// Draw a quad covering clip space from -1 to 1.
+pub struct TonemapShader {
+    pub program: Program,
+    pub sal_position: u32,
+    pub sul_color: u32,
+    pub sul_exposure: u32,
+}
+
+impl TonemapShader {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src())?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sul_color = program.get_uniform_location("color")?;
+        let sul_exposure = program.get_uniform_location("exposure")?;
+
+        Ok(Self {
+            program,
+            sal_position,
+            sul_color,
+            sul_exposure,
+        })
+    }
+
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        color: &Texture,
+        exposure: f32,
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program.use_()?;
+
+        let unit = ActiveTextureUnit(0);
+        gpu_state.set_active_texture(unit)?;
+        color.bind(gl::TEXTURE_2D)?;
+        self.program
+            .set_uniform_1i(self.sul_color as GLint, unit.0 as GLint)?;
+        self.program
+            .set_uniform_1f(self.sul_exposure as GLint, exposure)?;
+
+        let bindings = buffers.activate(gpu_state);
+
+        bindings.draw_elements(gl::TRIANGLE_STRIP, n_indices, 0)?;
+
+        buffers.deactivate(bindings);
+        unsafe {
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+
+        Ok(())
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "
+attribute vec4 a_position;
+
+varying vec2 v_uv;
+
+void main()
+{
+    v_uv = a_position.xy * 0.5 + 0.5;
+    gl_Position = vec4(a_position.xy, 0.0, 1.0);
+}
+"
+}
+
+fn shader_f_src() -> &'static str {
+    "#ifdef GL_ES
+precision mediump float;
+#endif
+varying vec2 v_uv;
+uniform sampler2D color;
+uniform float exposure;
+
+void main()
+{
+    vec4 hdr = texture2D(color, v_uv);
+    vec3 x = max(hdr.rgb * exposure, vec3(0.0));
+    vec3 mapped = (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+    // alpha untouched, so the layers under the eyes still show through
+    gl_FragColor = vec4(clamp(mapped, 0.0, 1.0), clamp(hdr.a, 0.0, 1.0));
+}"
+}
@@ -0,0 +1,207 @@
+use crate::GeometryBuffer;
+use gl::types::{GLenum, GLint, GLsizei};
+use gl_thin::gl_fancy::{ActiveTextureUnit, GPUState};
+use gl_thin::gl_helper::{GLBufferType, GLErrorWrapper, Program};
+use gl_thin::linear::XrMatrix4x4f;
+use gl_thin::yuv::{YuvLayout, YuvTextures};
+
+/// Which set of coefficients to use when converting YUV to RGB
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum YuvColorSpace {
+    /// standard definition video, and most cameras
+    Bt601,
+    /// HD video
+    Bt709,
+}
+
+/// The YUV→RGB conversion as a column-major mat3 (columns are the Y, U, V coefficients)
+/// plus the offset to subtract from (Y,U,V) first.
+pub fn yuv_to_rgb_matrix(color_space: YuvColorSpace, full_range: bool) -> ([f32; 9], [f32; 3]) {
+    #[rustfmt::skip]
+    let matrix = match (color_space, full_range) {
+        (YuvColorSpace::Bt601, false) => [
+            1.164, 1.164, 1.164,
+            0.0, -0.392, 2.017,
+            1.596, -0.813, 0.0,
+        ],
+        (YuvColorSpace::Bt601, true) => [
+            1.0, 1.0, 1.0,
+            0.0, -0.344, 1.772,
+            1.402, -0.714, 0.0,
+        ],
+        (YuvColorSpace::Bt709, false) => [
+            1.164, 1.164, 1.164,
+            0.0, -0.213, 2.112,
+            1.793, -0.533, 0.0,
+        ],
+        (YuvColorSpace::Bt709, true) => [
+            1.0, 1.0, 1.0,
+            0.0, -0.1873, 1.8556,
+            1.5748, -0.4681, 0.0,
+        ],
+    };
+    let offset = if full_range {
+        [0.0, 0.5, 0.5]
+    } else {
+        [16.0 / 255.0, 0.5, 0.5]
+    };
+    (matrix, offset)
+}
+
+/// Samples the planes of a [YuvTextures] and converts to RGB in the fragment shader.
+/// The shader is specific to the [YuvLayout] because NV12 and I420 need a different number of samplers.
+pub struct YuvShader {
+    pub program: Program,
+    pub layout: YuvLayout,
+    pub sal_position: u32,
+    pub sal_tex_coord: u32,
+    pub sul_matrix: u32,
+    pub sul_yuv_to_rgb: u32,
+    pub sul_yuv_offset: u32,
+    pub sul_planes: Vec<u32>,
+}
+
+impl YuvShader {
+    pub fn new(layout: YuvLayout) -> Result<Self, GLErrorWrapper> {
+        let program = Program::compile(shader_v_src(), shader_f_src(layout))?;
+
+        let sal_position = program.get_attribute_location("a_position")?;
+        let sal_tex_coord = program.get_attribute_location("a_texCoord")?;
+
+        let sul_matrix = program.get_uniform_location("u_matrix")?;
+        let sul_yuv_to_rgb = program.get_uniform_location("yuv_to_rgb")?;
+        let sul_yuv_offset = program.get_uniform_location("yuv_offset")?;
+        let sul_planes = plane_sampler_names(layout)
+            .iter()
+            .map(|name| program.get_uniform_location(name))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            program,
+            layout,
+            sal_position,
+            sal_tex_coord,
+            sul_matrix,
+            sul_yuv_to_rgb,
+            sul_yuv_offset,
+            sul_planes,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw<AT, IT: GLBufferType>(
+        &self,
+        matrix: &XrMatrix4x4f,
+        textures: &YuvTextures,
+        color_space: YuvColorSpace,
+        full_range: bool,
+        draw_mode: GLenum,
+        buffers: &dyn GeometryBuffer<AT, IT>,
+        n_indices: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        if textures.layout != self.layout {
+            return Err(GLErrorWrapper::with_message2(format!(
+                "shader compiled for {:?} but textures are {:?}",
+                self.layout, textures.layout
+            )));
+        }
+
+        self.program.use_()?;
+
+        let units = textures.bind_to_units(ActiveTextureUnit(0), gpu_state)?;
+        self.set_parameters(matrix, &units, color_space, full_range)?;
+
+        let bindings = buffers.activate(gpu_state);
+
+        bindings.draw_elements(draw_mode, n_indices, 0)?;
+
+        // unbind
+
+        buffers.deactivate(bindings);
+        unsafe {
+            gl::DisableVertexAttribArray(self.sal_tex_coord);
+            gl::DisableVertexAttribArray(self.sal_position);
+        }
+
+        Ok(())
+    }
+
+    pub fn set_parameters(
+        &self,
+        matrix: &XrMatrix4x4f,
+        units: &[ActiveTextureUnit],
+        color_space: YuvColorSpace,
+        full_range: bool,
+    ) -> Result<(), GLErrorWrapper> {
+        self.program
+            .set_mat4u(self.sul_matrix as GLint, matrix.slice())?;
+
+        for (location, unit) in self.sul_planes.iter().zip(units) {
+            self.program
+                .set_uniform_1i(*location as GLint, unit.0 as GLint)?;
+        }
+
+        let (yuv_to_rgb, yuv_offset) = yuv_to_rgb_matrix(color_space, full_range);
+        self.program
+            .set_mat3u(self.sul_yuv_to_rgb as GLint, &yuv_to_rgb)?;
+        self.program
+            .set_uniform_3fv(self.sul_yuv_offset as GLint, &yuv_offset)
+    }
+}
+
+fn plane_sampler_names(layout: YuvLayout) -> &'static [&'static str] {
+    match layout {
+        YuvLayout::Nv12 => &["tex_y", "tex_uv"],
+        YuvLayout::I420 => &["tex_y", "tex_u", "tex_v"],
+    }
+}
+
+fn shader_v_src() -> &'static str {
+    "
+attribute vec4 a_position;
+attribute vec2 a_texCoord;
+
+varying vec2 v_texCoord;
+
+uniform mat4 u_matrix;
+
+void main()
+{
+    gl_Position = u_matrix * a_position;
+    v_texCoord = a_texCoord;
+}
+"
+}
+
+fn shader_f_src(layout: YuvLayout) -> String {
+    let (samplers, fetch) = match layout {
+        YuvLayout::Nv12 => (
+            "uniform sampler2D tex_y;
+uniform sampler2D tex_uv;",
+            "vec3(texture2D(tex_y, v_texCoord).r, texture2D(tex_uv, v_texCoord).rg)",
+        ),
+        YuvLayout::I420 => (
+            "uniform sampler2D tex_y;
+uniform sampler2D tex_u;
+uniform sampler2D tex_v;",
+            "vec3(texture2D(tex_y, v_texCoord).r, texture2D(tex_u, v_texCoord).r, texture2D(tex_v, v_texCoord).r)",
+        ),
+    };
+
+    format!(
+        "#ifdef GL_ES
+precision highp float;
+#endif
+varying vec2 v_texCoord;
+{}
+uniform mat3 yuv_to_rgb;
+uniform vec3 yuv_offset;
+void main()
+{{
+    vec3 yuv = {} - yuv_offset;
+    gl_FragColor = vec4(clamp(yuv_to_rgb * yuv, 0.0, 1.0), 1.0);
+}}",
+        samplers, fetch
+    )
+}
@@ -0,0 +1,82 @@
+//! The bits of the AAudio C API that the microphone and the spatial audio output share.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub struct AudioError {
+    pub call: &'static str,
+    pub status: i32,
+}
+
+impl Display for AudioError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} failed with status {}", self.call, self.status)
+    }
+}
+
+impl Error for AudioError {}
+
+pub(crate) fn audio_check(call: &'static str, status: i32) -> Result<(), AudioError> {
+    if status == ffi::AAUDIO_OK {
+        Ok(())
+    } else {
+        Err(AudioError { call, status })
+    }
+}
+
+#[allow(non_camel_case_types, non_snake_case)]
+pub(crate) mod ffi {
+    use std::ffi::c_void;
+
+    pub type aaudio_result_t = i32;
+
+    pub const AAUDIO_OK: aaudio_result_t = 0;
+    pub const AAUDIO_DIRECTION_OUTPUT: i32 = 0;
+    pub const AAUDIO_DIRECTION_INPUT: i32 = 1;
+    pub const AAUDIO_FORMAT_PCM_FLOAT: i32 = 2;
+    pub const AAUDIO_PERFORMANCE_MODE_LOW_LATENCY: i32 = 12;
+
+    pub enum AAudioStreamBuilder {}
+    pub enum AAudioStream {}
+
+    #[link(name = "aaudio")]
+    extern "C" {
+        pub fn AAudio_createStreamBuilder(
+            builder: *mut *mut AAudioStreamBuilder,
+        ) -> aaudio_result_t;
+        pub fn AAudioStreamBuilder_setDirection(builder: *mut AAudioStreamBuilder, direction: i32);
+        pub fn AAudioStreamBuilder_setFormat(builder: *mut AAudioStreamBuilder, format: i32);
+        pub fn AAudioStreamBuilder_setChannelCount(
+            builder: *mut AAudioStreamBuilder,
+            channel_count: i32,
+        );
+        pub fn AAudioStreamBuilder_setSampleRate(builder: *mut AAudioStreamBuilder, rate: i32);
+        pub fn AAudioStreamBuilder_setPerformanceMode(builder: *mut AAudioStreamBuilder, mode: i32);
+        pub fn AAudioStreamBuilder_openStream(
+            builder: *mut AAudioStreamBuilder,
+            stream: *mut *mut AAudioStream,
+        ) -> aaudio_result_t;
+        pub fn AAudioStreamBuilder_delete(builder: *mut AAudioStreamBuilder) -> aaudio_result_t;
+
+        pub fn AAudioStream_requestStart(stream: *mut AAudioStream) -> aaudio_result_t;
+        pub fn AAudioStream_requestStop(stream: *mut AAudioStream) -> aaudio_result_t;
+        pub fn AAudioStream_close(stream: *mut AAudioStream) -> aaudio_result_t;
+        pub fn AAudioStream_getSampleRate(stream: *mut AAudioStream) -> i32;
+        pub fn AAudioStream_getBufferSizeInFrames(stream: *mut AAudioStream) -> i32;
+        pub fn AAudioStream_getFramesWritten(stream: *mut AAudioStream) -> i64;
+        pub fn AAudioStream_getFramesRead(stream: *mut AAudioStream) -> i64;
+        pub fn AAudioStream_read(
+            stream: *mut AAudioStream,
+            buffer: *mut c_void,
+            num_frames: i32,
+            timeout_nanoseconds: i64,
+        ) -> aaudio_result_t;
+        pub fn AAudioStream_write(
+            stream: *mut AAudioStream,
+            buffer: *const c_void,
+            num_frames: i32,
+            timeout_nanoseconds: i64,
+        ) -> aaudio_result_t;
+    }
+}
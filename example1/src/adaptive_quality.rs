@@ -0,0 +1,206 @@
+//! Decides how much optional rendering work to do.  Missed frames, the device's battery and
+//! thermal state ([crate::device_status]) and the XR runtime's performance notifications
+//! all lower the quality level; it creeps back up once things have been calm for a while.
+//! The governor also picks the CPU/GPU levels to request from the runtime.
+
+use crate::device_status::ThrottleLevel;
+use gl_thin::performance_settings::{
+    PerfSettingsDomainEXT, PerfSettingsLevelEXT, PerfSettingsNotificationLevelEXT,
+    PerformanceNotification,
+};
+use std::time::Duration;
+
+/// frames per evaluation
+const WINDOW_FRAMES: u32 = 90;
+/// a frame this many periods after the previous one missed its slot
+const LATE_FACTOR: f32 = 1.5;
+/// evaluations without a missed frame before trying the next level up
+const CALM_WINDOWS_TO_RAISE: u32 = 5;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum QualityLevel {
+    /// the scene, the UI, nothing else
+    Low,
+    /// no spectator view
+    Medium,
+    High,
+}
+
+impl QualityLevel {
+    pub const ALL: [QualityLevel; 3] =
+        [QualityLevel::Low, QualityLevel::Medium, QualityLevel::High];
+
+    fn lower(self) -> Self {
+        match self {
+            QualityLevel::High => QualityLevel::Medium,
+            _ => QualityLevel::Low,
+        }
+    }
+
+    fn higher(self) -> Self {
+        match self {
+            QualityLevel::Low => QualityLevel::Medium,
+            _ => QualityLevel::High,
+        }
+    }
+}
+
+impl From<ThrottleLevel> for QualityLevel {
+    fn from(throttle: ThrottleLevel) -> Self {
+        match throttle {
+            ThrottleLevel::Normal => QualityLevel::High,
+            ThrottleLevel::Reduce => QualityLevel::Medium,
+            ThrottleLevel::Minimum => QualityLevel::Low,
+        }
+    }
+}
+
+fn allowed_by_notification(level: PerfSettingsNotificationLevelEXT) -> QualityLevel {
+    match level {
+        PerfSettingsNotificationLevelEXT::WARNING => QualityLevel::Medium,
+        PerfSettingsNotificationLevelEXT::IMPAIRED => QualityLevel::Low,
+        _ => QualityLevel::High,
+    }
+}
+
+/// the less demanding of two performance levels
+fn gentler(a: PerfSettingsLevelEXT, b: PerfSettingsLevelEXT) -> PerfSettingsLevelEXT {
+    if a.into_raw() <= b.into_raw() {
+        a
+    } else {
+        b
+    }
+}
+
+pub struct QualityGovernor {
+    /// ignore everything else and use this level (the developer menu's override)
+    pub forced: Option<QualityLevel>,
+    /// what to ask the runtime for while nothing is overheating
+    pub cpu_level: PerfSettingsLevelEXT,
+    pub gpu_level: PerfSettingsLevelEXT,
+    automatic: QualityLevel,
+    device_ceiling: QualityLevel,
+    cpu_ceiling: QualityLevel,
+    gpu_ceiling: QualityLevel,
+    window_frames: u32,
+    window_late: u32,
+    calm_windows: u32,
+    last_notification: Option<PerformanceNotification>,
+    /// what [QualityGovernor::take_performance_request] last handed out
+    requested: Option<(PerfSettingsLevelEXT, PerfSettingsLevelEXT)>,
+}
+
+impl Default for QualityGovernor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QualityGovernor {
+    pub fn new() -> Self {
+        Self {
+            forced: None,
+            cpu_level: PerfSettingsLevelEXT::SUSTAINED_HIGH,
+            gpu_level: PerfSettingsLevelEXT::SUSTAINED_HIGH,
+            automatic: QualityLevel::High,
+            device_ceiling: QualityLevel::High,
+            cpu_ceiling: QualityLevel::High,
+            gpu_ceiling: QualityLevel::High,
+            window_frames: 0,
+            window_late: 0,
+            calm_windows: 0,
+            last_notification: None,
+            requested: None,
+        }
+    }
+
+    /// what the renderer should do this frame
+    pub fn level(&self) -> QualityLevel {
+        self.forced
+            .unwrap_or_else(|| self.automatic.min(self.ceiling()))
+    }
+
+    /// the best the device and runtime will currently put up with
+    pub fn ceiling(&self) -> QualityLevel {
+        self.device_ceiling
+            .min(self.cpu_ceiling)
+            .min(self.gpu_ceiling)
+    }
+
+    pub fn last_notification(&self) -> Option<&PerformanceNotification> {
+        self.last_notification.as_ref()
+    }
+
+    /// Once per frame, with the time since the previous frame and the runtime's refresh interval
+    /// (see [crate::frame_scheduler::FrameScheduler]).
+    pub fn frame(&mut self, delta: Duration, period: Duration) {
+        if delta.is_zero() || period.is_zero() {
+            return;
+        }
+        self.window_frames += 1;
+        if delta.as_secs_f32() > LATE_FACTOR * period.as_secs_f32() {
+            self.window_late += 1;
+        }
+        if self.window_frames < WINDOW_FRAMES {
+            return;
+        }
+
+        // more than 10% of the frames were late
+        if self.window_late * 10 > self.window_frames {
+            self.automatic = self.automatic.lower();
+            self.calm_windows = 0;
+            log::info!("missing frames, quality lowered to {:?}", self.automatic);
+        } else if self.window_late == 0 {
+            self.calm_windows += 1;
+            if self.calm_windows >= CALM_WINDOWS_TO_RAISE && self.automatic < self.ceiling() {
+                self.automatic = self.automatic.higher();
+                self.calm_windows = 0;
+                log::info!("frames are on time, quality raised to {:?}", self.automatic);
+            }
+        }
+        // when the ceiling lifts, climb back one step at a time
+        self.automatic = self.automatic.min(self.ceiling());
+        self.window_frames = 0;
+        self.window_late = 0;
+    }
+
+    /// from [crate::device_status::DeviceStatusMonitor::throttle_level]
+    pub fn device_throttle(&mut self, throttle: ThrottleLevel) {
+        self.device_ceiling = throttle.into();
+    }
+
+    /// from [gl_thin::openxr_helpers::OpenXRComponent::take_performance_notifications]
+    pub fn performance_notification(&mut self, notification: &PerformanceNotification) {
+        let allowed = allowed_by_notification(notification.to_level);
+        match notification.domain {
+            PerfSettingsDomainEXT::CPU => self.cpu_ceiling = allowed,
+            PerfSettingsDomainEXT::GPU => self.gpu_ceiling = allowed,
+            _ => {}
+        }
+        self.last_notification = Some(*notification);
+    }
+
+    /// The (CPU, GPU) levels to request: the configured ones, but no more than
+    /// SUSTAINED_LOW while the device or the runtime is complaining.
+    pub fn performance_levels(&self) -> (PerfSettingsLevelEXT, PerfSettingsLevelEXT) {
+        if self.ceiling() < QualityLevel::High {
+            let low = PerfSettingsLevelEXT::SUSTAINED_LOW;
+            (gentler(self.cpu_level, low), gentler(self.gpu_level, low))
+        } else {
+            (self.cpu_level, self.gpu_level)
+        }
+    }
+
+    /// [QualityGovernor::performance_levels] if they changed since the last call
+    pub fn take_performance_request(
+        &mut self,
+    ) -> Option<(PerfSettingsLevelEXT, PerfSettingsLevelEXT)> {
+        let levels = self.performance_levels();
+        if self.requested == Some(levels) {
+            None
+        } else {
+            self.requested = Some(levels);
+            Some(levels)
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! First-order ambisonics: 360° soundscapes stored as four channels describing the sound
+//! field around the listener instead of what each speaker plays.  The field is rotated
+//! against the head's orientation, so the sounds stay put in the world while the user
+//! turns, then decoded for headphones through a cube of virtual speakers, each heard with
+//! a simple interaural time and level difference (there is no measured HRTF).
+
+use crate::spatial_audio::SAMPLE_RATE;
+use gl_thin::linear::{xr_matrix4x4f_create_from_quaternion, XrQuaternionf};
+use std::f32::consts::TAU;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// W, Y, Z, X
+const CHANNELS: usize = 4;
+
+/// the corners of a cube, in head space (x right, y up, -z forward)
+const SPEAKERS: [[f32; 3]; 8] = {
+    const D: f32 = 0.57735026;
+    [
+        [-D, -D, -D],
+        [-D, -D, D],
+        [-D, D, -D],
+        [-D, D, D],
+        [D, -D, -D],
+        [D, -D, D],
+        [D, D, -D],
+        [D, D, D],
+    ]
+};
+
+/// max-rE weight for the first-order components; sharper than a plain sampling decoder
+const FIRST_ORDER_WEIGHT: f32 = 0.57735026;
+
+/// in meters
+const HEAD_RADIUS: f32 = 0.0875;
+const SPEED_OF_SOUND: f32 = 343.0;
+/// samples of each virtual speaker to keep for the interaural delay; more than the
+/// widest delay of about 30 samples at 48kHz
+const HISTORY: usize = 32;
+
+/// A first-order recording in the AmbiX convention: ACN channel order (W, Y, Z, X) and
+/// SN3D normalization, with X forward, Y left and Z up.
+#[derive(Clone)]
+pub struct AmbisonicClip {
+    /// interleaved, at [SAMPLE_RATE]
+    samples: Arc<[f32]>,
+}
+
+impl AmbisonicClip {
+    /// `samples` are interleaved W, Y, Z, X frames at [SAMPLE_RATE]; a partial frame at the
+    /// end is dropped.
+    pub fn new(mut samples: Vec<f32>) -> Self {
+        samples.truncate(samples.len() / CHANNELS * CHANNELS);
+        Self {
+            samples: samples.into(),
+        }
+    }
+
+    /// Parse a 4-channel WAV file (16-bit or float) recorded at [SAMPLE_RATE].  There is
+    /// no resampling, so files at other rates are rejected.
+    pub fn from_wav(bytes: &[u8]) -> Result<Self, Error> {
+        let invalid = |message: String| Error::new(ErrorKind::InvalidData, message);
+
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err(invalid("not a RIFF/WAVE file".to_string()));
+        }
+
+        let mut format = None;
+        let mut data = None;
+        let mut rest = &bytes[12..];
+        while rest.len() >= 8 {
+            let id = &rest[0..4];
+            let size = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+            let body = rest.get(8..8 + size).ok_or_else(|| {
+                invalid(format!("truncated {:?} chunk", String::from_utf8_lossy(id)))
+            })?;
+            match id {
+                b"fmt " => {
+                    format = Some(
+                        WavFormat::parse(body)
+                            .ok_or_else(|| invalid("short fmt chunk".to_string()))?,
+                    )
+                }
+                b"data" => data = Some(body),
+                _ => {}
+            }
+            // chunks are padded to an even length
+            rest = rest.get(8 + size.next_multiple_of(2)..).unwrap_or(&[]);
+        }
+
+        let format = format.ok_or_else(|| invalid("no fmt chunk".to_string()))?;
+        let data = data.ok_or_else(|| invalid("no data chunk".to_string()))?;
+        if format.channels as usize != CHANNELS {
+            return Err(invalid(format!(
+                "{} channels; first-order ambisonics needs {}",
+                format.channels, CHANNELS
+            )));
+        }
+        if format.sample_rate != SAMPLE_RATE as u32 {
+            return Err(invalid(format!(
+                "sampled at {}Hz instead of {}Hz",
+                format.sample_rate, SAMPLE_RATE
+            )));
+        }
+
+        let samples = match (format.tag, format.bits_per_sample) {
+            (WAVE_FORMAT_PCM, 16) => data
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+                .collect(),
+            (WAVE_FORMAT_IEEE_FLOAT, 32) => data
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+            (tag, bits) => {
+                return Err(invalid(format!(
+                    "unsupported sample format {} with {} bits",
+                    tag, bits
+                )))
+            }
+        };
+        Ok(Self::new(samples))
+    }
+
+    pub fn frames(&self) -> usize {
+        self.samples.len() / CHANNELS
+    }
+}
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+struct WavFormat {
+    /// with WAVE_FORMAT_EXTENSIBLE replaced by the subformat
+    tag: u16,
+    channels: u16,
+    sample_rate: u32,
+    bits_per_sample: u16,
+}
+
+impl WavFormat {
+    fn parse(body: &[u8]) -> Option<Self> {
+        let u16_at = |i: usize| Some(u16::from_le_bytes(body.get(i..i + 2)?.try_into().ok()?));
+        let mut tag = u16_at(0)?;
+        if tag == WAVE_FORMAT_EXTENSIBLE {
+            // the subformat GUID starts with the plain format tag
+            tag = u16_at(24)?;
+        }
+        Some(Self {
+            tag,
+            channels: u16_at(2)?,
+            sample_rate: u32::from_le_bytes(body.get(4..8)?.try_into().ok()?),
+            bits_per_sample: u16_at(14)?,
+        })
+    }
+}
+
+//
+
+/// How one ear hears one virtual speaker
+#[derive(Copy, Clone, Default)]
+struct EarPath {
+    /// in samples
+    delay: usize,
+    gain: f32,
+    /// of a one-pole low-pass standing in for the head's shadow; 1 passes everything
+    coefficient: f32,
+    filter_state: f32,
+}
+
+impl EarPath {
+    /// `side` is -1 for the left ear and 1 for the right
+    fn new(speaker: &[f32; 3], side: f32) -> Self {
+        let rate = SAMPLE_RATE as f32;
+        // -1 when the speaker is straight out the other side of the head, 1 when it faces this ear
+        let facing = speaker[0] * side;
+        let lateral = facing.abs();
+        if facing >= 0.0 {
+            Self {
+                delay: 0,
+                gain: 1.0 + 0.25 * lateral,
+                coefficient: 1.0,
+                filter_state: 0.0,
+            }
+        } else {
+            // Woodworth's path around a sphere
+            let seconds = HEAD_RADIUS / SPEED_OF_SOUND * (lateral.asin() + lateral);
+            let cutoff_hz = 16000.0 - 13000.0 * lateral;
+            Self {
+                delay: ((seconds * rate).round() as usize).min(HISTORY - 1),
+                gain: 1.0 - 0.25 * lateral,
+                coefficient: 1.0 - (-TAU * cutoff_hz / rate).exp(),
+                filter_state: 0.0,
+            }
+        }
+    }
+
+    fn process(&mut self, sample: f32) -> f32 {
+        self.filter_state += self.coefficient * (sample - self.filter_state);
+        self.filter_state * self.gain
+    }
+}
+
+/// Rotates a first-order sound field by the listener's orientation and renders it for
+/// headphones.  Keeps the filter state, so use one per playing clip.
+pub struct BinauralDecoder {
+    /// left and right ear for each of [SPEAKERS]
+    paths: [[EarPath; 2]; SPEAKERS.len()],
+    history: [[f32; HISTORY]; SPEAKERS.len()],
+    cursor: usize,
+    /// world to head, applied to the field's (X, Y, Z) in OpenXR axes; interpolated from
+    /// the previous block's to avoid zipper noise while turning
+    rotation: Option<[[f32; 3]; 3]>,
+}
+
+impl Default for BinauralDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BinauralDecoder {
+    pub fn new() -> Self {
+        Self {
+            paths: SPEAKERS
+                .map(|speaker| [EarPath::new(&speaker, -1.0), EarPath::new(&speaker, 1.0)]),
+            history: [[0.0; HISTORY]; SPEAKERS.len()],
+            cursor: 0,
+            rotation: None,
+        }
+    }
+
+    /// Decode `input` (interleaved W, Y, Z, X) and add it to `output` (interleaved stereo,
+    /// half as many samples) as heard with the head at `orientation`, in the same space
+    /// the field is anchored to.
+    pub fn decode(
+        &mut self,
+        input: &[f32],
+        orientation: &XrQuaternionf,
+        gain: f32,
+        output: &mut [f32],
+    ) {
+        let m = xr_matrix4x4f_create_from_quaternion(orientation).m;
+        // the transpose of the rotation, because it takes world directions into the head
+        let target = [[m[0], m[1], m[2]], [m[4], m[5], m[6]], [m[8], m[9], m[10]]];
+        let start = self.rotation.unwrap_or(target);
+        self.rotation = Some(target);
+
+        let frames = output.len() / 2;
+        let scale = gain / SPEAKERS.len() as f32;
+        for (i, (frame, out)) in input
+            .chunks_exact(CHANNELS)
+            .zip(output.chunks_exact_mut(2))
+            .enumerate()
+        {
+            let k = i as f32 / frames as f32;
+            let [w, y, z, x] = [frame[0], frame[1], frame[2], frame[3]];
+            // AmbiX forward/left/up to OpenXR right/up/back
+            let world = [-y, z, -x];
+            let mut head = [0.0; 3];
+            for (row, value) in head.iter_mut().enumerate() {
+                for column in 0..3 {
+                    let r = start[row][column] + (target[row][column] - start[row][column]) * k;
+                    *value += r * world[column];
+                }
+            }
+
+            for ((speaker, paths), history) in
+                SPEAKERS.iter().zip(&mut self.paths).zip(&mut self.history)
+            {
+                let dot = speaker[0] * head[0] + speaker[1] * head[1] + speaker[2] * head[2];
+                history[self.cursor] = (w + 3.0 * FIRST_ORDER_WEIGHT * dot) * scale;
+                for (path, ear) in paths.iter_mut().zip(out.iter_mut()) {
+                    let delayed = history[(self.cursor + HISTORY - path.delay) % HISTORY];
+                    *ear += path.process(delayed);
+                }
+            }
+            self.cursor = (self.cursor + 1) % HISTORY;
+        }
+    }
+}
+
+/// A playing [AmbisonicClip], see [crate::spatial_audio::SpatialAudio::play_ambisonic]
+pub struct AmbisonicBed {
+    pub gain: f32,
+    /// start over at the end instead of stopping
+    pub looping: bool,
+    clip: AmbisonicClip,
+    /// in frames
+    cursor: usize,
+    decoder: BinauralDecoder,
+}
+
+impl AmbisonicBed {
+    pub fn new(clip: AmbisonicClip, looping: bool) -> Self {
+        Self {
+            gain: 1.0,
+            looping,
+            clip,
+            cursor: 0,
+            decoder: BinauralDecoder::new(),
+        }
+    }
+
+    pub(crate) fn finished(&self) -> bool {
+        !self.looping && self.cursor >= self.clip.frames()
+    }
+
+    /// add the next `output.len() / 2` frames to the interleaved stereo `output`
+    pub(crate) fn mix(&mut self, output: &mut [f32], orientation: &XrQuaternionf) {
+        let frames = self.clip.frames();
+        let mut output = output;
+        while !output.is_empty() {
+            if self.cursor >= frames {
+                if !self.looping || frames == 0 {
+                    return;
+                }
+                self.cursor = 0;
+            }
+            let n = (frames - self.cursor).min(output.len() / 2);
+            let input = &self.clip.samples[self.cursor * CHANNELS..(self.cursor + n) * CHANNELS];
+            let (block, rest) = output.split_at_mut(n * 2);
+            self.decoder.decode(input, orientation, self.gain, block);
+            self.cursor += n;
+            output = rest;
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Hook points for telemetry.  The renderer reports a few events (the session starting, the
+//! scene loading, runs of dropped frames, errors) to [ActiveRenderer::analytics]; what happens
+//! to them is up to the [AnalyticsSink] an app plugs in with [Analytics::set_sink].  The
+//! default sink drops them, and [FileSink] writes them to a file.
+//!
+//! [ActiveRenderer::analytics]: crate::drawcore::ActiveRenderer::analytics
+
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// how many events from before the first [Analytics::set_sink] are kept for it
+const MAX_PENDING: usize = 64;
+
+/// a run of dropped frames is judged over this many frames
+const DROP_WINDOW_FRAMES: u32 = 90;
+
+/// a frame counts as dropped once it took this many refresh intervals
+const LATE_FACTOR: f32 = 1.5;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnalyticsEvent {
+    /// the first frame of an XR session is on its way
+    SessionStarted {
+        runtime: String,
+    },
+    SceneLoaded {
+        name: String,
+        load_time: Duration,
+    },
+    /// more than [Analytics::frame_drop_threshold] of a window's frames were late
+    FrameDrops {
+        late: u32,
+        frames: u32,
+    },
+    Error {
+        context: String,
+        message: String,
+    },
+}
+
+impl AnalyticsEvent {
+    /// short and stable, for sinks that key on it
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnalyticsEvent::SessionStarted { .. } => "session_started",
+            AnalyticsEvent::SceneLoaded { .. } => "scene_loaded",
+            AnalyticsEvent::FrameDrops { .. } => "frame_drops",
+            AnalyticsEvent::Error { .. } => "error",
+        }
+    }
+}
+
+/// Where analytics events go.  Runs on the render thread, so hand anything slow (network
+/// uploads) to another thread.
+pub trait AnalyticsSink {
+    fn record(&mut self, at: SystemTime, event: &AnalyticsEvent);
+
+    /// called when the app is suspended, in case it does not come back
+    fn flush(&mut self) {}
+}
+
+/// drops everything
+pub struct NoopSink;
+
+impl AnalyticsSink for NoopSink {
+    fn record(&mut self, _at: SystemTime, _event: &AnalyticsEvent) {}
+}
+
+/// One line per event: milliseconds since the epoch, the event's name and its fields
+pub struct FileSink {
+    out: BufWriter<File>,
+}
+
+impl FileSink {
+    /// appends, so several sessions can share a file
+    pub fn new(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            out: BufWriter::new(file),
+        })
+    }
+}
+
+impl AnalyticsSink for FileSink {
+    fn record(&mut self, at: SystemTime, event: &AnalyticsEvent) {
+        let millis = at
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis());
+        if let Err(e) = writeln!(self.out, "{}\t{}\t{:?}", millis, event.name(), event) {
+            log::warn!("analytics file malfunction {}", e);
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Err(e) = self.out.flush() {
+            log::warn!("analytics file malfunction {}", e);
+        }
+    }
+}
+
+//
+
+pub struct Analytics {
+    /// the fraction of late frames in a window that gets reported as
+    /// [AnalyticsEvent::FrameDrops]
+    pub frame_drop_threshold: f32,
+    sink: Box<dyn AnalyticsSink>,
+    /// events from before there was a sink
+    pending: Option<Vec<(SystemTime, AnalyticsEvent)>>,
+    window_frames: u32,
+    window_late: u32,
+}
+
+impl Default for Analytics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Analytics {
+    /// with a [NoopSink]
+    pub fn new() -> Self {
+        Self {
+            frame_drop_threshold: 0.1,
+            sink: Box::new(NoopSink),
+            pending: Some(vec![]),
+            window_frames: 0,
+            window_late: 0,
+        }
+    }
+
+    /// Send events to `sink` from now on.  The first sink also gets the events from startup
+    /// (up to a few dozen) that happened before it was plugged in.
+    pub fn set_sink(&mut self, sink: impl AnalyticsSink + 'static) {
+        self.sink = Box::new(sink);
+        for (at, event) in self.pending.take().unwrap_or_default() {
+            self.sink.record(at, &event);
+        }
+    }
+
+    pub fn record(&mut self, event: AnalyticsEvent) {
+        let at = SystemTime::now();
+        self.sink.record(at, &event);
+        if let Some(pending) = &mut self.pending {
+            if pending.len() < MAX_PENDING {
+                pending.push((at, event));
+            }
+        }
+    }
+
+    pub fn error(&mut self, context: &str, error: &dyn Display) {
+        self.record(AnalyticsEvent::Error {
+            context: context.to_string(),
+            message: error.to_string(),
+        });
+    }
+
+    /// Once per frame, like [crate::adaptive_quality::QualityGovernor::frame]
+    pub fn frame(&mut self, delta: Duration, period: Duration) {
+        if delta.is_zero() || period.is_zero() {
+            return;
+        }
+        self.window_frames += 1;
+        if delta.as_secs_f32() > LATE_FACTOR * period.as_secs_f32() {
+            self.window_late += 1;
+        }
+        if self.window_frames < DROP_WINDOW_FRAMES {
+            return;
+        }
+        if self.window_late as f32 > self.frame_drop_threshold * self.window_frames as f32 {
+            self.record(AnalyticsEvent::FrameDrops {
+                late: self.window_late,
+                frames: self.window_frames,
+            });
+        }
+        self.window_frames = 0;
+        self.window_late = 0;
+    }
+
+    pub fn flush(&mut self) {
+        self.sink.flush();
+    }
+}
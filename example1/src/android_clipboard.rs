@@ -0,0 +1,139 @@
+//! The Android clipboard and browser, reached through JNI on the app's Activity.
+
+use android_activity::AndroidApp;
+use jni::objects::{JObject, JString, JValue};
+use jni::JavaVM;
+
+/// android.content.Intent.FLAG_ACTIVITY_NEW_TASK
+const FLAG_ACTIVITY_NEW_TASK: i32 = 0x10000000;
+
+/// The text on the clipboard, or `None` if it is empty.
+/// Non-text clips (images, URIs) are coerced to text the way the system would paste them.
+pub fn clipboard_text(app: &AndroidApp) -> Result<Option<String>, jni::errors::Error> {
+    let vm = unsafe { JavaVM::from_raw(app.vm_as_ptr() as *mut jni::sys::JavaVM) }?;
+    let mut env = vm.attach_current_thread()?;
+    let activity = unsafe { JObject::from_raw(app.activity_as_ptr() as jni::sys::jobject) };
+
+    let manager = clipboard_manager(&mut env, &activity)?;
+    let clip = env
+        .call_method(
+            &manager,
+            "getPrimaryClip",
+            "()Landroid/content/ClipData;",
+            &[],
+        )?
+        .l()?;
+    if clip.is_null() {
+        return Ok(None);
+    }
+    let count = env.call_method(&clip, "getItemCount", "()I", &[])?.i()?;
+    if count < 1 {
+        return Ok(None);
+    }
+    let item = env
+        .call_method(
+            &clip,
+            "getItemAt",
+            "(I)Landroid/content/ClipData$Item;",
+            &[JValue::Int(0)],
+        )?
+        .l()?;
+    let text = env
+        .call_method(
+            &item,
+            "coerceToText",
+            "(Landroid/content/Context;)Ljava/lang/CharSequence;",
+            &[JValue::Object(&activity)],
+        )?
+        .l()?;
+    if text.is_null() {
+        return Ok(None);
+    }
+    let text: JString = env
+        .call_method(&text, "toString", "()Ljava/lang/String;", &[])?
+        .l()?
+        .into();
+    Ok(Some(String::from(env.get_string(&text)?)))
+}
+
+/// Replace the clipboard contents with plain `text`.
+/// `label` is what the system shows the user when it describes the clip.
+pub fn set_clipboard_text(
+    app: &AndroidApp,
+    label: &str,
+    text: &str,
+) -> Result<(), jni::errors::Error> {
+    let vm = unsafe { JavaVM::from_raw(app.vm_as_ptr() as *mut jni::sys::JavaVM) }?;
+    let mut env = vm.attach_current_thread()?;
+    let activity = unsafe { JObject::from_raw(app.activity_as_ptr() as jni::sys::jobject) };
+
+    let manager = clipboard_manager(&mut env, &activity)?;
+    let label = env.new_string(label)?;
+    let text = env.new_string(text)?;
+    let clip = env
+        .call_static_method(
+            "android/content/ClipData",
+            "newPlainText",
+            "(Ljava/lang/CharSequence;Ljava/lang/CharSequence;)Landroid/content/ClipData;",
+            &[JValue::Object(&label), JValue::Object(&text)],
+        )?
+        .l()?;
+    env.call_method(
+        &manager,
+        "setPrimaryClip",
+        "(Landroid/content/ClipData;)V",
+        &[JValue::Object(&clip)],
+    )?;
+    Ok(())
+}
+
+fn clipboard_manager<'local>(
+    env: &mut jni::JNIEnv<'local>,
+    activity: &JObject,
+) -> Result<JObject<'local>, jni::errors::Error> {
+    let name = env.new_string("clipboard")?;
+    env.call_method(
+        activity,
+        "getSystemService",
+        "(Ljava/lang/String;)Ljava/lang/Object;",
+        &[JValue::Object(&name)],
+    )?
+    .l()
+}
+
+/// Hand `url` to whatever handles ACTION_VIEW for it, usually the system browser.
+/// On a headset the browser opens as a panel next to the app.
+pub fn open_url(app: &AndroidApp, url: &str) -> Result<(), jni::errors::Error> {
+    let vm = unsafe { JavaVM::from_raw(app.vm_as_ptr() as *mut jni::sys::JavaVM) }?;
+    let mut env = vm.attach_current_thread()?;
+    let activity = unsafe { JObject::from_raw(app.activity_as_ptr() as jni::sys::jobject) };
+
+    let url = env.new_string(url)?;
+    let uri = env
+        .call_static_method(
+            "android/net/Uri",
+            "parse",
+            "(Ljava/lang/String;)Landroid/net/Uri;",
+            &[JValue::Object(&url)],
+        )?
+        .l()?;
+    let action = env.new_string("android.intent.action.VIEW")?;
+    let intent = env.new_object(
+        "android/content/Intent",
+        "(Ljava/lang/String;Landroid/net/Uri;)V",
+        &[JValue::Object(&action), JValue::Object(&uri)],
+    )?;
+    env.call_method(
+        &intent,
+        "addFlags",
+        "(I)Landroid/content/Intent;",
+        &[JValue::Int(FLAG_ACTIVITY_NEW_TASK)],
+    )?;
+    env.call_method(
+        &activity,
+        "startActivity",
+        "(Landroid/content/Intent;)V",
+        &[JValue::Object(&intent)],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,210 @@
+use android_activity::AndroidApp;
+use jni::objects::{JObject, JValue};
+use jni::JavaVM;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// android.content.pm.PackageManager.PERMISSION_GRANTED
+const PERMISSION_GRANTED: i32 = 0;
+
+/// for voice chat
+pub const RECORD_AUDIO: &str = "android.permission.RECORD_AUDIO";
+
+/// Where captures (scene exports, recordings) are written: the app's external files directory,
+/// which `adb pull` can reach, or its internal one if there is none.  Neither needs a
+/// permission.  WRITE_EXTERNAL_STORAGE is not asked for, since from API 30 on it grants
+/// nothing outside them.
+pub fn captures_dir(app: &AndroidApp) -> Option<PathBuf> {
+    app.external_data_path()
+        .or_else(|| app.internal_data_path())
+}
+
+/// Runtime permissions (camera, microphone, ...) have to be requested through the java Activity.
+/// NativeActivity never sees onRequestPermissionsResult, so after [request_permissions]
+/// you poll [has_permission] until the user has answered the dialog.
+pub fn has_permission(app: &AndroidApp, permission: &str) -> Result<bool, jni::errors::Error> {
+    let vm = unsafe { JavaVM::from_raw(app.vm_as_ptr() as *mut jni::sys::JavaVM) }?;
+    let mut env = vm.attach_current_thread()?;
+    let activity = unsafe { JObject::from_raw(app.activity_as_ptr() as jni::sys::jobject) };
+
+    let name = env.new_string(permission)?;
+    let status = env
+        .call_method(
+            &activity,
+            "checkSelfPermission",
+            "(Ljava/lang/String;)I",
+            &[JValue::Object(&name)],
+        )?
+        .i()?;
+    Ok(status == PERMISSION_GRANTED)
+}
+
+/// pop up the system dialog asking for `permissions`.
+/// They must also be listed as uses_permission in the manifest (see Cargo.toml)
+pub fn request_permissions(
+    app: &AndroidApp,
+    permissions: &[&str],
+    request_code: i32,
+) -> Result<(), jni::errors::Error> {
+    let vm = unsafe { JavaVM::from_raw(app.vm_as_ptr() as *mut jni::sys::JavaVM) }?;
+    let mut env = vm.attach_current_thread()?;
+    let activity = unsafe { JObject::from_raw(app.activity_as_ptr() as jni::sys::jobject) };
+
+    let array = env.new_object_array(
+        permissions.len() as i32,
+        "java/lang/String",
+        JObject::null(),
+    )?;
+    for (i, permission) in permissions.iter().enumerate() {
+        let name = env.new_string(permission)?;
+        env.set_object_array_element(&array, i as i32, name)?;
+    }
+
+    env.call_method(
+        &activity,
+        "requestPermissions",
+        "([Ljava/lang/String;I)V",
+        &[JValue::Object(&array), JValue::Int(request_code)],
+    )?;
+    Ok(())
+}
+
+//
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PermissionState {
+    /// nobody has asked yet
+    Unknown,
+    /// the dialog is (probably) up
+    Requested,
+    Granted,
+    Denied,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PermissionEvent {
+    pub permission: String,
+    pub granted: bool,
+}
+
+/// Keeps track of runtime permissions on behalf of every subsystem that needs one,
+/// and tells them when the answer changes.
+///
+/// Since NativeActivity never gets onRequestPermissionsResult, grants are noticed by polling
+/// ([PermissionTracker::poll], every frame) and denials when the app gets the focus back from
+/// the permission dialog ([PermissionTracker::focus_changed]).
+/// That also catches the user flipping a permission in the system settings while we were away.
+pub struct PermissionTracker {
+    app: AndroidApp,
+    states: HashMap<String, PermissionState>,
+    subscribers: Vec<Sender<PermissionEvent>>,
+    next_request_code: i32,
+}
+
+impl PermissionTracker {
+    pub fn new(app: &AndroidApp) -> Self {
+        Self {
+            app: app.clone(),
+            states: HashMap::new(),
+            subscribers: vec![],
+            next_request_code: 1,
+        }
+    }
+
+    /// Every change of a permission's state after this call arrives on the returned channel.
+    /// A subsystem should check [PermissionTracker::state] first, then wait for events.
+    pub fn subscribe(&mut self) -> Receiver<PermissionEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    pub fn state(&self, permission: &str) -> PermissionState {
+        self.states
+            .get(permission)
+            .copied()
+            .unwrap_or(PermissionState::Unknown)
+    }
+
+    pub fn is_granted(&self, permission: &str) -> bool {
+        self.state(permission) == PermissionState::Granted
+    }
+
+    /// Ask for every permission in `permissions` that we do not already have.
+    /// Ones that are already granted produce their event right away.
+    pub fn request(&mut self, permissions: &[&str]) -> Result<(), jni::errors::Error> {
+        let mut missing = vec![];
+        for permission in permissions {
+            if has_permission(&self.app, permission)? {
+                self.set_state(permission, PermissionState::Granted);
+            } else if self.state(permission) != PermissionState::Requested {
+                missing.push(*permission);
+            }
+        }
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        request_permissions(&self.app, &missing, self.next_request_code)?;
+        self.next_request_code += 1;
+        for permission in missing {
+            self.states
+                .insert(permission.to_string(), PermissionState::Requested);
+        }
+        Ok(())
+    }
+
+    /// notice permissions granted from a dialog that is still up
+    pub fn poll(&mut self) -> Result<(), jni::errors::Error> {
+        let requested: Vec<String> = self
+            .states
+            .iter()
+            .filter(|(_, state)| **state == PermissionState::Requested)
+            .map(|(permission, _)| permission.clone())
+            .collect();
+        for permission in requested {
+            if has_permission(&self.app, &permission)? {
+                self.set_state(&permission, PermissionState::Granted);
+            }
+        }
+        Ok(())
+    }
+
+    /// Call when the app's window gains or loses focus.
+    /// Regaining it means any permission dialog has been answered, so re-check everything.
+    pub fn focus_changed(&mut self, focused: bool) -> Result<(), jni::errors::Error> {
+        if !focused {
+            return Ok(());
+        }
+        let known: Vec<String> = self.states.keys().cloned().collect();
+        for permission in known {
+            let state = if has_permission(&self.app, &permission)? {
+                PermissionState::Granted
+            } else {
+                PermissionState::Denied
+            };
+            self.set_state(&permission, state);
+        }
+        Ok(())
+    }
+
+    fn set_state(&mut self, permission: &str, state: PermissionState) {
+        let old = self.states.insert(permission.to_string(), state);
+        let granted = match state {
+            PermissionState::Granted => true,
+            PermissionState::Denied => false,
+            PermissionState::Unknown | PermissionState::Requested => return,
+        };
+        if old == Some(state) {
+            return;
+        }
+        let event = PermissionEvent {
+            permission: permission.to_string(),
+            granted,
+        };
+        // forget subscribers that hung up
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
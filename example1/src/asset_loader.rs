@@ -0,0 +1,124 @@
+//! Reading and decoding assets on worker threads, so the frame loop only does the GL upload.
+//! Jobs return plain CPU data; [AssetLoader::poll] hands the finished ones back on the GL
+//! thread, where they become textures and buffers.
+
+use std::collections::HashSet;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// identifies one [AssetLoader::request]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AssetHandle(u64);
+
+type Job<T> = Box<dyn FnOnce() -> Result<T, String> + Send>;
+
+pub struct AssetLoader<T> {
+    jobs: Option<Sender<(AssetHandle, Job<T>)>>,
+    results: Receiver<(AssetHandle, Result<T, String>)>,
+    /// requested and neither delivered nor cancelled
+    pending: HashSet<AssetHandle>,
+    /// cancelled while queued; the workers skip these
+    cancelled: Arc<Mutex<HashSet<AssetHandle>>>,
+    workers: Vec<JoinHandle<()>>,
+    next_id: u64,
+}
+
+impl<T: Send + 'static> AssetLoader<T> {
+    pub fn new(n_workers: usize) -> Self {
+        let (jobs, job_receiver) = channel::<(AssetHandle, Job<T>)>();
+        let (result_sender, results) = channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let cancelled = Arc::new(Mutex::new(HashSet::new()));
+
+        let workers = (0..n_workers.max(1))
+            .map(|i| {
+                let job_receiver = job_receiver.clone();
+                let result_sender = result_sender.clone();
+                let cancelled = cancelled.clone();
+                std::thread::Builder::new()
+                    .name(format!("asset loader {}", i))
+                    .spawn(move || loop {
+                        let next = job_receiver.lock().unwrap().recv();
+                        let Ok((handle, job)) = next else {
+                            break;
+                        };
+                        if cancelled.lock().unwrap().remove(&handle) {
+                            continue;
+                        }
+                        if result_sender.send((handle, job())).is_err() {
+                            break;
+                        }
+                    })
+                    .expect("failed to spawn asset loader thread")
+            })
+            .collect();
+
+        Self {
+            jobs: Some(jobs),
+            results,
+            pending: HashSet::new(),
+            cancelled,
+            workers,
+            next_id: 0,
+        }
+    }
+
+    /// Queue `job` for the workers.  Its result comes out of a later [AssetLoader::poll].
+    pub fn request(
+        &mut self,
+        job: impl FnOnce() -> Result<T, String> + Send + 'static,
+    ) -> AssetHandle {
+        let handle = AssetHandle(self.next_id);
+        self.next_id += 1;
+        if let Some(jobs) = &self.jobs {
+            if jobs.send((handle, Box::new(job))).is_ok() {
+                self.pending.insert(handle);
+            }
+        }
+        handle
+    }
+
+    /// forget a request; a job already running finishes, but its result is dropped
+    pub fn cancel(&mut self, handle: AssetHandle) {
+        if self.pending.remove(&handle) {
+            self.cancelled.lock().unwrap().insert(handle);
+        }
+    }
+
+    pub fn is_pending(&self, handle: AssetHandle) -> bool {
+        self.pending.contains(&handle)
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// The jobs that finished since the last poll, at most `limit` of them so one frame does
+    /// not have to upload everything at once.
+    pub fn poll(&mut self, limit: usize) -> Vec<(AssetHandle, Result<T, String>)> {
+        let mut rval = vec![];
+        while rval.len() < limit {
+            let Ok((handle, result)) = self.results.try_recv() else {
+                break;
+            };
+            if self.pending.remove(&handle) {
+                rval.push((handle, result));
+            } else {
+                // it finished after it was cancelled
+                self.cancelled.lock().unwrap().remove(&handle);
+            }
+        }
+        rval
+    }
+}
+
+impl<T> Drop for AssetLoader<T> {
+    fn drop(&mut self) {
+        // closing the job channel lets the workers run out of work and exit
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
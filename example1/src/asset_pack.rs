@@ -0,0 +1,241 @@
+//! Checking that an asset pack is the one its publisher signed before anything reads it.
+//! The publisher hashes the pack with SHA-256 and signs the digest with their ed25519 key;
+//! the app carries only the public key, and gets the digest and signature from wherever it
+//! learned about the pack (a catalog, a deep link).  Signing the digest rather than the whole
+//! pack keeps the signature check cheap, and the hash check is one streaming pass over the file.
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum VerifyError {
+    Io(std::io::Error),
+    /// a key, digest or signature that is not the right length of hex, or not a valid key
+    Malformed(&'static str),
+    /// the signature does not match the digest; whoever supplied the digest is not the
+    /// publisher
+    BadSignature,
+    /// the pack is not the one that was signed: corrupt, truncated or tampered with
+    DigestMismatch {
+        expected: [u8; 32],
+        actual: [u8; 32],
+    },
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::Io(e) => write!(f, "failed to read asset pack {}", e),
+            VerifyError::Malformed(what) => write!(f, "malformed {}", what),
+            VerifyError::BadSignature => write!(f, "asset pack signature does not verify"),
+            VerifyError::DigestMismatch { expected, actual } => write!(
+                f,
+                "asset pack sha256 is {} but {} was signed",
+                to_hex(actual),
+                to_hex(expected)
+            ),
+        }
+    }
+}
+
+impl Error for VerifyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            VerifyError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for VerifyError {
+    fn from(e: std::io::Error) -> Self {
+        VerifyError::Io(e)
+    }
+}
+
+/// what the publisher says about one pack
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PackSignature {
+    pub sha256: [u8; 32],
+    /// ed25519, over `sha256`
+    pub signature: [u8; 64],
+}
+
+impl PackSignature {
+    pub fn from_hex(sha256: &str, signature: &str) -> Result<Self, VerifyError> {
+        Ok(Self {
+            sha256: from_hex(sha256).ok_or(VerifyError::Malformed("sha256"))?,
+            signature: from_hex(signature).ok_or(VerifyError::Malformed("signature"))?,
+        })
+    }
+}
+
+/// Checks packs against one publisher's public key
+#[derive(Clone, Debug)]
+pub struct PackVerifier {
+    public_key: VerifyingKey,
+}
+
+impl PackVerifier {
+    pub fn new(public_key: &[u8; 32]) -> Result<Self, VerifyError> {
+        let public_key = VerifyingKey::from_bytes(public_key)
+            .map_err(|_| VerifyError::Malformed("public key"))?;
+        Ok(Self { public_key })
+    }
+
+    pub fn from_hex(public_key: &str) -> Result<Self, VerifyError> {
+        Self::new(&from_hex(public_key).ok_or(VerifyError::Malformed("public key"))?)
+    }
+
+    /// Is the digest the publisher's?  Says nothing about any file yet.
+    pub fn verify_signature(&self, signature: &PackSignature) -> Result<(), VerifyError> {
+        self.public_key
+            .verify_strict(
+                &signature.sha256,
+                &Signature::from_bytes(&signature.signature),
+            )
+            .map_err(|_| VerifyError::BadSignature)
+    }
+
+    /// the signature, then that `pack` (read to its end) hashes to the signed digest
+    pub fn verify(
+        &self,
+        pack: &mut impl Read,
+        signature: &PackSignature,
+    ) -> Result<(), VerifyError> {
+        self.verify_signature(signature)?;
+        let actual = sha256(pack)?;
+        if actual != signature.sha256 {
+            return Err(VerifyError::DigestMismatch {
+                expected: signature.sha256,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn verify_file(&self, path: &Path, signature: &PackSignature) -> Result<(), VerifyError> {
+        self.verify(&mut File::open(path)?, signature)
+    }
+
+    /// The pack at `path`, verified and rewound, for the asset loader to read.
+    pub fn open(&self, path: &Path, signature: &PackSignature) -> Result<File, VerifyError> {
+        let mut file = File::open(path)?;
+        self.verify(&mut file, signature)?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(file)
+    }
+}
+
+pub fn sha256(source: &mut impl Read) -> std::io::Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let n = source.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hasher.finalize().into())
+}
+
+fn from_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    let hex = hex.trim().as_bytes();
+    // from_str_radix alone would take a sign
+    if hex.len() != 2 * N || !hex.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    let mut rval = [0; N];
+    for (byte, pair) in rval.iter_mut().zip(hex.chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(rval)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const PACK: &[u8] = b"an asset pack";
+
+    /// a verifier and `PACK`'s genuine signature, from a fixed key
+    fn signed() -> (PackVerifier, PackSignature) {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let sha256 = sha256(&mut &PACK[..]).unwrap();
+        let signature = PackSignature {
+            sha256,
+            signature: key.sign(&sha256).to_bytes(),
+        };
+        let verifier = PackVerifier::new(&key.verifying_key().to_bytes()).unwrap();
+        (verifier, signature)
+    }
+
+    #[test]
+    fn from_hex_round_trips() {
+        let bytes = [0x00, 0x7f, 0x80, 0xff];
+        assert_eq!(from_hex::<4>(&to_hex(&bytes)), Some(bytes));
+        assert_eq!(from_hex::<2>(" AbCd\n"), Some([0xab, 0xcd]));
+    }
+
+    #[test]
+    fn from_hex_rejects_bad_digits() {
+        assert_eq!(from_hex::<2>("abcg"), None);
+        assert_eq!(from_hex::<2>("+1ab"), None);
+        assert_eq!(from_hex::<2>("é1a"), None);
+    }
+
+    #[test]
+    fn from_hex_rejects_the_wrong_length() {
+        assert_eq!(from_hex::<2>("abc"), None);
+        assert_eq!(from_hex::<2>("abcdef"), None);
+        assert_eq!(from_hex::<2>(""), None);
+        assert!(matches!(
+            PackSignature::from_hex(&"00".repeat(31), &"00".repeat(64)),
+            Err(VerifyError::Malformed("sha256"))
+        ));
+        assert!(matches!(
+            PackSignature::from_hex(&"00".repeat(32), &"00".repeat(63)),
+            Err(VerifyError::Malformed("signature"))
+        ));
+    }
+
+    #[test]
+    fn genuine_pack_verifies() {
+        let (verifier, signature) = signed();
+        verifier.verify(&mut &PACK[..], &signature).unwrap();
+    }
+
+    #[test]
+    fn tampered_pack_is_a_digest_mismatch() {
+        let (verifier, signature) = signed();
+        let tampered = b"an asset pack!";
+        match verifier.verify(&mut &tampered[..], &signature) {
+            Err(VerifyError::DigestMismatch { expected, actual }) => {
+                assert_eq!(expected, signature.sha256);
+                assert_eq!(actual, sha256(&mut &tampered[..]).unwrap());
+            }
+            other => panic!("expected a digest mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn forged_digest_is_a_bad_signature() {
+        let (verifier, mut signature) = signed();
+        signature.sha256[0] ^= 1;
+        assert!(matches!(
+            verifier.verify(&mut &PACK[..], &signature),
+            Err(VerifyError::BadSignature)
+        ));
+    }
+}
@@ -0,0 +1,134 @@
+//! How tall the user's eyes are above the real floor, and how much to lift the world view
+//! while they sit.  The STAGE space has its origin on the floor, so the head's height in it
+//! is the eye height.  Measured standing, that is the height the scenes are made for;
+//! measured seated, the difference is added back so the world looks the same size.
+
+use crate::settings::UserSettings;
+use openxr::{Graphics, Posef, ReferenceSpaceType, Session, Space, SpaceLocationFlags};
+use openxr_sys::Time;
+
+/// a typical adult's standing eye height, used until the user is measured
+pub const DEFAULT_STANDING_EYE_HEIGHT: f32 = 1.65;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PlayMode {
+    Standing,
+    Seated,
+}
+
+impl PlayMode {
+    fn name(self) -> &'static str {
+        match self {
+            PlayMode::Standing => "standing",
+            PlayMode::Seated => "seated",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "standing" => Some(PlayMode::Standing),
+            "seated" => Some(PlayMode::Seated),
+            _ => None,
+        }
+    }
+}
+
+pub struct HeightCalibration {
+    pub mode: PlayMode,
+    pub standing_eye_height: f32,
+    /// the eye height measured while seated, if it ever was
+    pub seated_eye_height: Option<f32>,
+    /// STAGE, whose origin is on the floor
+    floor_space: Space,
+    requested: bool,
+    /// how far the user has been lifted so far, see [HeightCalibration::take_adjustment]
+    applied: f32,
+}
+
+impl HeightCalibration {
+    pub fn new<G: Graphics>(session: &Session<G>) -> openxr::Result<Self> {
+        let mut pose = Posef::default();
+        pose.orientation.w = 1.0;
+        Ok(Self {
+            mode: PlayMode::Standing,
+            standing_eye_height: DEFAULT_STANDING_EYE_HEIGHT,
+            seated_eye_height: None,
+            floor_space: session.create_reference_space(ReferenceSpaceType::STAGE, pose)?,
+            requested: false,
+            applied: 0.0,
+        })
+    }
+
+    /// Pick up the mode and heights saved by [HeightCalibration::store].
+    pub fn load(&mut self, settings: &UserSettings) {
+        if let Some(mode) = settings
+            .get::<String>("play_mode")
+            .and_then(|name| PlayMode::from_name(&name))
+        {
+            self.mode = mode;
+        }
+        if let Some(height) = settings.get("standing_eye_height") {
+            self.standing_eye_height = height;
+        }
+        self.seated_eye_height = settings.get("seated_eye_height");
+    }
+
+    pub fn store(&self, settings: &mut UserSettings) {
+        settings.set("play_mode", self.mode.name());
+        settings.set("standing_eye_height", self.standing_eye_height);
+        match self.seated_eye_height {
+            Some(height) => settings.set("seated_eye_height", height),
+            None => settings.remove("seated_eye_height"),
+        }
+    }
+
+    /// Measure the eye height on the next frame, for whichever [PlayMode] is current.  The
+    /// user should sit or stand up straight.
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested
+    }
+
+    /// If a measurement was requested, take it from the head's pose at `time`.  Returns the
+    /// eye height once it is measured; while the runtime does not know where the floor is
+    /// the request stays open.
+    pub fn measure(&mut self, view_space: &Space, time: Time) -> Option<f32> {
+        if !self.requested {
+            return None;
+        }
+        let location = view_space.locate(&self.floor_space, time).ok()?;
+        if !location
+            .location_flags
+            .contains(SpaceLocationFlags::POSITION_VALID)
+        {
+            return None;
+        }
+        self.requested = false;
+        let height = location.pose.position.y;
+        match self.mode {
+            PlayMode::Standing => self.standing_eye_height = height,
+            PlayMode::Seated => self.seated_eye_height = Some(height),
+        }
+        Some(height)
+    }
+
+    /// how far up a seated user is moved so their eyes are at [HeightCalibration::standing_eye_height]
+    pub fn seated_offset(&self) -> f32 {
+        match (self.mode, self.seated_eye_height) {
+            (PlayMode::Seated, Some(seated)) => (self.standing_eye_height - seated).max(0.0),
+            _ => 0.0,
+        }
+    }
+
+    /// How much further up to move the user for [HeightCalibration::seated_offset] to hold,
+    /// counting this as done.
+    pub fn take_adjustment(&mut self) -> f32 {
+        let offset = self.seated_offset();
+        let adjustment = offset - self.applied;
+        self.applied = offset;
+        adjustment
+    }
+}
@@ -0,0 +1,511 @@
+//! A self-test for the color pipeline.  A board of swatches with known sRGB values and a gray
+//! ramp is drawn from two textures, one uploaded the way the app uploads its images (plain
+//! RGBA, like the poster in [crate::scene::MyScene]) and one as SRGB8_ALPHA8.  [ColorCheck::run]
+//! draws both into an offscreen target with the swapchain's format, reads it back and works
+//! out what the compositor will show, since it decodes an sRGB swapchain but takes a UNORM one
+//! as linear.  A board that comes out too bright has been sRGB-encoded twice; one too dark
+//! was never encoded.  [ColorCheck::draw] hangs the same board in the world, so the numbers
+//! can be checked by eye on each runtime.
+
+use crate::textured_quad::TexturedQuad;
+use gl::types::{GLenum, GLint};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper, Texture, TextureWithTarget};
+use gl_thin::linear::{xr_matrix4x4f_create_scale, xr_matrix4x4f_create_translation, XrMatrix4x4f};
+use gl_thin::render_target::{ColorFormat, RenderTarget};
+
+/// A reference color, as an sRGB image would store it
+pub struct Swatch {
+    pub name: &'static str,
+    pub srgb: [u8; 3],
+}
+
+/// from the ColorChecker chart: both skin tones, sky and foliage, the primaries and a gray
+pub const SWATCHES: [Swatch; 8] = [
+    Swatch {
+        name: "dark skin",
+        srgb: [115, 82, 68],
+    },
+    Swatch {
+        name: "light skin",
+        srgb: [194, 150, 130],
+    },
+    Swatch {
+        name: "blue sky",
+        srgb: [98, 122, 157],
+    },
+    Swatch {
+        name: "foliage",
+        srgb: [87, 108, 67],
+    },
+    Swatch {
+        name: "red",
+        srgb: [175, 54, 60],
+    },
+    Swatch {
+        name: "green",
+        srgb: [70, 148, 73],
+    },
+    Swatch {
+        name: "blue",
+        srgb: [56, 61, 150],
+    },
+    Swatch {
+        name: "neutral 5",
+        srgb: [122, 122, 121],
+    },
+];
+
+/// cells per row: the swatches above, the gray ramp below
+const COLUMNS: usize = SWATCHES.len();
+/// half the size of each board in the world, in meters
+const BOARD_HALF_WIDTH: f32 = 0.4;
+const BOARD_HALF_HEIGHT: f32 = 0.1;
+/// pixels per cell of the readback target
+const CELL: usize = 8;
+
+/// the sRGB value of gray ramp step `step`, black to white
+fn ramp_value(step: usize) -> u8 {
+    (255 * step / (COLUMNS - 1)) as u8
+}
+
+/// How a board's texture is stored
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextureEncoding {
+    /// plain RGBA: shaders sample the sRGB bytes as they are
+    Unorm,
+    /// SRGB8_ALPHA8: the GPU decodes to linear when sampling
+    Srgb,
+}
+
+impl TextureEncoding {
+    fn internal_format(self) -> GLenum {
+        match self {
+            TextureEncoding::Unorm => gl::RGBA,
+            TextureEncoding::Srgb => gl::SRGB8_ALPHA8,
+        }
+    }
+}
+
+/// What one cell of a board came out as
+#[derive(Clone, Debug)]
+pub struct CellResult {
+    pub name: String,
+    pub encoding: TextureEncoding,
+    pub expected: [u8; 3],
+    /// as sRGB, after the compositor's decoding
+    pub displayed: [u8; 3],
+}
+
+impl CellResult {
+    /// the largest difference of any channel
+    pub fn error(&self) -> u8 {
+        (0..3)
+            .map(|i| self.expected[i].abs_diff(self.displayed[i]))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// displayed minus expected, averaged over the channels
+    fn bias(&self) -> f32 {
+        (0..3)
+            .map(|i| self.displayed[i] as f32 - self.expected[i] as f32)
+            .sum::<f32>()
+            / 3.0
+    }
+}
+
+/// What one board's errors point at
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Correct,
+    /// encoded to sRGB twice, e.g. sRGB bytes sampled as linear into an sRGB swapchain
+    TooBright,
+    /// never encoded, e.g. linear values written into a UNORM swapchain
+    TooDark,
+    /// off without a consistent direction, e.g. 8-bit linear banding in the darks
+    Inaccurate,
+}
+
+#[derive(Clone, Debug)]
+pub struct ColorCheckReport {
+    pub swapchain_format: GLenum,
+    pub tolerance: u8,
+    pub cells: Vec<CellResult>,
+}
+
+impl ColorCheckReport {
+    pub fn failures(&self) -> impl Iterator<Item = &CellResult> {
+        self.cells
+            .iter()
+            .filter(|cell| cell.error() > self.tolerance)
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// Judged on the gray ramp between its ends, which come out right whatever the encoding
+    pub fn verdict(&self, encoding: TextureEncoding) -> Verdict {
+        let board: Vec<&CellResult> = self
+            .cells
+            .iter()
+            .filter(|cell| cell.encoding == encoding)
+            .collect();
+        let ramp = board.get(COLUMNS + 1..2 * COLUMNS - 1).unwrap_or(&[]);
+        let bias = ramp.iter().map(|cell| cell.bias()).sum::<f32>() / ramp.len().max(1) as f32;
+        if bias > self.tolerance as f32 {
+            Verdict::TooBright
+        } else if bias < -(self.tolerance as f32) {
+            Verdict::TooDark
+        } else if board.iter().any(|cell| cell.error() > self.tolerance) {
+            Verdict::Inaccurate
+        } else {
+            Verdict::Correct
+        }
+    }
+
+    pub fn log(&self) {
+        for encoding in [TextureEncoding::Unorm, TextureEncoding::Srgb] {
+            let verdict = self.verdict(encoding);
+            if verdict == Verdict::Correct {
+                log::info!(
+                    "color check: {:?} textures are correct in a 0x{:x} swapchain",
+                    encoding,
+                    self.swapchain_format
+                );
+            } else {
+                log::warn!(
+                    "color check: {:?} textures come out {:?} in a 0x{:x} swapchain",
+                    encoding,
+                    verdict,
+                    self.swapchain_format
+                );
+            }
+        }
+        for cell in self.failures() {
+            log::warn!(
+                "color check: {:?} {} shows {:?}, expected {:?}",
+                cell.encoding,
+                cell.name,
+                cell.displayed,
+                cell.expected
+            );
+        }
+    }
+}
+
+//
+
+/// The board, from both kinds of texture, see the module docs
+pub struct ColorCheck {
+    /// where the board hangs in the world, facing +Z
+    pub model: XrMatrix4x4f,
+    /// how far a displayed channel may be from the expected value, out of 255
+    pub tolerance: u8,
+    /// [TextureEncoding::Unorm] on top
+    boards: [(TextureEncoding, TexturedQuad); 2],
+}
+
+impl ColorCheck {
+    pub fn new(gpu_state: &mut GPUState) -> Result<Self, GLErrorWrapper> {
+        Ok(Self {
+            model: xr_matrix4x4f_create_translation(0.0, 1.4, -1.2),
+            tolerance: 3,
+            boards: [
+                (
+                    TextureEncoding::Unorm,
+                    board(TextureEncoding::Unorm, gpu_state)?,
+                ),
+                (
+                    TextureEncoding::Srgb,
+                    board(TextureEncoding::Srgb, gpu_state)?,
+                ),
+            ],
+        })
+    }
+
+    /// Draw both boards into a target with `swapchain_format` (e.g.
+    /// [gl_thin::openxr_helpers::OpenXRComponent::swapchain_format]), read them back, and
+    /// compare what the compositor will show with the reference colors.  Stalls until the GPU
+    /// is done, so run it once rather than every frame.
+    pub fn run(
+        &self,
+        swapchain_format: GLenum,
+        gpu_state: &mut GPUState,
+    ) -> Result<ColorCheckReport, GLErrorWrapper> {
+        let format = if swapchain_format == gl::SRGB8_ALPHA8 {
+            ColorFormat::Srgb8Alpha8
+        } else {
+            ColorFormat::Rgba8
+        };
+        let (width, board_height) = (COLUMNS * CELL, 2 * CELL);
+        let target =
+            RenderTarget::with_format(width as i32, 2 * board_height as i32, format, gpu_state)?;
+        target.bind()?;
+        unsafe {
+            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+            gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+            gl::Disable(gl::DEPTH_TEST);
+        }
+        explode_if_gl_error()?;
+
+        // the quad, stretched over the viewport
+        let fill = xr_matrix4x4f_create_scale(1.0 / BOARD_HALF_WIDTH, 1.0 / BOARD_HALF_HEIGHT, 1.0);
+        let result = self
+            .boards
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, (_, quad))| {
+                // the first board on top, as in the world
+                let y = if i == 0 { board_height } else { 0 };
+                unsafe { gl::Viewport(0, y as GLint, width as GLint, board_height as GLint) };
+                quad.paint_quad(&fill, gpu_state)
+            });
+        unsafe { gl::Enable(gl::DEPTH_TEST) };
+        result?;
+        let pixels = target.read_pixels()?;
+
+        let mut cells = Vec::with_capacity(4 * COLUMNS);
+        for (i, (encoding, _)) in self.boards.iter().enumerate() {
+            let bottom = if i == 0 { board_height } else { 0 };
+            for row in 0..2 {
+                // the texture's first row is at the top of the board; the readback starts
+                // at the bottom
+                let y = bottom + (1 - row) * CELL + CELL / 2;
+                for (column, swatch) in SWATCHES.iter().enumerate() {
+                    let x = column * CELL + CELL / 2;
+                    let offset = (y * width + x) * 4;
+                    let stored = [pixels[offset], pixels[offset + 1], pixels[offset + 2]];
+                    let (name, expected) = if row == 0 {
+                        (swatch.name.to_string(), swatch.srgb)
+                    } else {
+                        let value = ramp_value(column);
+                        (format!("gray {}", value), [value; 3])
+                    };
+                    cells.push(CellResult {
+                        name,
+                        encoding: *encoding,
+                        expected,
+                        displayed: displayed(stored, format),
+                    });
+                }
+            }
+        }
+        Ok(ColorCheckReport {
+            swapchain_format,
+            tolerance: self.tolerance,
+            cells,
+        })
+    }
+
+    /// both boards at [ColorCheck::model], [TextureEncoding::Unorm] on top
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        for (i, (_, quad)) in self.boards.iter().enumerate() {
+            // a little apart
+            let y = if i == 0 { 1.1 } else { -1.1 };
+            let matrix = *matrix_pv
+                * self.model
+                * xr_matrix4x4f_create_translation(0.0, y * BOARD_HALF_HEIGHT, 0.0);
+            quad.paint_quad(&matrix, gpu_state)?;
+        }
+        Ok(())
+    }
+}
+
+/// the swatches above the ramp, one texel per cell
+fn board(
+    encoding: TextureEncoding,
+    gpu_state: &mut GPUState,
+) -> Result<TexturedQuad, GLErrorWrapper> {
+    let mut pixels = Vec::with_capacity(2 * COLUMNS * 4);
+    for swatch in &SWATCHES {
+        pixels.extend_from_slice(&swatch.srgb);
+        pixels.push(255);
+    }
+    for step in 0..COLUMNS {
+        let value = ramp_value(step);
+        pixels.extend_from_slice(&[value, value, value, 255]);
+    }
+
+    let texture = Texture::new()?;
+    {
+        let mut bound = texture.bound(gl::TEXTURE_2D, gpu_state)?;
+        bound.write_pixels(
+            0,
+            encoding.internal_format() as GLint,
+            COLUMNS as i32,
+            2,
+            gl::RGBA,
+            &pixels,
+        )?;
+        // sharp cells, and no mipmaps needed
+        bound.set_parameter(gl::TEXTURE_MIN_FILTER, gl::NEAREST)?;
+        bound.set_parameter(gl::TEXTURE_MAG_FILTER, gl::NEAREST)?;
+        bound.set_parameter(gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE)?;
+        bound.set_parameter(gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE)?;
+    }
+    TexturedQuad::new(
+        gpu_state,
+        BOARD_HALF_WIDTH,
+        BOARD_HALF_HEIGHT,
+        TextureWithTarget::new(texture, gl::TEXTURE_2D),
+    )
+}
+
+/// What the compositor shows for `stored`, as sRGB: it decodes an sRGB swapchain and treats
+/// any other as linear.
+fn displayed(stored: [u8; 3], format: ColorFormat) -> [u8; 3] {
+    match format {
+        ColorFormat::Srgb8Alpha8 => stored,
+        _ => stored.map(|value| linear_to_srgb(value as f32 / 255.0)),
+    }
+}
+
+fn linear_to_srgb(linear: f32) -> u8 {
+    let c = if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// both boards in [ColorCheck::run]'s order, each cell shown as `show` makes of it
+    fn report(show: impl Fn(TextureEncoding, [u8; 3]) -> [u8; 3]) -> ColorCheckReport {
+        let mut cells = vec![];
+        for encoding in [TextureEncoding::Unorm, TextureEncoding::Srgb] {
+            let swatches = SWATCHES.iter().map(|s| (s.name.to_string(), s.srgb));
+            let ramp = (0..COLUMNS).map(|step| {
+                let value = ramp_value(step);
+                (format!("gray {}", value), [value; 3])
+            });
+            for (name, expected) in swatches.chain(ramp) {
+                cells.push(CellResult {
+                    name,
+                    encoding,
+                    expected,
+                    displayed: show(encoding, expected),
+                });
+            }
+        }
+        ColorCheckReport {
+            swapchain_format: gl::SRGB8_ALPHA8,
+            tolerance: 3,
+            cells,
+        }
+    }
+
+    fn srgb_to_linear(srgb: u8) -> u8 {
+        let c = srgb as f32 / 255.0;
+        let linear = if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        };
+        (linear * 255.0).round() as u8
+    }
+
+    #[test]
+    fn ramp_runs_black_to_white() {
+        assert_eq!(ramp_value(0), 0);
+        assert_eq!(ramp_value(COLUMNS - 1), 255);
+        assert!((1..COLUMNS).all(|step| ramp_value(step) > ramp_value(step - 1)));
+    }
+
+    #[test]
+    fn linear_to_srgb_known_values() {
+        assert_eq!(linear_to_srgb(0.0), 0);
+        assert_eq!(linear_to_srgb(1.0), 255);
+        // 18% gray
+        assert_eq!(linear_to_srgb(0.18), 118);
+        assert_eq!(linear_to_srgb(-1.0), 0);
+        assert_eq!(linear_to_srgb(2.0), 255);
+    }
+
+    #[test]
+    fn displayed_decodes_by_format() {
+        let stored = [46, 128, 200];
+        assert_eq!(displayed(stored, ColorFormat::Srgb8Alpha8), stored);
+        assert_eq!(
+            displayed(stored, ColorFormat::Rgba8),
+            stored.map(|value| linear_to_srgb(value as f32 / 255.0))
+        );
+    }
+
+    #[test]
+    fn cell_error_is_the_worst_channel() {
+        let cell = CellResult {
+            name: "x".to_string(),
+            encoding: TextureEncoding::Unorm,
+            expected: [100, 100, 100],
+            displayed: [102, 90, 101],
+        };
+        assert_eq!(cell.error(), 10);
+        assert!((cell.bias() - (-7.0 / 3.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn correct_boards_pass() {
+        let report = report(|_, expected| expected);
+        assert!(report.passed());
+        assert_eq!(report.failures().count(), 0);
+        assert_eq!(report.verdict(TextureEncoding::Unorm), Verdict::Correct);
+        assert_eq!(report.verdict(TextureEncoding::Srgb), Verdict::Correct);
+    }
+
+    #[test]
+    fn encoded_twice_is_too_bright() {
+        let report = report(|encoding, expected| match encoding {
+            TextureEncoding::Unorm => expected.map(|v| linear_to_srgb(v as f32 / 255.0)),
+            TextureEncoding::Srgb => expected,
+        });
+        assert!(!report.passed());
+        assert_eq!(report.verdict(TextureEncoding::Unorm), Verdict::TooBright);
+        assert_eq!(report.verdict(TextureEncoding::Srgb), Verdict::Correct);
+        assert!(report
+            .failures()
+            .all(|cell| cell.encoding == TextureEncoding::Unorm));
+    }
+
+    #[test]
+    fn never_encoded_is_too_dark() {
+        let report = report(|encoding, expected| match encoding {
+            TextureEncoding::Unorm => expected,
+            TextureEncoding::Srgb => expected.map(srgb_to_linear),
+        });
+        assert_eq!(report.verdict(TextureEncoding::Unorm), Verdict::Correct);
+        assert_eq!(report.verdict(TextureEncoding::Srgb), Verdict::TooDark);
+    }
+
+    #[test]
+    fn one_bad_swatch_is_inaccurate() {
+        let report = report(|encoding, expected| {
+            if encoding == TextureEncoding::Srgb && expected == SWATCHES[4].srgb {
+                [expected[0] - 20, expected[1], expected[2]]
+            } else {
+                expected
+            }
+        });
+        assert_eq!(report.verdict(TextureEncoding::Unorm), Verdict::Correct);
+        assert_eq!(report.verdict(TextureEncoding::Srgb), Verdict::Inaccurate);
+        let failures: Vec<&str> = report.failures().map(|cell| cell.name.as_str()).collect();
+        assert_eq!(failures, ["red"]);
+    }
+
+    #[test]
+    fn within_tolerance_passes() {
+        let report = report(|_, expected| expected.map(|v| v.saturating_add(3)));
+        assert!(report.passed());
+        assert_eq!(report.verdict(TextureEncoding::Srgb), Verdict::Correct);
+    }
+}
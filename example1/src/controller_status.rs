@@ -0,0 +1,185 @@
+//! Whether each controller is connected and tracked, and how much battery it has left where
+//! something can tell.  Connection comes from the interaction profile the runtime reports for
+//! each hand, re-read whenever it says the profile changed; tracking from whether the pose
+//! action is active.  Core OpenXR has no battery query, so [ControllerStatusMonitor::battery_source]
+//! is where a vendor extension or platform API can plug one in.
+
+use crate::haptics::HapticHand;
+use crate::xr_input::XrInputs;
+use openxr::{Instance, Session};
+use openxr_sys::Path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+/// reads a controller's charge, 0..100, or `None` if it can not tell
+pub type BatterySource = Box<dyn FnMut(HapticHand) -> Option<i32>>;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ControllerStatus {
+    /// the runtime has bound an interaction profile to this hand
+    pub connected: bool,
+    /// the pose is being tracked
+    pub tracked: bool,
+    /// e.g. "/interaction_profiles/oculus/touch_controller"
+    pub interaction_profile: Option<String>,
+    /// 0..100; `None` without a [ControllerStatusMonitor::battery_source] that knows
+    pub battery_percent: Option<i32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControllerStatusEvent {
+    Connected {
+        hand: HapticHand,
+        interaction_profile: String,
+    },
+    Disconnected(HapticHand),
+    TrackingLost(HapticHand),
+    TrackingRegained(HapticHand),
+    /// the battery dropped to [ControllerStatusMonitor::low_battery_percent]; time to warn
+    /// the user
+    BatteryLow {
+        hand: HapticHand,
+        percent: i32,
+    },
+}
+
+/// Polled once a frame after the actions are synced, and tells subscribers what changed.
+pub struct ControllerStatusMonitor {
+    pub low_battery_percent: i32,
+    /// see the module docs
+    pub battery_source: Option<BatterySource>,
+    /// how often to ask [ControllerStatusMonitor::battery_source]
+    pub battery_interval: Duration,
+    /// left, right
+    status: [ControllerStatus; 2],
+    /// re-read the interaction profiles at the next poll
+    profiles_stale: bool,
+    last_battery_poll: Option<Instant>,
+    subscribers: Vec<Sender<ControllerStatusEvent>>,
+}
+
+impl Default for ControllerStatusMonitor {
+    fn default() -> Self {
+        Self {
+            low_battery_percent: 15,
+            battery_source: None,
+            battery_interval: Duration::from_secs(10),
+            status: Default::default(),
+            profiles_stale: true,
+            last_battery_poll: None,
+            subscribers: vec![],
+        }
+    }
+}
+
+impl ControllerStatusMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// every event after this call arrives on the returned channel
+    pub fn subscribe(&mut self) -> Receiver<ControllerStatusEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    pub fn status(&self, hand: HapticHand) -> &ControllerStatus {
+        &self.status[index(hand)]
+    }
+
+    /// after [gl_thin::openxr_helpers::OpenXRComponent::take_interaction_profile_changed]
+    pub fn interaction_profile_changed(&mut self) {
+        self.profiles_stale = true;
+    }
+
+    pub fn poll<G>(
+        &mut self,
+        instance: &Instance,
+        xr_session: &Session<G>,
+        inputs: &XrInputs,
+    ) -> openxr::Result<()> {
+        let profiles_stale = std::mem::take(&mut self.profiles_stale);
+        let now = Instant::now();
+        let battery_due = self.battery_source.is_some()
+            && self
+                .last_battery_poll
+                .is_none_or(|last| now.duration_since(last) >= self.battery_interval);
+        if battery_due {
+            self.last_battery_poll = Some(now);
+        }
+
+        for (hand, path) in [
+            (HapticHand::Left, inputs.user_hand_left),
+            (HapticHand::Right, inputs.user_hand_right),
+        ] {
+            let old = self.status[index(hand)].clone();
+            let mut new = old.clone();
+            if profiles_stale {
+                let profile = xr_session.current_interaction_profile(path)?;
+                new.interaction_profile = if profile == Path::NULL {
+                    None
+                } else {
+                    Some(instance.path_to_string(profile)?)
+                };
+                new.connected = new.interaction_profile.is_some();
+            }
+            new.tracked = inputs.controller_1.is_active(xr_session, path)?;
+            if battery_due {
+                if let Some(source) = &mut self.battery_source {
+                    new.battery_percent = source(hand);
+                }
+            }
+            self.changed(hand, &old, &new);
+            self.status[index(hand)] = new;
+        }
+        Ok(())
+    }
+
+    fn changed(&mut self, hand: HapticHand, old: &ControllerStatus, new: &ControllerStatus) {
+        if new.interaction_profile != old.interaction_profile {
+            match &new.interaction_profile {
+                Some(profile) => {
+                    log::info!("{:?} controller connected as {}", hand, profile);
+                    self.send(ControllerStatusEvent::Connected {
+                        hand,
+                        interaction_profile: profile.clone(),
+                    });
+                }
+                None => {
+                    log::info!("{:?} controller disconnected", hand);
+                    self.send(ControllerStatusEvent::Disconnected(hand));
+                }
+            }
+        }
+        if new.tracked != old.tracked {
+            self.send(if new.tracked {
+                ControllerStatusEvent::TrackingRegained(hand)
+            } else {
+                ControllerStatusEvent::TrackingLost(hand)
+            });
+        }
+        if let Some(percent) = new.battery_percent {
+            let was_low = old
+                .battery_percent
+                .is_some_and(|p| p <= self.low_battery_percent);
+            if percent <= self.low_battery_percent && !was_low {
+                log::info!("{:?} controller battery at {}%", hand, percent);
+                self.send(ControllerStatusEvent::BatteryLow { hand, percent });
+            }
+        }
+    }
+
+    fn send(&mut self, event: ControllerStatusEvent) {
+        // forget subscribers that hung up
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+fn index(hand: HapticHand) -> usize {
+    match hand {
+        HapticHand::Left => 0,
+        HapticHand::Right => 1,
+    }
+}
@@ -0,0 +1,117 @@
+//! A flat image (video, a UI panel) on a curved screen around the viewer, composited by the
+//! runtime as an XR_KHR_composition_layer_cylinder layer.  Every part of a cylinder
+//! centered on the viewer is the same distance away, which is easier on the eyes than a
+//! large flat screen.
+
+use gl_thin::composition_layers::{CompositionLayer, LayerId, LayerShape, LayerSwapchain};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::{FrameBuffer, GLErrorWrapper};
+use gl_thin::linear::XrVector3f;
+use gl_thin::openxr_helpers::{Backend, OpenXRComponent};
+use openxr::Posef;
+use std::error::Error;
+
+pub struct CurvedScreen {
+    id: LayerId,
+    frame_buffer: FrameBuffer,
+    /// in pixels
+    width: u32,
+    height: u32,
+    /// where the controller points at the screen, see [CurvedScreen::update_pointer]
+    pointer: Option<[f32; 2]>,
+}
+
+impl CurvedScreen {
+    /// A screen `width` meters wide along its arc of `central_angle` radians, for
+    /// `pixel_width` x `pixel_height` images.  `pose` is the center of the cylinder, usually
+    /// near the viewer's head, in the [OpenXRComponent]'s space.
+    pub fn new(
+        openxr: &mut OpenXRComponent<Backend>,
+        pose: Posef,
+        width: f32,
+        central_angle: f32,
+        pixel_width: u32,
+        pixel_height: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let shape = LayerShape::cylinder(
+            width,
+            central_angle,
+            pixel_width as f32 / pixel_height as f32,
+        );
+        let swapchain = LayerSwapchain::new(
+            &openxr.xr_session,
+            openxr.swapchain_format,
+            pixel_width,
+            pixel_height,
+        )?;
+        let id = openxr
+            .layers
+            .add(CompositionLayer::new(shape, swapchain).with_pose(pose))?;
+        Ok(Self {
+            id,
+            frame_buffer: FrameBuffer::new()?,
+            width: pixel_width,
+            height: pixel_height,
+            pointer: None,
+        })
+    }
+
+    /// Replace the image.  `paint` draws into the bound framebuffer, whose viewport covers
+    /// the whole screen.
+    pub fn update(
+        &mut self,
+        openxr: &mut OpenXRComponent<Backend>,
+        gpu_state: &mut GPUState,
+        paint: impl FnOnce(&mut GPUState) -> Result<(), GLErrorWrapper>,
+    ) -> Result<(), Box<dyn Error>> {
+        let layer = openxr
+            .layers
+            .get_mut(self.id)
+            .ok_or("the curved screen's layer was removed")?;
+        layer
+            .swapchain
+            .paint_gl(&self.frame_buffer, || paint(gpu_state))??;
+        Ok(())
+    }
+
+    /// Test a pointer ray against the screen.  The result stays available from
+    /// [CurvedScreen::pointer] until the next call.
+    pub fn update_pointer(
+        &mut self,
+        openxr: &OpenXRComponent<Backend>,
+        ray: Option<(XrVector3f, XrVector3f)>,
+    ) -> Option<[f32; 2]> {
+        self.pointer = ray.and_then(|(origin, direction)| {
+            let layer = openxr.layers.get(self.id)?;
+            if !layer.visible {
+                return None;
+            }
+            let [u, v] = layer.hit(&origin, &direction)?;
+            // pixels from the top left, the way UI toolkits count them
+            Some([u * self.width as f32, (1.0 - v) * self.height as f32])
+        });
+        self.pointer
+    }
+
+    /// in pixels from the image's top left corner, if the pointer is on the screen
+    pub fn pointer(&self) -> Option<[f32; 2]> {
+        self.pointer
+    }
+
+    pub fn set_visible(&self, openxr: &mut OpenXRComponent<Backend>, visible: bool) {
+        if let Some(layer) = openxr.layers.get_mut(self.id) {
+            layer.visible = visible;
+        }
+    }
+
+    /// move the cylinder's center
+    pub fn set_pose(&self, openxr: &mut OpenXRComponent<Backend>, pose: Posef) {
+        if let Some(layer) = openxr.layers.get_mut(self.id) {
+            layer.pose = pose;
+        }
+    }
+
+    pub fn remove(self, openxr: &mut OpenXRComponent<Backend>) {
+        openxr.layers.remove(self.id);
+    }
+}
@@ -0,0 +1,98 @@
+//! Colored lines for seeing what the code thinks: navmeshes, rays, arcs.  Collect the
+//! segments, [DebugLines::upload] them once they are all in, and draw them with the scene.
+
+use bob_shaders::flat_color_shader::FlatColorShader;
+use gl::types::{GLfloat, GLsizei, GLuint};
+use gl_thin::gl_fancy::{GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::XrMatrix4x4f;
+
+pub const RED: [f32; 3] = [0.9, 0.15, 0.1];
+pub const GREEN: [f32; 3] = [0.2, 0.9, 0.3];
+pub const CYAN: [f32; 3] = [0.1, 0.8, 0.9];
+
+pub struct DebugLines {
+    shader: FlatColorShader,
+    /// position and color of both ends of every segment
+    vertices: Vec<GLfloat>,
+    /// what the last [DebugLines::upload] sent, `None` if it was nothing
+    buffers: Option<VertexBufferBundle<'static, GLfloat, GLuint>>,
+}
+
+impl DebugLines {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        Ok(Self {
+            shader: FlatColorShader::new()?,
+            vertices: vec![],
+            buffers: None,
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    pub fn line(&mut self, a: &[f32; 3], b: &[f32; 3], color: [f32; 3]) {
+        self.vertices.extend_from_slice(a);
+        self.vertices.extend_from_slice(&color);
+        self.vertices.extend_from_slice(b);
+        self.vertices.extend_from_slice(&color);
+    }
+
+    /// segments between consecutive `points`
+    pub fn polyline(&mut self, points: &[[f32; 3]], color: [f32; 3]) {
+        for pair in points.windows(2) {
+            self.line(&pair[0], &pair[1], color);
+        }
+    }
+
+    /// three axis-aligned strokes `size` meters long through `center`
+    pub fn cross(&mut self, center: &[f32; 3], size: f32, color: [f32; 3]) {
+        for axis in 0..3 {
+            let mut a = *center;
+            let mut b = *center;
+            a[axis] -= 0.5 * size;
+            b[axis] += 0.5 * size;
+            self.line(&a, &b, color);
+        }
+    }
+
+    /// Send the lines collected so far to the GPU, for [DebugLines::draw].
+    pub fn upload(&mut self, gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper> {
+        if self.vertices.is_empty() {
+            self.buffers = None;
+            return Ok(());
+        }
+        let indices: Vec<GLuint> = (0..(self.vertices.len() / 6) as GLuint).collect();
+        self.buffers = Some(VertexBufferBundle::new(
+            gpu_state,
+            self.vertices.clone().into(),
+            indices.into(),
+            6,
+            &[
+                (self.shader.sal_position, 3, 0),
+                (self.shader.sal_color, 3, 3),
+            ],
+        )?);
+        Ok(())
+    }
+
+    /// the lines as of the last [DebugLines::upload], depth tested against the scene
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let Some(buffers) = &self.buffers else {
+            return Ok(());
+        };
+        self.shader.program.use_()?;
+        self.shader.set_params(matrix_pv);
+        let bound = buffers.bind(gpu_state)?;
+        bound.draw_elements(gl::LINES, buffers.index_count as GLsizei, 0)
+    }
+}
@@ -0,0 +1,244 @@
+//! Battery and thermal state, read from the Android BatteryManager and PowerManager through JNI.
+//! Subsystems that can shed load subscribe to [DeviceStatusMonitor] and back off before the
+//! OS throttles the clocks for us.
+
+use android_activity::AndroidApp;
+use jni::objects::{JObject, JValue};
+use jni::{JNIEnv, JavaVM};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// android.os.BatteryManager.BATTERY_PROPERTY_CAPACITY
+const BATTERY_PROPERTY_CAPACITY: i32 = 4;
+/// how far ahead PowerManager.getThermalHeadroom should forecast
+const HEADROOM_FORECAST_SECONDS: i32 = 10;
+
+/// android.os.PowerManager.THERMAL_STATUS_*
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum ThermalStatus {
+    None,
+    Light,
+    Moderate,
+    Severe,
+    Critical,
+    Emergency,
+    Shutdown,
+}
+
+impl ThermalStatus {
+    fn from_raw(status: i32) -> Option<Self> {
+        Some(match status {
+            0 => ThermalStatus::None,
+            1 => ThermalStatus::Light,
+            2 => ThermalStatus::Moderate,
+            3 => ThermalStatus::Severe,
+            4 => ThermalStatus::Critical,
+            5 => ThermalStatus::Emergency,
+            6 => ThermalStatus::Shutdown,
+            _ => return None,
+        })
+    }
+}
+
+/// How hard the app should be working.  Ordered from least to most throttled.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum ThrottleLevel {
+    Normal,
+    /// cut optional work (spectator view, reflections, resolution)
+    Reduce,
+    /// only what is needed to keep the headset usable
+    Minimum,
+}
+
+/// Fields are `None` when this Android version does not report them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DeviceStatus {
+    /// 0..100
+    pub battery_percent: Option<i32>,
+    pub charging: bool,
+    pub thermal_status: Option<ThermalStatus>,
+    /// 1.0 is where the OS starts throttling; see PowerManager.getThermalHeadroom
+    pub thermal_headroom: Option<f32>,
+}
+
+impl DeviceStatus {
+    pub fn throttle_level(&self, low_battery_percent: i32) -> ThrottleLevel {
+        let thermal = self.thermal_status.unwrap_or(ThermalStatus::None);
+        let headroom = self.thermal_headroom.unwrap_or(0.0);
+        let low_battery = !self.charging
+            && self
+                .battery_percent
+                .is_some_and(|p| p <= low_battery_percent);
+
+        if thermal >= ThermalStatus::Severe || headroom >= 0.95 {
+            ThrottleLevel::Minimum
+        } else if thermal >= ThermalStatus::Moderate || headroom >= 0.8 || low_battery {
+            ThrottleLevel::Reduce
+        } else {
+            ThrottleLevel::Normal
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DeviceStatusEvent {
+    /// after every poll
+    Updated(DeviceStatus),
+    ThrottleChanged {
+        from: ThrottleLevel,
+        to: ThrottleLevel,
+    },
+    /// the battery dropped to [DeviceStatusMonitor::low_battery_percent] while not charging;
+    /// time to warn the user
+    BatteryLow(i32),
+}
+
+/// Polls the device status (it is a handful of JNI calls, so every few seconds, not every frame)
+/// and tells subscribers what changed.
+pub struct DeviceStatusMonitor {
+    pub low_battery_percent: i32,
+    app: AndroidApp,
+    status: Option<DeviceStatus>,
+    throttle: ThrottleLevel,
+    subscribers: Vec<Sender<DeviceStatusEvent>>,
+}
+
+impl DeviceStatusMonitor {
+    pub fn new(app: &AndroidApp) -> Self {
+        Self {
+            low_battery_percent: 15,
+            app: app.clone(),
+            status: None,
+            throttle: ThrottleLevel::Normal,
+            subscribers: vec![],
+        }
+    }
+
+    /// every event after this call arrives on the returned channel
+    pub fn subscribe(&mut self) -> Receiver<DeviceStatusEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// `None` until the first [DeviceStatusMonitor::poll]
+    pub fn status(&self) -> Option<DeviceStatus> {
+        self.status
+    }
+
+    pub fn throttle_level(&self) -> ThrottleLevel {
+        self.throttle
+    }
+
+    pub fn poll(&mut self) -> Result<(), jni::errors::Error> {
+        let status = query_device_status(&self.app)?;
+
+        if let (Some(old), Some(percent)) = (self.status, status.battery_percent) {
+            let was_low = old
+                .battery_percent
+                .is_some_and(|p| p <= self.low_battery_percent);
+            if percent <= self.low_battery_percent && !was_low && !status.charging {
+                self.send(DeviceStatusEvent::BatteryLow(percent));
+            }
+        }
+        self.status = Some(status);
+        self.send(DeviceStatusEvent::Updated(status));
+
+        let throttle = status.throttle_level(self.low_battery_percent);
+        if throttle != self.throttle {
+            log::info!(
+                "device throttle level {:?} -> {:?}",
+                self.throttle,
+                throttle
+            );
+            self.send(DeviceStatusEvent::ThrottleChanged {
+                from: self.throttle,
+                to: throttle,
+            });
+            self.throttle = throttle;
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, event: DeviceStatusEvent) {
+        // forget subscribers that hung up
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event).is_ok());
+    }
+}
+
+pub fn query_device_status(app: &AndroidApp) -> Result<DeviceStatus, jni::errors::Error> {
+    let vm = unsafe { JavaVM::from_raw(app.vm_as_ptr() as *mut jni::sys::JavaVM) }?;
+    let mut env = vm.attach_current_thread()?;
+    let activity = unsafe { JObject::from_raw(app.activity_as_ptr() as jni::sys::jobject) };
+
+    let battery = system_service(&mut env, &activity, "batterymanager")?;
+    let battery_percent = env
+        .call_method(
+            &battery,
+            "getIntProperty",
+            "(I)I",
+            &[JValue::Int(BATTERY_PROPERTY_CAPACITY)],
+        )?
+        .i()?;
+    let charging = env.call_method(&battery, "isCharging", "()Z", &[])?.z()?;
+
+    let power = system_service(&mut env, &activity, "power")?;
+    // API 29
+    let thermal_status = optional(&mut env, |env| {
+        env.call_method(&power, "getCurrentThermalStatus", "()I", &[])?
+            .i()
+    })?
+    .and_then(ThermalStatus::from_raw);
+    // API 30; NaN if the device cannot tell
+    let thermal_headroom = optional(&mut env, |env| {
+        env.call_method(
+            &power,
+            "getThermalHeadroom",
+            "(I)F",
+            &[JValue::Int(HEADROOM_FORECAST_SECONDS)],
+        )?
+        .f()
+    })?
+    .filter(|headroom| !headroom.is_nan());
+
+    Ok(DeviceStatus {
+        // Integer.MIN_VALUE when unsupported
+        battery_percent: (battery_percent >= 0).then_some(battery_percent),
+        charging,
+        thermal_status,
+        thermal_headroom,
+    })
+}
+
+fn system_service<'local>(
+    env: &mut JNIEnv<'local>,
+    activity: &JObject,
+    name: &str,
+) -> Result<JObject<'local>, jni::errors::Error> {
+    let name = env.new_string(name)?;
+    env.call_method(
+        activity,
+        "getSystemService",
+        "(Ljava/lang/String;)Ljava/lang/Object;",
+        &[JValue::Object(&name)],
+    )?
+    .l()
+}
+
+/// For methods newer than the device: a java exception (NoSuchMethodError) becomes `None`.
+fn optional<'local, T>(
+    env: &mut JNIEnv<'local>,
+    call: impl FnOnce(&mut JNIEnv<'local>) -> Result<T, jni::errors::Error>,
+) -> Result<Option<T>, jni::errors::Error> {
+    match call(env) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) => {
+            if env.exception_check()? {
+                env.exception_clear()?;
+                Ok(None)
+            } else {
+                Err(e)
+            }
+        }
+    }
+}
@@ -0,0 +1,534 @@
+//! Fetching asset packs over HTTPS into the app's storage, so the APK can stay small and the
+//! content arrive on first run.  Each download runs on its own thread through Java's
+//! HttpURLConnection (which brings the platform's TLS and proxy settings along), writes to a
+//! `.part` file, and picks up where it left off with a Range request if it is interrupted,
+//! whether by the network or by the app being closed.  Progress comes back on the render
+//! thread from [DownloadManager::poll].  Packs from [DownloadManager::download_signed] are
+//! checked with [crate::asset_pack] before they are moved into place.
+
+use crate::asset_pack::{PackSignature, PackVerifier, VerifyError};
+use android_activity::AndroidApp;
+use jni::objects::JValue;
+use jni::{JNIEnv, JavaVM};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// how much to read from the connection at a time
+const CHUNK_SIZE: i32 = 64 * 1024;
+/// report progress at most this often, in bytes
+const PROGRESS_INTERVAL: u64 = 256 * 1024;
+
+/// identifies one [DownloadManager::download]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DownloadId(u64);
+
+#[derive(Debug)]
+pub enum DownloadEvent {
+    Progress {
+        id: DownloadId,
+        /// including what an earlier attempt already had
+        received: u64,
+        /// `None` if the server did not say
+        total: Option<u64>,
+    },
+    /// the pack is complete at `path`, ready for the asset loader
+    Finished { id: DownloadId, path: PathBuf },
+    /// gave up after [DownloadManager::retries]; the `.part` file stays for the next attempt
+    Failed { id: DownloadId, error: String },
+    /// the pack arrived but is not the one that was signed, so it was deleted
+    Rejected { id: DownloadId, error: VerifyError },
+}
+
+impl DownloadEvent {
+    pub fn id(&self) -> DownloadId {
+        match self {
+            DownloadEvent::Progress { id, .. }
+            | DownloadEvent::Finished { id, .. }
+            | DownloadEvent::Failed { id, .. }
+            | DownloadEvent::Rejected { id, .. } => *id,
+        }
+    }
+
+    /// `true` for the last event of a download
+    pub fn is_done(&self) -> bool {
+        !matches!(self, DownloadEvent::Progress { .. })
+    }
+}
+
+struct Active {
+    id: DownloadId,
+    cancelled: Arc<AtomicBool>,
+    callback: Box<dyn FnMut(&DownloadEvent)>,
+    /// from the latest [DownloadEvent::Progress]
+    received: u64,
+    total: Option<u64>,
+}
+
+pub struct DownloadManager {
+    /// attempts per download after the first one fails
+    pub retries: u32,
+    /// wait before the first retry; doubled for each one after
+    pub retry_delay: Duration,
+    app: AndroidApp,
+    directory: PathBuf,
+    events: Receiver<DownloadEvent>,
+    event_sender: Sender<DownloadEvent>,
+    active: Vec<Active>,
+    next_id: u64,
+}
+
+impl DownloadManager {
+    /// Packs are stored in `asset_packs` under the app's internal storage, which survives
+    /// updates but not an uninstall.
+    pub fn new(app: &AndroidApp) -> std::io::Result<Self> {
+        let directory = app
+            .internal_data_path()
+            .ok_or_else(|| std::io::Error::other("no internal data path"))?
+            .join("asset_packs");
+        Self::with_directory(app, directory)
+    }
+
+    pub fn with_directory(app: &AndroidApp, directory: PathBuf) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&directory)?;
+        let (event_sender, events) = channel();
+        Ok(Self {
+            retries: 3,
+            retry_delay: Duration::from_secs(2),
+            app: app.clone(),
+            directory,
+            events,
+            event_sender,
+            active: vec![],
+            next_id: 0,
+        })
+    }
+
+    /// Where the pack called `name` is (or will be) stored.  `name` has to be a plain file
+    /// name, so a pack can not land outside the directory.
+    pub fn path(&self, name: &str) -> std::io::Result<PathBuf> {
+        let plain =
+            !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', '\0']);
+        if !plain {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("not a plain file name: {:?}", name),
+            ));
+        }
+        Ok(self.directory.join(name))
+    }
+
+    pub fn is_downloaded(&self, name: &str) -> bool {
+        self.path(name).is_ok_and(|path| path.is_file())
+    }
+
+    /// Fetch `url`, which must be https, into [DownloadManager::path]`(name)` on a background
+    /// thread, resuming an earlier attempt if there was one.  `callback` gets every
+    /// [DownloadEvent] for it, on the thread that calls [DownloadManager::poll].  A pack that
+    /// is already complete finishes at the next poll without touching the network.
+    pub fn download(
+        &mut self,
+        url: &str,
+        name: &str,
+        callback: impl FnMut(&DownloadEvent) + 'static,
+    ) -> std::io::Result<DownloadId> {
+        self.start(url, name, None, Box::new(callback))
+    }
+
+    /// Like [DownloadManager::download], but the pack only finishes if `verifier` accepts it
+    /// with `signature`, and is deleted with a [DownloadEvent::Rejected] otherwise.  A copy
+    /// already in place is checked again, and fetched again if it fails.  A signature that
+    /// does not verify fails here, before anything is fetched.
+    pub fn download_signed(
+        &mut self,
+        url: &str,
+        name: &str,
+        verifier: &PackVerifier,
+        signature: PackSignature,
+        callback: impl FnMut(&DownloadEvent) + 'static,
+    ) -> Result<DownloadId, VerifyError> {
+        verifier.verify_signature(&signature)?;
+        let signature = Some((verifier.clone(), signature));
+        Ok(self.start(url, name, signature, Box::new(callback))?)
+    }
+
+    fn start(
+        &mut self,
+        url: &str,
+        name: &str,
+        signature: Option<(PackVerifier, PackSignature)>,
+        callback: Box<dyn FnMut(&DownloadEvent)>,
+    ) -> std::io::Result<DownloadId> {
+        let destination = self.path(name)?;
+        if !url
+            .get(..8)
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://"))
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("not an https URL: {}", url),
+            ));
+        }
+
+        let id = DownloadId(self.next_id);
+        self.next_id += 1;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.active.push(Active {
+            id,
+            cancelled: cancelled.clone(),
+            callback,
+            received: 0,
+            total: None,
+        });
+
+        if destination.is_file() && signature.is_none() {
+            let _ = self.event_sender.send(DownloadEvent::Finished {
+                id,
+                path: destination,
+            });
+            return Ok(id);
+        }
+
+        let job = Job {
+            id,
+            url: url.to_string(),
+            destination,
+            signature,
+            retries: self.retries,
+            retry_delay: self.retry_delay,
+            cancelled,
+            events: self.event_sender.clone(),
+        };
+        let app = self.app.clone();
+        std::thread::Builder::new()
+            .name(format!("download {}", name))
+            .spawn(move || job.run(&app))?;
+        Ok(id)
+    }
+
+    /// Stop a download between chunks.  Its `.part` file stays, so downloading it again
+    /// resumes.  No more events arrive for it.
+    pub fn cancel(&mut self, id: DownloadId) {
+        self.active.retain(|active| {
+            if active.id == id {
+                active.cancelled.store(true, Ordering::Relaxed);
+            }
+            active.id != id
+        });
+    }
+
+    pub fn active_count(&self) -> usize {
+        self.active.len()
+    }
+
+    /// Bytes received and expected across every active download, as of the last poll; the
+    /// total is `None` while any of them has not said.
+    pub fn progress(&self) -> (u64, Option<u64>) {
+        let received = self.active.iter().map(|active| active.received).sum();
+        let total = self.active.iter().map(|active| active.total).sum();
+        (received, total)
+    }
+
+    /// Once per frame: run the callbacks for whatever the downloads reported since the last
+    /// poll, and return those events.
+    pub fn poll(&mut self) -> Vec<DownloadEvent> {
+        let mut rval = vec![];
+        while let Ok(event) = self.events.try_recv() {
+            let id = event.id();
+            let Some(index) = self.active.iter().position(|active| active.id == id) else {
+                // cancelled
+                continue;
+            };
+            let active = &mut self.active[index];
+            if let DownloadEvent::Progress {
+                received, total, ..
+            } = event
+            {
+                active.received = received;
+                active.total = total;
+            }
+            (active.callback)(&event);
+            if event.is_done() {
+                self.active.remove(index);
+            }
+            rval.push(event);
+        }
+        rval
+    }
+}
+
+impl Drop for DownloadManager {
+    fn drop(&mut self) {
+        for active in &self.active {
+            active.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+//
+
+struct Job {
+    id: DownloadId,
+    url: String,
+    destination: PathBuf,
+    signature: Option<(PackVerifier, PackSignature)>,
+    retries: u32,
+    retry_delay: Duration,
+    cancelled: Arc<AtomicBool>,
+    events: Sender<DownloadEvent>,
+}
+
+impl Job {
+    fn run(self, app: &AndroidApp) {
+        if self.destination.is_file() {
+            match self.verify(&self.destination) {
+                Ok(()) => return self.finished(),
+                Err(e) => {
+                    log::warn!(
+                        "{:?} failed verification, fetching it again {}",
+                        self.destination,
+                        e
+                    );
+                    let _ = std::fs::remove_file(&self.destination);
+                }
+            }
+        }
+
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        let result = loop {
+            match self.attempt(app) {
+                Ok(()) => break Ok(()),
+                Err(e) if self.cancelled.load(Ordering::Relaxed) => break Err(e),
+                Err(e) if attempt < self.retries => {
+                    log::warn!("download of {} failed, retrying {}", self.url, e);
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(e) => break Err(e),
+            }
+        };
+
+        if let Err(error) = result {
+            log::warn!("download of {} malfunction {}", self.url, error);
+            let _ = self
+                .events
+                .send(DownloadEvent::Failed { id: self.id, error });
+            return;
+        }
+
+        let part = self.part_path();
+        if let Err(error) = self.verify(&part) {
+            log::warn!("download of {} rejected {}", self.url, error);
+            // resuming it would only append to whatever went wrong
+            let _ = std::fs::remove_file(&part);
+            let _ = self
+                .events
+                .send(DownloadEvent::Rejected { id: self.id, error });
+            return;
+        }
+        if let Err(e) = std::fs::rename(&part, &self.destination) {
+            let error = format!("failed to move {:?} into place {}", part, e);
+            log::warn!("download of {} malfunction {}", self.url, error);
+            let _ = self
+                .events
+                .send(DownloadEvent::Failed { id: self.id, error });
+            return;
+        }
+        log::info!("downloaded {} to {:?}", self.url, self.destination);
+        self.finished();
+    }
+
+    fn finished(&self) {
+        let _ = self.events.send(DownloadEvent::Finished {
+            id: self.id,
+            path: self.destination.clone(),
+        });
+    }
+
+    fn verify(&self, path: &Path) -> Result<(), VerifyError> {
+        match &self.signature {
+            Some((verifier, signature)) => verifier.verify_file(path, signature),
+            None => Ok(()),
+        }
+    }
+
+    fn part_path(&self) -> PathBuf {
+        let mut name = self.destination.file_name().unwrap_or_default().to_owned();
+        name.push(".part");
+        self.destination.with_file_name(name)
+    }
+
+    fn attempt(&self, app: &AndroidApp) -> Result<(), String> {
+        let part = self.part_path();
+        let offset = std::fs::metadata(&part).map_or(0, |meta| meta.len());
+
+        let vm = unsafe { JavaVM::from_raw(app.vm_as_ptr() as *mut jni::sys::JavaVM) }
+            .map_err(|e| e.to_string())?;
+        let mut env = vm.attach_current_thread().map_err(|e| e.to_string())?;
+        env.with_local_frame(16, |env| self.fetch(env, &part, offset))
+            .map_err(|e| match e {
+                FetchError::Jni(e) => java_error(&mut env, e),
+                FetchError::Other(message) => message,
+            })
+    }
+
+    /// one connection, appending to `part` from `offset`
+    fn fetch(&self, env: &mut JNIEnv, part: &Path, offset: u64) -> Result<(), FetchError> {
+        let url = env.new_string(&self.url)?;
+        let url = env.new_object(
+            "java/net/URL",
+            "(Ljava/lang/String;)V",
+            &[JValue::Object(&url)],
+        )?;
+        let connection = env
+            .call_method(&url, "openConnection", "()Ljava/net/URLConnection;", &[])?
+            .l()?;
+        if offset > 0 {
+            let key = env.new_string("Range")?;
+            let value = env.new_string(format!("bytes={}-", offset))?;
+            env.call_method(
+                &connection,
+                "setRequestProperty",
+                "(Ljava/lang/String;Ljava/lang/String;)V",
+                &[JValue::Object(&key), JValue::Object(&value)],
+            )?;
+        }
+
+        let code = env
+            .call_method(&connection, "getResponseCode", "()I", &[])?
+            .i()?;
+        let (mut file, mut received) = match code {
+            206 => (open_part(part, true)?, offset),
+            200 => {
+                if offset > 0 {
+                    log::info!("{} does not resume; starting over", self.url);
+                }
+                (open_part(part, false)?, 0)
+            }
+            // the range starts at the end; the part file is the whole thing
+            416 if offset > 0 => return Ok(()),
+            _ => {
+                return Err(FetchError::Other(format!(
+                    "HTTP {} from {}",
+                    code, self.url
+                )))
+            }
+        };
+        let length = env
+            .call_method(&connection, "getContentLengthLong", "()J", &[])?
+            .j()?;
+        let total = (length >= 0).then(|| received + length as u64);
+        self.progress(received, total);
+
+        let stream = env
+            .call_method(
+                &connection,
+                "getInputStream",
+                "()Ljava/io/InputStream;",
+                &[],
+            )?
+            .l()?;
+        let buffer = env.new_byte_array(CHUNK_SIZE)?;
+        let mut chunk = vec![0i8; CHUNK_SIZE as usize];
+        let mut last_report = received;
+        let result = loop {
+            if self.cancelled.load(Ordering::Relaxed) {
+                break Err(FetchError::Other("cancelled".to_string()));
+            }
+            let n = match env.call_method(&stream, "read", "([B)I", &[JValue::Object(&buffer)]) {
+                Ok(n) => n.i()?,
+                Err(e) => break Err(e.into()),
+            };
+            if n < 0 {
+                break Ok(());
+            }
+            let n = n as usize;
+            env.get_byte_array_region(&buffer, 0, &mut chunk[..n])?;
+            let bytes: Vec<u8> = chunk[..n].iter().map(|&b| b as u8).collect();
+            if let Err(e) = file.write_all(&bytes) {
+                break Err(FetchError::Other(format!(
+                    "failed to write {:?} {}",
+                    part, e
+                )));
+            }
+            received += n as u64;
+            if received - last_report >= PROGRESS_INTERVAL {
+                self.progress(received, total);
+                last_report = received;
+            }
+        };
+        // the stream and connection have to be closed even when the read failed part way
+        let pending = env.exception_occurred()?;
+        env.exception_clear()?;
+        let _ = env.call_method(&stream, "close", "()V", &[]);
+        let _ = env.call_method(&connection, "disconnect", "()V", &[]);
+        env.exception_clear()?;
+        if !pending.is_null() {
+            env.throw(pending)?;
+        }
+        result?;
+
+        self.progress(received, total);
+        if total.is_some_and(|total| received < total) {
+            return Err(FetchError::Other(format!(
+                "connection closed after {} of {:?} bytes",
+                received, total
+            )));
+        }
+        file.flush()
+            .map_err(|e| FetchError::Other(format!("failed to write {:?} {}", part, e)))
+    }
+
+    fn progress(&self, received: u64, total: Option<u64>) {
+        let _ = self.events.send(DownloadEvent::Progress {
+            id: self.id,
+            received,
+            total,
+        });
+    }
+}
+
+enum FetchError {
+    Jni(jni::errors::Error),
+    Other(String),
+}
+
+impl From<jni::errors::Error> for FetchError {
+    fn from(e: jni::errors::Error) -> Self {
+        FetchError::Jni(e)
+    }
+}
+
+fn open_part(part: &Path, append: bool) -> Result<File, FetchError> {
+    OpenOptions::new()
+        .create(true)
+        .append(append)
+        .write(true)
+        .truncate(!append)
+        .open(part)
+        .map_err(|e| FetchError::Other(format!("failed to open {:?} {}", part, e)))
+}
+
+/// Describe a failed JNI call, including the Java exception it threw, if any, and clear it
+/// so the thread can make JNI calls again.
+fn java_error(env: &mut JNIEnv, e: jni::errors::Error) -> String {
+    let Ok(exception) = env.exception_occurred() else {
+        return e.to_string();
+    };
+    if exception.is_null() {
+        return e.to_string();
+    }
+    let _ = env.exception_clear();
+    let description = env
+        .call_method(&exception, "toString", "()Ljava/lang/String;", &[])
+        .and_then(|s| s.l())
+        .and_then(|s| Ok(String::from(env.get_string(&s.into())?)));
+    let _ = env.exception_clear();
+    description.unwrap_or_else(|_| e.to_string())
+}
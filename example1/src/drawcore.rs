@@ -1,39 +1,130 @@
-use crate::scene::MyScene;
-use crate::xr_input::XrInputs;
+use crate::adaptive_quality::{QualityGovernor, QualityLevel};
+use crate::analytics::{Analytics, AnalyticsEvent};
+use crate::android_permissions::{captures_dir, PermissionTracker, RECORD_AUDIO};
+use crate::calibration::{HeightCalibration, PlayMode};
+use crate::color_check::{ColorCheck, ColorCheckReport};
+use crate::controller_status::ControllerStatusMonitor;
+use crate::curved_screen::CurvedScreen;
+use crate::device_status::DeviceStatusMonitor;
+use crate::downloads::DownloadManager;
+use crate::flashlight::Flashlight;
+use crate::frame_hooks::{FrameHooks, HookStage, ViewInfo};
+use crate::frame_scheduler::FrameScheduler;
+use crate::gestures::{GestureButton, GestureDetector};
+use crate::gltf_export::GltfDocument;
+use crate::hand_occlusion::{HandOcclusion, HandPoses};
+use crate::haptics::{HapticHand, HapticPattern, HapticSequencer};
+use crate::lod::LodView;
+use crate::microbench::{run_gl_suite, MicroBench};
+use crate::microphone::{AudioLevels, Microphone};
+use crate::minimap::Minimap;
+use crate::mirror::PlanarMirror;
+use crate::motion_vectors::{DepthCopy, MotionId, MotionVectors};
+use crate::msaa_resolve::MsaaResolver;
+use crate::painting::{Painter, StrokeCommand};
+use crate::panorama::Panorama;
+use crate::passthrough_camera::{PassthroughCamera, PassthroughCameraConfig};
+use crate::portal::Portal;
+use crate::pose_stream::{Pose, PoseStreamConfig, PoseStreamer, RemoteAvatar};
+use crate::post_chain::PostChain;
+use crate::presence::PresenceMonitor;
+use crate::profiler::Profiler;
+use crate::render_thread::RestartSignal;
+use crate::scene::{
+    inverse_view_matrix, matrix_rotation_about_x, projection_matrix, MyScene, FAR_Z, NEAR_Z,
+};
+use crate::scene_stack::{SceneStack, DEFAULT_SCENE};
+use crate::settings::UserSettings;
+use crate::spatial_audio::SpatialAudio;
+use crate::spectator::{SpectatorCamera, SpectatorConfig};
+use crate::startup::StartupOptions;
+use crate::teleport::Teleport;
+use crate::temporal_aa::{TemporalAa, TEMPORAL_AA};
+use crate::thumbnail::{bake_thumbnail, ThumbnailCamera};
+use crate::ui::developer_menu::{MenuSettings, EXPORT_SCENE_ITEM, QUALITY_ITEM};
+use crate::ui::hud::Hud;
+use crate::ui::keyboard::LaserKeyboard;
+use crate::ui::launcher::{SceneLauncher, THUMBNAIL_ASPECT};
+use crate::ui::ruler::Ruler;
+use crate::ui::text_field::{TextFieldEvent, TextInput};
+use crate::ui::units::{eye_pixels_per_radian, PanelDensity};
+use crate::ui::Ui;
+use crate::undo::{FnCommand, UndoStack};
+use crate::warmup::warm_up;
+use crate::xr_input::{InputContext, InputState, MenuInput, XrInputs};
 use crate::Drawable;
-use gl::types::GLsizei;
-use gl_thin::errors::XrErrorWrapped;
+use android_activity::AndroidApp;
+use gl::types::{GLenum, GLsizei};
+use gl_thin::errors::{Wrappable, XrErrorWrapped};
+use gl_thin::frame_arena::FrameArena;
+use gl_thin::gl_caps::gl_caps;
+use gl_thin::gl_counters::GLCallCounts;
 use gl_thin::gl_fancy::GPUState;
 use gl_thin::gl_helper::{explode_if_gl_error, FrameBuffer, GLErrorWrapper, Texture};
 use gl_thin::linear::{
-    xr_matrix4x4f_create_translation_rotation_scale, xr_matrix4x4f_invert_rigid_body, XrMatrix4x4f,
-    XrQuaternionf, XrVector3f,
+    xr_matrix4x4f_create_from_quaternion, xr_matrix4x4f_create_translation,
+    xr_matrix4x4f_create_translation_rotation_scale, xr_matrix4x4f_invert_rigid_body,
+    xr_matrix4x4f_transform_vector3f, XrMatrix4x4f, XrQuaternionf, XrVector3f,
 };
-use gl_thin::openxr_helpers::{Backend, OpenXRComponent};
+use gl_thin::multiview::{self, MultiviewFrameBuffer};
+use gl_thin::navmesh::NavMesh;
+use gl_thin::openxr_helpers::{Backend, LoopStatus, OpenXRComponent, XrConfig};
+use gl_thin::performance_settings::PerfSettingsDomainEXT;
+use gl_thin::render_graph::{RenderGraph, ResourceId};
+use gl_thin::render_queue::{RenderLayer, RenderQueue};
+use gl_thin::space_warp::SpaceWarpImages;
+use gl_thin::swapchain_readback::{FormatInfo, PixelRegion, SwapchainSnapshot};
+use gl_thin::watchdog::{Stall, Watchdog};
+use gl_thin::world_scale::WorldScale;
 use glutin::config::{ConfigTemplate, ConfigTemplateBuilder, GlConfig};
-use glutin::context::{AsRawContext, ContextAttributesBuilder, RawContext};
+use glutin::context::{AsRawContext, ContextAttributesBuilder, PossiblyCurrentContext, RawContext};
 use glutin::display::{AsRawDisplay, Display, DisplayApiPreference, GlDisplay, RawDisplay};
 use log::debug;
-use openxr::{Graphics, OpenGlEs, SpaceLocation, View, ViewConfigurationView};
+use openxr::{
+    Graphics, OpenGlEs, Posef, ReferenceSpaceType, Space, SpaceLocation, View,
+    ViewConfigurationView,
+};
 use openxr_sys::{Time, ViewConfigurationType};
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle, RawWindowHandle};
+use std::collections::VecDeque;
 use std::error::Error;
+use std::f32::consts::FRAC_PI_4;
 use std::ffi::c_void;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use winit::event::{ElementState, KeyEvent};
 use winit::event_loop::ActiveEventLoop;
+use winit::keyboard::{Key, NamedKey};
 use winit::window::Window;
 
 //
 
 pub struct FrameEnv {
     pub frame_buffer: FrameBuffer,
+    /// with 8 stencil bits if [FrameEnv::stencil]
     pub depth_buffer: Texture,
+    pub stencil: bool,
 }
 
 impl FrameEnv {
-    pub fn new(width: u32, height: u32, gpu_state: &mut GPUState) -> Result<Self, GLErrorWrapper> {
+    /// `stencil` packs a stencil buffer in with the depth buffer, see [GPUState::set_stencil]
+    pub fn new(
+        width: u32,
+        height: u32,
+        stencil: bool,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let (width, height) = (width as i32, height as i32);
+        let depth_buffer = if stencil {
+            Texture::depth_stencil_buffer(width, height, gpu_state)?
+        } else {
+            Texture::depth_buffer(width, height, gpu_state)?
+        };
         Ok(Self {
             frame_buffer: FrameBuffer::new()?,
-            depth_buffer: Texture::depth_buffer(width as i32, height as i32, gpu_state)?,
+            depth_buffer,
+            stencil,
         })
     }
 
@@ -43,11 +134,29 @@ impl FrameEnv {
         color_buffer: &Texture,
         width: u32,
         height: u32,
+    ) -> Result<(), GLErrorWrapper> {
+        self.prepare_to_draw_into(color_buffer, None, width, height)
+    }
+
+    /// Like [FrameEnv::prepare_to_draw], but attach `depth_image` instead of the
+    /// depth_buffer when there is one, e.g. the eye's
+    /// [OpenXRComponent::depth_layer](gl_thin::openxr_helpers::OpenXRComponent::depth_layer)
+    /// image.  It has to have a stencil if [FrameEnv::stencil] does.
+    pub fn prepare_to_draw_into(
+        &self,
+        color_buffer: &Texture,
+        depth_image: Option<&Texture>,
+        width: u32,
+        height: u32,
     ) -> Result<(), GLErrorWrapper> {
         self.frame_buffer.bind()?;
         color_buffer.attach(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, 0)?;
-        self.depth_buffer
-            .attach(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::TEXTURE_2D, 0)?;
+        depth_image.unwrap_or(&self.depth_buffer).attach(
+            gl::FRAMEBUFFER,
+            self.depth_attachment(),
+            gl::TEXTURE_2D,
+            0,
+        )?;
 
         unsafe { gl::Viewport(0, 0, width as GLsizei, height as GLsizei) }; // XXX
         explode_if_gl_error()?;
@@ -58,10 +167,77 @@ impl FrameEnv {
         }
         Ok(())
     }
+
+    /// Unless the depth is `submitted` to the compositor along with the color, drop it
+    /// before it is written back.
+    pub fn finish_drawing(&self, submitted: bool) -> Result<(), GLErrorWrapper> {
+        if submitted {
+            return Ok(());
+        }
+        self.frame_buffer.invalidate(&[self.depth_attachment()])
+    }
+
+    /// Push the bound depth out to the far plane, for a submitted depth image the scene was
+    /// not drawn into (e.g. it was drawn off screen and copied in), so the compositor
+    /// treats the image as it would one without depth.  Call after
+    /// [FrameEnv::prepare_to_draw_into].
+    pub fn clear_depth(&self) -> Result<(), GLErrorWrapper> {
+        unsafe {
+            gl::DepthMask(gl::TRUE);
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+        }
+        explode_if_gl_error()
+    }
+
+    fn depth_attachment(&self) -> GLenum {
+        if self.stencil {
+            gl::DEPTH_STENCIL_ATTACHMENT
+        } else {
+            gl::DEPTH_ATTACHMENT
+        }
+    }
 }
 
 //
 
+fn export_scene(document: &GltfDocument, dir: &Path) -> std::io::Result<PathBuf> {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let path = dir.join(format!("scene-{}.glb", seconds));
+    document.write_glb(&path)?;
+    Ok(path)
+}
+
+/// from the controller along its -Z axis
+fn controller_ray(location: &SpaceLocation) -> (XrVector3f, XrVector3f) {
+    let origin = location.pose.position.into();
+    let rotation = xr_matrix4x4f_create_from_quaternion(&location.pose.orientation.into());
+    let direction = xr_matrix4x4f_transform_vector3f(&rotation, &XrVector3f::new(0.0, 0.0, -1.0));
+    (origin, direction)
+}
+
+/// `location` moved from the tracking space into the world, see [ActiveRenderer::world_scale]
+fn to_world_location(scale: &WorldScale, mut location: SpaceLocation) -> SpaceLocation {
+    location.pose = scale.to_world_pose(location.pose);
+    location
+}
+
+/// where the [Ui::minimap] is shown: above the controller, tilted toward the user
+fn minimap_model(controller: &SpaceLocation) -> XrMatrix4x4f {
+    Pose::from(controller.pose).matrix()
+        * xr_matrix4x4f_create_translation(0.0, 0.1, 0.0)
+        * matrix_rotation_about_x(-FRAC_PI_4)
+}
+
+/// every portal's view of the current eye, see [ActiveRenderer::paint_one_view]
+const PORTAL_VIEWS: ResourceId = ResourceId("portal views");
+/// every mirror's reflection for the current eye
+const MIRROR_VIEWS: ResourceId = ResourceId("mirror views");
+const EYE_IMAGE: ResourceId = ResourceId("eye image");
+/// see [ActiveRenderer::motion_vectors]
+const MOTION_VECTORS: ResourceId = ResourceId("motion vectors");
+
 pub fn skybox_view_matrix(rotation: &XrQuaternionf) -> XrMatrix4x4f {
     let scale = XrVector3f::default_scale();
     let view_matrix = xr_matrix4x4f_create_translation_rotation_scale(
@@ -72,32 +248,324 @@ pub fn skybox_view_matrix(rotation: &XrQuaternionf) -> XrMatrix4x4f {
     xr_matrix4x4f_invert_rigid_body(&view_matrix)
 }
 
+/// per-frame data gathered before painting and shared by every view
+pub struct FrameData<'g> {
+    pub controller_1: Option<SpaceLocation>,
+    pub remote_avatars: Vec<RemoteAvatar>,
+    /// what a tracked spectator camera is following
+    pub spectator_tracked: Option<Pose>,
+    /// the eyes' orientation from locate_views, for the ambisonic decoder
+    pub view_orientation: Option<XrQuaternionf>,
+    /// of the eye buffers, from the views' field of view, for [Ui::set_density]
+    pub pixels_per_radian: Option<f32>,
+    /// for [ActiveRenderer::hand_occlusion]
+    pub hand_poses: Option<HandPoses>,
+    /// [FrameScheduler::frame_index] of this frame, for effects with history
+    pub frame_index: u64,
+    /// when the views and spaces are located, see [OpenXRComponent::pose_time]
+    pub pose_time: Time,
+    /// how many views have been painted so far
+    pub views_painted: usize,
+    /// from [MyScene::motion_models], while [ActiveRenderer::motion_vectors] is on
+    pub motion_models: Option<Vec<(MotionId, XrMatrix4x4f)>>,
+    pub gpu_state: &'g mut GPUState,
+    /// the first thing that went wrong, returned from [ActiveRenderer::draw_inner] once the
+    /// frame is submitted; the paint callbacks can not return it themselves
+    pub error: Option<XrErrorWrapped>,
+}
+
+impl FrameData<'_> {
+    /// keep `e` unless something already failed this frame
+    fn fail(&mut self, e: Box<dyn Error>) {
+        if self.error.is_none() {
+            self.error = Some(match e.downcast::<XrErrorWrapped>() {
+                Ok(e) => *e,
+                Err(e) => XrErrorWrapped::simple(e.to_string()),
+            });
+        }
+    }
+}
+
 pub struct ActiveRenderer {
     pub frame_env: FrameEnv,
     pub scene: MyScene,
+    /// every scene [ActiveRenderer::load_scene] can replace [ActiveRenderer::scene] with
+    pub scenes: SceneStack,
     pub openxr: OpenXRComponent<openxr::OpenGlEs>,
     pub gpu_state: GPUState,
+    /// optional multi-user pose sharing, see [ActiveRenderer::enable_pose_streaming]
+    pub pose_stream: Option<PoseStreamer>,
+    /// optional camera preview, see [ActiveRenderer::enable_passthrough_camera]
+    pub camera: Option<PassthroughCamera>,
+    /// optional third-person view, see [ActiveRenderer::enable_spectator_camera]
+    pub spectator: Option<SpectatorCamera>,
+    /// see [ActiveRenderer::add_portal]
+    pub portals: Vec<Portal>,
+    /// see [ActiveRenderer::add_floor_mirror]
+    pub mirrors: Vec<PlanarMirror>,
+    /// widgets, see [ActiveRenderer::enable_ui]
+    pub ui: Option<Ui>,
+    /// runtime permissions for subsystems that need them, see [ActiveRenderer::enable_permission_tracking]
+    pub permissions: Option<PermissionTracker>,
+    /// optional audio input feeding [MyScene::audio], see [ActiveRenderer::enable_microphone]
+    pub microphone: Option<Microphone>,
+    /// a runtime-composited screen, see [ActiveRenderer::enable_curved_screen]
+    pub curved_screen: Option<CurvedScreen>,
+    /// real hands hiding virtual things, see [ActiveRenderer::enable_hand_occlusion]
+    pub hand_occlusion: Option<HandOcclusion>,
+    /// full-screen effects over each eye, see [ActiveRenderer::enable_temporal_aa] and
+    /// [ActiveRenderer::enable_hdr]
+    pub post: Option<PostChain>,
+    /// multisampled eyes, see [ActiveRenderer::enable_msaa]
+    pub msaa: Option<MsaaResolver>,
+    /// both eyes drawn in one pass, see [StartupOptions::multiview]
+    pub multiview: Option<MultiviewFrameBuffer>,
+    /// per-pixel motion of each eye, see [ActiveRenderer::enable_motion_vectors]
+    pub motion_vectors: Option<MotionVectors>,
+    /// fills the space warp depth images, see [ActiveRenderer::enable_space_warp]
+    space_warp_depth: Option<DepthCopy>,
+    /// positional sound output, see [ActiveRenderer::enable_spatial_audio]
+    pub spatial_audio: Option<SpatialAudio>,
+    /// battery and thermal state, see [ActiveRenderer::enable_device_status]
+    pub device_status: Option<DeviceStatusMonitor>,
+    /// asset packs fetched in the background, see [ActiveRenderer::enable_downloads]
+    pub downloads: Option<DownloadManager>,
+    /// controller connection and battery, see [ActiveRenderer::enable_controller_status]
+    pub controller_status: Option<ControllerStatusMonitor>,
+    /// how much optional work (spectator, portals, mirrors) to do, and the CPU/GPU levels to ask for
+    pub quality: QualityGovernor,
+    /// frame timing and timers that run on the render thread
+    pub scheduler: FrameScheduler<ActiveRenderer>,
+    /// application code run at fixed points of every frame
+    pub hooks: FrameHooks<ActiveRenderer>,
+    /// callbacks for the headset coming off and going back on, see
+    /// [ActiveRenderer::enable_auto_pause]
+    pub presence: PresenceMonitor<ActiveRenderer>,
+    /// see [ActiveRenderer::enable_profiler_report]
+    pub profiler: Profiler,
+    /// painted strokes and developer menu edits, stepped through with the left X and Y buttons
+    pub undo: UndoStack<ActiveRenderer>,
+    /// clicks, long presses and chords from this frame's buttons
+    pub gestures: GestureDetector,
+    /// controller vibration patterns, played by [ActiveRenderer::handle_events_and_draw]
+    pub haptics: HapticSequencer,
+    /// where [ActiveRenderer::request_scene_export] writes, see [ActiveRenderer::enable_scene_export]
+    pub scene_export_dir: Option<PathBuf>,
+    /// telemetry hooks, silent until an app plugs in a sink
+    pub analytics: Analytics,
+    /// see [ActiveRenderer::enable_watchdog]
+    pub watchdog: Option<Watchdog>,
+    /// shared with the watchdog thread, which restarts through it, see [Drawable::set_restart_signal]
+    restart_signal: Arc<Mutex<Option<RestartSignal>>>,
+    /// what the user chose, kept across launches, see [ActiveRenderer::enable_height_calibration]
+    pub settings: Option<UserSettings>,
+    /// eye height and seated mode, see [ActiveRenderer::enable_height_calibration]
+    pub height_calibration: Option<HeightCalibration>,
+    /// applied to every tracked pose before anything uses it, see
+    /// [ActiveRenderer::set_world_scale]
+    pub world_scale: WorldScale,
+    /// the controllers' buttons and axes as of this frame's sync
+    pub input_state: InputState,
 
     inputs: XrInputs,
+    egl_display: *mut c_void,
+    /// Dropping it destroys the context, so it lives as long as the session that renders with
+    /// it.  The render thread takes it over, see [crate::render_thread].
+    _egl_context: PossiblyCurrentContext,
+    /// the VIEW reference space, used to find the head pose
+    view_space: Space,
+    /// a LOCAL space of our own, like [OpenXRComponent::xr_space] which is busy while the
+    /// views are painted; [Hud] elements locate the head in it just before they draw
+    late_space: Space,
+    spectator_space: Option<Space>,
+    /// head poses we rendered with, waiting for their display time to pass so they can be
+    /// compared with where the head really was
+    rendered_heads: VecDeque<(Time, Posef)>,
+    /// [MyScene::motion_models]'s list, lent to each frame and filled again
+    motion_models: Vec<(MotionId, XrMatrix4x4f)>,
+    scene_export_requested: bool,
+    /// the eye and region to read back, see [ActiveRenderer::request_swapchain_readback]
+    swapchain_readback: Option<(usize, PixelRegion)>,
+    swapchain_snapshot: Option<SwapchainSnapshot>,
+    /// the watchdog caught the frame loop stalling, see [Drawable::wants_restart]
+    restart_requested: bool,
+    /// the OpenXR session or instance is gone, see [Drawable::runtime_lost]
+    runtime_lost: bool,
 }
 
 impl Drawable for ActiveRenderer {
     fn handle_events_and_draw(&mut self) {
+        if self.runtime_lost {
+            return;
+        }
         // The event handling loop should probably be more sophisticated than this.
-        self.openxr.poll_till_no_events().unwrap();
+        match self.openxr.poll_till_no_events() {
+            Ok(LoopStatus::RuntimeLost) => return self.lose_runtime("the runtime said so"),
+            Ok(_) => {}
+            Err(e) => log::warn!("failed to poll for events {:?}", e),
+        }
+        for notification in self.openxr.take_performance_notifications() {
+            self.quality.performance_notification(&notification);
+        }
+        let session_state = self.openxr.session_state();
+        PresenceMonitor::update(self, session_state, |renderer| &mut renderer.presence);
+        if self.openxr.take_interaction_profile_changed() {
+            if let Some(monitor) = &mut self.controller_status {
+                monitor.interaction_profile_changed();
+            }
+        }
+        if self.openxr.swapchains_stale() {
+            if let Err(e) = self.recreate_swapchains() {
+                log::warn!("swapchain recreation malfunction {}", e);
+            }
+        }
 
-        //
+        FrameScheduler::run_due(self, Instant::now(), |renderer| &mut renderer.scheduler);
+        FrameHooks::run(self, HookStage::PreUpdate, |renderer| &mut renderer.hooks);
 
         match self.draw_inner() {
-            Ok(_) => {}
+            Ok(_) => FrameHooks::run(self, HookStage::PostRender, |renderer| &mut renderer.hooks),
+            Err(e) if e.runtime_lost() => return self.lose_runtime(&e.to_string()),
             Err(e) => {
                 log::error!("malfunction during draw_inner() {}", e);
+                self.analytics.error("draw", &e);
+                self.scheduler.frame_failed();
+                self.openxr.heartbeat.idle();
             }
         };
+
+        if let Some(stall) = self.watchdog.as_ref().and_then(Watchdog::take_stall) {
+            log::error!(
+                "frame loop was stuck in {} for {:.1}s, restarting the session",
+                stall.phase,
+                stall.elapsed.as_secs_f32()
+            );
+            self.profiler.log_report();
+            self.restart_requested = true;
+        }
+    }
+
+    fn wants_restart(&self) -> bool {
+        self.restart_requested || self.runtime_lost
+    }
+
+    fn runtime_lost(&self) -> bool {
+        self.runtime_lost
+    }
+
+    fn set_restart_signal(&mut self, signal: RestartSignal) {
+        *self
+            .restart_signal
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(signal);
+    }
+
+    fn wake_at(&self) -> Option<Instant> {
+        self.scheduler.wake_at()
     }
 
     fn suspend(&mut self) {
-        self.openxr.xr_session.request_exit().unwrap();
+        self.analytics.flush();
+        // a lost session can not be ended, only dropped
+        if self.runtime_lost {
+            return;
+        }
+        if let Err(e) = self.openxr.xr_session.request_exit() {
+            log::warn!("failed to request session exit {:?}", e);
+        }
+    }
+
+    fn focus_changed(&mut self, focused: bool) {
+        if let Some(permissions) = &mut self.permissions {
+            if let Err(e) = permissions.focus_changed(focused) {
+                log::warn!("failed to re-check permissions {}", e);
+            }
+        }
+    }
+
+    fn keyboard_input(&mut self, event: &KeyEvent) {
+        let Some(ui) = &mut self.ui else {
+            return;
+        };
+        let Some(input) = text_input_for_key(event) else {
+            return;
+        };
+        if let Some((index, TextFieldEvent::Submitted(text))) = ui.handle_input(input) {
+            debug!("text field {} submitted {:?}", index, text);
+        }
+    }
+}
+
+/// Compare the head poses of frames that have been on screen for a while with where the
+/// runtime now says the head was at their display time.
+fn measure_photon_to_pose_error(
+    rendered_heads: &mut VecDeque<(Time, Posef)>,
+    profiler: &mut Profiler,
+    view_space: &Space,
+    base: &Space,
+) {
+    // the previous frame's display time is about now; give the runtime's pose history
+    // a couple of frames to settle
+    while rendered_heads.len() > 2 {
+        let Some((time, rendered)) = rendered_heads.pop_front() else {
+            break;
+        };
+        let Ok(actual) = view_space.locate(base, time) else {
+            continue;
+        };
+        let (a, b) = (&rendered.orientation, &actual.pose.orientation);
+        let dot = (a.x * b.x + a.y * b.y + a.z * b.z + a.w * b.w)
+            .abs()
+            .min(1.0);
+        profiler.record(
+            "photon-to-pose rotation error (degrees)",
+            2.0 * dot.acos().to_degrees(),
+        );
+        let (p, q) = (&rendered.position, &actual.pose.position);
+        let distance = ((p.x - q.x).powi(2) + (p.y - q.y).powi(2) + (p.z - q.z).powi(2)).sqrt();
+        profiler.record("photon-to-pose position error (mm)", distance * 1000.0);
+    }
+}
+
+/// names of the [Profiler] series with each frame's [GLCallCounts]
+const GL_COUNT_SERIES: [&str; 5] = [
+    "draw calls",
+    "triangles",
+    "texture binds",
+    "program switches",
+    "buffer uploads",
+];
+
+fn record_gl_counts(profiler: &mut Profiler, counts: &GLCallCounts) {
+    let values = [
+        counts.draw_calls as f32,
+        counts.triangles as f32,
+        counts.texture_binds as f32,
+        counts.program_switches as f32,
+        counts.buffer_uploads as f32,
+    ];
+    for (name, value) in GL_COUNT_SERIES.into_iter().zip(values) {
+        profiler.record(name, value);
+    }
+}
+
+/// what a key press means to a [crate::ui::text_field::TextField]
+fn text_input_for_key(event: &KeyEvent) -> Option<TextInput> {
+    if event.state != ElementState::Pressed {
+        return None;
+    }
+    match &event.logical_key {
+        Key::Named(NamedKey::Backspace) => Some(TextInput::Backspace),
+        Key::Named(NamedKey::Delete) => Some(TextInput::Delete),
+        Key::Named(NamedKey::ArrowLeft) => Some(TextInput::Left),
+        Key::Named(NamedKey::ArrowRight) => Some(TextInput::Right),
+        Key::Named(NamedKey::Home) => Some(TextInput::Home),
+        Key::Named(NamedKey::End) => Some(TextInput::End),
+        Key::Named(NamedKey::Enter) => Some(TextInput::Enter),
+        _ => event
+            .text
+            .as_ref()
+            .map(|text| TextInput::Insert(text.to_string())),
     }
 }
 
@@ -114,36 +582,945 @@ impl ActiveRenderer {
         builder.build()
     }
 
-    pub fn new(event_loop: &ActiveEventLoop) -> Result<Self, Box<dyn Error>> {
-        let (display_ptr, raw_context) = Self::build_android_egl_context(event_loop)?;
+    pub fn new(
+        event_loop: &ActiveEventLoop,
+        options: &StartupOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (egl_context, display_ptr, raw_context) = Self::build_android_egl_context(event_loop)?;
 
         let mut gpu_state = GPUState::new();
+        let caps = gl_caps();
 
-        let openxr =
-            OpenXRComponent::new_android(display_ptr as *mut c_void, raw_context as *mut c_void)?;
+        // catch spec violations while developing, where the validation layer is packaged
+        let xr_config = if options.validation {
+            XrConfig::new().with_validation()
+        } else {
+            XrConfig::new()
+        };
+        // the multiview pass has no room for the sRGB encoding pass
+        let multiview = options.multiview && caps.srgb && multiview::is_supported();
+        if options.multiview && !multiview {
+            log::warn!("no GL_OVR_multiview2 or sRGB framebuffers; drawing each eye on its own");
+        }
+        let xr_config = if multiview {
+            // before the scene compiles its shaders, so they get multiview variants
+            multiview::enable_shader_variants();
+            xr_config.with_multiview()
+        } else {
+            xr_config
+        };
+        let openxr = OpenXRComponent::new_android(
+            display_ptr as *mut c_void,
+            raw_context as *mut c_void,
+            &xr_config,
+        )?;
 
         let vcv0 = openxr.view_config_views[0];
         let frame_env = FrameEnv::new(
             vcv0.recommended_image_rect_width,
             vcv0.recommended_image_rect_height,
+            true,
             &mut gpu_state,
         )?;
-        let scene = MyScene::new(&mut gpu_state)?;
+        let multiview = if openxr.is_multiview() {
+            Some(MultiviewFrameBuffer::new(
+                vcv0.recommended_image_rect_width,
+                vcv0.recommended_image_rect_height,
+                frame_env.stencil,
+                &mut gpu_state,
+            )?)
+        } else {
+            None
+        };
+        let mut scenes = SceneStack::builtin();
+        let scene_index = scenes.find(&options.scene).unwrap_or_else(|| {
+            log::warn!(
+                "no scene named {:?}, loading the {} one",
+                options.scene,
+                DEFAULT_SCENE
+            );
+            scenes.find(DEFAULT_SCENE).unwrap_or(0)
+        });
+        let scene_start = Instant::now();
+        let mut scene = scenes.build(scene_index, &mut gpu_state)?;
+        scene.update_ambient();
+        scenes.push(scene_index);
+        let mut analytics = Analytics::new();
+        analytics.record(AnalyticsEvent::SceneLoaded {
+            name: scenes.entries()[scene_index].name.clone(),
+            load_time: scene_start.elapsed(),
+        });
 
         let inputs = XrInputs::new(&openxr.xr_instance, &openxr.xr_session)?;
 
-        Ok(Self {
+        let view_space = {
+            let mut posef = Posef::default();
+            posef.orientation.w = 1.0;
+            openxr
+                .xr_session
+                .create_reference_space(ReferenceSpaceType::VIEW, posef)
+                .annotate_if_err(Some(&openxr.xr_instance), "failed to create view space")?
+        };
+        let late_space = {
+            let mut posef = Posef::default();
+            posef.orientation.w = 1.0;
+            openxr
+                .xr_session
+                .create_reference_space(ReferenceSpaceType::LOCAL, posef)
+                .annotate_if_err(Some(&openxr.xr_instance), "failed to create late space")?
+        };
+
+        let mut scheduler = FrameScheduler::new();
+        // a change to the system's resolution setting comes without an event
+        scheduler.every(Duration::from_secs(10), |renderer: &mut ActiveRenderer| {
+            if let Err(e) = renderer.openxr.check_recommended_size() {
+                log::warn!("failed to check the recommended size {}", e);
+            }
+        });
+
+        // the sky's colors drift with the time of day; the ambient light follows
+        scheduler.every(Duration::from_secs(1), |renderer: &mut ActiveRenderer| {
+            renderer.scene.update_ambient();
+        });
+
+        // without sRGB framebuffers the swapchain is RGBA8 and expects encoded values
+        let post = if caps.srgb {
+            None
+        } else {
+            log::warn!("{}; encoding sRGB in a last pass", caps);
+            let mut post = PostChain::new();
+            post.enable_srgb_encode(&mut gpu_state)?;
+            Some(post)
+        };
+
+        let mut renderer = Self {
             frame_env,
             scene,
+            scenes,
             openxr,
             gpu_state,
+            pose_stream: None,
+            camera: None,
+            spectator: None,
+            portals: vec![],
+            mirrors: vec![],
+            ui: None,
+            permissions: None,
+            microphone: None,
+            curved_screen: None,
+            hand_occlusion: None,
+            post,
+            msaa: None,
+            multiview,
+            motion_vectors: None,
+            space_warp_depth: None,
+            spatial_audio: None,
+            device_status: None,
+            downloads: None,
+            controller_status: None,
+            quality: QualityGovernor::new(),
+            scheduler,
+            hooks: FrameHooks::new(),
+            presence: PresenceMonitor::new(),
+            profiler: Profiler::default(),
+            undo: UndoStack::new(50),
+            gestures: GestureDetector::new(),
+            haptics: HapticSequencer::new(),
+            scene_export_dir: None,
+            analytics,
+            watchdog: None,
+            restart_signal: Arc::default(),
+            settings: None,
+            height_calibration: None,
+            world_scale: WorldScale::default(),
+            input_state: InputState::default(),
             inputs,
-        })
+            egl_display: display_ptr as *mut c_void,
+            _egl_context: egl_context,
+            view_space,
+            late_space,
+            spectator_space: None,
+            rendered_heads: VecDeque::new(),
+            motion_models: vec![],
+            scene_export_requested: false,
+            swapchain_readback: None,
+            swapchain_snapshot: None,
+            restart_requested: false,
+            runtime_lost: false,
+        };
+        renderer.apply_startup_options(options)?;
+        renderer.warm_up_scene();
+        Ok(renderer)
+    }
+
+    /// what [StartupOptions] asks for beyond the XR instance
+    fn apply_startup_options(&mut self, options: &StartupOptions) -> Result<(), GLErrorWrapper> {
+        // the frame loop rebuilds the swapchains before the first frame
+        self.openxr.set_render_scale(options.render_scale);
+        if options.prediction_offset_ms != 0.0 {
+            let nanos = (options.prediction_offset_ms * 1e6) as i64;
+            let offset = self
+                .openxr
+                .set_prediction_offset(openxr::Duration::from_nanos(nanos));
+            log::info!("predicting poses {}ns later", offset.as_nanos());
+        }
+        if options.gl_counts_hud {
+            self.enable_gl_counts_hud()?;
+        }
+        if let Some(period) = options.profiler_report {
+            self.enable_profiler_report(period);
+        }
+        Ok(())
+    }
+
+    /// Start publishing our head/controller poses and drawing the avatars of remote peers.
+    pub fn enable_pose_streaming(&mut self, config: PoseStreamConfig) -> std::io::Result<()> {
+        self.pose_stream = Some(PoseStreamer::new(config)?);
+        Ok(())
+    }
+
+    /// Show the headset camera feed.  The permission dialog pops up on the next frame,
+    /// and the preview appears once the user agrees.
+    pub fn enable_passthrough_camera(
+        &mut self,
+        app: &AndroidApp,
+        config: PassthroughCameraConfig,
+    ) -> Result<(), GLErrorWrapper> {
+        self.camera = Some(PassthroughCamera::new(
+            app,
+            config,
+            self.egl_display,
+            &mut self.gpu_state,
+        )?);
+        Ok(())
+    }
+
+    /// Also render the scene from a spectator camera into an offscreen target.
+    /// `tracked_space` is what a [crate::spectator::SpectatorMount::Tracked] camera follows.
+    pub fn enable_spectator_camera(
+        &mut self,
+        config: SpectatorConfig,
+        tracked_space: Option<Space>,
+    ) -> Result<(), GLErrorWrapper> {
+        self.spectator = Some(SpectatorCamera::new(config, &mut self.gpu_state)?);
+        self.spectator_space = tracked_space;
+        Ok(())
+    }
+
+    /// Add a `width`x`height` meter window at `entrance` showing the world as seen from `exit`
+    pub fn add_portal(
+        &mut self,
+        entrance: Pose,
+        exit: Pose,
+        width: f32,
+        height: f32,
+    ) -> Result<(), GLErrorWrapper> {
+        let vcv0 = self.openxr.view_config_views[0];
+        self.portals.push(Portal::new(
+            entrance,
+            exit,
+            width,
+            height,
+            vcv0.recommended_image_rect_width as i32,
+            vcv0.recommended_image_rect_height as i32,
+            &mut self.gpu_state,
+        )?);
+        Ok(())
+    }
+
+    /// Add a reflective `size`x`size` meter floor at height `y`
+    pub fn add_floor_mirror(&mut self, y: f32, size: f32) -> Result<(), GLErrorWrapper> {
+        let vcv0 = self.openxr.view_config_views[0];
+        self.mirrors.push(PlanarMirror::floor(
+            y,
+            size,
+            vcv0.recommended_image_rect_width as i32,
+            vcv0.recommended_image_rect_height as i32,
+            &mut self.gpu_state,
+        )?);
+        Ok(())
+    }
+
+    /// Start drawing [ActiveRenderer::ui]; add widgets to it afterwards.
+    pub fn enable_ui(&mut self) -> Result<&mut Ui, GLErrorWrapper> {
+        Ok(self.ui.insert(Ui::new(&mut self.gpu_state)?))
+    }
+
+    /// Show head-locked text, see [Hud::add].  Turns on [ActiveRenderer::ui] if it is not
+    /// already, and keeps the elements of a [Hud] that is.
+    pub fn enable_hud(&mut self) -> Result<&mut Hud, GLErrorWrapper> {
+        let ui = match self.ui.take() {
+            Some(ui) => ui,
+            None => Ui::new(&mut self.gpu_state)?,
+        };
+        let ui = self.ui.insert(ui);
+        let hud = match ui.hud.take() {
+            Some(hud) => hud,
+            None => Hud::new()?,
+        };
+        Ok(ui.hud.insert(hud))
+    }
+
+    /// Measure the room with the right trigger, see [Ruler].  Turns on [ActiveRenderer::ui]
+    /// if it is not already.  The trigger also paints, if painting is on.
+    pub fn enable_ruler(&mut self) -> Result<&mut Ruler, GLErrorWrapper> {
+        let ui = match self.ui.take() {
+            Some(ui) => ui,
+            None => Ui::new(&mut self.gpu_state)?,
+        };
+        Ok(self.ui.insert(ui).ruler.insert(Ruler::new()?))
+    }
+
+    /// Type into the focused text field with the controller's ray, see [LaserKeyboard].
+    /// The keyboard floats `width` meters wide below eye level and shows only while a text
+    /// field has focus.  Turns on [ActiveRenderer::ui] if it is not already.
+    pub fn enable_laser_keyboard(
+        &mut self,
+        width: f32,
+    ) -> Result<&mut LaserKeyboard, GLErrorWrapper> {
+        let model = xr_matrix4x4f_create_translation(0.0, -0.35, -0.6);
+        let keyboard = LaserKeyboard::new(model, width)?;
+        let ui = match self.ui.take() {
+            Some(ui) => ui,
+            None => Ui::new(&mut self.gpu_state)?,
+        };
+        Ok(self.ui.insert(ui).keyboard.insert(keyboard))
+    }
+
+    /// While the user is away, with the headset off or the system UI in front, stop the sun
+    /// and the positional audio and show "Paused" in the [Hud].  Register more with
+    /// [PresenceMonitor::on_change] on [ActiveRenderer::presence].
+    pub fn enable_auto_pause(&mut self) -> Result<(), GLErrorWrapper> {
+        let offset = Pose::new(XrVector3f::new(0.0, 0.0, -0.8), XrQuaternionf::default());
+        let label = self.enable_hud()?.add("", offset, [1.0, 1.0, 1.0, 1.0]);
+        self.presence.on_change(move |renderer, paused| {
+            renderer.scene.time_of_day.set_paused(paused);
+            if let Some(audio) = &mut renderer.spatial_audio {
+                audio.paused = paused;
+            }
+            if let Some(hud) = renderer.ui.as_mut().and_then(|ui| ui.hud.as_mut()) {
+                hud.set_text(label, if paused { "Paused" } else { "" });
+            }
+        });
+        Ok(())
+    }
+
+    /// Track runtime permissions; subsystems call [PermissionTracker::request] and
+    /// [PermissionTracker::subscribe] on it.
+    pub fn enable_permission_tracking(&mut self, app: &AndroidApp) -> &mut PermissionTracker {
+        self.permissions.insert(PermissionTracker::new(app))
+    }
+
+    /// Listen to the microphone so the scene can react to sound.  This asks for RECORD_AUDIO,
+    /// and capture starts once the user agrees.
+    pub fn enable_microphone(&mut self, app: &AndroidApp) -> Result<(), jni::errors::Error> {
+        self.permissions
+            .get_or_insert_with(|| PermissionTracker::new(app))
+            .request(&[RECORD_AUDIO])?;
+        self.microphone = Some(Microphone::new());
+        Ok(())
+    }
+
+    /// Play positional sounds, muffled by the props when they are in the way.
+    pub fn enable_spatial_audio(&mut self) -> Result<&mut SpatialAudio, Box<dyn Error>> {
+        let mut audio = SpatialAudio::new()?;
+        self.scene.props.register_occluders(&mut audio.occluders)?;
+        Ok(self.spatial_audio.insert(audio))
+    }
+
+    /// Poll the battery and thermal state every few seconds.  Subscribe to the returned monitor
+    /// to shed load when the device heats up, or to warn the user about the battery.
+    pub fn enable_device_status(&mut self, app: &AndroidApp) -> &mut DeviceStatusMonitor {
+        self.scheduler.every(Duration::from_secs(5), |renderer| {
+            if let Some(monitor) = &mut renderer.device_status {
+                if let Err(e) = monitor.poll() {
+                    log::warn!("failed to read device status {}", e);
+                }
+            }
+        });
+        self.device_status.insert(DeviceStatusMonitor::new(app))
+    }
+
+    /// Fetch asset packs in the background with the returned manager.  While any are
+    /// downloading, the [Hud] shows how far along they are.
+    pub fn enable_downloads(
+        &mut self,
+        app: &AndroidApp,
+    ) -> Result<&mut DownloadManager, Box<dyn Error>> {
+        let offset = Pose::new(XrVector3f::new(0.0, -0.25, -0.8), XrQuaternionf::default());
+        let label = self.enable_hud()?.add("", offset, [1.0, 1.0, 1.0, 1.0]);
+        self.scheduler
+            .every(Duration::from_millis(250), move |renderer| {
+                let Some(downloads) = &mut renderer.downloads else {
+                    return;
+                };
+                downloads.poll();
+                let text = match downloads.progress() {
+                    _ if downloads.active_count() == 0 => String::new(),
+                    (received, Some(total)) if total > 0 => {
+                        format!("Downloading {}%", received * 100 / total)
+                    }
+                    (received, _) => format!("Downloading {} MB", received >> 20),
+                };
+                if let Some(hud) = renderer.ui.as_mut().and_then(|ui| ui.hud.as_mut()) {
+                    hud.set_text(label, &text);
+                }
+            });
+        Ok(self.downloads.insert(DownloadManager::new(app)?))
+    }
+
+    /// Watch the controllers connect, disconnect, lose tracking and run low.  Subscribe to the
+    /// returned monitor to warn the user before one dies mid-session.
+    pub fn enable_controller_status(&mut self) -> &mut ControllerStatusMonitor {
+        self.controller_status
+            .insert(ControllerStatusMonitor::new())
+    }
+
+    /// Log [ActiveRenderer::profiler]'s statistics every `period`, e.g. the photon-to-pose error
+    /// while tuning [OpenXRComponent::set_prediction_offset].
+    pub fn enable_profiler_report(&mut self, period: Duration) {
+        self.scheduler
+            .every(period, |renderer| renderer.profiler.log_report());
+    }
+
+    /// Show, in the [Hud], how many draws, triangles, texture binds, program switches and
+    /// buffer uploads a frame takes, averaged over [ActiveRenderer::profiler]'s window.
+    pub fn enable_gl_counts_hud(&mut self) -> Result<(), GLErrorWrapper> {
+        let offset = Pose::new(XrVector3f::new(0.0, -0.3, -0.8), XrQuaternionf::default());
+        let label = self.enable_hud()?.add("", offset, [0.6, 1.0, 0.6, 1.0]);
+        self.scheduler
+            .every(Duration::from_millis(500), move |renderer| {
+                let text = GL_COUNT_SERIES
+                    .iter()
+                    .filter_map(|name| {
+                        let stats = renderer.profiler.stats(name)?;
+                        Some(format!("{} {:.0}", name, stats.mean))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if let Some(hud) = renderer.ui.as_mut().and_then(|ui| ui.hud.as_mut()) {
+                    hud.set_text(label, &text);
+                }
+            });
+        Ok(())
+    }
+
+    /// Paint ribbons in the air with the controller while the trigger is held.
+    pub fn enable_painting(&mut self) -> Result<&mut Painter, GLErrorWrapper> {
+        Ok(self.scene.painting.insert(Painter::new()?))
+    }
+
+    /// Show a `width` x `height` equirectangular image around the viewer instead of the sky,
+    /// as a composition layer if the runtime supports one.  Fill it with [Panorama::update].
+    pub fn enable_panorama(
+        &mut self,
+        width: u32,
+        height: u32,
+    ) -> Result<&mut Panorama, Box<dyn Error>> {
+        let panorama = Panorama::new(&mut self.openxr, width, height, &mut self.gpu_state)?;
+        Ok(self.scene.panorama.insert(panorama))
+    }
+
+    /// Submit the sky as a `size` pixel cube layer instead of drawing it into the eye
+    /// buffers.  False if the runtime has no cube layers, in which case nothing changes.
+    /// While the time of day runs, the faces are drawn again every `repaint` period.
+    pub fn enable_sky_layer(
+        &mut self,
+        size: u32,
+        repaint: Duration,
+    ) -> Result<bool, Box<dyn Error>> {
+        let scene = &mut self.scene;
+        if !scene.sky.use_cube_layer(
+            &mut self.openxr,
+            size,
+            &scene.time_of_day,
+            &mut self.gpu_state,
+        )? {
+            return Ok(false);
+        }
+        if scene.time_of_day.day_length.is_some() {
+            self.scheduler
+                .every(repaint, |renderer: &mut ActiveRenderer| {
+                    let scene = &renderer.scene;
+                    if let Err(e) = scene.sky.repaint_cube_layer(
+                        &mut renderer.openxr,
+                        &scene.time_of_day,
+                        &mut renderer.gpu_state,
+                    ) {
+                        log::warn!("failed to repaint the sky layer {}", e);
+                    }
+                });
+        }
+        Ok(true)
+    }
+
+    /// Aim an arc with the right thumbstick held down and jump to where it lands when it is
+    /// let go, as long as that is on `navmesh`.
+    pub fn enable_teleport(&mut self, navmesh: NavMesh) -> Result<&mut Teleport, GLErrorWrapper> {
+        Ok(self.scene.teleport.insert(Teleport::new(navmesh)?))
+    }
+
+    /// Hold a torch in the right hand: a spot light in [MyScene::lights] that follows the
+    /// controller, with its beam drawn in the air.  A switches it on and off.
+    pub fn enable_flashlight(&mut self) -> Result<&mut Flashlight, GLErrorWrapper> {
+        Ok(self
+            .scene
+            .flashlight
+            .insert(Flashlight::new(&mut self.gpu_state)?))
+    }
+
+    /// [ActiveRenderer::enable_flashlight], casting shadows from a `size` pixel depth map.
+    pub fn enable_shadowed_flashlight(
+        &mut self,
+        size: GLsizei,
+    ) -> Result<&mut Flashlight, GLErrorWrapper> {
+        let mut flashlight = Flashlight::new(&mut self.gpu_state)?;
+        flashlight.enable_shadows(size, &mut self.gpu_state)?;
+        Ok(self.scene.flashlight.insert(flashlight))
+    }
+
+    /// Move the user so they stand at `target` with their feet on it: horizontally the head
+    /// goes over it, vertically the floor under `head` (if the navmesh has one) goes to it.
+    fn teleport_to(
+        &mut self,
+        target: &XrVector3f,
+        head: &XrVector3f,
+    ) -> Result<(), XrErrorWrapped> {
+        let ground = self
+            .scene
+            .teleport
+            .as_ref()
+            .and_then(|teleport| {
+                teleport
+                    .navmesh
+                    .ground_below(&[head.x, head.y, head.z], 0.0, 2.5)
+            })
+            .map_or(target.y, |hit| hit.position[1]);
+        let delta = XrVector3f::new(target.x - head.x, target.y - ground, target.z - head.z);
+        self.openxr
+            .move_user(&self.world_scale.to_real_vector(&delta))?;
+        self.sync_late_space()?;
+        self.haptics.play(HapticHand::Right, HapticPattern::click());
+        Ok(())
+    }
+
+    /// After the user was moved, the HUD's space has to agree with the one everything else
+    /// is located in.
+    fn sync_late_space(&mut self) -> Result<(), XrErrorWrapped> {
+        self.late_space = self
+            .openxr
+            .xr_session
+            .create_reference_space(ReferenceSpaceType::LOCAL, self.openxr.space_origin())
+            .annotate_if_err(Some(&self.openxr.xr_instance), "failed to move late space")?;
+        Ok(())
+    }
+
+    /// Make the user `factor` times their size relative to the world (the world looks
+    /// `1 / factor` its size), growing or shrinking it around `world_point`, e.g. the floor
+    /// under the user.  Eyes, controllers and hands all move apart by `factor`.
+    pub fn set_world_scale(&mut self, factor: f32, world_point: &XrVector3f) {
+        self.world_scale = self.world_scale.rescaled(factor, world_point);
+        self.update_depth_planes();
+    }
+
+    /// Measure the user's eye height above the floor and, in [PlayMode::Seated], lift them to
+    /// their standing height.  The mode and heights are kept in `settings.txt` and applied
+    /// again at the next launch; nothing is measured until [ActiveRenderer::calibrate_height].
+    pub fn enable_height_calibration(
+        &mut self,
+        app: &AndroidApp,
+    ) -> Result<&mut HeightCalibration, Box<dyn Error>> {
+        if self.settings.is_none() {
+            self.settings = Some(UserSettings::load_for(app)?);
+        }
+        let mut calibration = HeightCalibration::new(&self.openxr.xr_session).annotate_if_err(
+            Some(&self.openxr.xr_instance),
+            "failed to create stage space",
+        )?;
+        if let Some(settings) = &self.settings {
+            calibration.load(settings);
+        }
+        self.height_calibration = Some(calibration);
+        self.apply_seated_offset()?;
+        Ok(self.height_calibration.as_mut().unwrap())
+    }
+
+    /// Measure the eye height on the next frame; the user should be sitting or standing up
+    /// straight, as the current [PlayMode] says.
+    pub fn calibrate_height(&mut self) {
+        match &mut self.height_calibration {
+            Some(calibration) => calibration.request(),
+            None => log::warn!("height calibration requested, but it was never enabled"),
+        }
+    }
+
+    /// Switch between standing and seated.  The first time seated, the seated eye height is
+    /// measured on the next frame.
+    pub fn set_play_mode(&mut self, mode: PlayMode) {
+        let Some(calibration) = &mut self.height_calibration else {
+            return;
+        };
+        calibration.mode = mode;
+        if mode == PlayMode::Seated && calibration.seated_eye_height.is_none() {
+            calibration.request();
+        }
+        self.height_calibrated();
+    }
+
+    /// Move the user to match a new measurement or mode, and remember it.
+    fn height_calibrated(&mut self) {
+        if let Err(e) = self.apply_seated_offset() {
+            log::warn!("failed to apply the seated offset {}", e);
+        }
+        let (Some(calibration), Some(settings)) = (&self.height_calibration, &mut self.settings)
+        else {
+            return;
+        };
+        calibration.store(settings);
+        if let Err(e) = settings.save() {
+            log::warn!("failed to save settings {}", e);
+        }
+    }
+
+    fn apply_seated_offset(&mut self) -> Result<(), XrErrorWrapped> {
+        let Some(calibration) = &mut self.height_calibration else {
+            return Ok(());
+        };
+        let lift = calibration.take_adjustment();
+        if lift == 0.0 {
+            return Ok(());
+        }
+        self.openxr.move_user(&XrVector3f::new(0.0, lift, 0.0))?;
+        self.sync_late_space()
+    }
+
+    /// Hang reference swatches and gray ramps in front of the user and check, by reading them
+    /// back from an offscreen copy, that the swapchain format and texture settings show them
+    /// with the right colors.  The report is logged as well as returned.
+    pub fn enable_color_check(&mut self) -> Result<ColorCheckReport, GLErrorWrapper> {
+        let color_check = ColorCheck::new(&mut self.gpu_state)?;
+        let report = color_check.run(self.openxr.swapchain_format, &mut self.gpu_state)?;
+        report.log();
+        self.scene.color_check = Some(color_check);
+        Ok(report)
+    }
+
+    /// Time uniform uploads, buffer binding and text rasterization on this device and log
+    /// the results, to compare between builds.  Takes a moment, so call it between frames.
+    pub fn run_microbenchmarks(&mut self) -> Result<MicroBench, GLErrorWrapper> {
+        let bench = run_gl_suite(&mut self.gpu_state)?;
+        bench.log();
+        Ok(bench)
+    }
+
+    /// Replace [ActiveRenderer::scene] with scene `index` of [ActiveRenderer::scenes], and
+    /// remember the one it replaces for [ActiveRenderer::previous_scene].  What was enabled on
+    /// the old scene (a panorama, the sky layer, painting) goes with it.
+    pub fn load_scene(&mut self, index: usize) -> Result<(), Box<dyn Error>> {
+        self.replace_scene(index)?;
+        self.scenes.push(index);
+        Ok(())
+    }
+
+    /// Go back to the scene shown before the current one, if there was one.
+    pub fn previous_scene(&mut self) -> Result<bool, Box<dyn Error>> {
+        match self.scenes.pop() {
+            Some(index) => {
+                self.replace_scene(index)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn replace_scene(&mut self, index: usize) -> Result<(), Box<dyn Error>> {
+        let start = Instant::now();
+        let scene = self.scenes.build(index, &mut self.gpu_state)?;
+        self.scene.sky.remove_cube_layer(&mut self.openxr);
+        if let Some(panorama) = &self.scene.panorama {
+            panorama.remove_layer(&mut self.openxr);
+        }
+        self.scene = scene;
+        self.scene.update_ambient();
+        self.warm_up_scene();
+        // the strokes and such it would undo are gone
+        self.undo.clear();
+        if let Some(audio) = &mut self.spatial_audio {
+            audio.occluders.clear();
+            self.scene.props.register_occluders(&mut audio.occluders)?;
+        }
+        self.analytics.record(AnalyticsEvent::SceneLoaded {
+            name: self.scenes.entries()[index].name.clone(),
+            load_time: start.elapsed(),
+        });
+        Ok(())
+    }
+
+    /// Draw the scene once offscreen, see [warm_up].  Failing only costs the hitches it would
+    /// have saved.
+    fn warm_up_scene(&mut self) {
+        let lod_height = self.openxr.view_config_views[0].recommended_image_rect_height as i32;
+        match warm_up(
+            &self.scene,
+            self.ui.as_ref(),
+            lod_height,
+            &mut self.gpu_state,
+        ) {
+            Ok(elapsed) => log::info!("scene warmed up in {:?}", elapsed),
+            Err(e) => log::warn!("scene warmup malfunction {}", e),
+        }
+    }
+
+    /// Float a list of [ActiveRenderer::scenes] in front of the user, shown while the menu
+    /// is open; picking one with the controller loads it and closes the menu.  It opens now,
+    /// and the thumbnails fill in over the next frames.
+    pub fn enable_launcher(&mut self) -> Result<&mut SceneLauncher, GLErrorWrapper> {
+        let model = xr_matrix4x4f_create_translation(0.0, -0.1, -1.0);
+        let mut launcher = SceneLauncher::new(model, &self.scenes)?;
+        launcher.set_visible(true);
+        self.inputs.set_active(InputContext::Menu, true);
+        self.scheduler
+            .after(Duration::ZERO, |renderer: &mut ActiveRenderer| {
+                renderer.bake_launcher_thumbnail(0)
+            });
+        let ui = match self.ui.take() {
+            Some(ui) => ui,
+            None => Ui::new(&mut self.gpu_state)?,
+        };
+        Ok(self.ui.insert(ui).launcher.insert(launcher))
+    }
+
+    /// Put a `pixel_width` x `pixel_height` screen on a cylinder around the starting head
+    /// position, `width` meters wide along an arc of `central_angle` radians.  Fill it with
+    /// [CurvedScreen::update]; the controller's ray is tested against it every frame.
+    pub fn enable_curved_screen(
+        &mut self,
+        width: f32,
+        central_angle: f32,
+        pixel_width: u32,
+        pixel_height: u32,
+    ) -> Result<&mut CurvedScreen, Box<dyn Error>> {
+        let mut pose = Posef::default();
+        pose.orientation.w = 1.0;
+        let screen = CurvedScreen::new(
+            &mut self.openxr,
+            pose,
+            width,
+            central_angle,
+            pixel_width,
+            pixel_height,
+        )?;
+        Ok(self.curved_screen.insert(screen))
+    }
+
+    /// Float a map of the props and strokes within `half_extent` meters of the user above
+    /// the controller, redrawn every second from a `pixels` square render.  The map is a
+    /// [Ui] panel, so this turns on [ActiveRenderer::ui] if it is not already.
+    pub fn enable_minimap(
+        &mut self,
+        pixels: i32,
+        half_extent: f32,
+    ) -> Result<&mut Minimap, GLErrorWrapper> {
+        let ui = match self.ui.take() {
+            Some(ui) => ui,
+            None => Ui::new(&mut self.gpu_state)?,
+        };
+        let minimap = Minimap::new(pixels, half_extent, 0.12, &mut self.gpu_state)?;
+        Ok(self.ui.insert(ui).minimap.insert(minimap))
+    }
+
+    /// Write the tracked hands into the depth buffer before the scene, so the real hands in
+    /// passthrough cover virtual things behind them.  Needs XR_FB_hand_tracking_mesh.
+    pub fn enable_hand_occlusion(&mut self) -> Result<(), Box<dyn Error>> {
+        self.hand_occlusion = Some(HandOcclusion::new(&self.openxr, &mut self.gpu_state)?);
+        Ok(())
+    }
+
+    /// Add [TemporalAa] to the [ActiveRenderer::post] chain.  It only runs for scenes that
+    /// ask for it with [MyScene::anti_flicker].
+    pub fn enable_temporal_aa(&mut self) -> Result<(), GLErrorWrapper> {
+        let taa = TemporalAa::new(&mut self.gpu_state)?;
+        self.post.get_or_insert_with(PostChain::new).add(taa);
+        Ok(())
+    }
+
+    /// Draw the eyes in half-float and tonemap them, with [MyScene::exposure].  Without
+    /// EXT_color_buffer_half_float this logs a warning and changes nothing.
+    pub fn enable_hdr(&mut self) -> Result<(), GLErrorWrapper> {
+        let post = self.post.get_or_insert_with(PostChain::new);
+        if !post.enable_hdr(&mut self.gpu_state)? {
+            log::warn!("half-float color buffers are not supported, staying LDR");
+        }
+        Ok(())
+    }
+
+    /// Draw the eyes with `samples` samples per pixel and resolve them into the swapchain
+    /// images, checking on the device that edges come out right in an sRGB swapchain.  An
+    /// active [ActiveRenderer::post] chain draws into its own targets instead.
+    pub fn enable_msaa(&mut self, samples: i32) -> Result<(), GLErrorWrapper> {
+        self.msaa = Some(MsaaResolver::new_checked(
+            samples,
+            self.openxr.swapchain_format,
+            self.frame_env.stencil,
+            &mut self.gpu_state,
+        )?);
+        Ok(())
+    }
+
+    /// Rebuild the eye swapchains, e.g. after [OpenXRComponent::set_render_scale], and the
+    /// depth buffer drawn with them.  The MSAA and post-processing targets follow the new
+    /// size by themselves.
+    pub fn recreate_swapchains(&mut self) -> Result<(), Box<dyn Error>> {
+        self.openxr.recreate_swapchains()?;
+        let vcv0 = self.openxr.view_config_views[0];
+        self.frame_env = FrameEnv::new(
+            vcv0.recommended_image_rect_width,
+            vcv0.recommended_image_rect_height,
+            self.frame_env.stencil,
+            &mut self.gpu_state,
+        )?;
+        if self.multiview.is_some() {
+            self.multiview = Some(MultiviewFrameBuffer::new(
+                vcv0.recommended_image_rect_width,
+                vcv0.recommended_image_rect_height,
+                self.frame_env.stencil,
+                &mut self.gpu_state,
+            )?);
+        }
+        Ok(())
+    }
+
+    /// Draw each eye's motion vectors into [ActiveRenderer::motion_vectors] every frame, for
+    /// the objects [MyScene::register_motion] knows.  Fails without half-float color buffers.
+    pub fn enable_motion_vectors(&mut self) -> Result<(), GLErrorWrapper> {
+        let mut motion = MotionVectors::new()?;
+        self.scene
+            .register_motion(&mut motion, &mut self.gpu_state)?;
+        self.motion_vectors = Some(motion);
+        Ok(())
+    }
+
+    /// Hand the motion vectors to the runtime with XR_FB_space_warp, so it can render at
+    /// half rate and fill in the frames between.  Turns on motion vectors if they are not
+    /// already.  Fails if the runtime lacks the extension.
+    pub fn enable_space_warp(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.motion_vectors.is_none() {
+            self.enable_motion_vectors()?;
+        }
+        if self.space_warp_depth.is_none() {
+            self.space_warp_depth = Some(DepthCopy::new(&mut self.gpu_state)?);
+        }
+        self.openxr.enable_space_warp()?;
+        self.update_depth_planes();
+        Ok(())
+    }
+
+    /// Hand each eye's depth to the runtime with XR_KHR_composition_layer_depth, for better
+    /// reprojection.  The eyes are then drawn straight into the submitted depth images
+    /// instead of [FrameEnv::depth_buffer].  Fails if the runtime lacks the extension.
+    pub fn enable_depth_layer(&mut self) -> Result<(), Box<dyn Error>> {
+        self.openxr.enable_depth_layer(self.frame_env.stencil)?;
+        self.update_depth_planes();
+        Ok(())
+    }
+
+    /// The compositor reads submitted depth in real meters, and [NEAR_Z] and [FAR_Z] are in
+    /// world ones; call whenever [ActiveRenderer::world_scale] changes.
+    fn update_depth_planes(&mut self) {
+        if let Some(space_warp) = &mut self.openxr.space_warp {
+            space_warp.near_z = self.world_scale.to_real_distance(NEAR_Z);
+            space_warp.far_z = self.world_scale.to_real_distance(FAR_Z);
+        }
+        if let Some(depth_layer) = &mut self.openxr.depth_layer {
+            depth_layer.near_z = self.world_scale.to_real_distance(NEAR_Z);
+            depth_layer.far_z = self.world_scale.to_real_distance(FAR_Z);
+        }
+    }
+
+    /// Pause or resume [ActiveRenderer::enable_space_warp]'s submission, e.g. for scenes
+    /// whose motion it smears.  Returns false if space warp was never enabled.
+    pub fn set_space_warp(&mut self, enabled: bool) -> bool {
+        match &mut self.openxr.space_warp {
+            Some(space_warp) => {
+                space_warp.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Watch the frame loop from another thread.  When one phase of a frame (waiting for
+    /// it, a swapchain image, painting) takes longer than `threshold`, the watchdog thread
+    /// asks the runtime to end the session and has the event thread build a fresh renderer,
+    /// without waiting for the stuck frame loop.  If the frame loop does get back, it logs
+    /// the profiler's numbers.
+    pub fn enable_watchdog(&mut self, threshold: Duration) -> std::io::Result<()> {
+        let heartbeat = self.openxr.heartbeat.clone();
+        let session = self.openxr.xr_session.clone();
+        let restart_signal = self.restart_signal.clone();
+        let on_stall = move |stall: Stall| {
+            log::error!(
+                "frame loop stuck in {}, restarting the session",
+                stall.phase
+            );
+            // lets a frame loop stuck waiting on the runtime finish before it is abandoned
+            if let Err(e) = session.request_exit() {
+                log::warn!("failed to request session exit {:?}", e);
+            }
+            let signal = restart_signal
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            match signal.as_ref() {
+                Some(signal) => signal.fire(false),
+                None => log::warn!("not on a render thread, nothing to restart"),
+            }
+        };
+        self.watchdog = Some(Watchdog::spawn(heartbeat, threshold, on_stall)?);
+        Ok(())
+    }
+
+    /// Allow [ActiveRenderer::request_scene_export], writing to [captures_dir].
+    pub fn enable_scene_export(&mut self, app: &AndroidApp) {
+        self.scene_export_dir = captures_dir(app);
+    }
+
+    /// Write the next frame's scene to a timestamped .glb in [ActiveRenderer::scene_export_dir].
+    pub fn request_scene_export(&mut self) {
+        if self.scene_export_dir.is_some() {
+            self.scene_export_requested = true;
+        } else {
+            log::warn!("scene export requested, but enable_scene_export() was never called");
+        }
+    }
+
+    /// what the eye swapchains were negotiated with, and how the compositor will take it
+    pub fn swapchain_format(&self) -> FormatInfo {
+        FormatInfo::of(self.openxr.swapchain_format)
+    }
+
+    /// Read `region` of `eye`'s image back in the next frame, as it is handed to the
+    /// compositor; collect it with [ActiveRenderer::take_swapchain_snapshot].  Stalls that
+    /// frame until the GPU has drawn the eye, so keep it to self-tests and bug reports.
+    pub fn request_swapchain_readback(&mut self, eye: usize, region: PixelRegion) {
+        self.swapchain_readback = Some((eye, region));
+    }
+
+    /// the last [ActiveRenderer::request_swapchain_readback]'s pixels, once they are in
+    pub fn take_swapchain_snapshot(&mut self) -> Option<SwapchainSnapshot> {
+        self.swapchain_snapshot.take()
+    }
+
+    /// Stop drawing; the render thread then drops this, instance and all, and the app
+    /// reconnects with a new one.
+    fn lose_runtime(&mut self, why: &str) {
+        log::error!("lost the VR runtime ({}), reconnecting", why);
+        self.analytics.error("runtime lost", &why);
+        self.runtime_lost = true;
     }
 
     pub fn build_android_egl_context(
         event_loop: &ActiveEventLoop,
-    ) -> Result<(*const c_void, *const c_void), Box<dyn Error>> {
+    ) -> Result<(PossiblyCurrentContext, *const c_void, *const c_void), Box<dyn Error>> {
         let raw_display = event_loop.raw_display_handle()?;
 
         let Display::Egl(glutin_display) =
@@ -192,59 +1569,651 @@ impl ActiveRenderer {
         let context = context.make_current_surfaceless()?;
 
         let RawContext::Egl(raw_context) = context.raw_context();
-        Ok((display_ptr, raw_context))
+        Ok((context, display_ptr, raw_context))
     }
 
     /// iterate through the various OpenXR views and paint them
     pub fn draw_inner(&mut self) -> Result<(), XrErrorWrapped> {
+        if let Some(camera) = &mut self.camera {
+            if let Err(e) = camera.update() {
+                log::warn!("camera preview malfunction {}", e);
+            }
+        }
+
+        if let Some(permissions) = &mut self.permissions {
+            if let Err(e) = permissions.poll() {
+                log::warn!("failed to poll permissions {}", e);
+            }
+        }
+        if let Some(microphone) = &mut self.microphone {
+            let granted = self
+                .permissions
+                .as_ref()
+                .is_some_and(|p| p.is_granted(RECORD_AUDIO));
+            match microphone.update(granted) {
+                Ok(()) => self.scene.audio = microphone.levels(),
+                Err(e) => {
+                    log::warn!("microphone malfunction {}", e);
+                    self.microphone = None;
+                    self.scene.audio = AudioLevels::default();
+                }
+            }
+        }
+        if let Some(monitor) = &self.device_status {
+            self.quality.device_throttle(monitor.throttle_level());
+        }
+        if let Some((cpu, gpu)) = self.quality.take_performance_request() {
+            for (domain, level) in [
+                (PerfSettingsDomainEXT::CPU, cpu),
+                (PerfSettingsDomainEXT::GPU, gpu),
+            ] {
+                if let Err(e) = self.openxr.set_performance_level(domain, level) {
+                    log::warn!("failed to set performance level {}", e);
+                }
+            }
+        }
+        if let Some(ui) = &mut self.ui {
+            if let Some(menu) = &mut ui.developer_menu {
+                menu.refresh(&self.quality);
+            }
+            if let Err(e) = ui.update(Instant::now(), &mut self.gpu_state) {
+                log::warn!("ui update malfunction {}", e);
+            }
+        }
+
+        FrameHooks::run(self, HookStage::PostUpdate, |renderer| &mut renderer.hooks);
+
         let gpu_state = &mut self.gpu_state;
+        // read in before_paint, acted on once the frame is out
+        let mut menu_input = MenuInput::default();
+        let mut paint_held = false;
+        let mut teleport_held = false;
+        let mut eye_height = None;
+        let mut gesture_buttons = GestureButton::ALL.map(|button| (button, false));
+        let mut undo_pressed = false;
+        let mut redo_pressed = false;
+        let mut controller_1 = None;
+        let mut left_grip = None;
+        let mut head_pose = None;
+        let mut view_orientation = None;
+        let mut pixels_per_radian = None;
+        if let Some(post) = &mut self.post {
+            post.set_enabled(TEMPORAL_AA, self.scene.anti_flicker);
+            post.exposure = self.scene.exposure;
+        }
+        let motion_enabled = self.motion_vectors.is_some();
+        let mut motion_models = None;
+        let mut frame_index = 0;
+        let mut frame_error = None;
+        let quality = self.quality.level();
+        let scale = self.world_scale;
+        let swapchain_format = FormatInfo::of(self.openxr.swapchain_format);
+        let (portals, mirrors): (&[Portal], &[PlanarMirror]) = if quality > QualityLevel::Low {
+            (&self.portals, &self.mirrors)
+        } else {
+            (&[], &[])
+        };
 
         let before_paint = |openxr: &OpenXRComponent<OpenGlEs>,
                             frame_state: &openxr::FrameState| {
-            self.inputs.sync_actions(&openxr.xr_session).unwrap();
-
-            let location = self.inputs.controller_1_locate_if_active(
-                &openxr.xr_session,
-                &openxr.xr_space,
+            self.scheduler.frame_started(
                 frame_state.predicted_display_time,
+                Duration::from_nanos(frame_state.predicted_display_period.as_nanos().max(0) as u64),
             );
+            self.quality
+                .frame(self.scheduler.frame_delta(), self.scheduler.frame_period());
+            if self.scheduler.frame_index() == 1 {
+                self.analytics.record(AnalyticsEvent::SessionStarted {
+                    runtime: openxr.runtime.to_string(),
+                });
+            }
+            self.analytics
+                .frame(self.scheduler.frame_delta(), self.scheduler.frame_period());
+            let synced = self
+                .inputs
+                .sync_actions(&openxr.xr_session)
+                .annotate_if_err(Some(&openxr.xr_instance), "failed to sync actions");
+            self.input_state = self.inputs.input_state(&openxr.xr_session);
+            if let Some(monitor) = &mut self.controller_status {
+                if let Err(e) = monitor.poll(&openxr.xr_instance, &openxr.xr_session, &self.inputs)
+                {
+                    log::warn!("failed to read controller status {}", e);
+                }
+            }
+            menu_input = self.inputs.menu_input(&openxr.xr_session);
+            // the brush is the right controller, so only its select paints
+            paint_held = self.inputs.held_by(
+                &openxr.xr_session,
+                &self.inputs.select,
+                self.inputs.user_hand_right,
+            );
+            teleport_held = self.inputs.held(&openxr.xr_session, &self.inputs.teleport);
+            gesture_buttons = self.inputs.gesture_buttons(&openxr.xr_session);
+            undo_pressed = self.inputs.pressed(&openxr.xr_session, &self.inputs.undo);
+            redo_pressed = self.inputs.pressed(&openxr.xr_session, &self.inputs.redo);
+            let pose_time = openxr.pose_time(frame_state);
+
+            let location = self
+                .inputs
+                .controller_1_locate_if_active(&openxr.xr_session, &openxr.xr_space, pose_time)
+                .map(|location| to_world_location(&scale, location));
 
             if false {
                 debug!("space location {:?}", location.map(|sl| sl.pose));
             }
-            (location, gpu_state)
+            controller_1 = location;
+            left_grip = self
+                .inputs
+                .controller_left_locate_if_active(&openxr.xr_session, &openxr.xr_space, pose_time)
+                .map(|location| Pose::from(scale.to_world_pose(location.pose)));
+
+            if let Some(calibration) = &mut self.height_calibration {
+                eye_height = calibration.measure(&self.view_space, pose_time);
+            }
+            let head = self.view_space.locate(&openxr.xr_space, pose_time);
+            measure_photon_to_pose_error(
+                &mut self.rendered_heads,
+                &mut self.profiler,
+                &self.view_space,
+                &openxr.xr_space,
+            );
+            if let Ok(head) = head {
+                self.rendered_heads
+                    .push_back((frame_state.predicted_display_time, head.pose));
+            }
+            let head = head.map(|head| to_world_location(&scale, head));
+            head_pose = head.ok().map(|head| Pose::from(head.pose));
+
+            let remote_avatars = match &mut self.pose_stream {
+                Some(pose_stream) => {
+                    if let Ok(head) = head {
+                        let controller = location.map(|l| Pose::from(l.pose));
+                        if let Err(e) = pose_stream.publish(Pose::from(head.pose), controller) {
+                            log::warn!("failed to publish pose {}", e);
+                        }
+                    }
+                    if let Err(e) = pose_stream.receive() {
+                        log::warn!("failed to receive remote poses {}", e);
+                    }
+                    pose_stream.remote_avatars()
+                }
+                None => vec![],
+            };
+
+            let spectator_tracked = self.spectator_space.as_ref().and_then(|space| {
+                space
+                    .locate(&openxr.xr_space, pose_time)
+                    .ok()
+                    .map(|location| Pose::from(scale.to_world_pose(location.pose)))
+            });
+
+            let hand_poses = self
+                .hand_occlusion
+                .as_ref()
+                .map(|hands| hands.locate(&openxr.xr_space, pose_time))
+                .map(|poses| poses.transformed(&scale.matrix()));
+
+            FrameData {
+                controller_1: location,
+                remote_avatars,
+                spectator_tracked,
+                view_orientation: None,
+                pixels_per_radian: None,
+                hand_poses,
+                frame_index: self.scheduler.frame_index(),
+                pose_time,
+                views_painted: 0,
+                motion_models: motion_enabled.then(|| {
+                    let mut models = std::mem::take(&mut self.motion_models);
+                    self.scene.motion_models(&location, &mut models);
+                    models
+                }),
+                gpu_state,
+                error: synced.err(),
+            }
         };
 
-        let lambda =
-            |view_i: &View,
-             vcv: &ViewConfigurationView,
-             predicted_display_time,
-             &render_destination: &u32,
-             // gpu_state: &mut GPUState,
-             (controller_1, gpu_state): &mut (Option<SpaceLocation>, &mut GPUState)| {
-                Self::paint_one_view(
-                    view_i,
-                    vcv,
+        let lambda = |view_i: &View,
+                      vcv: &ViewConfigurationView,
+                      predicted_display_time,
+                      &render_destination: &u32,
+                      space_warp: Option<&SpaceWarpImages<OpenGlEs>>,
+                      depth_image: Option<&u32>,
+                      arena: &FrameArena,
+                      frame: &mut FrameData| {
+            // both eyes share the head's orientation
+            frame.view_orientation = Some(view_i.pose.orientation.into());
+            frame.pixels_per_radian = Some(eye_pixels_per_radian(
+                vcv.recommended_image_rect_width,
+                view_i.fov.angle_left,
+                view_i.fov.angle_right,
+            ));
+            let eye = frame.views_painted;
+            frame.views_painted += 1;
+            let pose_time = frame.pose_time;
+            // late, for the HUD
+            let locate_head = || {
+                self.view_space
+                    .locate(&self.late_space, pose_time)
+                    .ok()
+                    .map(|head| Pose::from(scale.to_world_pose(head.pose)))
+            };
+            let view = View {
+                pose: scale.to_world_pose(view_i.pose),
+                fov: view_i.fov,
+            };
+            let readback = self
+                .swapchain_readback
+                .filter(|(readback_eye, _)| *readback_eye == eye);
+            self.hooks.run_pre_render_view(
+                &ViewInfo {
+                    frame_index: frame.frame_index,
+                    eye,
+                    pose: Pose::from(view.pose),
+                    fov: view.fov.into(),
+                    width: vcv.recommended_image_rect_width,
+                    height: vcv.recommended_image_rect_height,
                     predicted_display_time,
-                    &self.scene,
-                    &self.frame_env,
-                    render_destination,
-                    gpu_state,
-                    controller_1,
-                )
-                .unwrap();
+                },
+                frame.gpu_state,
+            );
+            if let Err(e) = Self::paint_one_view(
+                &view,
+                vcv,
+                predicted_display_time,
+                &self.scene,
+                &self.frame_env,
+                render_destination,
+                self.post
+                    .as_mut()
+                    .map(|post| (post, eye, frame.frame_index)),
+                self.msaa.as_mut(),
+                self.motion_vectors
+                    .as_mut()
+                    .zip(frame.motion_models.as_deref())
+                    .map(|(motion, models)| (motion, eye, frame.frame_index, models)),
+                space_warp.zip(self.space_warp_depth.as_mut()),
+                depth_image.copied(),
+                arena,
+                frame.gpu_state,
+                &frame.controller_1,
+                &frame.remote_avatars,
+                self.camera.as_ref(),
+                portals,
+                mirrors,
+                self.ui.as_ref(),
+                (frame.frame_index, &locate_head),
+                self.hand_occlusion.as_ref().zip(frame.hand_poses.as_ref()),
+                readback.map(|(_, region)| {
+                    (eye, region, swapchain_format, &mut self.swapchain_snapshot)
+                }),
+            ) {
+                frame.fail(e);
+            }
+            if readback.is_some() {
+                self.swapchain_readback = None;
+            }
+        };
+        let paint_both = |views: &[View],
+                          vcv: &ViewConfigurationView,
+                          _predicted_display_time,
+                          &render_destination: &u32,
+                          arena: &FrameArena,
+                          frame: &mut FrameData| {
+            let (Some(multiview), [left, right]) = (&self.multiview, views) else {
+                return;
+            };
+            frame.view_orientation = Some(left.pose.orientation.into());
+            frame.pixels_per_radian = Some(eye_pixels_per_radian(
+                vcv.recommended_image_rect_width,
+                left.fov.angle_left,
+                left.fov.angle_right,
+            ));
+            frame.views_painted += views.len();
+            let pose_time = frame.pose_time;
+            // late, for the HUD
+            let locate_head = || {
+                self.view_space
+                    .locate(&self.late_space, pose_time)
+                    .ok()
+                    .map(|head| Pose::from(scale.to_world_pose(head.pose)))
+            };
+            let world_view = |view: &View| View {
+                pose: scale.to_world_pose(view.pose),
+                fov: view.fov,
+            };
+            if let Err(e) = Self::paint_views_at_once(
+                &[world_view(left), world_view(right)],
+                vcv,
+                &self.scene,
+                multiview,
+                render_destination,
+                arena,
+                frame.gpu_state,
+                &frame.controller_1,
+                &frame.remote_avatars,
+                self.camera.as_ref(),
+                self.ui.as_ref(),
+                (frame.frame_index, &locate_head),
+                self.hand_occlusion.as_ref().zip(frame.hand_poses.as_ref()),
+            ) {
+                frame.fail(e);
+            }
+        };
+        let after_paint =
+            |_: &OpenXRComponent<OpenGlEs>, frame_state: &openxr::FrameState, frame: FrameData| {
+                view_orientation = frame.view_orientation;
+                pixels_per_radian = frame.pixels_per_radian;
+                motion_models = frame.motion_models;
+                frame_index = frame.frame_index;
+                frame_error = frame.error;
+                if std::mem::take(&mut self.scene_export_requested) {
+                    if let Some(dir) = &self.scene_export_dir {
+                        let document = self
+                            .scene
+                            .export_gltf(&frame.controller_1, &frame.remote_avatars);
+                        match export_scene(&document, dir) {
+                            Ok(path) => log::info!("exported scene to {}", path.display()),
+                            Err(e) => log::warn!("failed to export scene {}", e),
+                        }
+                    }
+                }
+                if let Some(spectator) = &mut self.spectator {
+                    if quality >= QualityLevel::High && spectator.update(frame.spectator_tracked) {
+                        if let Err(e) = spectator.render(
+                            &self.scene,
+                            frame_state.predicted_display_time,
+                            frame.gpu_state,
+                            &frame.controller_1,
+                            &frame.remote_avatars,
+                            self.camera.as_ref(),
+                        ) {
+                            log::warn!("failed to render spectator view {}", e);
+                        }
+                    }
+                }
             };
-        let after_paint = |_: &OpenXRComponent<OpenGlEs>, _: &openxr::FrameState, _| {};
 
         self.openxr.paint_vr_multiview(
             before_paint,
             lambda,
+            paint_both,
             after_paint,
             ViewConfigurationType::PRIMARY_STEREO,
             // &mut self.gpu_state,
+        )?;
+        record_gl_counts(&mut self.profiler, &self.gpu_state.end_frame());
+
+        if let Some(models) = motion_models {
+            self.scene
+                .motion
+                .advance(frame_index, models.iter().copied());
+            self.motion_models = models;
+        }
+        // e.g. the session was lost mid-frame; the caller decides whether to reconnect
+        if let Some(e) = frame_error {
+            return Err(e);
+        }
+
+        self.handle_menu_input(&menu_input, controller_1);
+        let gestures = self.gestures.update(&gesture_buttons, Instant::now());
+        if let Some(ui) = &mut self.ui {
+            let was_dragging = ui.is_dragging();
+            let keystrokes =
+                ui.handle_gestures(gestures, controller_1.as_ref().map(controller_ray));
+            if ui.is_dragging() && !was_dragging {
+                self.haptics.play(HapticHand::Right, HapticPattern::click());
+            }
+            for (_, event) in &keystrokes {
+                self.haptics.play(HapticHand::Right, HapticPattern::click());
+                if let Some((index, TextFieldEvent::Submitted(text))) = event {
+                    debug!("text field {} submitted {:?}", index, text);
+                }
+            }
+            if let (Some(audio), Some(keyboard)) = (&mut self.spatial_audio, &ui.keyboard) {
+                if !keystrokes.is_empty() {
+                    audio.play(keyboard.click.clone(), keyboard.position(), false);
+                }
+            }
+            // scrolling a panel is not painting
+            paint_held &= !ui.is_dragging();
+        }
+        if let Some(painting) = &mut self.scene.painting {
+            let brush = controller_1.map(|location| Pose::from(location.pose));
+            match painting.update(paint_held, brush, &mut self.gpu_state) {
+                Ok(true) => self.undo.push(StrokeCommand::new(|renderer: &mut Self| {
+                    renderer.scene.painting.as_mut()
+                })),
+                Ok(false) => {}
+                Err(e) => log::warn!("painting malfunction {}", e),
+            }
+        }
+        if let Some(ruler) = self.ui.as_mut().and_then(|ui| ui.ruler.as_mut()) {
+            ruler.handle_input(
+                self.input_state.right.trigger,
+                controller_1.as_ref().map(controller_ray),
+                head_pose.as_ref().map(|head| head.position),
+                &self.world_scale,
+            );
+        }
+        if let Some(flashlight) = &mut self.scene.flashlight {
+            if self.input_state.a().pressed {
+                flashlight.toggle();
+            }
+            let grip = controller_1.map(|location| Pose::from(location.pose));
+            flashlight.update(grip, &mut self.scene.lights);
+        }
+        if let Some(flashlight) = &self.scene.flashlight {
+            let scene = &self.scene;
+            let shadow = flashlight.render_shadows(&mut self.gpu_state, |pv, gpu_state| {
+                scene.draw_shadow_casters(pv, gpu_state)
+            });
+            self.scene.lights.shadow = shadow.unwrap_or_else(|e| {
+                log::warn!("failed to render the flashlight's shadows {}", e);
+                None
+            });
+        }
+        if let Some(teleport) = &mut self.scene.teleport {
+            let ray = controller_1.as_ref().map(controller_ray);
+            match teleport.update(teleport_held, ray, &mut self.gpu_state) {
+                Ok(Some(target)) => {
+                    if let Some(head) = &head_pose {
+                        if let Err(e) = self.teleport_to(&target, &head.position) {
+                            log::warn!("failed to teleport {}", e);
+                        }
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => log::warn!("teleport malfunction {}", e),
+            }
+        }
+        if let Some(height) = eye_height {
+            log::info!("eye height {:.2}m", height);
+            self.height_calibrated();
+        }
+        if let Some(minimap) = self.ui.as_mut().and_then(|ui| ui.minimap.as_mut()) {
+            minimap.model = controller_1.as_ref().map(minimap_model);
+            if let Some(head) = &head_pose {
+                if let Err(e) = minimap.update(&self.scene, &head.position, &mut self.gpu_state) {
+                    log::warn!("minimap malfunction {}", e);
+                }
+            }
+        }
+        if let Some(ui) = &mut self.ui {
+            if let Some(menu) = &mut ui.wrist_menu {
+                menu.update(
+                    left_grip.as_ref(),
+                    head_pose.as_ref().map(|head| &head.position),
+                );
+            }
+            ui.update_pointer(controller_1.as_ref().map(controller_ray));
+            // the first frame, or a new render scale
+            if let Some(ppr) = pixels_per_radian {
+                if (ppr - ui.pixels_per_radian).abs() > 0.01 * ui.pixels_per_radian {
+                    ui.pixels_per_radian = ppr;
+                    ui.set_density(PanelDensity::for_headset(ppr));
+                }
+            }
+            if let Some(head) = &head_pose {
+                ui.track_eye(&head.position);
+            }
+        }
+        if let Some(screen) = &mut self.curved_screen {
+            // composition layers are placed in the tracking space; scaling is uniform, so
+            // only the origin moves
+            let scale = self.world_scale;
+            let ray = controller_1
+                .as_ref()
+                .map(controller_ray)
+                .map(|(origin, direction)| (scale.to_real(&origin), direction));
+            screen.update_pointer(&self.openxr, ray);
+        }
+        self.update_hover(controller_1.as_ref());
+        if let (Some(audio), Some(head)) = (&mut self.spatial_audio, &head_pose) {
+            // rotate the soundscapes with what was actually rendered
+            let listener = Pose::new(head.position, view_orientation.unwrap_or(head.orientation));
+            if let Err(e) = audio.update(&listener) {
+                log::warn!("spatial audio malfunction {}", e);
+            }
+        }
+        for (hand, output) in self
+            .haptics
+            .update(Instant::now(), self.scheduler.frame_period())
+        {
+            if let Err(e) = self.inputs.vibrate(&self.openxr.xr_session, hand, output) {
+                log::warn!("haptics malfunction {}", e);
+            }
+        }
+        if undo_pressed {
+            UndoStack::undo(self, |renderer| &mut renderer.undo);
+        }
+        if redo_pressed {
+            UndoStack::redo(self, |renderer| &mut renderer.undo);
+        }
+        Ok(())
+    }
+
+    /// Outline the prop the controller points at, unless it points at a panel first.
+    fn update_hover(&mut self, controller: Option<&SpaceLocation>) {
+        let on_panel = self.ui.as_ref().is_some_and(|ui| ui.pointer.is_visible());
+        let hovered = controller
+            .filter(|_| !on_panel)
+            .map(controller_ray)
+            .and_then(|(origin, direction)| self.scene.props.pick(&origin, &direction));
+        let props = &mut self.scene.props;
+        props.clear_highlights();
+        if let Some(index) = hovered {
+            props.set_highlighted(index, true);
+        }
+    }
+
+    /// Open and close the menu, and pass the controller's pointing and thumbstick to the
+    /// developer menu.  Context changes take effect at the next sync.
+    fn handle_menu_input(&mut self, input: &MenuInput, controller: Option<SpaceLocation>) {
+        if input.toggle {
+            let open = !self.inputs.is_active(InputContext::Menu);
+            self.inputs.set_active(InputContext::Menu, open);
+        }
+
+        self.drive_launcher(input, controller);
+
+        let before = MenuSettings::of(&self.quality);
+        self.drive_developer_menu(input, controller);
+        let after = MenuSettings::of(&self.quality);
+        if after != before {
+            self.undo.push(FnCommand::new(
+                "developer menu",
+                move |renderer: &mut ActiveRenderer| after.restore(&mut renderer.quality),
+                move |renderer: &mut ActiveRenderer| before.restore(&mut renderer.quality),
+            ));
+        }
+    }
+
+    /// Bake the thumbnail of scene `index` and schedule the next one, so building every scene
+    /// is spread over several frames.
+    fn bake_launcher_thumbnail(&mut self, index: usize) {
+        let Some(entry) = self.scenes.entries().get(index) else {
+            return;
+        };
+        let Some(launcher) = self.ui.as_mut().and_then(|ui| ui.launcher.as_mut()) else {
+            return;
+        };
+        let (width, height) = (256, (256.0 / THUMBNAIL_ASPECT) as i32);
+        let baked = bake_thumbnail(
+            &self.scenes,
+            index,
+            &ThumbnailCamera::default(),
+            width,
+            height,
+            &mut self.gpu_state,
         )
+        .and_then(|texture| launcher.set_thumbnail(index, texture, &mut self.gpu_state));
+        if let Err(e) = baked {
+            log::warn!("failed to bake a thumbnail of {} {}", entry.name, e);
+        }
+        self.scheduler.after(
+            Duration::from_millis(50),
+            move |renderer: &mut ActiveRenderer| renderer.bake_launcher_thumbnail(index + 1),
+        );
+    }
+
+    fn drive_launcher(&mut self, input: &MenuInput, controller: Option<SpaceLocation>) {
+        let menu_open = self.inputs.is_active(InputContext::Menu);
+        let Some(launcher) = self.ui.as_mut().and_then(|ui| ui.launcher.as_mut()) else {
+            return;
+        };
+        launcher.set_visible(menu_open);
+        if !menu_open {
+            return;
+        }
+        launcher.refresh(&self.scenes);
+        launcher.view.scroll.thumbstick(input.scroll);
+        let (true, Some(location)) = (input.select, controller) else {
+            return;
+        };
+        let (origin, direction) = controller_ray(&location);
+        let Some(index) = launcher.item_at_ray(&origin, &direction) else {
+            return;
+        };
+        launcher.set_visible(false);
+        self.inputs.set_active(InputContext::Menu, false);
+        self.haptics.play(HapticHand::Right, HapticPattern::click());
+        if let Err(e) = self.load_scene(index) {
+            log::warn!("failed to load scene {} {}", index, e);
+        }
+    }
+
+    fn drive_developer_menu(&mut self, input: &MenuInput, controller: Option<SpaceLocation>) {
+        let menu = self.ui.as_mut().and_then(|ui| ui.developer_menu.as_mut());
+        self.inputs.set_active(InputContext::Debug, menu.is_some());
+        let Some(menu) = menu else {
+            return;
+        };
+
+        if input.debug_next {
+            menu.activate(QUALITY_ITEM, &mut self.quality);
+        }
+        if !self.inputs.is_active(InputContext::Menu) {
+            return;
+        }
+        menu.view.scroll.thumbstick(input.scroll);
+        if let (true, Some(location)) = (input.select, controller) {
+            let (origin, direction) = controller_ray(&location);
+            let item = menu
+                .view
+                .hit(&origin, &direction)
+                .and_then(|point| menu.view.item_at(point));
+            match item {
+                Some(EXPORT_SCENE_ITEM) => self.request_scene_export(),
+                Some(index) => menu.activate(index, &mut self.quality),
+                None => return,
+            }
+            self.haptics.play(HapticHand::Right, HapticPattern::click());
+        }
     }
 
+    /// Each eye is a small render graph: the portals and mirrors render their views first,
+    /// then the eye image which shows them.  The draw lists go in `arena`.
     #[allow(clippy::too_many_arguments)]
     fn paint_one_view(
         view_i: &View,
@@ -253,21 +2222,281 @@ impl ActiveRenderer {
         renderer: &MyScene,
         frame_env: &FrameEnv,
         color_buffer: <Backend as Graphics>::SwapchainImage,
+        post: Option<(&mut PostChain, usize, u64)>,
+        mut msaa: Option<&mut MsaaResolver>,
+        motion: Option<(&mut MotionVectors, usize, u64, &[(MotionId, XrMatrix4x4f)])>,
+        space_warp: Option<(&SpaceWarpImages<OpenGlEs>, &mut DepthCopy)>,
+        depth_image: Option<<Backend as Graphics>::SwapchainImage>,
+        arena: &FrameArena,
         gpu_state: &mut GPUState,
         controller_1: &Option<SpaceLocation>,
+        remote_avatars: &[RemoteAvatar],
+        camera: Option<&PassthroughCamera>,
+        portals: &[Portal],
+        mirrors: &[PlanarMirror],
+        ui: Option<&Ui>,
+        late_head: (u64, &dyn Fn() -> Option<Pose>),
+        hands: Option<(&HandOcclusion, &HandPoses)>,
+        mut readback: Option<(
+            usize,
+            PixelRegion,
+            FormatInfo,
+            &mut Option<SwapchainSnapshot>,
+        )>,
     ) -> Result<(), Box<dyn Error>> {
         let width = view_config_view.recommended_image_rect_width;
         let height = view_config_view.recommended_image_rect_height;
-        frame_env.prepare_to_draw(&Texture::borrowed(color_buffer), width, height)?;
-        renderer.draw(
-            &view_i.fov.into(),
-            &view_i.pose.orientation.into(),
-            &view_i.pose.position.into(),
-            time,
-            gpu_state,
-            controller_1,
-        )?;
+        let fov = view_i.fov.into();
+        let rotation = view_i.pose.orientation.into();
+        let translation = view_i.pose.position.into();
+        let (frame_index, locate_head) = late_head;
+
+        let mut graph = RenderGraph::new();
+        for portal in portals {
+            let targets = [&portal.target];
+            graph.add_pass_into("portal", &[], &[PORTAL_VIEWS], &targets, |gpu_state| {
+                portal.render_view(
+                    renderer,
+                    &fov,
+                    &rotation,
+                    &translation,
+                    time,
+                    gpu_state,
+                    controller_1,
+                    remote_avatars,
+                    camera,
+                )
+            });
+        }
+        for mirror in mirrors {
+            let targets = [&mirror.target];
+            graph.add_pass_into("mirror", &[], &[MIRROR_VIEWS], &targets, |gpu_state| {
+                mirror.render_view(
+                    renderer,
+                    &fov,
+                    &rotation,
+                    &translation,
+                    time,
+                    gpu_state,
+                    controller_1,
+                    remote_avatars,
+                    camera,
+                )
+            });
+        }
+        // the motion vectors pass fills the motion images, the eye pass the depth ones
+        let (space_warp, mut space_warp_depth) = match space_warp {
+            Some((images, depth_copy)) => (Some(images), Some((images, depth_copy))),
+            None => (None, None),
+        };
+        if let Some((motion, eye, frame_index, models)) = motion {
+            graph.add_pass("motion vectors", &[], &[MOTION_VECTORS], move |gpu_state| {
+                let matrix_pv =
+                    projection_matrix(&fov) * inverse_view_matrix(&rotation, &translation);
+                // the runtime wants its own size
+                let (target_width, target_height) = match space_warp {
+                    Some(images) => (images.width as i32, images.height as i32),
+                    None => motion.target_size(width as i32, height as i32),
+                };
+                motion.render(
+                    eye,
+                    frame_index,
+                    matrix_pv,
+                    target_width,
+                    target_height,
+                    models,
+                    &renderer.motion,
+                    gpu_state,
+                )?;
+                match space_warp {
+                    Some(images) => motion.copy_to(eye, &Texture::borrowed(*images.motion)),
+                    None => Ok(()),
+                }
+            });
+        }
+        // (chain, eye, frame index), while it has effects to run
+        let mut post = post.filter(|(chain, _, _)| chain.is_active());
+        let inputs = [PORTAL_VIEWS, MIRROR_VIEWS];
+        graph.add_pass("eye", &inputs, &[EYE_IMAGE], |gpu_state| {
+            let color = Texture::borrowed(color_buffer);
+            // the compositor's, in place of the frame_env's own
+            let depth = depth_image.map(Texture::borrowed);
+            match (&mut post, &mut msaa) {
+                (Some((chain, eye, _)), _) => {
+                    chain.begin(*eye, width as i32, height as i32, gpu_state)?
+                }
+                (None, Some(msaa)) => msaa.begin(width as i32, height as i32, gpu_state)?,
+                (None, None) => {
+                    frame_env.prepare_to_draw_into(&color, depth.as_ref(), width, height)?
+                }
+            }
+            let lod_view = LodView::from_current_viewport(translation, &fov);
+            let matrix_pv = projection_matrix(&fov) * inverse_view_matrix(&rotation, &translation);
+
+            let queue = {
+                let mut queue = RenderQueue::new_in(arena);
+                renderer.queue_draws(
+                    &mut queue,
+                    matrix_pv,
+                    lod_view,
+                    controller_1,
+                    remote_avatars,
+                    camera,
+                );
+                if let Some((occlusion, poses)) = hands {
+                    // before anything it should hide
+                    queue.add(RenderLayer::Opaque, i32::MIN, move |gpu_state| {
+                        occlusion.draw(&matrix_pv, poses, gpu_state)
+                    });
+                }
+                for portal in portals {
+                    queue.add(RenderLayer::Opaque, 0, move |gpu_state| {
+                        portal.draw(&matrix_pv, gpu_state)
+                    });
+                }
+                for mirror in mirrors {
+                    queue.add(RenderLayer::Opaque, 0, move |gpu_state| {
+                        mirror.draw(&matrix_pv, &translation, gpu_state)
+                    });
+                }
+                if let Some(ui) = ui {
+                    ui.queue_draws(&mut queue, matrix_pv);
+                    if let Some(hud) = &ui.hud {
+                        hud.queue_draws(
+                            &mut queue,
+                            matrix_pv,
+                            &ui.glyphs,
+                            frame_index,
+                            locate_head,
+                        );
+                    }
+                }
+                queue
+            };
+            if renderer.depth_prepass {
+                queue.execute_with_depth_prepass(gpu_state)?;
+            } else {
+                queue.execute(gpu_state)?;
+            }
+            if let Some((images, depth_copy)) = &mut space_warp_depth {
+                // the whole scene's, so the runtime reprojects more than the motion objects
+                depth_copy.copy(
+                    width as i32,
+                    height as i32,
+                    &Texture::borrowed(*images.depth),
+                    images.width as i32,
+                    images.height as i32,
+                    gpu_state,
+                )?;
+            }
+
+            if let Some((chain, eye, frame_index)) = &mut post {
+                chain.finish(
+                    *eye,
+                    *frame_index,
+                    matrix_pv,
+                    || frame_env.prepare_to_draw_into(&color, depth.as_ref(), width, height),
+                    gpu_state,
+                )?;
+            } else if let Some(msaa) = &msaa {
+                msaa.resolve(
+                    || frame_env.prepare_to_draw_into(&color, depth.as_ref(), width, height),
+                    gpu_state,
+                )?;
+            }
+            if depth.is_some() && (post.is_some() || msaa.is_some()) {
+                // the scene's depth stayed in the chain's or resolver's own buffer
+                frame_env.prepare_to_draw_into(&color, depth.as_ref(), width, height)?;
+                frame_env.clear_depth()?;
+            }
+            frame_env.finish_drawing(depth.is_some())?;
+            if let Some((eye, region, format, snapshot)) = &mut readback {
+                // before the image is released to the compositor
+                frame_env.frame_buffer.bind_read()?;
+                **snapshot = Some(SwapchainSnapshot::read(
+                    *eye,
+                    frame_index,
+                    *format,
+                    *region,
+                    width,
+                    height,
+                )?);
+            }
+            Ok(())
+        });
+        graph.execute(gpu_state)?;
+
+        Ok(())
+    }
+
+    /// Both eyes in one pass into the two layers of `color_array`, see [gl_thin::multiview].
+    /// The scene, the hands and the UI only: the portals, mirrors, motion vectors, post
+    /// effects, pre_render_view hooks and swapchain readback have no multiview path, and
+    /// level of detail is picked from the left eye.
+    #[allow(clippy::too_many_arguments)]
+    fn paint_views_at_once(
+        views: &[View; 2],
+        view_config_view: &ViewConfigurationView,
+        renderer: &MyScene,
+        multiview: &MultiviewFrameBuffer,
+        color_array: <Backend as Graphics>::SwapchainImage,
+        arena: &FrameArena,
+        gpu_state: &mut GPUState,
+        controller_1: &Option<SpaceLocation>,
+        remote_avatars: &[RemoteAvatar],
+        camera: Option<&PassthroughCamera>,
+        ui: Option<&Ui>,
+        late_head: (u64, &dyn Fn() -> Option<Pose>),
+        hands: Option<(&HandOcclusion, &HandPoses)>,
+    ) -> Result<(), Box<dyn Error>> {
+        let width = view_config_view.recommended_image_rect_width;
+        let height = view_config_view.recommended_image_rect_height;
+        let (frame_index, locate_head) = late_head;
+        let view_matrix = |view: &View| {
+            let fov = view.fov.into();
+            projection_matrix(&fov)
+                * inverse_view_matrix(&view.pose.orientation.into(), &view.pose.position.into())
+        };
+        let matrices_pv = [view_matrix(&views[0]), view_matrix(&views[1])];
 
+        let color = Texture::borrowed(color_array);
+        multiview.prepare_to_draw(&color, &matrices_pv, width, height)?;
+        let fov = views[0].fov.into();
+        let lod_view = LodView::from_current_viewport(views[0].pose.position.into(), &fov);
+        // the left eye's; the shaders move each vertex from there into the other eye
+        let matrix_pv = matrices_pv[0];
+
+        let queue = {
+            let mut queue = RenderQueue::new_in(arena);
+            renderer.queue_draws(
+                &mut queue,
+                matrix_pv,
+                lod_view,
+                controller_1,
+                remote_avatars,
+                camera,
+            );
+            if let Some((occlusion, poses)) = hands {
+                queue.add(RenderLayer::Opaque, i32::MIN, move |gpu_state| {
+                    occlusion.draw(&matrix_pv, poses, gpu_state)
+                });
+            }
+            if let Some(ui) = ui {
+                ui.queue_draws(&mut queue, matrix_pv);
+                if let Some(hud) = &ui.hud {
+                    hud.queue_draws(&mut queue, matrix_pv, &ui.glyphs, frame_index, locate_head);
+                }
+            }
+            queue
+        };
+        let drawn = if renderer.depth_prepass {
+            queue.execute_with_depth_prepass(gpu_state)
+        } else {
+            queue.execute(gpu_state)
+        };
+        // even after a failure, or every later program would use its multiview variant
+        multiview.finish_drawing()?;
+        drawn?;
         Ok(())
     }
 }
@@ -0,0 +1,57 @@
+//! The assets compiled into the app, and files that replace them at runtime.  A file named
+//! like the embedded asset in the override directory (see [set_override_dir]) is used
+//! instead; when it is missing or will not parse, the embedded copy is.  Textures that fail
+//! altogether come out as [gl_thin::fallback::white_image], and shaders that fail as
+//! magenta, see [gl_thin::fallback].
+
+use rusttype::Font;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+pub const FONT_NAME: &str = "Montserrat-Regular.ttf";
+pub const POSTER_NAME: &str = "sohma_g_dawling_poster.png";
+
+static OVERRIDE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Where the replacements are looked for, e.g. `assets` in the app's external data
+/// directory.  Only the first call counts.
+pub fn set_override_dir(dir: PathBuf) {
+    if OVERRIDE_DIR.set(dir).is_err() {
+        log::warn!("the asset override directory is already set");
+    }
+}
+
+/// the override for `name`, if there is a readable one
+pub fn read_override(name: &str) -> Option<Vec<u8>> {
+    let path = OVERRIDE_DIR.get()?.join(name);
+    match std::fs::read(&path) {
+        Ok(bytes) => {
+            log::info!("using {} in place of the built-in {}", path.display(), name);
+            Some(bytes)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            log::warn!("failed to read {} {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// [FONT_NAME], overridden or built in
+pub fn font() -> Font<'static> {
+    if let Some(bytes) = read_override(FONT_NAME) {
+        match Font::try_from_vec(bytes) {
+            Some(font) => return font,
+            None => log::warn!("failed to parse {}, using the built-in one", FONT_NAME),
+        }
+    }
+    Font::try_from_bytes(include_bytes!("Montserrat-Regular.ttf"))
+        .expect("failed to parse built-in font")
+}
+
+/// [POSTER_NAME], overridden or built in, still encoded
+#[cfg(feature = "png")]
+pub fn poster_png() -> Vec<u8> {
+    read_override(POSTER_NAME)
+        .unwrap_or_else(|| include_bytes!("sohma_g_dawling_poster.png").to_vec())
+}
@@ -0,0 +1,292 @@
+//! A torch in the user's hand: a spot light in [MyScene::lights](crate::scene::MyScene::lights)
+//! that follows a controller, lighting whatever lit meshes it points at, with a faint cone
+//! of light in the air so the beam itself can be seen.  The cone is as wide as the light's
+//! outer angle and fades out along its length.  With [Flashlight::enable_shadows] the beam
+//! stops at the first thing it hits: every frame the shadow casters are drawn from the
+//! light into a depth map, which the lit shaders compare against.
+
+use crate::pose_stream::Pose;
+use bob_shaders::beam_shader::BeamShader;
+use bob_shaders::lights::{Light, LightId, LightKind, LightManager, ShadowMap, SHADOW_MAP_UNIT};
+use gl::types::{GLfloat, GLint, GLsizei, GLuint, GLushort};
+use gl_thin::gl_caps::gl_caps;
+use gl_thin::gl_fancy::{GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::{explode_if_gl_error, FrameBuffer, GLErrorWrapper, Texture};
+use gl_thin::linear::{
+    xr_matrix4x4f_create_from_quaternion, xr_matrix4x4f_create_projection,
+    xr_matrix4x4f_create_scale, xr_matrix4x4f_create_translation, xr_matrix4x4f_invert_rigid_body,
+    xr_matrix4x4f_transform_vector3f, GraphicsAPI, XrMatrix4x4f, XrVector3f,
+};
+use std::f32::consts::TAU;
+
+/// around the cone's rim
+const CONE_SEGMENTS: usize = 24;
+
+/// meters in front of the light where its shadow map starts
+const SHADOW_NEAR: f32 = 0.05;
+
+/// the depth map of [Flashlight::enable_shadows]
+struct SpotShadow {
+    depth: Texture,
+    frame_buffer: FrameBuffer,
+    size: GLsizei,
+}
+
+pub struct Flashlight {
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// meters the light reaches
+    pub range: f32,
+    /// half-angles in radians, see [LightKind::Spot]
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    /// meters of visible beam; the light reaches on to [Flashlight::range]
+    pub beam_length: f32,
+    /// how strongly the beam shows, 0 to hide it
+    pub beam_opacity: f32,
+    on: bool,
+    /// the light in the [LightManager], while the flashlight is on and held
+    light: Option<LightId>,
+    /// where the controller was at the last [Flashlight::update]
+    pose: Option<Pose>,
+    shader: BeamShader,
+    shadow: Option<SpotShadow>,
+    /// see [ShadowMap::bias]
+    pub shadow_bias: f32,
+    /// a unit cone, apex at the origin, opening along -Z to a rim of radius 1 at z = -1
+    cone: VertexBufferBundle<'static, GLfloat, GLushort>,
+    n_indices: GLsizei,
+}
+
+impl Flashlight {
+    pub fn new(gpu_state: &mut GPUState) -> Result<Self, GLErrorWrapper> {
+        let shader = BeamShader::new()?;
+        let (vertices, indices) = cone_mesh();
+        let n_indices = indices.len() as GLsizei;
+        let cone = VertexBufferBundle::new(
+            gpu_state,
+            vertices.into(),
+            indices.into(),
+            4,
+            &[(shader.sal_position, 3, 0), (shader.sal_fade, 1, 3)],
+        )?;
+        Ok(Self {
+            color: [1.0, 0.93, 0.8],
+            intensity: 6.0,
+            range: 8.0,
+            inner_angle: 0.2,
+            outer_angle: 0.35,
+            beam_length: 1.5,
+            beam_opacity: 0.12,
+            on: true,
+            light: None,
+            pose: None,
+            shader,
+            shadow: None,
+            shadow_bias: 0.002,
+            cone,
+            n_indices,
+        })
+    }
+
+    /// Cast shadows from a `size` pixel square depth map, see [Flashlight::render_shadows].
+    /// Needs depth textures, so GLES 3.
+    pub fn enable_shadows(
+        &mut self,
+        size: GLsizei,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let caps = gl_caps();
+        if caps.gles && caps.major < 3 {
+            return Err(GLErrorWrapper::with_message2(format!(
+                "flashlight shadows need depth textures, {}",
+                caps
+            )));
+        }
+        let depth = Texture::depth_buffer(size, size, gpu_state)?;
+        {
+            let bound = depth.bound(gl::TEXTURE_2D, gpu_state)?;
+            // compared by hand in the shader, so no filtering between depths
+            bound.set_parameter(gl::TEXTURE_MIN_FILTER, gl::NEAREST)?;
+            bound.set_parameter(gl::TEXTURE_MAG_FILTER, gl::NEAREST)?;
+            bound.set_parameter(gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE)?;
+            bound.set_parameter(gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE)?;
+        }
+        let frame_buffer = FrameBuffer::new()?;
+        frame_buffer.bind()?;
+        let attached = depth.attach(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::TEXTURE_2D, 0);
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0) };
+        attached?;
+        self.shadow = Some(SpotShadow {
+            depth,
+            frame_buffer,
+            size,
+        });
+        Ok(())
+    }
+
+    pub fn shadows_enabled(&self) -> bool {
+        self.shadow.is_some()
+    }
+
+    /// Once per frame after [Flashlight::update] and before the views are drawn: draw the
+    /// shadow casters into the depth map with `draw_casters(matrix_pv)`, for
+    /// [LightManager::shadow].  The casters must not sample the map themselves, so draw them
+    /// with a [LightManager] of their own.  None while the light is off or without
+    /// [Flashlight::enable_shadows].
+    pub fn render_shadows(
+        &self,
+        gpu_state: &mut GPUState,
+        draw_casters: impl FnOnce(&XrMatrix4x4f, &mut GPUState) -> Result<(), GLErrorWrapper>,
+    ) -> Result<Option<ShadowMap>, GLErrorWrapper> {
+        let (Some(shadow), Some(pose), Some(light)) = (&self.shadow, &self.pose, self.light) else {
+            return Ok(None);
+        };
+        let tan = self.outer_angle.tan();
+        let projection = xr_matrix4x4f_create_projection(
+            GraphicsAPI::GraphicsOpenGLES,
+            -tan,
+            tan,
+            tan,
+            -tan,
+            SHADOW_NEAR,
+            self.range,
+        );
+        let matrix_pv = projection * xr_matrix4x4f_invert_rigid_body(&pose.matrix());
+
+        let mut previous: GLint = 0;
+        let mut viewport = [0 as GLint; 4];
+        unsafe {
+            gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous);
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+        shadow.frame_buffer.bind()?;
+        unsafe {
+            // last frame's map may still be on its unit, and it is the target now
+            gl::ActiveTexture(gl::TEXTURE0 + SHADOW_MAP_UNIT);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::Viewport(0, 0, shadow.size, shadow.size);
+            gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+            gl::Enable(gl::DEPTH_TEST);
+            gl::DepthMask(gl::TRUE);
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+            // pushes the casters' depth back a little, against shadow acne on slopes
+            gl::Enable(gl::POLYGON_OFFSET_FILL);
+            gl::PolygonOffset(2.0, 4.0);
+        }
+        let drawn = draw_casters(&matrix_pv, gpu_state);
+        unsafe {
+            gl::Disable(gl::POLYGON_OFFSET_FILL);
+            gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous as GLuint);
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        }
+        drawn?;
+        explode_if_gl_error()?;
+
+        // clip space to 0..1 texture coordinates and depth
+        let to_texture = xr_matrix4x4f_create_translation(0.5, 0.5, 0.5)
+            * xr_matrix4x4f_create_scale(0.5, 0.5, 0.5);
+        Ok(Some(ShadowMap {
+            light,
+            matrix: to_texture * matrix_pv,
+            texture: *shadow.depth.0.unwrap(),
+            bias: self.shadow_bias,
+        }))
+    }
+
+    pub fn set_on(&mut self, on: bool) {
+        self.on = on;
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    pub fn toggle(&mut self) {
+        self.on = !self.on;
+    }
+
+    /// Once per frame, with the pose of the controller holding it (it shines along the
+    /// controller's -Z, like its pointer ray).  The light is only in `lights` while the
+    /// flashlight is on and the controller is tracked.
+    pub fn update(&mut self, grip: Option<Pose>, lights: &mut LightManager) {
+        self.pose = grip.filter(|_| self.on);
+        let Some(pose) = &self.pose else {
+            self.remove_light(lights);
+            return;
+        };
+        let light = Light::new(self.light_kind(pose), self.color, self.intensity);
+        match self.light.and_then(|id| lights.get_mut(id)) {
+            Some(existing) => *existing = light,
+            None => self.light = Some(lights.add(light)),
+        }
+    }
+
+    /// Take the light out of `lights`, e.g. before dropping the flashlight.
+    pub fn remove_from(&mut self, lights: &mut LightManager) {
+        self.remove_light(lights);
+        self.pose = None;
+    }
+
+    fn remove_light(&mut self, lights: &mut LightManager) {
+        if let Some(id) = self.light.take() {
+            lights.remove(id);
+            if lights.shadow.is_some_and(|map| map.light == id) {
+                lights.shadow = None;
+            }
+        }
+    }
+
+    fn light_kind(&self, pose: &Pose) -> LightKind {
+        let rotation = xr_matrix4x4f_create_from_quaternion(&pose.orientation);
+        let forward = xr_matrix4x4f_transform_vector3f(&rotation, &XrVector3f::new(0.0, 0.0, -1.0));
+        let p = pose.position;
+        LightKind::Spot {
+            position: [p.x, p.y, p.z],
+            direction: [forward.x, forward.y, forward.z],
+            range: self.range,
+            inner_angle: self.inner_angle,
+            outer_angle: self.outer_angle,
+        }
+    }
+
+    /// The beam, added onto what is behind it; for [gl_thin::render_queue::RenderLayer::Transparent].
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let Some(pose) = &self.pose else {
+            return Ok(());
+        };
+        if self.beam_opacity <= 0.0 {
+            return Ok(());
+        }
+        let radius = self.beam_length * self.outer_angle.tan();
+        let model = pose.matrix() * xr_matrix4x4f_create_scale(radius, radius, self.beam_length);
+        let [r, g, b] = self.color;
+        self.shader
+            .set_params(&(*matrix_pv * model), &[r, g, b, self.beam_opacity])?;
+        let drawn = self
+            .cone
+            .bind(gpu_state)
+            .and_then(|bound| bound.draw_elements(gl::TRIANGLES, self.n_indices, 0));
+        self.shader.restore();
+        drawn
+    }
+}
+
+/// position and fade of every vertex, and the triangles of the cone's side; open at the rim
+fn cone_mesh() -> (Vec<GLfloat>, Vec<GLushort>) {
+    let mut vertices = vec![0.0, 0.0, 0.0, 1.0];
+    let mut indices = vec![];
+    for i in 0..CONE_SEGMENTS {
+        let (s, c) = (TAU * i as f32 / CONE_SEGMENTS as f32).sin_cos();
+        vertices.extend_from_slice(&[c, s, -1.0, 0.0]);
+        let next = (i + 1) % CONE_SEGMENTS;
+        indices.extend_from_slice(&[0, 1 + i as GLushort, 1 + next as GLushort]);
+    }
+    (vertices, indices)
+}
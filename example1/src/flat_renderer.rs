@@ -0,0 +1,495 @@
+//! Running on a phone without an OpenXR runtime.  Instead of failing at startup, the app
+//! draws the same [MyScene] into the Android window from a single camera at the head's
+//! starting position, and dragging a finger across the screen looks around.  Why there is
+//! no headset is written across the bottom of the screen.
+
+use crate::drawcore::ActiveRenderer;
+use crate::lod::LodView;
+use crate::render_thread::RestartSignal;
+use crate::scene::{inverse_view_matrix, projection_matrix, MyScene};
+use crate::startup::StartupOptions;
+use crate::text_painting::GlyphCache;
+use crate::ui::label::Label;
+use crate::ui::script::{self, UiScriptReport};
+use crate::ui::units::{eye_pixels_per_radian, PanelDensity};
+use crate::Drawable;
+use android_activity::AndroidApp;
+use bob_shaders::masked_solid_shader::MaskedSolidShader;
+use bob_shaders::material::{DepthMode, Material};
+use gl::types::GLsizei;
+use gl_thin::errors::{RuntimeUnavailable, XrErrorWrapped};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper};
+use gl_thin::linear::{xr_matrix4x4f_create_translation, XrFovf, XrQuaternionf, XrVector3f};
+use gl_thin::recipes::RecipeBook;
+use gl_thin::render_queue::RenderQueue;
+use glutin::context::{ContextAttributesBuilder, PossiblyCurrentContext};
+use glutin::display::{Display, DisplayApiPreference};
+use glutin::error::ErrorKind;
+use glutin::prelude::*;
+use glutin::surface::{Surface, SurfaceAttributesBuilder, WindowSurface};
+use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
+use std::collections::HashMap;
+use std::error::Error;
+use std::f32::consts::FRAC_PI_2;
+use std::num::NonZeroU32;
+use std::time::Instant;
+use winit::dpi::PhysicalPosition;
+use winit::event::{KeyEvent, Touch, TouchPhase};
+use winit::event_loop::ActiveEventLoop;
+use winit::platform::android::ActiveEventLoopExtAndroid;
+use winit::window::Window;
+
+/// of the shorter side of the screen
+const FIELD_OF_VIEW: f32 = 1.2;
+
+/// keeps the camera from flipping over the poles
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.05;
+
+/// how far in front of the camera the [UnavailableNotice] floats, and how tall its lines are,
+/// meters
+const NOTICE_DISTANCE: f32 = 1.0;
+const NOTICE_LINE_HEIGHT: f32 = 0.04;
+/// characters per line of the notice, so it fits across a phone held upright
+const NOTICE_COLUMNS: usize = 36;
+
+pub struct FlatRenderer {
+    /// why there is no headset; [RuntimeUnavailable::user_message] is worth showing
+    pub unavailable: RuntimeUnavailable,
+    /// [RuntimeUnavailable::user_message] over the scene
+    notice: UnavailableNotice,
+    pub scene: MyScene,
+    pub gpu_state: GPUState,
+    /// resources drawn besides the scene's, made again when the context is lost, see
+    /// [FlatRenderer::recover_context]
+    pub recipes: RecipeBook,
+    /// where the camera is, in the scene's LOCAL space; the origin is where a headset
+    /// would have started
+    pub eye: XrVector3f,
+    /// radians, positive turns left
+    yaw: f32,
+    /// radians, positive looks up
+    pitch: f32,
+    /// where each finger on the screen was last seen
+    touches: HashMap<u64, PhysicalPosition<f64>>,
+    width: u32,
+    height: u32,
+    // dropped in this order: the context before the surface before the window
+    context: PossiblyCurrentContext,
+    surface: Surface<WindowSurface>,
+    window: Window,
+}
+
+impl FlatRenderer {
+    pub fn new(
+        event_loop: &ActiveEventLoop,
+        unavailable: RuntimeUnavailable,
+    ) -> Result<Self, Box<dyn Error>> {
+        let raw_display = event_loop.raw_display_handle()?;
+        let Display::Egl(glutin_display) =
+            unsafe { Display::new(raw_display, DisplayApiPreference::Egl) }?;
+
+        let window = event_loop.create_window(Window::default_attributes())?;
+        let raw_window_handle = window.raw_window_handle()?;
+
+        let template = ActiveRenderer::config_template(raw_window_handle);
+        let config = unsafe { glutin_display.find_configs(template) }?
+            .next()
+            .ok_or("no EGL config for the window")?;
+
+        let size = window.inner_size();
+        let (width, height) = (size.width.max(1), size.height.max(1));
+        let surface = {
+            let attr = SurfaceAttributesBuilder::<WindowSurface>::new().build(
+                raw_window_handle,
+                NonZeroU32::new(width).unwrap(),
+                NonZeroU32::new(height).unwrap(),
+            );
+            unsafe { glutin_display.create_window_surface(&config, &attr) }?
+        };
+        let context = {
+            let attr = ContextAttributesBuilder::new().build(Some(raw_window_handle));
+            unsafe { glutin_display.create_context(&config, &attr) }?
+        }
+        .make_current(&surface)?;
+
+        let mut gpu_state = GPUState::new();
+        let scene = MyScene::new(&mut gpu_state)?;
+        let notice = UnavailableNotice::new(&unavailable.user_message(), &mut gpu_state)?;
+
+        Ok(Self {
+            unavailable,
+            notice,
+            scene,
+            gpu_state,
+            recipes: RecipeBook::new(),
+            eye: XrVector3f::default_translation(),
+            yaw: 0.0,
+            pitch: 0.0,
+            touches: HashMap::new(),
+            width,
+            height,
+            context,
+            surface,
+            window,
+        })
+    }
+
+    /// symmetric, [FIELD_OF_VIEW] across the shorter side
+    pub fn fov(&self) -> XrFovf {
+        let half = (0.5 * FIELD_OF_VIEW).tan();
+        let aspect = self.width as f32 / self.height as f32;
+        let (tan_x, tan_y) = if aspect >= 1.0 {
+            (half * aspect, half)
+        } else {
+            (half, half / aspect)
+        };
+        XrFovf {
+            angle_left: -tan_x.atan(),
+            angle_right: tan_x.atan(),
+            angle_up: tan_y.atan(),
+            angle_down: -tan_y.atan(),
+        }
+    }
+
+    /// yaw about +Y, then pitch about the turned +X
+    fn orientation(&self) -> XrQuaternionf {
+        let (sy, cy) = (0.5 * self.yaw).sin_cos();
+        let (sp, cp) = (0.5 * self.pitch).sin_cos();
+        XrQuaternionf::new(cy * sp, sy * cp, -sy * sp, cy * cp)
+    }
+
+    /// Dragging moves the world under the finger: right turns left, down looks up.
+    fn look(&mut self, dx: f64, dy: f64) {
+        let fov = self.fov();
+        let radians_per_pixel = (fov.angle_up - fov.angle_down) / self.height as f32;
+        self.yaw += dx as f32 * radians_per_pixel;
+        self.pitch = (self.pitch + dy as f32 * radians_per_pixel).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    fn draw(&mut self) -> Result<(), Box<dyn Error>> {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::Viewport(0, 0, self.width as GLsizei, self.height as GLsizei);
+        }
+        explode_if_gl_error()?;
+
+        let fov = self.fov();
+        let matrix_pv =
+            projection_matrix(&fov) * inverse_view_matrix(&self.orientation(), &self.eye);
+        let lod_view = LodView::new(self.eye, &fov, self.height as i32);
+
+        let mut queue = RenderQueue::new();
+        self.scene
+            .queue_draws(&mut queue, matrix_pv, lod_view, &None, &[], None);
+        queue.execute(&mut self.gpu_state)?;
+        self.notice.draw(&fov, self.height, &mut self.gpu_state)?;
+
+        match self.surface.swap_buffers(&self.context) {
+            Err(e) if e.error_kind() == ErrorKind::ContextLost => self.recover_context(),
+            result => Ok(result?),
+        }
+    }
+
+    /// After EGL reports the context lost, e.g. to a driver reset: make a new one on the same
+    /// surface, then the [FlatRenderer::recipes] and the scene again.  The old objects' names
+    /// mean nothing to the new context, so they are leaked rather than deleted, as
+    /// [RecipeBook::recreate_all] does.
+    fn recover_context(&mut self) -> Result<(), Box<dyn Error>> {
+        log::warn!("EGL context lost, making a new one");
+        let display = self.context.display();
+        let config = self.context.config();
+        let attr = ContextAttributesBuilder::new().build(Some(self.window.raw_window_handle()?));
+        self.context =
+            unsafe { display.create_context(&config, &attr) }?.make_current(&self.surface)?;
+
+        self.gpu_state = GPUState::new();
+        self.recipes.recreate_all(&mut self.gpu_state)?;
+        let scene = MyScene::new(&mut self.gpu_state)?;
+        std::mem::forget(std::mem::replace(&mut self.scene, scene));
+        let notice = UnavailableNotice::new(&self.unavailable.user_message(), &mut self.gpu_state)?;
+        std::mem::forget(std::mem::replace(&mut self.notice, notice));
+        Ok(())
+    }
+}
+
+/// A few lines of white text centered across the bottom of the screen, drawn over the scene
+struct UnavailableNotice {
+    lines: Vec<String>,
+    glyphs: GlyphCache,
+    program: MaskedSolidShader,
+    /// one per line, laid out at [UnavailableNotice::font_size]
+    labels: Vec<Label>,
+    font_size: f32,
+}
+
+impl UnavailableNotice {
+    fn new(message: &str, gpu_state: &mut GPUState) -> Result<Self, GLErrorWrapper> {
+        Ok(Self {
+            lines: wrap(message, NOTICE_COLUMNS),
+            glyphs: GlyphCache::new(1024, gpu_state)?,
+            program: MaskedSolidShader::new()?,
+            labels: vec![],
+            font_size: 0.0,
+        })
+    }
+
+    /// with the camera's `fov` over a screen `height` pixels tall
+    fn draw(
+        &mut self,
+        fov: &XrFovf,
+        height: u32,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let pixels_per_radian = eye_pixels_per_radian(height, fov.angle_down, fov.angle_up);
+        let font_size = PanelDensity::for_viewing_distance(pixels_per_radian, NOTICE_DISTANCE)
+            .font_size(NOTICE_LINE_HEIGHT);
+        let stale = self.labels.iter().any(|label| label.is_stale(&self.glyphs));
+        if font_size != self.font_size || stale {
+            self.font_size = font_size;
+            self.labels = self
+                .lines
+                .iter()
+                .map(|line| {
+                    Label::new(
+                        line,
+                        font_size,
+                        NOTICE_LINE_HEIGHT,
+                        f32::INFINITY,
+                        &mut self.glyphs,
+                        &self.program,
+                        gpu_state,
+                    )
+                })
+                .collect::<Result<_, _>>()?;
+        }
+
+        // the last line half a line above the bottom of the view
+        let bottom = NOTICE_DISTANCE * fov.angle_down.tan() + NOTICE_LINE_HEIGHT;
+        let projection = projection_matrix(fov);
+        let material = Material::default().with_depth(DepthMode::AlwaysOnTop);
+        for (i, label) in self.labels.iter().rev().enumerate() {
+            let y = bottom + i as f32 * NOTICE_LINE_HEIGHT;
+            let matrix = projection
+                * xr_matrix4x4f_create_translation(-0.5 * label.width, y, -NOTICE_DISTANCE);
+            label.draw(
+                &matrix,
+                &self.glyphs,
+                &self.program,
+                &[1.0, 1.0, 1.0, 1.0],
+                &material,
+                gpu_state,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// `text` broken at spaces into lines of at most `columns` characters, unless a single word
+/// is longer
+fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= columns => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
+impl Drawable for FlatRenderer {
+    fn handle_events_and_draw(&mut self) {
+        if let Err(e) = self.draw() {
+            log::error!("malfunction during flat draw {}", e);
+        }
+    }
+
+    fn suspend(&mut self) {
+        // the window surface goes away with the activity; everything is rebuilt on resume
+        self.touches.clear();
+    }
+
+    fn resized(&mut self, width: u32, height: u32) {
+        let (Some(w), Some(h)) = (NonZeroU32::new(width), NonZeroU32::new(height)) else {
+            return;
+        };
+        self.surface.resize(&self.context, w, h);
+        self.width = width;
+        self.height = height;
+        self.window.request_redraw();
+    }
+
+    fn touch(&mut self, touch: &Touch) {
+        match touch.phase {
+            TouchPhase::Started => {
+                self.touches.insert(touch.id, touch.location);
+            }
+            TouchPhase::Moved => {
+                if let Some(last) = self.touches.insert(touch.id, touch.location) {
+                    // several fingers each add their share, so a two-finger drag is not double speed
+                    let share = self.touches.len() as f64;
+                    self.look(
+                        (touch.location.x - last.x) / share,
+                        (touch.location.y - last.y) / share,
+                    );
+                }
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.touches.remove(&touch.id);
+            }
+        }
+    }
+}
+
+/// What `android_main` runs: the headset renderer, or a [FlatRenderer] when OpenXR
+/// could not start, e.g. on a phone with no runtime installed.
+pub enum AnyRenderer {
+    Xr(Box<ActiveRenderer>),
+    Flat(FlatRenderer),
+}
+
+impl AnyRenderer {
+    /// Try OpenXR first.  If it fails to come up (no loader, no runtime, no headset, see
+    /// [XrErrorWrapped::runtime_unavailable]), fall back to drawing flat; other failures are
+    /// returned as they are.
+    pub fn new(
+        event_loop: &ActiveEventLoop,
+        options: &StartupOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut renderer = Self::start(event_loop, options)?;
+        if options.ui_scripts {
+            renderer.run_ui_scripts(event_loop.android_app());
+        }
+        Ok(renderer)
+    }
+
+    fn start(
+        event_loop: &ActiveEventLoop,
+        options: &StartupOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        let e = match ActiveRenderer::new(event_loop, options) {
+            Ok(renderer) => return Ok(Self::Xr(Box::new(renderer))),
+            Err(e) => e,
+        };
+        let unavailable = e
+            .downcast_ref::<XrErrorWrapped>()
+            .and_then(|e| e.runtime_unavailable())
+            .cloned();
+        match unavailable {
+            Some(unavailable) => {
+                log::warn!("OpenXR is not available ({}); rendering to the window", e);
+                Ok(Self::Flat(FlatRenderer::new(event_loop, unavailable)?))
+            }
+            None => Err(e),
+        }
+    }
+
+    /// OpenXR only, after the runtime was lost: while it restarts it may well look
+    /// unavailable, which is no reason to give up on it for good.
+    pub fn reconnect(
+        event_loop: &ActiveEventLoop,
+        options: &StartupOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        let renderer = ActiveRenderer::new(event_loop, options)?;
+        Ok(Self::Xr(Box::new(renderer)))
+    }
+
+    /// see [StartupOptions::ui_scripts]
+    fn run_ui_scripts(&mut self, app: &AndroidApp) {
+        let gpu_state = match self {
+            Self::Xr(renderer) => &mut renderer.gpu_state,
+            Self::Flat(renderer) => &mut renderer.gpu_state,
+        };
+        match script::run_bundled(app, Instant::now(), gpu_state) {
+            Ok(reports) => reports.iter().for_each(UiScriptReport::log),
+            Err(e) => log::warn!("ui script malfunction {}", e),
+        }
+    }
+
+    fn drawable(&mut self) -> &mut dyn Drawable {
+        match self {
+            Self::Xr(renderer) => renderer.as_mut(),
+            Self::Flat(renderer) => renderer,
+        }
+    }
+}
+
+impl Drawable for AnyRenderer {
+    fn handle_events_and_draw(&mut self) {
+        self.drawable().handle_events_and_draw()
+    }
+
+    fn suspend(&mut self) {
+        self.drawable().suspend()
+    }
+
+    fn focus_changed(&mut self, focused: bool) {
+        self.drawable().focus_changed(focused)
+    }
+
+    fn keyboard_input(&mut self, event: &KeyEvent) {
+        self.drawable().keyboard_input(event)
+    }
+
+    fn resized(&mut self, width: u32, height: u32) {
+        self.drawable().resized(width, height)
+    }
+
+    fn touch(&mut self, touch: &Touch) {
+        self.drawable().touch(touch)
+    }
+
+    fn wake_at(&self) -> Option<Instant> {
+        match self {
+            Self::Xr(renderer) => renderer.wake_at(),
+            Self::Flat(renderer) => renderer.wake_at(),
+        }
+    }
+
+    fn wants_restart(&self) -> bool {
+        match self {
+            Self::Xr(renderer) => renderer.wants_restart(),
+            Self::Flat(renderer) => renderer.wants_restart(),
+        }
+    }
+
+    fn runtime_lost(&self) -> bool {
+        match self {
+            Self::Xr(renderer) => renderer.runtime_lost(),
+            Self::Flat(renderer) => renderer.runtime_lost(),
+        }
+    }
+
+    fn set_restart_signal(&mut self, signal: RestartSignal) {
+        match self {
+            Self::Xr(renderer) => renderer.set_restart_signal(signal),
+            Self::Flat(renderer) => renderer.set_restart_signal(signal),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_at_spaces() {
+        assert_eq!(
+            wrap("No OpenXR runtime  is available.", 12),
+            ["No OpenXR", "runtime is", "available."]
+        );
+    }
+
+    #[test]
+    fn long_words_get_a_line_of_their_own() {
+        assert_eq!(
+            wrap("a supercalifragilistic b", 8),
+            ["a", "supercalifragilistic", "b"]
+        );
+        assert!(wrap("", 8).is_empty());
+    }
+}
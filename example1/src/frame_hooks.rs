@@ -0,0 +1,177 @@
+//! Application code run at fixed points of every frame, for telemetry, custom passes and
+//! such, without changing [crate::drawcore].  Like the timers of
+//! [FrameScheduler](crate::frame_scheduler::FrameScheduler), most hooks get their owner
+//! (usually [crate::drawcore::ActiveRenderer]); [FrameHooks::pre_render_view] ones run
+//! while the views are painted, when the owner is in use, and get the view and the GPU
+//! instead.
+//!
+//! In frame order: [FrameHooks::pre_update], the renderer's own updates,
+//! [FrameHooks::post_update], [FrameHooks::pre_render_view] for each view, then
+//! [FrameHooks::post_render] once the frame is submitted and its input handled.
+
+use crate::pose_stream::Pose;
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::XrFovf;
+use openxr_sys::Time;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HookId(u64);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HookStage {
+    PreUpdate,
+    PostUpdate,
+    PostRender,
+}
+
+/// The view about to be painted, for [FrameHooks::pre_render_view]
+#[derive(Copy, Clone, Debug)]
+pub struct ViewInfo {
+    pub frame_index: u64,
+    /// 0 for the left eye
+    pub eye: usize,
+    /// in the world, see [crate::drawcore::ActiveRenderer::world_scale]
+    pub pose: Pose,
+    pub fov: XrFovf,
+    /// pixels
+    pub width: u32,
+    pub height: u32,
+    pub predicted_display_time: Time,
+}
+
+type OwnerHook<C> = Box<dyn FnMut(&mut C)>;
+type ViewHook = Box<dyn FnMut(&ViewInfo, &mut GPUState) -> Result<(), GLErrorWrapper>>;
+
+pub struct FrameHooks<C> {
+    pre_update: Vec<(HookId, OwnerHook<C>)>,
+    post_update: Vec<(HookId, OwnerHook<C>)>,
+    post_render: Vec<(HookId, OwnerHook<C>)>,
+    pre_render_view: Vec<(HookId, ViewHook)>,
+    /// hooks removed while their stage was running
+    removed: Vec<HookId>,
+    next_id: u64,
+}
+
+impl<C> Default for FrameHooks<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> FrameHooks<C> {
+    pub fn new() -> Self {
+        Self {
+            pre_update: vec![],
+            post_update: vec![],
+            post_render: vec![],
+            pre_render_view: vec![],
+            removed: vec![],
+            next_id: 1,
+        }
+    }
+
+    /// at the start of every frame, before the renderer updates anything
+    pub fn pre_update(&mut self, hook: impl FnMut(&mut C) + 'static) -> HookId {
+        self.add_owner_hook(HookStage::PreUpdate, Box::new(hook))
+    }
+
+    /// after the renderer's own updates, before any view is painted; changes to the scene
+    /// show in this frame
+    pub fn post_update(&mut self, hook: impl FnMut(&mut C) + 'static) -> HookId {
+        self.add_owner_hook(HookStage::PostUpdate, Box::new(hook))
+    }
+
+    /// once the frame is submitted and the input read with it has been acted on
+    pub fn post_render(&mut self, hook: impl FnMut(&mut C) + 'static) -> HookId {
+        self.add_owner_hook(HookStage::PostRender, Box::new(hook))
+    }
+
+    /// Before each view is painted, e.g. to render into a texture the scene shows.  Leave
+    /// the framebuffer bindings as they were; errors are logged and the view is painted
+    /// anyway.
+    pub fn pre_render_view(
+        &mut self,
+        hook: impl FnMut(&ViewInfo, &mut GPUState) -> Result<(), GLErrorWrapper> + 'static,
+    ) -> HookId {
+        let id = self.next_id();
+        self.pre_render_view.push((id, Box::new(hook)));
+        id
+    }
+
+    /// Safe to call from inside a hook, including the one being removed.
+    pub fn remove(&mut self, id: HookId) {
+        let before = self.len();
+        for hooks in [
+            &mut self.pre_update,
+            &mut self.post_update,
+            &mut self.post_render,
+        ] {
+            hooks.retain(|(i, _)| *i != id);
+        }
+        self.pre_render_view.retain(|(i, _)| *i != id);
+        if self.len() == before {
+            self.removed.push(id);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pre_update.len()
+            + self.post_update.len()
+            + self.post_render.len()
+            + self.pre_render_view.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Run `stage`'s hooks in the order they were added.  `hooks` finds this inside `owner`,
+    /// which the hooks get mutable access to.
+    pub fn run(owner: &mut C, stage: HookStage, hooks: fn(&mut C) -> &mut Self) {
+        let this = hooks(owner);
+        if this.stage_mut(stage).is_empty() {
+            return;
+        }
+        this.removed.clear();
+        let mut running = std::mem::take(this.stage_mut(stage));
+        for (_, hook) in &mut running {
+            hook(owner);
+        }
+        let this = hooks(owner);
+        let removed = std::mem::take(&mut this.removed);
+        running.retain(|(id, _)| !removed.contains(id));
+        // ahead of any added while they ran
+        let added = std::mem::replace(this.stage_mut(stage), running);
+        this.stage_mut(stage).extend(added);
+    }
+
+    /// Run the [FrameHooks::pre_render_view] hooks for `view`.
+    pub fn run_pre_render_view(&mut self, view: &ViewInfo, gpu_state: &mut GPUState) {
+        for (_, hook) in &mut self.pre_render_view {
+            if let Err(e) = hook(view, gpu_state) {
+                log::warn!("pre_render_view hook malfunction {}", e);
+            }
+        }
+    }
+
+    fn add_owner_hook(&mut self, stage: HookStage, hook: OwnerHook<C>) -> HookId {
+        let id = self.next_id();
+        self.stage_mut(stage).push((id, hook));
+        id
+    }
+
+    fn stage_mut(&mut self, stage: HookStage) -> &mut Vec<(HookId, OwnerHook<C>)> {
+        match stage {
+            HookStage::PreUpdate => &mut self.pre_update,
+            HookStage::PostUpdate => &mut self.post_update,
+            HookStage::PostRender => &mut self.post_render,
+        }
+    }
+
+    fn next_id(&mut self) -> HookId {
+        let id = HookId(self.next_id);
+        self.next_id += 1;
+        id
+    }
+}
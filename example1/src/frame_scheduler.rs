@@ -0,0 +1,166 @@
+//! Pacing for the render loop.  While the XR session is delivering frames, `xrWaitFrame`
+//! blocks until the runtime wants the next one, so the render thread can simply spin;
+//! when it is not, we sleep until the next timer or a retry.
+//! Timers registered here run on the render thread, with access to their owner (usually
+//! [crate::drawcore::ActiveRenderer]).
+
+use openxr_sys::Time;
+use std::time::{Duration, Instant};
+
+/// how long to sleep between attempts while the XR runtime is not handing out frames
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimerId(u64);
+
+enum TimerCallback<C> {
+    Once(Box<dyn FnOnce(&mut C)>),
+    Repeating(Duration, Box<dyn FnMut(&mut C)>),
+}
+
+struct Timer<C> {
+    id: TimerId,
+    due: Instant,
+    callback: TimerCallback<C>,
+}
+
+pub struct FrameScheduler<C> {
+    timers: Vec<Timer<C>>,
+    /// repeating timers cancelled while they were running
+    cancelled: Vec<TimerId>,
+    next_id: u64,
+    frame_index: u64,
+    last_display_time: Option<Time>,
+    frame_delta: Duration,
+    frame_period: Duration,
+    frames_stalled: bool,
+}
+
+impl<C> Default for FrameScheduler<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> FrameScheduler<C> {
+    pub fn new() -> Self {
+        Self {
+            timers: vec![],
+            cancelled: vec![],
+            next_id: 1,
+            frame_index: 0,
+            last_display_time: None,
+            frame_delta: Duration::ZERO,
+            frame_period: Duration::ZERO,
+            frames_stalled: false,
+        }
+    }
+
+    /// run `callback` once, on the first frame at least `delay` from now
+    pub fn after(&mut self, delay: Duration, callback: impl FnOnce(&mut C) + 'static) -> TimerId {
+        self.add(delay, TimerCallback::Once(Box::new(callback)))
+    }
+
+    /// run `callback` every `period`, starting one `period` from now.
+    /// It runs at most once per frame, however short the period.
+    pub fn every(&mut self, period: Duration, callback: impl FnMut(&mut C) + 'static) -> TimerId {
+        self.add(period, TimerCallback::Repeating(period, Box::new(callback)))
+    }
+
+    fn add(&mut self, delay: Duration, callback: TimerCallback<C>) -> TimerId {
+        let id = TimerId(self.next_id);
+        self.next_id += 1;
+        self.timers.push(Timer {
+            id,
+            due: Instant::now() + delay,
+            callback,
+        });
+        id
+    }
+
+    /// Safe to call from inside a timer's own callback.
+    pub fn cancel(&mut self, id: TimerId) {
+        let before = self.timers.len();
+        self.timers.retain(|timer| timer.id != id);
+        if self.timers.len() == before {
+            self.cancelled.push(id);
+        }
+    }
+
+    /// Run every timer that is due.  `scheduler` finds this scheduler inside `owner`,
+    /// which the callbacks get mutable access to.
+    pub fn run_due(owner: &mut C, now: Instant, scheduler: fn(&mut C) -> &mut Self) {
+        let this = scheduler(owner);
+        this.cancelled.clear();
+        let (due, waiting) = std::mem::take(&mut this.timers)
+            .into_iter()
+            .partition::<Vec<_>, _>(|timer| timer.due <= now);
+        this.timers = waiting;
+
+        for timer in due {
+            match timer.callback {
+                TimerCallback::Once(callback) => callback(owner),
+                TimerCallback::Repeating(period, mut callback) => {
+                    callback(owner);
+                    let this = scheduler(owner);
+                    if !this.cancelled.contains(&timer.id) {
+                        // a late frame should not cause a burst of catch-up calls
+                        let due = (timer.due + period).max(now);
+                        this.timers.push(Timer {
+                            id: timer.id,
+                            due,
+                            callback: TimerCallback::Repeating(period, callback),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Call with each XR frame's timing before painting it.
+    pub fn frame_started(&mut self, predicted_display_time: Time, predicted_period: Duration) {
+        if let Some(last) = self.last_display_time {
+            let nanos = predicted_display_time.as_nanos() - last.as_nanos();
+            self.frame_delta = Duration::from_nanos(nanos.max(0) as u64);
+        }
+        self.last_display_time = Some(predicted_display_time);
+        self.frame_period = predicted_period;
+        self.frame_index += 1;
+        self.frames_stalled = false;
+    }
+
+    /// the XR runtime did not give us a frame (e.g. the session is not running)
+    pub fn frame_failed(&mut self) {
+        self.frames_stalled = true;
+    }
+
+    /// frames painted so far
+    pub fn frame_index(&self) -> u64 {
+        self.frame_index
+    }
+
+    /// time between the predicted display times of the last two frames
+    pub fn frame_delta(&self) -> Duration {
+        self.frame_delta
+    }
+
+    /// the runtime's current refresh interval
+    pub fn frame_period(&self) -> Duration {
+        self.frame_period
+    }
+
+    /// `None` while XR frames are flowing (the frame loop paces itself); otherwise
+    /// when the render thread should wake up to try again or run a timer.
+    pub fn wake_at(&self) -> Option<Instant> {
+        if !self.frames_stalled {
+            return None;
+        }
+        let retry = Instant::now() + RETRY_INTERVAL;
+        Some(
+            self.timers
+                .iter()
+                .map(|timer| timer.due)
+                .fold(retry, Instant::min),
+        )
+    }
+}
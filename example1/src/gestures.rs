@@ -0,0 +1,163 @@
+//! Turns the buttons' raw up/down state into presses, clicks, double-clicks, long presses
+//! and chords, for the UI and the app to react to instead of polling actions.
+
+use std::time::{Duration, Instant};
+
+/// The buttons [GestureDetector] watches, see [crate::xr_input::XrInputs::gesture_buttons]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GestureButton {
+    /// the trigger, in whichever context has it
+    Select,
+    Menu,
+    Undo,
+    Redo,
+}
+
+impl GestureButton {
+    pub const ALL: [GestureButton; 4] = [
+        GestureButton::Select,
+        GestureButton::Menu,
+        GestureButton::Undo,
+        GestureButton::Redo,
+    ];
+
+    fn index(self) -> usize {
+        match self {
+            GestureButton::Select => 0,
+            GestureButton::Menu => 1,
+            GestureButton::Undo => 2,
+            GestureButton::Redo => 3,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Gesture {
+    Press(GestureButton),
+    Release(GestureButton),
+    /// Released before [GestureDetector::long_press].  The first click of a double-click is
+    /// reported too; wait out [GestureDetector::double_click] if that matters.
+    Click(GestureButton),
+    /// the second click within [GestureDetector::double_click] of the first
+    DoubleClick(GestureButton),
+    /// held for [GestureDetector::long_press]; no click follows the release
+    LongPress(GestureButton),
+    /// both buttons of one of [GestureDetector::chords] went down together; neither one
+    /// clicks or long-presses until both are released
+    Chord(GestureButton, GestureButton),
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct ButtonState {
+    down_since: Option<Instant>,
+    long_pressed: bool,
+    in_chord: bool,
+    last_click: Option<Instant>,
+}
+
+pub struct GestureDetector {
+    pub long_press: Duration,
+    pub double_click: Duration,
+    /// how far apart the presses of a chord may be
+    pub chord_window: Duration,
+    /// pairs to report as [Gesture::Chord], e.g. (Undo, Redo); none by default
+    pub chords: Vec<(GestureButton, GestureButton)>,
+    buttons: [ButtonState; GestureButton::ALL.len()],
+    events: Vec<Gesture>,
+}
+
+impl Default for GestureDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GestureDetector {
+    pub fn new() -> Self {
+        Self {
+            long_press: Duration::from_millis(500),
+            double_click: Duration::from_millis(300),
+            chord_window: Duration::from_millis(150),
+            chords: vec![],
+            buttons: Default::default(),
+            events: vec![],
+        }
+    }
+
+    /// Feed the buttons' state once per frame.  Returns the gestures that happened since the
+    /// last update, also available from [GestureDetector::events] until the next one.
+    pub fn update(&mut self, buttons: &[(GestureButton, bool)], now: Instant) -> &[Gesture] {
+        self.events.clear();
+
+        for &(button, down) in buttons {
+            let state = &mut self.buttons[button.index()];
+            match (state.down_since, down) {
+                (None, true) => {
+                    state.down_since = Some(now);
+                    state.long_pressed = false;
+                    state.in_chord = false;
+                    self.events.push(Gesture::Press(button));
+                }
+                (Some(_), false) => {
+                    state.down_since = None;
+                    self.events.push(Gesture::Release(button));
+                    if state.long_pressed || state.in_chord {
+                        continue;
+                    }
+                    let double = state
+                        .last_click
+                        .is_some_and(|last| now.duration_since(last) <= self.double_click);
+                    if double {
+                        state.last_click = None;
+                        self.events.push(Gesture::DoubleClick(button));
+                    } else {
+                        state.last_click = Some(now);
+                        self.events.push(Gesture::Click(button));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for &(a, b) in &self.chords {
+            let (sa, sb) = (self.buttons[a.index()], self.buttons[b.index()]);
+            let (Some(ta), Some(tb)) = (sa.down_since, sb.down_since) else {
+                continue;
+            };
+            let apart = ta.max(tb).duration_since(ta.min(tb));
+            if sa.in_chord || sb.in_chord || sa.long_pressed || sb.long_pressed {
+                continue;
+            }
+            if apart <= self.chord_window {
+                self.buttons[a.index()].in_chord = true;
+                self.buttons[b.index()].in_chord = true;
+                self.events.push(Gesture::Chord(a, b));
+            }
+        }
+
+        for button in GestureButton::ALL {
+            let state = &mut self.buttons[button.index()];
+            let Some(since) = state.down_since else {
+                continue;
+            };
+            if !state.long_pressed
+                && !state.in_chord
+                && now.duration_since(since) >= self.long_press
+            {
+                state.long_pressed = true;
+                self.events.push(Gesture::LongPress(button));
+            }
+        }
+
+        &self.events
+    }
+
+    /// what the last [GestureDetector::update] found
+    pub fn events(&self) -> &[Gesture] {
+        &self.events
+    }
+
+    pub fn is_down(&self, button: GestureButton) -> bool {
+        self.buttons[button.index()].down_since.is_some()
+    }
+}
@@ -0,0 +1,358 @@
+//! Dump what the headset is drawing to a binary glTF (.glb) so a desktop tool (Blender etc.)
+//! can show it from any angle.  Only meshes we still have the vertices for are exported,
+//! with flat `baseColorFactor` materials; lighting, fog and textures are left out.
+
+use gl_thin::linear::XrMatrix4x4f;
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+
+const GLB_MAGIC: u32 = 0x4654_6C67; // "glTF"
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+
+const COMPONENT_FLOAT: u32 = 5126;
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const TARGET_ARRAY_BUFFER: u32 = 34962;
+const TARGET_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GeometryId(usize);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MeshId(usize);
+
+struct Geometry {
+    position_accessor: usize,
+    normal_accessor: Option<usize>,
+    index_accessor: usize,
+}
+
+struct Accessor {
+    buffer_view: usize,
+    component_type: u32,
+    count: usize,
+    type_: &'static str,
+    /// only positions need these
+    min_max: Option<([f32; 3], [f32; 3])>,
+}
+
+struct BufferView {
+    offset: usize,
+    length: usize,
+    target: u32,
+}
+
+struct Mesh {
+    name: String,
+    geometry: usize,
+    material: usize,
+}
+
+struct Node {
+    name: String,
+    mesh: usize,
+    matrix: [f32; 16],
+}
+
+/// Collects geometry, meshes and nodes, then serializes them with [GltfDocument::to_glb].
+/// Geometry is stored once and can be shared by meshes of different colors.
+#[derive(Default)]
+pub struct GltfDocument {
+    bin: Vec<u8>,
+    buffer_views: Vec<BufferView>,
+    accessors: Vec<Accessor>,
+    geometries: Vec<Geometry>,
+    /// RGBA
+    materials: Vec<[f32; 4]>,
+    meshes: Vec<Mesh>,
+    nodes: Vec<Node>,
+}
+
+impl GltfDocument {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add triangles from interleaved vertex data, like [crate::suzanne::XYZABC].  `stride`
+    /// is in floats; the position is at `position_offset` and the optional normal at `normal_offset`.
+    pub fn add_geometry(
+        &mut self,
+        vertices: &[f32],
+        stride: usize,
+        position_offset: usize,
+        normal_offset: Option<usize>,
+        indices: impl IntoIterator<Item = u32>,
+    ) -> GeometryId {
+        let attribute = |offset: usize| -> Vec<[f32; 3]> {
+            vertices
+                .chunks_exact(stride)
+                .map(|v| [v[offset], v[offset + 1], v[offset + 2]])
+                .collect()
+        };
+
+        let positions = attribute(position_offset);
+        let min_max = positions.iter().fold(
+            ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]),
+            |(min, max), p| {
+                (
+                    [min[0].min(p[0]), min[1].min(p[1]), min[2].min(p[2])],
+                    [max[0].max(p[0]), max[1].max(p[1]), max[2].max(p[2])],
+                )
+            },
+        );
+        let position_accessor = self.add_vec3_accessor(&positions, Some(min_max));
+        let normal_accessor = normal_offset.map(|offset| {
+            let normals = attribute(offset);
+            self.add_vec3_accessor(&normals, None)
+        });
+
+        let indices: Vec<u32> = indices.into_iter().collect();
+        let buffer_view = self.add_buffer_view(
+            indices.iter().flat_map(|i| i.to_le_bytes()),
+            TARGET_ELEMENT_ARRAY_BUFFER,
+        );
+        self.accessors.push(Accessor {
+            buffer_view,
+            component_type: COMPONENT_UNSIGNED_INT,
+            count: indices.len(),
+            type_: "SCALAR",
+            min_max: None,
+        });
+        let index_accessor = self.accessors.len() - 1;
+
+        self.geometries.push(Geometry {
+            position_accessor,
+            normal_accessor,
+            index_accessor,
+        });
+        GeometryId(self.geometries.len() - 1)
+    }
+
+    /// a mesh made of `geometry` in a flat RGB color
+    pub fn add_mesh(&mut self, name: &str, geometry: GeometryId, base_color: [f32; 3]) -> MeshId {
+        let rgba = [base_color[0], base_color[1], base_color[2], 1.0];
+        let material = match self.materials.iter().position(|m| *m == rgba) {
+            Some(index) => index,
+            None => {
+                self.materials.push(rgba);
+                self.materials.len() - 1
+            }
+        };
+        self.meshes.push(Mesh {
+            name: name.to_string(),
+            geometry: geometry.0,
+            material,
+        });
+        MeshId(self.meshes.len() - 1)
+    }
+
+    /// place `mesh` in the scene; `world` is the model matrix it was drawn with
+    pub fn add_node(&mut self, name: &str, mesh: MeshId, world: &XrMatrix4x4f) {
+        self.nodes.push(Node {
+            name: name.to_string(),
+            mesh: mesh.0,
+            matrix: world.m,
+        });
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn add_buffer_view(&mut self, bytes: impl IntoIterator<Item = u8>, target: u32) -> usize {
+        let offset = self.bin.len();
+        self.bin.extend(bytes);
+        let length = self.bin.len() - offset;
+        // accessors must be aligned to their component size
+        self.bin.resize(self.bin.len().next_multiple_of(4), 0);
+        self.buffer_views.push(BufferView {
+            offset,
+            length,
+            target,
+        });
+        self.buffer_views.len() - 1
+    }
+
+    fn add_vec3_accessor(
+        &mut self,
+        values: &[[f32; 3]],
+        min_max: Option<([f32; 3], [f32; 3])>,
+    ) -> usize {
+        let buffer_view = self.add_buffer_view(
+            values
+                .iter()
+                .flat_map(|v| v.iter().flat_map(|x| x.to_le_bytes())),
+            TARGET_ARRAY_BUFFER,
+        );
+        self.accessors.push(Accessor {
+            buffer_view,
+            component_type: COMPONENT_FLOAT,
+            count: values.len(),
+            type_: "VEC3",
+            min_max,
+        });
+        self.accessors.len() - 1
+    }
+
+    fn json(&self) -> String {
+        let mut json = String::new();
+        json.push_str("{\"asset\":{\"version\":\"2.0\",\"generator\":\"android-openxr-exp\"}");
+        json.push_str(",\"scene\":0,\"scenes\":[{");
+        push_array(
+            &mut json,
+            "nodes",
+            (0..self.nodes.len()).map(|i| i.to_string()),
+        );
+        json.push_str("}]");
+
+        push_array(
+            &mut json,
+            "nodes",
+            self.nodes.iter().map(|node| {
+                format!(
+                    "{{\"name\":{},\"mesh\":{},\"matrix\":[{}]}}",
+                    json_string(&node.name),
+                    node.mesh,
+                    join(node.matrix.iter().map(|x| json_number(*x)))
+                )
+            }),
+        );
+
+        push_array(
+            &mut json,
+            "meshes",
+            self.meshes.iter().map(|mesh| {
+                let geometry = &self.geometries[mesh.geometry];
+                let mut attributes = format!("\"POSITION\":{}", geometry.position_accessor);
+                if let Some(normal) = geometry.normal_accessor {
+                    let _ = write!(attributes, ",\"NORMAL\":{}", normal);
+                }
+                format!(
+                    "{{\"name\":{},\"primitives\":[{{\"attributes\":{{{}}},\"indices\":{},\"material\":{}}}]}}",
+                    json_string(&mesh.name),
+                    attributes,
+                    geometry.index_accessor,
+                    mesh.material
+                )
+            }),
+        );
+
+        push_array(
+            &mut json,
+            "materials",
+            self.materials.iter().map(|rgba| {
+                format!(
+                    "{{\"pbrMetallicRoughness\":{{\"baseColorFactor\":[{}],\"metallicFactor\":0,\"roughnessFactor\":1}}}}",
+                    join(rgba.iter().map(|x| json_number(*x)))
+                )
+            }),
+        );
+
+        push_array(
+            &mut json,
+            "accessors",
+            self.accessors.iter().map(|accessor| {
+                let mut rval = format!(
+                    "{{\"bufferView\":{},\"componentType\":{},\"count\":{},\"type\":\"{}\"",
+                    accessor.buffer_view, accessor.component_type, accessor.count, accessor.type_
+                );
+                if let Some((min, max)) = accessor.min_max {
+                    let _ = write!(
+                        rval,
+                        ",\"min\":[{}],\"max\":[{}]",
+                        join(min.iter().map(|x| json_number(*x))),
+                        join(max.iter().map(|x| json_number(*x)))
+                    );
+                }
+                rval.push('}');
+                rval
+            }),
+        );
+
+        push_array(
+            &mut json,
+            "bufferViews",
+            self.buffer_views.iter().map(|view| {
+                format!(
+                    "{{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{},\"target\":{}}}",
+                    view.offset, view.length, view.target
+                )
+            }),
+        );
+
+        if !self.bin.is_empty() {
+            let _ = write!(json, ",\"buffers\":[{{\"byteLength\":{}}}]", self.bin.len());
+        }
+        json.push('}');
+        json
+    }
+
+    /// the whole document as a .glb: a JSON chunk followed by the binary chunk
+    pub fn to_glb(&self) -> Vec<u8> {
+        let mut json = self.json().into_bytes();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut chunks = vec![(CHUNK_JSON, &json)];
+        if !self.bin.is_empty() {
+            chunks.push((CHUNK_BIN, &self.bin));
+        }
+
+        let total = 12 + chunks.iter().map(|(_, c)| 8 + c.len()).sum::<usize>();
+        let mut glb = Vec::with_capacity(total);
+        for word in [GLB_MAGIC, GLB_VERSION, total as u32] {
+            glb.extend_from_slice(&word.to_le_bytes());
+        }
+        for (chunk_type, chunk) in chunks {
+            glb.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            glb.extend_from_slice(&chunk_type.to_le_bytes());
+            glb.extend_from_slice(chunk);
+        }
+        glb
+    }
+
+    pub fn write_glb(&self, path: &Path) -> io::Result<()> {
+        std::fs::write(path, self.to_glb())
+    }
+}
+
+/// `,"key":[items]`, or nothing when there are no items since glTF forbids empty arrays
+fn push_array(json: &mut String, key: &str, items: impl Iterator<Item = String>) {
+    let items = join(items);
+    if !items.is_empty() {
+        if !json.ends_with('{') {
+            json.push(',');
+        }
+        let _ = write!(json, "\"{}\":[{}]", key, items);
+    }
+}
+
+fn join(items: impl Iterator<Item = String>) -> String {
+    items.collect::<Vec<_>>().join(",")
+}
+
+/// JSON has no NaN or infinity
+fn json_number(x: f32) -> String {
+    if x.is_finite() {
+        format!("{}", x)
+    } else {
+        "0".to_string()
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut rval = String::with_capacity(s.len() + 2);
+    rval.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => rval.push_str("\\\""),
+            '\\' => rval.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(rval, "\\u{:04x}", c as u32);
+            }
+            c => rval.push(c),
+        }
+    }
+    rval.push('"');
+    rval
+}
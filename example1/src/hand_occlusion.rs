@@ -0,0 +1,136 @@
+//! Letting the user's real hands cover virtual things.  With passthrough (or the camera
+//! preview) behind the scene, virtual objects otherwise draw over the hands.  Each tracked
+//! hand's mesh goes into the depth buffer only, before anything else, so whatever is behind
+//! it fails the depth test and the hand shows through.
+
+use bob_shaders::skinned_mask_shader::{SkinnedMaskShader, SKINNED_VERTEX_STRIDE};
+use gl::types::{GLfloat, GLsizei, GLushort};
+use gl_thin::errors::XrErrorWrapped;
+use gl_thin::gl_fancy::{GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper};
+use gl_thin::hand_mesh::HandMeshTracker;
+use gl_thin::linear::XrMatrix4x4f;
+use gl_thin::openxr_helpers::{Backend, OpenXRComponent};
+use openxr::{Hand, Space};
+use openxr_sys::Time;
+use std::error::Error;
+
+struct OccludingHand {
+    tracker: HandMeshTracker,
+    buffers: VertexBufferBundle<'static, GLfloat, GLushort>,
+    n_indices: GLsizei,
+}
+
+/// The skinning matrices of each hand for one frame, from [HandOcclusion::locate]
+pub struct HandPoses {
+    /// in the order of [HandOcclusion]'s hands, `None` for the ones not tracked
+    skins: Vec<Option<Vec<XrMatrix4x4f>>>,
+}
+
+impl HandPoses {
+    /// the same hands moved by `matrix`, e.g. [gl_thin::world_scale::WorldScale::matrix]
+    pub fn transformed(mut self, matrix: &XrMatrix4x4f) -> Self {
+        for skin in self.skins.iter_mut().flatten() {
+            for joint in skin.iter_mut() {
+                *joint = *matrix * *joint;
+            }
+        }
+        self
+    }
+}
+
+pub struct HandOcclusion {
+    hands: Vec<OccludingHand>,
+    shader: SkinnedMaskShader,
+}
+
+impl HandOcclusion {
+    /// Fails if the runtime does not offer hand meshes, see
+    /// [OpenXRComponent::supports_hand_meshes].
+    pub fn new(
+        openxr: &OpenXRComponent<Backend>,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, Box<dyn Error>> {
+        if !openxr.supports_hand_meshes() {
+            return Err(XrErrorWrapped::simple("the runtime has no hand meshes").into());
+        }
+        let shader = SkinnedMaskShader::new()?;
+        let mut hands = vec![];
+        for hand in [Hand::LEFT, Hand::RIGHT] {
+            let tracker = HandMeshTracker::new(&openxr.xr_instance, &openxr.xr_session, hand)?;
+            let mesh = &tracker.mesh;
+            let mut vertices =
+                Vec::with_capacity(mesh.positions.len() * SKINNED_VERTEX_STRIDE as usize);
+            for ((position, joints), weights) in
+                mesh.positions.iter().zip(&mesh.joints).zip(&mesh.weights)
+            {
+                vertices.extend_from_slice(position);
+                vertices.extend(joints.map(|j| j as GLfloat));
+                vertices.extend_from_slice(weights);
+            }
+            let n_indices = mesh.indices.len() as GLsizei;
+            let buffers = VertexBufferBundle::new(
+                gpu_state,
+                vertices.into(),
+                mesh.indices.clone().into(),
+                SKINNED_VERTEX_STRIDE,
+                &shader.attributes(),
+            )?;
+            hands.push(OccludingHand {
+                tracker,
+                buffers,
+                n_indices,
+            });
+        }
+        Ok(Self { hands, shader })
+    }
+
+    /// where the hands are at `time`, relative to `base`; once per frame
+    pub fn locate(&self, base: &Space, time: Time) -> HandPoses {
+        let skins = self
+            .hands
+            .iter()
+            .map(|hand| {
+                hand.tracker.locate(base, time).unwrap_or_else(|e| {
+                    log::warn!("hand occlusion malfunction {}", e);
+                    None
+                })
+            })
+            .collect();
+        HandPoses { skins }
+    }
+
+    /// Write the hands' depth, leaving color alone.  Draw it right after the clear.
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        poses: &HandPoses,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        unsafe {
+            gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+            gl::Enable(gl::DEPTH_TEST);
+            gl::DepthMask(gl::TRUE);
+        }
+        explode_if_gl_error()?;
+
+        let mut result = Ok(());
+        for (hand, skin) in self.hands.iter().zip(&poses.skins) {
+            let Some(skin) = skin else {
+                continue;
+            };
+            result = self
+                .shader
+                .draw(matrix_pv, skin, &hand.buffers, hand.n_indices, gpu_state);
+            if result.is_err() {
+                break;
+            }
+        }
+
+        unsafe {
+            gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+        }
+        result?;
+        explode_if_gl_error()
+    }
+}
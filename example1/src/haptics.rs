@@ -0,0 +1,244 @@
+//! Controller vibration patterns made of timed segments, played per hand against the frame
+//! clock.  [HapticSequencer::update] says what each hand should be doing this frame;
+//! [crate::xr_input::XrInputs::vibrate] passes that on to the runtime.
+
+use std::time::{Duration, Instant};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum HapticHand {
+    Left,
+    Right,
+}
+
+/// One stretch of a pattern.  The amplitude moves linearly from `from` to `to`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HapticSegment {
+    pub duration: Duration,
+    /// 0..1
+    pub from: f32,
+    pub to: f32,
+    /// Hz; 0 lets the runtime pick
+    pub frequency: f32,
+}
+
+impl HapticSegment {
+    pub fn steady(duration: Duration, amplitude: f32) -> Self {
+        Self::ramp(duration, amplitude, amplitude)
+    }
+
+    pub fn ramp(duration: Duration, from: f32, to: f32) -> Self {
+        Self {
+            duration,
+            from,
+            to,
+            frequency: 0.0,
+        }
+    }
+
+    pub fn pause(duration: Duration) -> Self {
+        Self::steady(duration, 0.0)
+    }
+
+    pub fn with_frequency(mut self, frequency: f32) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    fn amplitude_at(&self, elapsed: Duration) -> f32 {
+        let fraction = if self.duration.is_zero() {
+            1.0
+        } else {
+            (elapsed.as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+        };
+        self.from + (self.to - self.from) * fraction
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HapticPattern {
+    pub name: String,
+    pub segments: Vec<HapticSegment>,
+}
+
+impl HapticPattern {
+    pub fn new(name: impl Into<String>, segments: Vec<HapticSegment>) -> Self {
+        Self {
+            name: name.into(),
+            segments,
+        }
+    }
+
+    /// a short tick, for buttons
+    pub fn click() -> Self {
+        Self::new(
+            "click",
+            vec![HapticSegment::steady(Duration::from_millis(15), 0.6)],
+        )
+    }
+
+    pub fn double_click() -> Self {
+        let tick = HapticSegment::steady(Duration::from_millis(15), 0.6);
+        Self::new(
+            "double-click",
+            vec![tick, HapticSegment::pause(Duration::from_millis(60)), tick],
+        )
+    }
+
+    /// swells from nothing to full over `duration`
+    pub fn ramp(duration: Duration) -> Self {
+        Self::new("ramp", vec![HapticSegment::ramp(duration, 0.0, 1.0)])
+    }
+
+    /// a double thump and a rest, once
+    pub fn heartbeat() -> Self {
+        Self::new(
+            "heartbeat",
+            vec![
+                HapticSegment::ramp(Duration::from_millis(60), 0.8, 0.2),
+                HapticSegment::pause(Duration::from_millis(100)),
+                HapticSegment::ramp(Duration::from_millis(80), 0.5, 0.1),
+                HapticSegment::pause(Duration::from_millis(500)),
+            ],
+        )
+    }
+
+    /// the built-in patterns by name
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "click" => Some(Self::click()),
+            "double-click" => Some(Self::double_click()),
+            "ramp" => Some(Self::ramp(Duration::from_millis(300))),
+            "heartbeat" => Some(Self::heartbeat()),
+            _ => None,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.segments.iter().map(|segment| segment.duration).sum()
+    }
+
+    /// the segment playing `elapsed` into the pattern, and how far into it
+    fn segment_at(&self, elapsed: Duration) -> Option<(&HapticSegment, Duration)> {
+        let mut start = Duration::ZERO;
+        for segment in &self.segments {
+            let end = start + segment.duration;
+            if elapsed < end {
+                return Some((segment, elapsed - start));
+            }
+            start = end;
+        }
+        None
+    }
+}
+
+/// What a hand's actuator should do, until the next [HapticSequencer::update]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum HapticOutput {
+    Vibrate {
+        amplitude: f32,
+        /// Hz; 0 lets the runtime pick
+        frequency: f32,
+        duration: Duration,
+    },
+    Stop,
+}
+
+/// identifies one [HapticSequencer::play], for [HapticSequencer::cancel]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HapticId(u64);
+
+struct Playing {
+    id: HapticId,
+    hand: HapticHand,
+    pattern: HapticPattern,
+    started: Option<Instant>,
+}
+
+/// At most one pattern per hand; playing another on the same hand replaces it.
+#[derive(Default)]
+pub struct HapticSequencer {
+    playing: Vec<Playing>,
+    /// hands whose pattern ended or was cancelled since the last update
+    stopped: Vec<HapticHand>,
+    next_id: u64,
+}
+
+impl HapticSequencer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start `pattern` on `hand` at the next [HapticSequencer::update].
+    pub fn play(&mut self, hand: HapticHand, pattern: HapticPattern) -> HapticId {
+        self.playing.retain(|p| p.hand != hand);
+        let id = HapticId(self.next_id);
+        self.next_id += 1;
+        self.playing.push(Playing {
+            id,
+            hand,
+            pattern,
+            started: None,
+        });
+        id
+    }
+
+    /// stop a pattern early; does nothing if it already finished or was replaced
+    pub fn cancel(&mut self, id: HapticId) {
+        if let Some(index) = self.playing.iter().position(|p| p.id == id) {
+            let playing = self.playing.remove(index);
+            self.stopped.push(playing.hand);
+        }
+    }
+
+    pub fn cancel_hand(&mut self, hand: HapticHand) {
+        let before = self.playing.len();
+        self.playing.retain(|p| p.hand != hand);
+        if self.playing.len() != before {
+            self.stopped.push(hand);
+        }
+    }
+
+    pub fn is_playing(&self, id: HapticId) -> bool {
+        self.playing.iter().any(|p| p.id == id)
+    }
+
+    /// What each hand with something to do should do now.  `frame_period` is how long the
+    /// output has to last, at most, before the next update replaces it.
+    pub fn update(
+        &mut self,
+        now: Instant,
+        frame_period: Duration,
+    ) -> Vec<(HapticHand, HapticOutput)> {
+        let mut rval: Vec<_> = self
+            .stopped
+            .drain(..)
+            .map(|hand| (hand, HapticOutput::Stop))
+            .collect();
+
+        self.playing.retain_mut(|playing| {
+            let started = *playing.started.get_or_insert(now);
+            match playing.pattern.segment_at(now.duration_since(started)) {
+                Some((segment, into)) => {
+                    let amplitude = segment.amplitude_at(into);
+                    let output = if amplitude > 0.0 {
+                        // a little past the next frame, so late frames do not leave gaps
+                        HapticOutput::Vibrate {
+                            amplitude,
+                            frequency: segment.frequency,
+                            duration: (segment.duration - into).min(frame_period * 2),
+                        }
+                    } else {
+                        HapticOutput::Stop
+                    };
+                    rval.push((playing.hand, output));
+                    true
+                }
+                None => {
+                    rval.push((playing.hand, HapticOutput::Stop));
+                    false
+                }
+            }
+        });
+        rval
+    }
+}
@@ -0,0 +1,77 @@
+//! The headset's compositor bends each eye image to cancel out the lenses, so what the user
+//! sees is the undistorted scene.  Anything that skips the compositor (recordings, a desktop
+//! simulator) shows a flat image instead.  This pass puts the lens back in: barrel distortion
+//! and color fringing, so captures look like the view through the headset.
+
+use bob_shaders::lens_distortion_shader::{LensDistortionParameters, LensDistortionShader};
+use gl::types::GLfloat;
+use gl_thin::gl_fancy::{ActiveTextureUnit, GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper, Texture};
+use gl_thin::render_target::RenderTarget;
+
+pub struct LensPreview {
+    pub parameters: LensDistortionParameters,
+    /// the distorted image
+    pub target: RenderTarget,
+    shader: LensDistortionShader,
+    buffers: VertexBufferBundle<'static, GLfloat, u8>,
+}
+
+impl LensPreview {
+    pub fn new(
+        width: i32,
+        height: i32,
+        parameters: LensDistortionParameters,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let target = RenderTarget::new(width, height, gpu_state)?;
+        let shader = LensDistortionShader::new()?;
+
+        static INDICES: [u8; 4] = [0, 1, 2, 3];
+        let buffers = VertexBufferBundle::new(
+            gpu_state,
+            vec![-1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0].into(),
+            (&INDICES).into(),
+            2,
+            &[(shader.sal_position, 2, 0)],
+        )?;
+
+        Ok(Self {
+            parameters: LensDistortionParameters {
+                aspect_ratio: target.aspect_ratio(),
+                ..parameters
+            },
+            target,
+            shader,
+            buffers,
+        })
+    }
+
+    /// Draw `source` (an undistorted image) into [LensPreview::target] as the lens would show it.
+    pub fn apply(&self, source: &Texture, gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper> {
+        self.target.bind()?;
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::DepthMask(gl::FALSE);
+        }
+        explode_if_gl_error()?;
+
+        let result = self.shader.draw(
+            source,
+            ActiveTextureUnit(0),
+            &self.parameters,
+            gl::TRIANGLE_STRIP,
+            &self.buffers,
+            self.buffers.index_count as _,
+            gpu_state,
+        );
+
+        unsafe {
+            gl::DepthMask(gl::TRUE);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+        result?;
+        explode_if_gl_error()?;
+        self.target.invalidate()
+    }
+}
@@ -4,69 +4,239 @@
 // android-activity's examples-na-winit-glutin
 
 use android_activity::AndroidApp;
-use drawcore::ActiveRenderer;
+use flat_renderer::AnyRenderer;
 use gl_thin::gl_helper::initialize_gl_using_egli;
-use std::ops::Add;
+use render_thread::{RenderMessage, RenderThread, RestartSignal};
+use startup::StartupOptions;
 use std::time::{Duration, Instant};
 use winit::application::ApplicationHandler;
-use winit::event::{StartCause, WindowEvent};
-use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop, EventLoopBuilder};
+use winit::event::{KeyEvent, Touch, WindowEvent};
+use winit::event_loop::{
+    ActiveEventLoop, ControlFlow, EventLoop, EventLoopBuilder, EventLoopProxy,
+};
 use winit::platform::android::EventLoopBuilderExtAndroid;
 use winit::window::WindowId;
 
+pub mod aaudio;
+pub mod adaptive_quality;
+pub mod ambisonics;
+pub mod analytics;
+pub mod android_clipboard;
+pub mod android_permissions;
+pub mod asset_loader;
+pub mod asset_pack;
+pub mod calibration;
+pub mod color_check;
+pub mod controller_status;
+pub mod curved_screen;
+pub mod debug_draw;
+pub mod device_status;
+pub mod downloads;
 pub mod drawcore;
+pub mod fallback_assets;
+pub mod flashlight;
+pub mod flat_renderer;
+pub mod frame_hooks;
+pub mod frame_scheduler;
+pub mod gestures;
+pub mod gltf_export;
+pub mod hand_occlusion;
+pub mod haptics;
+pub mod lens_preview;
+pub mod lod;
+pub mod microbench;
+pub mod microphone;
+pub mod minimap;
+pub mod mirror;
+pub mod motion_vectors;
+pub mod msaa_resolve;
+pub mod painting;
+pub mod panorama;
+pub mod passthrough_camera;
+pub mod portal;
+pub mod pose_stream;
+pub mod post_chain;
+pub mod presence;
+pub mod profiler;
+pub mod props;
 pub mod rainbow_triangle;
+pub mod render_thread;
 pub mod scene;
+pub mod scene_stack;
+pub mod settings;
+pub mod sky;
+pub mod spatial_audio;
+pub mod spectator;
+pub mod startup;
+pub mod stick_response;
 pub mod suzanne;
+pub mod teleport;
+pub mod temporal_aa;
 pub mod text_painting;
 pub mod textured_quad;
+pub mod thumbnail;
+pub mod ui;
+pub mod undo;
+pub mod warmup;
+pub mod world_streaming;
 pub mod xr_input;
 
 //
 
+/// Something that draws frames, run on the render thread by [RenderThread]
 pub trait Drawable {
     fn handle_events_and_draw(&mut self);
 
     fn suspend(&mut self);
+
+    /// the window gained or lost focus, e.g. to a system dialog
+    fn focus_changed(&mut self, _focused: bool) {}
+
+    /// keys from the soft keyboard (or a paired hardware keyboard)
+    fn keyboard_input(&mut self, _event: &KeyEvent) {}
+
+    /// the window changed size, in physical pixels
+    fn resized(&mut self, _width: u32, _height: u32) {}
+
+    /// fingers on the screen, when it is a phone rather than a headset
+    fn touch(&mut self, _touch: &Touch) {}
+
+    /// When the render thread should draw next; `None` to redraw continuously because
+    /// drawing blocks until the XR runtime wants another frame.
+    fn wake_at(&self) -> Option<Instant> {
+        None
+    }
+
+    /// The drawable is wedged, e.g. its XR session stopped answering.  The render thread
+    /// stops and a fresh drawable is built in its place.
+    fn wants_restart(&self) -> bool {
+        false
+    }
+
+    /// It [Drawable::wants_restart] because the XR runtime went away (crashed, updated).
+    /// The app keeps building new drawables until the runtime is back, see
+    /// [AppState::Reconnecting].
+    fn runtime_lost(&self) -> bool {
+        false
+    }
+
+    /// For restarting from threads of the drawable's own, which notice a render thread that
+    /// is stuck and so will never get to ask [Drawable::wants_restart].
+    fn set_restart_signal(&mut self, _signal: RestartSignal) {}
 }
 
-pub enum AppState<T: Drawable> {
+/// the first wait between attempts at reconnecting, doubling up to [MAX_RECONNECT_DELAY]
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+pub enum AppState {
+    #[default]
     Paused,
-    Active(T),
+    /// drawing on its own thread
+    Active(RenderThread),
+    /// "Reconnecting to VR runtime": the last drawable lost its runtime, and building a new
+    /// one has failed `attempts` times so far.  The next try is at `retry_at`.
+    Reconnecting { attempts: u32, retry_at: Instant },
 }
 
-impl<T: Drawable> Default for AppState<T> {
-    fn default() -> Self {
-        Self::Paused
+impl AppState {
+    pub fn is_reconnecting(&self) -> bool {
+        matches!(self, AppState::Reconnecting { .. })
     }
+
+    /// something to tell the user about this state, `None` when there is nothing to say
+    pub fn user_message(&self) -> Option<String> {
+        match self {
+            AppState::Reconnecting { attempts: 0, .. } => {
+                Some("Reconnecting to the VR runtime\u{2026}".into())
+            }
+            AppState::Reconnecting { attempts, .. } => Some(format!(
+                "Reconnecting to the VR runtime\u{2026} (attempt {})",
+                attempts + 1
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// sent to the event loop from other threads
+#[derive(Debug)]
+pub enum AppEvent {
+    /// the render thread ended because its drawable [Drawable::wants_restart]
+    RestartDrawable {
+        /// see [Drawable::runtime_lost]
+        runtime_lost: bool,
+    },
 }
 
+/// `factory` builds the drawable; its second argument is true while
+/// [AppState::Reconnecting], when falling back to drawing without the runtime would be
+/// premature.
 pub struct MyApp<T: Drawable, F, E: std::fmt::Debug>
 where
-    F: Fn(&ActiveEventLoop) -> Result<T, E>,
+    F: Fn(&ActiveEventLoop, bool) -> Result<T, E>,
 {
-    state: AppState<T>,
+    state: AppState,
     factory: F,
+    proxy: EventLoopProxy<AppEvent>,
 }
 
-impl<T: Drawable, F, E: std::fmt::Debug> ApplicationHandler for MyApp<T, F, E>
+impl<T: Drawable, F, E: std::fmt::Debug> MyApp<T, F, E>
 where
-    F: Fn(&ActiveEventLoop) -> Result<T, E>,
+    F: Fn(&ActiveEventLoop, bool) -> Result<T, E>,
 {
-    fn new_events(&mut self, _event_loop: &ActiveEventLoop, _cause: StartCause) {
-        if let AppState::Active(app) = &mut self.state {
-            app.handle_events_and_draw();
+    /// Move to `state`, announcing it if it is one the user should hear about.
+    fn set_state(&mut self, state: AppState) {
+        let was_reconnecting = self.state.is_reconnecting();
+        self.state = state;
+        match self.state.user_message() {
+            Some(message) => log::warn!("{}", message),
+            None if was_reconnecting => log::warn!("reconnected to the VR runtime"),
+            None => {}
         }
     }
+}
 
+impl<T: Drawable + 'static, F, E: std::fmt::Debug> ApplicationHandler<AppEvent> for MyApp<T, F, E>
+where
+    F: Fn(&ActiveEventLoop, bool) -> Result<T, E>,
+{
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        match (self.factory)(event_loop) {
-            Ok(x) => {
-                self.state = AppState::Active(x);
-            }
-            Err(e) => {
-                log::error!("malfunction building drawable {:?}", e)
+        let proxy = self.proxy.clone();
+        let on_restart = move |runtime_lost| {
+            if proxy
+                .send_event(AppEvent::RestartDrawable { runtime_lost })
+                .is_err()
+            {
+                log::warn!("event loop is gone, not restarting");
             }
+        };
+        let attempts = match self.state {
+            AppState::Reconnecting { attempts, .. } => Some(attempts),
+            _ => None,
+        };
+        match (self.factory)(event_loop, attempts.is_some()) {
+            Ok(x) => match RenderThread::spawn(x, on_restart) {
+                Ok(thread) => self.set_state(AppState::Active(thread)),
+                Err(e) => log::error!("malfunction starting render thread {}", e),
+            },
+            Err(e) => match attempts {
+                Some(attempts) => {
+                    let delay =
+                        (RECONNECT_DELAY * 2u32.saturating_pow(attempts)).min(MAX_RECONNECT_DELAY);
+                    log::warn!(
+                        "failed to reconnect to the VR runtime, trying again in {:.1}s {:?}",
+                        delay.as_secs_f32(),
+                        e
+                    );
+                    self.set_state(AppState::Reconnecting {
+                        attempts: attempts + 1,
+                        retry_at: Instant::now() + delay,
+                    });
+                }
+                None => log::error!("malfunction building drawable {:?}", e),
+            },
         }
     }
 
@@ -76,63 +246,79 @@ where
         _window_id: WindowId,
         event: WindowEvent,
     ) {
-        let control_flow = window_event_loop_one_pass(event, event_loop, &mut self.state);
-        event_loop.set_control_flow(control_flow);
+        window_event_loop_one_pass(event, event_loop, &self.state);
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: AppEvent) {
+        match event {
+            AppEvent::RestartDrawable { runtime_lost } => {
+                // unless it was suspended in the meantime
+                if let AppState::Active(_) = self.state {
+                    log::warn!("restarting the drawable");
+                    // the thread has already ended, so this only joins it
+                    self.set_state(if runtime_lost {
+                        AppState::Reconnecting {
+                            attempts: 0,
+                            retry_at: Instant::now(),
+                        }
+                    } else {
+                        AppState::Paused
+                    });
+                    self.resumed(event_loop);
+                }
+            }
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if let AppState::Reconnecting { retry_at, .. } = self.state {
+            if Instant::now() >= retry_at {
+                self.resumed(event_loop);
+            }
+        }
+        match self.state {
+            AppState::Reconnecting { retry_at, .. } => {
+                event_loop.set_control_flow(ControlFlow::WaitUntil(retry_at))
+            }
+            // the render thread paces itself
+            _ => event_loop.set_control_flow(ControlFlow::Wait),
+        }
     }
 
     fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
         log::debug!("suspend");
-        if let AppState::Active(app) = &mut self.state {
-            app.suspend();
-        }
-        // log::trace!("Suspended, dropping surface state...");
-        // app.surface_state = None;
+        // dropping the thread suspends the drawable and waits for it to be dropped
         self.state = AppState::Paused;
     }
 }
 
 //
 
-fn window_event_loop_one_pass<T: Drawable>(
-    event: WindowEvent,
-    event_loop: &ActiveEventLoop,
-    app: &mut AppState<T>,
-) -> ControlFlow {
+fn window_event_loop_one_pass(event: WindowEvent, event_loop: &ActiveEventLoop, app: &AppState) {
     log::trace!("Received Winit event: {event:?}");
 
-    let static_graphics = false;
-
-    let mut control_flow = match app {
-        AppState::Paused => ControlFlow::Wait,
-        AppState::Active(_) => {
-            if static_graphics {
-                ControlFlow::Poll
-            } else {
-                // trigger redraws every 6 milliseconds
-                ControlFlow::WaitUntil(Instant::now().add(Duration::from_millis(6)))
-            }
+    let AppState::Active(render_thread) = app else {
+        if let WindowEvent::CloseRequested = event {
+            event_loop.exit();
         }
+        return;
     };
-
     match event {
-        WindowEvent::Resized(_size) => {
-            // Winit: doesn't currently implicitly request a redraw
-            // for a resize which may be required on some platforms...
-            if let AppState::Active(_) = app {
-                control_flow = ControlFlow::Poll; // this should trigger a redraw via NewEvents
-            }
+        WindowEvent::Resized(size) => {
+            render_thread.send(RenderMessage::Resized(size.width, size.height));
         }
-        WindowEvent::RedrawRequested => {
-            log::trace!("Handling Redraw Request");
-            if let AppState::Active(app) = app {
-                app.handle_events_and_draw();
-            }
+        WindowEvent::Focused(focused) => {
+            render_thread.send(RenderMessage::FocusChanged(focused));
+        }
+        WindowEvent::KeyboardInput { event, .. } => {
+            render_thread.send(RenderMessage::KeyboardInput(event));
+        }
+        WindowEvent::Touch(touch) => {
+            render_thread.send(RenderMessage::Touch(touch));
         }
         WindowEvent::CloseRequested => event_loop.exit(),
         _ => {}
     }
-
-    control_flow
 }
 
 //
@@ -150,18 +336,27 @@ fn android_main(android_app: AndroidApp) {
 
     log::debug!("bob test");
 
-    let mut builder: EventLoopBuilder<_> = EventLoop::builder();
-    let event_loop: EventLoop<()> = builder.with_android_app(android_app).build().unwrap();
+    let options = StartupOptions::load(&android_app);
+    if let Some(dir) = android_app.external_data_path() {
+        fallback_assets::set_override_dir(dir.join("assets"));
+    }
+
+    let mut builder: EventLoopBuilder<_> = EventLoop::with_user_event();
+    let event_loop: EventLoop<AppEvent> = builder.with_android_app(android_app).build().unwrap();
 
     log::debug!("got event loop");
 
-    let app = AppState::<ActiveRenderer>::default();
     let mut app = MyApp {
-        state: app,
-        factory: |event_loop| {
+        state: AppState::default(),
+        proxy: event_loop.create_proxy(),
+        factory: |event_loop, reconnecting| {
             initialize_gl_using_egli();
 
-            ActiveRenderer::new(event_loop)
+            if reconnecting {
+                AnyRenderer::reconnect(event_loop, &options)
+            } else {
+                AnyRenderer::new(event_loop, &options)
+            }
         },
     };
     event_loop.run_app(&mut app).unwrap();
@@ -0,0 +1,260 @@
+//! Level of detail: a drawable registers several meshes, from full detail to coarse,
+//! and each view picks one per object based on how far away it is
+//! (or how big the simplification error would look on screen).
+
+use gl::types::GLint;
+use gl_thin::linear::{XrFovf, XrMatrix4x4f, XrVector3f};
+use std::collections::HashMap;
+
+pub struct LodLevel<M> {
+    pub mesh: M,
+    /// the [LodMetric::Distance] metric uses this level up to this distance (in mesh units)
+    pub max_distance: f32,
+    /// how far (in mesh units) this mesh's surface strays from the full-detail one
+    pub geometric_error: f32,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum LodMetric {
+    /// use the first level whose `max_distance` is beyond the object.
+    /// Objects beyond the last level are culled.
+    Distance,
+    /// use the coarsest level whose `geometric_error`, projected onto the screen,
+    /// is at most this many pixels
+    ScreenSpaceError { max_pixels: f32 },
+}
+
+/// The meshes for one drawable, finest first
+pub struct LodGroup<M> {
+    pub levels: Vec<LodLevel<M>>,
+    pub metric: LodMetric,
+    /// around the mesh origin, in mesh units, for culling against [LodView::frustum].
+    /// Infinite (never culled) unless set.
+    pub bounding_radius: f32,
+}
+
+impl<M> LodGroup<M> {
+    pub fn new(metric: LodMetric) -> Self {
+        Self {
+            levels: vec![],
+            metric,
+            bounding_radius: f32::INFINITY,
+        }
+    }
+
+    pub fn with_bounding_radius(mut self, bounding_radius: f32) -> Self {
+        self.bounding_radius = bounding_radius;
+        self
+    }
+
+    /// levels must be added from finest to coarsest
+    pub fn with_level(mut self, mesh: M, max_distance: f32, geometric_error: f32) -> Self {
+        self.levels.push(LodLevel {
+            mesh,
+            max_distance,
+            geometric_error,
+        });
+        self
+    }
+
+    pub fn finest(&self) -> Option<&M> {
+        self.levels.first().map(|level| &level.mesh)
+    }
+
+    /// Which mesh to draw for an object at `center` drawn with a uniform `scale`.
+    /// `None` means the object is too far away to draw at all, or outside the view's
+    /// [LodView::frustum].
+    pub fn select(&self, center: &XrVector3f, scale: f32, view: &LodView) -> Option<&M> {
+        self.select_index(center, scale, view)
+            .map(|i| &self.levels[i].mesh)
+    }
+
+    pub fn select_index(&self, center: &XrVector3f, scale: f32, view: &LodView) -> Option<usize> {
+        if let Some(frustum) = &view.frustum {
+            if !frustum.intersects_sphere(center, self.bounding_radius * scale) {
+                return None;
+            }
+        }
+        let distance = view.distance_to(center);
+        match self.metric {
+            LodMetric::Distance => self
+                .levels
+                .iter()
+                .position(|level| distance < level.max_distance * scale),
+            LodMetric::ScreenSpaceError { max_pixels } => {
+                let acceptable = self.levels.iter().rposition(|level| {
+                    view.projected_error(level.geometric_error * scale, distance) <= max_pixels
+                });
+                // even the finest level is too coarse when you are nose to nose with it
+                acceptable.or_else(|| (!self.levels.is_empty()).then_some(0))
+            }
+        }
+    }
+
+    /// convenience for when the model matrix is all you have
+    pub fn select_for_model(&self, model: &XrMatrix4x4f, view: &LodView) -> Option<&M> {
+        let (center, scale) = model_center_and_scale(model);
+        self.select(&center, scale, view)
+    }
+}
+
+/// What the LOD selection needs to know about the view it is drawing
+#[derive(Copy, Clone, Debug)]
+pub struct LodView {
+    pub eye_position: XrVector3f,
+    /// pixels per unit of size at unit distance: viewport_height / (2 tan(fov_y/2))
+    pub projection_scale: f32,
+    /// objects entirely outside it are skipped; `None` draws everything in range
+    pub frustum: Option<Frustum>,
+}
+
+impl LodView {
+    pub fn new(eye_position: XrVector3f, fov: &XrFovf, viewport_height: i32) -> Self {
+        let tan_height = fov.angle_up.tan() - fov.angle_down.tan();
+        Self {
+            eye_position,
+            projection_scale: viewport_height as f32 / tan_height,
+            frustum: None,
+        }
+    }
+
+    /// also cull against what `matrix_pv` can see
+    pub fn with_frustum(mut self, matrix_pv: &XrMatrix4x4f) -> Self {
+        self.frustum = Some(Frustum::from_matrix(matrix_pv));
+        self
+    }
+
+    /// like [LodView::new] using the height of the current GL viewport
+    pub fn from_current_viewport(eye_position: XrVector3f, fov: &XrFovf) -> Self {
+        let mut viewport = [0 as GLint; 4];
+        unsafe { gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr()) };
+        Self::new(eye_position, fov, viewport[3])
+    }
+
+    pub fn distance_to(&self, point: &XrVector3f) -> f32 {
+        let dx = point.x - self.eye_position.x;
+        let dy = point.y - self.eye_position.y;
+        let dz = point.z - self.eye_position.z;
+        (dx * dx + dy * dy + dz * dz).sqrt()
+    }
+
+    /// how many pixels tall an error of `error` meters looks at `distance` meters
+    pub fn projected_error(&self, error: f32, distance: f32) -> f32 {
+        error * self.projection_scale / distance.max(1e-3)
+    }
+}
+
+/// The six clip planes of a view-projection matrix, in the space the matrix takes points
+/// from.  Each is (a,b,c,d), normalized, with ax+by+cz+d >= 0 on the inside.
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum {
+    pub planes: [[f32; 4]; 6],
+}
+
+impl Frustum {
+    /// Gribb & Hartmann: -w <= x,y,z <= w in clip space is a plane test on each row sum.
+    /// Works for oblique near planes and mirrored matrices alike.
+    pub fn from_matrix(matrix_pv: &XrMatrix4x4f) -> Self {
+        let m = &matrix_pv.m;
+        // column major
+        let row = |i: usize| [m[i], m[4 + i], m[8 + i], m[12 + i]];
+        let w = row(3);
+        let mut planes = [[0.0; 4]; 6];
+        for (axis, pair) in planes.chunks_exact_mut(2).enumerate() {
+            let r = row(axis);
+            for (plane, sign) in pair.iter_mut().zip([1.0, -1.0]) {
+                *plane = std::array::from_fn(|i| w[i] + sign * r[i]);
+                let length = plane[..3].iter().map(|c| c * c).sum::<f32>().sqrt();
+                *plane = if length > 1e-6 {
+                    plane.map(|c| c / length)
+                } else {
+                    // e.g. the far plane of an infinite projection: nothing is outside it
+                    [0.0, 0.0, 0.0, 1.0]
+                };
+            }
+        }
+        Self { planes }
+    }
+
+    /// false only when the sphere is entirely outside one of the planes
+    pub fn intersects_sphere(&self, center: &XrVector3f, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|[a, b, c, d]| a * center.x + b * center.y + c * center.z + d >= -radius)
+    }
+}
+
+/// the translation of `model`, and the length of its X axis
+pub fn model_center_and_scale(model: &XrMatrix4x4f) -> (XrVector3f, f32) {
+    let m = &model.m;
+    let scale = (m[0] * m[0] + m[1] * m[1] + m[2] * m[2]).sqrt();
+    (XrVector3f::new(m[12], m[13], m[14]), scale)
+}
+
+//
+
+/// Make a coarser version of an indexed triangle mesh by snapping vertices to a grid of
+/// `cell_size` and merging those that land in the same cell (Rossignac & Borrel vertex clustering).
+/// Fast enough to do at load time, and the error is at most the cell diagonal.
+///
+/// `vertices` holds `stride` floats per vertex, position first.  Merged vertices get the average
+/// of every attribute; if `normal_offset` is given, the averaged normal is renormalized.
+pub fn simplify_by_clustering(
+    vertices: &[f32],
+    stride: usize,
+    indices: &[u16],
+    cell_size: f32,
+    normal_offset: Option<usize>,
+) -> (Vec<f32>, Vec<u16>) {
+    let mut cells: HashMap<(i32, i32, i32), usize> = HashMap::new();
+    let mut sums: Vec<f32> = vec![];
+    let mut counts: Vec<u32> = vec![];
+
+    let remap: Vec<usize> = vertices
+        .chunks_exact(stride)
+        .map(|vertex| {
+            let key = (
+                (vertex[0] / cell_size).floor() as i32,
+                (vertex[1] / cell_size).floor() as i32,
+                (vertex[2] / cell_size).floor() as i32,
+            );
+            let cluster = *cells.entry(key).or_insert_with(|| {
+                sums.resize(sums.len() + stride, 0.0);
+                counts.push(0);
+                counts.len() - 1
+            });
+            for (sum, value) in sums[cluster * stride..].iter_mut().zip(vertex) {
+                *sum += value;
+            }
+            counts[cluster] += 1;
+            cluster
+        })
+        .collect();
+
+    for (cluster, vertex) in sums.chunks_exact_mut(stride).enumerate() {
+        let n = counts[cluster] as f32;
+        vertex.iter_mut().for_each(|v| *v /= n);
+        if let Some(offset) = normal_offset {
+            let normal = &mut vertex[offset..offset + 3];
+            let length = normal.iter().map(|c| c * c).sum::<f32>().sqrt();
+            if length > 0.0 {
+                normal.iter_mut().for_each(|c| *c /= length);
+            }
+        }
+    }
+
+    let new_indices = indices
+        .chunks_exact(3)
+        .map(|tri| {
+            [
+                remap[tri[0] as usize],
+                remap[tri[1] as usize],
+                remap[tri[2] as usize],
+            ]
+        })
+        .filter(|[a, b, c]| a != b && b != c && a != c)
+        .flat_map(|tri| tri.map(|i| i as u16))
+        .collect();
+
+    (sums, new_indices)
+}
@@ -0,0 +1,161 @@
+//! Timing, on the device, the hot paths that need a live GL context or the app's assets:
+//! uniform uploads, vertex buffer binding, and text rasterization.  Criterion can't run
+//! inside the app, so this is a small harness of its own: each benchmark runs in batches,
+//! each batch ends with a glFinish so the driver's deferred work is counted, and the
+//! median batch is reported.  Compare the logged numbers between builds on the same headset.
+
+use crate::text_painting::{default_font, render_glyphs_to_rgb};
+use bob_shaders::flat_color_shader::FlatColorShader;
+use gl::types::{GLfloat, GLint, GLuint};
+use gl_thin::gl_fancy::{GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper};
+use gl_thin::linear::{xr_matrix4x4f_create_translation, xr_matrix4x4f_identity};
+use gl_thin::uniform_batch::UniformBatch;
+use rusttype::{point, Scale};
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug)]
+pub struct BenchResult {
+    pub name: &'static str,
+    /// per iteration, in the median batch
+    pub median: Duration,
+    /// per iteration, in the fastest batch
+    pub min: Duration,
+    pub iterations: u32,
+}
+
+impl Display for BenchResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: median {:.2}µs, min {:.2}µs ({} iterations)",
+            self.name,
+            self.median.as_secs_f64() * 1e6,
+            self.min.as_secs_f64() * 1e6,
+            self.iterations,
+        )
+    }
+}
+
+pub struct MicroBench {
+    /// batches timed per benchmark, after one thrown away to warm caches and the driver
+    pub batches: u32,
+    /// calls per batch
+    pub batch_size: u32,
+    results: Vec<BenchResult>,
+}
+
+impl MicroBench {
+    pub fn new(batches: u32, batch_size: u32) -> Self {
+        Self {
+            batches,
+            batch_size,
+            results: vec![],
+        }
+    }
+
+    /// Time `f`, which does one iteration of whatever is measured.
+    pub fn run(
+        &mut self,
+        name: &'static str,
+        mut f: impl FnMut(u32) -> Result<(), GLErrorWrapper>,
+    ) -> Result<&BenchResult, GLErrorWrapper> {
+        let mut per_iteration = Vec::with_capacity(self.batches as usize);
+        for batch in 0..=self.batches {
+            let start = Instant::now();
+            for i in 0..self.batch_size {
+                f(i)?;
+            }
+            unsafe { gl::Finish() };
+            let elapsed = start.elapsed() / self.batch_size.max(1);
+            if batch > 0 {
+                per_iteration.push(elapsed);
+            }
+        }
+        explode_if_gl_error()?;
+        per_iteration.sort();
+        self.results.push(BenchResult {
+            name,
+            median: per_iteration
+                .get(per_iteration.len() / 2)
+                .copied()
+                .unwrap_or_default(),
+            min: per_iteration.first().copied().unwrap_or_default(),
+            iterations: self.batches * self.batch_size,
+        });
+        Ok(self.results.last().unwrap())
+    }
+
+    pub fn results(&self) -> &[BenchResult] {
+        &self.results
+    }
+
+    pub fn log(&self) {
+        for result in &self.results {
+            log::info!("microbench {}", result);
+        }
+    }
+}
+
+/// Every benchmark, each with scratch resources of its own.  It binds its own program and
+/// buffers, so run it between frames.
+pub fn run_gl_suite(gpu_state: &mut GPUState) -> Result<MicroBench, GLErrorWrapper> {
+    let mut bench = MicroBench::new(15, 200);
+
+    let shader = FlatColorShader::new()?;
+    shader.program.use_()?;
+    // a different matrix every call, as the frame loop's draws have
+    let matrices = [
+        xr_matrix4x4f_identity(),
+        xr_matrix4x4f_create_translation(0.0, 1.0, -2.0),
+    ];
+    bench.run("glUniformMatrix4fv", |i| {
+        shader.set_params(&matrices[i as usize % 2]);
+        Ok(())
+    })?;
+    let mut batch = UniformBatch::new();
+    bench.run("UniformBatch mat4, changed", |i| {
+        batch.set_mat4u(shader.sul_matrix as GLint, matrices[i as usize % 2].slice());
+        batch.flush().map(|_| ())
+    })?;
+    bench.run("UniformBatch mat4, unchanged", |_| {
+        batch.set_mat4u(shader.sul_matrix as GLint, matrices[0].slice());
+        batch.flush().map(|_| ())
+    })?;
+
+    let vertices: Vec<GLfloat> = vec![
+        0.0, 0.0, 0.0, 1.0, 0.0, 0.0, //
+        1.0, 0.0, 0.0, 0.0, 1.0, 0.0, //
+        0.0, 1.0, 0.0, 0.0, 0.0, 1.0, //
+    ];
+    let indices: Vec<GLuint> = vec![0, 1, 2];
+    let buffers = VertexBufferBundle::new(
+        gpu_state,
+        vertices.into(),
+        indices.into(),
+        6,
+        &[(shader.sal_position, 3, 0), (shader.sal_color, 3, 3)],
+    )?;
+    bench.run("VertexBufferBundle::bind", |_| {
+        buffers.bind(gpu_state).map(|_| ())
+    })?;
+
+    let font = default_font();
+    let scale = Scale::uniform(48.0);
+    let glyphs: Vec<_> = font
+        .layout(
+            "The quick brown fox jumps over the lazy dog",
+            scale,
+            point(0.0, font.v_metrics(scale).ascent),
+        )
+        .collect();
+    let (width, height) = (1024, 64);
+    let mut pixels = vec![0u8; 3 * width as usize * height as usize];
+    bench.run("rasterize 43 glyphs at 48px", |_| {
+        render_glyphs_to_rgb(width, height, &glyphs, &mut pixels);
+        Ok(())
+    })?;
+
+    Ok(bench)
+}
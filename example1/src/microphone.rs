@@ -0,0 +1,226 @@
+//! Microphone input through AAudio, boiled down every frame to a loudness and a coarse spectrum
+//! that the scene can react to.
+
+pub use crate::aaudio::AudioError;
+use crate::aaudio::{audio_check, ffi};
+use std::f32::consts::TAU;
+use std::ptr::null_mut;
+
+/// samples per FFT; at 48kHz that is about 11ms of audio
+pub const FFT_SIZE: usize = 512;
+/// the spectrum is summarized into this many log-spaced bands
+pub const BAND_COUNT: usize = 8;
+
+/// What the scene gets to see of the microphone each frame
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AudioLevels {
+    /// root mean square of the most recent [FFT_SIZE] samples, 0..1
+    pub rms: f32,
+    /// largest absolute sample in the same window
+    pub peak: f32,
+    /// magnitude of each band, lowest frequencies first, roughly 0..1
+    pub bands: [f32; BAND_COUNT],
+}
+
+//
+
+/// Needs the RECORD_AUDIO permission; see [crate::android_permissions::PermissionTracker].
+pub struct Microphone {
+    /// 0 is raw; closer to 1 makes the levels fall back slowly after a loud sound
+    pub smoothing: f32,
+    stream: Option<InputStream>,
+    /// the most recent [FFT_SIZE] samples, oldest first
+    window: Vec<f32>,
+    read_buffer: Vec<f32>,
+    levels: AudioLevels,
+}
+
+impl Default for Microphone {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Microphone {
+    pub fn new() -> Self {
+        Self {
+            smoothing: 0.8,
+            stream: None,
+            window: vec![0.0; FFT_SIZE],
+            read_buffer: vec![0.0; 4096],
+            levels: AudioLevels::default(),
+        }
+    }
+
+    pub fn levels(&self) -> AudioLevels {
+        self.levels
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Once per frame.  Opens the input the first time `permission_granted` is true,
+    /// then drains whatever audio arrived since the last frame and updates [Microphone::levels].
+    pub fn update(&mut self, permission_granted: bool) -> Result<(), AudioError> {
+        if self.stream.is_none() {
+            if !permission_granted {
+                return Ok(());
+            }
+            self.stream = Some(InputStream::open()?);
+        }
+        let Some(stream) = &self.stream else {
+            return Ok(());
+        };
+
+        let mut any = false;
+        loop {
+            let n = stream.read(&mut self.read_buffer)?;
+            if n == 0 {
+                break;
+            }
+            any = true;
+            let fresh = &self.read_buffer[..n];
+            if n >= FFT_SIZE {
+                self.window.copy_from_slice(&fresh[n - FFT_SIZE..]);
+            } else {
+                self.window.drain(..n);
+                self.window.extend_from_slice(fresh);
+            }
+        }
+
+        if any {
+            let current = analyze(&self.window);
+            let keep = self.smoothing;
+            let blend = |old: f32, new: f32| new.max(old * keep + new * (1.0 - keep));
+            self.levels.rms = blend(self.levels.rms, current.rms);
+            self.levels.peak = blend(self.levels.peak, current.peak);
+            for (old, new) in self.levels.bands.iter_mut().zip(current.bands) {
+                *old = blend(*old, new);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn analyze(window: &[f32]) -> AudioLevels {
+    let n = window.len();
+    let rms = (window.iter().map(|s| s * s).sum::<f32>() / n as f32).sqrt();
+    let peak = window.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+
+    // Hann window to keep the edges of the buffer from smearing across the spectrum
+    let mut re: Vec<f32> = window
+        .iter()
+        .enumerate()
+        .map(|(i, s)| s * 0.5 * (1.0 - (TAU * i as f32 / (n - 1) as f32).cos()))
+        .collect();
+    let mut im = vec![0.0; n];
+    fft(&mut re, &mut im);
+
+    // bin 0 is DC; split 1..n/2 into bands that double in width
+    let mut bands = [0.0; BAND_COUNT];
+    let half = n / 2;
+    for (b, band) in bands.iter_mut().enumerate() {
+        let start = (half >> (BAND_COUNT - b)).max(1);
+        let end = (half >> (BAND_COUNT - 1 - b)).max(start + 1);
+        let sum: f32 = (start..end)
+            .map(|k| (re[k] * re[k] + im[k] * im[k]).sqrt())
+            .sum();
+        // a full-scale sine lands n/4 in its bin after the Hann window
+        *band = (sum / (n as f32 * 0.25)).min(1.0);
+    }
+
+    AudioLevels { rms, peak, bands }
+}
+
+/// in-place iterative radix-2 FFT; the length must be a power of two
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if j > i {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+    while size <= n {
+        let step = -TAU / size as f32;
+        for start in (0..n).step_by(size) {
+            for k in 0..size / 2 {
+                let (sin, cos) = (step * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + size / 2);
+                let tr = re[b] * cos - im[b] * sin;
+                let ti = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - tr;
+                im[b] = im[a] - ti;
+                re[a] += tr;
+                im[a] += ti;
+            }
+        }
+        size *= 2;
+    }
+}
+
+//
+
+/// a mono float AAudio input stream, read without blocking
+struct InputStream(*mut ffi::AAudioStream);
+
+impl InputStream {
+    fn open() -> Result<Self, AudioError> {
+        let mut builder = null_mut();
+        audio_check("AAudio_createStreamBuilder", unsafe {
+            ffi::AAudio_createStreamBuilder(&mut builder)
+        })?;
+
+        let mut stream = null_mut();
+        let status = unsafe {
+            ffi::AAudioStreamBuilder_setDirection(builder, ffi::AAUDIO_DIRECTION_INPUT);
+            ffi::AAudioStreamBuilder_setFormat(builder, ffi::AAUDIO_FORMAT_PCM_FLOAT);
+            ffi::AAudioStreamBuilder_setChannelCount(builder, 1);
+            ffi::AAudioStreamBuilder_setPerformanceMode(
+                builder,
+                ffi::AAUDIO_PERFORMANCE_MODE_LOW_LATENCY,
+            );
+            let status = ffi::AAudioStreamBuilder_openStream(builder, &mut stream);
+            ffi::AAudioStreamBuilder_delete(builder);
+            status
+        };
+        audio_check("AAudioStreamBuilder_openStream", status)?;
+        let rval = Self(stream);
+
+        audio_check("AAudioStream_requestStart", unsafe {
+            ffi::AAudioStream_requestStart(rval.0)
+        })?;
+        log::debug!("microphone open at {} Hz", unsafe {
+            ffi::AAudioStream_getSampleRate(rval.0)
+        });
+        Ok(rval)
+    }
+
+    /// the number of samples copied into `buffer`, 0 if none are waiting
+    fn read(&self, buffer: &mut [f32]) -> Result<usize, AudioError> {
+        let n = unsafe {
+            ffi::AAudioStream_read(self.0, buffer.as_mut_ptr().cast(), buffer.len() as i32, 0)
+        };
+        if n < 0 {
+            return Err(AudioError {
+                call: "AAudioStream_read",
+                status: n,
+            });
+        }
+        Ok(n as usize)
+    }
+}
+
+impl Drop for InputStream {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::AAudioStream_requestStop(self.0);
+            ffi::AAudioStream_close(self.0);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! A top-down map of the props and painted strokes around the user, rendered with an
+//! orthographic camera into a small texture now and then and shown on a panel that the
+//! caller places, e.g. on the wrist.  It is one of the [crate::ui::Ui]'s panels, so the
+//! pointer lands on it and [Minimap::world_at] says where in the world it points.
+
+use crate::scene::{matrix_rotation_about_x, MyScene};
+use crate::ui::scroll::plane_hit;
+use bob_shaders::raw_texture_shader::RawTextureShader;
+use gl::types::GLfloat;
+use gl_thin::gl_fancy::{ActiveTextureUnit, GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper, Texture, TextureWithTarget};
+use gl_thin::linear::{
+    xr_matrix4x4f_create_orthographic, xr_matrix4x4f_create_translation,
+    xr_matrix4x4f_invert_rigid_body, GraphicsAPI, XrMatrix4x4f, XrVector3f,
+};
+use gl_thin::render_target::RenderTarget;
+use std::f32::consts::FRAC_PI_2;
+use std::time::{Duration, Instant};
+
+/// how far above the user the map's camera floats, and how far below it sees
+const CAMERA_HEIGHT: f32 = 10.0;
+const DEPTH: f32 = 20.0;
+
+pub struct Minimap {
+    /// meters of world from the middle of the map to each edge
+    pub half_extent: f32,
+    /// how often [Minimap::update] redraws the map; [Minimap::request_render] forces it sooner
+    pub interval: Duration,
+    /// shown where nothing was drawn
+    pub background: [f32; 4],
+    /// the panel's center in the world, facing +Z with north (-Z in the map) toward +Y;
+    /// hidden while `None`
+    pub model: Option<XrMatrix4x4f>,
+    pub target: RenderTarget,
+    /// meters across the panel
+    panel_size: f32,
+    /// where the map was centered the last time it was drawn
+    center: Option<XrVector3f>,
+    last_render: Option<Instant>,
+    render_requested: bool,
+    /// a borrowed handle to [RenderTarget::color]
+    texture: TextureWithTarget,
+    program: RawTextureShader,
+    buffers: VertexBufferBundle<'static, GLfloat, u8>,
+}
+
+impl Minimap {
+    /// `pixels` is the size of the square texture, `panel_size` the width of the panel it
+    /// is shown on, in meters
+    pub fn new(
+        pixels: i32,
+        half_extent: f32,
+        panel_size: f32,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let target = RenderTarget::new(pixels, pixels, gpu_state)?;
+        let texture =
+            TextureWithTarget::new(Texture::borrowed(target.color.borrow()), gl::TEXTURE_2D);
+        let program = RawTextureShader::new(gl::TEXTURE_2D)?;
+
+        let buffers = {
+            let d = 0.5 * panel_size;
+            // the target's first row is its bottom
+            let quad = vec![
+                -d, -d, 0.0, 0.0, //
+                d, -d, 1.0, 0.0, //
+                -d, d, 0.0, 1.0, //
+                d, d, 1.0, 1.0,
+            ];
+
+            static INDICES: [u8; 4] = [0, 1, 2, 3];
+            VertexBufferBundle::<'static, GLfloat, u8>::new(
+                gpu_state,
+                quad.into(),
+                (&INDICES).into(),
+                4,
+                &[
+                    (program.shader_attribute_position_location, 2, 0),
+                    (program.shader_attribute_texture_location, 2, 2),
+                ],
+            )?
+        };
+
+        Ok(Self {
+            half_extent,
+            interval: Duration::from_secs(1),
+            background: [0.1, 0.15, 0.1, 0.85],
+            model: None,
+            target,
+            panel_size,
+            center: None,
+            last_render: None,
+            render_requested: true,
+            texture,
+            program,
+            buffers,
+        })
+    }
+
+    /// redraw at the next [Minimap::update], e.g. after the props moved
+    pub fn request_render(&mut self) {
+        self.render_requested = true;
+    }
+
+    /// where the map was centered the last time it was drawn
+    pub fn center(&self) -> Option<XrVector3f> {
+        self.center
+    }
+
+    /// The map's camera: looking straight down at `center`, with -Z (forward from where the
+    /// session started) at the top of the image.
+    pub fn matrix_pv(&self, center: &XrVector3f) -> XrMatrix4x4f {
+        let e = self.half_extent;
+        let projection = xr_matrix4x4f_create_orthographic(
+            GraphicsAPI::GraphicsOpenGL,
+            -e,
+            e,
+            -e,
+            e,
+            0.0,
+            DEPTH,
+        );
+        let camera = xr_matrix4x4f_create_translation(center.x, center.y + CAMERA_HEIGHT, center.z)
+            * matrix_rotation_about_x(-FRAC_PI_2);
+        projection * xr_matrix4x4f_invert_rigid_body(&camera)
+    }
+
+    /// Redraw the map around `center` if [Minimap::interval] has passed or a render was
+    /// requested.  Returns true if it did.  Leaves the target bound.
+    pub fn update(
+        &mut self,
+        scene: &MyScene,
+        center: &XrVector3f,
+        gpu_state: &mut GPUState,
+    ) -> Result<bool, GLErrorWrapper> {
+        let now = Instant::now();
+        let due = self
+            .last_render
+            .is_none_or(|last| now.duration_since(last) >= self.interval);
+        if !due && !self.render_requested {
+            return Ok(false);
+        }
+        self.render_requested = false;
+        self.last_render = Some(now);
+        self.center = Some(*center);
+
+        self.target.bind()?;
+        let [r, g, b, a] = self.background;
+        unsafe {
+            gl::ClearColor(r, g, b, a);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            // everything else counts on the default
+            gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+        }
+        explode_if_gl_error()?;
+
+        scene.draw_overview(&self.matrix_pv(center), gpu_state)?;
+        self.target.invalidate()?;
+        Ok(true)
+    }
+
+    /// shown: placed, and drawn at least once
+    pub fn is_visible(&self) -> bool {
+        self.model.is_some() && self.center.is_some()
+    }
+
+    /// Where a pointer ray hits the panel, in its local coordinates, if it does.
+    pub fn hit(&self, origin: &XrVector3f, direction: &XrVector3f) -> Option<[f32; 2]> {
+        let model = self.model.as_ref().filter(|_| self.is_visible())?;
+        let [x, y] = plane_hit(model, origin, direction)?;
+        let d = 0.5 * self.panel_size;
+        (x.abs() <= d && y.abs() <= d).then_some([x, y])
+    }
+
+    /// The spot on the ground (at the height the map was centered at) shown under a point
+    /// from [Minimap::hit].
+    pub fn world_at(&self, point: [f32; 2]) -> Option<XrVector3f> {
+        let center = self.center?;
+        let meters_per_panel_meter = self.half_extent / (0.5 * self.panel_size);
+        Some(XrVector3f::new(
+            center.x + point[0] * meters_per_panel_meter,
+            center.y,
+            center.z - point[1] * meters_per_panel_meter,
+        ))
+    }
+
+    /// Paint the panel at [Minimap::model].
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let Some(model) = self.model.as_ref().filter(|_| self.is_visible()) else {
+            return Ok(());
+        };
+        self.program.set_params(
+            &(*matrix_pv * *model),
+            &self.texture,
+            ActiveTextureUnit(0),
+            gpu_state,
+        )?;
+        let binding = self.buffers.bind(gpu_state)?;
+        binding.draw_elements(gl::TRIANGLE_STRIP, self.buffers.index_count as _, 0)?;
+        drop(binding);
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+//! Planar reflections: each eye's view of the scene mirrored about a plane is rendered into a
+//! [RenderTarget], then composited onto the mirror quad with a Fresnel falloff.
+//! This is the portal trick again with a reflection instead of a second location,
+//! so it shares the oblique near-plane clipping from [crate::portal].
+
+use crate::lod::LodView;
+use crate::passthrough_camera::PassthroughCamera;
+use crate::portal::{oblique_near_plane, transform_plane_to_camera};
+use crate::pose_stream::{Pose, RemoteAvatar};
+use crate::scene::{projection_matrix, MyScene};
+use bob_shaders::mirror_shader::MirrorShader;
+use gl::types::{GLfloat, GLsizei};
+use gl_thin::gl_fancy::{GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper};
+use gl_thin::linear::{
+    xr_matrix4x4f_invert_rigid_body, xr_matrix4x4f_transform_vector3f, XrFovf, XrMatrix4x4f,
+    XrQuaternionf, XrVector3f,
+};
+use gl_thin::render_target::RenderTarget;
+use openxr::SpaceLocation;
+use openxr_sys::Time;
+use std::f32::consts::FRAC_1_SQRT_2;
+
+pub struct PlanarMirror {
+    /// The quad is centered here and reflects on its +Z side
+    pub pose: Pose,
+    pub width: f32,
+    pub height: f32,
+    /// fraction reflected when looking straight at the mirror, see [MirrorShader::draw]
+    pub reflectance: f32,
+    pub tint: [f32; 3],
+    /// the current eye's reflection.  Same size as the eye's swapchain image.
+    pub target: RenderTarget,
+    shader: MirrorShader,
+    buffers: VertexBufferBundle<'static, GLfloat, u8>,
+}
+
+impl PlanarMirror {
+    pub fn new(
+        pose: Pose,
+        width: f32,
+        height: f32,
+        target_width: i32,
+        target_height: i32,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let target = RenderTarget::new(target_width, target_height, gpu_state)?;
+        let shader = MirrorShader::new()?;
+
+        let buffers = {
+            let (dx, dy) = (width * 0.5, height * 0.5);
+            let quad = vec![
+                -dx, -dy, //
+                dx, -dy, //
+                -dx, dy, //
+                dx, dy,
+            ];
+
+            static INDICES: [u8; 4] = [0, 1, 2, 3];
+            VertexBufferBundle::<'static, GLfloat, u8>::new(
+                gpu_state,
+                quad.into(),
+                (&INDICES).into(),
+                2,
+                &[(shader.sal_position, 2, 0)],
+            )?
+        };
+
+        Ok(Self {
+            pose,
+            width,
+            height,
+            reflectance: 0.2,
+            tint: [1.0, 1.0, 1.0],
+            target,
+            shader,
+            buffers,
+        })
+    }
+
+    /// a `size`x`size` floor at height `y`, centered under the origin
+    pub fn floor(
+        y: f32,
+        size: f32,
+        target_width: i32,
+        target_height: i32,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        // tip +Z up to +Y
+        let pose = Pose::new(
+            XrVector3f::new(0.0, y, 0.0),
+            XrQuaternionf::new(-FRAC_1_SQRT_2, 0.0, 0.0, FRAC_1_SQRT_2),
+        );
+        Self::new(pose, size, size, target_width, target_height, gpu_state)
+    }
+
+    /// the reflecting side, in world space
+    pub fn normal(&self) -> XrVector3f {
+        let m = self.pose.matrix();
+        XrVector3f::new(m.m[8], m.m[9], m.m[10])
+    }
+
+    /// (a,b,c,d) with ax+by+cz+d > 0 in front of the mirror
+    pub fn plane(&self) -> [f32; 4] {
+        let n = self.normal();
+        let p = &self.pose.position;
+        [n.x, n.y, n.z, -(n.x * p.x + n.y * p.y + n.z * p.z)]
+    }
+
+    /// Householder reflection about [PlanarMirror::plane]
+    #[rustfmt::skip]
+    pub fn reflection_matrix(&self) -> XrMatrix4x4f {
+        let [a, b, c, d] = self.plane();
+        [
+            1.0 - 2.0 * a * a, -2.0 * a * b, -2.0 * a * c, 0.0,
+            -2.0 * a * b, 1.0 - 2.0 * b * b, -2.0 * b * c, 0.0,
+            -2.0 * a * c, -2.0 * b * c, 1.0 - 2.0 * c * c, 0.0,
+            -2.0 * a * d, -2.0 * b * d, -2.0 * c * d, 1.0,
+        ].into()
+    }
+
+    fn in_front(&self, point: &XrVector3f) -> bool {
+        let [a, b, c, d] = self.plane();
+        a * point.x + b * point.y + c * point.z + d > 0.0
+    }
+
+    /// Render what an eye at `rotation`,`translation` sees in the mirror into [PlanarMirror::target].
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_view(
+        &self,
+        scene: &MyScene,
+        fov: &XrFovf,
+        rotation: &XrQuaternionf,
+        translation: &XrVector3f,
+        time: Time,
+        gpu_state: &mut GPUState,
+        controller_1: &Option<SpaceLocation>,
+        remote_avatars: &[RemoteAvatar],
+        camera: Option<&PassthroughCamera>,
+    ) -> Result<(), GLErrorWrapper> {
+        if !self.in_front(translation) {
+            return Ok(());
+        }
+
+        let eye = Pose::new(*translation, *rotation).matrix();
+        let inverse_view = xr_matrix4x4f_invert_rigid_body(&eye);
+
+        // after reflecting, the things in front of the mirror are behind it
+        let [a, b, c, d] = self.plane();
+        let plane = transform_plane_to_camera(&eye, &[-a, -b, -c, -d]);
+        let projection = oblique_near_plane(&projection_matrix(fov), &plane);
+
+        let reflection = self.reflection_matrix();
+        let matrix_pv = projection * inverse_view * reflection;
+        // The scene is drawn unreflected and seen from the eye's mirror image, so that is
+        // what distances are measured from.  The reflected frustum, its near plane on the
+        // mirror, skips whatever the mirror can not show.
+        let reflected_eye = xr_matrix4x4f_transform_vector3f(&reflection, translation);
+        let lod_view =
+            LodView::new(reflected_eye, fov, self.target.height).with_frustum(&matrix_pv);
+
+        self.target.bind()?;
+        // the reflection turns counter-clockwise triangles clockwise
+        unsafe { gl::FrontFace(gl::CW) };
+        explode_if_gl_error()?;
+        let result = scene.draw_pv(
+            &matrix_pv,
+            &lod_view,
+            time,
+            gpu_state,
+            controller_1,
+            remote_avatars,
+            camera,
+        );
+        unsafe { gl::FrontFace(gl::CCW) };
+        result?;
+        explode_if_gl_error()
+    }
+
+    /// Composite the reflection onto the mirror quad.
+    /// `matrix_pv` must be the same view the target was rendered for.
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        eye_position: &XrVector3f,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        if !self.in_front(eye_position) {
+            return Ok(());
+        }
+
+        self.shader.draw(
+            matrix_pv,
+            &self.pose.matrix(),
+            eye_position,
+            &self.normal(),
+            self.reflectance,
+            &self.tint,
+            &self.target.color,
+            gl::TRIANGLE_STRIP,
+            &self.buffers,
+            self.buffers.index_count as GLsizei,
+            gpu_state,
+        )
+    }
+}
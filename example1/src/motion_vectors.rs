@@ -0,0 +1,362 @@
+//! Per-pixel motion vectors, groundwork for handing them to the runtime with
+//! XR_FB_space_warp so it can synthesize every other frame.  Each registered object is drawn
+//! into a small half-float target with its current and previous model-view-projection;
+//! the difference in normalized device coordinates lands in RGB.  Anything not registered
+//! (the sky, UI) reads as not moving.
+
+use crate::post_chain::fullscreen_quad;
+use bob_shaders::depth_copy_shader::DepthCopyShader;
+use bob_shaders::motion_vector_shader::MotionVectorShader;
+use gl::types::{GLfloat, GLint, GLsizei, GLuint, GLushort};
+use gl_thin::gl_fancy::{GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::{explode_if_gl_error, FrameBuffer, GLErrorWrapper, Texture};
+use gl_thin::linear::XrMatrix4x4f;
+use gl_thin::render_target::{ColorFormat, RenderTarget};
+use std::collections::HashMap;
+
+/// Names one object across frames, so its previous model matrix can be found
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MotionId(pub u32);
+
+/// The model matrices objects had in the previous frame.  [crate::scene::MyScene] keeps one
+/// and advances it once the frame is painted.
+#[derive(Default)]
+pub struct MotionHistory {
+    previous: HashMap<MotionId, XrMatrix4x4f>,
+    previous_frame: Option<u64>,
+}
+
+impl MotionHistory {
+    /// Where `id` was in the frame before `frame_index`.  `None` if it was not there, or
+    /// that frame was skipped.
+    pub fn previous(&self, id: MotionId, frame_index: u64) -> Option<&XrMatrix4x4f> {
+        if self.previous_frame? + 1 != frame_index {
+            return None;
+        }
+        self.previous.get(&id)
+    }
+
+    /// remember `frame_index`'s models for the next frame
+    pub fn advance(
+        &mut self,
+        frame_index: u64,
+        models: impl IntoIterator<Item = (MotionId, XrMatrix4x4f)>,
+    ) {
+        self.previous.clear();
+        self.previous.extend(models);
+        self.previous_frame = Some(frame_index);
+    }
+}
+
+//
+
+/// a mesh registered with [MotionVectors::add_mesh]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MotionMesh(usize);
+
+/// the target for one eye and the camera it last saw
+struct MotionEye {
+    target: RenderTarget,
+    matrix_pv: XrMatrix4x4f,
+    frame_index: u64,
+}
+
+pub struct MotionVectors {
+    /// the target is this many times smaller than the eye image each way
+    pub downscale: u32,
+    shader: MotionVectorShader,
+    meshes: Vec<VertexBufferBundle<'static, GLfloat, GLushort>>,
+    /// which mesh each object is drawn with
+    objects: HashMap<MotionId, MotionMesh>,
+    eyes: Vec<Option<MotionEye>>,
+    /// for [MotionVectors::copy_to]
+    copy_frame_buffer: FrameBuffer,
+}
+
+impl MotionVectors {
+    /// Fails if the device can not render to [ColorFormat::Rgba16F]
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        if !ColorFormat::Rgba16F.is_renderable() {
+            return Err(GLErrorWrapper::with_message2(
+                "motion vectors need half-float color buffers".to_string(),
+            ));
+        }
+        Ok(Self {
+            downscale: 4,
+            shader: MotionVectorShader::new()?,
+            meshes: vec![],
+            objects: HashMap::new(),
+            eyes: vec![],
+            copy_frame_buffer: FrameBuffer::new()?,
+        })
+    }
+
+    /// Only the positions are used: the first `position_size` floats of every `stride`.
+    pub fn add_mesh(
+        &mut self,
+        vertices: &'static [GLfloat],
+        stride: GLsizei,
+        position_size: i32,
+        indices: Vec<GLushort>,
+        gpu_state: &mut GPUState,
+    ) -> Result<MotionMesh, GLErrorWrapper> {
+        let buffers = VertexBufferBundle::new(
+            gpu_state,
+            vertices.into(),
+            indices.into(),
+            stride,
+            &[(self.shader.sal_position, position_size, 0)],
+        )?;
+        self.meshes.push(buffers);
+        Ok(MotionMesh(self.meshes.len() - 1))
+    }
+
+    /// draw object `id` with `mesh` whenever it is in the models given to [MotionVectors::render]
+    pub fn assign(&mut self, id: MotionId, mesh: MotionMesh) {
+        self.objects.insert(id, mesh);
+    }
+
+    /// the size of the target for an eye image `width` x `height`, see [MotionVectors::downscale]
+    pub fn target_size(&self, width: i32, height: i32) -> (i32, i32) {
+        let downscale = self.downscale.max(1) as i32;
+        ((width / downscale).max(1), (height / downscale).max(1))
+    }
+
+    /// Draw `eye`'s motion vectors into a target `width` x `height`, usually
+    /// [MotionVectors::target_size].  `models` are this frame's; the previous frame's come
+    /// from `history`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        eye: usize,
+        frame_index: u64,
+        matrix_pv: XrMatrix4x4f,
+        width: i32,
+        height: i32,
+        models: &[(MotionId, XrMatrix4x4f)],
+        history: &MotionHistory,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        if self.eyes.len() <= eye {
+            self.eyes.resize_with(eye + 1, || None);
+        }
+        let stale = !matches!(
+            &self.eyes[eye],
+            Some(motion_eye) if motion_eye.target.width == width
+                && motion_eye.target.height == height
+        );
+        if stale {
+            self.eyes[eye] = Some(MotionEye {
+                target: RenderTarget::with_format(width, height, ColorFormat::Rgba16F, gpu_state)?,
+                matrix_pv,
+                frame_index,
+            });
+        }
+        let Some(motion_eye) = &mut self.eyes[eye] else {
+            return Ok(());
+        };
+        // after a skipped frame, leave the camera's motion out rather than guess
+        let previous_pv = if motion_eye.frame_index + 1 == frame_index {
+            motion_eye.matrix_pv
+        } else {
+            matrix_pv
+        };
+
+        motion_eye.target.bind()?;
+        unsafe {
+            gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+        explode_if_gl_error()?;
+        for (id, model) in models {
+            let Some(MotionMesh(mesh)) = self.objects.get(id) else {
+                continue;
+            };
+            let buffers = &self.meshes[*mesh];
+            let previous_model = history.previous(*id, frame_index).unwrap_or(model);
+            self.shader.draw(
+                &(matrix_pv * *model),
+                &(previous_pv * *previous_model),
+                buffers,
+                buffers.index_count as GLsizei,
+                gpu_state,
+            )?;
+        }
+
+        motion_eye.matrix_pv = matrix_pv;
+        motion_eye.frame_index = frame_index;
+        Ok(())
+    }
+
+    /// Copy what [MotionVectors::render] last drew for `eye` into `motion`, e.g.
+    /// XR_FB_space_warp's swapchain image, which must be the same size.  The depth to go with
+    /// it is the eye's own, see [DepthCopy].
+    pub fn copy_to(&self, eye: usize, motion: &Texture) -> Result<(), GLErrorWrapper> {
+        let Some(source) = self.output(eye) else {
+            return Ok(());
+        };
+        self.copy_frame_buffer.bind()?;
+        motion.attach(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, 0)?;
+        source.frame_buffer.bind_read()?;
+        unsafe {
+            gl::BlitFramebuffer(
+                0,
+                0,
+                source.width,
+                source.height,
+                0,
+                0,
+                source.width,
+                source.height,
+                gl::COLOR_BUFFER_BIT,
+                gl::NEAREST,
+            );
+        }
+        explode_if_gl_error()
+    }
+
+    /// what [MotionVectors::render] last drew for `eye`
+    pub fn output(&self, eye: usize) -> Option<&RenderTarget> {
+        self.eyes
+            .get(eye)?
+            .as_ref()
+            .map(|motion_eye| &motion_eye.target)
+    }
+}
+
+//
+
+/// Copies an eye's depth into a smaller depth image without a stencil, e.g.
+/// XR_FB_space_warp's, so the runtime reprojects the whole scene and not just the objects
+/// [MotionVectors] knows.  The depth is blitted (which also resolves MSAA) into a texture
+/// like the eye's, then drawn into the destination by a shader writing gl_FragDepth.
+pub struct DepthCopy {
+    shader: DepthCopyShader,
+    quad: VertexBufferBundle<'static, GLfloat, u8>,
+    frame_buffer: FrameBuffer,
+    /// the eye's depth as the last [DepthCopy::copy] found it: texture, size and stencil
+    scene_depth: Option<(Texture, i32, i32, bool)>,
+}
+
+impl DepthCopy {
+    pub fn new(gpu_state: &mut GPUState) -> Result<Self, GLErrorWrapper> {
+        let shader = DepthCopyShader::new()?;
+        let quad = fullscreen_quad(shader.sal_position, gpu_state)?;
+        Ok(Self {
+            shader,
+            quad,
+            frame_buffer: FrameBuffer::new()?,
+            scene_depth: None,
+        })
+    }
+
+    /// Copy the depth of the framebuffer bound for drawing, `width` x `height`, into
+    /// `destination`, a DEPTH_COMPONENT24 texture `destination_width` x `destination_height`.
+    /// The source framebuffer and viewport are bound again afterwards.
+    pub fn copy(
+        &mut self,
+        width: i32,
+        height: i32,
+        destination: &Texture,
+        destination_width: i32,
+        destination_height: i32,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let (mut source, mut stencil) = (0, 0);
+        unsafe {
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut source);
+            // a packed depth+stencil buffer is attached at both
+            gl::GetFramebufferAttachmentParameteriv(
+                gl::DRAW_FRAMEBUFFER,
+                gl::STENCIL_ATTACHMENT,
+                gl::FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE,
+                &mut stencil,
+            );
+        }
+        let stencil = stencil != gl::NONE as GLint;
+        let result = self.copy_from(
+            source as GLuint,
+            (width, height, stencil),
+            destination,
+            (destination_width, destination_height),
+            gpu_state,
+        );
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, source as GLuint);
+            gl::Viewport(0, 0, width, height);
+            gl::DepthFunc(gl::LESS);
+        }
+        result.and_then(|_| explode_if_gl_error())
+    }
+
+    fn copy_from(
+        &mut self,
+        source: GLuint,
+        (width, height, stencil): (i32, i32, bool),
+        destination: &Texture,
+        (destination_width, destination_height): (i32, i32),
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let scene_depth = match self.scene_depth.take() {
+            Some(depth) if (depth.1, depth.2, depth.3) == (width, height, stencil) => depth,
+            _ => {
+                let texture = if stencil {
+                    Texture::depth_stencil_buffer(width, height, gpu_state)?
+                } else {
+                    Texture::depth_buffer(width, height, gpu_state)?
+                };
+                {
+                    let bound = texture.bound(gl::TEXTURE_2D, gpu_state)?;
+                    bound.set_parameter(gl::TEXTURE_MIN_FILTER, gl::NEAREST)?;
+                    bound.set_parameter(gl::TEXTURE_MAG_FILTER, gl::NEAREST)?;
+                }
+                (texture, width, height, stencil)
+            }
+        };
+        let texture = &self.scene_depth.insert(scene_depth).0;
+        let (attachment, other) = if stencil {
+            (gl::DEPTH_STENCIL_ATTACHMENT, gl::DEPTH_ATTACHMENT)
+        } else {
+            (gl::DEPTH_ATTACHMENT, gl::DEPTH_STENCIL_ATTACHMENT)
+        };
+
+        // the same format and size, so the blit resolves MSAA
+        self.frame_buffer.bind()?;
+        unsafe {
+            gl::FramebufferTexture2D(gl::DRAW_FRAMEBUFFER, other, gl::TEXTURE_2D, 0, 0);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, source);
+        }
+        texture.attach(gl::DRAW_FRAMEBUFFER, attachment, gl::TEXTURE_2D, 0)?;
+        unsafe {
+            gl::BlitFramebuffer(
+                0,
+                0,
+                width,
+                height,
+                0,
+                0,
+                width,
+                height,
+                gl::DEPTH_BUFFER_BIT,
+                gl::NEAREST,
+            );
+        }
+        explode_if_gl_error()?;
+
+        unsafe { gl::FramebufferTexture2D(gl::DRAW_FRAMEBUFFER, attachment, gl::TEXTURE_2D, 0, 0) };
+        destination.attach(
+            gl::DRAW_FRAMEBUFFER,
+            gl::DEPTH_ATTACHMENT,
+            gl::TEXTURE_2D,
+            0,
+        )?;
+        unsafe {
+            gl::Viewport(0, 0, destination_width, destination_height);
+            gl::Enable(gl::DEPTH_TEST);
+            gl::DepthFunc(gl::ALWAYS);
+            gl::DepthMask(gl::TRUE);
+        }
+        self.shader.draw(texture, &self.quad, 4, gpu_state)
+    }
+}
@@ -0,0 +1,266 @@
+//! Multisampled eye images.  Each view is drawn into a [MsaaTarget] and resolved into the
+//! swapchain image with the [ResolvePlan] its format calls for; see [gl_thin::msaa] for why the
+//! color space matters.  [MsaaResolver::check] measures a resolve on the device itself, and
+//! [MsaaResolver::new_checked] falls back to the shader when the driver's blit averages in
+//! the wrong space.
+
+use crate::post_chain::{fullscreen_quad, without_depth};
+use bob_shaders::flat_color_shader::FlatColorShader;
+use bob_shaders::msaa_resolve_shader::MsaaResolveShader;
+use gl::types::{GLenum, GLfloat, GLsizei};
+use gl_thin::gl_fancy::{GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper};
+use gl_thin::linear::xr_matrix4x4f_identity;
+use gl_thin::msaa::{with_srgb_writes, ColorSpace, MsaaTarget, ResolveMethod, ResolvePlan};
+use gl_thin::render_target::{ColorFormat, RenderTarget};
+
+/// pixels on a side of [MsaaResolver::check]'s target
+const CHECK_SIZE: i32 = 4;
+/// how far [ResolveCheck::actual] may be from [ResolveCheck::expected]
+const CHECK_TOLERANCE: u8 = 4;
+
+/// What [MsaaResolver::check] measured
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ResolveCheck {
+    pub plan: ResolvePlan,
+    /// half white and half black averaged as linear light: 188 in an sRGB destination, 128
+    /// in a linear one
+    pub expected: u8,
+    /// 128 in an sRGB destination means the samples were averaged sRGB-encoded
+    pub actual: u8,
+}
+
+impl ResolveCheck {
+    pub fn passed(&self) -> bool {
+        self.actual.abs_diff(self.expected) <= CHECK_TOLERANCE
+    }
+}
+
+pub struct MsaaResolver {
+    pub samples: GLsizei,
+    /// what the samples are stored as: the swapchain's format where it is renderable, so a
+    /// blit can resolve
+    pub format: ColorFormat,
+    pub plan: ResolvePlan,
+    swapchain_format: GLenum,
+    stencil: bool,
+    /// made at the first [MsaaResolver::begin], and again if the size changes
+    target: Option<MsaaTarget>,
+    /// for [ResolveMethod::Shader]
+    shader: Option<(MsaaResolveShader, VertexBufferBundle<'static, GLfloat, u8>)>,
+}
+
+impl MsaaResolver {
+    /// `stencil` to match the [crate::drawcore::FrameEnv] it stands in for
+    pub fn new(
+        samples: GLsizei,
+        swapchain_format: GLenum,
+        stencil: bool,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let format = match swapchain_format {
+            gl::SRGB8_ALPHA8 => ColorFormat::Srgb8Alpha8,
+            _ => ColorFormat::Rgba8,
+        };
+        let mut rval = Self {
+            samples,
+            format,
+            plan: ResolvePlan::choose(format, swapchain_format)?,
+            swapchain_format,
+            stencil,
+            target: None,
+            shader: None,
+        };
+        rval.set_plan(rval.plan, gpu_state)?;
+        Ok(rval)
+    }
+
+    /// Like [MsaaResolver::new], then [MsaaResolver::check] the plan, and switch to a shader
+    /// resolve if a blit fails it.
+    pub fn new_checked(
+        samples: GLsizei,
+        swapchain_format: GLenum,
+        stencil: bool,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let mut rval = Self::new(samples, swapchain_format, stencil, gpu_state)?;
+        let Some(check) = rval.check(gpu_state)? else {
+            log::info!("MSAA resolve not checked; {}", rval.plan);
+            return Ok(rval);
+        };
+        if check.passed() {
+            log::info!("MSAA resolve checks out; {}", rval.plan);
+        } else if rval.plan.method == ResolveMethod::Blit {
+            log::warn!(
+                "MSAA blit resolve gave {} instead of {}, resolving with a shader",
+                check.actual,
+                check.expected
+            );
+            rval.set_plan(
+                ResolvePlan::shader(rval.format, swapchain_format)?,
+                gpu_state,
+            )?;
+        } else {
+            log::warn!(
+                "MSAA shader resolve gave {} instead of {}",
+                check.actual,
+                check.expected
+            );
+        }
+        Ok(rval)
+    }
+
+    pub fn set_plan(
+        &mut self,
+        plan: ResolvePlan,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        if plan.method == ResolveMethod::Shader && self.shader.is_none() {
+            let shader = MsaaResolveShader::new()?;
+            let buffers = fullscreen_quad(shader.sal_position, gpu_state)?;
+            self.shader = Some((shader, buffers));
+        }
+        self.plan = plan;
+        self.target = None;
+        Ok(())
+    }
+
+    /// Bind the multisampled target, `width` x `height`, for drawing a view into, until
+    /// [MsaaResolver::resolve]
+    pub fn begin(
+        &mut self,
+        width: i32,
+        height: i32,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let target = match self.target.take() {
+            Some(target) if target.width == width && target.height == height => target,
+            _ => MsaaTarget::new(
+                width,
+                height,
+                self.samples,
+                self.format,
+                self.plan,
+                self.stencil,
+                gpu_state,
+            )?,
+        };
+        self.target.insert(target).bind()
+    }
+
+    /// Average the samples into the swapchain image, which `bind_destination` binds for
+    /// drawing, then drop them.
+    pub fn resolve(
+        &self,
+        bind_destination: impl FnOnce() -> Result<(), GLErrorWrapper>,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let Some(target) = &self.target else {
+            return Err(GLErrorWrapper::with_message2(
+                "MSAA resolve without begin".to_string(),
+            ));
+        };
+        bind_destination()?;
+        match (target.plan.method, &self.shader, target.color_texture()) {
+            (ResolveMethod::Blit, _, _) => target.resolve_blit()?,
+            (ResolveMethod::Shader, Some((shader, buffers)), Some(color)) => {
+                // each pixel replaces what was there
+                unsafe { gl::Disable(gl::BLEND) };
+                explode_if_gl_error()?;
+                with_srgb_writes(target.plan.written, || {
+                    without_depth(|| shader.draw(color, target.samples, buffers, 4, gpu_state))
+                })?;
+            }
+            (ResolveMethod::Shader, _, _) => {
+                return Err(GLErrorWrapper::with_message2(
+                    "MSAA shader resolve without its shader or texture".to_string(),
+                ))
+            }
+        }
+        target.invalidate()
+    }
+
+    /// Resolve a test image and read it back: every pixel has half its samples white and
+    /// half black, which averages to 188 in an sRGB swapchain and 128 in a linear one.
+    /// `Ok(None)` where it can not tell: a swapchain format no [RenderTarget] has, a single
+    /// sample, or no glSampleMaski (GLES 3.1) to pick the samples with.
+    pub fn check(
+        &mut self,
+        gpu_state: &mut GPUState,
+    ) -> Result<Option<ResolveCheck>, GLErrorWrapper> {
+        let destination_format = match self.swapchain_format {
+            gl::SRGB8_ALPHA8 => ColorFormat::Srgb8Alpha8,
+            gl::RGBA8 => ColorFormat::Rgba8,
+            _ => return Ok(None),
+        };
+        if !gl::SampleMaski::is_loaded() {
+            return Ok(None);
+        }
+        let destination =
+            RenderTarget::with_format(CHECK_SIZE, CHECK_SIZE, destination_format, gpu_state)?;
+
+        self.begin(CHECK_SIZE, CHECK_SIZE, gpu_state)?;
+        let samples = self.target.as_ref().map_or(0, |target| target.samples);
+        if samples < 2 {
+            return Ok(None);
+        }
+        unsafe {
+            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            // back to what the eyes clear to; the eye layer is blended by its alpha
+            gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+        }
+        explode_if_gl_error()?;
+        fill_half_the_samples(samples, gpu_state)?;
+        self.resolve(|| destination.bind(), gpu_state)?;
+
+        let pixels = destination.read_pixels()?;
+        let center = ((CHECK_SIZE / 2) * CHECK_SIZE + CHECK_SIZE / 2) as usize * 4;
+        Ok(Some(ResolveCheck {
+            plan: self.plan,
+            expected: match self.plan.written {
+                ColorSpace::Srgb => 188,
+                ColorSpace::Linear => 128,
+            },
+            actual: pixels[center],
+        }))
+    }
+}
+
+/// white into the first half of every pixel's samples of the bound framebuffer
+fn fill_half_the_samples(samples: GLsizei, gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper> {
+    static INDICES: [u8; 4] = [0, 1, 2, 3];
+    #[rustfmt::skip]
+    let vertices = vec![
+        -1.0, -1.0, 0.0, 1.0, 1.0, 1.0,
+        1.0, -1.0, 0.0, 1.0, 1.0, 1.0,
+        -1.0, 1.0, 0.0, 1.0, 1.0, 1.0,
+        1.0, 1.0, 0.0, 1.0, 1.0, 1.0,
+    ];
+    let shader = FlatColorShader::new()?;
+    let buffers = VertexBufferBundle::<'static, GLfloat, u8>::new(
+        gpu_state,
+        vertices.into(),
+        (&INDICES).into(),
+        6,
+        &[(shader.sal_position, 3, 0), (shader.sal_color, 3, 3)],
+    )?;
+    shader.program.use_()?;
+    shader.set_params(&xr_matrix4x4f_identity());
+
+    let mask = (1u32 << (samples / 2)) - 1;
+    unsafe {
+        gl::Disable(gl::BLEND);
+        gl::Enable(gl::SAMPLE_MASK);
+        gl::SampleMaski(0, mask);
+    }
+    let result = without_depth(|| {
+        let binding = buffers.bind(gpu_state)?;
+        binding.draw_elements(gl::TRIANGLE_STRIP, 4, 0)
+    });
+    unsafe {
+        gl::SampleMaski(0, !0);
+        gl::Disable(gl::SAMPLE_MASK);
+    }
+    result
+}
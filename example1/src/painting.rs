@@ -0,0 +1,286 @@
+//! Drawing in the air: while the trigger is held, a flat ribbon is extruded along the
+//! controller's path, the way a paintbrush leaves a stroke.
+
+use crate::gltf_export::GltfDocument;
+use crate::pose_stream::Pose;
+use crate::undo::Command;
+use bob_shaders::fog::Fog;
+use bob_shaders::lights::LightManager;
+use bob_shaders::sun_phong_shader::SunPhongShader;
+use gl::types::{GLfloat, GLsizei, GLuint};
+use gl_thin::editable_mesh::EditableMesh;
+use gl_thin::gl_fancy::{GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::{
+    xr_matrix4x4f_create_from_quaternion, xr_matrix4x4f_identity, xr_matrix4x4f_transform_vector3f,
+    XrMatrix4x4f, XrVector3f,
+};
+use gl_thin::static_batch::VertexLayout;
+
+/// position and normal
+const LAYOUT: VertexLayout = VertexLayout {
+    stride: 6,
+    position_offset: 0,
+    normal_offset: Some(3),
+};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StrokeStyle {
+    pub color: [f32; 3],
+    /// in meters
+    pub width: f32,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        Self {
+            color: [0.9, 0.2, 0.2],
+            width: 0.02,
+        }
+    }
+}
+
+pub struct Stroke {
+    pub style: StrokeStyle,
+    mesh: EditableMesh,
+    bundle: VertexBufferBundle<'static, GLfloat, GLuint>,
+    last_sample: Option<XrVector3f>,
+}
+
+impl Stroke {
+    fn new(
+        style: StrokeStyle,
+        phong: &SunPhongShader,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let mesh = EditableMesh::new(gpu_state, LAYOUT, vec![], vec![])?;
+        let bundle = VertexBufferBundle::from_buffers(
+            gpu_state,
+            &mesh.buffers,
+            LAYOUT.stride as GLsizei,
+            &[(phong.sal_position, 3, 0), (phong.sal_normal, 3, 3)],
+        )?;
+        Ok(Self {
+            style,
+            mesh,
+            bundle,
+            last_sample: None,
+        })
+    }
+
+    /// Add a cross-section of the ribbon at `brush`, spanning the controller's X axis with
+    /// its Y axis as the normal, and join it to the previous one.
+    fn extend(&mut self, brush: &Pose) -> Result<(), GLErrorWrapper> {
+        let rotation = xr_matrix4x4f_create_from_quaternion(&brush.orientation);
+        let across = xr_matrix4x4f_transform_vector3f(
+            &rotation,
+            &XrVector3f::new(self.style.width / 2.0, 0.0, 0.0),
+        );
+        let normal = xr_matrix4x4f_transform_vector3f(&rotation, &XrVector3f::new(0.0, 1.0, 0.0));
+        let p = brush.position;
+        let left = p - across;
+        let right = p + across;
+
+        let first = self.mesh.push_vertices(&[
+            left.x, left.y, left.z, normal.x, normal.y, normal.z, //
+            right.x, right.y, right.z, normal.x, normal.y, normal.z,
+        ])?;
+        if self.last_sample.is_some() {
+            let (l0, r0, l1, r1) = (first - 2, first - 1, first, first + 1);
+            self.mesh.push_indices(&[l0, r0, l1, r0, r1, l1])?;
+        }
+        self.last_sample = Some(p);
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.mesh.index_count() == 0
+    }
+
+    fn draw(
+        &self,
+        phong: &SunPhongShader,
+        matrix_pv: &XrMatrix4x4f,
+        sun_direction: &[f32; 3],
+        fog: &Fog,
+        lights: &LightManager,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        phong.draw(
+            &xr_matrix4x4f_identity(),
+            matrix_pv,
+            sun_direction,
+            &self.style.color,
+            fog,
+            lights,
+            &self.bundle,
+            self.mesh.index_count() as GLsizei,
+            gpu_state,
+        )
+    }
+}
+
+/// Every stroke painted so far, plus the one in progress.
+pub struct Painter {
+    /// used for the next stroke
+    pub style: StrokeStyle,
+    /// how far the controller has to move before the stroke grows, in meters
+    pub spacing: f32,
+    phong: SunPhongShader,
+    strokes: Vec<Stroke>,
+    active: Option<Stroke>,
+}
+
+impl Painter {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        Ok(Self {
+            style: StrokeStyle::default(),
+            spacing: 0.005,
+            phong: SunPhongShader::new()?,
+            strokes: vec![],
+            active: None,
+        })
+    }
+
+    /// Call once a frame with the trigger state and the controller pose.  A stroke starts
+    /// when `held` goes true and is finished when it goes false or tracking is lost.
+    /// Returns true when a stroke was finished; record a [StrokeCommand] to make it undoable.
+    pub fn update(
+        &mut self,
+        held: bool,
+        brush: Option<Pose>,
+        gpu_state: &mut GPUState,
+    ) -> Result<bool, GLErrorWrapper> {
+        let brush = match (held, brush) {
+            (true, Some(brush)) => brush,
+            _ => return Ok(self.finish_stroke()),
+        };
+
+        let stroke = match &mut self.active {
+            Some(stroke) => stroke,
+            None => self
+                .active
+                .insert(Stroke::new(self.style, &self.phong, gpu_state)?),
+        };
+        let far_enough = match stroke.last_sample {
+            Some(last) => {
+                let d = brush.position - last;
+                d.x * d.x + d.y * d.y + d.z * d.z >= self.spacing * self.spacing
+            }
+            None => true,
+        };
+        if far_enough {
+            stroke.extend(&brush)?;
+            stroke.mesh.upload(gpu_state)?;
+        }
+        Ok(false)
+    }
+
+    fn finish_stroke(&mut self) -> bool {
+        match self.active.take() {
+            // a tap without moving leaves nothing worth keeping
+            Some(stroke) if !stroke.is_empty() => {
+                self.strokes.push(stroke);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn is_painting(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// finished strokes, oldest first
+    pub fn strokes(&self) -> &[Stroke] {
+        &self.strokes
+    }
+
+    /// Remove the most recent finished stroke.  Hand it to [Painter::restore] to bring it back.
+    pub fn undo(&mut self) -> Option<Stroke> {
+        self.strokes.pop()
+    }
+
+    pub fn restore(&mut self, stroke: Stroke) {
+        self.strokes.push(stroke);
+    }
+
+    pub fn clear(&mut self) {
+        self.strokes.clear();
+        self.active = None;
+    }
+
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        sun_direction: &[f32; 3],
+        fog: &Fog,
+        lights: &LightManager,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        for stroke in self.strokes.iter().chain(&self.active) {
+            stroke.draw(
+                &self.phong,
+                matrix_pv,
+                sun_direction,
+                fog,
+                lights,
+                gpu_state,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// every stroke as its own mesh; the vertices are already in world space
+    pub fn export_gltf(&self, document: &mut GltfDocument) {
+        for (i, stroke) in self.strokes.iter().chain(&self.active).enumerate() {
+            let geometry = document.add_geometry(
+                stroke.mesh.vertices(),
+                LAYOUT.stride,
+                LAYOUT.position_offset,
+                LAYOUT.normal_offset,
+                stroke.mesh.indices().iter().copied(),
+            );
+            let mesh = document.add_mesh("stroke", geometry, stroke.style.color);
+            document.add_node(&format!("stroke {}", i), mesh, &xr_matrix4x4f_identity());
+        }
+    }
+}
+
+/// Undoes and redoes the most recently finished stroke.  `painter` finds the [Painter]
+/// inside the [crate::undo::UndoStack]'s owner.
+pub struct StrokeCommand<C> {
+    painter: fn(&mut C) -> Option<&mut Painter>,
+    /// while undone
+    stroke: Option<Stroke>,
+}
+
+impl<C> StrokeCommand<C> {
+    pub fn new(painter: fn(&mut C) -> Option<&mut Painter>) -> Self {
+        Self {
+            painter,
+            stroke: None,
+        }
+    }
+}
+
+impl<C> Command<C> for StrokeCommand<C> {
+    fn apply(&mut self, owner: &mut C) {
+        if let (Some(painter), Some(stroke)) = ((self.painter)(owner), self.stroke.take()) {
+            painter.restore(stroke);
+        }
+    }
+
+    fn revert(&mut self, owner: &mut C) {
+        if let Some(painter) = (self.painter)(owner) {
+            self.stroke = painter.undo();
+        }
+    }
+
+    fn name(&self) -> &str {
+        "paint stroke"
+    }
+}
@@ -0,0 +1,156 @@
+//! 360° images and video around the viewer.  When the runtime supports
+//! XR_KHR_composition_layer_equirect2 the frames go into an equirect layer behind the eye
+//! buffers, which the compositor samples at full quality; otherwise they are drawn into
+//! the eye buffers on a cube around the eye, the way the sky is.
+
+use crate::sky::{eye_cube, SKY_RADIUS};
+use bob_shaders::equirect_shader::EquirectShader;
+use gl::types::{GLfloat, GLsizei, GLushort};
+use gl_thin::composition_layers::{
+    CompositionLayer, LayerId, LayerPlacement, LayerShape, LayerSwapchain,
+};
+use gl_thin::gl_fancy::{ActiveTextureUnit, GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::{explode_if_gl_error, FrameBuffer, GLErrorWrapper};
+use gl_thin::linear::{
+    xr_matrix4x4f_create_scale, xr_matrix4x4f_create_translation_v, XrMatrix4x4f, XrVector3f,
+};
+use gl_thin::openxr_helpers::{Backend, OpenXRComponent};
+use gl_thin::render_target::RenderTarget;
+use std::error::Error;
+
+enum PanoramaOutput {
+    Layer {
+        id: LayerId,
+        /// for drawing into the layer's swapchain images
+        frame_buffer: FrameBuffer,
+    },
+    /// the fallback
+    Cube {
+        target: RenderTarget,
+        shader: EquirectShader,
+        buffers: VertexBufferBundle<'static, GLfloat, GLushort>,
+    },
+}
+
+/// Shown instead of the sky, see [crate::scene::MyScene::panorama]
+pub struct Panorama {
+    output: PanoramaOutput,
+}
+
+impl Panorama {
+    /// `width` x `height` is the size of the equirectangular frames
+    pub fn new(
+        openxr: &mut OpenXRComponent<Backend>,
+        width: u32,
+        height: u32,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, Box<dyn Error>> {
+        let shape = LayerShape::full_equirect();
+        let output = if openxr.layers.supports(&shape) {
+            let swapchain =
+                LayerSwapchain::new(&openxr.xr_session, openxr.swapchain_format, width, height)?;
+            let id = openxr.layers.add(
+                CompositionLayer::new(shape, swapchain).with_placement(LayerPlacement::Underlay),
+            )?;
+            PanoramaOutput::Layer {
+                id,
+                frame_buffer: FrameBuffer::new()?,
+            }
+        } else {
+            log::info!("no equirect layer support, drawing the panorama in the eye buffers");
+            let shader = EquirectShader::new()?;
+            PanoramaOutput::Cube {
+                target: RenderTarget::new(width as i32, height as i32, gpu_state)?,
+                buffers: eye_cube(shader.sal_position, gpu_state)?,
+                shader,
+            }
+        };
+        Ok(Self { output })
+    }
+
+    /// true if the compositor shows the panorama, false if it is drawn into the eye buffers
+    pub fn uses_layer(&self) -> bool {
+        matches!(self.output, PanoramaOutput::Layer { .. })
+    }
+
+    /// Take the layer, if there is one, out of the frame; for when the panorama is dropped.
+    pub fn remove_layer(&self, openxr: &mut OpenXRComponent<Backend>) {
+        if let PanoramaOutput::Layer { id, .. } = &self.output {
+            openxr.layers.remove(*id);
+        }
+    }
+
+    /// Replace the image.  `paint` has to fill the bound framebuffer's viewport with the
+    /// whole equirectangular frame, e.g. a decoded video frame drawn with
+    /// [bob_shaders::yuv_shader::YuvShader] on a full-screen quad.
+    pub fn update(
+        &mut self,
+        openxr: &mut OpenXRComponent<Backend>,
+        gpu_state: &mut GPUState,
+        paint: impl FnOnce(&mut GPUState) -> Result<(), GLErrorWrapper>,
+    ) -> Result<(), Box<dyn Error>> {
+        match &self.output {
+            PanoramaOutput::Layer { id, frame_buffer } => {
+                let layer = openxr
+                    .layers
+                    .get_mut(*id)
+                    .ok_or("the panorama's layer was removed")?;
+                layer
+                    .swapchain
+                    .paint_gl(frame_buffer, || paint(gpu_state))??;
+            }
+            PanoramaOutput::Cube { target, .. } => {
+                target.bind()?;
+                paint(gpu_state)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Paint the background in place of [crate::sky::Sky::draw].  With a layer this draws
+    /// nothing: the eye buffers are cleared to transparent, which lets the layer show.
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        eye_position: &XrVector3f,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let PanoramaOutput::Cube {
+            target,
+            shader,
+            buffers,
+        } = &self.output
+        else {
+            return Ok(());
+        };
+
+        let model = xr_matrix4x4f_create_translation_v(eye_position)
+            * xr_matrix4x4f_create_scale(SKY_RADIUS, SKY_RADIUS, SKY_RADIUS);
+
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::DepthMask(gl::FALSE);
+        }
+        explode_if_gl_error()?;
+
+        let result = shader.draw(
+            &(*matrix_pv * model),
+            &target.color,
+            ActiveTextureUnit(0),
+            buffers,
+            buffers.index_count as GLsizei,
+            gpu_state,
+        );
+
+        unsafe { gl::DepthMask(gl::TRUE) };
+        result?;
+        explode_if_gl_error()
+    }
+
+    /// take the layer out of the stack; the fallback has nothing to clean up
+    pub fn remove(self, openxr: &mut OpenXRComponent<Backend>) {
+        if let PanoramaOutput::Layer { id, .. } = self.output {
+            openxr.layers.remove(id);
+        }
+    }
+}
@@ -0,0 +1,585 @@
+//! Show the headset's passthrough cameras inside the app, on runtimes which let apps open them
+//! with the regular android camera2 API (e.g. Quest with the HEADSET_CAMERA permission).
+//! Frames arrive as AHardwareBuffers which are sampled directly as an external texture,
+//! so nothing is copied or converted on the CPU.
+
+use crate::android_permissions::{has_permission, request_permissions};
+use crate::textured_quad::TexturedQuad;
+use android_activity::AndroidApp;
+use gl_thin::external_image::{EglImage, ExternalImageLoader, TEXTURE_EXTERNAL_OES};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper, Texture, TextureWithTarget};
+use gl_thin::linear::{xr_matrix4x4f_create_scale, XrMatrix4x4f};
+use gl_thin::multiview;
+use std::error::Error;
+use std::ffi::{c_int, c_void, CStr, CString};
+use std::fmt::{Display, Formatter};
+use std::ptr::null_mut;
+
+pub const CAMERA_PERMISSIONS: [&str; 2] = [
+    "android.permission.CAMERA",
+    "horizonos.permission.HEADSET_CAMERA",
+];
+
+const PERMISSION_REQUEST_CODE: i32 = 0x43414d;
+
+/// Where the camera image is drawn
+#[derive(Copy, Clone)]
+pub enum CameraPreviewPlacement {
+    /// fills each eye's view behind the scene.  The camera is not at the eye, so this is only approximate.
+    Background,
+    /// a 1x1 quad (before `model` is applied) in the world
+    Quad(XrMatrix4x4f),
+}
+
+pub struct PassthroughCameraConfig {
+    /// which camera to open.  `None` picks the first one the camera manager lists.
+    /// On Quest the passthrough cameras are "50" (left) and "51" (right).
+    pub camera_id: Option<String>,
+    pub width: i32,
+    pub height: i32,
+    pub placement: CameraPreviewPlacement,
+}
+
+impl PassthroughCameraConfig {
+    pub fn new(placement: CameraPreviewPlacement) -> Self {
+        Self {
+            camera_id: None,
+            width: 1280,
+            height: 960,
+            placement,
+        }
+    }
+}
+
+//
+
+#[derive(Debug)]
+pub struct CameraError {
+    pub call: &'static str,
+    pub status: i32,
+}
+
+impl Display for CameraError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} failed with status {}", self.call, self.status)
+    }
+}
+
+impl Error for CameraError {}
+
+fn camera_check(call: &'static str, status: i32) -> Result<(), CameraError> {
+    if status == ffi::ACAMERA_OK {
+        Ok(())
+    } else {
+        Err(CameraError { call, status })
+    }
+}
+
+//
+
+pub struct PassthroughCamera {
+    pub config: PassthroughCameraConfig,
+    pub quad: TexturedQuad,
+
+    app: AndroidApp,
+    image_loader: ExternalImageLoader,
+    permission_requested: bool,
+    /// keeps the buffer that the texture is sampling from out of the camera's hands.
+    /// Field order matters: the frame goes back to the reader before the stream closes it.
+    current_frame: Option<CameraFrame>,
+    stream: Option<CameraStream>,
+}
+
+impl PassthroughCamera {
+    /// Nothing is opened until [PassthroughCamera::update] finds that the permissions were granted.
+    pub fn new(
+        app: &AndroidApp,
+        config: PassthroughCameraConfig,
+        egl_display: *mut c_void,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let image_loader = ExternalImageLoader::new(egl_display)?;
+
+        let texture = Texture::new()?;
+        {
+            let bound = texture.bound(TEXTURE_EXTERNAL_OES, gpu_state)?;
+            bound.set_parameter(gl::TEXTURE_MIN_FILTER, gl::LINEAR)?;
+            bound.set_parameter(gl::TEXTURE_MAG_FILTER, gl::LINEAR)?;
+            bound.set_parameter(gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE)?;
+            bound.set_parameter(gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE)?;
+        }
+        let quad = TexturedQuad::new(
+            gpu_state,
+            0.5,
+            0.5,
+            TextureWithTarget::new(texture, TEXTURE_EXTERNAL_OES),
+        )?;
+
+        Ok(Self {
+            config,
+            quad,
+            app: app.clone(),
+            image_loader,
+            permission_requested: false,
+            current_frame: None,
+            stream: None,
+        })
+    }
+
+    /// call once per frame.  Requests permissions, opens the camera once they are granted,
+    /// and latches the newest camera image into the texture.
+    pub fn update(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.stream.is_none() {
+            let mut granted = true;
+            for permission in CAMERA_PERMISSIONS {
+                granted &= has_permission(&self.app, permission)?;
+            }
+            if !granted {
+                if !self.permission_requested {
+                    log::debug!("requesting camera permissions");
+                    request_permissions(&self.app, &CAMERA_PERMISSIONS, PERMISSION_REQUEST_CODE)?;
+                    self.permission_requested = true;
+                }
+                return Ok(());
+            }
+
+            self.stream = Some(CameraStream::open(&self.config)?);
+        }
+
+        if let Some(stream) = &self.stream {
+            if let Some(image) = stream.acquire_latest_image()? {
+                let egl_image = unsafe {
+                    self.image_loader
+                        .import_hardware_buffer(image.hardware_buffer()?)?
+                };
+                self.image_loader
+                    .attach(&self.quad.texture.texture, &egl_image)?;
+                self.current_frame = Some(CameraFrame {
+                    _egl_image: egl_image,
+                    _image: image,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// true once at least one camera image has arrived
+    pub fn has_frame(&self) -> bool {
+        self.current_frame.is_some()
+    }
+
+    /// For [CameraPreviewPlacement::Background].  Call right after clearing the view.
+    pub fn draw_background(&self, gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper> {
+        if !self.has_frame() {
+            return Ok(());
+        }
+        unsafe { gl::Disable(gl::DEPTH_TEST) };
+        explode_if_gl_error()?;
+
+        // the quad is 1x1, clip space is 2x2
+        multiview::in_screen_space(|| {
+            self.quad
+                .paint_quad(&xr_matrix4x4f_create_scale(2.0, 2.0, 1.0), gpu_state)
+        })
+    }
+
+    /// For [CameraPreviewPlacement::Quad]
+    pub fn draw_quad(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        model: &XrMatrix4x4f,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        if !self.has_frame() {
+            return Ok(());
+        }
+        self.quad.paint_quad(&(*matrix_pv * *model), gpu_state)
+    }
+}
+
+//
+
+/// an image we acquired from the reader, returned to it on drop
+struct AcquiredImage(*mut ffi::AImage);
+
+impl AcquiredImage {
+    fn hardware_buffer(&self) -> Result<*const c_void, CameraError> {
+        let mut buffer = null_mut();
+        let status = unsafe { ffi::AImage_getHardwareBuffer(self.0, &mut buffer) };
+        if status != ffi::AMEDIA_OK {
+            return Err(CameraError {
+                call: "AImage_getHardwareBuffer",
+                status,
+            });
+        }
+        Ok(buffer as *const c_void)
+    }
+}
+
+impl Drop for AcquiredImage {
+    fn drop(&mut self) {
+        unsafe { ffi::AImage_delete(self.0) };
+    }
+}
+
+/// field order matters: the EGLImage must go before the buffer is handed back to the camera
+struct CameraFrame {
+    _egl_image: EglImage,
+    _image: AcquiredImage,
+}
+
+//
+
+/// camera device → capture session → image reader, torn down in reverse
+struct CameraStream {
+    manager: *mut ffi::ACameraManager,
+    device: *mut ffi::ACameraDevice,
+    reader: *mut ffi::AImageReader,
+    output: *mut ffi::ACaptureSessionOutput,
+    outputs: *mut ffi::ACaptureSessionOutputContainer,
+    target: *mut ffi::ACameraOutputTarget,
+    request: *mut ffi::ACaptureRequest,
+    session: *mut ffi::ACameraCaptureSession,
+    // the camera service holds on to these
+    device_callbacks: Box<ffi::ACameraDevice_StateCallbacks>,
+    session_callbacks: Box<ffi::ACameraCaptureSession_stateCallbacks>,
+}
+
+impl CameraStream {
+    fn open(config: &PassthroughCameraConfig) -> Result<Self, CameraError> {
+        // each step stores into `rval` as it goes, so Drop cleans up after a failure part way through
+        let mut rval = Self {
+            manager: unsafe { ffi::ACameraManager_create() },
+            device: null_mut(),
+            reader: null_mut(),
+            output: null_mut(),
+            outputs: null_mut(),
+            target: null_mut(),
+            request: null_mut(),
+            session: null_mut(),
+            device_callbacks: Box::new(ffi::ACameraDevice_StateCallbacks {
+                context: null_mut(),
+                on_disconnected: device_disconnected,
+                on_error: device_error,
+            }),
+            session_callbacks: Box::new(ffi::ACameraCaptureSession_stateCallbacks {
+                context: null_mut(),
+                on_closed: session_state_ignored,
+                on_ready: session_state_ignored,
+                on_active: session_state_ignored,
+            }),
+        };
+
+        let camera_id = match &config.camera_id {
+            Some(id) => CString::new(id.as_str()).map_err(|_| CameraError {
+                call: "ACameraManager_openCamera (camera id contains a NUL)",
+                status: 0,
+            })?,
+            None => rval.first_camera_id()?,
+        };
+        log::debug!("opening camera {:?}", camera_id);
+
+        unsafe {
+            camera_check(
+                "ACameraManager_openCamera",
+                ffi::ACameraManager_openCamera(
+                    rval.manager,
+                    camera_id.as_ptr(),
+                    &mut *rval.device_callbacks,
+                    &mut rval.device,
+                ),
+            )?;
+
+            let status = ffi::AImageReader_newWithUsage(
+                config.width,
+                config.height,
+                ffi::AIMAGE_FORMAT_PRIVATE,
+                ffi::AHARDWAREBUFFER_USAGE_GPU_SAMPLED_IMAGE,
+                // one being sampled, one being filled, one spare
+                3,
+                &mut rval.reader,
+            );
+            if status != ffi::AMEDIA_OK {
+                return Err(CameraError {
+                    call: "AImageReader_newWithUsage",
+                    status,
+                });
+            }
+            let mut window = null_mut();
+            let status = ffi::AImageReader_getWindow(rval.reader, &mut window);
+            if status != ffi::AMEDIA_OK {
+                return Err(CameraError {
+                    call: "AImageReader_getWindow",
+                    status,
+                });
+            }
+
+            camera_check(
+                "ACaptureSessionOutputContainer_create",
+                ffi::ACaptureSessionOutputContainer_create(&mut rval.outputs),
+            )?;
+            camera_check(
+                "ACaptureSessionOutput_create",
+                ffi::ACaptureSessionOutput_create(window, &mut rval.output),
+            )?;
+            camera_check(
+                "ACaptureSessionOutputContainer_add",
+                ffi::ACaptureSessionOutputContainer_add(rval.outputs, rval.output),
+            )?;
+
+            camera_check(
+                "ACameraDevice_createCaptureRequest",
+                ffi::ACameraDevice_createCaptureRequest(
+                    rval.device,
+                    ffi::TEMPLATE_PREVIEW,
+                    &mut rval.request,
+                ),
+            )?;
+            camera_check(
+                "ACameraOutputTarget_create",
+                ffi::ACameraOutputTarget_create(window, &mut rval.target),
+            )?;
+            camera_check(
+                "ACaptureRequest_addTarget",
+                ffi::ACaptureRequest_addTarget(rval.request, rval.target),
+            )?;
+
+            camera_check(
+                "ACameraDevice_createCaptureSession",
+                ffi::ACameraDevice_createCaptureSession(
+                    rval.device,
+                    rval.outputs,
+                    &*rval.session_callbacks,
+                    &mut rval.session,
+                ),
+            )?;
+            camera_check(
+                "ACameraCaptureSession_setRepeatingRequest",
+                ffi::ACameraCaptureSession_setRepeatingRequest(
+                    rval.session,
+                    null_mut(),
+                    1,
+                    &mut rval.request,
+                    null_mut(),
+                ),
+            )?;
+        }
+
+        Ok(rval)
+    }
+
+    fn first_camera_id(&self) -> Result<CString, CameraError> {
+        let mut list: *mut ffi::ACameraIdList = null_mut();
+        unsafe {
+            camera_check(
+                "ACameraManager_getCameraIdList",
+                ffi::ACameraManager_getCameraIdList(self.manager, &mut list),
+            )?;
+            let ids = std::slice::from_raw_parts((*list).camera_ids, (*list).num_cameras as usize);
+            for id in ids {
+                log::debug!("found camera {:?}", CStr::from_ptr(*id));
+            }
+            let rval = ids.first().map(|id| CStr::from_ptr(*id).to_owned());
+            ffi::ACameraManager_deleteCameraIdList(list);
+            rval.ok_or(CameraError {
+                call: "ACameraManager_getCameraIdList (no cameras)",
+                status: 0,
+            })
+        }
+    }
+
+    /// `None` if the camera has not delivered anything new since the last call
+    fn acquire_latest_image(&self) -> Result<Option<AcquiredImage>, CameraError> {
+        let mut image = null_mut();
+        let status = unsafe { ffi::AImageReader_acquireLatestImage(self.reader, &mut image) };
+        match status {
+            ffi::AMEDIA_OK => Ok(Some(AcquiredImage(image))),
+            ffi::AMEDIA_IMGREADER_NO_BUFFER_AVAILABLE => Ok(None),
+            _ => Err(CameraError {
+                call: "AImageReader_acquireLatestImage",
+                status,
+            }),
+        }
+    }
+}
+
+impl Drop for CameraStream {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.session.is_null() {
+                ffi::ACameraCaptureSession_close(self.session);
+            }
+            if !self.request.is_null() {
+                ffi::ACaptureRequest_free(self.request);
+            }
+            if !self.target.is_null() {
+                ffi::ACameraOutputTarget_free(self.target);
+            }
+            if !self.outputs.is_null() {
+                ffi::ACaptureSessionOutputContainer_free(self.outputs);
+            }
+            if !self.output.is_null() {
+                ffi::ACaptureSessionOutput_free(self.output);
+            }
+            if !self.device.is_null() {
+                ffi::ACameraDevice_close(self.device);
+            }
+            if !self.reader.is_null() {
+                ffi::AImageReader_delete(self.reader);
+            }
+            ffi::ACameraManager_delete(self.manager);
+        }
+    }
+}
+
+unsafe extern "C" fn device_disconnected(_context: *mut c_void, _device: *mut ffi::ACameraDevice) {
+    log::warn!("camera disconnected");
+}
+
+unsafe extern "C" fn device_error(
+    _context: *mut c_void,
+    _device: *mut ffi::ACameraDevice,
+    error: c_int,
+) {
+    log::error!("camera device error {}", error);
+}
+
+unsafe extern "C" fn session_state_ignored(
+    _context: *mut c_void,
+    _session: *mut ffi::ACameraCaptureSession,
+) {
+}
+
+/// The subset of the NDK camera and media APIs we need (NdkCameraManager.h, NdkImageReader.h)
+#[allow(non_camel_case_types)]
+mod ffi {
+    use std::ffi::{c_char, c_int, c_void};
+
+    pub const ACAMERA_OK: i32 = 0;
+    pub const AMEDIA_OK: i32 = 0;
+    pub const AMEDIA_IMGREADER_NO_BUFFER_AVAILABLE: i32 = -30001;
+    pub const TEMPLATE_PREVIEW: c_int = 1;
+    pub const AIMAGE_FORMAT_PRIVATE: i32 = 0x22;
+    pub const AHARDWAREBUFFER_USAGE_GPU_SAMPLED_IMAGE: u64 = 1 << 8;
+
+    pub enum ACameraManager {}
+    pub enum ACameraDevice {}
+    pub enum ACaptureRequest {}
+    pub enum ACameraOutputTarget {}
+    pub enum ACaptureSessionOutput {}
+    pub enum ACaptureSessionOutputContainer {}
+    pub enum ACameraCaptureSession {}
+    pub enum AImageReader {}
+    pub enum AImage {}
+    pub enum AHardwareBuffer {}
+    pub enum ANativeWindow {}
+
+    #[repr(C)]
+    pub struct ACameraIdList {
+        pub num_cameras: c_int,
+        pub camera_ids: *const *const c_char,
+    }
+
+    #[repr(C)]
+    pub struct ACameraDevice_StateCallbacks {
+        pub context: *mut c_void,
+        pub on_disconnected: unsafe extern "C" fn(*mut c_void, *mut ACameraDevice),
+        pub on_error: unsafe extern "C" fn(*mut c_void, *mut ACameraDevice, c_int),
+    }
+
+    #[repr(C)]
+    pub struct ACameraCaptureSession_stateCallbacks {
+        pub context: *mut c_void,
+        pub on_closed: unsafe extern "C" fn(*mut c_void, *mut ACameraCaptureSession),
+        pub on_ready: unsafe extern "C" fn(*mut c_void, *mut ACameraCaptureSession),
+        pub on_active: unsafe extern "C" fn(*mut c_void, *mut ACameraCaptureSession),
+    }
+
+    #[link(name = "camera2ndk")]
+    extern "C" {
+        pub fn ACameraManager_create() -> *mut ACameraManager;
+        pub fn ACameraManager_delete(manager: *mut ACameraManager);
+        pub fn ACameraManager_getCameraIdList(
+            manager: *mut ACameraManager,
+            list: *mut *mut ACameraIdList,
+        ) -> i32;
+        pub fn ACameraManager_deleteCameraIdList(list: *mut ACameraIdList);
+        pub fn ACameraManager_openCamera(
+            manager: *mut ACameraManager,
+            camera_id: *const c_char,
+            callbacks: *mut ACameraDevice_StateCallbacks,
+            device: *mut *mut ACameraDevice,
+        ) -> i32;
+        pub fn ACameraDevice_close(device: *mut ACameraDevice) -> i32;
+        pub fn ACameraDevice_createCaptureRequest(
+            device: *const ACameraDevice,
+            template: c_int,
+            request: *mut *mut ACaptureRequest,
+        ) -> i32;
+        pub fn ACameraDevice_createCaptureSession(
+            device: *mut ACameraDevice,
+            outputs: *const ACaptureSessionOutputContainer,
+            callbacks: *const ACameraCaptureSession_stateCallbacks,
+            session: *mut *mut ACameraCaptureSession,
+        ) -> i32;
+        pub fn ACaptureSessionOutputContainer_create(
+            container: *mut *mut ACaptureSessionOutputContainer,
+        ) -> i32;
+        pub fn ACaptureSessionOutputContainer_free(container: *mut ACaptureSessionOutputContainer);
+        pub fn ACaptureSessionOutputContainer_add(
+            container: *mut ACaptureSessionOutputContainer,
+            output: *const ACaptureSessionOutput,
+        ) -> i32;
+        pub fn ACaptureSessionOutput_create(
+            window: *mut ANativeWindow,
+            output: *mut *mut ACaptureSessionOutput,
+        ) -> i32;
+        pub fn ACaptureSessionOutput_free(output: *mut ACaptureSessionOutput);
+        pub fn ACameraOutputTarget_create(
+            window: *mut ANativeWindow,
+            target: *mut *mut ACameraOutputTarget,
+        ) -> i32;
+        pub fn ACameraOutputTarget_free(target: *mut ACameraOutputTarget);
+        pub fn ACaptureRequest_addTarget(
+            request: *mut ACaptureRequest,
+            target: *const ACameraOutputTarget,
+        ) -> i32;
+        pub fn ACaptureRequest_free(request: *mut ACaptureRequest);
+        pub fn ACameraCaptureSession_setRepeatingRequest(
+            session: *mut ACameraCaptureSession,
+            callbacks: *mut c_void,
+            num_requests: c_int,
+            requests: *mut *mut ACaptureRequest,
+            sequence_id: *mut c_int,
+        ) -> i32;
+        pub fn ACameraCaptureSession_close(session: *mut ACameraCaptureSession);
+    }
+
+    #[link(name = "mediandk")]
+    extern "C" {
+        pub fn AImageReader_newWithUsage(
+            width: i32,
+            height: i32,
+            format: i32,
+            usage: u64,
+            max_images: i32,
+            reader: *mut *mut AImageReader,
+        ) -> i32;
+        pub fn AImageReader_delete(reader: *mut AImageReader);
+        pub fn AImageReader_getWindow(
+            reader: *mut AImageReader,
+            window: *mut *mut ANativeWindow,
+        ) -> i32;
+        pub fn AImageReader_acquireLatestImage(
+            reader: *mut AImageReader,
+            image: *mut *mut AImage,
+        ) -> i32;
+        pub fn AImage_getHardwareBuffer(
+            image: *const AImage,
+            buffer: *mut *mut AHardwareBuffer,
+        ) -> i32;
+        pub fn AImage_delete(image: *mut AImage);
+    }
+}
@@ -0,0 +1,242 @@
+//! A rectangular window in the world that shows the scene as seen from somewhere else.
+//!
+//! Each eye's view through the portal is rendered into a [RenderTarget] from a virtual camera
+//! that stands in the same place relative to the exit as the eye does relative to the entrance.
+//! The near plane of that camera is tilted onto the exit (an "oblique" projection) so whatever is
+//! between the virtual camera and the exit does not block the view.  The target's stencil
+//! buffer restricts that render to the pixels the entrance covers, since nothing else of it
+//! is ever seen.
+
+use crate::lod::LodView;
+use crate::passthrough_camera::PassthroughCamera;
+use crate::pose_stream::{Pose, RemoteAvatar};
+use crate::scene::{inverse_view_matrix, projection_matrix, MyScene};
+use bob_shaders::mask_shader::MaskShader;
+use bob_shaders::screen_space_texture_shader::ScreenSpaceTextureShader;
+use gl::types::{GLfloat, GLsizei};
+use gl_thin::gl_fancy::{ActiveTextureUnit, GPUState, StencilState, VertexBufferBundle};
+use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper};
+use gl_thin::linear::{
+    xr_matrix4x4f_invert_rigid_body, XrFovf, XrMatrix4x4f, XrQuaternionf, XrVector3f,
+};
+use gl_thin::render_target::RenderTarget;
+use openxr::SpaceLocation;
+use openxr_sys::Time;
+
+pub struct Portal {
+    /// The quad is centered here, and you look into it from its +Z side
+    pub entrance: Pose,
+    /// The virtual camera looks out of this pose's -Z side
+    pub exit: Pose,
+    pub width: f32,
+    pub height: f32,
+    /// what the current eye sees through the portal.  Same size as the eye's swapchain image.
+    pub target: RenderTarget,
+    shader: ScreenSpaceTextureShader,
+    buffers: VertexBufferBundle<'static, GLfloat, u8>,
+    /// the entrance quad again, for [MaskShader]
+    mask_shader: MaskShader,
+    mask_buffers: VertexBufferBundle<'static, GLfloat, u8>,
+}
+
+impl Portal {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        entrance: Pose,
+        exit: Pose,
+        width: f32,
+        height: f32,
+        target_width: i32,
+        target_height: i32,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let target = RenderTarget::with_stencil(target_width, target_height, gpu_state)?;
+        let shader = ScreenSpaceTextureShader::new()?;
+        let mask_shader = MaskShader::new()?;
+
+        let (dx, dy) = (width * 0.5, height * 0.5);
+        let quad = vec![
+            -dx, -dy, //
+            dx, -dy, //
+            -dx, dy, //
+            dx, dy,
+        ];
+        static INDICES: [u8; 4] = [0, 1, 2, 3];
+        let buffers = VertexBufferBundle::<'static, GLfloat, u8>::new(
+            gpu_state,
+            quad.clone().into(),
+            (&INDICES).into(),
+            2,
+            &[(shader.sal_position, 2, 0)],
+        )?;
+        let mask_buffers = VertexBufferBundle::<'static, GLfloat, u8>::new(
+            gpu_state,
+            quad.into(),
+            (&INDICES).into(),
+            2,
+            &[(mask_shader.sal_position, 2, 0)],
+        )?;
+
+        Ok(Self {
+            entrance,
+            exit,
+            width,
+            height,
+            target,
+            shader,
+            buffers,
+            mask_shader,
+            mask_buffers,
+        })
+    }
+
+    /// carries a pose from the entrance side of the portal to the exit side
+    pub fn entrance_to_exit(&self) -> XrMatrix4x4f {
+        self.exit.matrix() * xr_matrix4x4f_invert_rigid_body(&self.entrance.matrix())
+    }
+
+    /// The exit plane in world space as (a,b,c,d) with ax+by+cz+d >= 0 on the visible side.
+    pub fn exit_plane(&self) -> [f32; 4] {
+        let exit = self.exit.matrix();
+        // -Z axis of the exit
+        let normal = [-exit.m[8], -exit.m[9], -exit.m[10]];
+        let p = &self.exit.position;
+        let d = -(normal[0] * p.x + normal[1] * p.y + normal[2] * p.z);
+        [normal[0], normal[1], normal[2], d]
+    }
+
+    /// Render what an eye at `rotation`,`translation` sees through the portal into [Portal::target].
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_view(
+        &self,
+        scene: &MyScene,
+        fov: &XrFovf,
+        rotation: &XrQuaternionf,
+        translation: &XrVector3f,
+        time: Time,
+        gpu_state: &mut GPUState,
+        controller_1: &Option<SpaceLocation>,
+        remote_avatars: &[RemoteAvatar],
+        camera: Option<&PassthroughCamera>,
+    ) -> Result<(), GLErrorWrapper> {
+        let eye = Pose::new(*translation, *rotation).matrix();
+        let virtual_camera = self.entrance_to_exit() * eye;
+        let inverse_view = xr_matrix4x4f_invert_rigid_body(&virtual_camera);
+
+        let plane = transform_plane_to_camera(&virtual_camera, &self.exit_plane());
+        let projection = projection_matrix(fov);
+        // once the eye has walked behind the entrance the plane would clip the wrong half
+        let projection = if plane[3] < 0.0 {
+            oblique_near_plane(&projection, &plane)
+        } else {
+            projection
+        };
+
+        let m = &virtual_camera.m;
+        let lod_view = LodView::new(
+            XrVector3f::new(m[12], m[13], m[14]),
+            fov,
+            self.target.height,
+        );
+
+        self.target.bind()?;
+        let eye_pv = projection_matrix(fov) * inverse_view_matrix(rotation, translation);
+        self.mask_entrance(&eye_pv, gpu_state)?;
+        gpu_state.set_stencil(Some(StencilState::equal(1)))?;
+        let result = scene.draw_pv(
+            &(projection * inverse_view),
+            &lod_view,
+            time,
+            gpu_state,
+            controller_1,
+            remote_avatars,
+            camera,
+        );
+        gpu_state.set_stencil(None)?;
+        result
+    }
+
+    /// Set the bound target's stencil to 1 where the eye sees the entrance and 0 elsewhere,
+    /// leaving its color and depth alone.
+    fn mask_entrance(
+        &self,
+        eye_pv: &XrMatrix4x4f,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        unsafe {
+            gl::StencilMask(0xff);
+            gl::ClearStencil(0);
+            gl::Clear(gl::STENCIL_BUFFER_BIT);
+            gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+            gl::Disable(gl::DEPTH_TEST);
+            gl::DepthMask(gl::FALSE);
+        }
+        explode_if_gl_error()?;
+
+        gpu_state.set_stencil(Some(StencilState::write(1)))?;
+        let result = self.mask_shader.draw(
+            &(*eye_pv * self.entrance.matrix()),
+            gl::TRIANGLE_STRIP,
+            &self.mask_buffers,
+            self.mask_buffers.index_count as GLsizei,
+            gpu_state,
+        );
+
+        unsafe {
+            gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+            gl::DepthMask(gl::TRUE);
+        }
+        result?;
+        explode_if_gl_error()
+    }
+
+    /// paint the entrance quad with the contents of [Portal::target].
+    /// `matrix_pv` must be the same view the target was rendered for.
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.shader.draw(
+            &(*matrix_pv * self.entrance.matrix()),
+            &self.target.color,
+            ActiveTextureUnit(0),
+            gl::TRIANGLE_STRIP,
+            &self.buffers,
+            self.buffers.index_count as GLsizei,
+            gpu_state,
+        )
+    }
+}
+
+/// Planes are covectors, so going from world to camera space is multiplying by the
+/// transpose of the camera's pose matrix (the inverse-transpose of the view matrix).
+pub fn transform_plane_to_camera(camera: &XrMatrix4x4f, plane: &[f32; 4]) -> [f32; 4] {
+    let mut rval = [0.0; 4];
+    for (j, out) in rval.iter_mut().enumerate() {
+        *out = (0..4).map(|i| camera.m[4 * j + i] * plane[i]).sum();
+    }
+    rval
+}
+
+/// Replace the near plane of an OpenGL projection with `clip_plane` (in camera space,
+/// facing away from the camera), keeping the far plane as close to the original as possible.
+/// Eric Lengyel, "Oblique View Frustum Depth Projection and Clipping", 2005.
+pub fn oblique_near_plane(projection: &XrMatrix4x4f, clip_plane: &[f32; 4]) -> XrMatrix4x4f {
+    let m = &projection.m;
+    let q = [
+        (clip_plane[0].signum() + m[8]) / m[0],
+        (clip_plane[1].signum() + m[9]) / m[5],
+        -1.0,
+        (1.0 + m[10]) / m[14],
+    ];
+    let dot: f32 = clip_plane.iter().zip(&q).map(|(a, b)| a * b).sum();
+    let scale = 2.0 / dot;
+
+    let mut rval = *projection;
+    rval.m[2] = clip_plane[0] * scale;
+    rval.m[6] = clip_plane[1] * scale;
+    rval.m[10] = clip_plane[2] * scale + 1.0;
+    rval.m[14] = clip_plane[3] * scale;
+    rval
+}
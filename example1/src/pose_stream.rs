@@ -0,0 +1,404 @@
+//! Publish the local head/controller poses over UDP and collect the poses of remote peers,
+//! so several headsets can see each other in the same demo.
+//!
+//! The wire format is a fixed-size little-endian packet; there is no reliability layer
+//! because a lost pose is replaced by the next one a few milliseconds later anyway.
+
+use gl_thin::linear::{
+    xr_matrix4x4f_create_translation_rotation_scale, xr_matrix4x4f_transform_vector3f,
+    xr_quaternionf_lerp, xr_vector3f_lerp, XrMatrix4x4f, XrQuaternionf, XrVector3f,
+};
+use std::collections::{HashMap, VecDeque};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+const MAGIC: u32 = 0x424f_4250; // "BOBP"
+const PACKET_SIZE: usize = 4 + 4 + 4 + 8 + 1 + 7 * 4 + 7 * 4;
+/// how many samples we keep for each peer
+const HISTORY: usize = 16;
+/// A packet this many sequence numbers behind the newest, or this many seconds older, is
+/// from the peer starting over rather than late; far more than the network reorders.
+const RESTART_SEQUENCE_GAP: i32 = 300;
+const RESTART_REWIND: f64 = 2.0;
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Pose {
+    pub position: XrVector3f,
+    pub orientation: XrQuaternionf,
+}
+
+impl Pose {
+    pub fn new(position: XrVector3f, orientation: XrQuaternionf) -> Self {
+        Self {
+            position,
+            orientation,
+        }
+    }
+
+    pub fn matrix(&self) -> XrMatrix4x4f {
+        xr_matrix4x4f_create_translation_rotation_scale(
+            &self.position,
+            &self.orientation,
+            &XrVector3f::default_scale(),
+        )
+    }
+
+    /// `local` is relative to `self`; the result is in the same space as `self`
+    pub fn compose(&self, local: &Pose) -> Pose {
+        Pose {
+            position: xr_matrix4x4f_transform_vector3f(&self.matrix(), &local.position),
+            orientation: self.orientation * local.orientation,
+        }
+    }
+
+    pub fn lerp(&self, other: &Pose, fraction: f32) -> Pose {
+        Pose {
+            position: xr_vector3f_lerp(&self.position, &other.position, fraction),
+            orientation: xr_quaternionf_lerp(&self.orientation, &other.orientation, fraction),
+        }
+    }
+}
+
+impl From<openxr::Posef> for Pose {
+    fn from(value: openxr::Posef) -> Self {
+        Self::new(value.position.into(), value.orientation.into())
+    }
+}
+
+/// One pose update from one peer
+#[derive(Copy, Clone, Debug)]
+pub struct PosePacket {
+    pub peer_id: u32,
+    pub sequence: u32,
+    /// seconds on the sender's clock
+    pub timestamp: f64,
+    pub head: Pose,
+    pub controller: Option<Pose>,
+}
+
+impl PosePacket {
+    pub fn encode(&self) -> [u8; PACKET_SIZE] {
+        let mut rval = [0u8; PACKET_SIZE];
+        let mut cursor = 0;
+        let mut put = |bytes: &[u8]| {
+            rval[cursor..cursor + bytes.len()].copy_from_slice(bytes);
+            cursor += bytes.len();
+        };
+        put(&MAGIC.to_le_bytes());
+        put(&self.peer_id.to_le_bytes());
+        put(&self.sequence.to_le_bytes());
+        put(&self.timestamp.to_le_bytes());
+        put(&[self.controller.is_some() as u8]);
+        for pose in [Some(self.head), self.controller] {
+            let pose = pose.unwrap_or_default();
+            for v in pose_floats(&pose) {
+                put(&v.to_le_bytes());
+            }
+        }
+        rval
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != PACKET_SIZE {
+            return None;
+        }
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let f32_at = |i: usize| f32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        if u32_at(0) != MAGIC {
+            return None;
+        }
+        let peer_id = u32_at(4);
+        let sequence = u32_at(8);
+        let timestamp = f64::from_le_bytes(bytes[12..20].try_into().unwrap());
+        let has_controller = bytes[20] != 0;
+        let pose_at = |base: usize| {
+            let v: Vec<f32> = (0..7).map(|i| f32_at(base + 4 * i)).collect();
+            Pose::new(
+                XrVector3f::new(v[0], v[1], v[2]),
+                XrQuaternionf::new(v[3], v[4], v[5], v[6]),
+            )
+        };
+        let head = pose_at(21);
+        let controller = if has_controller {
+            Some(pose_at(21 + 7 * 4))
+        } else {
+            None
+        };
+        Some(Self {
+            peer_id,
+            sequence,
+            timestamp,
+            head,
+            controller,
+        })
+    }
+}
+
+fn pose_floats(pose: &Pose) -> [f32; 7] {
+    [
+        pose.position.x,
+        pose.position.y,
+        pose.position.z,
+        pose.orientation.x,
+        pose.orientation.y,
+        pose.orientation.z,
+        pose.orientation.w,
+    ]
+}
+
+//
+
+pub struct PoseStreamConfig {
+    /// something like `0.0.0.0:7777`
+    pub bind_address: SocketAddr,
+    /// where to send our poses.  A broadcast address works if the socket is allowed to broadcast.
+    pub peers: Vec<SocketAddr>,
+    pub peer_id: u32,
+    /// how many packets per second to publish
+    pub send_rate_hz: f32,
+    /// remote poses are rendered this far in the past so we usually have two samples to interpolate between
+    pub interpolation_delay: Duration,
+    /// how far past the newest sample we are willing to dead-reckon before freezing the avatar
+    pub max_extrapolation: Duration,
+    /// peers we have not heard from in this long are dropped
+    pub peer_timeout: Duration,
+}
+
+impl PoseStreamConfig {
+    pub fn new(bind_address: SocketAddr, peers: Vec<SocketAddr>, peer_id: u32) -> Self {
+        Self {
+            bind_address,
+            peers,
+            peer_id,
+            send_rate_hz: 30.0,
+            interpolation_delay: Duration::from_millis(100),
+            max_extrapolation: Duration::from_millis(250),
+            peer_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// The transforms a scene needs to draw one remote participant
+#[derive(Copy, Clone, Debug)]
+pub struct RemoteAvatar {
+    pub peer_id: u32,
+    pub head: XrMatrix4x4f,
+    pub controller: Option<XrMatrix4x4f>,
+}
+
+struct RemotePeer {
+    samples: VecDeque<PosePacket>,
+    /// estimate of (our clock - their clock); the minimum observed keeps network delay out of it
+    clock_offset: f64,
+    last_heard: Instant,
+}
+
+impl RemotePeer {
+    fn new(packet: PosePacket, now: f64) -> Self {
+        let mut rval = Self {
+            samples: VecDeque::with_capacity(HISTORY),
+            clock_offset: now - packet.timestamp,
+            last_heard: Instant::now(),
+        };
+        rval.insert(packet, now);
+        rval
+    }
+
+    fn insert(&mut self, packet: PosePacket, now: f64) {
+        if let Some(newest) = self.samples.back() {
+            // sequence numbers wrap, so compare with wrapping arithmetic
+            let step = packet.sequence.wrapping_sub(newest.sequence) as i32;
+            let restarted = step < -RESTART_SEQUENCE_GAP
+                || packet.timestamp < newest.timestamp - RESTART_REWIND;
+            if restarted {
+                // the same id from a fresh process: its sequence and clock started over
+                self.samples.clear();
+                self.clock_offset = now - packet.timestamp;
+            } else if step <= 0 {
+                return; // stale or duplicate
+            }
+        }
+        self.clock_offset = self.clock_offset.min(now - packet.timestamp);
+        self.last_heard = Instant::now();
+
+        if self.samples.len() >= HISTORY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(packet);
+    }
+
+    /// interpolate between the samples bracketing `render_time`, or dead-reckon past the newest one
+    fn sample_at(&self, render_time: f64, max_extrapolation: f64) -> Option<(Pose, Option<Pose>)> {
+        // convert from our clock to the sender's clock
+        let render_time = render_time - self.clock_offset;
+        let newest = self.samples.back()?;
+
+        if render_time >= newest.timestamp {
+            let previous = match self.samples.len() {
+                0 | 1 => return Some((newest.head, newest.controller)),
+                n => &self.samples[n - 2],
+            };
+            let dt = newest.timestamp - previous.timestamp;
+            if dt <= 0.0 {
+                return Some((newest.head, newest.controller));
+            }
+            let ahead = (render_time - newest.timestamp).min(max_extrapolation);
+            let fraction = 1.0 + (ahead / dt) as f32;
+            let head = extrapolate(&previous.head, &newest.head, fraction);
+            let controller = match (previous.controller, newest.controller) {
+                (Some(a), Some(b)) => Some(extrapolate(&a, &b, fraction)),
+                (_, b) => b,
+            };
+            return Some((head, controller));
+        }
+
+        let mut later = newest;
+        for earlier in self.samples.iter().rev().skip(1) {
+            if earlier.timestamp <= render_time {
+                let dt = later.timestamp - earlier.timestamp;
+                let fraction = if dt > 0.0 {
+                    ((render_time - earlier.timestamp) / dt) as f32
+                } else {
+                    1.0
+                };
+                let head = earlier.head.lerp(&later.head, fraction);
+                let controller = match (earlier.controller, later.controller) {
+                    (Some(a), Some(b)) => Some(a.lerp(&b, fraction)),
+                    (_, b) => b,
+                };
+                return Some((head, controller));
+            }
+            later = earlier;
+        }
+
+        // older than anything we have; show the oldest sample
+        let oldest = self.samples.front()?;
+        Some((oldest.head, oldest.controller))
+    }
+}
+
+/// linear dead reckoning of position; orientation is held at the newest sample
+fn extrapolate(previous: &Pose, newest: &Pose, fraction: f32) -> Pose {
+    Pose {
+        position: xr_vector3f_lerp(&previous.position, &newest.position, fraction),
+        orientation: newest.orientation,
+    }
+}
+
+//
+
+pub struct PoseStreamer {
+    socket: UdpSocket,
+    config: PoseStreamConfig,
+    epoch: Instant,
+    sequence: u32,
+    last_send: Option<Instant>,
+    peers: HashMap<u32, RemotePeer>,
+}
+
+impl PoseStreamer {
+    pub fn new(config: PoseStreamConfig) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(config.bind_address)?;
+        socket.set_nonblocking(true)?;
+        socket.set_broadcast(true)?;
+        Ok(Self {
+            socket,
+            config,
+            epoch: Instant::now(),
+            sequence: 0,
+            last_send: None,
+            peers: HashMap::new(),
+        })
+    }
+
+    fn now(&self) -> f64 {
+        self.epoch.elapsed().as_secs_f64()
+    }
+
+    /// Send our poses if enough time has passed since the last packet.
+    /// Call this every frame; the configured rate limits the traffic.
+    pub fn publish(&mut self, head: Pose, controller: Option<Pose>) -> std::io::Result<()> {
+        let interval = Duration::from_secs_f32(1.0 / self.config.send_rate_hz.max(0.1));
+        if let Some(last_send) = self.last_send {
+            if last_send.elapsed() < interval {
+                return Ok(());
+            }
+        }
+        self.last_send = Some(Instant::now());
+        self.sequence = self.sequence.wrapping_add(1);
+
+        let packet = PosePacket {
+            peer_id: self.config.peer_id,
+            sequence: self.sequence,
+            timestamp: self.now(),
+            head,
+            controller,
+        };
+        let bytes = packet.encode();
+        for peer in &self.config.peers {
+            match self.socket.send_to(&bytes, peer) {
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Drain every packet waiting on the socket.
+    pub fn receive(&mut self) -> std::io::Result<()> {
+        let mut buffer = [0u8; 512];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((len, _from)) => {
+                    let Some(packet) = PosePacket::decode(&buffer[..len]) else {
+                        log::trace!("ignoring malformed pose packet ({} bytes)", len);
+                        continue;
+                    };
+                    if packet.peer_id == self.config.peer_id {
+                        continue; // our own broadcast
+                    }
+                    let now = self.now();
+                    match self.peers.get_mut(&packet.peer_id) {
+                        Some(peer) => peer.insert(packet, now),
+                        None => {
+                            log::debug!("new remote peer {}", packet.peer_id);
+                            self.peers
+                                .insert(packet.peer_id, RemotePeer::new(packet, now));
+                        }
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let timeout = self.config.peer_timeout;
+        self.peers.retain(|id, peer| {
+            let alive = peer.last_heard.elapsed() < timeout;
+            if !alive {
+                log::debug!("remote peer {} timed out", id);
+            }
+            alive
+        });
+        Ok(())
+    }
+
+    /// Smoothed transforms for every peer we have heard from recently.
+    pub fn remote_avatars(&self) -> Vec<RemoteAvatar> {
+        let render_time = self.now() - self.config.interpolation_delay.as_secs_f64();
+        let max_extrapolation = self.config.max_extrapolation.as_secs_f64();
+        self.peers
+            .iter()
+            .filter_map(|(peer_id, peer)| {
+                let (head, controller) = peer.sample_at(render_time, max_extrapolation)?;
+                Some(RemoteAvatar {
+                    peer_id: *peer_id,
+                    head: head.matrix(),
+                    controller: controller.map(|c| c.matrix()),
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,319 @@
+//! Full-screen passes over each eye's image before it goes to the compositor.  While any
+//! effect is enabled, the eye is drawn into an offscreen [RenderTarget] instead of the
+//! swapchain image; [PostChain::finish] runs the enabled effects in order, each reading the
+//! previous one's output, and copies the last result into the swapchain image.
+//!
+//! With [PostChain::enable_hdr] the offscreen targets are half-float, so lighting and effects
+//! keep values past 1.0, and the last step tonemaps them into the swapchain instead of copying.
+//! With [PostChain::enable_srgb_encode] it sRGB-encodes them instead, for swapchains that
+//! can not do it themselves.
+
+use bob_shaders::srgb_encode_shader::SrgbEncodeShader;
+use bob_shaders::tonemap_shader::TonemapShader;
+use gl::types::{GLfloat, GLuint};
+use gl_thin::gl_fancy::{GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper, Texture};
+use gl_thin::linear::XrMatrix4x4f;
+use gl_thin::render_target::{ColorFormat, RenderTarget, StoreOp};
+
+/// What an effect knows about the view it is processing
+pub struct PostFrame<'a> {
+    /// which of the frame's views, 0 for the left eye
+    pub eye: usize,
+    /// counts frames, so effects with history can tell when they missed one
+    pub frame_index: u64,
+    /// what the scene was drawn with
+    pub matrix_pv: XrMatrix4x4f,
+    /// the scene's depth buffer, whatever effects ran before
+    pub depth: &'a Texture,
+}
+
+pub trait PostEffect {
+    /// what [PostChain::set_enabled] finds it by
+    fn name(&self) -> &'static str;
+
+    /// Draw `input` through the effect into `output`, which is bound with its viewport set.
+    /// Depth testing is off.
+    fn apply(
+        &mut self,
+        frame: &PostFrame,
+        input: &RenderTarget,
+        output: &RenderTarget,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper>;
+}
+
+struct PostSlot {
+    effect: Box<dyn PostEffect>,
+    enabled: bool,
+}
+
+/// the scene goes into the first; the effects ping-pong between them
+struct PostEye {
+    targets: [RenderTarget; 2],
+}
+
+/// the final pass out of half-float targets
+struct Tonemap {
+    shader: TonemapShader,
+    buffers: VertexBufferBundle<'static, GLfloat, u8>,
+}
+
+/// the final pass into an RGBA8 swapchain on devices without sRGB framebuffers
+struct SrgbEncode {
+    shader: SrgbEncodeShader,
+    buffers: VertexBufferBundle<'static, GLfloat, u8>,
+}
+
+pub struct PostChain {
+    /// scales the HDR image before tonemapping; only used after [PostChain::enable_hdr]
+    pub exposure: f32,
+    effects: Vec<PostSlot>,
+    eyes: Vec<PostEye>,
+    tonemap: Option<Tonemap>,
+    srgb_encode: Option<SrgbEncode>,
+}
+
+impl Default for PostChain {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            effects: vec![],
+            eyes: vec![],
+            tonemap: None,
+            srgb_encode: None,
+        }
+    }
+}
+
+impl PostChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draw the eyes into [ColorFormat::Rgba16F] targets and tonemap them into the swapchain
+    /// image.  Returns false, and stays with RGBA8, if the device cannot render to half-float.
+    pub fn enable_hdr(&mut self, gpu_state: &mut GPUState) -> Result<bool, GLErrorWrapper> {
+        if !ColorFormat::Rgba16F.is_renderable() {
+            return Ok(false);
+        }
+        if self.tonemap.is_none() {
+            let shader = TonemapShader::new()?;
+            let buffers = fullscreen_quad(shader.sal_position, gpu_state)?;
+            self.tonemap = Some(Tonemap { shader, buffers });
+        }
+        Ok(true)
+    }
+
+    /// Encode the result with an approximation of the sRGB curve on the way into the
+    /// swapchain image, where the swapchain stores sRGB-encoded values but the device has no
+    /// sRGB framebuffers to encode them (see [gl_thin::gl_caps]).  Also keeps the chain
+    /// active with no effects enabled.  Does nothing to a chain that [PostChain::enable_hdr]
+    /// made tonemap instead.
+    pub fn enable_srgb_encode(&mut self, gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper> {
+        if self.srgb_encode.is_none() {
+            let shader = SrgbEncodeShader::new()?;
+            let buffers = fullscreen_quad(shader.sal_position, gpu_state)?;
+            self.srgb_encode = Some(SrgbEncode { shader, buffers });
+        }
+        Ok(())
+    }
+
+    /// what the offscreen targets hold
+    pub fn format(&self) -> ColorFormat {
+        if self.tonemap.is_some() {
+            ColorFormat::Rgba16F
+        } else {
+            ColorFormat::Rgba8
+        }
+    }
+
+    /// see [PostChain::add]
+    pub fn with_effect(mut self, effect: impl PostEffect + 'static) -> Self {
+        self.add(effect);
+        self
+    }
+
+    /// run `effect` after the ones already added, enabled
+    pub fn add(&mut self, effect: impl PostEffect + 'static) {
+        self.effects.push(PostSlot {
+            effect: Box::new(effect),
+            enabled: true,
+        });
+    }
+
+    /// Turn the effect called `name` on or off.  Returns false if there is none.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        let slot = self
+            .effects
+            .iter_mut()
+            .find(|slot| slot.effect.name() == name);
+        match slot {
+            Some(slot) => {
+                slot.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// any effect is enabled, or HDR or sRGB encoding is; otherwise draw straight into the
+    /// swapchain image
+    pub fn is_active(&self) -> bool {
+        self.tonemap.is_some()
+            || self.srgb_encode.is_some()
+            || self.effects.iter().any(|slot| slot.enabled)
+    }
+
+    /// Bind `eye`'s offscreen target, `width` x `height`, for drawing the scene into
+    pub fn begin(
+        &mut self,
+        eye: usize,
+        width: i32,
+        height: i32,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let format = self.format();
+        while self.eyes.len() <= eye {
+            self.eyes
+                .push(PostEye::new(width, height, format, gpu_state)?);
+        }
+        let target = &self.eyes[eye].targets[0];
+        if target.width != width || target.height != height || target.format != format {
+            self.eyes[eye] = PostEye::new(width, height, format, gpu_state)?;
+        }
+        self.eyes[eye].targets[0].bind()
+    }
+
+    /// Run the enabled effects over what was drawn since [PostChain::begin], then call
+    /// `bind_output` to bind the swapchain image and copy (or tonemap, or encode) the result
+    /// into it.
+    pub fn finish(
+        &mut self,
+        eye: usize,
+        frame_index: u64,
+        matrix_pv: XrMatrix4x4f,
+        bind_output: impl FnOnce() -> Result<(), GLErrorWrapper>,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let Some(PostEye { targets }) = self.eyes.get(eye) else {
+            return Ok(());
+        };
+        let frame = PostFrame {
+            eye,
+            frame_index,
+            matrix_pv,
+            depth: &targets[0].depth,
+        };
+
+        let mut current = 0;
+        without_depth(|| {
+            for slot in self.effects.iter_mut().filter(|slot| slot.enabled) {
+                let (input, output) = (&targets[current], &targets[1 - current]);
+                output.bind()?;
+                slot.effect.apply(&frame, input, output, gpu_state)?;
+                current = 1 - current;
+            }
+            Ok(())
+        })?;
+
+        bind_output()?;
+        let result = match (&self.tonemap, &self.srgb_encode) {
+            // a float image can not be blitted into a fixed-point one anyway
+            (Some(tonemap), _) => without_depth(|| {
+                tonemap.shader.draw(
+                    &targets[current].color,
+                    self.exposure,
+                    &tonemap.buffers,
+                    tonemap.buffers.index_count as _,
+                    gpu_state,
+                )
+            }),
+            // and without sRGB framebuffers there is no glBlitFramebuffer either
+            (None, Some(encode)) => without_depth(|| {
+                encode.shader.draw(
+                    &targets[current].color,
+                    &encode.buffers,
+                    encode.buffers.index_count as _,
+                    gpu_state,
+                )
+            }),
+            (None, None) => blit_color(&targets[current]),
+        };
+        result?;
+        // both were only ever read within this frame's chain
+        for target in targets {
+            target.invalidate()?;
+        }
+        Ok(())
+    }
+}
+
+impl PostEye {
+    fn new(
+        width: i32,
+        height: i32,
+        format: ColorFormat,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let scratch = |gpu_state: &mut GPUState| {
+            let mut target = RenderTarget::with_format(width, height, format, gpu_state)?;
+            target.color_store = StoreOp::DontCare;
+            Ok::<_, GLErrorWrapper>(target)
+        };
+        Ok(Self {
+            targets: [scratch(gpu_state)?, scratch(gpu_state)?],
+        })
+    }
+}
+
+/// full-screen passes neither test nor write depth
+pub fn without_depth(
+    pass: impl FnOnce() -> Result<(), GLErrorWrapper>,
+) -> Result<(), GLErrorWrapper> {
+    unsafe {
+        gl::Disable(gl::DEPTH_TEST);
+        gl::DepthMask(gl::FALSE);
+    }
+    let result = pass();
+    unsafe {
+        gl::DepthMask(gl::TRUE);
+        gl::Enable(gl::DEPTH_TEST);
+    }
+    result
+}
+
+/// copy `source`'s color into the bound draw framebuffer, which must be the same size
+pub fn blit_color(source: &RenderTarget) -> Result<(), GLErrorWrapper> {
+    source.frame_buffer.bind_read()?;
+    unsafe {
+        gl::BlitFramebuffer(
+            0,
+            0,
+            source.width,
+            source.height,
+            0,
+            0,
+            source.width,
+            source.height,
+            gl::COLOR_BUFFER_BIT,
+            gl::NEAREST,
+        );
+    }
+    explode_if_gl_error()
+}
+
+/// a triangle strip covering clip space from -1 to 1, for the effects' shaders
+pub fn fullscreen_quad(
+    sal_position: GLuint,
+    gpu_state: &mut GPUState,
+) -> Result<VertexBufferBundle<'static, GLfloat, u8>, GLErrorWrapper> {
+    static INDICES: [u8; 4] = [0, 1, 2, 3];
+    VertexBufferBundle::new(
+        gpu_state,
+        vec![-1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0].into(),
+        (&INDICES).into(),
+        2,
+        &[(sal_position, 2, 0)],
+    )
+}
@@ -0,0 +1,66 @@
+//! Notices the headset coming off and going back on.  Runtimes take the session out of the
+//! FOCUSED state when nobody is wearing the headset, and when the system's own UI takes over,
+//! which calls for a pause just the same, so that is what this watches.
+//! Callbacks run on the render thread, with access to their owner (usually
+//! [crate::drawcore::ActiveRenderer]), to pause the simulation and audio and show a panel.
+
+use openxr::SessionState;
+
+pub struct PresenceMonitor<C> {
+    /// the session has been FOCUSED at least once; before that nobody has gone anywhere
+    focused_once: bool,
+    paused: bool,
+    callbacks: Vec<Box<dyn FnMut(&mut C, bool)>>,
+}
+
+impl<C> Default for PresenceMonitor<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> PresenceMonitor<C> {
+    pub fn new() -> Self {
+        Self {
+            focused_once: false,
+            paused: false,
+            callbacks: vec![],
+        }
+    }
+
+    /// Run `callback` with `true` when the user goes away and `false` when they come back
+    pub fn on_change(&mut self, callback: impl FnMut(&mut C, bool) + 'static) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Call with the session's state after polling the runtime's events.  `monitor` finds
+    /// this monitor inside `owner`, which the callbacks get mutable access to.
+    pub fn update(owner: &mut C, state: SessionState, monitor: fn(&mut C) -> &mut Self) {
+        let this = monitor(owner);
+        let present = state == SessionState::FOCUSED;
+        this.focused_once |= present;
+        let paused = !present && this.focused_once;
+        if paused == this.paused {
+            return;
+        }
+        this.paused = paused;
+        log::info!(
+            "user {} ({:?})",
+            if paused { "went away" } else { "is back" },
+            state
+        );
+
+        let mut callbacks = std::mem::take(&mut this.callbacks);
+        for callback in &mut callbacks {
+            callback(owner, paused);
+        }
+        // keep any registered while they ran
+        let this = monitor(owner);
+        callbacks.append(&mut this.callbacks);
+        this.callbacks = callbacks;
+    }
+}
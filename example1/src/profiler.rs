@@ -0,0 +1,108 @@
+//! Rolling statistics over the last few hundred samples of whatever is worth watching:
+//! how long things take, how far off a prediction was, and so on.
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SeriesStats {
+    pub count: usize,
+    pub last: f32,
+    pub mean: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+struct Series {
+    name: &'static str,
+    samples: VecDeque<f32>,
+}
+
+impl Series {
+    fn stats(&self) -> Option<SeriesStats> {
+        let last = *self.samples.back()?;
+        let count = self.samples.len();
+        let (sum, min, max) = self.samples.iter().fold(
+            (0.0, f32::INFINITY, f32::NEG_INFINITY),
+            |(sum, min, max), x| (sum + x, min.min(*x), max.max(*x)),
+        );
+        Some(SeriesStats {
+            count,
+            last,
+            mean: sum / count as f32,
+            min,
+            max,
+        })
+    }
+}
+
+pub struct Profiler {
+    /// samples kept per series
+    pub window: usize,
+    series: Vec<Series>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new(300)
+    }
+}
+
+impl Profiler {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            series: vec![],
+        }
+    }
+
+    pub fn record(&mut self, name: &'static str, value: f32) {
+        let index = match self.series.iter().position(|s| s.name == name) {
+            Some(index) => index,
+            None => {
+                self.series.push(Series {
+                    name,
+                    samples: VecDeque::with_capacity(self.window),
+                });
+                self.series.len() - 1
+            }
+        };
+        let samples = &mut self.series[index].samples;
+        while samples.len() >= self.window.max(1) {
+            samples.pop_front();
+        }
+        samples.push_back(value);
+    }
+
+    /// run `f`, recording how long it took in milliseconds
+    pub fn time<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let rval = f();
+        self.record(name, start.elapsed().as_secs_f32() * 1000.0);
+        rval
+    }
+
+    pub fn stats(&self, name: &str) -> Option<SeriesStats> {
+        self.series.iter().find(|s| s.name == name)?.stats()
+    }
+
+    /// every series, in the order they were first recorded
+    pub fn all_stats(&self) -> impl Iterator<Item = (&'static str, SeriesStats)> + '_ {
+        self.series
+            .iter()
+            .filter_map(|s| s.stats().map(|stats| (s.name, stats)))
+    }
+
+    pub fn log_report(&self) {
+        for (name, stats) in self.all_stats() {
+            log::info!(
+                "{}: mean {:.3} min {:.3} max {:.3} ({} samples)",
+                name,
+                stats.mean,
+                stats.min,
+                stats.max,
+                stats.count
+            );
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Static decoration merged with [StaticBatchBuilder] so the whole ring costs one draw
+//! call per color.
+
+use crate::gltf_export::{GeometryId, GltfDocument};
+use crate::scene::matrix_rotation_about_y;
+use bob_shaders::fog::Fog;
+use bob_shaders::lights::LightManager;
+use bob_shaders::outline_shader::{OutlineShader, OutlineStyle};
+use bob_shaders::sun_phong_shader::SunPhongShader;
+use gl::types::{GLfloat, GLsizei, GLuint, GLushort};
+use gl_thin::gl_fancy::{GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::{
+    xr_matrix4x4f_create_scale, xr_matrix4x4f_create_translation, XrMatrix4x4f, XrVector3f,
+};
+use gl_thin::raycast::{OccluderId, Raycaster};
+use gl_thin::static_batch::{StaticBatchBuilder, VertexLayout};
+use std::f32::consts::TAU;
+
+const PROP_COLORS: [[f32; 3]; 2] = [[0.9, 0.6, 0.1], [0.2, 0.7, 0.3]];
+
+pub struct StaticProps {
+    phong: SunPhongShader,
+    /// one merged mesh per entry of [PROP_COLORS]
+    batches: Vec<([f32; 3], VertexBufferBundle<'static, GLfloat, GLuint>)>,
+    /// world matrix and index into [PROP_COLORS] of each prop, kept for [StaticProps::export_gltf]
+    /// and [StaticProps::register_occluders]
+    placements: Vec<(XrMatrix4x4f, usize)>,
+    /// every prop, for [StaticProps::pick]; `picker_ids[i]` is prop `i`
+    picker: Raycaster,
+    picker_ids: Vec<OccluderId>,
+    /// see [StaticProps::set_highlighted]
+    highlighted: Vec<bool>,
+    pub outline_style: OutlineStyle,
+    outline: OutlineShader,
+    /// a single unbatched prop, for drawing outlines one at a time
+    outline_mesh: VertexBufferBundle<'static, GLfloat, GLushort>,
+}
+
+impl StaticProps {
+    /// `count` small monkey heads in a circle of `radius` around the origin at floor height
+    pub fn ring(
+        count: usize,
+        radius: f32,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let phong = SunPhongShader::new()?;
+        let layout = VertexLayout::new(6, 0, Some(3));
+
+        let mut builder = StaticBatchBuilder::new();
+        let mut placements = Vec::with_capacity(count);
+        for i in 0..count {
+            let theta = TAU * i as f32 / count as f32;
+            let world = matrix_rotation_about_y(theta)
+                * xr_matrix4x4f_create_translation(0.0, -1.4, -radius)
+                * xr_matrix4x4f_create_scale(0.1, 0.1, 0.1);
+            let material = i % PROP_COLORS.len();
+            builder.add(
+                material,
+                layout,
+                &crate::suzanne::XYZABC,
+                &crate::suzanne::TRIANGLE_INDICES,
+                &world,
+            )?;
+            placements.push((world, material));
+        }
+
+        let attributes = [(phong.sal_position, 3, 0), (phong.sal_normal, 3, 3)];
+        let batches = builder
+            .build(gpu_state)?
+            .into_iter()
+            .map(|batch| {
+                let bundle = VertexBufferBundle::from_buffers(
+                    gpu_state,
+                    &batch.buffers,
+                    batch.layout.stride as GLsizei,
+                    &attributes,
+                )?;
+                Ok((PROP_COLORS[batch.material], bundle))
+            })
+            .collect::<Result<_, GLErrorWrapper>>()?;
+
+        let mut picker = Raycaster::new();
+        let picker_ids = Self::register_meshes(&placements, &mut picker)?;
+
+        let outline = OutlineShader::new()?;
+        let outline_mesh = VertexBufferBundle::new(
+            gpu_state,
+            (&crate::suzanne::XYZABC).into(),
+            (&crate::suzanne::TRIANGLE_INDICES).into(),
+            6,
+            &[(outline.sal_position, 3, 0), (outline.sal_normal, 3, 3)],
+        )?;
+
+        Ok(Self {
+            phong,
+            batches,
+            highlighted: vec![false; placements.len()],
+            placements,
+            picker,
+            picker_ids,
+            outline_style: OutlineStyle::default(),
+            outline,
+            outline_mesh,
+        })
+    }
+
+    /// Add each prop as its own node.  The batches only live on the GPU, so this starts
+    /// over from the suzanne mesh they were built from.
+    pub fn export_gltf(&self, document: &mut GltfDocument, geometry: GeometryId) {
+        let meshes: Vec<_> = PROP_COLORS
+            .iter()
+            .map(|color| document.add_mesh("prop", geometry, *color))
+            .collect();
+        for (i, (world, material)) in self.placements.iter().enumerate() {
+            document.add_node(&format!("prop {}", i), meshes[*material], world);
+        }
+    }
+
+    /// Register each prop with `raycaster`, e.g. so they block sound.
+    pub fn register_occluders(&self, raycaster: &mut Raycaster) -> Result<(), GLErrorWrapper> {
+        Self::register_meshes(&self.placements, raycaster)?;
+        Ok(())
+    }
+
+    fn register_meshes(
+        placements: &[(XrMatrix4x4f, usize)],
+        raycaster: &mut Raycaster,
+    ) -> Result<Vec<OccluderId>, GLErrorWrapper> {
+        let layout = VertexLayout::new(6, 0, Some(3));
+        placements
+            .iter()
+            .map(|(world, _)| {
+                raycaster.add_mesh(
+                    layout,
+                    &crate::suzanne::XYZABC,
+                    &crate::suzanne::TRIANGLE_INDICES,
+                    world,
+                )
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.placements.len()
+    }
+
+    /// where each prop is, in the order [StaticProps::pick] numbers them
+    pub fn world_matrices(&self) -> impl Iterator<Item = &XrMatrix4x4f> {
+        self.placements.iter().map(|(world, _)| world)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.placements.is_empty()
+    }
+
+    /// the prop a pointer ray (origin, direction) hits first, if any
+    pub fn pick(&self, origin: &XrVector3f, direction: &XrVector3f) -> Option<usize> {
+        let hit = self.picker.cast(origin, direction, f32::INFINITY)?;
+        self.picker_ids.iter().position(|id| *id == hit.occluder)
+    }
+
+    /// Outline prop `index` with [StaticProps::outline_style] (or stop), e.g. while the
+    /// pointer is on it.
+    pub fn set_highlighted(&mut self, index: usize, highlighted: bool) {
+        if let Some(flag) = self.highlighted.get_mut(index) {
+            *flag = highlighted;
+        }
+    }
+
+    pub fn is_highlighted(&self, index: usize) -> bool {
+        self.highlighted.get(index).copied().unwrap_or(false)
+    }
+
+    pub fn clear_highlights(&mut self) {
+        self.highlighted.fill(false);
+    }
+
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        sun_direction: &[f32; 3],
+        fog: &Fog,
+        lights: &LightManager,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let identity = xr_matrix4x4f_create_scale(1.0, 1.0, 1.0);
+        for (color, buffers) in &self.batches {
+            self.phong.draw(
+                &identity,
+                matrix_pv,
+                sun_direction,
+                color,
+                fog,
+                lights,
+                buffers,
+                buffers.index_count as GLsizei,
+                gpu_state,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Outline the highlighted props.  Call after [StaticProps::draw] and before anything
+    /// transparent.
+    pub fn draw_highlights(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        for ((world, _), _) in self
+            .placements
+            .iter()
+            .zip(&self.highlighted)
+            .filter(|(_, highlighted)| **highlighted)
+        {
+            self.outline.draw(
+                world,
+                matrix_pv,
+                &self.outline_style,
+                &self.outline_mesh,
+                self.outline_mesh.index_count as GLsizei,
+                gpu_state,
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,10 @@
+use crate::lod::{simplify_by_clustering, LodGroup, LodMetric, LodView};
 use crate::text_painting;
 use bob_shaders::flat_color_shader::FlatColorShader;
+use bob_shaders::fog::Fog;
+use bob_shaders::lights::LightManager;
 use bob_shaders::masked_solid_shader::MaskedSolidShader;
+use bob_shaders::material::Material;
 use bob_shaders::sun_phong_shader::SunPhongShader;
 use bob_shaders::GeometryBuffer;
 use gl::types::{GLfloat, GLint, GLsizei, GLushort};
@@ -11,6 +15,13 @@ use std::mem::size_of;
 
 //
 
+/// x, y, then r, g, b for each corner
+pub const COLOR_TRIANGLE: [GLfloat; 3 * 5] = [
+    -0.5, -0.5, 0.0, 1.0, 0.0, //
+    0.0, 0.5, 0.0, 0.0, 1.0, //
+    0.5, -0.5, 1.0, 0.0, 0.0,
+];
+
 pub struct RainbowTriangle<'a> {
     pub program: FlatColorShader,
     pub buffers: VertexBufferBundle<'a, GLfloat, u8>,
@@ -23,12 +34,6 @@ impl RainbowTriangle<'_> {
         program.program.use_()?;
 
         let buffers = {
-            const COLOR_TRIANGLE: [GLfloat; 3 * 5] = [
-                -0.5, -0.5, 0.0, 1.0, 0.0, //
-                0.0, 0.5, 0.0, 0.0, 1.0, //
-                0.5, -0.5, 1.0, 0.0, 0.0,
-            ];
-
             static INDICES: [u8; 3] = [0, 1, 2];
             VertexBufferBundle::<'static, GLfloat, u8>::new(
                 gpu_state,
@@ -94,12 +99,14 @@ impl RainbowTriangle<'_> {
 
 pub struct Suzanne {
     phong: SunPhongShader,
-    buffers: VertexBufferBundle<'static, GLfloat, GLushort>,
+    /// the original mesh, then two coarser ones made by [simplify_by_clustering]
+    lods: LodGroup<VertexBufferBundle<'static, GLfloat, GLushort>>,
 }
 
 impl Suzanne {
     pub fn new(gpu_state: &mut GPUState) -> Result<Self, GLErrorWrapper> {
         let phong = SunPhongShader::new()?;
+        let attributes = [(phong.sal_position, 3, 0), (phong.sal_normal, 3, 3)];
 
         let indices = &crate::suzanne::TRIANGLE_INDICES;
         let buffers = VertexBufferBundle::new(
@@ -107,14 +114,36 @@ impl Suzanne {
             (&crate::suzanne::XYZABC).into(),
             (indices).into(),
             6,
-            &[(phong.sal_position, 3, 0), (phong.sal_normal, 3, 3)],
+            &attributes,
         )?;
 
-        Ok(Self { phong, buffers })
+        // the head is about 2.7 units wide
+        let mut lods = LodGroup::new(LodMetric::ScreenSpaceError { max_pixels: 1.5 })
+            .with_bounding_radius(1.5)
+            .with_level(buffers, 12.0, 0.0);
+        for (cell_size, max_distance) in [(0.08, 30.0), (0.2, 80.0)] {
+            let (vertices, indices) =
+                simplify_by_clustering(&crate::suzanne::XYZABC, 6, indices, cell_size, Some(3));
+            let buffers = VertexBufferBundle::new(
+                gpu_state,
+                vertices.into(),
+                indices.into(),
+                6,
+                &attributes,
+            )?;
+            // worst case a vertex moves to the far corner of its cell
+            lods = lods.with_level(buffers, max_distance, cell_size * 3f32.sqrt());
+        }
+
+        Ok(Self { phong, lods })
+    }
+
+    fn full_detail(&self) -> &VertexBufferBundle<'static, GLfloat, GLushort> {
+        &self.lods.levels[0].mesh
     }
 
     pub fn index_count(&self) -> GLsizei {
-        self.buffers.index_count as GLsizei
+        self.full_detail().index_count as GLsizei
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -124,6 +153,8 @@ impl Suzanne {
         pv_matrix: &XrMatrix4x4f,
         sun_direction: &[f32; 3],
         color: &[f32; 3],
+        fog: &Fog,
+        lights: &LightManager,
         n_indices: GLsizei,
         gpu_state: &mut GPUState,
     ) -> Result<(), GLErrorWrapper> {
@@ -132,16 +163,47 @@ impl Suzanne {
             pv_matrix,
             sun_direction,
             color,
+            fog,
+            lights,
             self,
             n_indices,
             gpu_state,
         )
     }
+
+    /// like [Suzanne::draw] but with the level of detail chosen for `lod_view`
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_lod(
+        &self,
+        m_matrix: &XrMatrix4x4f,
+        pv_matrix: &XrMatrix4x4f,
+        sun_direction: &[f32; 3],
+        color: &[f32; 3],
+        fog: &Fog,
+        lights: &LightManager,
+        lod_view: &LodView,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let Some(buffers) = self.lods.select_for_model(m_matrix, lod_view) else {
+            return Ok(());
+        };
+        self.phong.draw(
+            m_matrix,
+            pv_matrix,
+            sun_direction,
+            color,
+            fog,
+            lights,
+            buffers,
+            buffers.index_count as GLsizei,
+            gpu_state,
+        )
+    }
 }
 
 impl GeometryBuffer<GLfloat, GLushort> for Suzanne {
     fn activate<'a>(&'a self, gpu_state: &'a mut GPUState) -> BoundBuffers<'a, GLfloat, GLushort> {
-        self.buffers.bind(gpu_state).unwrap()
+        self.full_detail().bind(gpu_state).unwrap()
     }
 
     fn deactivate(&self, _droppable: BoundBuffers<GLfloat, GLushort>) {}
@@ -219,6 +281,7 @@ impl TextMessage {
             &self.texture,
             &[1.0, 0.5, 0.0, 1.0],
             None,
+            &Material::default(),
             gl::TRIANGLE_STRIP,
             self,
             n_indices,
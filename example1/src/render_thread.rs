@@ -0,0 +1,239 @@
+//! Runs a [Drawable] on a thread of its own, so a frame loop stuck waiting on the XR runtime
+//! or the GPU can not hold up the Android event thread until the system declares the app not
+//! responding.  The event thread still builds the drawable, since that needs the
+//! [winit::event_loop::ActiveEventLoop], then hands it over along with its EGL context and
+//! from then on only sends it [RenderMessage]s.
+
+use crate::Drawable;
+use egli::ffi::{self, EGLContext, EGLDisplay, EGLSurface, EGLint};
+use std::ptr::null_mut;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use winit::event::{KeyEvent, Touch};
+
+const EGL_DRAW: EGLint = 0x3059;
+const EGL_READ: EGLint = 0x305A;
+
+/// how long dropping a [RenderThread] waits for the thread to end, well inside the 5 seconds
+/// Android gives the event thread before it declares the app not responding
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// what the event thread passes on, see the [Drawable] method of the same name
+pub enum RenderMessage {
+    FocusChanged(bool),
+    KeyboardInput(KeyEvent),
+    Resized(u32, u32),
+    Touch(Touch),
+}
+
+/// The EGL context that was current on the thread that built a drawable.  A context can
+/// only be current on one thread at a time, so it is released there and made current again
+/// on the render thread.
+struct CurrentEgl {
+    display: EGLDisplay,
+    draw: EGLSurface,
+    read: EGLSurface,
+    context: EGLContext,
+}
+
+impl CurrentEgl {
+    /// `None` if nothing was current on this thread
+    fn release() -> Option<Self> {
+        let context = unsafe { ffi::eglGetCurrentContext() };
+        if context.is_null() {
+            return None;
+        }
+        let current = unsafe {
+            Self {
+                display: ffi::eglGetCurrentDisplay(),
+                draw: ffi::eglGetCurrentSurface(EGL_DRAW),
+                read: ffi::eglGetCurrentSurface(EGL_READ),
+                context,
+            }
+        };
+        unsafe { ffi::eglMakeCurrent(current.display, null_mut(), null_mut(), null_mut()) };
+        Some(current)
+    }
+
+    fn make_current(&self) -> bool {
+        unsafe { ffi::eglMakeCurrent(self.display, self.draw, self.read, self.context) != 0 }
+    }
+}
+
+/// a drawable and its context on their way to the render thread
+struct Handover<T>(T, Option<CurrentEgl>);
+
+// The drawable holds GL objects and raw EGL and OpenXR handles, which are only usable where
+// its context is current.  The context is released before the move and made current again
+// on the render thread, and nothing else touches the drawable after that.
+unsafe impl<T> Send for Handover<T> {}
+
+impl<T> Handover<T> {
+    // a method, so the thread's closure captures the whole (Send) Handover and not its fields
+    fn into_inner(self) -> (T, Option<CurrentEgl>) {
+        (self.0, self.1)
+    }
+}
+
+/// Asks the event thread for a fresh drawable, from whichever thread notices first: the
+/// render thread when its drawable [Drawable::wants_restart], or one of the drawable's own
+/// (e.g. a watchdog) when the render thread is stuck.  Only the first [RestartSignal::fire]
+/// counts, so a stuck thread that comes back later can not restart the drawable that
+/// replaced it.
+#[derive(Clone)]
+pub struct RestartSignal {
+    on_restart: Arc<Mutex<Option<Box<dyn FnOnce(bool) + Send>>>>,
+}
+
+impl RestartSignal {
+    fn new(on_restart: impl FnOnce(bool) + Send + 'static) -> Self {
+        Self {
+            on_restart: Arc::new(Mutex::new(Some(Box::new(on_restart)))),
+        }
+    }
+
+    /// `runtime_lost` as in [Drawable::runtime_lost]
+    pub fn fire(&self, runtime_lost: bool) {
+        let on_restart = self
+            .on_restart
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take();
+        if let Some(on_restart) = on_restart {
+            on_restart(runtime_lost);
+        }
+    }
+}
+
+/// The event thread's handle on the render thread.  Dropping it suspends the drawable and
+/// waits up to [STOP_TIMEOUT] for the thread to drop it; a thread stuck longer than that is
+/// left to finish on its own.
+pub struct RenderThread {
+    sender: Option<Sender<RenderMessage>>,
+    thread: Option<JoinHandle<()>>,
+    /// disconnects when the thread ends, panics included
+    finished: Receiver<()>,
+}
+
+impl RenderThread {
+    /// Move `drawable`, and the EGL context current on this thread, to a new thread that
+    /// draws until this is dropped.  If the drawable [Drawable::wants_restart], the thread
+    /// ends on its own and calls `on_restart` with whether the drawable's
+    /// [Drawable::runtime_lost]; the drawable can also call it sooner through the
+    /// [RestartSignal] it is given, see [Drawable::set_restart_signal].
+    pub fn spawn<T: Drawable + 'static>(
+        mut drawable: T,
+        on_restart: impl FnOnce(bool) + Send + 'static,
+    ) -> std::io::Result<Self> {
+        let restart_signal = RestartSignal::new(on_restart);
+        drawable.set_restart_signal(restart_signal.clone());
+        let handover = Handover(drawable, CurrentEgl::release());
+        let (sender, receiver) = mpsc::channel();
+        let (finished_sender, finished) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("render".to_string())
+            .spawn(move || {
+                let _finished = finished_sender;
+                let (mut drawable, egl) = handover.into_inner();
+                if let Some(egl) = &egl {
+                    if !egl.make_current() {
+                        log::error!("failed to make the EGL context current on the render thread");
+                    }
+                }
+                let restart = run(&mut drawable, &receiver);
+                let runtime_lost = drawable.runtime_lost();
+                // while the context is still current, for the GL objects' sake
+                drop(drawable);
+                if egl.is_some() {
+                    CurrentEgl::release();
+                }
+                if restart {
+                    restart_signal.fire(runtime_lost);
+                }
+            })?;
+        Ok(Self {
+            sender: Some(sender),
+            thread: Some(thread),
+            finished,
+        })
+    }
+
+    /// delivered before the next frame; dropped if the render thread has already ended
+    pub fn send(&self, message: RenderMessage) {
+        if let Some(sender) = &self.sender {
+            if sender.send(message).is_err() {
+                log::warn!("render thread is gone, dropping message");
+            }
+        }
+    }
+}
+
+impl Drop for RenderThread {
+    fn drop(&mut self) {
+        // the render thread notices the disconnect between frames
+        self.sender = None;
+        let Some(thread) = self.thread.take() else {
+            return;
+        };
+        match self.finished.recv_timeout(STOP_TIMEOUT) {
+            Err(RecvTimeoutError::Timeout) => {
+                // dropping the handle detaches it
+                log::error!(
+                    "render thread did not stop within {:?}, leaving it behind",
+                    STOP_TIMEOUT
+                );
+            }
+            _ => {
+                if thread.join().is_err() {
+                    log::error!("render thread panicked");
+                }
+            }
+        }
+    }
+}
+
+//
+
+/// Draw until the channel closes or the drawable [Drawable::wants_restart], handling
+/// messages between frames.  Only blocks while the drawable has a [Drawable::wake_at] to wait
+/// for.  Returns whether it stopped for a restart.
+fn run<T: Drawable>(drawable: &mut T, receiver: &Receiver<RenderMessage>) -> bool {
+    let mut restart = false;
+    loop {
+        let message = match drawable.wake_at() {
+            Some(wake_at) => {
+                match receiver.recv_timeout(wake_at.saturating_duration_since(Instant::now())) {
+                    Ok(message) => Some(message),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            None => match receiver.try_recv() {
+                Ok(message) => Some(message),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => break,
+            },
+        };
+        match message {
+            Some(message) => deliver(drawable, message),
+            None => drawable.handle_events_and_draw(),
+        }
+        if drawable.wants_restart() {
+            restart = true;
+            break;
+        }
+    }
+    drawable.suspend();
+    restart
+}
+
+fn deliver<T: Drawable>(drawable: &mut T, message: RenderMessage) {
+    match message {
+        RenderMessage::FocusChanged(focused) => drawable.focus_changed(focused),
+        RenderMessage::KeyboardInput(event) => drawable.keyboard_input(&event),
+        RenderMessage::Resized(width, height) => drawable.resized(width, height),
+        RenderMessage::Touch(touch) => drawable.touch(&touch),
+    }
+}
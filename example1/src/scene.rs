@@ -1,6 +1,22 @@
-use crate::rainbow_triangle::{RainbowTriangle, Suzanne, TextMessage};
+use crate::color_check::ColorCheck;
+use crate::flashlight::Flashlight;
+use crate::gltf_export::GltfDocument;
+use crate::lod::LodView;
+use crate::microphone::AudioLevels;
+use crate::motion_vectors::{MotionHistory, MotionId, MotionVectors};
+use crate::painting::Painter;
+use crate::panorama::Panorama;
+use crate::passthrough_camera::{CameraPreviewPlacement, PassthroughCamera};
+use crate::pose_stream::RemoteAvatar;
+use crate::props::StaticProps;
+use crate::rainbow_triangle::{RainbowTriangle, Suzanne, TextMessage, COLOR_TRIANGLE};
+use crate::sky::{Sky, TimeOfDay};
+use crate::teleport::Teleport;
 #[cfg(feature = "png")]
 use crate::textured_quad::TexturedQuad;
+use bob_shaders::fog::{Fog, FogMode};
+use bob_shaders::lights::LightManager;
+use gl_thin::camera_relative::{CameraRelative, WorldPosition};
 use gl_thin::gl_fancy::GPUState;
 use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper};
 use gl_thin::linear::{
@@ -9,15 +25,56 @@ use gl_thin::linear::{
     xr_matrix4x4f_create_translation_rotation_scale, xr_matrix4x4f_create_translation_v,
     xr_matrix4x4f_invert_rigid_body, GraphicsAPI, XrFovf, XrMatrix4x4f, XrQuaternionf, XrVector3f,
 };
+use gl_thin::render_queue::{RenderLayer, RenderQueue};
 use openxr::SpaceLocation;
 use openxr_sys::Time;
 use std::f32::consts::{PI, TAU};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const RAINBOW_TRIANGLE_MOTION: MotionId = MotionId(0);
+const CONTROLLER_MOTION: MotionId = MotionId(1);
+/// prop `i` is `MotionId(PROPS_MOTION + i)`
+const PROPS_MOTION: u32 = 1000;
 
 pub struct MyScene {
     pub rainbow_triangle: RainbowTriangle<'static>,
     pub suzanne: Suzanne,
     pub text_message: TextMessage,
+    pub props: StaticProps,
+    /// strokes drawn with the controller, see [crate::drawcore::ActiveRenderer::enable_painting]
+    pub painting: Option<Painter>,
+    /// the aiming arc and navmesh, see [crate::drawcore::ActiveRenderer::enable_teleport]
+    pub teleport: Option<Teleport>,
+    /// a spot light on the controller, see [crate::drawcore::ActiveRenderer::enable_flashlight]
+    pub flashlight: Option<Flashlight>,
+    pub sky: Sky,
+    /// a 360° image shown instead of the sky, see [crate::drawcore::ActiveRenderer::enable_panorama]
+    pub panorama: Option<Panorama>,
+    /// reference colors, see [crate::drawcore::ActiveRenderer::enable_color_check]
+    pub color_check: Option<ColorCheck>,
+    /// where the sun is, for the sky and every lit mesh
+    pub time_of_day: TimeOfDay,
+    pub fog: Fog,
+    /// replace [MyScene::fog]'s color with the sky's horizon so distant things melt into it
+    pub fog_matches_sky: bool,
+    /// lamps and such on top of the sun, lighting the same meshes it does
+    pub lights: LightManager,
+    /// Where the tracking space's origin is in a world too big for f32 coordinates.
+    /// Content placed with [WorldPosition]s is drawn through [MyScene::camera_relative].
+    pub world_origin: WorldPosition,
+    /// the latest microphone levels; all zero unless [crate::drawcore::ActiveRenderer::enable_microphone]
+    pub audio: AudioLevels,
+    /// Smooth the eye images over time, for scenes whose thin, high-contrast geometry shimmers.
+    /// Only with [crate::drawcore::ActiveRenderer::enable_temporal_aa].
+    pub anti_flicker: bool,
+    /// how bright the HDR image is before tonemapping.
+    /// Only with [crate::drawcore::ActiveRenderer::enable_hdr].
+    pub exposure: f32,
+    /// Draw the opaque geometry's depth first, so each pixel is only shaded once, for scenes
+    /// with expensive fragment shaders and a lot of overdraw.
+    pub depth_prepass: bool,
+    /// last frame's model matrices, for [crate::drawcore::ActiveRenderer::enable_motion_vectors]
+    pub motion: MotionHistory,
     #[cfg(feature = "png")]
     pub poster: TexturedQuad,
 }
@@ -28,83 +85,207 @@ impl MyScene {
             rainbow_triangle: RainbowTriangle::new(gpu_state)?,
             suzanne: Suzanne::new(gpu_state)?,
             text_message: TextMessage::new(gpu_state)?,
+            props: StaticProps::ring(12, 3.0, gpu_state)?,
+            painting: None,
+            teleport: None,
+            flashlight: None,
+            sky: Sky::new(gpu_state)?,
+            panorama: None,
+            color_check: None,
+            time_of_day: TimeOfDay::animated(15.0, Duration::from_secs(240)),
+            fog: Fog {
+                color: [0.65, 0.8, 0.95],
+                mode: FogMode::ExponentialSquared { density: 0.05 },
+            },
+            fog_matches_sky: true,
+            lights: LightManager::new(),
+            world_origin: WorldPosition::default(),
+            audio: AudioLevels::default(),
+            anti_flicker: false,
+            exposure: 1.0,
+            depth_prepass: false,
+            motion: MotionHistory::default(),
             #[cfg(feature = "png")]
-            poster: poster::default_poster(
-                gpu_state,
-                &poster::default_poster_png().expect("failed to parse internal PNG"),
-            )?,
+            poster: poster::default_poster(gpu_state, &poster::default_poster_png())?,
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn draw(
         &self,
         fov: &XrFovf,
         rotation: &XrQuaternionf,
         translation: &XrVector3f,
-        _time: Time,
+        time: Time,
         gpu_state: &mut GPUState,
         controller_1: &Option<SpaceLocation>,
+        remote_avatars: &[RemoteAvatar],
+        camera: Option<&PassthroughCamera>,
     ) -> Result<(), GLErrorWrapper> {
-        let (theta, rotation_matrix) = rotation_matrix_for_now();
+        let matrix_pv = projection_matrix(fov) * inverse_view_matrix(rotation, translation);
+        self.draw_pv(
+            &matrix_pv,
+            &LodView::from_current_viewport(*translation, fov),
+            time,
+            gpu_state,
+            controller_1,
+            remote_avatars,
+            camera,
+        )
+    }
 
-        unsafe {
-            let green = (theta.sin() + 1.0) * 0.5;
-            gl::ClearColor(0.0, green, 0.3, 1.0)
-        };
-        explode_if_gl_error()?;
-        unsafe { gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT) };
-        explode_if_gl_error()?;
+    /// like [MyScene::draw], but for callers that need to build their own projection
+    /// (e.g. [crate::portal::Portal]'s clipped one).
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_pv(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        lod_view: &LodView,
+        _time: Time,
+        gpu_state: &mut GPUState,
+        controller_1: &Option<SpaceLocation>,
+        remote_avatars: &[RemoteAvatar],
+        camera: Option<&PassthroughCamera>,
+    ) -> Result<(), GLErrorWrapper> {
+        let mut queue = RenderQueue::new();
+        self.queue_draws(
+            &mut queue,
+            *matrix_pv,
+            *lod_view,
+            controller_1,
+            remote_avatars,
+            camera,
+        );
+        queue.execute(gpu_state)
+    }
 
-        unsafe { gl::Enable(gl::DEPTH_TEST) };
-        explode_if_gl_error()?;
+    /// Add what [MyScene::draw_pv] draws to `queue`: the clear and the sky in
+    /// [RenderLayer::Background], the meshes in [RenderLayer::Opaque], outlines, text and a
+    /// flashlight's beam in [RenderLayer::Transparent].  Callers can add their own draws (portals, UI, ...) to the
+    /// same queue and let the layers order them.
+    pub fn queue_draws<'a>(
+        &'a self,
+        queue: &mut RenderQueue<'a>,
+        matrix_pv: XrMatrix4x4f,
+        lod_view: LodView,
+        controller_1: &'a Option<SpaceLocation>,
+        remote_avatars: &'a [RemoteAvatar],
+        camera: Option<&'a PassthroughCamera>,
+    ) {
+        let (_, rotation_matrix) = rotation_matrix_for_now();
+        let sun_direction = self.time_of_day.sun_direction();
+        let fog = self.current_fog();
 
-        if true {
+        queue.add(RenderLayer::Background, i32::MIN, |_| {
+            unsafe {
+                // transparent, so underlay layers (a panorama, passthrough) show where nothing
+                // is drawn; set here since other passes leave their own clear color behind
+                gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+                gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            }
+            explode_if_gl_error()
+        });
+        queue.add(RenderLayer::Background, 0, move |gpu_state| {
+            match &self.panorama {
+                Some(panorama) => panorama.draw(&matrix_pv, &lod_view.eye_position, gpu_state),
+                None => self.sky.draw(
+                    &matrix_pv,
+                    &lod_view.eye_position,
+                    &self.time_of_day,
+                    gpu_state,
+                ),
+            }
+        });
+        if let Some(camera) = camera {
+            if let CameraPreviewPlacement::Background = camera.config.placement {
+                queue.add(RenderLayer::Background, 1, |gpu_state| {
+                    camera.draw_background(gpu_state)
+                });
+            }
+        }
+        // everything in front of the background tests depth and blends
+        queue.add(RenderLayer::Background, i32::MAX, |_| {
             unsafe {
+                gl::Enable(gl::DEPTH_TEST);
                 gl::Enable(gl::BLEND);
                 gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
             }
-        }
+            explode_if_gl_error()
+        });
 
         //
 
-        let matrix_pv = {
-            let projection_matrix = xr_matrix4x4f_create_projection_fov(
-                GraphicsAPI::GraphicsOpenGL,
-                fov,
-                0.01,
-                10_000.0,
-            );
-            //log::debug!("matrix = {}", debug_string_matrix(&projection_matrix),);
-            let view_matrix = xr_matrix4x4f_create_translation_rotation_scale(
-                translation,
-                rotation,
-                &XrVector3f::default_scale(),
-            );
-            let inverse_view_matrix = xr_matrix4x4f_invert_rigid_body(&view_matrix);
+        queue.add(RenderLayer::Opaque, 0, move |gpu_state| {
+            let model = self.rainbow_triangle_model(&rotation_matrix);
+            self.rainbow_triangle
+                .paint_color_triangle(&(matrix_pv * model), gpu_state)
+        });
 
-            projection_matrix * inverse_view_matrix
-        };
+        queue.add(RenderLayer::Opaque, 0, move |gpu_state| {
+            self.props
+                .draw(&matrix_pv, &sun_direction, &fog, &self.lights, gpu_state)
+        });
+        // over the finished opaque world, so nothing drawn later hides the rims
+        queue.add(RenderLayer::Transparent, i32::MIN, move |gpu_state| {
+            self.props.draw_highlights(&matrix_pv, gpu_state)
+        });
 
-        {
-            let model = xr_matrix4x4f_create_translation(1.0, 0.0, -2.0);
-            let model = model * rotation_matrix;
-            self.rainbow_triangle
-                .paint_color_triangle(&(matrix_pv * model), gpu_state)?;
+        if let Some(painting) = &self.painting {
+            queue.add(RenderLayer::Opaque, 0, move |gpu_state| {
+                painting.draw(&matrix_pv, &sun_direction, &fog, &self.lights, gpu_state)
+            });
+        }
+
+        if let Some(teleport) = &self.teleport {
+            queue.add(RenderLayer::Opaque, 0, move |gpu_state| {
+                teleport.draw(&matrix_pv, gpu_state)
+            });
+        }
+
+        if let Some(flashlight) = &self.flashlight {
+            queue.add(RenderLayer::Transparent, 0, move |gpu_state| {
+                flashlight.draw(&matrix_pv, gpu_state)
+            });
+        }
+
+        if let Some(color_check) = &self.color_check {
+            queue.add(RenderLayer::Opaque, 0, move |gpu_state| {
+                color_check.draw(&matrix_pv, gpu_state)
+            });
         }
 
         if let Some(controller_1) = controller_1 {
-            let model = Self::suzanne_hand_matrix(controller_1);
-            self.suzanne.draw(
-                &model,
-                &matrix_pv,
-                &[0.0, 1.0, 0.0],
-                &[0.0, 0.0, 1.0],
-                self.suzanne.index_count(),
-                gpu_state,
-            )?;
+            queue.add(RenderLayer::Opaque, 0, move |gpu_state| {
+                let model = Self::suzanne_hand_matrix(controller_1);
+                self.suzanne.draw_lod(
+                    &model,
+                    &matrix_pv,
+                    &sun_direction,
+                    &[0.0, 0.0, 1.0],
+                    &fog,
+                    &self.lights,
+                    &lod_view,
+                    gpu_state,
+                )
+            });
+        }
+
+        for avatar in remote_avatars {
+            queue.add(RenderLayer::Opaque, 0, move |gpu_state| {
+                self.draw_remote_avatar(avatar, &matrix_pv, &lod_view, gpu_state)
+            });
+        }
+
+        if let Some(camera) = camera {
+            if let CameraPreviewPlacement::Quad(model) = &camera.config.placement {
+                queue.add(RenderLayer::Opaque, 0, move |gpu_state| {
+                    camera.draw_quad(&matrix_pv, model, gpu_state)
+                });
+            }
         }
 
-        {
+        // blended glyph quads, which would hide what is behind them from a depth pre-pass
+        queue.add(RenderLayer::Transparent, 0, move |gpu_state| {
             let model = {
                 let translate = xr_matrix4x4f_create_translation(0.0, -0.5, -3.0);
                 let s = 0.2;
@@ -116,21 +297,242 @@ impl MyScene {
             };
             let matrix = matrix_pv * model;
             self.text_message
-                .draw(&matrix, self.text_message.index_count(), gpu_state)?;
-        }
+                .draw(&matrix, self.text_message.index_count(), gpu_state)
+        });
 
         #[cfg(feature = "png")]
-        {
+        queue.add(RenderLayer::Opaque, 0, move |gpu_state| {
             use std::f32::consts::FRAC_1_SQRT_2;
             let model = matrix_rotation_about_y2(FRAC_1_SQRT_2, -FRAC_1_SQRT_2);
             let model = xr_matrix4x4f_create_translation(-2.0, 0.0, -2.0) * model;
             let matrix = matrix_pv * model;
-            self.poster.paint_quad(&matrix, gpu_state)?;
+            self.poster.paint_quad(&matrix, gpu_state)
+        });
+    }
+
+    /// Just the props and painted strokes, lit from straight above and without fog or
+    /// [MyScene::lights], for
+    /// [crate::minimap::Minimap].  Clearing is up to the caller.
+    pub fn draw_overview(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let sun_direction = [0.0, 1.0, 0.0];
+        let fog = Fog {
+            color: [0.0; 3],
+            mode: FogMode::Off,
+        };
+        let lights = LightManager::new();
+
+        unsafe { gl::Enable(gl::DEPTH_TEST) };
+        explode_if_gl_error()?;
+
+        self.props
+            .draw(matrix_pv, &sun_direction, &fog, &lights, gpu_state)?;
+        if let Some(painting) = &self.painting {
+            painting.draw(matrix_pv, &sun_direction, &fog, &lights, gpu_state)?;
         }
+        Ok(())
+    }
 
+    /// The props and painted strokes again, into [Flashlight::render_shadows]'s depth map.
+    /// They get no [MyScene::lights], so nothing samples the map being drawn.
+    pub fn draw_shadow_casters(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let sun_direction = [0.0, 1.0, 0.0];
+        let fog = Fog {
+            color: [0.0; 3],
+            mode: FogMode::Off,
+        };
+        let lights = LightManager::new();
+
+        self.props
+            .draw(matrix_pv, &sun_direction, &fog, &lights, gpu_state)?;
+        if let Some(painting) = &self.painting {
+            painting.draw(matrix_pv, &sun_direction, &fog, &lights, gpu_state)?;
+        }
         Ok(())
     }
 
+    /// The rebasing for drawing [WorldPosition]s from `lod_view`'s eye: use its
+    /// [CameraRelative::view_projection] of the view's `matrix_pv` with its
+    /// [CameraRelative::model]s.
+    pub fn camera_relative(&self, lod_view: &LodView) -> CameraRelative {
+        CameraRelative::new(self.world_origin, &lod_view.eye_position)
+    }
+
+    /// Light the meshes with the sky's colors as the sun moves.  A [MyScene::panorama] hides
+    /// the sky, so its owner sets [LightManager::ambient] from the frames instead.
+    pub fn update_ambient(&mut self) {
+        if self.panorama.is_none() {
+            self.lights.ambient = self.time_of_day.ambient();
+        }
+    }
+
+    /// [MyScene::fog], tinted to the horizon if [MyScene::fog_matches_sky]
+    pub fn current_fog(&self) -> Fog {
+        if self.fog_matches_sky {
+            Fog {
+                color: self.time_of_day.sky_parameters().horizon_color,
+                ..self.fog
+            }
+        } else {
+            self.fog
+        }
+    }
+
+    /// remote peers are drawn as a monkey head with another monkey head for their controller
+    fn draw_remote_avatar(
+        &self,
+        avatar: &RemoteAvatar,
+        matrix_pv: &XrMatrix4x4f,
+        lod_view: &LodView,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let sun_direction = self.time_of_day.sun_direction();
+        let fog = self.current_fog();
+        let model = Self::avatar_head_matrix(avatar);
+        self.suzanne.draw_lod(
+            &model,
+            matrix_pv,
+            &sun_direction,
+            &[1.0, 0.0, 1.0],
+            &fog,
+            &self.lights,
+            lod_view,
+            gpu_state,
+        )?;
+
+        if let Some(model) = Self::avatar_controller_matrix(avatar) {
+            self.suzanne.draw_lod(
+                &model,
+                matrix_pv,
+                &sun_direction,
+                &[1.0, 0.5, 1.0],
+                &fog,
+                &self.lights,
+                lod_view,
+                gpu_state,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn avatar_head_matrix(avatar: &RemoteAvatar) -> XrMatrix4x4f {
+        let facing = matrix_rotation_about_y(PI);
+        avatar.head * facing * xr_matrix4x4f_create_scale(0.12, 0.12, 0.12)
+    }
+
+    fn avatar_controller_matrix(avatar: &RemoteAvatar) -> Option<XrMatrix4x4f> {
+        let upright = matrix_rotation_about_x(PI);
+        avatar
+            .controller
+            .map(|controller| controller * upright * xr_matrix4x4f_create_scale(0.05, 0.05, 0.05))
+    }
+
+    /// Everything [MyScene::draw_pv] draws with a mesh we have the vertices for: the props,
+    /// the painted strokes, the monkey head on the controller and the remote avatars.
+    /// Colors match the draw calls.
+    pub fn export_gltf(
+        &self,
+        controller_1: &Option<SpaceLocation>,
+        remote_avatars: &[RemoteAvatar],
+    ) -> GltfDocument {
+        let mut document = GltfDocument::new();
+        let suzanne = document.add_geometry(
+            &crate::suzanne::XYZABC,
+            6,
+            0,
+            Some(3),
+            crate::suzanne::TRIANGLE_INDICES.iter().map(|i| *i as u32),
+        );
+
+        self.props.export_gltf(&mut document, suzanne);
+        if let Some(painting) = &self.painting {
+            painting.export_gltf(&mut document);
+        }
+
+        if let Some(controller_1) = controller_1 {
+            let mesh = document.add_mesh("controller", suzanne, [0.0, 0.0, 1.0]);
+            document.add_node(
+                "controller 1",
+                mesh,
+                &Self::suzanne_hand_matrix(controller_1),
+            );
+        }
+
+        if !remote_avatars.is_empty() {
+            let head = document.add_mesh("avatar head", suzanne, [1.0, 0.0, 1.0]);
+            let controller = document.add_mesh("avatar controller", suzanne, [1.0, 0.5, 1.0]);
+            for (i, avatar) in remote_avatars.iter().enumerate() {
+                document.add_node(
+                    &format!("avatar {} head", i),
+                    head,
+                    &Self::avatar_head_matrix(avatar),
+                );
+                if let Some(model) = Self::avatar_controller_matrix(avatar) {
+                    document.add_node(&format!("avatar {} controller", i), controller, &model);
+                }
+            }
+        }
+
+        document
+    }
+
+    /// spinning in place, and pulsing with the loudness of the room
+    fn rainbow_triangle_model(&self, rotation_matrix: &XrMatrix4x4f) -> XrMatrix4x4f {
+        let model = xr_matrix4x4f_create_translation(1.0, 0.0, -2.0);
+        let pulse = 1.0 + 2.0 * self.audio.rms;
+        model * *rotation_matrix * xr_matrix4x4f_create_scale(pulse, pulse, pulse)
+    }
+
+    /// Give `motion` the meshes of everything [MyScene::motion_models] reports
+    pub fn register_motion(
+        &self,
+        motion: &mut MotionVectors,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let triangle = motion.add_mesh(&COLOR_TRIANGLE, 5, 2, vec![0, 1, 2], gpu_state)?;
+        motion.assign(RAINBOW_TRIANGLE_MOTION, triangle);
+        let suzanne = motion.add_mesh(
+            &crate::suzanne::XYZABC,
+            6,
+            3,
+            crate::suzanne::TRIANGLE_INDICES.to_vec(),
+            gpu_state,
+        )?;
+        motion.assign(CONTROLLER_MOTION, suzanne);
+        for i in 0..self.props.len() {
+            motion.assign(MotionId(PROPS_MOTION + i as u32), suzanne);
+        }
+        Ok(())
+    }
+
+    /// The model matrix of each object the motion vectors cover, as of now, in place of what
+    /// `models` held.  The props do not move, but the camera does.
+    pub fn motion_models(
+        &self,
+        controller_1: &Option<SpaceLocation>,
+        models: &mut Vec<(MotionId, XrMatrix4x4f)>,
+    ) {
+        let (_, rotation_matrix) = rotation_matrix_for_now();
+        models.clear();
+        models.push((
+            RAINBOW_TRIANGLE_MOTION,
+            self.rainbow_triangle_model(&rotation_matrix),
+        ));
+        if let Some(controller_1) = controller_1 {
+            models.push((CONTROLLER_MOTION, Self::suzanne_hand_matrix(controller_1)));
+        }
+        for (i, world) in self.props.world_matrices().enumerate() {
+            models.push((MotionId(PROPS_MOTION + i as u32), *world));
+        }
+    }
+
     /// matrix to attach the monkey head to the controller
     fn suzanne_hand_matrix(controller_1: &SpaceLocation) -> XrMatrix4x4f {
         let translate = xr_matrix4x4f_create_translation_v(&controller_1.pose.position.into());
@@ -148,16 +550,28 @@ impl MyScene {
 
 #[cfg(feature = "png")]
 mod poster {
+    use crate::fallback_assets::{self, POSTER_NAME};
     use crate::textured_quad::TexturedQuad;
     use gl::types::GLint;
+    use gl_thin::fallback::WHITE_PIXEL;
     use gl_thin::gl_fancy::GPUState;
     use gl_thin::gl_helper::{GLErrorWrapper, Texture, TextureWithTarget};
-    use png::{ColorType, OutputInfo};
+    use png::{BitDepth, ColorType, OutputInfo};
 
-    pub fn default_poster_png() -> Result<DecodedPNG, png::DecodingError> {
-        let raw = include_bytes!("sohma_g_dawling_poster.png");
+    /// [fallback_assets::poster_png], or a white pixel if that will not decode
+    pub fn default_poster_png() -> DecodedPNG {
+        decode(&fallback_assets::poster_png()).unwrap_or_else(|e| {
+            log::error!(
+                "failed to decode {}, using a white pixel {}",
+                POSTER_NAME,
+                e
+            );
+            DecodedPNG::white_pixel()
+        })
+    }
 
-        let decoder = png::Decoder::new(raw.as_slice());
+    fn decode(raw: &[u8]) -> Result<DecodedPNG, png::DecodingError> {
+        let decoder = png::Decoder::new(raw);
         let mut reader = decoder.read_info()?;
         let mut buf = vec![0u8; reader.output_buffer_size()];
         let info = reader.next_frame(&mut buf)?;
@@ -170,6 +584,19 @@ mod poster {
     }
 
     impl DecodedPNG {
+        pub fn white_pixel() -> Self {
+            Self {
+                buf: WHITE_PIXEL.to_vec(),
+                info: OutputInfo {
+                    width: 1,
+                    height: 1,
+                    color_type: ColorType::Rgba,
+                    bit_depth: BitDepth::Eight,
+                    line_size: WHITE_PIXEL.len(),
+                },
+            }
+        }
+
         pub fn bytes(&self) -> &[u8] {
             &self.buf[..self.info.buffer_size()]
         }
@@ -213,6 +640,24 @@ mod poster {
     }
 }
 
+/// the near and far planes of [projection_matrix]
+pub const NEAR_Z: f32 = 0.01;
+pub const FAR_Z: f32 = 10_000.0;
+
+pub fn projection_matrix(fov: &XrFovf) -> XrMatrix4x4f {
+    xr_matrix4x4f_create_projection_fov(GraphicsAPI::GraphicsOpenGL, fov, NEAR_Z, FAR_Z)
+}
+
+/// world space to eye space
+pub fn inverse_view_matrix(rotation: &XrQuaternionf, translation: &XrVector3f) -> XrMatrix4x4f {
+    let view_matrix = xr_matrix4x4f_create_translation_rotation_scale(
+        translation,
+        rotation,
+        &XrVector3f::default_scale(),
+    );
+    xr_matrix4x4f_invert_rigid_body(&view_matrix)
+}
+
 fn rotation_matrix_for_now() -> (f32, XrMatrix4x4f) {
     let theta = if let Ok(duration) = SystemTime::now().duration_since(UNIX_EPOCH) {
         let tm = duration.as_millis();
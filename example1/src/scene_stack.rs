@@ -0,0 +1,110 @@
+//! The scenes example1 can show, by name, so one binary can run every demo.  Each is a
+//! [MyScene] set up its own way.  [ActiveRenderer::load_scene](crate::drawcore::ActiveRenderer::load_scene)
+//! builds one and pushes it; [SceneStack::pop] goes back to the one shown before.
+
+use crate::color_check::ColorCheck;
+use crate::painting::Painter;
+use crate::scene::MyScene;
+use crate::sky::TimeOfDay;
+use bob_shaders::fog::FogMode;
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::GLErrorWrapper;
+
+/// the scene [SceneStack::builtin] registers first, [MyScene::new] as it is
+pub const DEFAULT_SCENE: &str = "default";
+
+pub type SceneBuilder = fn(&mut GPUState) -> Result<MyScene, GLErrorWrapper>;
+
+pub struct SceneEntry {
+    pub name: String,
+    pub build: SceneBuilder,
+}
+
+#[derive(Default)]
+pub struct SceneStack {
+    entries: Vec<SceneEntry>,
+    /// indices into [SceneStack::entries], the last one showing
+    history: Vec<usize>,
+}
+
+impl SceneStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the demos that need nothing from outside the APK
+    pub fn builtin() -> Self {
+        let mut stack = Self::new();
+        stack.register(DEFAULT_SCENE, MyScene::new);
+        stack.register("dusk", |gpu_state| {
+            let mut scene = MyScene::new(gpu_state)?;
+            scene.time_of_day = TimeOfDay::fixed(19.5);
+            Ok(scene)
+        });
+        stack.register("fog", |gpu_state| {
+            let mut scene = MyScene::new(gpu_state)?;
+            scene.fog.mode = FogMode::ExponentialSquared { density: 0.35 };
+            Ok(scene)
+        });
+        stack.register("painting", |gpu_state| {
+            let mut scene = MyScene::new(gpu_state)?;
+            scene.painting = Some(Painter::new()?);
+            Ok(scene)
+        });
+        stack.register("color check", |gpu_state| {
+            let mut scene = MyScene::new(gpu_state)?;
+            scene.color_check = Some(ColorCheck::new(gpu_state)?);
+            Ok(scene)
+        });
+        stack
+    }
+
+    /// Add a scene; a name already taken is replaced.  Returns its index.
+    pub fn register(&mut self, name: impl Into<String>, build: SceneBuilder) -> usize {
+        let name = name.into();
+        match self.find(&name) {
+            Some(index) => {
+                self.entries[index].build = build;
+                index
+            }
+            None => {
+                self.entries.push(SceneEntry { name, build });
+                self.entries.len() - 1
+            }
+        }
+    }
+
+    /// in the order they were registered
+    pub fn entries(&self) -> &[SceneEntry] {
+        &self.entries
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.name == name)
+    }
+
+    pub fn build(&self, index: usize, gpu_state: &mut GPUState) -> Result<MyScene, GLErrorWrapper> {
+        (self.entries[index].build)(gpu_state)
+    }
+
+    /// the scene showing, if any was pushed
+    pub fn current(&self) -> Option<usize> {
+        self.history.last().copied()
+    }
+
+    /// record that scene `index` is showing
+    pub fn push(&mut self, index: usize) {
+        if self.current() != Some(index) {
+            self.history.push(index);
+        }
+    }
+
+    /// Forget the scene showing; returns the one to show again, if there was one before.
+    pub fn pop(&mut self) -> Option<usize> {
+        if self.history.len() < 2 {
+            return None;
+        }
+        self.history.pop();
+        self.current()
+    }
+}
@@ -0,0 +1,67 @@
+//! What the user chose, kept across launches in a small `key = value` text file in the app's
+//! internal storage.  Unknown keys are kept as they are, so older and newer builds can share
+//! a file.
+
+use android_activity::AndroidApp;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub struct UserSettings {
+    path: PathBuf,
+    values: BTreeMap<String, String>,
+}
+
+impl UserSettings {
+    /// `settings.txt` under the app's internal storage, which survives updates but not an
+    /// uninstall
+    pub fn load_for(app: &AndroidApp) -> io::Result<Self> {
+        let path = app
+            .internal_data_path()
+            .ok_or_else(|| io::Error::other("no internal data path"))?
+            .join("settings.txt");
+        Self::load(path)
+    }
+
+    /// Read `path`; a missing file is the same as an empty one.
+    pub fn load(path: PathBuf) -> io::Result<Self> {
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let values = text
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .collect();
+        Ok(Self { path, values })
+    }
+
+    /// Write everything back, through a temporary file so a crash can not leave half of it.
+    pub fn save(&self) -> io::Result<()> {
+        let mut text = String::new();
+        for (key, value) in &self.values {
+            text.push_str(&format!("{} = {}\n", key, value));
+        }
+        let part = self.path.with_extension("part");
+        std::fs::write(&part, text)?;
+        std::fs::rename(&part, &self.path)
+    }
+
+    /// `None` if it is missing or does not parse as a `T`
+    pub fn get<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.values.get(key)?.parse().ok()
+    }
+
+    pub fn set(&mut self, key: &str, value: impl Display) {
+        self.values.insert(key.to_string(), value.to_string());
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.values.remove(key);
+    }
+}
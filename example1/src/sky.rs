@@ -0,0 +1,381 @@
+//! A procedural sky around the viewer and the sun that lights both it and the lit meshes.
+//! While the sun holds still the sky can go into a cube composition layer
+//! ([Sky::use_cube_layer]), which the compositor samples at display resolution instead of
+//! it being drawn into every eye buffer.
+
+use bob_shaders::sky_shader::{SkyParameters, SkyShader};
+use bob_shaders::spherical_harmonics::ShIrradiance;
+use gl::types::{GLfloat, GLsizei, GLushort};
+use gl_thin::composition_layers::{
+    CompositionLayer, LayerId, LayerPlacement, LayerShape, LayerSwapchain,
+};
+use gl_thin::gl_fancy::{GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::{explode_if_gl_error, FrameBuffer, GLErrorWrapper};
+use gl_thin::linear::{
+    xr_matrix4x4f_create_projection, xr_matrix4x4f_create_scale,
+    xr_matrix4x4f_create_translation_v, GraphicsAPI, XrMatrix4x4f, XrVector3f,
+};
+use gl_thin::openxr_helpers::{Backend, OpenXRComponent};
+use std::error::Error;
+use std::f32::consts::TAU;
+use std::time::{Duration, Instant};
+
+/// far enough that walking around (or looking into a mirror) barely shifts it,
+/// well inside the far clip plane
+pub(crate) const SKY_RADIUS: f32 = 500.0;
+
+/// Where the sun is.  The hour either stays put or runs on a clock with
+/// [TimeOfDay::day_length] per 24 hours.
+#[derive(Clone, Debug)]
+pub struct TimeOfDay {
+    /// 0..24 at the moment this was created
+    pub start_hour: f32,
+    /// `None` freezes the sun at [TimeOfDay::start_hour]
+    pub day_length: Option<Duration>,
+    /// how far the sun's path tips away from straight overhead toward +Z, radians
+    pub tilt: f32,
+    epoch: Instant,
+    /// see [TimeOfDay::set_paused]
+    paused_at: Option<Instant>,
+}
+
+impl TimeOfDay {
+    pub fn fixed(hour: f32) -> Self {
+        Self {
+            start_hour: hour,
+            day_length: None,
+            tilt: 0.4,
+            epoch: Instant::now(),
+            paused_at: None,
+        }
+    }
+
+    /// starts at `hour` and goes through a whole day every `day_length`
+    pub fn animated(hour: f32, day_length: Duration) -> Self {
+        Self {
+            day_length: Some(day_length),
+            ..Self::fixed(hour)
+        }
+    }
+
+    /// Stop the clock, e.g. while nobody is wearing the headset; it picks up where it left off.
+    pub fn set_paused(&mut self, paused: bool) {
+        match (paused, self.paused_at) {
+            (true, None) => self.paused_at = Some(Instant::now()),
+            (false, Some(paused_at)) => {
+                self.epoch += paused_at.elapsed();
+                self.paused_at = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// 0..24
+    pub fn hour(&self) -> f32 {
+        let now = self.paused_at.unwrap_or_else(Instant::now);
+        let elapsed = match self.day_length {
+            Some(day_length) => {
+                24.0 * now.duration_since(self.epoch).as_secs_f32() / day_length.as_secs_f32()
+            }
+            None => 0.0,
+        };
+        (self.start_hour + elapsed).rem_euclid(24.0)
+    }
+
+    /// Unit vector toward the sun.  It rises in +X at 6, peaks at 12 and sets in -X at 18.
+    pub fn sun_direction(&self) -> [f32; 3] {
+        let angle = TAU * (self.hour() - 6.0) / 24.0;
+        let (sin, cos) = angle.sin_cos();
+        [cos, sin * self.tilt.cos(), sin * self.tilt.sin()]
+    }
+
+    /// blends day, sunset and night palettes by how high the sun is
+    pub fn sky_parameters(&self) -> SkyParameters {
+        let sun_direction = self.sun_direction();
+        let elevation = sun_direction[1];
+
+        let day = SkyPalette {
+            zenith: [0.15, 0.35, 0.8],
+            horizon: [0.65, 0.8, 0.95],
+            ground: [0.3, 0.28, 0.25],
+            sun: [1.0, 0.95, 0.85],
+        };
+        let sunset = SkyPalette {
+            zenith: [0.2, 0.25, 0.5],
+            horizon: [0.95, 0.5, 0.25],
+            ground: [0.2, 0.15, 0.12],
+            sun: [1.0, 0.6, 0.3],
+        };
+        let night = SkyPalette {
+            zenith: [0.01, 0.01, 0.04],
+            horizon: [0.05, 0.06, 0.12],
+            ground: [0.02, 0.02, 0.02],
+            sun: [0.0, 0.0, 0.0],
+        };
+
+        let palette = if elevation > 0.0 {
+            sunset.mix(&day, smoothstep(0.0, 0.3, elevation))
+        } else {
+            sunset.mix(&night, smoothstep(0.0, 0.2, -elevation))
+        };
+
+        SkyParameters {
+            sun_direction,
+            zenith_color: palette.zenith,
+            horizon_color: palette.horizon,
+            ground_color: palette.ground,
+            sun_color: palette.sun,
+            sun_radius: 0.03,
+        }
+    }
+
+    /// the sky's light on everything, for [bob_shaders::lights::LightManager::ambient]
+    pub fn ambient(&self) -> ShIrradiance {
+        let parameters = self.sky_parameters();
+        ShIrradiance::from_directions(|direction| parameters.sky_radiance(direction))
+    }
+}
+
+impl Default for TimeOfDay {
+    /// mid-afternoon
+    fn default() -> Self {
+        Self::fixed(15.0)
+    }
+}
+
+struct SkyPalette {
+    zenith: [f32; 3],
+    horizon: [f32; 3],
+    ground: [f32; 3],
+    sun: [f32; 3],
+}
+
+impl SkyPalette {
+    fn mix(&self, other: &SkyPalette, t: f32) -> SkyPalette {
+        let lerp = |a: [f32; 3], b: [f32; 3]| {
+            [
+                a[0] + (b[0] - a[0]) * t,
+                a[1] + (b[1] - a[1]) * t,
+                a[2] + (b[2] - a[2]) * t,
+            ]
+        };
+        SkyPalette {
+            zenith: lerp(self.zenith, other.zenith),
+            horizon: lerp(self.horizon, other.horizon),
+            ground: lerp(self.ground, other.ground),
+            sun: lerp(self.sun, other.sun),
+        }
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+//
+
+/// [SkyShader] on a cube centered on the eye, or in a cube layer
+pub struct Sky {
+    shader: SkyShader,
+    buffers: VertexBufferBundle<'static, GLfloat, GLushort>,
+    layer: Option<SkyLayer>,
+}
+
+struct SkyLayer {
+    id: LayerId,
+    /// for drawing into the faces
+    frame_buffer: FrameBuffer,
+}
+
+impl Sky {
+    pub fn new(gpu_state: &mut GPUState) -> Result<Self, GLErrorWrapper> {
+        let shader = SkyShader::new()?;
+
+        let buffers = eye_cube(shader.sal_position, gpu_state)?;
+
+        Ok(Self {
+            shader,
+            buffers,
+            layer: None,
+        })
+    }
+
+    /// Paint the sky into a `size` pixel cube layer behind the eye buffers and stop drawing
+    /// it into them.  False, and the sky stays in the eye buffers, if the runtime lacks
+    /// XR_KHR_composition_layer_cube.  The layer holds one moment, so
+    /// [Sky::repaint_cube_layer] it when `time_of_day` moves.
+    pub fn use_cube_layer(
+        &mut self,
+        openxr: &mut OpenXRComponent<Backend>,
+        size: u32,
+        time_of_day: &TimeOfDay,
+        gpu_state: &mut GPUState,
+    ) -> Result<bool, Box<dyn Error>> {
+        if self.layer.is_some() {
+            return Ok(true);
+        }
+        if !openxr.layers.supports(&LayerShape::Cube) {
+            log::info!("no cube layer support, drawing the sky in the eye buffers");
+            return Ok(false);
+        }
+        let swapchain = LayerSwapchain::cube(&openxr.xr_session, openxr.swapchain_format, size)?;
+        let id = openxr.layers.add(
+            CompositionLayer::new(LayerShape::Cube, swapchain)
+                .with_placement(LayerPlacement::Underlay),
+        )?;
+        self.layer = Some(SkyLayer {
+            id,
+            frame_buffer: FrameBuffer::new()?,
+        });
+        self.repaint_cube_layer(openxr, time_of_day, gpu_state)?;
+        Ok(true)
+    }
+
+    pub fn uses_cube_layer(&self) -> bool {
+        self.layer.is_some()
+    }
+
+    /// Draw the six faces of the cube layer again, e.g. after the sun moved.  Does nothing
+    /// without one.
+    pub fn repaint_cube_layer(
+        &self,
+        openxr: &mut OpenXRComponent<Backend>,
+        time_of_day: &TimeOfDay,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), Box<dyn Error>> {
+        let Some(layer) = &self.layer else {
+            return Ok(());
+        };
+        let swapchain = &mut openxr
+            .layers
+            .get_mut(layer.id)
+            .ok_or("the sky's layer was removed")?
+            .swapchain;
+        // a 90° square frustum per face; the faces are no farther than SKY_RADIUS along it
+        let projection = xr_matrix4x4f_create_projection(
+            GraphicsAPI::GraphicsOpenGL,
+            -1.0,
+            1.0,
+            1.0,
+            -1.0,
+            1.0,
+            2.0 * SKY_RADIUS,
+        );
+        let origin = XrVector3f::new(0.0, 0.0, 0.0);
+        swapchain.paint_cube_gl(&layer.frame_buffer, |face| {
+            let matrix_pv = projection * cube_face_view(face);
+            self.draw_cube(&matrix_pv, &origin, time_of_day, gpu_state)
+        })??;
+        Ok(())
+    }
+
+    /// back to drawing the sky in the eye buffers
+    pub fn remove_cube_layer(&mut self, openxr: &mut OpenXRComponent<Backend>) {
+        if let Some(layer) = self.layer.take() {
+            openxr.layers.remove(layer.id);
+        }
+    }
+
+    /// Paint the whole background.  This ignores and leaves alone the depth buffer,
+    /// so do it right after clearing.  With a cube layer this draws nothing: the eye
+    /// buffers are cleared to transparent, which lets the layer show.
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        eye_position: &XrVector3f,
+        time_of_day: &TimeOfDay,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        if self.layer.is_some() {
+            return Ok(());
+        }
+        self.draw_cube(matrix_pv, eye_position, time_of_day, gpu_state)
+    }
+
+    fn draw_cube(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        eye_position: &XrVector3f,
+        time_of_day: &TimeOfDay,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let model = xr_matrix4x4f_create_translation_v(eye_position)
+            * xr_matrix4x4f_create_scale(SKY_RADIUS, SKY_RADIUS, SKY_RADIUS);
+
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::DepthMask(gl::FALSE);
+        }
+        explode_if_gl_error()?;
+
+        let result = self.shader.draw(
+            &(*matrix_pv * model),
+            &time_of_day.sky_parameters(),
+            &self.buffers,
+            self.buffers.index_count as GLsizei,
+            gpu_state,
+        );
+
+        unsafe { gl::DepthMask(gl::TRUE) };
+        result?;
+        explode_if_gl_error()
+    }
+}
+
+/// The view rotation that looks out through cube map face `face`, in the GL order
+/// (+X, -X, +Y, -Y, +Z, -Z) and with the GL convention's upside-down "up" for the side faces
+#[rustfmt::skip]
+fn cube_face_view(face: u32) -> XrMatrix4x4f {
+    // the rows of the matrix: right, up and backward in world coordinates
+    let [r, u, b]: [[f32; 3]; 3] = match face {
+        0 => [[0.0, 0.0, -1.0], [0.0, -1.0, 0.0], [-1.0, 0.0, 0.0]],
+        1 => [[0.0, 0.0, 1.0], [0.0, -1.0, 0.0], [1.0, 0.0, 0.0]],
+        2 => [[1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, -1.0, 0.0]],
+        3 => [[1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]],
+        4 => [[1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, -1.0]],
+        _ => [[-1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, 1.0]],
+    };
+    // column-major
+    [
+        r[0], u[0], b[0], 0.0,
+        r[1], u[1], b[1], 0.0,
+        r[2], u[2], b[2], 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    ].into()
+}
+
+/// A unit cube for shaders that color by direction from the eye, like [SkyShader].
+/// Drawn from inside, so scale it up and center it on the eye.
+pub(crate) fn eye_cube(
+    sal_position: u32,
+    gpu_state: &mut GPUState,
+) -> Result<VertexBufferBundle<'static, GLfloat, GLushort>, GLErrorWrapper> {
+    #[rustfmt::skip]
+    let xyz: Vec<GLfloat> = vec![
+        -1.0, -1.0, -1.0,
+        1.0, -1.0, -1.0,
+        -1.0, 1.0, -1.0,
+        1.0, 1.0, -1.0,
+        -1.0, -1.0, 1.0,
+        1.0, -1.0, 1.0,
+        -1.0, 1.0, 1.0,
+        1.0, 1.0, 1.0,
+    ];
+    #[rustfmt::skip]
+    let indices: Vec<GLushort> = vec![
+        0, 1, 2, 2, 1, 3, // -Z
+        4, 6, 5, 5, 6, 7, // +Z
+        0, 2, 4, 4, 2, 6, // -X
+        1, 5, 3, 3, 5, 7, // +X
+        0, 4, 1, 1, 4, 5, // -Y
+        2, 3, 6, 6, 3, 7, // +Y
+    ];
+    VertexBufferBundle::new(
+        gpu_state,
+        xyz.into(),
+        indices.into(),
+        3,
+        &[(sal_position, 3, 0)],
+    )
+}
@@ -0,0 +1,326 @@
+//! Positional sound through AAudio.  Each source is panned and attenuated by where it is
+//! relative to the head, and muffled (quieter, with the highs filtered off) while the
+//! [Raycaster] finds registered geometry between it and the head.  Mixing happens on the
+//! render thread: every frame tops the output stream up a little ahead of playback.
+//! Ambisonic soundscapes ([AmbisonicBed]) are mixed into the same stream.
+
+use crate::aaudio::{audio_check, ffi, AudioError};
+use crate::ambisonics::{AmbisonicBed, AmbisonicClip};
+use crate::pose_stream::Pose;
+use gl_thin::linear::{
+    xr_matrix4x4f_invert_rigid_body, xr_matrix4x4f_transform_vector3f, XrVector3f,
+};
+use gl_thin::raycast::Raycaster;
+use std::f32::consts::{FRAC_PI_4, TAU};
+use std::ptr::null_mut;
+use std::sync::Arc;
+
+/// what sources' samples are expected to be recorded at
+pub const SAMPLE_RATE: i32 = 48000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SourceId(u64);
+
+/// How a sound changes when something is between it and the listener
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OcclusionSettings {
+    /// volume multiplier while occluded
+    pub gain: f32,
+    /// the low-pass filter's cutoff while occluded
+    pub cutoff_hz: f32,
+    /// roughly how long, in seconds, a sound takes to become muffled or clear again
+    pub fade: f32,
+}
+
+impl Default for OcclusionSettings {
+    fn default() -> Self {
+        Self {
+            gain: 0.35,
+            cutoff_hz: 800.0,
+            fade: 0.1,
+        }
+    }
+}
+
+pub struct SoundSource {
+    pub position: XrVector3f,
+    pub gain: f32,
+    /// start over at the end instead of stopping
+    pub looping: bool,
+    /// mono, at [SAMPLE_RATE]
+    samples: Arc<[f32]>,
+    cursor: usize,
+    occluded: bool,
+    /// these approach their occluded or clear values over [OcclusionSettings::fade]
+    occlusion_gain: f32,
+    filter_coefficient: f32,
+    filter_state: f32,
+}
+
+impl SoundSource {
+    pub fn is_occluded(&self) -> bool {
+        self.occluded
+    }
+
+    fn finished(&self) -> bool {
+        !self.looping && self.cursor >= self.samples.len()
+    }
+
+    fn next_sample(&mut self) -> f32 {
+        if self.cursor >= self.samples.len() {
+            if !self.looping || self.samples.is_empty() {
+                return 0.0;
+            }
+            self.cursor = 0;
+        }
+        let sample = self.samples[self.cursor];
+        self.cursor += 1;
+        sample
+    }
+}
+
+pub struct SpatialAudio {
+    /// geometry that blocks sound; see [crate::props::StaticProps::register_occluders]
+    pub occluders: Raycaster,
+    pub occlusion: OcclusionSettings,
+    /// how far ahead of playback to keep the stream filled, in seconds.  Has to cover the
+    /// longest gap between frames, or the output stutters.
+    pub lead: f32,
+    /// Play silence and hold every sound where it is, e.g. while nobody is wearing the headset
+    pub paused: bool,
+    sources: Vec<(SourceId, SoundSource)>,
+    beds: Vec<(SourceId, AmbisonicBed)>,
+    next_id: u64,
+    stream: OutputStream,
+    /// interleaved stereo
+    mix: Vec<f32>,
+}
+
+impl SpatialAudio {
+    pub fn new() -> Result<Self, AudioError> {
+        Ok(Self {
+            occluders: Raycaster::new(),
+            occlusion: OcclusionSettings::default(),
+            lead: 0.05,
+            paused: false,
+            sources: vec![],
+            beds: vec![],
+            next_id: 0,
+            stream: OutputStream::open(2)?,
+            mix: vec![],
+        })
+    }
+
+    /// Start playing mono `samples` (at [SAMPLE_RATE]) from `position`.
+    pub fn play(&mut self, samples: Arc<[f32]>, position: XrVector3f, looping: bool) -> SourceId {
+        let id = SourceId(self.next_id);
+        self.next_id += 1;
+        self.sources.push((
+            id,
+            SoundSource {
+                position,
+                gain: 1.0,
+                looping,
+                samples,
+                cursor: 0,
+                occluded: false,
+                occlusion_gain: 1.0,
+                filter_coefficient: 1.0,
+                filter_state: 0.0,
+            },
+        ));
+        id
+    }
+
+    /// Start playing a 360° soundscape.  It is anchored to the world, so it turns against
+    /// the listener's head; it has no position.
+    pub fn play_ambisonic(&mut self, clip: AmbisonicClip, looping: bool) -> SourceId {
+        let id = SourceId(self.next_id);
+        self.next_id += 1;
+        self.beds.push((id, AmbisonicBed::new(clip, looping)));
+        id
+    }
+
+    /// None once the sound has finished or was stopped
+    pub fn source_mut(&mut self, id: SourceId) -> Option<&mut SoundSource> {
+        self.sources
+            .iter_mut()
+            .find(|(i, _)| *i == id)
+            .map(|(_, source)| source)
+    }
+
+    /// None once the soundscape has finished or was stopped
+    pub fn ambisonic_mut(&mut self, id: SourceId) -> Option<&mut AmbisonicBed> {
+        self.beds
+            .iter_mut()
+            .find(|(i, _)| *i == id)
+            .map(|(_, bed)| bed)
+    }
+
+    /// stops a positional sound or a soundscape
+    pub fn stop(&mut self, id: SourceId) {
+        self.sources.retain(|(i, _)| *i != id);
+        self.beds.retain(|(i, _)| *i != id);
+    }
+
+    /// Once per frame, with where the listener's head is.  Tests each source for occlusion
+    /// and mixes enough audio to stay [SpatialAudio::lead] seconds ahead.  The head's
+    /// orientation also rotates the ambisonic soundscapes.
+    pub fn update(&mut self, head: &Pose) -> Result<(), AudioError> {
+        for (_, source) in &mut self.sources {
+            source.occluded = self.occluders.is_blocked(&head.position, &source.position);
+        }
+
+        let frames = self
+            .stream
+            .frames_wanted((self.lead * SAMPLE_RATE as f32) as i64)?;
+        if frames == 0 {
+            return Ok(());
+        }
+        if self.paused {
+            // keep the stream fed, so it does not underrun into a glitch on resume
+            self.mix.clear();
+            self.mix.resize(frames * 2, 0.0);
+            return self.stream.write(&self.mix);
+        }
+
+        let rate = SAMPLE_RATE as f32;
+        // how far toward their targets the occlusion parameters get during this block
+        let fade = 1.0 - (-(frames as f32) / (self.occlusion.fade.max(1e-3) * rate)).exp();
+        let muffled = 1.0 - (-TAU * self.occlusion.cutoff_hz / rate).exp();
+        let to_head = xr_matrix4x4f_invert_rigid_body(&head.matrix());
+
+        self.mix.clear();
+        self.mix.resize(frames * 2, 0.0);
+        for (_, source) in &mut self.sources {
+            let local = xr_matrix4x4f_transform_vector3f(&to_head, &source.position);
+            let distance = (local.x * local.x + local.y * local.y + local.z * local.z).sqrt();
+            // -1 is hard left, 1 hard right; equal-power panning between them
+            let pan = if distance > 1e-4 {
+                local.x / distance
+            } else {
+                0.0
+            };
+            let angle = (pan + 1.0) * FRAC_PI_4;
+            let attenuation = source.gain / distance.max(1.0);
+            let (left, right) = (angle.cos() * attenuation, angle.sin() * attenuation);
+
+            let (target_gain, target_coefficient) = if source.occluded {
+                (self.occlusion.gain, muffled)
+            } else {
+                (1.0, 1.0)
+            };
+            let (gain0, coefficient0) = (source.occlusion_gain, source.filter_coefficient);
+            let gain1 = gain0 + (target_gain - gain0) * fade;
+            let coefficient1 = coefficient0 + (target_coefficient - coefficient0) * fade;
+
+            for (i, out) in self.mix.chunks_exact_mut(2).enumerate() {
+                let k = i as f32 / frames as f32;
+                let gain = gain0 + (gain1 - gain0) * k;
+                let coefficient = coefficient0 + (coefficient1 - coefficient0) * k;
+                // one-pole low-pass; a coefficient of 1 passes everything
+                let sample = source.next_sample();
+                source.filter_state += coefficient * (sample - source.filter_state);
+                let value = source.filter_state * gain;
+                out[0] += value * left;
+                out[1] += value * right;
+            }
+            source.occlusion_gain = gain1;
+            source.filter_coefficient = coefficient1;
+        }
+        self.sources.retain(|(_, source)| !source.finished());
+
+        for (_, bed) in &mut self.beds {
+            bed.mix(&mut self.mix, &head.orientation);
+        }
+        self.beds.retain(|(_, bed)| !bed.finished());
+
+        for sample in &mut self.mix {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+        self.stream.write(&self.mix)
+    }
+}
+
+//
+
+/// a float AAudio output stream, written without blocking
+struct OutputStream {
+    stream: *mut ffi::AAudioStream,
+    channels: usize,
+}
+
+impl OutputStream {
+    fn open(channels: usize) -> Result<Self, AudioError> {
+        let mut builder = null_mut();
+        audio_check("AAudio_createStreamBuilder", unsafe {
+            ffi::AAudio_createStreamBuilder(&mut builder)
+        })?;
+
+        let mut stream = null_mut();
+        let status = unsafe {
+            ffi::AAudioStreamBuilder_setDirection(builder, ffi::AAUDIO_DIRECTION_OUTPUT);
+            ffi::AAudioStreamBuilder_setFormat(builder, ffi::AAUDIO_FORMAT_PCM_FLOAT);
+            ffi::AAudioStreamBuilder_setChannelCount(builder, channels as i32);
+            ffi::AAudioStreamBuilder_setSampleRate(builder, SAMPLE_RATE);
+            ffi::AAudioStreamBuilder_setPerformanceMode(
+                builder,
+                ffi::AAUDIO_PERFORMANCE_MODE_LOW_LATENCY,
+            );
+            let status = ffi::AAudioStreamBuilder_openStream(builder, &mut stream);
+            ffi::AAudioStreamBuilder_delete(builder);
+            status
+        };
+        audio_check("AAudioStreamBuilder_openStream", status)?;
+        let rval = Self { stream, channels };
+
+        audio_check("AAudioStream_requestStart", unsafe {
+            ffi::AAudioStream_requestStart(rval.stream)
+        })?;
+        Ok(rval)
+    }
+
+    /// how many frames to write to have `lead` frames queued, limited by the stream's buffer
+    fn frames_wanted(&self, lead: i64) -> Result<usize, AudioError> {
+        let (written, read, capacity) = unsafe {
+            (
+                ffi::AAudioStream_getFramesWritten(self.stream),
+                ffi::AAudioStream_getFramesRead(self.stream),
+                ffi::AAudioStream_getBufferSizeInFrames(self.stream),
+            )
+        };
+        if capacity < 0 {
+            return Err(AudioError {
+                call: "AAudioStream_getBufferSizeInFrames",
+                status: capacity,
+            });
+        }
+        let queued = written - read;
+        let target = lead.min(capacity as i64);
+        Ok((target - queued).max(0) as usize)
+    }
+
+    /// interleaved samples; whatever does not fit is dropped
+    fn write(&self, samples: &[f32]) -> Result<(), AudioError> {
+        let frames = samples.len() / self.channels;
+        let n = unsafe {
+            ffi::AAudioStream_write(self.stream, samples.as_ptr().cast(), frames as i32, 0)
+        };
+        if n < 0 {
+            return Err(AudioError {
+                call: "AAudioStream_write",
+                status: n,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Drop for OutputStream {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::AAudioStream_requestStop(self.stream);
+            ffi::AAudioStream_close(self.stream);
+        }
+    }
+}
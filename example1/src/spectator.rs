@@ -0,0 +1,163 @@
+//! A third-person "spectator" view of the scene, rendered from a virtual camera into an
+//! offscreen [RenderTarget] so it can be mirrored to a screen or recorded for trailers.
+
+use crate::lens_preview::LensPreview;
+use crate::passthrough_camera::PassthroughCamera;
+use crate::pose_stream::{Pose, RemoteAvatar};
+use crate::scene::MyScene;
+use bob_shaders::lens_distortion_shader::LensDistortionParameters;
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::XrFovf;
+use gl_thin::render_target::RenderTarget;
+use openxr::SpaceLocation;
+use openxr_sys::Time;
+
+/// Where the spectator camera is
+#[derive(Copy, Clone, Debug)]
+pub enum SpectatorMount {
+    /// a pose in the stage/local space
+    Fixed(Pose),
+    /// follows a tracked pose (a controller, the head, ...) supplied every frame, offset by `offset`
+    Tracked { offset: Pose },
+}
+
+pub struct SpectatorConfig {
+    pub width: i32,
+    pub height: i32,
+    /// radians
+    pub vertical_fov: f32,
+    pub mount: SpectatorMount,
+    /// 0 follows the mount exactly.  Closer to 1 glides after it, which hides hand and head jitter.
+    pub smoothing: f32,
+    /// render every Nth frame.  The spectator view does not need the headset's refresh rate.
+    pub render_interval: u32,
+    /// run the image through a [LensPreview], so it looks like the view through the headset
+    pub lens: Option<LensDistortionParameters>,
+}
+
+impl SpectatorConfig {
+    pub fn new(mount: SpectatorMount) -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            vertical_fov: 60.0f32.to_radians(),
+            mount,
+            smoothing: 0.9,
+            render_interval: 2,
+            lens: None,
+        }
+    }
+}
+
+pub struct SpectatorCamera {
+    pub config: SpectatorConfig,
+    /// the scene as the camera sees it; see [SpectatorCamera::output] for the finished image
+    pub target: RenderTarget,
+    pub lens_preview: Option<LensPreview>,
+    pose: Option<Pose>,
+    frame_counter: u32,
+}
+
+impl SpectatorCamera {
+    pub fn new(config: SpectatorConfig, gpu_state: &mut GPUState) -> Result<Self, GLErrorWrapper> {
+        let target = RenderTarget::new(config.width, config.height, gpu_state)?;
+        let lens_preview = match config.lens {
+            Some(lens) => Some(LensPreview::new(
+                config.width,
+                config.height,
+                lens,
+                gpu_state,
+            )?),
+            None => None,
+        };
+        Ok(Self {
+            config,
+            target,
+            lens_preview,
+            pose: None,
+            frame_counter: 0,
+        })
+    }
+
+    /// the most recent spectator image; the screen mirror and recorder read from this.
+    pub fn output(&self) -> &RenderTarget {
+        match &self.lens_preview {
+            Some(preview) => &preview.target,
+            None => &self.target,
+        }
+    }
+
+    /// the pose the next render will use
+    pub fn pose(&self) -> Option<Pose> {
+        self.pose
+    }
+
+    /// Advance the smoothed pose.  `tracked` is the current pose of whatever a
+    /// [SpectatorMount::Tracked] camera follows.
+    /// Returns true if this is a frame the spectator view should be rendered on.
+    pub fn update(&mut self, tracked: Option<Pose>) -> bool {
+        let goal = match self.config.mount {
+            SpectatorMount::Fixed(pose) => Some(pose),
+            SpectatorMount::Tracked { offset } => tracked.map(|t| t.compose(&offset)),
+        };
+
+        if let Some(goal) = goal {
+            self.pose = Some(match self.pose {
+                Some(pose) => pose.lerp(&goal, 1.0 - self.config.smoothing),
+                None => goal,
+            });
+        }
+
+        self.frame_counter += 1;
+        if self.frame_counter < self.config.render_interval {
+            return false;
+        }
+        self.frame_counter = 0;
+        self.pose.is_some()
+    }
+
+    /// a symmetric field of view matching the target's aspect ratio
+    pub fn fov(&self) -> XrFovf {
+        let half_y = self.config.vertical_fov * 0.5;
+        let half_x = (half_y.tan() * self.target.aspect_ratio()).atan();
+        XrFovf {
+            angle_left: -half_x,
+            angle_right: half_x,
+            angle_up: half_y,
+            angle_down: -half_y,
+        }
+    }
+
+    pub fn render(
+        &self,
+        scene: &MyScene,
+        time: Time,
+        gpu_state: &mut GPUState,
+        controller_1: &Option<SpaceLocation>,
+        remote_avatars: &[RemoteAvatar],
+        camera: Option<&PassthroughCamera>,
+    ) -> Result<(), GLErrorWrapper> {
+        let Some(pose) = self.pose else {
+            return Ok(());
+        };
+
+        self.target.bind()?;
+        scene.draw(
+            &self.fov(),
+            &pose.orientation,
+            &pose.position,
+            time,
+            gpu_state,
+            controller_1,
+            remote_avatars,
+            camera,
+        )?;
+
+        self.target.invalidate()?;
+        if let Some(preview) = &self.lens_preview {
+            preview.apply(&self.target.color, gpu_state)?;
+        }
+        Ok(())
+    }
+}
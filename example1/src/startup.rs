@@ -0,0 +1,156 @@
+//! Options for one launch, so a build can be tried at another render scale or with the
+//! debug overlays without rebuilding it.  The defaults are overridden by `startup.txt` in
+//! the app's external files directory, which `adb push` can reach
+//! (`/sdcard/Android/data/<package>/files/startup.txt`, `key = value` lines), and that by the
+//! extras of the intent that launched the activity:
+//!
+//! ```text
+//! adb shell am start -n <package>/android.app.NativeActivity --ef render_scale 0.8 --ez gl_counts_hud true
+//! ```
+//!
+//! Values that do not parse are logged and skipped.
+
+use crate::scene_stack::DEFAULT_SCENE;
+use crate::settings::UserSettings;
+use android_activity::AndroidApp;
+use jni::objects::{JObject, JObjectArray, JString, JValue};
+use jni::JavaVM;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Clone, Debug)]
+pub struct StartupOptions {
+    /// `render_scale`, see [gl_thin::openxr_helpers::OpenXRComponent::set_render_scale]
+    pub render_scale: f32,
+    /// `validation`: load the OpenXR core validation layer, if it is packaged.  On in debug
+    /// builds.
+    pub validation: bool,
+    /// `scene`, a name in [crate::scene_stack::SceneStack::builtin]
+    pub scene: String,
+    /// `gl_counts_hud`, see [crate::drawcore::ActiveRenderer::enable_gl_counts_hud]
+    pub gl_counts_hud: bool,
+    /// `profiler_report`, in seconds; see
+    /// [crate::drawcore::ActiveRenderer::enable_profiler_report]
+    pub profiler_report: Option<Duration>,
+    /// `multiview`: draw both eyes in one pass where GL_OVR_multiview2 is available, see
+    /// [gl_thin::multiview]
+    pub multiview: bool,
+    /// `ui_scripts`: play the [crate::ui::script::bundled] UI scripts at startup and log
+    /// the reports
+    pub ui_scripts: bool,
+    /// `prediction_offset_ms`, see
+    /// [gl_thin::openxr_helpers::OpenXRComponent::set_prediction_offset]
+    pub prediction_offset_ms: f32,
+}
+
+impl Default for StartupOptions {
+    fn default() -> Self {
+        Self {
+            render_scale: 1.0,
+            validation: cfg!(debug_assertions),
+            scene: DEFAULT_SCENE.to_string(),
+            gl_counts_hud: false,
+            profiler_report: None,
+            multiview: false,
+            ui_scripts: false,
+            prediction_offset_ms: 0.0,
+        }
+    }
+}
+
+impl StartupOptions {
+    /// The defaults, then the config file, then the intent extras.
+    pub fn load(app: &AndroidApp) -> Self {
+        let mut options = Self::default();
+        if let Some(dir) = app.external_data_path() {
+            match UserSettings::load(dir.join("startup.txt")) {
+                Ok(file) => options.merge(|key| file.get(key)),
+                Err(e) => log::warn!("failed to read startup.txt {}", e),
+            }
+        }
+        match intent_extras(app) {
+            Ok(extras) => options.merge(|key| extras.get(key).cloned()),
+            Err(e) => log::warn!("failed to read the intent extras {}", e),
+        }
+        log::info!("startup options {:?}", options);
+        options
+    }
+
+    /// Override the options `lookup` has a value for.
+    pub fn merge(&mut self, lookup: impl Fn(&str) -> Option<String>) {
+        merge_one(&mut self.render_scale, "render_scale", &lookup);
+        merge_one(&mut self.validation, "validation", &lookup);
+        merge_one(&mut self.scene, "scene", &lookup);
+        merge_one(&mut self.gl_counts_hud, "gl_counts_hud", &lookup);
+        let mut seconds = self.profiler_report.map_or(0.0, |d| d.as_secs_f32());
+        merge_one(&mut seconds, "profiler_report", &lookup);
+        self.profiler_report = (seconds > 0.0).then(|| Duration::from_secs_f32(seconds));
+        merge_one(&mut self.multiview, "multiview", &lookup);
+        merge_one(&mut self.ui_scripts, "ui_scripts", &lookup);
+        merge_one(
+            &mut self.prediction_offset_ms,
+            "prediction_offset_ms",
+            &lookup,
+        );
+    }
+}
+
+fn merge_one<T: FromStr>(value: &mut T, key: &str, lookup: &impl Fn(&str) -> Option<String>) {
+    let Some(text) = lookup(key) else {
+        return;
+    };
+    match text.parse() {
+        Ok(parsed) => *value = parsed,
+        Err(_) => log::warn!("ignoring startup option {} = {:?}", key, text),
+    }
+}
+
+/// The extras of the activity's intent, each as its `toString()`.
+pub fn intent_extras(app: &AndroidApp) -> Result<BTreeMap<String, String>, jni::errors::Error> {
+    let vm = unsafe { JavaVM::from_raw(app.vm_as_ptr() as *mut jni::sys::JavaVM) }?;
+    let mut env = vm.attach_current_thread()?;
+    let activity = unsafe { JObject::from_raw(app.activity_as_ptr() as jni::sys::jobject) };
+
+    let mut extras = BTreeMap::new();
+    let intent = env
+        .call_method(&activity, "getIntent", "()Landroid/content/Intent;", &[])?
+        .l()?;
+    if intent.is_null() {
+        return Ok(extras);
+    }
+    let bundle = env
+        .call_method(&intent, "getExtras", "()Landroid/os/Bundle;", &[])?
+        .l()?;
+    if bundle.is_null() {
+        return Ok(extras);
+    }
+    let keys = env
+        .call_method(&bundle, "keySet", "()Ljava/util/Set;", &[])?
+        .l()?;
+    let keys: JObjectArray = env
+        .call_method(&keys, "toArray", "()[Ljava/lang/Object;", &[])?
+        .l()?
+        .into();
+    for i in 0..env.get_array_length(&keys)? {
+        let key: JString = env.get_object_array_element(&keys, i)?.into();
+        let value = env
+            .call_method(
+                &bundle,
+                "get",
+                "(Ljava/lang/String;)Ljava/lang/Object;",
+                &[JValue::Object(&key)],
+            )?
+            .l()?;
+        if value.is_null() {
+            continue;
+        }
+        let value: JString = env
+            .call_method(&value, "toString", "()Ljava/lang/String;", &[])?
+            .l()?
+            .into();
+        let key = String::from(env.get_string(&key)?);
+        extras.insert(key, String::from(env.get_string(&value)?));
+    }
+    Ok(extras)
+}
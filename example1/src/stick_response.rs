@@ -0,0 +1,97 @@
+//! Shaping of thumbstick deflection: a dead zone to hide drift around the center, a
+//! saturation point so worn sticks still reach full speed, and a response curve for finer
+//! control near the middle.
+
+use openxr_sys::Vector2f;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ResponseCurve {
+    Linear,
+    /// Blends the linear response with its cube; 0 is linear, 1 is a pure cube.  Small
+    /// deflections get gentler while full deflection stays full.
+    Expo(f32),
+}
+
+impl ResponseCurve {
+    /// `t` in 0..1
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            ResponseCurve::Linear => t,
+            ResponseCurve::Expo(k) => {
+                let k = k.clamp(0.0, 1.0);
+                (1.0 - k) * t + k * t * t * t
+            }
+        }
+    }
+}
+
+/// The response of one axis
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AxisResponse {
+    /// deflections up to this read as 0
+    pub dead_zone: f32,
+    /// deflections from this on read as full
+    pub saturation: f32,
+    pub curve: ResponseCurve,
+}
+
+impl Default for AxisResponse {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.1,
+            saturation: 0.95,
+            curve: ResponseCurve::Linear,
+        }
+    }
+}
+
+impl AxisResponse {
+    /// the unshaped response, for axes that are already processed elsewhere
+    pub const RAW: AxisResponse = AxisResponse {
+        dead_zone: 0.0,
+        saturation: 1.0,
+        curve: ResponseCurve::Linear,
+    };
+
+    /// `value` in -1..1; the result keeps its sign
+    pub fn apply(&self, value: f32) -> f32 {
+        let magnitude = value.abs();
+        if magnitude <= self.dead_zone {
+            return 0.0;
+        }
+        let range = self.saturation - self.dead_zone;
+        let t = if range > 0.0 {
+            ((magnitude - self.dead_zone) / range).min(1.0)
+        } else {
+            1.0
+        };
+        self.curve.apply(t).copysign(value)
+    }
+}
+
+/// Per-axis shaping for a vec2 action, see [crate::xr_input::XrInputs::stick_response]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct StickResponse {
+    pub x: AxisResponse,
+    pub y: AxisResponse,
+}
+
+impl StickResponse {
+    pub const RAW: StickResponse = StickResponse {
+        x: AxisResponse::RAW,
+        y: AxisResponse::RAW,
+    };
+
+    pub fn with_curve(mut self, curve: ResponseCurve) -> Self {
+        self.x.curve = curve;
+        self.y.curve = curve;
+        self
+    }
+
+    pub fn apply(&self, value: Vector2f) -> Vector2f {
+        Vector2f {
+            x: self.x.apply(value.x),
+            y: self.y.apply(value.y),
+        }
+    }
+}
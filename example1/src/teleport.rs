@@ -0,0 +1,119 @@
+//! Pointing at the floor and jumping there.  While the teleport button is held an arc falls
+//! from the controller; where it comes down on the [NavMesh] is the target, and letting go
+//! moves the user there.  An arc that lands anywhere else (off the mesh, or out of reach)
+//! is drawn red and letting go does nothing.
+
+use crate::debug_draw::{DebugLines, CYAN, GREEN, RED};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::{XrMatrix4x4f, XrVector3f};
+use gl_thin::navmesh::NavMesh;
+
+const GRAVITY: f32 = 9.8;
+/// seconds of flight between the arc's points
+const ARC_STEP: f32 = 0.03;
+/// how many points the arc has at most; with [ARC_STEP] it flies for two seconds
+const ARC_POINTS: usize = 67;
+
+pub struct Teleport {
+    pub navmesh: NavMesh,
+    /// how fast the arc leaves the controller, m/s; faster reaches farther
+    pub arc_speed: f32,
+    /// draw the navmesh's edges, to see where the walkable area is
+    pub show_navmesh: bool,
+    /// where the arc lands, if that is walkable
+    target: Option<XrVector3f>,
+    aiming: bool,
+    lines: DebugLines,
+}
+
+impl Teleport {
+    pub fn new(navmesh: NavMesh) -> Result<Self, GLErrorWrapper> {
+        Ok(Self {
+            navmesh,
+            arc_speed: 7.0,
+            show_navmesh: false,
+            target: None,
+            aiming: false,
+            lines: DebugLines::new()?,
+        })
+    }
+
+    /// Once per frame, with whether the teleport button is held and the controller's ray.
+    /// Returns where to go on the frame the button is let go with the arc on a valid target.
+    pub fn update(
+        &mut self,
+        held: bool,
+        ray: Option<(XrVector3f, XrVector3f)>,
+        gpu_state: &mut GPUState,
+    ) -> Result<Option<XrVector3f>, GLErrorWrapper> {
+        let released = self.aiming && !held;
+        self.aiming = held;
+        let chosen = if released { self.target } else { None };
+
+        self.target = None;
+        self.lines.clear();
+        if let (true, Some((origin, direction))) = (held, ray) {
+            self.aim(&origin, &direction);
+        }
+        if self.show_navmesh {
+            for (a, b) in self.navmesh.edges() {
+                self.lines.line(&a, &b, CYAN);
+            }
+        }
+        self.lines.upload(gpu_state)?;
+        Ok(chosen)
+    }
+
+    /// where letting go now would go
+    pub fn target(&self) -> Option<XrVector3f> {
+        self.target
+    }
+
+    /// Follow the arc until it meets the navmesh, or anything else below the lowest walkable
+    /// point.
+    fn aim(&mut self, origin: &XrVector3f, direction: &XrVector3f) {
+        let mut position = [origin.x, origin.y, origin.z];
+        let mut velocity = [direction.x, direction.y, direction.z].map(|v| v * self.arc_speed);
+        let floor = self
+            .navmesh
+            .vertices
+            .iter()
+            .map(|v| v[1])
+            .fold(f32::INFINITY, f32::min);
+
+        let mut arc = vec![position];
+        for _ in 1..ARC_POINTS {
+            let step = [0, 1, 2].map(|axis| velocity[axis] * ARC_STEP);
+            if let Some(hit) = self.navmesh.cast(&position, &step, 1.0) {
+                arc.push(hit.position);
+                let [x, y, z] = hit.position;
+                self.target = Some(XrVector3f::new(x, y, z));
+                break;
+            }
+            position = [0, 1, 2].map(|axis| position[axis] + step[axis]);
+            velocity[1] -= GRAVITY * ARC_STEP;
+            arc.push(position);
+            if position[1] < floor - 1.0 {
+                break;
+            }
+        }
+
+        match self.target {
+            Some(target) => {
+                self.lines.polyline(&arc, GREEN);
+                self.lines
+                    .cross(&[target.x, target.y, target.z], 0.3, GREEN);
+            }
+            None => self.lines.polyline(&arc, RED),
+        }
+    }
+
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.lines.draw(matrix_pv, gpu_state)
+    }
+}
@@ -0,0 +1,115 @@
+//! Cheap temporal anti-aliasing for the [PostChain], against the shimmer of thin,
+//! high-contrast geometry (cables, fences, text edges) as the head moves.  Costs a
+//! full-screen pass and a history image per eye, far less than raising MSAA.
+
+use crate::post_chain::{blit_color, fullscreen_quad, PostEffect, PostFrame};
+use bob_shaders::temporal_aa_shader::{TemporalAaParameters, TemporalAaShader};
+use gl::types::GLfloat;
+use gl_thin::gl_fancy::{GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::{xr_matrix4x4f_invert, XrMatrix4x4f};
+use gl_thin::render_target::RenderTarget;
+
+/// what [crate::post_chain::PostChain::set_enabled] knows it by
+pub const TEMPORAL_AA: &str = "temporal aa";
+
+/// the last result for one eye, and the camera it was seen from
+struct History {
+    target: RenderTarget,
+    matrix_pv: XrMatrix4x4f,
+    frame_index: u64,
+}
+
+pub struct TemporalAa {
+    /// how much of each new frame goes into the result; see [TemporalAaParameters::blend]
+    pub blend: f32,
+    shader: TemporalAaShader,
+    buffers: VertexBufferBundle<'static, GLfloat, u8>,
+    history: Vec<Option<History>>,
+}
+
+impl TemporalAa {
+    pub fn new(gpu_state: &mut GPUState) -> Result<Self, GLErrorWrapper> {
+        let shader = TemporalAaShader::new()?;
+        let buffers = fullscreen_quad(shader.sal_position, gpu_state)?;
+        Ok(Self {
+            blend: 0.15,
+            shader,
+            buffers,
+            history: vec![],
+        })
+    }
+}
+
+impl PostEffect for TemporalAa {
+    fn name(&self) -> &'static str {
+        TEMPORAL_AA
+    }
+
+    fn apply(
+        &mut self,
+        frame: &PostFrame,
+        input: &RenderTarget,
+        output: &RenderTarget,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        if self.history.len() <= frame.eye {
+            self.history.resize_with(frame.eye + 1, || None);
+        }
+        // a skipped frame (or the effect being switched off for a while) leaves nothing to reuse
+        let history = self.history[frame.eye].as_ref().filter(|history| {
+            history.frame_index + 1 == frame.frame_index && same_shape(&history.target, output)
+        });
+
+        let parameters = TemporalAaParameters {
+            reprojection: match history {
+                Some(history) => history.matrix_pv * xr_matrix4x4f_invert(&frame.matrix_pv),
+                None => frame.matrix_pv,
+            },
+            texel_size: [1.0 / input.width as f32, 1.0 / input.height as f32],
+            blend: self.blend,
+            history_valid: history.is_some(),
+        };
+        let history_color = history.map_or(&input.color, |history| &history.target.color);
+        self.shader.draw(
+            &input.color,
+            frame.depth,
+            history_color,
+            &parameters,
+            &self.buffers,
+            self.buffers.index_count as _,
+            gpu_state,
+        )?;
+
+        // keep the result for the next frame
+        let stale = !matches!(
+            &self.history[frame.eye],
+            Some(history) if same_shape(&history.target, output)
+        );
+        if stale {
+            self.history[frame.eye] = Some(History {
+                target: RenderTarget::with_format(
+                    output.width,
+                    output.height,
+                    output.format,
+                    gpu_state,
+                )?,
+                matrix_pv: frame.matrix_pv,
+                frame_index: frame.frame_index,
+            });
+        }
+        let Some(history) = &mut self.history[frame.eye] else {
+            return Ok(());
+        };
+        history.target.frame_buffer.bind()?;
+        blit_color(output)?;
+        history.matrix_pv = frame.matrix_pv;
+        history.frame_index = frame.frame_index;
+        output.frame_buffer.bind()
+    }
+}
+
+/// blits need matching sizes and formats
+fn same_shape(a: &RenderTarget, b: &RenderTarget) -> bool {
+    a.width == b.width && a.height == b.height && a.format == b.format
+}
@@ -1,7 +1,15 @@
+use crate::fallback_assets;
 use gl::types::{GLenum, GLint};
 use gl_thin::gl_fancy::GPUState;
 use gl_thin::gl_helper::{GLErrorWrapper, Texture, TextureWithTarget};
+use gl_thin::texture_atlas::{TextureAtlas, UvRect};
 use rusttype::{point, Font, PositionedGlyph, Scale};
+use std::collections::HashMap;
+
+/// see [fallback_assets::font]
+pub fn default_font() -> Font<'static> {
+    fallback_assets::font()
+}
 
 pub fn text_to_greyscale_texture(
     width: GLint,
@@ -11,8 +19,7 @@ pub fn text_to_greyscale_texture(
     gpu_state: &mut GPUState,
     tgt: GLenum,
 ) -> Result<TextureWithTarget, GLErrorWrapper> {
-    let font = Font::try_from_bytes(include_bytes!("Montserrat-Regular.ttf"))
-        .expect("failed to parse font");
+    let font = default_font();
 
     let scale = Scale {
         x: font_size,
@@ -121,3 +128,190 @@ pub fn render_glyphs_to_rgb<'a, 'f: 'a>(
         }
     }
 }
+
+//
+
+/// Where one glyph ended up in a [GlyphCache]'s atlas
+#[derive(Copy, Clone, Debug)]
+pub struct CachedGlyph {
+    pub uv: UvRect,
+    /// offset of the bitmap's top left corner from the pen position on the baseline, in pixels, +y down
+    pub left: i32,
+    pub top: i32,
+    pub width: i32,
+    pub height: i32,
+    pub advance: f32,
+}
+
+/// One glyph of a laid-out string, in pixels relative to the top left of the line, +y down
+#[derive(Copy, Clone, Debug)]
+pub struct GlyphQuad {
+    pub x0: f32,
+    pub y0: f32,
+    pub x1: f32,
+    pub y1: f32,
+    pub uv: UvRect,
+}
+
+/// Glyphs are rasterized on first use into a shared [TextureAtlas],
+/// so any amount of text at a handful of sizes draws from a single texture.
+pub struct GlyphCache {
+    font: Font<'static>,
+    pub atlas: TextureAtlas,
+    /// keyed by character and font size bits
+    glyphs: HashMap<(char, u32), CachedGlyph>,
+    generation: u32,
+}
+
+impl GlyphCache {
+    pub fn new(atlas_size: i32, gpu_state: &mut GPUState) -> Result<Self, GLErrorWrapper> {
+        Ok(Self {
+            font: default_font(),
+            atlas: TextureAtlas::new(atlas_size, atlas_size, gpu_state)?,
+            glyphs: HashMap::new(),
+            generation: 0,
+        })
+    }
+
+    /// Goes up every time the atlas fills and gets flushed.
+    /// Geometry built from older [GlyphQuad]s has stale texture coordinates and must be rebuilt.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// (ascent, descent) in pixels; descent is negative
+    pub fn line_metrics(&self, font_size: f32) -> (f32, f32) {
+        let v = self.font.v_metrics(Scale::uniform(font_size));
+        (v.ascent, v.descent)
+    }
+
+    pub fn glyph(
+        &mut self,
+        c: char,
+        font_size: f32,
+        gpu_state: &mut GPUState,
+    ) -> Result<CachedGlyph, GLErrorWrapper> {
+        let key = (c, font_size.to_bits());
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return Ok(*glyph);
+        }
+
+        let glyph = match self.rasterize(c, font_size, gpu_state)? {
+            Some(glyph) => glyph,
+            None => {
+                // out of room: start over with only what is needed from now on
+                self.glyphs.clear();
+                self.atlas.clear(gpu_state)?;
+                self.generation += 1;
+                self.rasterize(c, font_size, gpu_state)?.ok_or_else(|| {
+                    GLErrorWrapper::with_message2(format!(
+                        "glyph {:?} at {} px does not fit in an empty atlas",
+                        c, font_size
+                    ))
+                })?
+            }
+        };
+        self.glyphs.insert(key, glyph);
+        Ok(glyph)
+    }
+
+    fn rasterize(
+        &mut self,
+        c: char,
+        font_size: f32,
+        gpu_state: &mut GPUState,
+    ) -> Result<Option<CachedGlyph>, GLErrorWrapper> {
+        let scaled = self.font.glyph(c).scaled(Scale::uniform(font_size));
+        let advance = scaled.h_metrics().advance_width;
+        let positioned = scaled.positioned(point(0.0, 0.0));
+
+        let Some(bb) = positioned.pixel_bounding_box() else {
+            // whitespace
+            return Ok(Some(CachedGlyph {
+                uv: UvRect {
+                    u0: 0.0,
+                    v0: 0.0,
+                    u1: 0.0,
+                    v1: 0.0,
+                },
+                left: 0,
+                top: 0,
+                width: 0,
+                height: 0,
+                advance,
+            }));
+        };
+
+        let (width, height) = (bb.width(), bb.height());
+        // coverage in every channel, so it works as a mask (like MaskedSolidShader's red channel)
+        // or as premultiplied white
+        let mut pixels = vec![0u8; (4 * width * height) as usize];
+        positioned.draw(|x, y, v| {
+            let i = 4 * (x + y * width as u32) as usize;
+            pixels[i..i + 4].fill((v * 255.9) as u8);
+        });
+
+        Ok(self
+            .atlas
+            .insert_rgba(width, height, &pixels, gpu_state)?
+            .map(|(_, uv)| CachedGlyph {
+                uv,
+                left: bb.min.x,
+                top: bb.min.y,
+                width,
+                height,
+                advance,
+            }))
+    }
+
+    /// Lay `text` out on one line, caching any glyphs it needs.
+    /// Returns the quads and the width of the line.
+    pub fn layout(
+        &mut self,
+        text: &str,
+        font_size: f32,
+        gpu_state: &mut GPUState,
+    ) -> Result<(Vec<GlyphQuad>, f32), GLErrorWrapper> {
+        // if the atlas is flushed partway through, the earlier quads are stale; try once more
+        for _ in 0..2 {
+            if let Some(rval) = self.layout_once(text, font_size, gpu_state)? {
+                return Ok(rval);
+            }
+        }
+        Err(GLErrorWrapper::with_message2(format!(
+            "{:?} at {} px needs more glyphs than the atlas holds",
+            text, font_size
+        )))
+    }
+
+    fn layout_once(
+        &mut self,
+        text: &str,
+        font_size: f32,
+        gpu_state: &mut GPUState,
+    ) -> Result<Option<(Vec<GlyphQuad>, f32)>, GLErrorWrapper> {
+        let generation = self.generation;
+        let (ascent, _) = self.line_metrics(font_size);
+        let mut quads = Vec::with_capacity(text.len());
+        let mut x = 0.0;
+        for c in text.chars() {
+            let glyph = self.glyph(c, font_size, gpu_state)?;
+            if self.generation != generation {
+                return Ok(None);
+            }
+            if glyph.width > 0 {
+                let x0 = x + glyph.left as f32;
+                let y0 = ascent + glyph.top as f32;
+                quads.push(GlyphQuad {
+                    x0,
+                    y0,
+                    x1: x0 + glyph.width as f32,
+                    y1: y0 + glyph.height as f32,
+                    uv: glyph.uv,
+                });
+            }
+            x += glyph.advance;
+        }
+        Ok(Some((quads, x)))
+    }
+}
@@ -17,7 +17,7 @@ impl TexturedQuad {
         dy: f32,
         texture: TextureWithTarget,
     ) -> Result<Self, GLErrorWrapper> {
-        let program = RawTextureShader::new(gl::TEXTURE_2D)?;
+        let program = RawTextureShader::new(texture.target)?;
 
         program.shader.use_()?;
 
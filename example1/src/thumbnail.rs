@@ -0,0 +1,91 @@
+//! Small pictures of scenes for menus, e.g. the rows of a
+//! [SceneLauncher](crate::ui::launcher::SceneLauncher).  A scene is built just for the
+//! picture, drawn once from a fixed camera into a [RenderTarget], and dropped with everything
+//! it put on the GPU; only the color texture is kept.
+
+use crate::lod::LodView;
+use crate::scene::{inverse_view_matrix, projection_matrix, MyScene};
+use crate::scene_stack::SceneStack;
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper, TextureWithTarget};
+use gl_thin::linear::{XrFovf, XrQuaternionf, XrVector3f};
+use gl_thin::render_queue::RenderQueue;
+use gl_thin::render_target::RenderTarget;
+
+/// Where the picture is taken from, in the scene's LOCAL space
+#[derive(Copy, Clone, Debug)]
+pub struct ThumbnailCamera {
+    pub eye: XrVector3f,
+    pub orientation: XrQuaternionf,
+    /// radians, top to bottom; the width follows the picture's aspect ratio
+    pub vertical_fov: f32,
+}
+
+impl Default for ThumbnailCamera {
+    /// where a headset starts, looking ahead and a little down
+    fn default() -> Self {
+        let (s, c) = (-0.1f32).sin_cos();
+        Self {
+            eye: XrVector3f::default_translation(),
+            orientation: XrQuaternionf::new(s, 0.0, 0.0, c),
+            vertical_fov: 1.0,
+        }
+    }
+}
+
+impl ThumbnailCamera {
+    fn fov(&self, aspect: f32) -> XrFovf {
+        let tan_y = (0.5 * self.vertical_fov).tan();
+        let tan_x = tan_y * aspect;
+        XrFovf {
+            angle_left: -tan_x.atan(),
+            angle_right: tan_x.atan(),
+            angle_up: tan_y.atan(),
+            angle_down: -tan_y.atan(),
+        }
+    }
+}
+
+/// Build scene `index` of `stack`, draw it and let it go.
+pub fn bake_thumbnail(
+    stack: &SceneStack,
+    index: usize,
+    camera: &ThumbnailCamera,
+    width: i32,
+    height: i32,
+    gpu_state: &mut GPUState,
+) -> Result<TextureWithTarget, GLErrorWrapper> {
+    let scene = stack.build(index, gpu_state)?;
+    render_thumbnail(&scene, camera, width, height, gpu_state)
+}
+
+/// Draw `scene` into a fresh `width` x `height` texture.  Leaves the default framebuffer
+/// bound.
+pub fn render_thumbnail(
+    scene: &MyScene,
+    camera: &ThumbnailCamera,
+    width: i32,
+    height: i32,
+    gpu_state: &mut GPUState,
+) -> Result<TextureWithTarget, GLErrorWrapper> {
+    let target = RenderTarget::new(width, height, gpu_state)?;
+    target.bind()?;
+
+    let fov = camera.fov(target.aspect_ratio());
+    let matrix_pv = projection_matrix(&fov) * inverse_view_matrix(&camera.orientation, &camera.eye);
+    let lod_view = LodView::new(camera.eye, &fov, height);
+    let mut queue = RenderQueue::new();
+    scene.queue_draws(&mut queue, matrix_pv, lod_view, &None, &[], None);
+    let drawn = queue.execute(gpu_state).and_then(|_| target.invalidate());
+
+    unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0) };
+    explode_if_gl_error()?;
+    drawn?;
+
+    // the framebuffer and depth buffer are deleted here
+    let RenderTarget { color, .. } = target;
+    Ok(TextureWithTarget {
+        texture: color,
+        target: gl::TEXTURE_2D,
+    })
+}
@@ -0,0 +1,145 @@
+//! Knobs for trying out the renderer from inside the headset: a list whose items cycle
+//! through the settings of the [QualityGovernor] when picked, and one to export the scene.
+
+use crate::adaptive_quality::{QualityGovernor, QualityLevel};
+use crate::ui::scroll::{ScrollLayout, ScrollView};
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::XrMatrix4x4f;
+use gl_thin::performance_settings::{
+    PerfSettingsDomainEXT, PerfSettingsLevelEXT, PerfSettingsNotificationLevelEXT,
+    PerfSettingsSubDomainEXT,
+};
+
+/// the item that cycles [QualityGovernor::forced]
+pub const QUALITY_ITEM: usize = 0;
+const CPU_LEVEL_ITEM: usize = 1;
+const GPU_LEVEL_ITEM: usize = 2;
+/// the item that asks for [crate::drawcore::ActiveRenderer::request_scene_export]; the
+/// menu can't do that itself, so [DeveloperMenu::activate] ignores it
+pub const EXPORT_SCENE_ITEM: usize = 3;
+
+/// The governor settings the menu edits, kept so an edit can be undone.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MenuSettings {
+    pub forced: Option<QualityLevel>,
+    pub cpu_level: PerfSettingsLevelEXT,
+    pub gpu_level: PerfSettingsLevelEXT,
+}
+
+impl MenuSettings {
+    pub fn of(governor: &QualityGovernor) -> Self {
+        Self {
+            forced: governor.forced,
+            cpu_level: governor.cpu_level,
+            gpu_level: governor.gpu_level,
+        }
+    }
+
+    pub fn restore(&self, governor: &mut QualityGovernor) {
+        governor.forced = self.forced;
+        governor.cpu_level = self.cpu_level;
+        governor.gpu_level = self.gpu_level;
+    }
+}
+
+pub struct DeveloperMenu {
+    pub view: ScrollView,
+}
+
+impl DeveloperMenu {
+    pub fn new(model: XrMatrix4x4f) -> Result<Self, GLErrorWrapper> {
+        Ok(Self {
+            view: ScrollView::new(model, 0.5, 0.35, ScrollLayout::List),
+        })
+    }
+
+    /// Show the governor's current settings.  Cheap when nothing changed, so call it every frame.
+    pub fn refresh(&mut self, governor: &QualityGovernor) {
+        let quality = match governor.forced {
+            Some(level) => format!("quality: {:?} (forced)", level),
+            None => format!("quality: auto ({:?})", governor.level()),
+        };
+        let mut items = vec![
+            quality,
+            format!("CPU level: {}", level_name(governor.cpu_level)),
+            format!("GPU level: {}", level_name(governor.gpu_level)),
+            "export scene (glTF)".to_string(),
+        ];
+        if let Some(notification) = governor.last_notification() {
+            items.push(format!(
+                "runtime: {} {} {}",
+                domain_name(notification.domain),
+                sub_domain_name(notification.sub_domain),
+                notification_name(notification.to_level),
+            ));
+        }
+        if items != self.view.items() {
+            self.view.set_items(items);
+        }
+    }
+
+    /// The user picked item `index` (see [ScrollView::item_at]); step that setting to its next value.
+    pub fn activate(&mut self, index: usize, governor: &mut QualityGovernor) {
+        match index {
+            QUALITY_ITEM => {
+                governor.forced = match governor.forced {
+                    None => Some(QualityLevel::ALL[0]),
+                    Some(level) => QualityLevel::ALL
+                        .iter()
+                        .skip_while(|l| **l != level)
+                        .nth(1)
+                        .copied(),
+                }
+            }
+            CPU_LEVEL_ITEM => governor.cpu_level = next_level(governor.cpu_level),
+            GPU_LEVEL_ITEM => governor.gpu_level = next_level(governor.gpu_level),
+            _ => return,
+        }
+        self.refresh(governor);
+    }
+}
+
+fn next_level(level: PerfSettingsLevelEXT) -> PerfSettingsLevelEXT {
+    match level {
+        PerfSettingsLevelEXT::POWER_SAVINGS => PerfSettingsLevelEXT::SUSTAINED_LOW,
+        PerfSettingsLevelEXT::SUSTAINED_LOW => PerfSettingsLevelEXT::SUSTAINED_HIGH,
+        PerfSettingsLevelEXT::SUSTAINED_HIGH => PerfSettingsLevelEXT::BOOST,
+        _ => PerfSettingsLevelEXT::POWER_SAVINGS,
+    }
+}
+
+fn level_name(level: PerfSettingsLevelEXT) -> &'static str {
+    match level {
+        PerfSettingsLevelEXT::POWER_SAVINGS => "power savings",
+        PerfSettingsLevelEXT::SUSTAINED_LOW => "sustained low",
+        PerfSettingsLevelEXT::SUSTAINED_HIGH => "sustained high",
+        PerfSettingsLevelEXT::BOOST => "boost",
+        _ => "?",
+    }
+}
+
+fn domain_name(domain: PerfSettingsDomainEXT) -> &'static str {
+    match domain {
+        PerfSettingsDomainEXT::CPU => "CPU",
+        PerfSettingsDomainEXT::GPU => "GPU",
+        _ => "?",
+    }
+}
+
+fn sub_domain_name(sub_domain: PerfSettingsSubDomainEXT) -> &'static str {
+    match sub_domain {
+        PerfSettingsSubDomainEXT::COMPOSITING => "compositing",
+        PerfSettingsSubDomainEXT::RENDERING => "rendering",
+        PerfSettingsSubDomainEXT::THERMAL => "thermal",
+        _ => "?",
+    }
+}
+
+fn notification_name(level: PerfSettingsNotificationLevelEXT) -> &'static str {
+    match level {
+        PerfSettingsNotificationLevelEXT::NORMAL => "normal",
+        PerfSettingsNotificationLevelEXT::WARNING => "warning",
+        PerfSettingsNotificationLevelEXT::IMPAIRED => "impaired",
+        _ => "?",
+    }
+}
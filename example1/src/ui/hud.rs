@@ -0,0 +1,146 @@
+//! Head-locked text that stays in view.  Anything drawn relative to the views located at
+//! the start of the frame is a frame or so behind the head by the time it is on screen,
+//! which the runtime's reprojection hides for the world but not for something that should
+//! move with the head.  So each element's model matrix is worked out as its draw runs,
+//! from a head pose located just before.
+
+use crate::pose_stream::Pose;
+use crate::text_painting::GlyphCache;
+use crate::ui::label::Label;
+use crate::ui::units::PanelDensity;
+use bob_shaders::masked_solid_shader::MaskedSolidShader;
+use bob_shaders::material::{DepthMode, Material};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::{xr_matrix4x4f_create_translation, XrMatrix4x4f};
+use gl_thin::render_queue::{RenderLayer, RenderQueue};
+use std::cell::Cell;
+
+pub struct HudElement {
+    pub text: String,
+    /// relative to the head; -Z is straight ahead
+    pub offset: Pose,
+    pub color: [f32; 4],
+    /// `None` until laid out by [Hud::update]
+    label: Option<Label>,
+}
+
+pub struct Hud {
+    elements: Vec<HudElement>,
+    /// meters
+    pub line_height: f32,
+    pub density: PanelDensity,
+    program: MaskedSolidShader,
+    /// the frame index and head pose of the first draw of a frame, so both eyes agree
+    latched: Cell<Option<(u64, Pose)>>,
+}
+
+impl Hud {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        Ok(Self {
+            elements: vec![],
+            line_height: 0.02,
+            density: PanelDensity::default(),
+            program: MaskedSolidShader::new()?,
+            latched: Cell::new(None),
+        })
+    }
+
+    /// Show `text` centered at `offset` from the head; returns its index for [Hud::set_text]
+    pub fn add(&mut self, text: &str, offset: Pose, color: [f32; 4]) -> usize {
+        self.elements.push(HudElement {
+            text: text.to_string(),
+            offset,
+            color,
+            label: None,
+        });
+        self.elements.len() - 1
+    }
+
+    /// laid out again at the next [Hud::update]
+    pub fn set_text(&mut self, index: usize, text: &str) {
+        if let Some(element) = self.elements.get_mut(index) {
+            if element.text != text {
+                element.text = text.to_string();
+                element.label = None;
+            }
+        }
+    }
+
+    /// once per frame, before any view is drawn
+    pub fn update(
+        &mut self,
+        glyphs: &mut GlyphCache,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let font_size = self.density.font_size(self.line_height);
+        for element in &mut self.elements {
+            let fresh = element
+                .label
+                .as_ref()
+                .is_some_and(|label| !label.is_stale(glyphs));
+            if !fresh {
+                element.label = Some(Label::new(
+                    &element.text,
+                    font_size,
+                    self.line_height,
+                    f32::INFINITY,
+                    glyphs,
+                    &self.program,
+                    gpu_state,
+                )?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Queue the elements over everything else.  `locate_head` is only called once the
+    /// queue gets to them, and only for the first eye of `frame_index`; the other eye reuses
+    /// that pose.  Nothing is drawn while it returns `None`, e.g. the head is not tracked.
+    pub fn queue_draws<'a>(
+        &'a self,
+        queue: &mut RenderQueue<'a>,
+        matrix_pv: XrMatrix4x4f,
+        glyphs: &'a GlyphCache,
+        frame_index: u64,
+        locate_head: &'a dyn Fn() -> Option<Pose>,
+    ) {
+        if self.elements.is_empty() {
+            return;
+        }
+        queue.add(RenderLayer::Overlay, 0, move |gpu_state| {
+            let Some(head) = self.latch(frame_index, locate_head) else {
+                return Ok(());
+            };
+            let material = Material::default().with_depth(DepthMode::AlwaysOnTop);
+            for element in &self.elements {
+                let Some(label) = &element.label else {
+                    continue;
+                };
+                let matrix = matrix_pv
+                    * head.compose(&element.offset).matrix()
+                    * xr_matrix4x4f_create_translation(-0.5 * label.width, 0.0, 0.0);
+                label.draw(
+                    &matrix,
+                    glyphs,
+                    &self.program,
+                    &element.color,
+                    &material,
+                    gpu_state,
+                )?;
+            }
+            Ok(())
+        });
+    }
+
+    fn latch(&self, frame_index: u64, locate_head: &dyn Fn() -> Option<Pose>) -> Option<Pose> {
+        match self.latched.get() {
+            Some((latched_frame, head)) if latched_frame == frame_index => Some(head),
+            _ => {
+                let head = locate_head()?;
+                self.latched.set(Some((frame_index, head)));
+                Some(head)
+            }
+        }
+    }
+}
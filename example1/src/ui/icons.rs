@@ -0,0 +1,122 @@
+//! Small UI images packed into one [TextureAtlas] so a panel full of icons binds a single texture.
+
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::texture_atlas::{TextureAtlas, UvRect};
+use std::collections::HashMap;
+
+pub struct IconAtlas {
+    pub atlas: TextureAtlas,
+    icons: HashMap<String, UvRect>,
+}
+
+impl IconAtlas {
+    pub fn new(atlas_size: i32, gpu_state: &mut GPUState) -> Result<Self, GLErrorWrapper> {
+        Ok(Self {
+            atlas: TextureAtlas::new(atlas_size, atlas_size, gpu_state)?,
+            icons: HashMap::new(),
+        })
+    }
+
+    /// an atlas with the icons every panel uses already rasterized at `size` pixels
+    pub fn with_builtin_icons(
+        atlas_size: i32,
+        size: i32,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let mut rval = Self::new(atlas_size, gpu_state)?;
+        for (name, coverage) in BUILTIN_ICONS {
+            rval.insert_rgba(name, size, size, &rasterize(size, coverage), gpu_state)?;
+        }
+        Ok(rval)
+    }
+
+    /// Add (or replace) an RGBA8 icon.  Replacing does not reclaim the old icon's space.
+    pub fn insert_rgba(
+        &mut self,
+        name: &str,
+        width: i32,
+        height: i32,
+        pixels: &[u8],
+        gpu_state: &mut GPUState,
+    ) -> Result<UvRect, GLErrorWrapper> {
+        let (_, uv) = self
+            .atlas
+            .insert_rgba(width, height, pixels, gpu_state)?
+            .ok_or_else(|| {
+                GLErrorWrapper::with_message2(format!(
+                    "no room in the icon atlas for {} ({}x{})",
+                    name, width, height
+                ))
+            })?;
+        self.icons.insert(name.to_string(), uv);
+        Ok(uv)
+    }
+
+    pub fn uv(&self, name: &str) -> Option<UvRect> {
+        self.icons.get(name).copied()
+    }
+}
+
+//
+
+type Coverage = fn(f32, f32) -> f32;
+
+/// Each icon is a coverage function over [-1,1]x[-1,1] (+y down), 1 inside, 0 outside.
+const BUILTIN_ICONS: [(&str, Coverage); 5] = [
+    ("circle", |x, y| edge(1.0 - (x * x + y * y).sqrt())),
+    ("close", |x, y| {
+        let d = segment_distance(x, y, -0.6, -0.6, 0.6, 0.6)
+            .min(segment_distance(x, y, -0.6, 0.6, 0.6, -0.6));
+        edge(0.15 - d)
+    }),
+    ("check", |x, y| {
+        let d = segment_distance(x, y, -0.6, 0.0, -0.15, 0.5)
+            .min(segment_distance(x, y, -0.15, 0.5, 0.65, -0.45));
+        edge(0.15 - d)
+    }),
+    ("plus", |x, y| {
+        let d = segment_distance(x, y, -0.65, 0.0, 0.65, 0.0)
+            .min(segment_distance(x, y, 0.0, -0.65, 0.0, 0.65));
+        edge(0.15 - d)
+    }),
+    ("minus", |x, y| {
+        edge(0.15 - segment_distance(x, y, -0.65, 0.0, 0.65, 0.0))
+    }),
+];
+
+/// white with the coverage as alpha, 4x4 supersampled
+fn rasterize(size: i32, coverage: Coverage) -> Vec<u8> {
+    const SAMPLES: i32 = 4;
+    let mut pixels = Vec::with_capacity((4 * size * size) as usize);
+    for py in 0..size {
+        for px in 0..size {
+            let mut sum = 0.0;
+            for sy in 0..SAMPLES {
+                for sx in 0..SAMPLES {
+                    let x = (px as f32 + (sx as f32 + 0.5) / SAMPLES as f32) / size as f32;
+                    let y = (py as f32 + (sy as f32 + 0.5) / SAMPLES as f32) / size as f32;
+                    sum += coverage(2.0 * x - 1.0, 2.0 * y - 1.0);
+                }
+            }
+            let alpha = sum / (SAMPLES * SAMPLES) as f32;
+            pixels.extend_from_slice(&[255, 255, 255, (alpha * 255.9) as u8]);
+        }
+    }
+    pixels
+}
+
+fn edge(signed_distance: f32) -> f32 {
+    if signed_distance >= 0.0 {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn segment_distance(x: f32, y: f32, x0: f32, y0: f32, x1: f32, y1: f32) -> f32 {
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let t = (((x - x0) * dx + (y - y0) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+    let (ex, ey) = (x - (x0 + t * dx), y - (y0 + t * dy));
+    (ex * ex + ey * ey).sqrt()
+}
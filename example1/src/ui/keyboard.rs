@@ -0,0 +1,482 @@
+//! A virtual keyboard floating in front of the user, typed on by pointing the controller's
+//! ray at a key and pressing select.  Holding select repeats the key; shift applies to the
+//! next letter, or to every letter after a double tap.  What it types goes to the focused
+//! [crate::ui::text_field::TextField], through [crate::ui::Ui::handle_gestures].
+
+use crate::gestures::{Gesture, GestureButton};
+use crate::spatial_audio::SAMPLE_RATE;
+use crate::text_painting::GlyphCache;
+use crate::ui::label::Label;
+use crate::ui::raster_scale::{apparent_pixels, RasterScale};
+use crate::ui::scroll::plane_hit;
+use crate::ui::shapes::ShapePainter;
+use crate::ui::text_field::TextInput;
+use crate::ui::units::PanelDensity;
+use bob_shaders::masked_solid_shader::MaskedSolidShader;
+use bob_shaders::material::{DepthMode, Material};
+use bob_shaders::sdf_shape_shader::{SdfShape, SdfShapeStyle};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::{xr_matrix4x4f_create_translation, XrMatrix4x4f, XrVector3f};
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const LETTER_ROWS: [&str; 3] = ["qwertyuiop", "asdfghjkl", "zxcvbnm"];
+const SYMBOL_ROWS: [&str; 3] = ["1234567890", "@#$%&-+()", "*\"':;!?"];
+
+/// the widest row, in keys
+const ROW_KEYS: f32 = 10.0;
+const ROWS: f32 = 4.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeyAction {
+    Char(char),
+    Space,
+    Backspace,
+    Enter,
+    Shift,
+    /// switch between letters and symbols
+    Layer,
+}
+
+impl KeyAction {
+    /// what the key types, `None` for the ones that only change the keyboard
+    pub fn input(&self) -> Option<TextInput> {
+        match self {
+            KeyAction::Char(c) => Some(TextInput::Insert(c.to_string())),
+            KeyAction::Space => Some(TextInput::Insert(" ".to_string())),
+            KeyAction::Backspace => Some(TextInput::Backspace),
+            KeyAction::Enter => Some(TextInput::Enter),
+            KeyAction::Shift | KeyAction::Layer => None,
+        }
+    }
+
+    fn repeats(&self) -> bool {
+        matches!(
+            self,
+            KeyAction::Char(_) | KeyAction::Space | KeyAction::Backspace
+        )
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeyLayer {
+    Letters,
+    Symbols,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShiftState {
+    Off,
+    /// for the next letter only
+    Once,
+    Locked,
+}
+
+/// One key going down, or repeating while held
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Keystroke {
+    /// with shift already applied
+    pub action: KeyAction,
+    pub repeat: bool,
+}
+
+struct Key {
+    action: KeyAction,
+    /// in keys, from the middle of the keyboard
+    center: [f32; 2],
+    width: f32,
+}
+
+struct HeldKey {
+    index: usize,
+    next_repeat: Instant,
+}
+
+struct KeyLabels {
+    labels: Vec<Label>,
+    layer: KeyLayer,
+    caps: bool,
+    font_size: f32,
+}
+
+pub struct LaserKeyboard {
+    /// centered on the keyboard, +X right, +Y up, meters
+    pub model: XrMatrix4x4f,
+    /// meters from one key to the next
+    pub key_pitch: f32,
+    /// how long a key is held before it starts repeating
+    pub repeat_delay: Duration,
+    pub repeat_interval: Duration,
+    /// how soon a second tap on shift has to come to lock it
+    pub shift_lock_window: Duration,
+    /// sets the size glyphs are rasterized at, see [LaserKeyboard::font_size]
+    pub density: PanelDensity,
+    /// see [LaserKeyboard::track_eye]
+    pub raster_scale: RasterScale,
+    pub text_color: [f32; 4],
+    pub background: SdfShapeStyle,
+    pub key_style: SdfShapeStyle,
+    /// the key under the pointer, and shift while it is on
+    pub hover_style: SdfShapeStyle,
+    pub pressed_style: SdfShapeStyle,
+    /// what [crate::ui::Ui::draw] draws the keyboard with
+    pub depth: DepthMode,
+    /// mono, at [SAMPLE_RATE], for playing at [LaserKeyboard::position] on each keystroke
+    pub click: Arc<[f32]>,
+    layer: KeyLayer,
+    shift: ShiftState,
+    last_shift_tap: Option<Instant>,
+    keys: Vec<Key>,
+    hovered: Option<usize>,
+    held: Option<HeldKey>,
+    labels: Option<KeyLabels>,
+    program: MaskedSolidShader,
+}
+
+impl LaserKeyboard {
+    /// `width` meters across, rows scaled to match
+    pub fn new(model: XrMatrix4x4f, width: f32) -> Result<Self, GLErrorWrapper> {
+        let key_pitch = width / ROW_KEYS;
+        let key_style = SdfShapeStyle::solid([0.9, 0.9, 0.9, 1.0]);
+        Ok(Self {
+            model,
+            key_pitch,
+            repeat_delay: Duration::from_millis(500),
+            repeat_interval: Duration::from_millis(80),
+            shift_lock_window: Duration::from_millis(400),
+            density: PanelDensity::default(),
+            raster_scale: RasterScale::new(),
+            text_color: [0.1, 0.1, 0.1, 1.0],
+            background: SdfShapeStyle {
+                fill: [0.2, 0.2, 0.25, 0.9],
+                border_color: [0.5, 0.5, 0.6, 1.0],
+                border_width: 0.005,
+                shadow_color: [0.0, 0.0, 0.0, 0.3],
+                shadow_offset: [0.0, -0.01],
+                shadow_softness: 0.02,
+            },
+            key_style,
+            hover_style: SdfShapeStyle {
+                fill: [1.0, 1.0, 1.0, 1.0],
+                border_color: [0.2, 0.5, 1.0, 1.0],
+                border_width: 0.06 * key_pitch,
+                ..key_style
+            },
+            pressed_style: SdfShapeStyle::solid([0.6, 0.75, 1.0, 1.0]),
+            depth: DepthMode::World,
+            click: key_click(),
+            layer: KeyLayer::Letters,
+            shift: ShiftState::Off,
+            last_shift_tap: None,
+            keys: layout(KeyLayer::Letters),
+            hovered: None,
+            held: None,
+            labels: None,
+            program: MaskedSolidShader::new()?,
+        })
+    }
+
+    pub fn width(&self) -> f32 {
+        ROW_KEYS * self.key_pitch
+    }
+
+    pub fn height(&self) -> f32 {
+        ROWS * self.key_pitch
+    }
+
+    /// the middle of the keyboard, for placing [LaserKeyboard::click]
+    pub fn position(&self) -> XrVector3f {
+        let m = &self.model.m;
+        XrVector3f::new(m[12], m[13], m[14])
+    }
+
+    pub fn layer(&self) -> KeyLayer {
+        self.layer
+    }
+
+    pub fn shift(&self) -> ShiftState {
+        self.shift
+    }
+
+    fn caps(&self) -> bool {
+        self.layer == KeyLayer::Letters && self.shift != ShiftState::Off
+    }
+
+    /// Where a pointer ray hits the keyboard, in its local coordinates, if it does
+    pub fn hit(&self, origin: &XrVector3f, direction: &XrVector3f) -> Option<[f32; 2]> {
+        let [x, y] = plane_hit(&self.model, origin, direction)?;
+        (x.abs() <= 0.5 * self.width() && y.abs() <= 0.5 * self.height()).then_some([x, y])
+    }
+
+    fn key_at_ray(&self, ray: Option<(XrVector3f, XrVector3f)>) -> Option<usize> {
+        let (origin, direction) = ray?;
+        let [x, y] = self.hit(&origin, &direction)?;
+        let (x, y) = (x / self.key_pitch, y / self.key_pitch);
+        self.keys.iter().position(|key| {
+            (x - key.center[0]).abs() <= 0.5 * key.width && (y - key.center[1]).abs() <= 0.5
+        })
+    }
+
+    /// Press, hold and release keys with select.  Call once per frame, with the gestures from
+    /// [crate::gestures::GestureDetector::update], so held keys repeat on time.
+    pub fn handle_gestures(
+        &mut self,
+        gestures: &[Gesture],
+        ray: Option<(XrVector3f, XrVector3f)>,
+        now: Instant,
+    ) -> Vec<Keystroke> {
+        self.hovered = self.key_at_ray(ray);
+        let mut rval = vec![];
+        for gesture in gestures {
+            match gesture {
+                Gesture::Press(GestureButton::Select) => {
+                    if let Some(index) = self.hovered {
+                        self.held = Some(HeldKey {
+                            index,
+                            next_repeat: now + self.repeat_delay,
+                        });
+                        rval.push(self.press(index, now, false));
+                    }
+                }
+                Gesture::Release(GestureButton::Select) => self.held = None,
+                _ => {}
+            }
+        }
+
+        // sliding off the key stops the repeat
+        if self.held.as_ref().map(|held| held.index) != self.hovered {
+            self.held = None;
+        }
+        if let Some(held) = &mut self.held {
+            if now >= held.next_repeat && self.keys[held.index].action.repeats() {
+                // after a stall, carry on from now instead of catching up
+                held.next_repeat = (held.next_repeat + self.repeat_interval).max(now);
+                let index = held.index;
+                rval.push(self.press(index, now, true));
+            }
+        }
+        rval
+    }
+
+    fn press(&mut self, index: usize, now: Instant, repeat: bool) -> Keystroke {
+        let action = match self.keys[index].action {
+            KeyAction::Char(c) if self.caps() => {
+                if self.shift == ShiftState::Once {
+                    self.shift = ShiftState::Off;
+                }
+                KeyAction::Char(c.to_uppercase().next().unwrap_or(c))
+            }
+            KeyAction::Shift => {
+                let double_tap = self
+                    .last_shift_tap
+                    .is_some_and(|last| now.duration_since(last) <= self.shift_lock_window);
+                self.shift = match self.shift {
+                    ShiftState::Once if double_tap => ShiftState::Locked,
+                    ShiftState::Off => ShiftState::Once,
+                    _ => ShiftState::Off,
+                };
+                self.last_shift_tap = Some(now);
+                KeyAction::Shift
+            }
+            KeyAction::Layer => {
+                self.layer = match self.layer {
+                    KeyLayer::Letters => KeyLayer::Symbols,
+                    KeyLayer::Symbols => KeyLayer::Letters,
+                };
+                self.keys = layout(self.layer);
+                self.held = None;
+                self.hovered = None;
+                KeyAction::Layer
+            }
+            action => action,
+        };
+        Keystroke { action, repeat }
+    }
+
+    /// meters
+    fn line_height(&self) -> f32 {
+        0.45 * self.key_pitch
+    }
+
+    /// pixels; the size glyphs are rasterized at, not how big they look, before
+    /// [LaserKeyboard::raster_scale]
+    pub fn font_size(&self) -> f32 {
+        self.density.font_size(self.line_height())
+    }
+
+    /// like [crate::ui::scroll::ScrollView::track_eye]
+    pub fn track_eye(&mut self, eye: &XrVector3f, pixels_per_radian: f32) {
+        let apparent = apparent_pixels(&self.model, self.line_height(), eye, pixels_per_radian);
+        self.raster_scale.update(apparent, self.font_size());
+    }
+
+    /// Lay the key legends out again if the layer or shift changed, the glyph atlas was
+    /// flushed or the font size changed.  Call this every frame before drawing.
+    pub fn update(
+        &mut self,
+        glyphs: &mut GlyphCache,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let font_size = self.font_size() * self.raster_scale.factor();
+        let caps = self.caps();
+        if let Some(labels) = &self.labels {
+            let stale = labels.labels.iter().any(|label| label.is_stale(glyphs));
+            if !stale
+                && labels.layer == self.layer
+                && labels.caps == caps
+                && labels.font_size == font_size
+            {
+                return Ok(());
+            }
+        }
+
+        let mut labels = Vec::with_capacity(self.keys.len());
+        for key in &self.keys {
+            labels.push(Label::new(
+                &legend(key.action, self.layer, caps),
+                font_size,
+                self.line_height(),
+                (key.width - 0.2) * self.key_pitch,
+                glyphs,
+                &self.program,
+                gpu_state,
+            )?);
+        }
+        self.labels = Some(KeyLabels {
+            labels,
+            layer: self.layer,
+            caps,
+            font_size,
+        });
+        Ok(())
+    }
+
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        glyphs: &GlyphCache,
+        shapes: &ShapePainter,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let matrix = *matrix_pv * self.model;
+        let material = Material::default().with_depth(self.depth);
+        shapes.draw(
+            &matrix,
+            &SdfShape::RoundedRect {
+                half_width: 0.5 * self.width() + 0.2 * self.key_pitch,
+                half_height: 0.5 * self.height() + 0.2 * self.key_pitch,
+                corner_radius: 0.3 * self.key_pitch,
+            },
+            &self.background,
+            &material,
+            gpu_state,
+        )?;
+
+        let labels = self.labels.as_ref().map(|labels| &labels.labels[..]);
+        let half_height = 0.45 * self.key_pitch;
+        for (i, key) in self.keys.iter().enumerate() {
+            let style = if self.held.as_ref().is_some_and(|held| held.index == i) {
+                &self.pressed_style
+            } else if self.hovered == Some(i)
+                || (key.action == KeyAction::Shift && self.shift != ShiftState::Off)
+            {
+                &self.hover_style
+            } else {
+                &self.key_style
+            };
+            let [x, y] = key.center.map(|c| c * self.key_pitch);
+            let key_matrix = matrix * xr_matrix4x4f_create_translation(x, y, 0.001);
+            shapes.draw(
+                &key_matrix,
+                &SdfShape::RoundedRect {
+                    half_width: 0.5 * key.width * self.key_pitch - 0.05 * self.key_pitch,
+                    half_height,
+                    corner_radius: 0.2 * self.key_pitch,
+                },
+                style,
+                &material,
+                gpu_state,
+            )?;
+
+            if let Some(label) = labels.and_then(|labels| labels.get(i)) {
+                let text_matrix =
+                    key_matrix * xr_matrix4x4f_create_translation(-0.5 * label.width, 0.0, 0.001);
+                label.draw(
+                    &text_matrix,
+                    glyphs,
+                    &self.program,
+                    &self.text_color,
+                    &material,
+                    gpu_state,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// the keys of `layer`, rows centered on the keyboard's middle
+fn layout(layer: KeyLayer) -> Vec<Key> {
+    let (rows, corner, comma) = match layer {
+        KeyLayer::Letters => (LETTER_ROWS, KeyAction::Shift, ','),
+        KeyLayer::Symbols => (SYMBOL_ROWS, KeyAction::Char('_'), '/'),
+    };
+    let chars = |row: &str| -> Vec<(KeyAction, f32)> {
+        row.chars().map(|c| (KeyAction::Char(c), 1.0)).collect()
+    };
+
+    let mut third = vec![(corner, 1.5)];
+    third.extend(chars(rows[2]));
+    third.push((KeyAction::Backspace, 1.5));
+    let bottom = vec![
+        (KeyAction::Layer, 1.5),
+        (KeyAction::Char(comma), 1.0),
+        (KeyAction::Space, 5.0),
+        (KeyAction::Char('.'), 1.0),
+        (KeyAction::Enter, 1.5),
+    ];
+
+    let mut keys = vec![];
+    for (r, row) in [chars(rows[0]), chars(rows[1]), third, bottom]
+        .iter()
+        .enumerate()
+    {
+        let y = 0.5 * (ROWS - 1.0) - r as f32;
+        let mut x = -0.5 * row.iter().map(|(_, width)| width).sum::<f32>();
+        for &(action, width) in row {
+            keys.push(Key {
+                action,
+                center: [x + 0.5 * width, y],
+                width,
+            });
+            x += width;
+        }
+    }
+    keys
+}
+
+fn legend(action: KeyAction, layer: KeyLayer, caps: bool) -> String {
+    match action {
+        KeyAction::Char(c) if caps => c.to_uppercase().collect(),
+        KeyAction::Char(c) => c.to_string(),
+        KeyAction::Space => String::new(),
+        KeyAction::Backspace => "del".to_string(),
+        KeyAction::Enter => "enter".to_string(),
+        KeyAction::Shift => "shift".to_string(),
+        KeyAction::Layer if layer == KeyLayer::Letters => "?123".to_string(),
+        KeyAction::Layer => "abc".to_string(),
+    }
+}
+
+/// a dozen milliseconds of a fading 2 kHz tone
+fn key_click() -> Arc<[f32]> {
+    let rate = SAMPLE_RATE as f32;
+    (0..SAMPLE_RATE as usize * 12 / 1000)
+        .map(|i| {
+            let t = i as f32 / rate;
+            0.3 * (-400.0 * t).exp() * (TAU * 2000.0 * t).sin()
+        })
+        .collect()
+}
@@ -0,0 +1,104 @@
+//! Text that does not change, laid out once from the shared [GlyphCache].
+
+use crate::text_painting::GlyphCache;
+use bob_shaders::masked_solid_shader::MaskedSolidShader;
+use bob_shaders::material::Material;
+use gl::types::{GLfloat, GLsizei, GLushort};
+use gl_thin::gl_fancy::{GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::XrMatrix4x4f;
+
+pub struct Label {
+    buffers: VertexBufferBundle<'static, GLfloat, GLushort>,
+    /// meters, after dropping whatever did not fit
+    pub width: f32,
+    glyph_generation: u32,
+}
+
+impl Label {
+    /// Lay out `text` starting at x=0, vertically centered on y=0, with lines `line_height` meters tall.
+    /// Glyphs past `max_width` are left off.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        text: &str,
+        font_size: f32,
+        line_height: f32,
+        max_width: f32,
+        glyphs: &mut GlyphCache,
+        program: &MaskedSolidShader,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let (ascent, descent) = glyphs.line_metrics(font_size);
+        let scale = line_height / (ascent - descent);
+        let top = 0.5 * line_height;
+
+        let (quads, _) = glyphs.layout(text, font_size, gpu_state)?;
+
+        let mut xyzuv = vec![];
+        let mut indices = vec![];
+        let mut width: f32 = 0.0;
+        for quad in &quads {
+            let (x0, x1) = (quad.x0 * scale, quad.x1 * scale);
+            if x1 > max_width {
+                break;
+            }
+            width = width.max(x1);
+            let (y0, y1) = (top - quad.y0 * scale, top - quad.y1 * scale);
+            let base = (xyzuv.len() / 5) as GLushort;
+            let uv = &quad.uv;
+            #[rustfmt::skip]
+            xyzuv.extend_from_slice(&[
+                x0, y1, 0.0, uv.u0, uv.v1,
+                x1, y1, 0.0, uv.u1, uv.v1,
+                x0, y0, 0.0, uv.u0, uv.v0,
+                x1, y0, 0.0, uv.u1, uv.v0,
+            ]);
+            indices.extend_from_slice(&[base, base + 1, base + 2, base + 2, base + 1, base + 3]);
+        }
+
+        let buffers = VertexBufferBundle::new(
+            gpu_state,
+            xyzuv.into(),
+            indices.into(),
+            3 + 2,
+            &[(program.sal_position, 3, 0), (program.sal_tex_coord, 2, 3)],
+        )?;
+
+        Ok(Self {
+            buffers,
+            width,
+            glyph_generation: glyphs.generation(),
+        })
+    }
+
+    /// the glyph atlas was flushed since this was laid out, so its texture coordinates are garbage
+    pub fn is_stale(&self, glyphs: &GlyphCache) -> bool {
+        self.glyph_generation != glyphs.generation()
+    }
+
+    pub fn draw(
+        &self,
+        matrix: &XrMatrix4x4f,
+        glyphs: &GlyphCache,
+        program: &MaskedSolidShader,
+        color: &[f32; 4],
+        material: &Material,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        if self.buffers.index_count == 0 {
+            return Ok(());
+        }
+        let [r, g, b, _] = *color;
+        program.draw(
+            matrix,
+            &glyphs.atlas.texture,
+            color,
+            Some(&[r, g, b, 0.0]),
+            material,
+            gl::TRIANGLES,
+            &self.buffers,
+            self.buffers.index_count as GLsizei,
+            gpu_state,
+        )
+    }
+}
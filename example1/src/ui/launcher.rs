@@ -0,0 +1,106 @@
+//! A panel listing the scenes of a [SceneStack], to pick one with the controller.  Each row
+//! has the scene's name and, once it has one, a thumbnail of the scene at its right end.
+
+use crate::scene_stack::SceneStack;
+use crate::textured_quad::TexturedQuad;
+use crate::ui::scroll::{ScrollLayout, ScrollView};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::{GLErrorWrapper, TextureWithTarget};
+use gl_thin::linear::{xr_matrix4x4f_create_translation, XrMatrix4x4f, XrVector3f};
+
+/// width over height of the thumbnails
+pub const THUMBNAIL_ASPECT: f32 = 16.0 / 9.0;
+
+pub struct SceneLauncher {
+    /// the scene names, in [SceneStack::entries] order
+    pub view: ScrollView,
+    /// indexed like the items
+    thumbnails: Vec<Option<TexturedQuad>>,
+    visible: bool,
+}
+
+impl SceneLauncher {
+    pub fn new(model: XrMatrix4x4f, stack: &SceneStack) -> Result<Self, GLErrorWrapper> {
+        let mut view = ScrollView::new(model, 0.6, 0.5, ScrollLayout::List);
+        view.item_height = 0.1;
+        let mut launcher = Self {
+            view,
+            thumbnails: vec![],
+            visible: false,
+        };
+        launcher.refresh(stack);
+        Ok(launcher)
+    }
+
+    /// List the scenes registered since, keeping the thumbnails of the ones already listed.
+    pub fn refresh(&mut self, stack: &SceneStack) {
+        let names: Vec<String> = stack.entries().iter().map(|e| e.name.clone()).collect();
+        if names != self.view.items() {
+            self.thumbnails.resize_with(names.len(), || None);
+            self.view.set_items(names);
+        }
+    }
+
+    /// Show `texture` (see [THUMBNAIL_ASPECT]) in scene `index`'s row.
+    pub fn set_thumbnail(
+        &mut self,
+        index: usize,
+        texture: TextureWithTarget,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let Some(slot) = self.thumbnails.get_mut(index) else {
+            return Ok(());
+        };
+        let half_height = 0.4 * self.view.item_height;
+        *slot = Some(TexturedQuad::new(
+            gpu_state,
+            half_height * THUMBNAIL_ASPECT,
+            half_height,
+            texture,
+        )?);
+        Ok(())
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// the scene a pointer ray (origin, direction) is on, while the panel shows
+    pub fn item_at_ray(&self, origin: &XrVector3f, direction: &XrVector3f) -> Option<usize> {
+        if !self.visible {
+            return None;
+        }
+        self.view
+            .hit(origin, direction)
+            .and_then(|point| self.view.item_at(point))
+    }
+
+    /// The thumbnails, over the rows [ScrollView::draw] drew.  They can't be clipped to the
+    /// panel like the text is, so a row's thumbnail only shows while all of the row is inside.
+    pub fn draw_thumbnails(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let view = &self.view;
+        let half_height = 0.4 * view.item_height;
+        let half_width = half_height * THUMBNAIL_ASPECT;
+        for index in view.visible_items() {
+            let Some(Some(thumbnail)) = self.thumbnails.get(index) else {
+                continue;
+            };
+            let [x, y] = view.item_center(index);
+            if y.abs() + 0.5 * view.item_height > 0.5 * view.height {
+                continue;
+            }
+            let x = x + 0.5 * view.cell_width() - 0.1 * view.item_height - half_width;
+            let matrix = *matrix_pv * view.model * xr_matrix4x4f_create_translation(x, y, 0.002);
+            thumbnail.paint_quad(&matrix, gpu_state)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,434 @@
+//! Building blocks for in-headset user interface panels.
+
+use crate::android_clipboard::{clipboard_text, set_clipboard_text};
+use crate::gestures::{Gesture, GestureButton};
+use crate::minimap::Minimap;
+use crate::text_painting::GlyphCache;
+use android_activity::AndroidApp;
+use bob_shaders::masked_solid_shader::MaskedSolidShader;
+use bob_shaders::material::Material;
+use developer_menu::DeveloperMenu;
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::{XrMatrix4x4f, XrVector3f};
+use gl_thin::render_queue::{RenderLayer, RenderQueue};
+use hud::Hud;
+use icons::IconAtlas;
+use keyboard::{Keystroke, LaserKeyboard};
+use launcher::SceneLauncher;
+use pointer::PointerCursor;
+use ruler::Ruler;
+use scroll::ScrollView;
+use shapes::ShapePainter;
+use std::time::Instant;
+use text_field::{TextField, TextFieldEvent, TextInput};
+use units::{PanelDensity, QUEST2_PIXELS_PER_RADIAN};
+use wrist_menu::WristMenu;
+
+pub mod developer_menu;
+pub mod hud;
+pub mod icons;
+pub mod keyboard;
+pub mod label;
+pub mod launcher;
+pub mod pointer;
+pub mod raster_scale;
+pub mod ruler;
+pub mod script;
+pub mod scroll;
+pub mod shapes;
+pub mod text_field;
+pub mod units;
+pub mod wrist_menu;
+
+/// The resources every widget draws with, and the widgets themselves
+pub struct Ui {
+    pub glyphs: GlyphCache,
+    pub icons: IconAtlas,
+    pub shapes: ShapePainter,
+    /// draws the scroll views' labels
+    pub text_program: MaskedSolidShader,
+    pub text_fields: Vec<TextField>,
+    pub scroll_views: Vec<ScrollView>,
+    /// see [DeveloperMenu::refresh]
+    pub developer_menu: Option<DeveloperMenu>,
+    /// see [WristMenu::update]
+    pub wrist_menu: Option<WristMenu>,
+    /// see [crate::drawcore::ActiveRenderer::enable_launcher]
+    pub launcher: Option<SceneLauncher>,
+    /// types into the focused text field; shows while one is focused, see
+    /// [crate::drawcore::ActiveRenderer::enable_laser_keyboard]
+    pub keyboard: Option<LaserKeyboard>,
+    /// head-locked text, see [Hud::queue_draws]
+    pub hud: Option<Hud>,
+    /// see [crate::drawcore::ActiveRenderer::enable_ruler]
+    pub ruler: Option<Ruler>,
+    /// see [crate::drawcore::ActiveRenderer::enable_minimap]
+    pub minimap: Option<Minimap>,
+    /// where the controller points at a panel
+    pub pointer: PointerCursor,
+    /// of the eye buffers near the middle of the view, for [Ui::track_eye]
+    pub pixels_per_radian: f32,
+    /// the panel being scrolled with the pointer, an index into [Ui::panels]
+    dragging: Option<usize>,
+    /// of the last [Ui::update]
+    now: Instant,
+}
+
+impl Ui {
+    pub fn new(gpu_state: &mut GPUState) -> Result<Self, GLErrorWrapper> {
+        Ok(Self {
+            // room for the 4x glyphs of a panel seen up close, see [raster_scale]
+            glyphs: GlyphCache::new(2048, gpu_state)?,
+            icons: IconAtlas::with_builtin_icons(256, 48, gpu_state)?,
+            shapes: ShapePainter::new(gpu_state)?,
+            text_program: MaskedSolidShader::new()?,
+            text_fields: vec![],
+            scroll_views: vec![],
+            developer_menu: None,
+            wrist_menu: None,
+            launcher: None,
+            keyboard: None,
+            hud: None,
+            ruler: None,
+            minimap: None,
+            pointer: PointerCursor::new(),
+            pixels_per_radian: QUEST2_PIXELS_PER_RADIAN,
+            dragging: None,
+            now: Instant::now(),
+        })
+    }
+
+    /// Once per frame, before any view is drawn.  `now` is the frame's time: the caret
+    /// blink, scroll coasting and key repeat all run on it rather than the wall clock, so a
+    /// [script::UiScriptRunner] can replay them exactly.
+    pub fn update(&mut self, now: Instant, gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper> {
+        self.now = now;
+        for field in &mut self.text_fields {
+            field.update(now, &mut self.glyphs, gpu_state)?;
+        }
+        for view in &mut self.scroll_views {
+            view.update(now, &mut self.glyphs, &self.text_program, gpu_state)?;
+        }
+        if let Some(menu) = &mut self.developer_menu {
+            menu.view
+                .update(now, &mut self.glyphs, &self.text_program, gpu_state)?;
+        }
+        if let Some(menu) = &mut self.wrist_menu {
+            menu.view
+                .update(now, &mut self.glyphs, &self.text_program, gpu_state)?;
+        }
+        if let Some(launcher) = &mut self.launcher {
+            launcher
+                .view
+                .update(now, &mut self.glyphs, &self.text_program, gpu_state)?;
+        }
+        if let Some(keyboard) = &mut self.keyboard {
+            keyboard.update(&mut self.glyphs, gpu_state)?;
+        }
+        if let Some(hud) = &mut self.hud {
+            hud.update(&mut self.glyphs, gpu_state)?;
+        }
+        if let Some(ruler) = &mut self.ruler {
+            ruler.update(&mut self.glyphs, gpu_state)?;
+        }
+        Ok(())
+    }
+
+    /// Use `density` for every widget, e.g. [PanelDensity::for_headset] once the eye buffer
+    /// size is known, so text comes out the same number of pixels tall on any headset.
+    pub fn set_density(&mut self, density: PanelDensity) {
+        for field in &mut self.text_fields {
+            field.density = density;
+        }
+        let menus = [
+            self.developer_menu.as_mut().map(|menu| &mut menu.view),
+            self.wrist_menu.as_mut().map(|menu| &mut menu.view),
+            self.launcher.as_mut().map(|launcher| &mut launcher.view),
+        ];
+        for view in self
+            .scroll_views
+            .iter_mut()
+            .chain(menus.into_iter().flatten())
+        {
+            view.density = density;
+        }
+        if let Some(keyboard) = &mut self.keyboard {
+            keyboard.density = density;
+        }
+        if let Some(hud) = &mut self.hud {
+            hud.density = density;
+        }
+        if let Some(ruler) = &mut self.ruler {
+            ruler.density = density;
+        }
+    }
+
+    /// Pick how finely each widget's text is rasterized from how far it is from `eye`, so
+    /// it stays crisp up close.  Once per frame; the text is laid out again at the next
+    /// [Ui::update].
+    pub fn track_eye(&mut self, eye: &XrVector3f) {
+        let pixels_per_radian = self.pixels_per_radian;
+        for field in &mut self.text_fields {
+            field.track_eye(eye, pixels_per_radian);
+        }
+        for view in self.panels_mut() {
+            view.track_eye(eye, pixels_per_radian);
+        }
+        if let Some(keyboard) = &mut self.keyboard {
+            keyboard.track_eye(eye, pixels_per_radian);
+        }
+    }
+
+    /// Draw every widget, each in the [gl_thin::render_queue::RenderLayer] its
+    /// [bob_shaders::material::DepthMode] asks for, so the always-on-top ones come last.
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let mut queue = RenderQueue::new();
+        self.queue_draws(&mut queue, *matrix_pv);
+        queue.execute(gpu_state)
+    }
+
+    /// like [Ui::draw], into a queue shared with the rest of the frame
+    pub fn queue_draws<'a>(&'a self, queue: &mut RenderQueue<'a>, matrix_pv: XrMatrix4x4f) {
+        for field in &self.text_fields {
+            queue.add(field.depth.render_layer(), 0, move |gpu_state| {
+                field.draw(
+                    &matrix_pv,
+                    &self.glyphs,
+                    &self.shapes,
+                    &Material::default().with_depth(field.depth),
+                    gpu_state,
+                )
+            });
+        }
+        for view in self.panels() {
+            queue.add(view.depth.render_layer(), 0, move |gpu_state| {
+                view.draw(
+                    &matrix_pv,
+                    &self.glyphs,
+                    &self.shapes,
+                    &self.text_program,
+                    gpu_state,
+                )
+            });
+        }
+        if let Some(keyboard) = self.shown_keyboard() {
+            queue.add(keyboard.depth.render_layer(), 0, move |gpu_state| {
+                keyboard.draw(&matrix_pv, &self.glyphs, &self.shapes, gpu_state)
+            });
+        }
+        if let Some(launcher) = self.launcher.as_ref().filter(|l| l.is_visible()) {
+            // over its rows
+            queue.add(launcher.view.depth.render_layer(), 1, move |gpu_state| {
+                launcher.draw_thumbnails(&matrix_pv, gpu_state)
+            });
+        }
+        if let Some(ruler) = &self.ruler {
+            queue.add(RenderLayer::Opaque, 0, move |gpu_state| {
+                ruler.draw_line(&matrix_pv, gpu_state)
+            });
+            queue.add(RenderLayer::Overlay, 0, move |gpu_state| {
+                ruler.draw_label(&matrix_pv, &self.glyphs, gpu_state)
+            });
+        }
+        if let Some(minimap) = self.minimap.as_ref().filter(|map| map.is_visible()) {
+            queue.add(RenderLayer::Opaque, 0, move |gpu_state| {
+                minimap.draw(&matrix_pv, gpu_state)
+            });
+        }
+        if self.pointer.is_visible() {
+            // after the panels it lies on
+            queue.add(self.pointer.depth.render_layer(), 1, move |gpu_state| {
+                self.pointer.draw(&matrix_pv, &self.shapes, gpu_state)
+            });
+        }
+    }
+
+    /// Move the [Ui::pointer] to where a pointer ray (origin, direction) first meets the
+    /// keyboard, a scroll view or the minimap, or hide it.
+    pub fn update_pointer(&mut self, ray: Option<(XrVector3f, XrVector3f)>) {
+        let hit = ray.and_then(|(origin, direction)| {
+            let keyboard = self
+                .shown_keyboard()
+                .and_then(|keyboard| Some((keyboard.model, keyboard.hit(&origin, &direction)?)));
+            let minimap = || {
+                let minimap = self.minimap.as_ref()?;
+                Some((minimap.model?, minimap.hit(&origin, &direction)?))
+            };
+            keyboard
+                .or_else(|| {
+                    self.panels()
+                        .find_map(|view| Some((view.model, view.hit(&origin, &direction)?)))
+                })
+                .or_else(minimap)
+        });
+        self.pointer
+            .set_hit(hit.as_ref().map(|(model, point)| (model, *point)));
+    }
+
+    /// Type on the [Ui::keyboard] and scroll panels by dragging them: pressing select on a
+    /// panel grabs it, moving the ray moves the content and letting go flings it.  Call once
+    /// per frame with the gestures from [crate::gestures::GestureDetector::update].  Returns
+    /// the keystrokes, for feedback, with what each did to the focused text field.
+    pub fn handle_gestures(
+        &mut self,
+        gestures: &[Gesture],
+        ray: Option<(XrVector3f, XrVector3f)>,
+    ) -> Vec<(Keystroke, Option<(usize, TextFieldEvent)>)> {
+        let typing = self.is_typing();
+        let now = self.now;
+        let mut on_keyboard = false;
+        let mut keystrokes = vec![];
+        if let Some(keyboard) = self.keyboard.as_mut().filter(|_| typing) {
+            on_keyboard =
+                ray.is_some_and(|(origin, direction)| keyboard.hit(&origin, &direction).is_some());
+            keystrokes = keyboard.handle_gestures(gestures, ray, now);
+        }
+        let keystrokes = keystrokes
+            .into_iter()
+            .map(|keystroke| {
+                let event = keystroke
+                    .action
+                    .input()
+                    .and_then(|input| self.handle_input(input));
+                (keystroke, event)
+            })
+            .collect();
+
+        let hit_y = |view: &ScrollView| {
+            let (origin, direction) = ray?;
+            view.hit(&origin, &direction).map(|[_, y]| y)
+        };
+        for gesture in gestures {
+            match gesture {
+                Gesture::Press(GestureButton::Select) if on_keyboard => {}
+                Gesture::Press(GestureButton::Select) => {
+                    let grabbed = self.panels().position(|view| hit_y(view).is_some());
+                    self.dragging = grabbed;
+                    if let Some(view) = self.dragged_panel() {
+                        if let Some(y) = hit_y(view) {
+                            view.scroll.drag_begin(y, now);
+                        }
+                    }
+                }
+                Gesture::Release(GestureButton::Select) => {
+                    if let Some(view) = self.dragged_panel() {
+                        view.scroll.drag_end();
+                    }
+                    self.dragging = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(view) = self.dragged_panel() {
+            // off the panel, the content stays where it was
+            if let Some(y) = hit_y(view) {
+                view.scroll.drag_to(y, now);
+            }
+        }
+        keystrokes
+    }
+
+    /// true while select is scrolling a panel rather than acting on the world
+    pub fn is_dragging(&self) -> bool {
+        self.dragging.is_some()
+    }
+
+    /// a text field has focus, so the [Ui::keyboard] shows
+    pub fn is_typing(&self) -> bool {
+        self.text_fields.iter().any(|field| field.is_focused())
+    }
+
+    fn shown_keyboard(&self) -> Option<&LaserKeyboard> {
+        self.keyboard.as_ref().filter(|_| self.is_typing())
+    }
+
+    fn dragged_panel(&mut self) -> Option<&mut ScrollView> {
+        let index = self.dragging?;
+        self.panels_mut().nth(index)
+    }
+
+    /// the scroll views showing this frame, including the menus' and the launcher's
+    fn panels(&self) -> impl Iterator<Item = &ScrollView> {
+        let developer = self.developer_menu.as_ref().map(|menu| &menu.view);
+        let wrist = self
+            .wrist_menu
+            .as_ref()
+            .filter(|menu| menu.is_visible())
+            .map(|menu| &menu.view);
+        let launcher = self
+            .launcher
+            .as_ref()
+            .filter(|launcher| launcher.is_visible())
+            .map(|launcher| &launcher.view);
+        self.scroll_views
+            .iter()
+            .chain(developer)
+            .chain(wrist)
+            .chain(launcher)
+    }
+
+    fn panels_mut(&mut self) -> impl Iterator<Item = &mut ScrollView> {
+        let developer = self.developer_menu.as_mut().map(|menu| &mut menu.view);
+        let wrist = self
+            .wrist_menu
+            .as_mut()
+            .filter(|menu| menu.is_visible())
+            .map(|menu| &mut menu.view);
+        let launcher = self
+            .launcher
+            .as_mut()
+            .filter(|launcher| launcher.is_visible())
+            .map(|launcher| &mut launcher.view);
+        self.scroll_views
+            .iter_mut()
+            .chain(developer)
+            .chain(wrist)
+            .chain(launcher)
+    }
+
+    /// Send typing to text field `index` (and stop sending it to any other)
+    pub fn focus_text_field(&mut self, index: usize, app: &AndroidApp) {
+        for (i, field) in self.text_fields.iter_mut().enumerate() {
+            if i != index && field.is_focused() {
+                field.blur(app);
+            }
+        }
+        if let Some(field) = self.text_fields.get_mut(index) {
+            field.focus(app);
+        }
+    }
+
+    /// put the focused text field's contents on the system clipboard
+    pub fn copy_focused(&self, app: &AndroidApp) -> Result<(), jni::errors::Error> {
+        match self.text_fields.iter().find(|field| field.is_focused()) {
+            Some(field) => set_clipboard_text(app, "text", field.text()),
+            None => Ok(()),
+        }
+    }
+
+    /// type the clipboard's text into the focused text field
+    pub fn paste_into_focused(
+        &mut self,
+        app: &AndroidApp,
+    ) -> Result<Option<(usize, TextFieldEvent)>, jni::errors::Error> {
+        Ok(match clipboard_text(app)? {
+            Some(text) => self.handle_input(TextInput::Insert(text)),
+            None => None,
+        })
+    }
+
+    /// pass keyboard input to the focused text field, if any
+    pub fn handle_input(&mut self, input: TextInput) -> Option<(usize, TextFieldEvent)> {
+        let (index, field) = self
+            .text_fields
+            .iter_mut()
+            .enumerate()
+            .find(|(_, field)| field.is_focused())?;
+        field.handle_input(input).map(|event| (index, event))
+    }
+}
@@ -0,0 +1,75 @@
+//! The dot showing where the controller's ray meets a panel.
+
+use crate::ui::shapes::ShapePainter;
+use bob_shaders::material::{DepthMode, Material};
+use bob_shaders::sdf_shape_shader::{SdfShape, SdfShapeStyle};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::{xr_matrix4x4f_create_translation, XrMatrix4x4f};
+
+pub struct PointerCursor {
+    /// in meters
+    pub radius: f32,
+    pub style: SdfShapeStyle,
+    /// [DepthMode::SOFT] by default: over the panel it lies on, under a hand in front of it
+    pub depth: DepthMode,
+    /// where the cursor is drawn, see [PointerCursor::set_hit]
+    model: Option<XrMatrix4x4f>,
+}
+
+impl Default for PointerCursor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PointerCursor {
+    pub fn new() -> Self {
+        Self {
+            radius: 0.006,
+            style: SdfShapeStyle {
+                fill: [1.0, 1.0, 1.0, 0.9],
+                border_color: [0.2, 0.5, 1.0, 1.0],
+                border_width: 0.0015,
+                shadow_color: [0.0; 4],
+                shadow_offset: [0.0; 2],
+                shadow_softness: 0.0,
+            },
+            depth: DepthMode::SOFT,
+            model: None,
+        }
+    }
+
+    /// `panel` is the model matrix of the panel the ray hit and `point` where, in the panel's
+    /// local coordinates (e.g. from [crate::ui::scroll::ScrollView::hit]).  `None` hides the
+    /// cursor.
+    pub fn set_hit(&mut self, hit: Option<(&XrMatrix4x4f, [f32; 2])>) {
+        // just above the items and their labels, see [crate::ui::scroll::ScrollView::draw]
+        self.model =
+            hit.map(|(panel, [x, y])| *panel * xr_matrix4x4f_create_translation(x, y, 0.003));
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.model.is_some()
+    }
+
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        shapes: &ShapePainter,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let Some(model) = &self.model else {
+            return Ok(());
+        };
+        shapes.draw(
+            &(*matrix_pv * *model),
+            &SdfShape::Circle {
+                radius: self.radius,
+            },
+            &self.style,
+            &Material::default().with_depth(self.depth),
+            gpu_state,
+        )
+    }
+}
@@ -0,0 +1,66 @@
+//! Rasterizing text bigger as the viewer comes closer.  Glyphs rasterized for a panel seen
+//! from across the room turn to mush a hand's width away, and ones rasterized for close up
+//! shimmer and waste atlas space from afar.  A [RasterScale] picks 1x, 2x or 4x of a widget's
+//! `font_size` from how many pixels a line of its text actually covers.
+
+use gl_thin::linear::{XrMatrix4x4f, XrVector3f};
+
+const MAX_FACTOR: f32 = 4.0;
+
+/// how far past a step the apparent size has to go before the factor changes, so text
+/// hovering around a boundary is not rasterized again every frame
+const HYSTERESIS: f32 = 1.25;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RasterScale {
+    factor: f32,
+}
+
+impl RasterScale {
+    pub fn new() -> Self {
+        Self { factor: 1.0 }
+    }
+
+    /// 1, 2 or 4
+    pub fn factor(&self) -> f32 {
+        self.factor
+    }
+
+    /// `apparent` is how many pixels tall a line of text is in the eye buffer, `font_size`
+    /// what it is rasterized at for 1x.  True when the factor changed, and the text has to
+    /// be laid out again at `font_size * factor()`.
+    pub fn update(&mut self, apparent: f32, font_size: f32) -> bool {
+        let ideal = apparent / font_size;
+        let mut factor = self.factor;
+        while factor < MAX_FACTOR && ideal > factor * HYSTERESIS {
+            factor *= 2.0;
+        }
+        while factor > 1.0 && ideal < 0.5 * factor / HYSTERESIS {
+            factor *= 0.5;
+        }
+        let changed = factor != self.factor;
+        self.factor = factor;
+        changed
+    }
+}
+
+impl Default for RasterScale {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How many pixels tall `height` meters of a widget at `model`'s origin look from `eye`.
+/// Good enough for picking a [RasterScale]; it ignores the panel's tilt and lens distortion.
+pub fn apparent_pixels(
+    model: &XrMatrix4x4f,
+    height: f32,
+    eye: &XrVector3f,
+    pixels_per_radian: f32,
+) -> f32 {
+    let dx = model.m[12] - eye.x;
+    let dy = model.m[13] - eye.y;
+    let dz = model.m[14] - eye.z;
+    let distance = (dx * dx + dy * dy + dz * dz).sqrt().max(0.01);
+    2.0 * (0.5 * height / distance).atan() * pixels_per_radian
+}
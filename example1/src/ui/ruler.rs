@@ -0,0 +1,266 @@
+//! A tape measure for the room.  Pulling the trigger drops the first end at the tip of the
+//! controller, a line then stretches from it to the controller with the length floating
+//! above its middle, and pulling the trigger again drops the second end.  The next pull
+//! starts over.  Lengths are real ones: with a [WorldScale] other than 1 the world meters
+//! between the ends are converted back before they are shown.
+
+use crate::debug_draw::{DebugLines, CYAN, GREEN};
+use crate::text_painting::GlyphCache;
+use crate::ui::label::Label;
+use crate::ui::units::PanelDensity;
+use bob_shaders::masked_solid_shader::MaskedSolidShader;
+use bob_shaders::material::{DepthMode, Material};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::{
+    xr_matrix4x4f_create_from_quaternion, xr_matrix4x4f_create_translation,
+    xr_matrix4x4f_create_translation_v, xr_vector3f_dot, XrMatrix4x4f, XrQuaternionf, XrVector3f,
+};
+use gl_thin::world_scale::WorldScale;
+
+/// how far the trigger goes down to count as a pull, and back up to count as let go
+const PULL: f32 = 0.6;
+const LET_GO: f32 = 0.3;
+/// meters along the pointer ray from the controller to where the ends are dropped
+const TIP: f32 = 0.06;
+/// meters from the line up to the label
+const LABEL_RAISE: f32 = 0.03;
+
+pub struct Ruler {
+    /// meters
+    pub line_height: f32,
+    pub density: PanelDensity,
+    pub color: [f32; 4],
+    start: Option<XrVector3f>,
+    /// the other end, following the controller until it is dropped
+    end: Option<XrVector3f>,
+    dropped: bool,
+    pulled: bool,
+    /// where the label turns to face
+    eye: Option<XrVector3f>,
+    /// the ends are in the world, the label is in real meters
+    world_scale: WorldScale,
+    text: String,
+    /// the line and the label need laying out again at the next [Ruler::update]
+    dirty: bool,
+    lines: DebugLines,
+    /// `None` until laid out by [Ruler::update]
+    label: Option<Label>,
+    program: MaskedSolidShader,
+}
+
+impl Ruler {
+    pub fn new() -> Result<Self, GLErrorWrapper> {
+        Ok(Self {
+            line_height: 0.03,
+            density: PanelDensity::default(),
+            color: [1.0, 1.0, 1.0, 1.0],
+            start: None,
+            end: None,
+            dropped: false,
+            pulled: false,
+            eye: None,
+            world_scale: WorldScale::default(),
+            text: String::new(),
+            dirty: false,
+            lines: DebugLines::new()?,
+            label: None,
+            program: MaskedSolidShader::new()?,
+        })
+    }
+
+    /// Once per frame, with how far the trigger is down (0..1), the controller's pointer ray
+    /// (origin, direction) and where the head is, all in the world, and the
+    /// [WorldScale] that put them there.
+    pub fn handle_input(
+        &mut self,
+        trigger: f32,
+        ray: Option<(XrVector3f, XrVector3f)>,
+        eye: Option<XrVector3f>,
+        world_scale: &WorldScale,
+    ) {
+        if world_scale.factor != self.world_scale.factor {
+            self.dirty = true;
+        }
+        self.world_scale = *world_scale;
+        let was_pulled = self.pulled;
+        self.pulled = if was_pulled {
+            trigger > LET_GO
+        } else {
+            trigger >= PULL
+        };
+        self.eye = eye;
+        let Some((origin, direction)) = ray else {
+            return;
+        };
+        let tip = origin + direction * TIP;
+
+        if self.pulled && !was_pulled {
+            if self.start.is_none() || self.dropped {
+                self.start = Some(tip);
+                self.end = None;
+                self.dropped = false;
+            } else {
+                self.end = Some(tip);
+                self.dropped = true;
+            }
+            self.dirty = true;
+        } else if self.start.is_some() && !self.dropped {
+            self.end = Some(tip);
+            self.dirty = true;
+        }
+    }
+
+    /// Forget the measurement.
+    pub fn clear(&mut self) {
+        self.start = None;
+        self.end = None;
+        self.dropped = false;
+        self.dirty = true;
+    }
+
+    /// the measured length in real meters, once both ends are down
+    pub fn measurement(&self) -> Option<f32> {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) if self.dropped => Some(self.length(&start, &end)),
+            _ => None,
+        }
+    }
+
+    /// real meters between two world points
+    fn length(&self, a: &XrVector3f, b: &XrVector3f) -> f32 {
+        self.world_scale.to_real_distance(distance(a, b))
+    }
+
+    /// once per frame, before any view is drawn
+    pub fn update(
+        &mut self,
+        glyphs: &mut GlyphCache,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let stale = self
+            .label
+            .as_ref()
+            .is_some_and(|label| label.is_stale(glyphs));
+        if !self.dirty && !stale {
+            return Ok(());
+        }
+        self.dirty = false;
+
+        self.lines.clear();
+        if let Some(start) = self.start {
+            let marker = 0.2 * self.line_height;
+            self.lines.cross(&xyz(&start), marker, GREEN);
+            if let Some(end) = self.end {
+                self.lines.line(&xyz(&start), &xyz(&end), CYAN);
+                self.lines.cross(&xyz(&end), marker, GREEN);
+            }
+        }
+        self.lines.upload(gpu_state)?;
+
+        let text = match (self.start, self.end) {
+            (Some(start), Some(end)) => format_distance(self.length(&start, &end)),
+            _ => String::new(),
+        };
+        if text.is_empty() {
+            self.label = None;
+        } else if self.label.is_none() || stale || text != self.text {
+            self.label = Some(Label::new(
+                &text,
+                self.density.font_size(self.line_height),
+                self.line_height,
+                f32::INFINITY,
+                glyphs,
+                &self.program,
+                gpu_state,
+            )?);
+        }
+        self.text = text;
+        Ok(())
+    }
+
+    /// the line, tested against the world's depth
+    pub fn draw_line(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.lines.draw(matrix_pv, gpu_state)
+    }
+
+    /// The length, over everything, turned about the vertical to face the eye.
+    pub fn draw_label(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        glyphs: &GlyphCache,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let (Some(label), Some(start), Some(end)) = (&self.label, self.start, self.end) else {
+            return Ok(());
+        };
+        let middle = (start + end) * 0.5 + XrVector3f::new(0.0, LABEL_RAISE, 0.0);
+        let yaw = self.eye.map_or(0.0, |eye| {
+            let toward = eye - middle;
+            toward.x.atan2(toward.z)
+        });
+        let (s, c) = (0.5 * yaw).sin_cos();
+        let facing = xr_matrix4x4f_create_from_quaternion(&XrQuaternionf::new(0.0, s, 0.0, c));
+        let matrix = *matrix_pv
+            * xr_matrix4x4f_create_translation_v(&middle)
+            * facing
+            * xr_matrix4x4f_create_translation(-0.5 * label.width, 0.0, 0.0);
+        label.draw(
+            &matrix,
+            glyphs,
+            &self.program,
+            &self.color,
+            &Material::default().with_depth(DepthMode::AlwaysOnTop),
+            gpu_state,
+        )
+    }
+}
+
+/// centimeters below a meter, meters from there on
+pub fn format_distance(meters: f32) -> String {
+    if meters < 1.0 {
+        format!("{:.1} cm", meters * 100.0)
+    } else {
+        format!("{:.2} m", meters)
+    }
+}
+
+fn distance(a: &XrVector3f, b: &XrVector3f) -> f32 {
+    let d = *b - *a;
+    xr_vector3f_dot(&d, &d).sqrt()
+}
+
+fn xyz(v: &XrVector3f) -> [f32; 3] {
+    [v.x, v.y, v.z]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn centimeters_below_a_meter() {
+        assert_eq!(format_distance(0.0), "0.0 cm");
+        assert_eq!(format_distance(0.123), "12.3 cm");
+        assert_eq!(format_distance(0.999), "99.9 cm");
+    }
+
+    #[test]
+    fn meters_from_a_meter() {
+        assert_eq!(format_distance(1.0), "1.00 m");
+        assert_eq!(format_distance(2.345), "2.35 m");
+    }
+
+    #[test]
+    fn world_meters_are_converted() {
+        let giant = WorldScale::new(10.0);
+        let a = XrVector3f::new(0.0, 0.0, 0.0);
+        let b = XrVector3f::new(3.0, 4.0, 0.0);
+        let shown = format_distance(giant.to_real_distance(distance(&a, &b)));
+        assert_eq!(shown, "50.0 cm");
+    }
+}
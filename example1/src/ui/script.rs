@@ -0,0 +1,644 @@
+//! Scripted UI checks.  A [UiScript] moves a pointer ray over a [Ui], presses and releases
+//! select, types, and checks what the widgets did, including a hash of the rendered pixels,
+//! so layout and interaction regressions show up without putting on a headset.  It needs a GL
+//! context but no OpenXR runtime, so it runs in the Android emulator next to the
+//! [crate::flat_renderer::FlatRenderer] as well as on a device.
+//!
+//! One step per line; `#` starts a comment:
+//!
+//! ```text
+//! point 0 1.5 0  0 0 -1     # pointer ray: origin, direction
+//! away                      # no pointer ray, as with the controller asleep
+//! press
+//! release
+//! click                     # press, then release a frame later
+//! wait 300                  # milliseconds of frames
+//! face 180                  # the camera's heading, degrees left of -Z
+//! focus 0                   # text field 0 of Ui::text_fields
+//! type hello world
+//! expect_text 0 hello world
+//! expect_scroll 0 0.25 0.01 # scroll view 0 of Ui::scroll_views: offset, tolerance
+//! expect_pointer on
+//! expect_hash 9c0f2a7e1b3d5546
+//! hash                      # only record the hash, e.g. to write the first expect_hash
+//! ```
+//!
+//! Every step that moves the pointer or presses select runs one frame, and frames are
+//! [FRAME] apart on a clock the caller starts (see [UiScriptRunner::run]) rather than the
+//! wall clock, so flings coast and the caret blinks the same way on every run.  Pixel hashes
+//! still depend on the GPU and driver, so record them on the device (or emulator image) that
+//! will check them.
+//!
+//! [StartupOptions::ui_scripts](crate::startup::StartupOptions::ui_scripts) runs the
+//! [bundled] scripts against a [fixture_ui] at startup.
+
+use crate::gestures::{GestureButton, GestureDetector};
+use crate::scene::{inverse_view_matrix, projection_matrix};
+use crate::ui::scroll::{ScrollLayout, ScrollView};
+use crate::ui::text_field::{TextField, TextInput};
+use crate::ui::Ui;
+use android_activity::AndroidApp;
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper};
+use gl_thin::linear::{
+    xr_matrix4x4f_create_translation, XrFovf, XrMatrix4x4f, XrQuaternionf, XrVector3f,
+};
+use gl_thin::render_target::RenderTarget;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+
+/// how far apart the simulated frames are
+pub const FRAME: Duration = Duration::from_millis(14);
+
+/// see [bundled]
+const BUNDLED: [(&str, &str); 1] = [(
+    "scroll_and_type",
+    include_str!("scripts/scroll_and_type.txt"),
+)];
+
+#[derive(Clone, Debug)]
+pub enum UiStep {
+    Point {
+        origin: XrVector3f,
+        direction: XrVector3f,
+    },
+    Away,
+    Press,
+    Release,
+    Click,
+    Wait(Duration),
+    /// radians left of -Z
+    Face(f32),
+    Focus(usize),
+    Type(String),
+    ExpectText {
+        field: usize,
+        text: String,
+    },
+    ExpectScroll {
+        panel: usize,
+        offset: f32,
+        tolerance: f32,
+    },
+    ExpectPointer(bool),
+    ExpectHash(u64),
+    Hash,
+}
+
+/// A step and the script line it came from, 1-based
+#[derive(Clone, Debug)]
+pub struct ScriptLine {
+    pub line: usize,
+    pub step: UiStep,
+}
+
+#[derive(Debug)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ScriptError {}
+
+#[derive(Clone, Debug)]
+pub struct UiScript {
+    pub name: String,
+    pub steps: Vec<ScriptLine>,
+}
+
+impl UiScript {
+    pub fn parse(name: impl Into<String>, source: &str) -> Result<Self, ScriptError> {
+        let mut steps = vec![];
+        for (i, line) in source.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let step = parse_step(line).map_err(|message| ScriptError {
+                line: line_number,
+                message,
+            })?;
+            steps.push(ScriptLine {
+                line: line_number,
+                step,
+            });
+        }
+        Ok(Self {
+            name: name.into(),
+            steps,
+        })
+    }
+}
+
+fn parse_step(line: &str) -> Result<UiStep, String> {
+    let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    let numbers = || -> Result<Vec<f32>, String> {
+        rest.split_whitespace()
+            .map(|word| word.parse().map_err(|_| format!("not a number: {}", word)))
+            .collect()
+    };
+    let index_and_text = || -> Result<(usize, String), String> {
+        let (index, text) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let index = index
+            .parse()
+            .map_err(|_| format!("not an index: {}", index))?;
+        Ok((index, text.trim().to_string()))
+    };
+
+    Ok(match command {
+        "point" => match numbers()?[..] {
+            [ox, oy, oz, dx, dy, dz] => UiStep::Point {
+                origin: XrVector3f::new(ox, oy, oz),
+                direction: XrVector3f::new(dx, dy, dz),
+            },
+            _ => return Err("point takes an origin and a direction".to_string()),
+        },
+        "away" => UiStep::Away,
+        "press" => UiStep::Press,
+        "release" => UiStep::Release,
+        "click" => UiStep::Click,
+        "wait" => match numbers()?[..] {
+            // whole microseconds, so a wait lands on the same frame every time
+            [ms] if ms >= 0.0 => UiStep::Wait(Duration::from_micros((ms * 1000.0).round() as u64)),
+            _ => return Err("wait takes milliseconds".to_string()),
+        },
+        "face" => match numbers()?[..] {
+            [degrees] => UiStep::Face(degrees.to_radians()),
+            _ => return Err("face takes degrees".to_string()),
+        },
+        "focus" => UiStep::Focus(index_and_text()?.0),
+        "type" => UiStep::Type(rest.to_string()),
+        "expect_text" => {
+            let (field, text) = index_and_text()?;
+            UiStep::ExpectText { field, text }
+        }
+        "expect_scroll" => {
+            let (panel, numbers) = index_and_text()?;
+            let numbers: Vec<f32> = numbers
+                .split_whitespace()
+                .map(|word| word.parse().map_err(|_| format!("not a number: {}", word)))
+                .collect::<Result<_, _>>()?;
+            let (offset, tolerance) = match numbers[..] {
+                [offset] => (offset, 0.001),
+                [offset, tolerance] => (offset, tolerance),
+                _ => return Err("expect_scroll takes an offset and a tolerance".to_string()),
+            };
+            UiStep::ExpectScroll {
+                panel,
+                offset,
+                tolerance,
+            }
+        }
+        "expect_pointer" => match rest {
+            "on" => UiStep::ExpectPointer(true),
+            "off" => UiStep::ExpectPointer(false),
+            _ => return Err("expect_pointer takes on or off".to_string()),
+        },
+        "expect_hash" => UiStep::ExpectHash(
+            u64::from_str_radix(rest, 16).map_err(|_| format!("not a hex hash: {}", rest))?,
+        ),
+        "hash" => UiStep::Hash,
+        _ => return Err(format!("unknown step {}", command)),
+    })
+}
+
+//
+
+/// A check that did not hold
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptFailure {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UiScriptReport {
+    pub name: String,
+    pub failures: Vec<ScriptFailure>,
+    /// every hash taken, by line, for recording new `expect_hash` steps
+    pub hashes: Vec<(usize, u64)>,
+}
+
+impl UiScriptReport {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// one line per failure, or that it passed
+    pub fn log(&self) {
+        for (line, hash) in &self.hashes {
+            log::info!("{} line {}: hash {:016x}", self.name, line, hash);
+        }
+        if self.passed() {
+            log::info!("{} passed", self.name);
+        }
+        for failure in &self.failures {
+            log::warn!("{} line {}: {}", self.name, failure.line, failure.message);
+        }
+    }
+}
+
+/// Plays [UiScript]s against a [Ui], rendering it offscreen from a fixed camera for the hashes
+pub struct UiScriptRunner {
+    pub target: RenderTarget,
+    /// where the camera the hashed images are rendered from stands
+    pub eye: XrVector3f,
+    pub fov: XrFovf,
+    /// radians left of -Z, set by `face`
+    heading: f32,
+    gestures: GestureDetector,
+    ray: Option<(XrVector3f, XrVector3f)>,
+    select: bool,
+    /// the simulated clock, see [UiScriptRunner::run]
+    now: Instant,
+}
+
+impl UiScriptRunner {
+    pub fn new(
+        width: i32,
+        height: i32,
+        eye: XrVector3f,
+        fov: XrFovf,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        Ok(Self {
+            target: RenderTarget::new(width, height, gpu_state)?,
+            eye,
+            fov,
+            heading: 0.0,
+            gestures: GestureDetector::new(),
+            ray: None,
+            select: false,
+            now: Instant::now(),
+        })
+    }
+
+    /// the camera the hashed images are rendered from
+    pub fn matrix_pv(&self) -> XrMatrix4x4f {
+        let (s, c) = (0.5 * self.heading).sin_cos();
+        let orientation = XrQuaternionf::new(0.0, s, 0.0, c);
+        projection_matrix(&self.fov) * inverse_view_matrix(&orientation, &self.eye)
+    }
+
+    /// Run every step, going on past failed checks.  The first frame is [FRAME] after
+    /// `start`, and each one after that [FRAME] later, however long they really take.
+    /// `app` is for focusing text fields.
+    pub fn run(
+        &mut self,
+        script: &UiScript,
+        ui: &mut Ui,
+        app: &AndroidApp,
+        start: Instant,
+        gpu_state: &mut GPUState,
+    ) -> Result<UiScriptReport, GLErrorWrapper> {
+        self.ray = None;
+        self.select = false;
+        self.heading = 0.0;
+        self.gestures = GestureDetector::new();
+        self.now = start;
+        let mut report = UiScriptReport {
+            name: script.name.clone(),
+            ..Default::default()
+        };
+        let mut fail = |line: usize, message: String| {
+            report.failures.push(ScriptFailure { line, message });
+        };
+        let mut hashes = vec![];
+
+        for ScriptLine { line, step } in &script.steps {
+            let line = *line;
+            match step {
+                UiStep::Point { origin, direction } => {
+                    self.ray = Some((*origin, *direction));
+                    self.frame(ui, gpu_state)?;
+                }
+                UiStep::Away => {
+                    self.ray = None;
+                    self.frame(ui, gpu_state)?;
+                }
+                UiStep::Press | UiStep::Release => {
+                    self.select = matches!(step, UiStep::Press);
+                    self.frame(ui, gpu_state)?;
+                }
+                UiStep::Click => {
+                    self.select = true;
+                    self.frame(ui, gpu_state)?;
+                    self.select = false;
+                    self.frame(ui, gpu_state)?;
+                }
+                UiStep::Wait(duration) => {
+                    let until = self.now + *duration;
+                    while self.now < until {
+                        self.frame(ui, gpu_state)?;
+                    }
+                }
+                UiStep::Face(heading) => self.heading = *heading,
+                UiStep::Focus(field) => {
+                    if *field < ui.text_fields.len() {
+                        ui.focus_text_field(*field, app);
+                    } else {
+                        fail(line, format!("no text field {}", field));
+                    }
+                }
+                UiStep::Type(text) => {
+                    ui.handle_input(TextInput::Insert(text.clone()));
+                    self.frame(ui, gpu_state)?;
+                }
+                UiStep::ExpectText { field, text } => match ui.text_fields.get(*field) {
+                    Some(actual) if actual.text() == text => {}
+                    Some(actual) => fail(
+                        line,
+                        format!(
+                            "text field {} has {:?}, not {:?}",
+                            field,
+                            actual.text(),
+                            text
+                        ),
+                    ),
+                    None => fail(line, format!("no text field {}", field)),
+                },
+                UiStep::ExpectScroll {
+                    panel,
+                    offset,
+                    tolerance,
+                } => match ui.scroll_views.get(*panel) {
+                    Some(view) if (view.scroll.offset - offset).abs() <= *tolerance => {}
+                    Some(view) => fail(
+                        line,
+                        format!(
+                            "scroll view {} is at {}, not {}",
+                            panel, view.scroll.offset, offset
+                        ),
+                    ),
+                    None => fail(line, format!("no scroll view {}", panel)),
+                },
+                UiStep::ExpectPointer(visible) => {
+                    if ui.pointer.is_visible() != *visible {
+                        fail(line, format!("pointer visible is {}", !visible));
+                    }
+                }
+                UiStep::ExpectHash(expected) => {
+                    let hash = self.hash(ui, gpu_state)?;
+                    hashes.push((line, hash));
+                    if hash != *expected {
+                        fail(
+                            line,
+                            format!("rendered {:016x}, expected {:016x}", hash, expected),
+                        );
+                    }
+                }
+                UiStep::Hash => hashes.push((line, self.hash(ui, gpu_state)?)),
+            }
+        }
+        report.hashes = hashes;
+        Ok(report)
+    }
+
+    /// what the renderer does with the UI each frame, [FRAME] after the last one
+    fn frame(&mut self, ui: &mut Ui, gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper> {
+        self.now += FRAME;
+        let gestures = self
+            .gestures
+            .update(&[(GestureButton::Select, self.select)], self.now);
+        ui.handle_gestures(gestures, self.ray);
+        ui.update_pointer(self.ray);
+        ui.update(self.now, gpu_state)
+    }
+
+    /// render the UI to [UiScriptRunner::target] and hash the pixels
+    fn hash(&mut self, ui: &mut Ui, gpu_state: &mut GPUState) -> Result<u64, GLErrorWrapper> {
+        self.target.bind()?;
+        let blend = unsafe { gl::IsEnabled(gl::BLEND) };
+        unsafe {
+            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            // back to what the eyes clear to; the eye layer is blended by its alpha
+            gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+        explode_if_gl_error()?;
+        let drawn = ui.draw(&self.matrix_pv(), gpu_state);
+        if blend == gl::FALSE {
+            unsafe { gl::Disable(gl::BLEND) };
+        }
+        drawn?;
+        Ok(fnv1a(&self.target.read_pixels()?))
+    }
+}
+
+/// The scripts compiled into the app, the ones [run_bundled] plays
+pub fn bundled() -> Vec<UiScript> {
+    BUNDLED
+        .iter()
+        .filter_map(|(name, source)| match UiScript::parse(*name, source) {
+            Ok(script) => Some(script),
+            Err(e) => {
+                log::warn!("failed to parse ui script {} {}", name, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// What the [bundled] scripts expect to find: text field 0, 0.5 m wide, at 1.6 m, and below
+/// it scroll view 0, a list of 20 items in a 0.3 m tall panel centered at 1.3 m; both 1 m
+/// down -Z.
+pub fn fixture_ui(gpu_state: &mut GPUState) -> Result<Ui, GLErrorWrapper> {
+    let mut ui = Ui::new(gpu_state)?;
+    ui.text_fields.push(TextField::new(
+        xr_matrix4x4f_create_translation(0.0, 1.6, -1.0),
+        0.5,
+        0.08,
+    )?);
+    let mut view = ScrollView::new(
+        xr_matrix4x4f_create_translation(0.0, 1.3, -1.0),
+        0.5,
+        0.3,
+        ScrollLayout::List,
+    );
+    view.set_items((0..20).map(|i| format!("item {}", i)).collect());
+    ui.scroll_views.push(view);
+    Ok(ui)
+}
+
+/// Play each of the [bundled] scripts against a fresh [fixture_ui], watched by a 256x256
+/// camera at 1.5 m facing -Z, on a clock that starts at `start`.
+pub fn run_bundled(
+    app: &AndroidApp,
+    start: Instant,
+    gpu_state: &mut GPUState,
+) -> Result<Vec<UiScriptReport>, GLErrorWrapper> {
+    let half = 0.5f32;
+    let fov = XrFovf {
+        angle_left: -half,
+        angle_right: half,
+        angle_up: half,
+        angle_down: -half,
+    };
+    let mut runner = UiScriptRunner::new(256, 256, XrVector3f::new(0.0, 1.5, 0.0), fov, gpu_state)?;
+    let mut reports = vec![];
+    for script in bundled() {
+        let mut ui = fixture_ui(gpu_state)?;
+        reports.push(runner.run(&script, &mut ui, app, start, gpu_state)?);
+        for field in &mut ui.text_fields {
+            if field.is_focused() {
+                field.blur(app);
+            }
+        }
+    }
+    Ok(reports)
+}
+
+/// 64-bit FNV-1a; only has to be stable, not strong
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps(source: &str) -> Vec<UiStep> {
+        UiScript::parse("test", source)
+            .unwrap()
+            .steps
+            .into_iter()
+            .map(|line| line.step)
+            .collect()
+    }
+
+    fn error(source: &str) -> ScriptError {
+        UiScript::parse("test", source).unwrap_err()
+    }
+
+    #[test]
+    fn comments_and_blank_lines() {
+        let script = UiScript::parse("test", "# a comment\n\n  press  # held\nrelease\n").unwrap();
+        let lines: Vec<usize> = script.steps.iter().map(|line| line.line).collect();
+        assert_eq!(lines, [3, 4]);
+        assert!(matches!(script.steps[0].step, UiStep::Press));
+        assert!(matches!(script.steps[1].step, UiStep::Release));
+    }
+
+    #[test]
+    fn point() {
+        match &steps("point 0 1.5 0  0 0 -1")[..] {
+            [UiStep::Point { origin, direction }] => {
+                assert_eq!([origin.x, origin.y, origin.z], [0.0, 1.5, 0.0]);
+                assert_eq!([direction.x, direction.y, direction.z], [0.0, 0.0, -1.0]);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(parse_step("point 0 1.5 0").is_err());
+        assert!(parse_step("point 0 1.5 0 0 0 x").is_err());
+    }
+
+    #[test]
+    fn simple_steps() {
+        assert!(matches!(parse_step("away"), Ok(UiStep::Away)));
+        assert!(matches!(parse_step("click"), Ok(UiStep::Click)));
+        assert!(matches!(parse_step("hash"), Ok(UiStep::Hash)));
+    }
+
+    #[test]
+    fn wait_and_face() {
+        assert!(matches!(
+            parse_step("wait 300"),
+            Ok(UiStep::Wait(d)) if d == Duration::from_millis(300)
+        ));
+        assert!(parse_step("wait -1").is_err());
+        assert!(parse_step("wait").is_err());
+        match parse_step("face 180") {
+            Ok(UiStep::Face(heading)) => assert!((heading - std::f32::consts::PI).abs() < 1e-6),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn text_steps() {
+        assert!(matches!(parse_step("focus 2"), Ok(UiStep::Focus(2))));
+        assert!(parse_step("focus x").is_err());
+        assert!(matches!(
+            parse_step("type hello world"),
+            Ok(UiStep::Type(text)) if text == "hello world"
+        ));
+        assert!(matches!(
+            parse_step("expect_text 1 hello  world "),
+            Ok(UiStep::ExpectText { field: 1, text }) if text == "hello  world"
+        ));
+        assert!(matches!(
+            parse_step("expect_text 0"),
+            Ok(UiStep::ExpectText { field: 0, text }) if text.is_empty()
+        ));
+    }
+
+    #[test]
+    fn expect_scroll() {
+        assert!(matches!(
+            parse_step("expect_scroll 0 0.25 0.01"),
+            Ok(UiStep::ExpectScroll { panel: 0, offset, tolerance })
+                if offset == 0.25 && tolerance == 0.01
+        ));
+        assert!(matches!(
+            parse_step("expect_scroll 3 0.5"),
+            Ok(UiStep::ExpectScroll { panel: 3, offset, tolerance })
+                if offset == 0.5 && tolerance == 0.001
+        ));
+        assert!(parse_step("expect_scroll 0").is_err());
+        assert!(parse_step("expect_scroll 0 1 2 3").is_err());
+    }
+
+    #[test]
+    fn expect_pointer_and_hash() {
+        assert!(matches!(
+            parse_step("expect_pointer on"),
+            Ok(UiStep::ExpectPointer(true))
+        ));
+        assert!(matches!(
+            parse_step("expect_pointer off"),
+            Ok(UiStep::ExpectPointer(false))
+        ));
+        assert!(parse_step("expect_pointer maybe").is_err());
+        assert!(matches!(
+            parse_step("expect_hash 9c0f2a7e1b3d5546"),
+            Ok(UiStep::ExpectHash(0x9c0f2a7e1b3d5546))
+        ));
+        assert!(parse_step("expect_hash xyz").is_err());
+    }
+
+    #[test]
+    fn errors_carry_the_line() {
+        let e = error("press\n\n# fine so far\nwiggle 3\n");
+        assert_eq!(e.line, 4);
+        assert!(e.message.contains("wiggle"), "{}", e.message);
+        assert_eq!(error("wait soon").line, 1);
+    }
+
+    #[test]
+    fn bundled_scripts_parse() {
+        for (name, source) in BUNDLED {
+            if let Err(e) = UiScript::parse(name, source) {
+                panic!("{} {}", name, e);
+            }
+        }
+    }
+
+    /// the blank frame `scroll_and_type` expects after `face 180`
+    #[test]
+    fn blank_frame_hash() {
+        let black: Vec<u8> = [0, 0, 0, 255].repeat(256 * 256);
+        assert_eq!(fnv1a(&black), 0x53eb0cbe294a2325);
+    }
+}
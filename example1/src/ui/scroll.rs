@@ -0,0 +1,421 @@
+//! Panels of items that scroll vertically, as a list or a grid.  Only the rows in view
+//! get text geometry, so long lists cost about the same as short ones.
+
+use crate::text_painting::GlyphCache;
+use crate::ui::label::Label;
+use crate::ui::raster_scale::{apparent_pixels, RasterScale};
+use crate::ui::shapes::ShapePainter;
+use crate::ui::units::PanelDensity;
+use bob_shaders::masked_solid_shader::MaskedSolidShader;
+use bob_shaders::material::{ClipPlanes, DepthMode, Material};
+use bob_shaders::sdf_shape_shader::{SdfShape, SdfShapeStyle};
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::{
+    xr_matrix4x4f_create_translation, xr_matrix4x4f_invert, xr_matrix4x4f_transform_vector3f,
+    XrMatrix4x4f, XrVector3f,
+};
+use std::ops::Range;
+use std::time::Instant;
+
+/// A scroll position that keeps moving after a fling and slows down on its own.
+/// Offsets are meters of content scrolled out the top of the view.
+pub struct KineticScroll {
+    pub offset: f32,
+    /// meters per second, positive scrolls toward the end
+    pub velocity: f32,
+    /// the fraction of the velocity that survives one second of coasting
+    pub friction: f32,
+    /// meters per second with the thumbstick all the way over
+    pub thumbstick_speed: f32,
+    max_offset: f32,
+    drag: Option<(f32, Instant)>,
+}
+
+impl Default for KineticScroll {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KineticScroll {
+    pub fn new() -> Self {
+        Self {
+            offset: 0.0,
+            velocity: 0.0,
+            friction: 0.05,
+            thumbstick_speed: 1.0,
+            max_offset: 0.0,
+            drag: None,
+        }
+    }
+
+    /// how far the content reaches past the bottom of the view when scrolled to the top
+    pub fn set_max_offset(&mut self, max_offset: f32) {
+        self.max_offset = max_offset.max(0.0);
+        self.offset = self.offset.clamp(0.0, self.max_offset);
+    }
+
+    /// `y` is the thumbstick's forward deflection, -1..1, already dead-zoned (see
+    /// [crate::stick_response::StickResponse]); pushing forward scrolls toward the top.
+    /// Releasing the stick lets the list coast to a stop.
+    pub fn thumbstick(&mut self, y: f32) {
+        if self.drag.is_none() && y != 0.0 {
+            self.velocity = -y * self.thumbstick_speed;
+        }
+    }
+
+    /// `y` is where the pointer hit the view, in its local coordinates (meters, +Y up)
+    pub fn drag_begin(&mut self, y: f32, now: Instant) {
+        self.drag = Some((y, now));
+        self.velocity = 0.0;
+    }
+
+    /// the content follows the pointer
+    pub fn drag_to(&mut self, y: f32, now: Instant) {
+        let Some((last_y, last_time)) = self.drag else {
+            return;
+        };
+        let dy = y - last_y;
+        self.offset = (self.offset + dy).clamp(0.0, self.max_offset);
+
+        let dt = now.duration_since(last_time).as_secs_f32();
+        if dt > 0.0 {
+            // smooth it; the last sample before letting go is often noisy
+            self.velocity = 0.5 * self.velocity + 0.5 * dy / dt;
+        }
+        self.drag = Some((y, now));
+    }
+
+    /// let go, flinging the content with the drag's speed
+    pub fn drag_end(&mut self) {
+        self.drag = None;
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// advance the coasting by `dt` seconds
+    pub fn step(&mut self, dt: f32) {
+        if self.drag.is_some() {
+            return;
+        }
+        self.offset += self.velocity * dt;
+        self.velocity *= self.friction.powf(dt);
+        if self.offset <= 0.0 || self.offset >= self.max_offset {
+            self.offset = self.offset.clamp(0.0, self.max_offset);
+            self.velocity = 0.0;
+        }
+        if self.velocity.abs() < 0.005 {
+            self.velocity = 0.0;
+        }
+    }
+}
+
+//
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ScrollLayout {
+    /// one item per row
+    List,
+    Grid {
+        columns: usize,
+    },
+}
+
+pub struct ScrollView {
+    /// centered on the panel, +X right, +Y up, meters
+    pub model: XrMatrix4x4f,
+    pub width: f32,
+    pub height: f32,
+    pub layout: ScrollLayout,
+    pub item_height: f32,
+    /// between items, and between the items and the panel's edge
+    pub spacing: f32,
+    /// sets the size glyphs are rasterized at, see [ScrollView::font_size]
+    pub density: PanelDensity,
+    /// see [ScrollView::track_eye]
+    pub raster_scale: RasterScale,
+    pub text_color: [f32; 4],
+    pub background: SdfShapeStyle,
+    pub item_style: SdfShapeStyle,
+    pub scroll: KineticScroll,
+    pub depth: DepthMode,
+    items: Vec<String>,
+    /// labels for the items in view, in no particular order
+    visible: Vec<(usize, Label)>,
+    /// what [ScrollView::visible] was laid out at
+    visible_font_size: f32,
+    last_update: Option<Instant>,
+}
+
+impl ScrollView {
+    pub fn new(model: XrMatrix4x4f, width: f32, height: f32, layout: ScrollLayout) -> Self {
+        let item_height = 0.06;
+        Self {
+            model,
+            width,
+            height,
+            layout,
+            item_height,
+            spacing: 0.01,
+            density: PanelDensity::default(),
+            raster_scale: RasterScale::new(),
+            text_color: [0.1, 0.1, 0.1, 1.0],
+            background: SdfShapeStyle {
+                fill: [0.2, 0.2, 0.25, 0.9],
+                border_color: [0.5, 0.5, 0.6, 1.0],
+                border_width: 0.005,
+                shadow_color: [0.0, 0.0, 0.0, 0.3],
+                shadow_offset: [0.0, -0.01],
+                shadow_softness: 0.02,
+            },
+            item_style: SdfShapeStyle::solid([0.9, 0.9, 0.9, 1.0]),
+            scroll: KineticScroll::new(),
+            depth: DepthMode::World,
+            items: vec![],
+            visible: vec![],
+            visible_font_size: 0.0,
+            last_update: None,
+        }
+    }
+
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+
+    pub fn set_items(&mut self, items: Vec<String>) {
+        self.items = items;
+        self.visible.clear();
+        self.scroll
+            .set_max_offset(self.content_height() - self.height);
+    }
+
+    fn columns(&self) -> usize {
+        match self.layout {
+            ScrollLayout::List => 1,
+            ScrollLayout::Grid { columns } => columns.max(1),
+        }
+    }
+
+    fn row_count(&self) -> usize {
+        self.items.len().div_ceil(self.columns())
+    }
+
+    fn row_pitch(&self) -> f32 {
+        self.item_height + self.spacing
+    }
+
+    fn content_height(&self) -> f32 {
+        self.row_count() as f32 * self.row_pitch() + self.spacing
+    }
+
+    /// the width of one item, meters
+    pub fn cell_width(&self) -> f32 {
+        let columns = self.columns() as f32;
+        (self.width - (columns + 1.0) * self.spacing) / columns
+    }
+
+    /// the rows at least partly inside the panel at the current scroll offset
+    fn visible_rows(&self) -> Range<usize> {
+        let pitch = self.row_pitch();
+        let top = self.scroll.offset - self.spacing;
+        let first = (top / pitch).floor().max(0.0) as usize;
+        let last = ((top + self.height) / pitch).ceil().max(0.0) as usize;
+        first..last.min(self.row_count())
+    }
+
+    /// the items at least partly inside the panel at the current scroll offset
+    pub fn visible_items(&self) -> Range<usize> {
+        let rows = self.visible_rows();
+        let columns = self.columns();
+        (rows.start * columns)..(rows.end * columns).min(self.items.len())
+    }
+
+    /// center of item `index` in the panel's local coordinates, scrolled
+    pub fn item_center(&self, index: usize) -> [f32; 2] {
+        let columns = self.columns();
+        let (row, column) = (index / columns, index % columns);
+        let cell_width = self.cell_width();
+        let x = -0.5 * self.width
+            + self.spacing
+            + column as f32 * (cell_width + self.spacing)
+            + 0.5 * cell_width;
+        let y = 0.5 * self.height
+            - self.spacing
+            - row as f32 * self.row_pitch()
+            - 0.5 * self.item_height
+            + self.scroll.offset;
+        [x, y]
+    }
+
+    /// Where a pointer ray hits the panel, in its local coordinates, if it does.
+    /// Feed the `y` to [KineticScroll::drag_begin]/[KineticScroll::drag_to] for ray-dragging.
+    pub fn hit(&self, origin: &XrVector3f, direction: &XrVector3f) -> Option<[f32; 2]> {
+        let [x, y] = plane_hit(&self.model, origin, direction)?;
+        (x.abs() <= 0.5 * self.width && y.abs() <= 0.5 * self.height).then_some([x, y])
+    }
+
+    /// the item under a point from [ScrollView::hit]
+    pub fn item_at(&self, point: [f32; 2]) -> Option<usize> {
+        let half = [0.5 * self.cell_width(), 0.5 * self.item_height];
+        self.visible_items().find(|&i| {
+            let [x, y] = self.item_center(i);
+            (point[0] - x).abs() <= half[0] && (point[1] - y).abs() <= half[1]
+        })
+    }
+
+    /// Coast the scrolling up to `now`, the frame's time, and lay out labels for items that
+    /// came into view.  Call this every frame before drawing.  `program` is shared by every
+    /// panel, see [crate::ui::Ui::text_program].
+    pub fn update(
+        &mut self,
+        now: Instant,
+        glyphs: &mut GlyphCache,
+        program: &MaskedSolidShader,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        if let Some(last) = self.last_update {
+            self.scroll.step(now.duration_since(last).as_secs_f32());
+        }
+        self.last_update = Some(now);
+
+        let font_size = self.font_size() * self.raster_scale.factor();
+        if font_size != self.visible_font_size {
+            self.visible.clear();
+            self.visible_font_size = font_size;
+        }
+
+        let in_view = self.visible_items();
+        self.visible
+            .retain(|(i, label)| in_view.contains(i) && !label.is_stale(glyphs));
+
+        let line_height = self.line_height();
+        let max_width = self.cell_width() - 2.0 * self.text_inset();
+        for i in in_view {
+            if self.visible.iter().any(|(j, _)| *j == i) {
+                continue;
+            }
+            let label = Label::new(
+                &self.items[i],
+                font_size,
+                line_height,
+                max_width,
+                glyphs,
+                program,
+                gpu_state,
+            )?;
+            self.visible.push((i, label));
+        }
+        Ok(())
+    }
+
+    /// Rasterize the text finer or coarser as the panel is seen from closer or farther away.
+    /// The labels are laid out again on the next [ScrollView::update] when that changes, as
+    /// they are when [ScrollView::density] does.
+    pub fn track_eye(&mut self, eye: &XrVector3f, pixels_per_radian: f32) {
+        let apparent = apparent_pixels(&self.model, self.line_height(), eye, pixels_per_radian);
+        self.raster_scale.update(apparent, self.font_size());
+    }
+
+    /// meters
+    fn line_height(&self) -> f32 {
+        0.6 * self.item_height
+    }
+
+    /// pixels; the size glyphs are rasterized at, before [ScrollView::raster_scale]
+    pub fn font_size(&self) -> f32 {
+        self.density.font_size(self.line_height())
+    }
+
+    fn text_inset(&self) -> f32 {
+        0.25 * self.item_height
+    }
+
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        glyphs: &GlyphCache,
+        shapes: &ShapePainter,
+        program: &MaskedSolidShader,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let matrix = *matrix_pv * self.model;
+        shapes.draw(
+            &matrix,
+            &SdfShape::RoundedRect {
+                half_width: 0.5 * self.width,
+                half_height: 0.5 * self.height,
+                corner_radius: 2.0 * self.spacing,
+            },
+            &self.background,
+            &Material::default().with_depth(self.depth),
+            gpu_state,
+        )?;
+
+        // keep the items inside the frame
+        let inset = self.background.border_width;
+        let material = Material::clipped(ClipPlanes::rectangle(
+            matrix_pv,
+            &self.model,
+            0.5 * self.width - inset,
+            0.5 * self.height - inset,
+        ))
+        .with_depth(self.depth);
+
+        let half_width = 0.5 * self.cell_width();
+        for (i, label) in &self.visible {
+            let [x, y] = self.item_center(*i);
+            let item_matrix = matrix * xr_matrix4x4f_create_translation(x, y, 0.001);
+            shapes.draw(
+                &item_matrix,
+                &SdfShape::RoundedRect {
+                    half_width,
+                    half_height: 0.5 * self.item_height,
+                    corner_radius: 0.2 * self.item_height,
+                },
+                &self.item_style,
+                &material,
+                gpu_state,
+            )?;
+            let text_matrix = item_matrix
+                * xr_matrix4x4f_create_translation(-half_width + self.text_inset(), 0.0, 0.001);
+            label.draw(
+                &text_matrix,
+                glyphs,
+                program,
+                &self.text_color,
+                &material,
+                gpu_state,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Where a ray (origin, direction) meets the XY plane of `model`, in its local coordinates.
+/// `None` if the ray runs parallel to the plane or points away from it.
+pub fn plane_hit(
+    model: &XrMatrix4x4f,
+    origin: &XrVector3f,
+    direction: &XrVector3f,
+) -> Option<[f32; 2]> {
+    let inverse = xr_matrix4x4f_invert(model);
+    let o = xr_matrix4x4f_transform_vector3f(&inverse, origin);
+    let tip = XrVector3f::new(
+        origin.x + direction.x,
+        origin.y + direction.y,
+        origin.z + direction.z,
+    );
+    let tip = xr_matrix4x4f_transform_vector3f(&inverse, &tip);
+    let dz = tip.z - o.z;
+    if dz.abs() < 1e-6 {
+        return None;
+    }
+    let t = -o.z / dz;
+    if t < 0.0 {
+        return None;
+    }
+    Some([o.x + t * (tip.x - o.x), o.y + t * (tip.y - o.y)])
+}
@@ -0,0 +1,59 @@
+//! Flat UI shapes (panel backgrounds, buttons, toggles, progress rings) drawn with [SdfShapeShader].
+
+use bob_shaders::material::Material;
+use bob_shaders::sdf_shape_shader::{SdfShape, SdfShapeShader, SdfShapeStyle};
+use gl::types::{GLfloat, GLsizei};
+use gl_thin::gl_fancy::{GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::XrMatrix4x4f;
+
+/// One shader and one quad, shared by every shape
+pub struct ShapePainter {
+    shader: SdfShapeShader,
+    buffers: VertexBufferBundle<'static, GLfloat, u8>,
+}
+
+impl ShapePainter {
+    pub fn new(gpu_state: &mut GPUState) -> Result<Self, GLErrorWrapper> {
+        let shader = SdfShapeShader::new()?;
+
+        static QUAD: [GLfloat; 8] = [
+            -1.0, -1.0, //
+            1.0, -1.0, //
+            -1.0, 1.0, //
+            1.0, 1.0,
+        ];
+        static INDICES: [u8; 4] = [0, 1, 2, 3];
+        let buffers = VertexBufferBundle::<'static, GLfloat, u8>::new(
+            gpu_state,
+            (&QUAD).into(),
+            (&INDICES).into(),
+            2,
+            &[(shader.sal_position, 2, 0)],
+        )?;
+
+        Ok(Self { shader, buffers })
+    }
+
+    /// `matrix` carries the shape's local coordinates (centered on the shape, +y up) to clip space.
+    /// Blending must be enabled for the antialiasing and shadow.
+    pub fn draw(
+        &self,
+        matrix: &XrMatrix4x4f,
+        shape: &SdfShape,
+        style: &SdfShapeStyle,
+        material: &Material,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.shader.draw(
+            matrix,
+            shape,
+            style,
+            material,
+            gl::TRIANGLE_STRIP,
+            &self.buffers,
+            self.buffers.index_count as GLsizei,
+            gpu_state,
+        )
+    }
+}
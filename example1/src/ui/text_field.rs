@@ -0,0 +1,351 @@
+//! A single-line text entry box.  Typing comes from the Android soft keyboard,
+//! which [TextField::focus] pops up.
+
+use crate::text_painting::GlyphCache;
+use crate::ui::raster_scale::{apparent_pixels, RasterScale};
+use crate::ui::shapes::ShapePainter;
+use crate::ui::units::PanelDensity;
+use android_activity::AndroidApp;
+use bob_shaders::masked_solid_shader::MaskedSolidShader;
+use bob_shaders::material::{DepthMode, Material};
+use bob_shaders::sdf_shape_shader::{SdfShape, SdfShapeStyle};
+use gl::types::{GLfloat, GLsizei, GLushort};
+use gl_thin::gl_fancy::{GPUState, VertexBufferBundle};
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::{xr_matrix4x4f_create_translation, XrMatrix4x4f, XrVector3f};
+use std::time::{Duration, Instant};
+
+/// Editing input for a [TextField], independent of where it came from
+#[derive(Clone, Debug, PartialEq)]
+pub enum TextInput {
+    /// text committed by the keyboard, usually one character
+    Insert(String),
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    Enter,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TextFieldEvent {
+    Changed,
+    /// the user pressed Enter; carries the text
+    Submitted(String),
+}
+
+const CARET_BLINK: Duration = Duration::from_millis(530);
+
+pub struct TextField {
+    /// centered on the field, +X right, +Y up, meters
+    pub model: XrMatrix4x4f,
+    pub width: f32,
+    pub height: f32,
+    /// sets the size glyphs are rasterized at, see [TextField::font_size]
+    pub density: PanelDensity,
+    /// see [TextField::track_eye]
+    pub raster_scale: RasterScale,
+    pub max_length: usize,
+    pub text_color: [f32; 4],
+    pub background: SdfShapeStyle,
+    pub focused_border: [f32; 4],
+    /// what [crate::ui::Ui::draw] draws the field with
+    pub depth: DepthMode,
+    text: String,
+    /// in chars, not bytes
+    caret: usize,
+    focused: bool,
+    blink_epoch: Instant,
+    /// of the last [TextField::update]; the caret blinks on this clock
+    now: Instant,
+    geometry: Option<TextGeometry>,
+    program: MaskedSolidShader,
+}
+
+struct TextGeometry {
+    buffers: VertexBufferBundle<'static, GLfloat, GLushort>,
+    caret_x: f32,
+    glyph_generation: u32,
+    font_size: f32,
+}
+
+impl TextField {
+    pub fn new(model: XrMatrix4x4f, width: f32, height: f32) -> Result<Self, GLErrorWrapper> {
+        Ok(Self {
+            model,
+            width,
+            height,
+            density: PanelDensity::default(),
+            raster_scale: RasterScale::new(),
+            max_length: 256,
+            text_color: [0.1, 0.1, 0.1, 1.0],
+            background: SdfShapeStyle {
+                fill: [0.95, 0.95, 0.95, 1.0],
+                border_color: [0.5, 0.5, 0.5, 1.0],
+                border_width: height * 0.05,
+                shadow_color: [0.0, 0.0, 0.0, 0.3],
+                shadow_offset: [0.0, -height * 0.05],
+                shadow_softness: height * 0.1,
+            },
+            focused_border: [0.2, 0.5, 1.0, 1.0],
+            depth: DepthMode::World,
+            text: String::new(),
+            caret: 0,
+            focused: false,
+            blink_epoch: Instant::now(),
+            now: Instant::now(),
+            geometry: None,
+            program: MaskedSolidShader::new()?,
+        })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.chars().take(self.max_length).collect();
+        self.caret = self.text.chars().count();
+        self.geometry = None;
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    /// start editing and show the soft keyboard
+    pub fn focus(&mut self, app: &AndroidApp) {
+        self.focused = true;
+        self.blink_epoch = self.now;
+        app.show_soft_input(true);
+    }
+
+    /// stop editing and hide the soft keyboard
+    pub fn blur(&mut self, app: &AndroidApp) {
+        self.focused = false;
+        app.hide_soft_input(false);
+    }
+
+    pub fn handle_input(&mut self, input: TextInput) -> Option<TextFieldEvent> {
+        if !self.focused {
+            return None;
+        }
+        // keep the caret solid while typing
+        self.blink_epoch = self.now;
+
+        let length = self.text.chars().count();
+        let changed = match input {
+            TextInput::Insert(text) => {
+                let room = self.max_length.saturating_sub(length);
+                let text: String = text
+                    .chars()
+                    .filter(|c| !c.is_control())
+                    .take(room)
+                    .collect();
+                let at = self.byte_offset(self.caret);
+                self.text.insert_str(at, &text);
+                self.caret += text.chars().count();
+                !text.is_empty()
+            }
+            TextInput::Backspace if self.caret > 0 => {
+                self.caret -= 1;
+                self.text.remove(self.byte_offset(self.caret));
+                true
+            }
+            TextInput::Delete if self.caret < length => {
+                self.text.remove(self.byte_offset(self.caret));
+                true
+            }
+            TextInput::Left => {
+                self.caret = self.caret.saturating_sub(1);
+                false
+            }
+            TextInput::Right => {
+                self.caret = (self.caret + 1).min(length);
+                false
+            }
+            TextInput::Home => {
+                self.caret = 0;
+                false
+            }
+            TextInput::End => {
+                self.caret = length;
+                false
+            }
+            TextInput::Enter => return Some(TextFieldEvent::Submitted(self.text.clone())),
+            TextInput::Backspace | TextInput::Delete => false,
+        };
+
+        // the caret moved, if nothing else
+        self.geometry = None;
+        changed.then_some(TextFieldEvent::Changed)
+    }
+
+    fn byte_offset(&self, char_index: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(char_index)
+            .map_or(self.text.len(), |(i, _)| i)
+    }
+
+    /// meters
+    fn line_height(&self) -> f32 {
+        0.6 * self.height
+    }
+
+    /// pixels; the size glyphs are rasterized at, not how big they look, before
+    /// [TextField::raster_scale]
+    pub fn font_size(&self) -> f32 {
+        self.density.font_size(self.line_height())
+    }
+
+    fn padding(&self) -> f32 {
+        self.height * 0.15
+    }
+
+    /// like [crate::ui::scroll::ScrollView::track_eye]
+    pub fn track_eye(&mut self, eye: &XrVector3f, pixels_per_radian: f32) {
+        let apparent = apparent_pixels(&self.model, self.line_height(), eye, pixels_per_radian);
+        self.raster_scale.update(apparent, self.font_size());
+    }
+
+    /// Rebuild the text geometry if the text changed, the glyph atlas was flushed or the
+    /// font size changed.  `now` is the frame's time, which the caret blinks by.
+    /// Call this every frame before drawing.
+    pub fn update(
+        &mut self,
+        now: Instant,
+        glyphs: &mut GlyphCache,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        self.now = now;
+        let font_size = self.font_size() * self.raster_scale.factor();
+        if let Some(geometry) = &self.geometry {
+            if geometry.glyph_generation == glyphs.generation() && geometry.font_size == font_size {
+                return Ok(());
+            }
+        }
+
+        let (ascent, descent) = glyphs.line_metrics(font_size);
+        let scale = self.line_height() / (ascent - descent);
+
+        let prefix: String = self.text.chars().take(self.caret).collect();
+        let (_, caret_px) = glyphs.layout(&prefix, font_size, gpu_state)?;
+        let (quads, _) = glyphs.layout(&self.text, font_size, gpu_state)?;
+
+        // scroll so the caret stays inside the box
+        let inner_width = self.width - 2.0 * self.padding();
+        let scroll = (caret_px * scale - inner_width).max(0.0);
+        let left = -0.5 * self.width + self.padding() - scroll;
+        let top = 0.5 * (ascent - descent) * scale;
+
+        let mut xyzuv = vec![];
+        let mut indices = vec![];
+        for quad in &quads {
+            let (x0, x1) = (left + quad.x0 * scale, left + quad.x1 * scale);
+            // whole glyphs only; a half-visible one would poke out of the box
+            if x0 < -0.5 * self.width + self.padding() || x1 > 0.5 * self.width - self.padding() {
+                continue;
+            }
+            let (y0, y1) = (top - quad.y0 * scale, top - quad.y1 * scale);
+            let base = (xyzuv.len() / 5) as GLushort;
+            let uv = &quad.uv;
+            #[rustfmt::skip]
+            xyzuv.extend_from_slice(&[
+                x0, y1, 0.0, uv.u0, uv.v1,
+                x1, y1, 0.0, uv.u1, uv.v1,
+                x0, y0, 0.0, uv.u0, uv.v0,
+                x1, y0, 0.0, uv.u1, uv.v0,
+            ]);
+            indices.extend_from_slice(&[base, base + 1, base + 2, base + 2, base + 1, base + 3]);
+        }
+
+        let buffers = VertexBufferBundle::new(
+            gpu_state,
+            xyzuv.into(),
+            indices.into(),
+            3 + 2,
+            &[
+                (self.program.sal_position, 3, 0),
+                (self.program.sal_tex_coord, 2, 3),
+            ],
+        )?;
+
+        self.geometry = Some(TextGeometry {
+            buffers,
+            caret_x: left + caret_px * scale,
+            glyph_generation: glyphs.generation(),
+            font_size,
+        });
+        Ok(())
+    }
+
+    /// `material` can clip the field, e.g. to the panel it scrolls in
+    pub fn draw(
+        &self,
+        matrix_pv: &XrMatrix4x4f,
+        glyphs: &GlyphCache,
+        shapes: &ShapePainter,
+        material: &Material,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let matrix = *matrix_pv * self.model;
+
+        let mut background = self.background;
+        if self.focused {
+            background.border_color = self.focused_border;
+        }
+        shapes.draw(
+            &matrix,
+            &SdfShape::RoundedRect {
+                half_width: 0.5 * self.width,
+                half_height: 0.5 * self.height,
+                corner_radius: 0.25 * self.height,
+            },
+            &background,
+            material,
+            gpu_state,
+        )?;
+
+        let Some(geometry) = &self.geometry else {
+            return Ok(());
+        };
+
+        // just in front of the background, so it wins the depth test
+        let matrix = matrix * xr_matrix4x4f_create_translation(0.0, 0.0, 0.001);
+        if geometry.buffers.index_count > 0 {
+            let [r, g, b, _] = self.text_color;
+            self.program.draw(
+                &matrix,
+                &glyphs.atlas.texture,
+                &self.text_color,
+                Some(&[r, g, b, 0.0]),
+                material,
+                gl::TRIANGLES,
+                &geometry.buffers,
+                geometry.buffers.index_count as GLsizei,
+                gpu_state,
+            )?;
+        }
+
+        let blink_phase =
+            self.now.duration_since(self.blink_epoch).as_millis() / CARET_BLINK.as_millis();
+        if self.focused && blink_phase & 1 == 0 {
+            let half_height = 0.35 * self.height;
+            shapes.draw(
+                &(matrix * xr_matrix4x4f_create_translation(geometry.caret_x, 0.0, 0.0)),
+                &SdfShape::RoundedRect {
+                    half_width: 0.02 * half_height,
+                    half_height,
+                    corner_radius: 0.0,
+                },
+                &SdfShapeStyle::solid(self.text_color),
+                material,
+                gpu_state,
+            )?;
+        }
+        Ok(())
+    }
+}
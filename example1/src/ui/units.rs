@@ -0,0 +1,78 @@
+//! Widgets are sized in meters; what they rasterize (glyphs, panel textures) is sized in
+//! pixels.  A [PanelDensity] ties the two together, so a widget only has to say how big it
+//! is, and looks the same on any panel size and any headset.
+
+/// a Quest 2 eye buffer at its default resolution, near the middle of the view
+pub const QUEST2_PIXELS_PER_RADIAN: f32 = 1150.0;
+
+/// Eye buffer pixels per radian near the middle of a view `width` pixels across, whose left
+/// and right edges are `angle_left` and `angle_right` radians off center.
+pub fn eye_pixels_per_radian(width: u32, angle_left: f32, angle_right: f32) -> f32 {
+    // tan grows by one per radian at the middle
+    width as f32 / (angle_right.tan() - angle_left.tan())
+}
+
+/// how far away UI panels are usually held, meters
+pub const ARMS_LENGTH: f32 = 0.9;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PanelDensity {
+    pub pixels_per_meter: f32,
+}
+
+impl PanelDensity {
+    pub fn new(pixels_per_meter: f32) -> Self {
+        Self { pixels_per_meter }
+    }
+
+    /// one texel per eye-buffer pixel for a panel `distance` meters away, facing the viewer
+    pub fn for_viewing_distance(pixels_per_radian: f32, distance: f32) -> Self {
+        Self::new(pixels_per_radian / distance.max(0.01))
+    }
+
+    /// for a headset whose eye buffers have `pixels_per_radian`, with panels at [ARMS_LENGTH]
+    pub fn for_headset(pixels_per_radian: f32) -> Self {
+        Self::for_viewing_distance(pixels_per_radian, ARMS_LENGTH)
+    }
+
+    pub fn pixels(&self, meters: f32) -> f32 {
+        meters * self.pixels_per_meter
+    }
+
+    pub fn meters(&self, pixels: f32) -> f32 {
+        pixels / self.pixels_per_meter
+    }
+
+    /// The size to rasterize glyphs at for lines `line_height` meters tall.  The glyph
+    /// cache's font size is the height of a line (ascent to descent) in pixels.
+    pub fn font_size(&self, line_height: f32) -> f32 {
+        self.pixels(line_height).round().max(1.0)
+    }
+
+    /// the texture to render a `width` by `height` meter panel into, at least 1x1
+    pub fn texture_size(&self, width: f32, height: f32) -> (i32, i32) {
+        (
+            (self.pixels(width).ceil() as i32).max(1),
+            (self.pixels(height).ceil() as i32).max(1),
+        )
+    }
+}
+
+impl Default for PanelDensity {
+    fn default() -> Self {
+        Self::for_headset(QUEST2_PIXELS_PER_RADIAN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_4;
+
+    #[test]
+    fn symmetric_view() {
+        // 90° across, so one unit of tangent each side of the middle
+        let ppr = eye_pixels_per_radian(2000, -FRAC_PI_4, FRAC_PI_4);
+        assert!((ppr - 1000.0).abs() < 0.1, "{}", ppr);
+    }
+}
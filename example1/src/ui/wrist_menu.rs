@@ -0,0 +1,89 @@
+//! A small panel worn on the left wrist, like a watch.  It follows the left grip pose and
+//! only shows while the user turns it toward their eyes.
+
+use crate::pose_stream::Pose;
+use crate::scene::matrix_rotation_about_y;
+use crate::ui::scroll::{ScrollLayout, ScrollView};
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::linear::{
+    xr_matrix4x4f_create_translation, xr_vector3f_dot, xr_vector3f_normalize, XrMatrix4x4f,
+    XrVector3f,
+};
+use std::f32::consts::FRAC_PI_2;
+
+pub struct WristMenu {
+    /// The panel relative to the left grip pose (+X right, +Y up along the thumb, +Z back
+    /// toward the elbow).  The default puts it on the back of the wrist, facing away from
+    /// the palm; controllers differ, so tune it per device.
+    pub offset: XrMatrix4x4f,
+    /// radians; the panel shows once the angle between its normal and the direction to the
+    /// eyes drops below this
+    pub show_angle: f32,
+    /// radians, larger than [WristMenu::show_angle] so the panel does not flicker at the edge
+    pub hide_angle: f32,
+    /// the widgets on the panel.  Its model is overwritten by [WristMenu::update].
+    pub view: ScrollView,
+    visible: bool,
+}
+
+impl WristMenu {
+    /// a `width` x `height` meter panel
+    pub fn new(width: f32, height: f32) -> Result<Self, GLErrorWrapper> {
+        let offset = xr_matrix4x4f_create_translation(-0.045, 0.0, 0.07)
+            * matrix_rotation_about_y(FRAC_PI_2);
+        Ok(Self {
+            offset,
+            show_angle: 35f32.to_radians(),
+            hide_angle: 50f32.to_radians(),
+            view: ScrollView::new(offset, width, height, ScrollLayout::List),
+            visible: false,
+        })
+    }
+
+    /// Follow the left grip and decide whether the user is looking at it.  Either pose
+    /// missing (controller asleep, tracking lost) hides the panel.  Returns
+    /// [WristMenu::is_visible].
+    pub fn update(&mut self, grip: Option<&Pose>, eyes: Option<&XrVector3f>) -> bool {
+        let (Some(grip), Some(eyes)) = (grip, eyes) else {
+            self.visible = false;
+            return false;
+        };
+        let model = grip.matrix() * self.offset;
+        self.view.model = model;
+
+        let m = &model.m;
+        let normal = xr_vector3f_normalize(&XrVector3f::new(m[8], m[9], m[10]));
+        let to_eyes = xr_vector3f_normalize(&XrVector3f::new(
+            eyes.x - m[12],
+            eyes.y - m[13],
+            eyes.z - m[14],
+        ));
+        let angle = xr_vector3f_dot(&normal, &to_eyes).clamp(-1.0, 1.0).acos();
+        let limit = if self.visible {
+            self.hide_angle
+        } else {
+            self.show_angle
+        };
+        self.visible = angle < limit;
+        self.visible
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// the item a pointer ray (origin, direction) is on, while the panel shows
+    pub fn item_at_ray(&self, origin: &XrVector3f, direction: &XrVector3f) -> Option<usize> {
+        if !self.visible {
+            return None;
+        }
+        self.view
+            .hit(origin, direction)
+            .and_then(|point| self.view.item_at(point))
+    }
+
+    /// the panel's model matrix, as of the last [WristMenu::update]
+    pub fn model(&self) -> &XrMatrix4x4f {
+        &self.view.model
+    }
+}
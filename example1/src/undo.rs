@@ -0,0 +1,133 @@
+//! Undo and redo for interactive tools.  Each edit is a [Command] that knows how to apply
+//! and revert itself against its owner (usually [crate::drawcore::ActiveRenderer]); the
+//! [UndoStack] keeps the last few of them.
+
+use std::collections::VecDeque;
+
+pub trait Command<C> {
+    fn apply(&mut self, owner: &mut C);
+
+    fn revert(&mut self, owner: &mut C);
+
+    /// for logs and menus, e.g. "paint stroke"
+    fn name(&self) -> &str;
+}
+
+/// A [Command] made of two closures, for edits that are easiest to describe as a before
+/// and after.
+pub struct FnCommand<C> {
+    name: String,
+    apply: Box<dyn FnMut(&mut C)>,
+    revert: Box<dyn FnMut(&mut C)>,
+}
+
+impl<C> FnCommand<C> {
+    pub fn new(
+        name: impl Into<String>,
+        apply: impl FnMut(&mut C) + 'static,
+        revert: impl FnMut(&mut C) + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            apply: Box::new(apply),
+            revert: Box::new(revert),
+        }
+    }
+}
+
+impl<C> Command<C> for FnCommand<C> {
+    fn apply(&mut self, owner: &mut C) {
+        (self.apply)(owner)
+    }
+
+    fn revert(&mut self, owner: &mut C) {
+        (self.revert)(owner)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+pub struct UndoStack<C> {
+    /// how many commands can be undone; the oldest are forgotten first
+    pub capacity: usize,
+    done: VecDeque<Box<dyn Command<C>>>,
+    undone: Vec<Box<dyn Command<C>>>,
+}
+
+impl<C> UndoStack<C> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            done: VecDeque::new(),
+            undone: vec![],
+        }
+    }
+
+    /// Remember a command whose effect has already happened (e.g. a stroke that was just
+    /// painted).  Anything that could have been redone is forgotten.
+    pub fn push(&mut self, command: impl Command<C> + 'static) {
+        self.undone.clear();
+        self.done.push_back(Box::new(command));
+        while self.done.len() > self.capacity {
+            self.done.pop_front();
+        }
+    }
+
+    /// Apply `command` and remember it.  `stack` finds this stack inside `owner`, which the
+    /// command gets mutable access to.
+    pub fn execute(
+        owner: &mut C,
+        stack: fn(&mut C) -> &mut Self,
+        mut command: impl Command<C> + 'static,
+    ) {
+        command.apply(owner);
+        stack(owner).push(command);
+    }
+
+    /// Revert the most recent command.  Returns false if there was nothing to undo.
+    pub fn undo(owner: &mut C, stack: fn(&mut C) -> &mut Self) -> bool {
+        let Some(mut command) = stack(owner).done.pop_back() else {
+            return false;
+        };
+        log::debug!("undo {}", command.name());
+        command.revert(owner);
+        stack(owner).undone.push(command);
+        true
+    }
+
+    /// Re-apply the most recently undone command.  Returns false if there was nothing to redo.
+    pub fn redo(owner: &mut C, stack: fn(&mut C) -> &mut Self) -> bool {
+        let Some(mut command) = stack(owner).undone.pop() else {
+            return false;
+        };
+        log::debug!("redo {}", command.name());
+        command.apply(owner);
+        stack(owner).done.push_back(command);
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    /// what [UndoStack::undo] would revert
+    pub fn undo_name(&self) -> Option<&str> {
+        self.done.back().map(|c| c.name())
+    }
+
+    /// what [UndoStack::redo] would re-apply
+    pub fn redo_name(&self) -> Option<&str> {
+        self.undone.last().map(|c| c.name())
+    }
+
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+}
@@ -0,0 +1,83 @@
+//! Drawing a freshly loaded scene once where nobody sees it.  Drivers put off compiling a
+//! program for the state it is drawn with, and uploading a texture, until the first draw that
+//! uses them, which then takes long enough to drop frames.  A [warm_up] straight after loading
+//! draws everything the scene has, in every direction, into a tiny [RenderTarget], and waits
+//! for the driver to finish, so that cost is paid before the first visible frame.
+
+use crate::lod::LodView;
+use crate::scene::{inverse_view_matrix, projection_matrix, MyScene};
+use crate::ui::Ui;
+use gl_thin::gl_fancy::GPUState;
+use gl_thin::gl_helper::{explode_if_gl_error, GLErrorWrapper};
+use gl_thin::linear::{XrFovf, XrQuaternionf, XrVector3f};
+use gl_thin::render_queue::RenderQueue;
+use gl_thin::render_target::RenderTarget;
+use std::f32::consts::FRAC_PI_4;
+use std::time::{Duration, Instant};
+
+/// pixels on a side; only the draws matter, not what they cover
+const WARMUP_SIZE: i32 = 16;
+
+/// Draw `scene` (and `ui`, if there is one) from the start pose, looking along ±X, ±Y and ±Z
+/// with a 90° field of view.  The level of detail is picked as for a `lod_height` pixel tall
+/// view, e.g. the eye buffers', so the meshes the first frames will draw are the ones drawn
+/// here.  Leaves the default framebuffer bound; returns how long it took.
+pub fn warm_up(
+    scene: &MyScene,
+    ui: Option<&Ui>,
+    lod_height: i32,
+    gpu_state: &mut GPUState,
+) -> Result<Duration, GLErrorWrapper> {
+    let start = Instant::now();
+    let target = RenderTarget::new(WARMUP_SIZE, WARMUP_SIZE, gpu_state)?;
+    target.bind()?;
+    let drawn = draw_all_directions(scene, ui, lod_height, gpu_state);
+    unsafe {
+        gl::Finish();
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    }
+    explode_if_gl_error()?;
+    drawn?;
+    Ok(start.elapsed())
+}
+
+fn draw_all_directions(
+    scene: &MyScene,
+    ui: Option<&Ui>,
+    lod_height: i32,
+    gpu_state: &mut GPUState,
+) -> Result<(), GLErrorWrapper> {
+    let fov = XrFovf {
+        angle_left: -FRAC_PI_4,
+        angle_right: FRAC_PI_4,
+        angle_up: FRAC_PI_4,
+        angle_down: -FRAC_PI_4,
+    };
+    let eye = XrVector3f::default_translation();
+    let (s, c) = FRAC_PI_4.sin_cos();
+    let directions = [
+        XrQuaternionf::default(),
+        XrQuaternionf::new(0.0, s, 0.0, c),
+        XrQuaternionf::new(0.0, 1.0, 0.0, 0.0),
+        XrQuaternionf::new(0.0, -s, 0.0, c),
+        XrQuaternionf::new(s, 0.0, 0.0, c),
+        XrQuaternionf::new(-s, 0.0, 0.0, c),
+    ];
+    for orientation in &directions {
+        let matrix_pv = projection_matrix(&fov) * inverse_view_matrix(orientation, &eye);
+        let mut queue = RenderQueue::new();
+        scene.queue_draws(
+            &mut queue,
+            matrix_pv,
+            LodView::new(eye, &fov, lod_height),
+            &None,
+            &[],
+            None,
+        );
+        if let Some(ui) = ui {
+            ui.queue_draws(&mut queue, matrix_pv);
+        }
+        queue.execute(gpu_state)?;
+    }
+    Ok(())
+}
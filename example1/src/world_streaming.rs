@@ -0,0 +1,221 @@
+//! Keeping only the part of a large world near the player in memory.  The world is cut into
+//! square chunks on the XZ plane; [ChunkManager] loads the ones that come within
+//! [ChunkManager::load_radius] through an [AssetLoader] and drops them, with their GPU
+//! resources, once they are past [ChunkManager::unload_radius].  With a
+//! [ChunkManager::budget], chunks out of load range go early when GPU memory runs short.
+
+use crate::asset_loader::{AssetHandle, AssetLoader};
+use gl_thin::camera_relative::WorldPosition;
+use gl_thin::gl_helper::GLErrorWrapper;
+use gl_thin::gpu_memory::{gpu_memory_totals, GpuBudget};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ChunkCoord {
+    pub x: i32,
+    pub z: i32,
+}
+
+enum ChunkState<C> {
+    Loading(AssetHandle),
+    Loaded(C),
+    /// not retried until the chunk is unloaded and wanted again
+    Failed,
+}
+
+/// `D` is what a chunk's source produces on a worker thread (decoded meshes, pixels), `C`
+/// the GPU-side chunk made from it on the GL thread.
+pub struct ChunkManager<D, C> {
+    /// edge length of a chunk, in meters
+    pub chunk_size: f64,
+    /// chunks whose center comes this close (horizontally) are loaded
+    pub load_radius: f64,
+    /// Loaded chunks stay until their center is this far away.  Keep it a chunk or so past
+    /// [ChunkManager::load_radius] so walking along a boundary does not reload the same chunk
+    /// over and over.
+    pub unload_radius: f64,
+    /// how many finished chunks get uploaded per frame, to spread the hitches
+    pub uploads_per_frame: usize,
+    /// Evicts the least recently wanted chunks, of those between the two radii, while gl-thin's
+    /// [gpu_memory_totals] are over it.  Chunks count as wanted while inside the load radius.
+    pub budget: Option<GpuBudget<ChunkCoord>>,
+    source: Arc<dyn Fn(ChunkCoord) -> Result<D, String> + Send + Sync>,
+    loader: AssetLoader<D>,
+    chunks: HashMap<ChunkCoord, ChunkState<C>>,
+    loading: HashMap<AssetHandle, ChunkCoord>,
+}
+
+impl<D: Send + 'static, C> ChunkManager<D, C> {
+    /// `source` runs on the loader's threads, so it must not touch GL
+    pub fn new(
+        chunk_size: f64,
+        load_radius: f64,
+        unload_radius: f64,
+        source: impl Fn(ChunkCoord) -> Result<D, String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            chunk_size,
+            load_radius,
+            unload_radius: unload_radius.max(load_radius),
+            uploads_per_frame: 1,
+            budget: None,
+            source: Arc::new(source),
+            loader: AssetLoader::new(2),
+            chunks: HashMap::new(),
+            loading: HashMap::new(),
+        }
+    }
+
+    pub fn chunk_at(&self, position: &WorldPosition) -> ChunkCoord {
+        ChunkCoord {
+            x: (position.x / self.chunk_size).floor() as i32,
+            z: (position.z / self.chunk_size).floor() as i32,
+        }
+    }
+
+    /// the chunk's corner with the lowest X and Z, at Y 0; chunk contents are relative to it
+    pub fn chunk_origin(&self, coord: ChunkCoord) -> WorldPosition {
+        WorldPosition::new(
+            coord.x as f64 * self.chunk_size,
+            0.0,
+            coord.z as f64 * self.chunk_size,
+        )
+    }
+
+    fn horizontal_distance(&self, coord: ChunkCoord, position: &WorldPosition) -> f64 {
+        let half = self.chunk_size / 2.0;
+        let origin = self.chunk_origin(coord);
+        let dx = origin.x + half - position.x;
+        let dz = origin.z + half - position.z;
+        (dx * dx + dz * dz).sqrt()
+    }
+
+    /// Call once per frame on the GL thread.  Requests the chunks that came into range,
+    /// nearest first, drops the ones that left it, and turns up to
+    /// [ChunkManager::uploads_per_frame] finished loads into GPU chunks with `upload`.
+    pub fn update(
+        &mut self,
+        player: &WorldPosition,
+        mut upload: impl FnMut(ChunkCoord, D) -> Result<C, GLErrorWrapper>,
+    ) {
+        // unload first, so the memory is free before anything new arrives
+        let far: Vec<ChunkCoord> = self
+            .chunks
+            .keys()
+            .copied()
+            .filter(|coord| self.horizontal_distance(*coord, player) > self.unload_radius)
+            .collect();
+        for coord in far {
+            self.unload(coord);
+        }
+
+        if self.budget.is_some() {
+            let wanted: Vec<ChunkCoord> = self
+                .chunks
+                .keys()
+                .copied()
+                .filter(|coord| self.horizontal_distance(*coord, player) <= self.load_radius)
+                .collect();
+            if let Some(budget) = &mut self.budget {
+                budget.begin_frame();
+                for coord in &wanted {
+                    budget.touch(coord);
+                }
+            }
+        }
+
+        let center = self.chunk_at(player);
+        let reach = (self.load_radius / self.chunk_size).ceil() as i32 + 1;
+        let mut wanted = vec![];
+        for x in center.x - reach..=center.x + reach {
+            for z in center.z - reach..=center.z + reach {
+                let coord = ChunkCoord { x, z };
+                let distance = self.horizontal_distance(coord, player);
+                if distance <= self.load_radius && !self.chunks.contains_key(&coord) {
+                    wanted.push((distance, coord));
+                }
+            }
+        }
+        wanted.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (_, coord) in wanted {
+            let source = self.source.clone();
+            let handle = self.loader.request(move || source(coord));
+            if !self.loader.is_pending(handle) {
+                // the workers are gone; the chunk stays unloaded and is asked for again
+                // at the next update
+                log::warn!("chunk {:?} could not be queued for loading", coord);
+                break;
+            }
+            self.chunks.insert(coord, ChunkState::Loading(handle));
+            self.loading.insert(handle, coord);
+        }
+
+        for (handle, result) in self.loader.poll(self.uploads_per_frame) {
+            let Some(coord) = self.loading.remove(&handle) else {
+                continue;
+            };
+            let before = gpu_memory_totals().total_bytes();
+            let state = match result.map(|data| upload(coord, data)) {
+                Ok(Ok(chunk)) => {
+                    if let Some(budget) = &mut self.budget {
+                        let after = gpu_memory_totals().total_bytes();
+                        budget.register(coord, after.saturating_sub(before));
+                    }
+                    ChunkState::Loaded(chunk)
+                }
+                Ok(Err(e)) => {
+                    log::warn!("chunk {:?} upload malfunction {}", coord, e);
+                    ChunkState::Failed
+                }
+                Err(e) => {
+                    log::warn!("chunk {:?} load malfunction {}", coord, e);
+                    ChunkState::Failed
+                }
+            };
+            self.chunks.insert(coord, state);
+        }
+
+        let evicted = match &mut self.budget {
+            Some(budget) => budget.enforce(gpu_memory_totals().total_bytes()),
+            None => vec![],
+        };
+        for coord in evicted {
+            self.unload(coord);
+        }
+    }
+
+    /// drop a chunk now, loaded or not; it comes back if it is still in range at the next update
+    pub fn unload(&mut self, coord: ChunkCoord) {
+        if let Some(budget) = &mut self.budget {
+            budget.remove(&coord);
+        }
+        if let Some(ChunkState::Loading(handle)) = self.chunks.remove(&coord) {
+            self.loader.cancel(handle);
+            self.loading.remove(&handle);
+        }
+    }
+
+    /// unload everything, e.g. after teleporting across the world
+    pub fn clear(&mut self) {
+        let all: Vec<ChunkCoord> = self.chunks.keys().copied().collect();
+        for coord in all {
+            self.unload(coord);
+        }
+    }
+
+    pub fn loaded(&self) -> impl Iterator<Item = (ChunkCoord, &C)> {
+        self.chunks.iter().filter_map(|(coord, state)| match state {
+            ChunkState::Loaded(chunk) => Some((*coord, chunk)),
+            _ => None,
+        })
+    }
+
+    pub fn loaded_count(&self) -> usize {
+        self.loaded().count()
+    }
+
+    pub fn loading_count(&self) -> usize {
+        self.loading.len()
+    }
+}
@@ -1,89 +1,490 @@
+use crate::gestures::GestureButton;
+use crate::haptics::{HapticHand, HapticOutput};
+use crate::stick_response::StickResponse;
 use gl_thin::errors::{Wrappable, XrErrorWrapped};
 use gl_thin::openxr_helpers::Backend;
 use openxr::{
-    Action, ActionSet, ActiveActionSet, Binding, Instance, Session, Space, SpaceLocation,
+    Action, ActionSet, ActionTy, ActiveActionSet, Binding, Haptic, HapticVibration, Instance,
+    Session, Space, SpaceLocation,
 };
-use openxr_sys::{Path, Posef, Time};
+use openxr_sys::{Path, Posef, Time, Vector2f};
+
+/// Each context is an action set.  When an input is bound in more than one active set,
+/// only the highest priority one sees it, so opening the menu takes the trigger away from gameplay.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InputContext {
+    Gameplay,
+    Menu,
+    Debug,
+}
+
+impl InputContext {
+    pub const ALL: [InputContext; 3] = [
+        InputContext::Gameplay,
+        InputContext::Menu,
+        InputContext::Debug,
+    ];
+
+    pub fn priority(self) -> u32 {
+        match self {
+            InputContext::Gameplay => 0,
+            InputContext::Menu => 10,
+            InputContext::Debug => 20,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            InputContext::Gameplay => "gameplay",
+            InputContext::Menu => "menu",
+            InputContext::Debug => "debug",
+        }
+    }
+}
+
+/// What the menu-related buttons did since the last sync
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MenuInput {
+    /// the menu button, whether it opened or closed the menu
+    pub toggle: bool,
+    pub select: bool,
+    /// thumbstick forward deflection, -1..1
+    pub scroll: f32,
+    pub debug_next: bool,
+}
+
+/// A button as of a sync
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ButtonState {
+    /// down now
+    pub held: bool,
+    /// went down since the last sync
+    pub pressed: bool,
+    /// came up since the last sync
+    pub released: bool,
+}
+
+/// One controller's buttons and axes as of a sync; all idle while it is not tracked or its
+/// inputs belong to a higher priority [InputContext]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ControllerState {
+    /// 0..1
+    pub trigger: f32,
+    /// how hard the grip is squeezed, 0..1
+    pub squeeze: f32,
+    /// -1..1 on each axis, shaped by [XrInputs::stick_response]
+    pub thumbstick: Vector2f,
+    /// A on the right controller, X on the left
+    pub primary: ButtonState,
+    /// B on the right controller, Y on the left
+    pub secondary: ButtonState,
+}
+
+/// Both controllers as of a sync, from [XrInputs::input_state], for scene code that reacts
+/// to the buttons directly rather than through the named actions
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct InputState {
+    pub left: ControllerState,
+    pub right: ControllerState,
+}
+
+impl InputState {
+    pub fn a(&self) -> ButtonState {
+        self.right.primary
+    }
+
+    pub fn b(&self) -> ButtonState {
+        self.right.secondary
+    }
+
+    pub fn x(&self) -> ButtonState {
+        self.left.primary
+    }
+
+    pub fn y(&self) -> ButtonState {
+        self.left.secondary
+    }
+}
 
 pub struct XrInputs {
-    pub action_set: ActionSet,
+    pub gameplay_set: ActionSet,
+    pub menu_set: ActionSet,
+    pub debug_set: ActionSet,
+    pub user_hand_left: Path,
     pub user_hand_right: Path,
+
+    // gameplay
     pub controller_1: Action<Posef>,
     pub controller_space_1: Space,
+    /// the left grip, for things worn on the wrist
+    pub controller_space_left: Space,
+    pub select: Action<bool>,
+    /// opens the menu
+    pub menu_button: Action<bool>,
+    /// step back and forth through [crate::undo::UndoStack]
+    pub undo: Action<bool>,
+    pub redo: Action<bool>,
+    /// the controllers' vibration, see [XrInputs::vibrate]
+    pub haptic: Action<Haptic>,
+    /// held to aim, see [crate::teleport::Teleport]
+    pub teleport: Action<bool>,
+    /// the raw controls, per hand, see [XrInputs::input_state]
+    pub trigger: Action<f32>,
+    pub squeeze: Action<f32>,
+    pub thumbstick: Action<Vector2f>,
+    pub primary_button: Action<bool>,
+    pub secondary_button: Action<bool>,
+
+    // menu; these share inputs with gameplay and win while the menu is active
+    pub menu_select: Action<bool>,
+    pub menu_scroll: Action<Vector2f>,
+    /// the menu button again, to close it
+    pub menu_back: Action<bool>,
+
+    // debug
+    pub debug_next: Action<bool>,
+
+    /// dead zone and curve applied by [XrInputs::thumbstick]
+    pub stick_response: StickResponse,
+
+    /// the sets synced each frame
+    active: Vec<InputContext>,
 }
 
 impl XrInputs {
     pub fn new(instance: &Instance, xr_session: &Session<Backend>) -> Result<Self, XrErrorWrapped> {
-        let action_set = instance
-            .create_action_set("pants", "pants", 0)
-            .annotate_if_err(Some(instance), "failed to create_action_set")?;
+        let create_set = |context: InputContext| {
+            instance
+                .create_action_set(context.name(), context.name(), context.priority())
+                .annotate_if_err(Some(instance), "failed to create_action_set")
+        };
+        let gameplay_set = create_set(InputContext::Gameplay)?;
+        let menu_set = create_set(InputContext::Menu)?;
+        let debug_set = create_set(InputContext::Debug)?;
 
         //
 
-        let user_hand_left = instance
-            .string_to_path("/user/hand/left")
-            .annotate_if_err(Some(instance), "failed to ")?;
-        let user_hand_right = instance
-            .string_to_path("/user/hand/right")
-            .annotate_if_err(Some(instance), "failed to ")?;
-        let pose_action = action_set
-            .create_action::<Posef>(
-                "hand_pose",
-                "controller 1",
-                &[user_hand_left, user_hand_right],
-            )
-            .annotate_if_err(Some(instance), "failed to ")?;
-        let left_grip_pose = instance
-            .string_to_path("/user/hand/left/input/grip/pose")
-            .annotate_if_err(Some(instance), "failed to ")?;
-        let right_grip_pose = instance
-            .string_to_path("/user/hand/right/input/grip/pose")
-            .annotate_if_err(Some(instance), "failed to ")?;
-        let bindings = [
-            Binding::new(&pose_action, left_grip_pose),
-            Binding::new(&pose_action, right_grip_pose),
-        ];
-        {
-            let interaction_profile = instance
-                .string_to_path("/interaction_profiles/khr/simple_controller")
-                .annotate_if_err(Some(instance), "failed to ")?;
+        let path = |name: &str| {
+            instance
+                .string_to_path(name)
+                .annotate_if_err(Some(instance), format!("failed to make path {}", name))
+        };
+        let user_hand_left = path("/user/hand/left")?;
+        let user_hand_right = path("/user/hand/right")?;
+        let hands = [user_hand_left, user_hand_right];
 
+        let pose_action =
+            action::<Posef>(instance, &gameplay_set, "hand_pose", "controller 1", &hands)?;
+        let select = action::<bool>(instance, &gameplay_set, "select", "select", &hands)?;
+        let menu_button = action::<bool>(instance, &gameplay_set, "open_menu", "open menu", &[])?;
+        let undo = action::<bool>(instance, &gameplay_set, "undo", "undo", &[])?;
+        let redo = action::<bool>(instance, &gameplay_set, "redo", "redo", &[])?;
+        let haptic = action::<Haptic>(instance, &gameplay_set, "haptic", "vibration", &hands)?;
+        let teleport = action::<bool>(instance, &gameplay_set, "teleport", "teleport", &[])?;
+        let trigger = action::<f32>(instance, &gameplay_set, "trigger", "trigger", &hands)?;
+        let squeeze = action::<f32>(instance, &gameplay_set, "squeeze", "squeeze", &hands)?;
+        let thumbstick =
+            action::<Vector2f>(instance, &gameplay_set, "thumbstick", "thumbstick", &hands)?;
+        let primary_button =
+            action::<bool>(instance, &gameplay_set, "primary_button", "A or X", &hands)?;
+        let secondary_button = action::<bool>(
+            instance,
+            &gameplay_set,
+            "secondary_button",
+            "B or Y",
+            &hands,
+        )?;
+        let menu_select =
+            action::<bool>(instance, &menu_set, "menu_select", "menu select", &hands)?;
+        let menu_scroll =
+            action::<Vector2f>(instance, &menu_set, "menu_scroll", "menu scroll", &hands)?;
+        let menu_back = action::<bool>(instance, &menu_set, "close_menu", "close menu", &[])?;
+        let debug_next = action::<bool>(instance, &debug_set, "debug_next", "debug next", &[])?;
+
+        {
+            let interaction_profile = path("/interaction_profiles/khr/simple_controller")?;
+            let bindings = [
+                Binding::new(&pose_action, path("/user/hand/left/input/grip/pose")?),
+                Binding::new(&pose_action, path("/user/hand/right/input/grip/pose")?),
+                Binding::new(&select, path("/user/hand/left/input/select/click")?),
+                Binding::new(&select, path("/user/hand/right/input/select/click")?),
+                Binding::new(&menu_select, path("/user/hand/left/input/select/click")?),
+                Binding::new(&menu_select, path("/user/hand/right/input/select/click")?),
+                Binding::new(&trigger, path("/user/hand/left/input/select/click")?),
+                Binding::new(&trigger, path("/user/hand/right/input/select/click")?),
+                Binding::new(&menu_button, path("/user/hand/left/input/menu/click")?),
+                Binding::new(&menu_back, path("/user/hand/left/input/menu/click")?),
+                Binding::new(&haptic, path("/user/hand/left/output/haptic")?),
+                Binding::new(&haptic, path("/user/hand/right/output/haptic")?),
+            ];
             instance
                 .suggest_interaction_profile_bindings(interaction_profile, &bindings)
-                .annotate_if_err(Some(instance), "failed to ")?;
+                .annotate_if_err(
+                    Some(instance),
+                    "failed to suggest simple_controller bindings",
+                )?;
         }
 
         {
-            let interaction_profile = instance
-                .string_to_path("/interaction_profiles/oculus/touch_controller")
-                .annotate_if_err(Some(instance), "failed to ")?;
+            let interaction_profile = path("/interaction_profiles/oculus/touch_controller")?;
+            let bindings = [
+                Binding::new(&pose_action, path("/user/hand/left/input/grip/pose")?),
+                Binding::new(&pose_action, path("/user/hand/right/input/grip/pose")?),
+                Binding::new(&select, path("/user/hand/left/input/trigger/value")?),
+                Binding::new(&select, path("/user/hand/right/input/trigger/value")?),
+                Binding::new(&menu_select, path("/user/hand/left/input/trigger/value")?),
+                Binding::new(&menu_select, path("/user/hand/right/input/trigger/value")?),
+                Binding::new(&menu_scroll, path("/user/hand/left/input/thumbstick")?),
+                Binding::new(&menu_scroll, path("/user/hand/right/input/thumbstick")?),
+                Binding::new(&menu_button, path("/user/hand/left/input/menu/click")?),
+                Binding::new(&menu_back, path("/user/hand/left/input/menu/click")?),
+                Binding::new(&debug_next, path("/user/hand/right/input/b/click")?),
+                Binding::new(&undo, path("/user/hand/left/input/x/click")?),
+                Binding::new(&redo, path("/user/hand/left/input/y/click")?),
+                Binding::new(&teleport, path("/user/hand/right/input/thumbstick/click")?),
+                Binding::new(&trigger, path("/user/hand/left/input/trigger/value")?),
+                Binding::new(&trigger, path("/user/hand/right/input/trigger/value")?),
+                Binding::new(&squeeze, path("/user/hand/left/input/squeeze/value")?),
+                Binding::new(&squeeze, path("/user/hand/right/input/squeeze/value")?),
+                Binding::new(&thumbstick, path("/user/hand/left/input/thumbstick")?),
+                Binding::new(&thumbstick, path("/user/hand/right/input/thumbstick")?),
+                Binding::new(&primary_button, path("/user/hand/left/input/x/click")?),
+                Binding::new(&primary_button, path("/user/hand/right/input/a/click")?),
+                Binding::new(&secondary_button, path("/user/hand/left/input/y/click")?),
+                Binding::new(&secondary_button, path("/user/hand/right/input/b/click")?),
+                Binding::new(&haptic, path("/user/hand/left/output/haptic")?),
+                Binding::new(&haptic, path("/user/hand/right/output/haptic")?),
+            ];
             instance
                 .suggest_interaction_profile_bindings(interaction_profile, &bindings)
-                .annotate_if_err(Some(instance), "failed to ")?;
+                .annotate_if_err(
+                    Some(instance),
+                    "failed to suggest touch_controller bindings",
+                )?;
         }
 
         let mut posef = Posef::default();
         posef.orientation.w = 1.0;
         let controller_space_1 = pose_action
             .create_space(xr_session.clone(), user_hand_right, posef)
-            .annotate_if_err(Some(instance), "failed to ")?;
+            .annotate_if_err(Some(instance), "failed to create controller space")?;
+        let controller_space_left = pose_action
+            .create_space(xr_session.clone(), user_hand_left, posef)
+            .annotate_if_err(Some(instance), "failed to create left controller space")?;
 
         //
 
         xr_session
-            .attach_action_sets(&[&action_set])
+            .attach_action_sets(&[&gameplay_set, &menu_set, &debug_set])
             .annotate_if_err(Some(instance), "failed to attach_action_sets")?;
 
         Ok(Self {
-            action_set,
+            gameplay_set,
+            menu_set,
+            debug_set,
+            user_hand_left,
             user_hand_right,
             controller_1: pose_action,
             controller_space_1,
+            controller_space_left,
+            select,
+            menu_button,
+            undo,
+            redo,
+            haptic,
+            teleport,
+            trigger,
+            squeeze,
+            thumbstick,
+            primary_button,
+            secondary_button,
+            menu_select,
+            menu_scroll,
+            menu_back,
+            debug_next,
+            stick_response: StickResponse::default(),
+            active: vec![InputContext::Gameplay],
         })
     }
 
+    pub fn action_set(&self, context: InputContext) -> &ActionSet {
+        match context {
+            InputContext::Gameplay => &self.gameplay_set,
+            InputContext::Menu => &self.menu_set,
+            InputContext::Debug => &self.debug_set,
+        }
+    }
+
+    /// Sync `context`'s actions from the next [XrInputs::sync_actions] on.
+    pub fn activate(&mut self, context: InputContext) {
+        if !self.is_active(context) {
+            self.active.push(context);
+        }
+    }
+
+    pub fn deactivate(&mut self, context: InputContext) {
+        self.active.retain(|c| *c != context);
+    }
+
+    pub fn set_active(&mut self, context: InputContext, active: bool) {
+        if active {
+            self.activate(context)
+        } else {
+            self.deactivate(context)
+        }
+    }
+
+    pub fn is_active(&self, context: InputContext) -> bool {
+        self.active.contains(&context)
+    }
+
+    /// the active context with the highest priority, the one that gets contested inputs
+    pub fn focused(&self) -> Option<InputContext> {
+        self.active.iter().copied().max_by_key(|c| c.priority())
+    }
+
     pub fn sync_actions(&self, xr_session: &Session<Backend>) -> openxr::Result<()> {
-        xr_session.sync_actions(&[ActiveActionSet::new(&self.action_set)])
+        let active: Vec<_> = self
+            .active
+            .iter()
+            .map(|c| ActiveActionSet::new(self.action_set(*c)))
+            .collect();
+        xr_session.sync_actions(&active)
+    }
+
+    /// true on the sync where a button went down
+    pub fn pressed<G>(&self, xr_session: &Session<G>, action: &Action<bool>) -> bool {
+        action
+            .state(xr_session, Path::NULL)
+            .is_ok_and(|s| s.is_active && s.changed_since_last_sync && s.current_state)
+    }
+
+    /// true while a button is down
+    pub fn held<G>(&self, xr_session: &Session<G>, action: &Action<bool>) -> bool {
+        self.held_by(xr_session, action, Path::NULL)
+    }
+
+    /// like [XrInputs::held], for the button on one `hand`, e.g. [XrInputs::user_hand_right]
+    pub fn held_by<G>(&self, xr_session: &Session<G>, action: &Action<bool>, hand: Path) -> bool {
+        action
+            .state(xr_session, hand)
+            .is_ok_and(|s| s.is_active && s.current_state)
+    }
+
+    /// the larger deflection of either thumbstick bound to `action`, shaped by
+    /// [XrInputs::stick_response]
+    pub fn thumbstick<G>(&self, xr_session: &Session<G>, action: &Action<Vector2f>) -> Vector2f {
+        let raw = [self.user_hand_left, self.user_hand_right]
+            .iter()
+            .filter_map(|hand| action.state(xr_session, *hand).ok())
+            .filter(|s| s.is_active)
+            .map(|s| s.current_state)
+            .fold(Vector2f::default(), |a, b| {
+                if b.x * b.x + b.y * b.y > a.x * a.x + a.y * a.y {
+                    b
+                } else {
+                    a
+                }
+            });
+        self.stick_response.apply(raw)
+    }
+
+    /// Both controllers' raw controls, as of the last [XrInputs::sync_actions].
+    pub fn input_state<G>(&self, xr_session: &Session<G>) -> InputState {
+        InputState {
+            left: self.controller_state(xr_session, self.user_hand_left),
+            right: self.controller_state(xr_session, self.user_hand_right),
+        }
+    }
+
+    fn controller_state<G>(&self, xr_session: &Session<G>, hand: Path) -> ControllerState {
+        let value = |action: &Action<f32>| {
+            action
+                .state(xr_session, hand)
+                .ok()
+                .filter(|s| s.is_active)
+                .map_or(0.0, |s| s.current_state)
+        };
+        let button = |action: &Action<bool>| match action.state(xr_session, hand) {
+            Ok(s) if s.is_active => ButtonState {
+                held: s.current_state,
+                pressed: s.changed_since_last_sync && s.current_state,
+                released: s.changed_since_last_sync && !s.current_state,
+            },
+            _ => ButtonState::default(),
+        };
+        let thumbstick = self
+            .thumbstick
+            .state(xr_session, hand)
+            .ok()
+            .filter(|s| s.is_active)
+            .map_or(Vector2f::default(), |s| s.current_state);
+        ControllerState {
+            trigger: value(&self.trigger),
+            squeeze: value(&self.squeeze),
+            thumbstick: self.stick_response.apply(thumbstick),
+            primary: button(&self.primary_button),
+            secondary: button(&self.secondary_button),
+        }
+    }
+
+    /// Read the menu and debug contexts' actions; inactive ones read as idle.
+    pub fn menu_input<G>(&self, xr_session: &Session<G>) -> MenuInput {
+        MenuInput {
+            toggle: self.pressed(xr_session, &self.menu_button)
+                || self.pressed(xr_session, &self.menu_back),
+            select: self.pressed(xr_session, &self.menu_select),
+            scroll: self.thumbstick(xr_session, &self.menu_scroll).y,
+            debug_next: self.pressed(xr_session, &self.debug_next),
+        }
+    }
+
+    /// whether each of the [GestureButton]s is down, for [crate::gestures::GestureDetector]
+    pub fn gesture_buttons<G>(&self, xr_session: &Session<G>) -> [(GestureButton, bool); 4] {
+        [
+            (
+                GestureButton::Select,
+                self.held(xr_session, &self.select) || self.held(xr_session, &self.menu_select),
+            ),
+            (
+                GestureButton::Menu,
+                self.held(xr_session, &self.menu_button) || self.held(xr_session, &self.menu_back),
+            ),
+            (GestureButton::Undo, self.held(xr_session, &self.undo)),
+            (GestureButton::Redo, self.held(xr_session, &self.redo)),
+        ]
+    }
+
+    /// pass one of [crate::haptics::HapticSequencer::update]'s outputs to the runtime
+    pub fn vibrate<G>(
+        &self,
+        xr_session: &Session<G>,
+        hand: HapticHand,
+        output: HapticOutput,
+    ) -> openxr::Result<()> {
+        let path = match hand {
+            HapticHand::Left => self.user_hand_left,
+            HapticHand::Right => self.user_hand_right,
+        };
+        match output {
+            HapticOutput::Vibrate {
+                amplitude,
+                frequency,
+                duration,
+            } => {
+                let frequency = if frequency > 0.0 {
+                    frequency
+                } else {
+                    openxr::FREQUENCY_UNSPECIFIED
+                };
+                let vibration = HapticVibration::new()
+                    .amplitude(amplitude)
+                    .frequency(frequency)
+                    .duration(openxr::Duration::from_nanos(duration.as_nanos() as i64));
+                self.haptic.apply_feedback(xr_session, path, &vibration)
+            }
+            HapticOutput::Stop => self.haptic.stop_feedback(xr_session, path),
+        }
     }
 
     pub fn controller_1_locate(
@@ -110,4 +511,34 @@ impl XrInputs {
             None
         }
     }
+
+    pub fn controller_left_locate_if_active<G>(
+        &self,
+        xr_session: &Session<G>,
+        base: &Space,
+        predicted_display_time: Time,
+    ) -> Option<SpaceLocation> {
+        if self
+            .controller_1
+            .is_active(xr_session, self.user_hand_left)
+            .unwrap_or(false)
+        {
+            self.controller_space_left
+                .locate(base, predicted_display_time)
+                .ok()
+        } else {
+            None
+        }
+    }
+}
+
+fn action<T: ActionTy>(
+    instance: &Instance,
+    set: &ActionSet,
+    name: &str,
+    localized_name: &str,
+    subaction_paths: &[Path],
+) -> Result<Action<T>, XrErrorWrapped> {
+    set.create_action::<T>(name, localized_name, subaction_paths)
+        .annotate_if_err(Some(instance), format!("failed to create action {}", name))
 }
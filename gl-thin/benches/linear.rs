@@ -0,0 +1,86 @@
+//! The matrix math every frame does a few hundred times: building model and view matrices,
+//! multiplying them, inverting poses.  `cargo bench -p gl-thin` on the host; the paths that
+//! need a GL context are timed on the device by example1's microbench module.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use gl_thin::linear::{
+    xr_matrix4x4f_create_from_quaternion, xr_matrix4x4f_create_projection_fov,
+    xr_matrix4x4f_create_translation_rotation_scale, xr_matrix4x4f_invert,
+    xr_matrix4x4f_invert_rigid_body, xr_matrix4x4f_multiply, xr_matrix4x4f_transform_vector3f,
+    GraphicsAPI, XrFovf, XrMatrix4x4f, XrQuaternionf, XrVector3f,
+};
+use std::hint::black_box;
+
+/// a head-height pose turned a little about two axes, like a typical eye
+fn sample_pose() -> (XrVector3f, XrQuaternionf) {
+    let (s, c) = (0.3f32.sin(), 0.3f32.cos());
+    let rotation = XrQuaternionf::new(0.1 * s, s, 0.0, c);
+    (XrVector3f::new(0.2, 1.6, -0.4), rotation)
+}
+
+fn sample_matrix() -> XrMatrix4x4f {
+    let (translation, rotation) = sample_pose();
+    xr_matrix4x4f_create_translation_rotation_scale(
+        &translation,
+        &rotation,
+        &XrVector3f::scale(0.5),
+    )
+}
+
+fn construction(c: &mut Criterion) {
+    let (translation, rotation) = sample_pose();
+    let scale = XrVector3f::scale(0.5);
+    c.bench_function("create_translation_rotation_scale", |b| {
+        b.iter(|| {
+            xr_matrix4x4f_create_translation_rotation_scale(
+                black_box(&translation),
+                black_box(&rotation),
+                black_box(&scale),
+            )
+        })
+    });
+    c.bench_function("create_from_quaternion", |b| {
+        b.iter(|| xr_matrix4x4f_create_from_quaternion(black_box(&rotation)))
+    });
+    let fov = XrFovf {
+        angle_left: -0.9,
+        angle_right: 0.8,
+        angle_up: 0.85,
+        angle_down: -0.95,
+    };
+    c.bench_function("create_projection_fov", |b| {
+        b.iter(|| {
+            xr_matrix4x4f_create_projection_fov(
+                GraphicsAPI::GraphicsOpenGLES,
+                black_box(&fov),
+                0.05,
+                100.0,
+            )
+        })
+    });
+}
+
+fn arithmetic(c: &mut Criterion) {
+    let a = sample_matrix();
+    let b = xr_matrix4x4f_invert(&a);
+    c.bench_function("multiply", |bencher| {
+        bencher.iter(|| xr_matrix4x4f_multiply(black_box(&a), black_box(&b)))
+    });
+    c.bench_function("invert_rigid_body", |bencher| {
+        bencher.iter(|| xr_matrix4x4f_invert_rigid_body(black_box(&a)))
+    });
+    c.bench_function("invert", |bencher| {
+        bencher.iter(|| xr_matrix4x4f_invert(black_box(&a)))
+    });
+    let v = XrVector3f::new(1.0, 2.0, 3.0);
+    c.bench_function("transform_vector3f", |bencher| {
+        bencher.iter(|| xr_matrix4x4f_transform_vector3f(black_box(&a), black_box(&v)))
+    });
+    let (_, q) = sample_pose();
+    c.bench_function("quaternion multiply", |bencher| {
+        bencher.iter(|| black_box(q) * black_box(q))
+    });
+}
+
+criterion_group!(benches, construction, arithmetic);
+criterion_main!(benches);
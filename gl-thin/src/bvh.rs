@@ -0,0 +1,250 @@
+//! A bounding volume hierarchy: a binary tree of boxes over a set of boxed items, so a ray
+//! only visits the items whose boxes it passes through instead of all of them.  When items
+//! move, [Bvh::refit] grows or shrinks the boxes above them without rebuilding the tree;
+//! that stays correct, but the tree gets looser the farther things move from where it was
+//! built.
+
+/// An axis-aligned box
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    /// contains nothing; the starting point for [Aabb::union]s
+    pub const EMPTY: Aabb = Aabb {
+        min: [f32::INFINITY; 3],
+        max: [f32::NEG_INFINITY; 3],
+    };
+
+    pub fn of_points(points: impl IntoIterator<Item = [f32; 3]>) -> Self {
+        points.into_iter().fold(Self::EMPTY, |bounds, p| {
+            bounds.union(&Aabb { min: p, max: p })
+        })
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: [0, 1, 2].map(|axis| self.min[axis].min(other.min[axis])),
+            max: [0, 1, 2].map(|axis| self.max[axis].max(other.max[axis])),
+        }
+    }
+
+    pub fn center(&self) -> [f32; 3] {
+        [0, 1, 2].map(|axis| 0.5 * (self.min[axis] + self.max[axis]))
+    }
+
+    /// The box around this one's eight corners after `transform`, a column-major 4x4 matrix
+    pub fn transformed(&self, transform: &[f32; 16]) -> Aabb {
+        let m = transform;
+        let corners = (0..8).map(|corner| {
+            let p = [0, 1, 2].map(|axis| {
+                if corner & (1 << axis) == 0 {
+                    self.min[axis]
+                } else {
+                    self.max[axis]
+                }
+            });
+            [0, 1, 2].map(|row| m[row] * p[0] + m[4 + row] * p[1] + m[8 + row] * p[2] + m[12 + row])
+        });
+        Self::of_points(corners)
+    }
+
+    /// Slab test: the `t` at which `origin + t * direction` enters the box (0 if it starts
+    /// inside), if it does so by `max_t`.
+    pub fn ray_entry(&self, origin: &[f32; 3], direction: &[f32; 3], max_t: f32) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = max_t;
+        for axis in 0..3 {
+            if direction[axis] == 0.0 {
+                if origin[axis] < self.min[axis] || origin[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (self.min[axis] - origin[axis]) / direction[axis];
+            let t1 = (self.max[axis] - origin[axis]) / direction[axis];
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+            if near > far {
+                return None;
+            }
+        }
+        Some(near)
+    }
+}
+
+//
+
+/// how many items a leaf may hold before it is split
+const LEAF_SIZE: usize = 4;
+/// [Node::parent] of the root
+const NO_PARENT: usize = usize::MAX;
+
+struct Node {
+    bounds: Aabb,
+    parent: usize,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    /// `Bvh::items[first..first + count]`
+    Leaf {
+        first: usize,
+        count: usize,
+    },
+    Inner {
+        left: usize,
+        right: usize,
+    },
+}
+
+pub struct Bvh {
+    /// the root first
+    nodes: Vec<Node>,
+    /// item numbers, grouped so each leaf's are contiguous
+    items: Vec<usize>,
+    /// the leaf each item is in
+    leaf_of: Vec<usize>,
+}
+
+impl Bvh {
+    /// over items `0..boxes.len()`, item `i` in `boxes[i]`
+    pub fn build(boxes: &[Aabb]) -> Self {
+        let mut rval = Self {
+            nodes: Vec::with_capacity(2 * boxes.len() / LEAF_SIZE + 1),
+            items: (0..boxes.len()).collect(),
+            leaf_of: vec![0; boxes.len()],
+        };
+        if !boxes.is_empty() {
+            rval.build_node(boxes, 0, boxes.len(), NO_PARENT);
+        }
+        rval
+    }
+
+    /// Split the items at the median of their centers along the axis they are most spread
+    /// out on, until the pieces fit in a leaf.
+    fn build_node(&mut self, boxes: &[Aabb], first: usize, count: usize, parent: usize) -> usize {
+        let index = self.nodes.len();
+        let members = first..first + count;
+        let bounds = self.items[members.clone()]
+            .iter()
+            .fold(Aabb::EMPTY, |bounds, &item| bounds.union(&boxes[item]));
+        self.nodes.push(Node {
+            bounds,
+            parent,
+            kind: NodeKind::Leaf { first, count },
+        });
+
+        if count <= LEAF_SIZE {
+            for &item in &self.items[members] {
+                self.leaf_of[item] = index;
+            }
+            return index;
+        }
+
+        let centers = Aabb::of_points(
+            self.items[members.clone()]
+                .iter()
+                .map(|&item| boxes[item].center()),
+        );
+        let extent = [0, 1, 2].map(|axis| centers.max[axis] - centers.min[axis]);
+        let axis = (0..3)
+            .max_by(|&a, &b| extent[a].total_cmp(&extent[b]))
+            .unwrap_or(0);
+        let half = count / 2;
+        self.items[members].select_nth_unstable_by(half, |&a, &b| {
+            boxes[a].center()[axis].total_cmp(&boxes[b].center()[axis])
+        });
+
+        let left = self.build_node(boxes, first, half, index);
+        let right = self.build_node(boxes, first + half, count - half, index);
+        self.nodes[index].kind = NodeKind::Inner { left, right };
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaf_of.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaf_of.is_empty()
+    }
+
+    /// Item `item` moved to `boxes[item]`; fix the boxes of its leaf and the nodes above it.
+    /// `boxes` is the same list the tree was built from, with any changes.
+    pub fn refit(&mut self, item: usize, boxes: &[Aabb]) {
+        let Some(&leaf) = self.leaf_of.get(item) else {
+            return;
+        };
+        let mut index = leaf;
+        while index != NO_PARENT {
+            let bounds = match self.nodes[index].kind {
+                NodeKind::Leaf { first, count } => self.items[first..first + count]
+                    .iter()
+                    .fold(Aabb::EMPTY, |bounds, &item| bounds.union(&boxes[item])),
+                NodeKind::Inner { left, right } => {
+                    self.nodes[left].bounds.union(&self.nodes[right].bounds)
+                }
+            };
+            let node = &mut self.nodes[index];
+            if node.bounds == bounds {
+                // nothing above changes either
+                break;
+            }
+            node.bounds = bounds;
+            index = node.parent;
+        }
+    }
+
+    /// The nearest hit along `origin + t * direction` for `0 <= t <= max_t`, as the item
+    /// and its `t`.  `hit` tests one item against the ray, returning the `t` of its nearest
+    /// hit no farther than the limit it is given; boxes beyond the nearest hit so far are
+    /// skipped.
+    pub fn cast(
+        &self,
+        origin: &[f32; 3],
+        direction: &[f32; 3],
+        max_t: f32,
+        mut hit: impl FnMut(usize, f32) -> Option<f32>,
+    ) -> Option<(usize, f32)> {
+        let mut nearest: Option<(usize, f32)> = None;
+        let root = self.nodes.first()?;
+        let mut stack = vec![(0, root.bounds.ray_entry(origin, direction, max_t)?)];
+        while let Some((index, entry)) = stack.pop() {
+            let limit = nearest.map_or(max_t, |(_, t)| t);
+            if entry > limit {
+                continue;
+            }
+            match self.nodes[index].kind {
+                NodeKind::Leaf { first, count } => {
+                    for &item in &self.items[first..first + count] {
+                        let limit = nearest.map_or(max_t, |(_, t)| t);
+                        if let Some(t) = hit(item, limit) {
+                            if t <= limit {
+                                nearest = Some((item, t));
+                            }
+                        }
+                    }
+                }
+                NodeKind::Inner { left, right } => {
+                    let near = |child: usize| {
+                        self.nodes[child]
+                            .bounds
+                            .ray_entry(origin, direction, limit)
+                            .map(|t| (child, t))
+                    };
+                    // the nearer child goes on top, so it is searched first
+                    match (near(left), near(right)) {
+                        (Some(a), Some(b)) if a.1 <= b.1 => stack.extend([b, a]),
+                        (Some(a), Some(b)) => stack.extend([a, b]),
+                        (Some(a), None) | (None, Some(a)) => stack.push(a),
+                        (None, None) => {}
+                    }
+                }
+            }
+        }
+        nearest
+    }
+}
@@ -0,0 +1,82 @@
+//! Drawing things far from the origin without float32 jitter.  A vertex a few kilometers out
+//! has only millimeters of precision left, and `pv * model` cancels two large translations
+//! against each other in f32.  Instead, keep positions in the world as [WorldPosition]s (f64),
+//! subtract the eye's position in f64 each frame, and draw with a view-projection that has
+//! no translation in it, so everything the GPU sees is small.
+
+use crate::linear::{xr_matrix4x4f_create_translation_v, XrMatrix4x4f, XrVector3f};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct WorldPosition {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl WorldPosition {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    /// `self` moved by `offset`
+    pub fn offset(&self, offset: &XrVector3f) -> Self {
+        Self {
+            x: self.x + offset.x as f64,
+            y: self.y + offset.y as f64,
+            z: self.z + offset.z as f64,
+        }
+    }
+
+    /// where `self` is seen from `origin`; only precise when the two are close
+    pub fn relative_to(&self, origin: &WorldPosition) -> XrVector3f {
+        XrVector3f::new(
+            (self.x - origin.x) as f32,
+            (self.y - origin.y) as f32,
+            (self.z - origin.z) as f32,
+        )
+    }
+
+    pub fn distance(&self, other: &WorldPosition) -> f64 {
+        let (dx, dy, dz) = (self.x - other.x, self.y - other.y, self.z - other.z);
+        (dx * dx + dy * dy + dz * dz).sqrt()
+    }
+}
+
+/// The rebasing for one view, built again each frame (and for each eye).
+#[derive(Copy, Clone, Debug)]
+pub struct CameraRelative {
+    eye_world: WorldPosition,
+    eye_local: XrVector3f,
+}
+
+impl CameraRelative {
+    /// `tracking_origin` is where the origin of the tracking space is in the world (the
+    /// tracking space is not rotated against the world), `eye` is the eye in tracking space.
+    pub fn new(tracking_origin: WorldPosition, eye: &XrVector3f) -> Self {
+        Self {
+            eye_world: tracking_origin.offset(eye),
+            eye_local: *eye,
+        }
+    }
+
+    pub fn eye(&self) -> WorldPosition {
+        self.eye_world
+    }
+
+    /// Strip the eye's translation off the usual tracking-space `matrix_pv`.  Pair the
+    /// result with models from [CameraRelative::model], not world-space ones.
+    pub fn view_projection(&self, matrix_pv: &XrMatrix4x4f) -> XrMatrix4x4f {
+        matrix_pv * xr_matrix4x4f_create_translation_v(&self.eye_local)
+    }
+
+    /// the model matrix of something at `position`, relative to the eye, then `local`
+    /// (rotation, scale, ...) around that position
+    pub fn model(&self, position: &WorldPosition, local: &XrMatrix4x4f) -> XrMatrix4x4f {
+        xr_matrix4x4f_create_translation_v(&position.relative_to(&self.eye_world)) * local
+    }
+
+    /// where `position` is relative to the eye, e.g. for a distance check or a light
+    pub fn rebase(&self, position: &WorldPosition) -> XrVector3f {
+        position.relative_to(&self.eye_world)
+    }
+}
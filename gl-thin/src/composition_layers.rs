@@ -0,0 +1,625 @@
+//! Layers the runtime composites together with the projection layer.  The runtime samples
+//! their swapchains directly at display time, so a video or panel shown this way skips
+//! the resampling it would suffer when drawn into the eye buffers.
+
+use crate::errors::{Wrappable, XrErrorWrapped};
+use crate::frame_arena::{ArenaVec, FrameArena};
+use crate::gl_helper::{explode_if_gl_error, FrameBuffer, GLErrorWrapper, Texture};
+use crate::linear::{
+    xr_matrix4x4f_create_translation_rotation_scale, xr_matrix4x4f_invert_rigid_body,
+    xr_matrix4x4f_transform_vector3f, XrVector3f,
+};
+use gl::types::GLsizei;
+use openxr::{
+    CompositionLayerBase, CompositionLayerCubeKHR, CompositionLayerCylinderKHR,
+    CompositionLayerEquirect2KHR, CompositionLayerQuad, Graphics, Instance, OpenGlEs, Posef,
+    Session, Space, Swapchain, SwapchainCreateFlags, SwapchainCreateInfo, SwapchainSubImage,
+    SwapchainUsageFlags,
+};
+pub use openxr_sys::{CompositionLayerFlags, CompositionLayerSecureContentFlagsFB};
+use openxr_sys::{
+    CompositionLayerSecureContentFB, Duration as XrDuration, Extent2Df, Extent2Di, EyeVisibility,
+    Offset2Di, Rect2Di,
+};
+use std::f32::consts::{FRAC_PI_2, TAU};
+use std::ffi::c_void;
+
+/// The surface a layer's image is mapped onto
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LayerShape {
+    /// A flat rectangle centered on the layer's pose, facing +Z.  Part of core OpenXR, so
+    /// every runtime supports it.
+    Quad {
+        /// in meters
+        width: f32,
+        height: f32,
+    },
+    /// XR_KHR_composition_layer_equirect2: an equirectangular image on the inside of a
+    /// sphere around the layer's pose.  The middle of the image faces -Z.
+    Equirect {
+        /// in meters; 0 puts the sphere infinitely far away
+        radius: f32,
+        /// how much of the horizon the image covers, radians
+        central_horizontal_angle: f32,
+        /// how far above and below the horizon the image reaches, radians
+        upper_vertical_angle: f32,
+        lower_vertical_angle: f32,
+    },
+    /// XR_KHR_composition_layer_cylinder: a flat image bent around the inside of a
+    /// vertical cylinder centered on the layer's pose, the middle of the arc facing -Z.
+    Cylinder {
+        /// in meters
+        radius: f32,
+        /// how much of the circle the image covers, radians
+        central_angle: f32,
+        /// width / height of the curved surface, usually the image's
+        aspect_ratio: f32,
+    },
+    /// XR_KHR_composition_layer_cube: a cube map around the viewer, infinitely far away, for
+    /// a sky that does not move.  Only the pose's orientation counts.  Its swapchain has to
+    /// come from [LayerSwapchain::cube].
+    Cube,
+}
+
+impl LayerShape {
+    /// the whole sphere, infinitely far away; what a 360° video needs
+    pub fn full_equirect() -> Self {
+        LayerShape::Equirect {
+            radius: 0.0,
+            central_horizontal_angle: TAU,
+            upper_vertical_angle: FRAC_PI_2,
+            lower_vertical_angle: -FRAC_PI_2,
+        }
+    }
+
+    /// A curved screen `width` meters across (measured along the arc) that bends through
+    /// `central_angle` radians, for an image of `aspect_ratio` width / height.  Viewed from
+    /// the center, a flatter arc is a more distant screen.
+    pub fn cylinder(width: f32, central_angle: f32, aspect_ratio: f32) -> Self {
+        LayerShape::Cylinder {
+            radius: width / central_angle,
+            central_angle,
+            aspect_ratio,
+        }
+    }
+}
+
+/// whether a layer is composited behind the projection layer or in front of it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LayerPlacement {
+    /// Shows through wherever the projection layer's alpha is below 1, so clear the eye
+    /// buffers to transparent where it should be visible.
+    Underlay,
+    Overlay,
+}
+
+//
+
+/// A swapchain holding a layer's image, separate from the eye buffers
+pub struct LayerSwapchain<G: Graphics> {
+    pub swapchain: Swapchain<G>,
+    pub images: Vec<G::SwapchainImage>,
+    pub width: u32,
+    pub height: u32,
+    /// 6 for a cube map, 1 otherwise
+    faces: u32,
+    /// the runtime rejects layers whose swapchain was never released
+    has_image: bool,
+}
+
+impl<G: Graphics> LayerSwapchain<G> {
+    pub fn new(
+        session: &Session<G>,
+        format: G::Format,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, XrErrorWrapped> {
+        Self::with_usage(
+            session,
+            format,
+            SwapchainUsageFlags::SAMPLED | SwapchainUsageFlags::COLOR_ATTACHMENT,
+            width,
+            height,
+        )
+    }
+
+    /// e.g. DEPTH_STENCIL_ATTACHMENT for a depth image
+    pub fn with_usage(
+        session: &Session<G>,
+        format: G::Format,
+        usage_flags: SwapchainUsageFlags,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, XrErrorWrapped> {
+        Self::create(session, format, usage_flags, width, height, 1)
+    }
+
+    /// six `size` x `size` faces for a [LayerShape::Cube]
+    pub fn cube(
+        session: &Session<G>,
+        format: G::Format,
+        size: u32,
+    ) -> Result<Self, XrErrorWrapped> {
+        Self::create(
+            session,
+            format,
+            SwapchainUsageFlags::SAMPLED | SwapchainUsageFlags::COLOR_ATTACHMENT,
+            size,
+            size,
+            6,
+        )
+    }
+
+    fn create(
+        session: &Session<G>,
+        format: G::Format,
+        usage_flags: SwapchainUsageFlags,
+        width: u32,
+        height: u32,
+        faces: u32,
+    ) -> Result<Self, XrErrorWrapped> {
+        let swapchain = session
+            .create_swapchain(&SwapchainCreateInfo::<G> {
+                create_flags: SwapchainCreateFlags::EMPTY,
+                usage_flags,
+                format,
+                sample_count: 1,
+                width,
+                height,
+                face_count: faces,
+                array_size: 1,
+                mip_count: 1,
+            })
+            .annotate_if_err(None, "failed to create layer swapchain")?;
+        let images = swapchain
+            .enumerate_images()
+            .annotate_if_err(None, "failed to enumerate layer swapchain images")?;
+        Ok(Self {
+            swapchain,
+            images,
+            width,
+            height,
+            faces,
+            has_image: false,
+        })
+    }
+
+    /// Acquire the next image, let `paint` fill it, and release it to be composited.
+    pub fn paint<T>(
+        &mut self,
+        paint: impl FnOnce(&G::SwapchainImage) -> T,
+    ) -> Result<T, XrErrorWrapped> {
+        let index = self
+            .swapchain
+            .acquire_image()
+            .annotate_if_err(None, "failed to acquire layer swapchain image")?;
+        self.swapchain
+            .wait_image(XrDuration::INFINITE)
+            .annotate_if_err(None, "failed to wait for layer swapchain image")?;
+
+        let rval = paint(&self.images[index as usize]);
+
+        self.swapchain
+            .release_image()
+            .annotate_if_err(None, "failed to release layer swapchain image")?;
+        self.has_image = true;
+        Ok(rval)
+    }
+
+    pub fn has_image(&self) -> bool {
+        self.has_image
+    }
+
+    pub fn is_cube(&self) -> bool {
+        self.faces == 6
+    }
+
+    pub(crate) fn sub_image(&self) -> SwapchainSubImage<G> {
+        SwapchainSubImage::new()
+            .swapchain(&self.swapchain)
+            .image_rect(Rect2Di {
+                offset: Offset2Di { x: 0, y: 0 },
+                extent: Extent2Di {
+                    width: self.width as i32,
+                    height: self.height as i32,
+                },
+            })
+            .image_array_index(0)
+    }
+}
+
+impl LayerSwapchain<OpenGlEs> {
+    /// [LayerSwapchain::paint] with the image attached to `frame_buffer`, which is left
+    /// bound with the viewport covering the image.
+    pub fn paint_gl<T>(
+        &mut self,
+        frame_buffer: &FrameBuffer,
+        paint: impl FnOnce() -> Result<T, GLErrorWrapper>,
+    ) -> Result<Result<T, GLErrorWrapper>, XrErrorWrapped> {
+        let (width, height) = (self.width, self.height);
+        self.paint(|&image| {
+            frame_buffer.bind()?;
+            Texture::borrowed(image).attach(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                0,
+            )?;
+            unsafe { gl::Viewport(0, 0, width as GLsizei, height as GLsizei) };
+            explode_if_gl_error()?;
+            paint()
+        })
+    }
+
+    /// [LayerSwapchain::paint_gl] for a cube swapchain: `paint` is called once per face, in
+    /// the GL order (+X, -X, +Y, -Y, +Z, -Z), with that face attached to `frame_buffer`.
+    pub fn paint_cube_gl(
+        &mut self,
+        frame_buffer: &FrameBuffer,
+        mut paint: impl FnMut(u32) -> Result<(), GLErrorWrapper>,
+    ) -> Result<Result<(), GLErrorWrapper>, XrErrorWrapped> {
+        let (size, faces) = (self.width, self.faces);
+        self.paint(|&image| {
+            frame_buffer.bind()?;
+            unsafe { gl::Viewport(0, 0, size as GLsizei, size as GLsizei) };
+            for face in 0..faces {
+                Texture::borrowed(image).attach(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                    0,
+                )?;
+                explode_if_gl_error()?;
+                paint(face)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+//
+
+pub struct CompositionLayer<G: Graphics> {
+    pub shape: LayerShape,
+    /// in the component's reference space
+    pub pose: Posef,
+    pub placement: LayerPlacement,
+    /// How the compositor blends the layer with the ones below it.  With
+    /// [CompositionLayerFlags::BLEND_TEXTURE_SOURCE_ALPHA] the image's alpha is honored
+    /// instead of treating it as opaque, and the colors are taken as premultiplied by alpha
+    /// (see [crate::gl_fancy::premultiply_alpha]) unless
+    /// [CompositionLayerFlags::UNPREMULTIPLIED_ALPHA] is set too.
+    pub flags: CompositionLayerFlags,
+    /// XR_FB_composition_layer_secure_content: keep the layer out of screenshots, recordings
+    /// and casts, by leaving it out ([CompositionLayerSecureContentFlagsFB::EXCLUDE_LAYER])
+    /// or showing black instead ([CompositionLayerSecureContentFlagsFB::REPLACE_LAYER]).
+    /// The headset still shows it.  `None` lets it be captured like everything else.
+    pub secure_content: Option<CompositionLayerSecureContentFlagsFB>,
+    /// hidden layers are not submitted
+    pub visible: bool,
+    pub swapchain: LayerSwapchain<G>,
+}
+
+impl<G: Graphics> CompositionLayer<G> {
+    /// An opaque, visible overlay at the origin of the component's space; adjust it with
+    /// the `with_` methods before [LayerStack::add]ing it.
+    pub fn new(shape: LayerShape, swapchain: LayerSwapchain<G>) -> Self {
+        let mut pose = Posef::default();
+        pose.orientation.w = 1.0;
+        Self {
+            shape,
+            pose,
+            placement: LayerPlacement::Overlay,
+            flags: CompositionLayerFlags::EMPTY,
+            secure_content: None,
+            visible: true,
+            swapchain,
+        }
+    }
+
+    pub fn with_pose(mut self, pose: Posef) -> Self {
+        self.pose = pose;
+        self
+    }
+
+    pub fn with_placement(mut self, placement: LayerPlacement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    pub fn with_flags(mut self, flags: CompositionLayerFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// see [CompositionLayer::secure_content]
+    pub fn with_secure_content(mut self, flags: CompositionLayerSecureContentFlagsFB) -> Self {
+        self.secure_content = Some(flags);
+        self
+    }
+
+    /// blend with the layers below by the image's premultiplied alpha
+    pub fn translucent(self) -> Self {
+        self.with_flags(CompositionLayerFlags::BLEND_TEXTURE_SOURCE_ALPHA)
+    }
+
+    /// Where a pointer ray (in the layer's space) meets the layer, as a position in its
+    /// image: 0..1 from left to right and from bottom to top.  Equirect layers surround
+    /// the viewer and can not be pointed at.
+    pub fn hit(&self, origin: &XrVector3f, direction: &XrVector3f) -> Option<[f32; 2]> {
+        let model = xr_matrix4x4f_create_translation_rotation_scale(
+            &self.pose.position.into(),
+            &self.pose.orientation.into(),
+            &XrVector3f::default_scale(),
+        );
+        let inverse = xr_matrix4x4f_invert_rigid_body(&model);
+        let o = xr_matrix4x4f_transform_vector3f(&inverse, origin);
+        let tip = xr_matrix4x4f_transform_vector3f(&inverse, &(*origin + *direction));
+        let d = tip - o;
+
+        let (radius, central_angle, aspect_ratio) = match self.shape {
+            LayerShape::Quad { width, height } => {
+                // where the ray crosses the plane z = 0, from the front
+                if d.z >= -1e-9 {
+                    return None;
+                }
+                let t = -o.z / d.z;
+                if t < 0.0 {
+                    return None;
+                }
+                let u = 0.5 + (o.x + d.x * t) / width;
+                let v = 0.5 + (o.y + d.y * t) / height;
+                return ((0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v)).then_some([u, v]);
+            }
+            LayerShape::Cylinder {
+                radius,
+                central_angle,
+                aspect_ratio,
+            } => (radius, central_angle, aspect_ratio),
+            LayerShape::Equirect { .. } | LayerShape::Cube => return None,
+        };
+
+        // where the ray crosses the infinite cylinder x² + z² = radius²
+        let a = d.x * d.x + d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.z * d.z);
+        let c = o.x * o.x + o.z * o.z - radius * radius;
+        let discriminant = b * b - 4.0 * a * c;
+        if a < 1e-9 || discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        let height = radius * central_angle / aspect_ratio;
+        [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+            .into_iter()
+            .filter(|&t| t >= 0.0)
+            .find_map(|t| {
+                let p = o + XrVector3f::new(d.x * t, d.y * t, d.z * t);
+                let angle = p.x.atan2(-p.z);
+                let u = 0.5 + angle / central_angle;
+                let v = 0.5 + p.y / height;
+                ((0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v)).then_some([u, v])
+            })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LayerId(u64);
+
+/// The layers submitted every frame besides the projection layer, see
+/// [crate::openxr_helpers::OpenXRComponent::layers]
+pub struct LayerStack<G: Graphics> {
+    equirect2: bool,
+    cylinder: bool,
+    cube: bool,
+    secure_content: bool,
+    layers: Vec<(LayerId, CompositionLayer<G>)>,
+    next_id: u64,
+}
+
+impl<G: Graphics> LayerStack<G> {
+    /// supports whichever layer extensions the instance was created with
+    pub fn new(instance: &Instance) -> Self {
+        Self {
+            equirect2: instance.exts().khr_composition_layer_equirect2.is_some(),
+            cylinder: instance.exts().khr_composition_layer_cylinder.is_some(),
+            cube: instance.exts().khr_composition_layer_cube.is_some(),
+            secure_content: instance
+                .exts()
+                .fb_composition_layer_secure_content
+                .is_some(),
+            layers: vec![],
+            next_id: 0,
+        }
+    }
+
+    /// false if the runtime lacks the extension for this kind of layer; show the content
+    /// some other way then
+    pub fn supports(&self, shape: &LayerShape) -> bool {
+        match shape {
+            LayerShape::Quad { .. } => true,
+            LayerShape::Equirect { .. } => self.equirect2,
+            LayerShape::Cylinder { .. } => self.cylinder,
+            LayerShape::Cube => self.cube,
+        }
+    }
+
+    /// false if the runtime lacks XR_FB_composition_layer_secure_content, so
+    /// [CompositionLayer::secure_content] can not be honored
+    pub fn supports_secure_content(&self) -> bool {
+        self.secure_content
+    }
+
+    pub fn add(&mut self, layer: CompositionLayer<G>) -> Result<LayerId, XrErrorWrapped> {
+        if !self.supports(&layer.shape) {
+            return Err(XrErrorWrapped::simple(format!(
+                "the runtime does not support {:?} layers",
+                layer.shape
+            )));
+        }
+        if (layer.shape == LayerShape::Cube) != layer.swapchain.is_cube() {
+            return Err(XrErrorWrapped::simple(
+                "cube layers need a cube swapchain, and only they can have one",
+            ));
+        }
+        if layer.secure_content.is_some() && !self.secure_content {
+            return Err(XrErrorWrapped::simple(
+                "the runtime does not support secure content layers",
+            ));
+        }
+        let id = LayerId(self.next_id);
+        self.next_id += 1;
+        self.layers.push((id, layer));
+        Ok(id)
+    }
+
+    pub fn get(&self, id: LayerId) -> Option<&CompositionLayer<G>> {
+        self.layers
+            .iter()
+            .find(|(i, _)| *i == id)
+            .map(|(_, layer)| layer)
+    }
+
+    pub fn get_mut(&mut self, id: LayerId) -> Option<&mut CompositionLayer<G>> {
+        self.layers
+            .iter_mut()
+            .find(|(i, _)| *i == id)
+            .map(|(_, layer)| layer)
+    }
+
+    pub fn remove(&mut self, id: LayerId) -> Option<CompositionLayer<G>> {
+        let index = self.layers.iter().position(|(i, _)| *i == id)?;
+        Some(self.layers.remove(index).1)
+    }
+
+    /// the layers to submit this frame, in order, split into those behind and in front of
+    /// the projection layer
+    pub(crate) fn build<'a>(
+        &'a self,
+        space: &'a Space,
+        arena: &'a FrameArena,
+    ) -> (
+        ArenaVec<'a, BuiltLayer<'a, G>>,
+        ArenaVec<'a, BuiltLayer<'a, G>>,
+    ) {
+        let mut underlays = ArenaVec::new_in(arena);
+        let mut overlays = ArenaVec::new_in(arena);
+        for (_, layer) in &self.layers {
+            if !layer.visible || !layer.swapchain.has_image() {
+                continue;
+            }
+            let built = match layer.shape {
+                LayerShape::Quad { width, height } => BuiltLayer::Quad(
+                    CompositionLayerQuad::new()
+                        .layer_flags(layer.flags)
+                        .space(space)
+                        .eye_visibility(EyeVisibility::BOTH)
+                        .sub_image(layer.swapchain.sub_image())
+                        .pose(layer.pose)
+                        .size(Extent2Df { width, height }),
+                ),
+                LayerShape::Equirect {
+                    radius,
+                    central_horizontal_angle,
+                    upper_vertical_angle,
+                    lower_vertical_angle,
+                } => BuiltLayer::Equirect(
+                    CompositionLayerEquirect2KHR::new()
+                        .layer_flags(layer.flags)
+                        .space(space)
+                        .eye_visibility(EyeVisibility::BOTH)
+                        .sub_image(layer.swapchain.sub_image())
+                        .pose(layer.pose)
+                        .radius(radius)
+                        .central_horizontal_angle(central_horizontal_angle)
+                        .upper_vertical_angle(upper_vertical_angle)
+                        .lower_vertical_angle(lower_vertical_angle),
+                ),
+                LayerShape::Cylinder {
+                    radius,
+                    central_angle,
+                    aspect_ratio,
+                } => BuiltLayer::Cylinder(
+                    CompositionLayerCylinderKHR::new()
+                        .layer_flags(layer.flags)
+                        .space(space)
+                        .eye_visibility(EyeVisibility::BOTH)
+                        .sub_image(layer.swapchain.sub_image())
+                        .pose(layer.pose)
+                        .radius(radius)
+                        .central_angle(central_angle)
+                        .aspect_ratio(aspect_ratio),
+                ),
+                LayerShape::Cube => BuiltLayer::Cube(
+                    CompositionLayerCubeKHR::new()
+                        .layer_flags(layer.flags)
+                        .space(space)
+                        .eye_visibility(EyeVisibility::BOTH)
+                        .swapchain(&layer.swapchain.swapchain)
+                        .image_array_index(0)
+                        .orientation(layer.pose.orientation),
+                ),
+            };
+            let built = match layer.secure_content {
+                Some(flags) => {
+                    let info = arena.alloc(CompositionLayerSecureContentFB {
+                        ty: CompositionLayerSecureContentFB::TYPE,
+                        next: std::ptr::null(),
+                        flags,
+                    });
+                    built.chained(info as *const CompositionLayerSecureContentFB as *const c_void)
+                }
+                None => built,
+            };
+            match layer.placement {
+                LayerPlacement::Underlay => underlays.push(built),
+                LayerPlacement::Overlay => overlays.push(built),
+            }
+        }
+        (underlays, overlays)
+    }
+}
+
+/// the OpenXR structure for one layer, borrowing its swapchain
+pub(crate) enum BuiltLayer<'a, G: Graphics> {
+    Quad(CompositionLayerQuad<'a, G>),
+    Equirect(CompositionLayerEquirect2KHR<'a, G>),
+    Cylinder(CompositionLayerCylinderKHR<'a, G>),
+    Cube(CompositionLayerCubeKHR<'a, G>),
+}
+
+impl<'a, G: Graphics> BuiltLayer<'a, G> {
+    pub(crate) fn base(&self) -> &CompositionLayerBase<'a, G> {
+        match self {
+            BuiltLayer::Quad(layer) => layer,
+            BuiltLayer::Equirect(layer) => layer,
+            BuiltLayer::Cylinder(layer) => layer,
+            BuiltLayer::Cube(layer) => layer,
+        }
+    }
+
+    /// the same layer with `next` (which must outlive it) chained onto its structure
+    fn chained(self, next: *const c_void) -> Self {
+        match self {
+            BuiltLayer::Quad(layer) => {
+                let mut raw = layer.into_raw();
+                raw.next = next;
+                BuiltLayer::Quad(unsafe { CompositionLayerQuad::from_raw(raw) })
+            }
+            BuiltLayer::Equirect(layer) => {
+                let mut raw = layer.into_raw();
+                raw.next = next;
+                BuiltLayer::Equirect(unsafe { CompositionLayerEquirect2KHR::from_raw(raw) })
+            }
+            BuiltLayer::Cylinder(layer) => {
+                let mut raw = layer.into_raw();
+                raw.next = next;
+                BuiltLayer::Cylinder(unsafe { CompositionLayerCylinderKHR::from_raw(raw) })
+            }
+            BuiltLayer::Cube(layer) => {
+                let mut raw = layer.into_raw();
+                raw.next = next;
+                BuiltLayer::Cube(unsafe { CompositionLayerCubeKHR::from_raw(raw) })
+            }
+        }
+    }
+}
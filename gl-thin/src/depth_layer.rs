@@ -0,0 +1,146 @@
+//! XR_KHR_composition_layer_depth: along with each eye image, hand the runtime the depth
+//! buffer it was drawn with.  Knowing how far away each pixel is lets the runtime reproject
+//! the image properly when the head moves between rendering and display, instead of
+//! treating everything as infinitely far away.
+
+use crate::composition_layers::LayerSwapchain;
+use crate::errors::XrErrorWrapped;
+use crate::frame_arena::{ArenaVec, FrameArena};
+use openxr::{
+    CompositionLayerProjectionView, Graphics, Instance, Session, SwapchainUsageFlags,
+    ViewConfigurationView,
+};
+use openxr_sys::CompositionLayerDepthInfoKHR;
+use std::ffi::c_void;
+use std::ptr::null;
+
+/// see [crate::openxr_helpers::OpenXRComponent::depth_layer]
+pub struct DepthLayer<G: Graphics> {
+    /// Submit the depth images with each frame.  While false no images are acquired and
+    /// the eye images go to the runtime without depth.
+    pub enabled: bool,
+    /// the near and far planes the depth images are drawn with
+    pub near_z: f32,
+    pub far_z: f32,
+    format: G::Format,
+    /// one per eye, the size of the eye swapchains
+    eyes: Vec<LayerSwapchain<G>>,
+}
+
+impl<G: Graphics> DepthLayer<G> {
+    /// whether the instance was created with XR_KHR_composition_layer_depth
+    pub fn is_supported(instance: &Instance) -> bool {
+        instance.exts().khr_composition_layer_depth.is_some()
+    }
+
+    /// One depth swapchain for each of `views`, at the size of its eye swapchain.
+    pub fn new(
+        instance: &Instance,
+        session: &Session<G>,
+        views: &[ViewConfigurationView],
+        format: G::Format,
+    ) -> Result<Self, XrErrorWrapped> {
+        if !Self::is_supported(instance) {
+            return Err(XrErrorWrapped::simple(
+                "the runtime has no XR_KHR_composition_layer_depth",
+            ));
+        }
+        Ok(Self {
+            enabled: true,
+            near_z: 0.01,
+            far_z: 10_000.0,
+            format,
+            eyes: create_depth_swapchains(session, views, format)?,
+        })
+    }
+
+    /// Make the swapchains again to match `views`, after the eye swapchains were.  If this
+    /// fails there are no depth images until a later call succeeds.
+    pub(crate) fn recreate(
+        &mut self,
+        session: &Session<G>,
+        views: &[ViewConfigurationView],
+    ) -> Result<(), XrErrorWrapped> {
+        // before making the new ones; a runtime may only have memory for one set
+        self.eyes.clear();
+        self.eyes = create_depth_swapchains(session, views, self.format)?;
+        Ok(())
+    }
+
+    /// Acquire `eye`'s depth image, let `paint` draw into it along with the eye image, and
+    /// release it.  `paint` gets `None` while disabled.
+    pub(crate) fn paint_eye<T>(
+        &mut self,
+        eye: usize,
+        paint: impl FnOnce(Option<&G::SwapchainImage>) -> T,
+    ) -> Result<T, XrErrorWrapped> {
+        if !self.enabled {
+            return Ok(paint(None));
+        }
+        match self.eyes.get_mut(eye) {
+            Some(depth) => depth.paint(|image| paint(Some(image))),
+            None => Ok(paint(None)),
+        }
+    }
+
+    /// One `XrCompositionLayerDepthInfoKHR` per eye, to chain onto the projection views;
+    /// empty unless every eye's image holds something.  `next(eye)` is chained on after
+    /// each, e.g. that eye's space warp info, or null.
+    pub(crate) fn layer_infos<'a>(
+        &self,
+        arena: &'a FrameArena,
+        next: impl Fn(usize) -> *const c_void,
+    ) -> ArenaVec<'a, CompositionLayerDepthInfoKHR> {
+        let mut infos = ArenaVec::with_capacity_in(self.eyes.len(), arena);
+        let ready = self.eyes.iter().all(LayerSwapchain::has_image);
+        if !self.enabled || !ready {
+            return infos;
+        }
+        infos.extend(self.eyes.iter().enumerate().map(|(eye, depth)| {
+            CompositionLayerDepthInfoKHR {
+                ty: CompositionLayerDepthInfoKHR::TYPE,
+                next: next(eye),
+                sub_image: depth.sub_image().into_raw(),
+                min_depth: 0.0,
+                max_depth: 1.0,
+                near_z: self.near_z,
+                far_z: self.far_z,
+            }
+        }));
+        infos
+    }
+}
+
+fn create_depth_swapchains<G: Graphics>(
+    session: &Session<G>,
+    views: &[ViewConfigurationView],
+    format: G::Format,
+) -> Result<Vec<LayerSwapchain<G>>, XrErrorWrapped> {
+    views
+        .iter()
+        .map(|view| {
+            LayerSwapchain::with_usage(
+                session,
+                format,
+                SwapchainUsageFlags::SAMPLED | SwapchainUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                view.recommended_image_rect_width,
+                view.recommended_image_rect_height,
+            )
+        })
+        .collect()
+}
+
+/// `view` with `info` chained on, which must outlive the frame's submission
+pub(crate) fn chain_depth<'a, G: Graphics>(
+    view: CompositionLayerProjectionView<'a, G>,
+    info: &'a CompositionLayerDepthInfoKHR,
+) -> CompositionLayerProjectionView<'a, G> {
+    let mut raw = view.into_raw();
+    raw.next = info as *const CompositionLayerDepthInfoKHR as *const c_void;
+    unsafe { CompositionLayerProjectionView::from_raw(raw) }
+}
+
+/// null where there is nothing to chain on
+pub(crate) fn chain_next<T>(info: Option<&T>) -> *const c_void {
+    info.map_or(null(), |info| info as *const T as *const c_void)
+}
@@ -0,0 +1,270 @@
+//! Geometry that changes after it is uploaded, for sculpting and drawing.  The CPU copy of
+//! the vertices and indices stays next to the [VertexBufferLite], edits mark what they
+//! touched, and [EditableMesh::upload] sends only that part to the GPU.
+
+use crate::gl_fancy::{GPUState, VertexBufferLite};
+use crate::gl_helper::{Buffer, BufferTarget, GLErrorWrapper};
+use crate::static_batch::{cross, normalize, VertexLayout};
+use gl::types::{GLfloat, GLuint};
+use std::ops::Range;
+use std::rc::Rc;
+
+/// the span of elements changed since the last upload
+#[derive(Clone, Debug, Default)]
+struct DirtyRange(Option<Range<usize>>);
+
+impl DirtyRange {
+    fn mark(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        self.0 = Some(match self.0.take() {
+            Some(old) => old.start.min(range.start)..old.end.max(range.end),
+            None => range,
+        });
+    }
+
+    fn take(&mut self) -> Option<Range<usize>> {
+        self.0.take()
+    }
+}
+
+/// A mesh with interleaved float vertices (see [VertexLayout]) and 32-bit indices that can
+/// be edited in place.  Rig [EditableMesh::buffers] for a shader with
+/// [crate::gl_fancy::VertexBufferBundle::from_buffers] once; the bundle shares the GPU
+/// buffers, so it sees every upload.  Draw [EditableMesh::index_count] indices, since the
+/// bundle's own count is a snapshot.
+pub struct EditableMesh {
+    pub layout: VertexLayout,
+    pub buffers: VertexBufferLite<'static, GLfloat, GLuint>,
+    vertices: Vec<GLfloat>,
+    indices: Vec<GLuint>,
+    /// in floats
+    dirty_vertices: DirtyRange,
+    dirty_indices: DirtyRange,
+    /// how many floats and indices the GPU buffers have room for
+    vertex_capacity: usize,
+    index_capacity: usize,
+}
+
+impl EditableMesh {
+    pub fn new(
+        gpu_state: &mut GPUState,
+        layout: VertexLayout,
+        vertices: Vec<GLfloat>,
+        indices: Vec<GLuint>,
+    ) -> Result<Self, GLErrorWrapper> {
+        if !vertices.chunks_exact(layout.stride).remainder().is_empty() {
+            return Err(GLErrorWrapper::with_message2(format!(
+                "{} floats is not a whole number of {}-float vertices",
+                vertices.len(),
+                layout.stride
+            )));
+        }
+        let vertex_count = vertices.len() / layout.stride;
+        check_indices(&indices, vertex_count)?;
+
+        let mut rval = Self {
+            layout,
+            buffers: VertexBufferLite {
+                vertex_buffer: Rc::new(Buffer::new()?),
+                index_buffer: Rc::new(Buffer::new()?),
+                index_count: 0,
+            },
+            vertices,
+            indices,
+            dirty_vertices: DirtyRange::default(),
+            dirty_indices: DirtyRange::default(),
+            vertex_capacity: 0,
+            index_capacity: 0,
+        };
+        rval.upload(gpu_state)?;
+        Ok(rval)
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len() / self.layout.stride
+    }
+
+    pub fn index_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// every vertex, `layout.stride` floats each
+    pub fn vertices(&self) -> &[GLfloat] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[GLuint] {
+        &self.indices
+    }
+
+    pub fn vertex(&self, index: usize) -> &[GLfloat] {
+        let start = index * self.layout.stride;
+        &self.vertices[start..start + self.layout.stride]
+    }
+
+    /// all of vertex `index`'s floats, marked for upload
+    pub fn vertex_mut(&mut self, index: usize) -> &mut [GLfloat] {
+        let start = index * self.layout.stride;
+        let range = start..start + self.layout.stride;
+        self.dirty_vertices.mark(range.clone());
+        &mut self.vertices[range]
+    }
+
+    pub fn position(&self, index: usize) -> [f32; 3] {
+        let p = &self.vertex(index)[self.layout.position_offset..];
+        [p[0], p[1], p[2]]
+    }
+
+    pub fn set_position(&mut self, index: usize, position: [f32; 3]) {
+        let offset = self.layout.position_offset;
+        self.vertex_mut(index)[offset..offset + 3].copy_from_slice(&position);
+    }
+
+    /// Move some vertices by `delta`, e.g. the ones under a sculpting brush.
+    /// Normals are left alone; follow up with [EditableMesh::recompute_normals].
+    pub fn move_vertices(&mut self, vertices: impl IntoIterator<Item = usize>, delta: [f32; 3]) {
+        for index in vertices {
+            let [x, y, z] = self.position(index);
+            self.set_position(index, [x + delta[0], y + delta[1], z + delta[2]]);
+        }
+    }
+
+    /// Append vertices (`layout.stride` floats each) and return the index of the first one.
+    pub fn push_vertices(&mut self, vertices: &[GLfloat]) -> Result<GLuint, GLErrorWrapper> {
+        if !vertices
+            .chunks_exact(self.layout.stride)
+            .remainder()
+            .is_empty()
+        {
+            return Err(GLErrorWrapper::with_message2(format!(
+                "{} floats is not a whole number of {}-float vertices",
+                vertices.len(),
+                self.layout.stride
+            )));
+        }
+        let first = self.vertex_count() as GLuint;
+        let start = self.vertices.len();
+        self.vertices.extend_from_slice(vertices);
+        self.dirty_vertices.mark(start..self.vertices.len());
+        Ok(first)
+    }
+
+    /// Append indices, which must refer to vertices that already exist.
+    pub fn push_indices(&mut self, indices: &[GLuint]) -> Result<(), GLErrorWrapper> {
+        check_indices(indices, self.vertex_count())?;
+        let start = self.indices.len();
+        self.indices.extend_from_slice(indices);
+        self.dirty_indices.mark(start..self.indices.len());
+        Ok(())
+    }
+
+    /// Drop every vertex and index, e.g. to start a new stroke.
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+        self.dirty_vertices.take();
+        self.dirty_indices.take();
+    }
+
+    /// Rebuild the normals of vertices `range` from the triangles that use them, weighted by
+    /// triangle area.  Does nothing if the layout has no normal.
+    pub fn recompute_normals(&mut self, range: Range<usize>) {
+        let Some(normal_offset) = self.layout.normal_offset else {
+            return;
+        };
+        let range = range.start.min(self.vertex_count())..range.end.min(self.vertex_count());
+        if range.is_empty() {
+            return;
+        }
+
+        let mut sums = vec![[0.0f32; 3]; range.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            if !triangle.iter().any(|i| range.contains(&(*i as usize))) {
+                continue;
+            }
+            let [a, b, c] = [0, 1, 2].map(|k| self.position(triangle[k] as usize));
+            let ab = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+            let ac = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+            // twice the area, pointing out of the counter-clockwise face
+            let face = cross(&ab, &ac);
+            for &i in triangle {
+                if let Some(sum) = (i as usize)
+                    .checked_sub(range.start)
+                    .and_then(|k| sums.get_mut(k))
+                {
+                    *sum = [sum[0] + face[0], sum[1] + face[1], sum[2] + face[2]];
+                }
+            }
+        }
+
+        for (k, sum) in sums.into_iter().enumerate() {
+            let normal = normalize(sum);
+            self.vertex_mut(range.start + k)[normal_offset..normal_offset + 3]
+                .copy_from_slice(&normal);
+        }
+    }
+
+    /// [EditableMesh::recompute_normals] for the whole mesh
+    pub fn recompute_all_normals(&mut self) {
+        self.recompute_normals(0..self.vertex_count());
+    }
+
+    /// Send the edits since the last upload to the GPU.  Only the changed span is copied,
+    /// unless the mesh outgrew its buffers, which are then reallocated at twice the size.
+    /// `gpu_state` is only borrowed to be sure no vertex array is bound, since binding the
+    /// index buffer would change it.
+    pub fn upload(&mut self, _gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper> {
+        upload_range(
+            &self.buffers.vertex_buffer,
+            &self.vertices,
+            &mut self.vertex_capacity,
+            self.dirty_vertices.take(),
+        )?;
+        upload_range(
+            &self.buffers.index_buffer,
+            &self.indices,
+            &mut self.index_capacity,
+            self.dirty_indices.take(),
+        )?;
+        self.buffers.index_count = self.indices.len();
+        Ok(())
+    }
+
+    /// true if there are edits that [EditableMesh::upload] has not sent yet
+    pub fn is_dirty(&self) -> bool {
+        self.dirty_vertices.0.is_some() || self.dirty_indices.0.is_some()
+    }
+}
+
+fn check_indices(indices: &[GLuint], vertex_count: usize) -> Result<(), GLErrorWrapper> {
+    match indices.iter().find(|i| **i as usize >= vertex_count) {
+        Some(index) => Err(GLErrorWrapper::with_message2(format!(
+            "index {} is out of range for {} vertices",
+            index, vertex_count
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn upload_range<B: BufferTarget, T>(
+    buffer: &Buffer<'static, B, T>,
+    data: &[T],
+    capacity: &mut usize,
+    dirty: Option<Range<usize>>,
+) -> Result<(), GLErrorWrapper> {
+    if data.len() > *capacity || *capacity == 0 {
+        *capacity = (2 * data.len()).max(64);
+        buffer.allocate_dynamic(*capacity)?;
+        buffer.update(0, data)
+    } else {
+        match dirty {
+            Some(range) => {
+                let range = range.start.min(data.len())..range.end.min(data.len());
+                buffer.update(range.start, &data[range])
+            }
+            None => Ok(()),
+        }
+    }
+}
@@ -3,6 +3,10 @@ use std::fmt::{Debug, Display, Formatter};
 pub struct XrErrorWrapped {
     pub xr_err: Option<String>,
     pub detail: String,
+    /// set when OpenXR could not start at all, see [XrErrorWrapped::runtime_unavailable]
+    pub unavailable: Option<RuntimeUnavailable>,
+    /// the session or the whole instance is gone, see [XrErrorWrapped::runtime_lost]
+    pub lost: bool,
 }
 
 impl XrErrorWrapped {
@@ -10,15 +14,37 @@ impl XrErrorWrapped {
         Self {
             xr_err: Some(xr_err),
             detail: detail.into(),
+            unavailable: None,
+            lost: false,
         }
     }
     pub fn simple(detail: impl Into<String>) -> Self {
         Self {
             xr_err: None,
             detail: detail.into(),
+            unavailable: None,
+            lost: false,
         }
     }
 
+    pub fn with_unavailable(mut self, unavailable: RuntimeUnavailable) -> Self {
+        self.unavailable = Some(unavailable);
+        self
+    }
+
+    /// Why there is no OpenXR here, if that is what went wrong, as opposed to a failure
+    /// after the runtime came up.
+    pub fn runtime_unavailable(&self) -> Option<&RuntimeUnavailable> {
+        self.unavailable.as_ref()
+    }
+
+    /// The runtime went away under a running app (ERROR_SESSION_LOST or
+    /// ERROR_INSTANCE_LOST), e.g. it crashed or was updated.  Nothing made with the old
+    /// instance works again; tear it all down and create a new one.
+    pub fn runtime_lost(&self) -> bool {
+        self.lost
+    }
+
     #[cfg(feature = "openxr")]
     pub fn build(
         e: openxr_sys::Result,
@@ -29,7 +55,12 @@ impl XrErrorWrapped {
             Some(instance) => crate::openxr_helpers::message_for_error(&instance.as_raw(), e),
             None => format!("OpenXR failed {:?}", e),
         };
-        XrErrorWrapped::new(x, msg.into())
+        let mut wrapped = XrErrorWrapped::new(x, msg.into());
+        wrapped.lost = matches!(
+            e,
+            openxr_sys::Result::ERROR_SESSION_LOST | openxr_sys::Result::ERROR_INSTANCE_LOST
+        );
+        wrapped
     }
 }
 
@@ -53,6 +84,37 @@ impl std::error::Error for XrErrorWrapped {}
 
 //
 
+/// Why OpenXR did not start, for apps that want to tell the user what to do about it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RuntimeUnavailable {
+    /// the OpenXR loader itself could not be set up
+    Loader,
+    /// no runtime is installed, or none is set as the active one
+    NoRuntime,
+    /// a runtime answered, but has no headset ready
+    NoHeadset { runtime: String },
+}
+
+impl RuntimeUnavailable {
+    /// something to put in front of the user, rather than in the log
+    pub fn user_message(&self) -> String {
+        match self {
+            RuntimeUnavailable::Loader => "OpenXR could not be loaded on this device.".into(),
+            RuntimeUnavailable::NoRuntime => concat!(
+                "No OpenXR runtime is available.  Install one, or enable it as the ",
+                "active runtime, and restart the app."
+            )
+            .into(),
+            RuntimeUnavailable::NoHeadset { runtime } => format!(
+                "{} is running, but no headset is connected or ready.",
+                runtime
+            ),
+        }
+    }
+}
+
+//
+
 #[cfg(feature = "openxr")]
 /// This only exists so I can chain a call onto a Result to convert it
 pub trait Wrappable<T> {
@@ -0,0 +1,130 @@
+use crate::gl_helper::{explode_if_gl_error, GLErrorWrapper, Texture};
+use gl::types::GLenum;
+use std::ffi::{c_void, CString};
+use std::ptr::null_mut;
+
+/// the gl crate is generated from desktop GL, which does not have this GLES constant
+pub const TEXTURE_EXTERNAL_OES: GLenum = 0x8D65;
+
+const EGL_NATIVE_BUFFER_ANDROID: u32 = 0x3140;
+const EGL_IMAGE_PRESERVED_KHR: i32 = 0x30D2;
+const EGL_TRUE: i32 = 1;
+const EGL_NONE: i32 = 0x3038;
+
+type GetNativeClientBufferFn = unsafe extern "C" fn(buffer: *const c_void) -> *mut c_void;
+type CreateImageFn = unsafe extern "C" fn(
+    display: *mut c_void,
+    context: *mut c_void,
+    target: u32,
+    buffer: *mut c_void,
+    attrib_list: *const i32,
+) -> *mut c_void;
+type DestroyImageFn = unsafe extern "C" fn(display: *mut c_void, image: *mut c_void) -> u32;
+type ImageTargetTextureFn = unsafe extern "C" fn(target: GLenum, image: *mut c_void);
+
+/// The EGL and GLES extension functions needed to sample an android AHardwareBuffer
+/// (camera frames, video decoder output) as a `samplerExternalOES` without copying it.
+/// They are not part of the core API, so we have to look them up.
+#[derive(Copy, Clone)]
+pub struct ExternalImageLoader {
+    display: *mut c_void,
+    get_native_client_buffer: GetNativeClientBufferFn,
+    create_image: CreateImageFn,
+    destroy_image: DestroyImageFn,
+    image_target_texture: ImageTargetTextureFn,
+}
+
+impl ExternalImageLoader {
+    /// `display` is the EGLDisplay that owns the current context
+    pub fn new(display: *mut c_void) -> Result<Self, GLErrorWrapper> {
+        unsafe {
+            Ok(Self {
+                display,
+                get_native_client_buffer: std::mem::transmute::<*mut c_void, GetNativeClientBufferFn>(
+                    lookup("eglGetNativeClientBufferANDROID")?,
+                ),
+                create_image: std::mem::transmute::<*mut c_void, CreateImageFn>(lookup(
+                    "eglCreateImageKHR",
+                )?),
+                destroy_image: std::mem::transmute::<*mut c_void, DestroyImageFn>(lookup(
+                    "eglDestroyImageKHR",
+                )?),
+                image_target_texture: std::mem::transmute::<*mut c_void, ImageTargetTextureFn>(
+                    lookup("glEGLImageTargetTexture2DOES")?,
+                ),
+            })
+        }
+    }
+
+    /// Wrap an AHardwareBuffer in an EGLImage.
+    /// # Safety
+    /// `hardware_buffer` must point to a live AHardwareBuffer, and must outlive the returned image.
+    pub unsafe fn import_hardware_buffer(
+        &self,
+        hardware_buffer: *const c_void,
+    ) -> Result<EglImage, GLErrorWrapper> {
+        let client_buffer = (self.get_native_client_buffer)(hardware_buffer);
+        if client_buffer.is_null() {
+            return Err(GLErrorWrapper::with_message2(
+                "eglGetNativeClientBufferANDROID failed".to_string(),
+            ));
+        }
+
+        let attributes = [EGL_IMAGE_PRESERVED_KHR, EGL_TRUE, EGL_NONE];
+        let image = (self.create_image)(
+            self.display,
+            null_mut(), // EGL_NO_CONTEXT
+            EGL_NATIVE_BUFFER_ANDROID,
+            client_buffer,
+            attributes.as_ptr(),
+        );
+        if image.is_null() {
+            return Err(GLErrorWrapper::with_message2(
+                "eglCreateImageKHR failed".to_string(),
+            ));
+        }
+
+        Ok(EglImage {
+            image,
+            display: self.display,
+            destroy_image: self.destroy_image,
+        })
+    }
+
+    /// make `texture` (which must be usable as [TEXTURE_EXTERNAL_OES]) sample from `image`.
+    pub fn attach(&self, texture: &Texture, image: &EglImage) -> Result<(), GLErrorWrapper> {
+        texture.bind(TEXTURE_EXTERNAL_OES)?;
+        unsafe { (self.image_target_texture)(TEXTURE_EXTERNAL_OES, image.image) };
+        explode_if_gl_error()
+    }
+}
+
+fn lookup(name: &str) -> Result<*mut c_void, GLErrorWrapper> {
+    let c_name = CString::new(name).unwrap();
+    let rval = unsafe { egli::ffi::eglGetProcAddress(c_name.as_ptr()) } as *mut c_void;
+    if rval.is_null() {
+        Err(GLErrorWrapper::with_message2(format!(
+            "{} is not available",
+            name
+        )))
+    } else {
+        Ok(rval)
+    }
+}
+
+//
+
+/// An EGLImage, destroyed on drop.
+/// A texture that was [attached](ExternalImageLoader::attach) to it keeps the contents alive,
+/// but will not see new contents until it is attached to another image.
+pub struct EglImage {
+    image: *mut c_void,
+    display: *mut c_void,
+    destroy_image: DestroyImageFn,
+}
+
+impl Drop for EglImage {
+    fn drop(&mut self) {
+        unsafe { (self.destroy_image)(self.display, self.image) };
+    }
+}
@@ -0,0 +1,97 @@
+//! Stand-ins compiled into the library for assets that failed to load: a white pixel for a
+//! texture, and a shader that paints everything magenta.  A missing file or a shader the
+//! driver rejects then shows up as an obvious blotch in the scene instead of an error that
+//! takes the whole scene down with it.
+
+use crate::gl_fancy::GPUState;
+use crate::gl_helper::{GLErrorWrapper, Texture};
+use crate::multiview::active_names;
+use crate::recipes::ImageData;
+use gl::types::{GLint, GLuint};
+use std::ffi::CString;
+
+/// RGBA
+pub const WHITE_PIXEL: [u8; 4] = [255, 255, 255, 255];
+
+/// what [error_fragment_shader] paints
+pub const ERROR_COLOR: [f32; 4] = [1.0, 0.0, 1.0, 1.0];
+
+/// Where [crate::gl_helper::Program::get_attribute_location] points `name`, an attribute
+/// the error program `program` optimized out: a location it does not read, counting down
+/// from GL_MAX_VERTEX_ATTRIBS - 1 (as low as 7 on GLES 2), so arrays enabled there are
+/// harmless.  `missing` holds the names given one so far, so each keeps a slot of its own
+/// and asking again gives the same one.  Only once the free slots run out do they share
+/// the lowest.
+pub(crate) fn unused_attribute(program: GLuint, name: &str, missing: &mut Vec<String>) -> GLuint {
+    let index = match missing.iter().position(|n| n == name) {
+        Some(index) => index,
+        None => {
+            missing.push(name.to_string());
+            missing.len() - 1
+        }
+    };
+    let mut max_attributes = 0;
+    unsafe { gl::GetIntegerv(gl::MAX_VERTEX_ATTRIBS, &mut max_attributes) };
+    let used: Vec<GLint> = active_names(
+        program,
+        gl::ACTIVE_ATTRIBUTES,
+        gl::ACTIVE_ATTRIBUTE_MAX_LENGTH,
+    )
+    .into_iter()
+    .filter_map(|(name, _)| CString::new(name).ok())
+    .map(|name| unsafe { gl::GetAttribLocation(program, name.as_ptr()) })
+    .collect();
+    let free: Vec<GLint> = (0..max_attributes.max(1))
+        .rev()
+        .filter(|location| !used.contains(location))
+        .collect();
+    match free.get(index).or(free.last()) {
+        Some(&location) => location as GLuint,
+        // the program reads every slot, so there is nowhere harmless left
+        None => (max_attributes.max(1) - 1) as GLuint,
+    }
+}
+
+/// 1x1, [WHITE_PIXEL]; multiplies to nothing wherever a texture is modulated
+pub fn white_image() -> ImageData {
+    ImageData {
+        width: 1,
+        height: 1,
+        format: gl::RGBA,
+        pixels: WHITE_PIXEL.to_vec(),
+    }
+}
+
+/// a [white_image] texture
+pub fn white_texture(gpu_state: &mut GPUState) -> Result<Texture, GLErrorWrapper> {
+    let image = white_image();
+    let texture = Texture::new()?;
+    texture.bound(gl::TEXTURE_2D, gpu_state)?.write_pixels(
+        0,
+        image.format as GLint,
+        image.width,
+        image.height,
+        image.format,
+        &image.pixels,
+    )?;
+    Ok(texture)
+}
+
+/// A fragment shader in the same GLSL version as `vertex_shader` that paints [ERROR_COLOR]
+/// and reads nothing, so it links with any vertex shader that compiles.
+pub fn error_fragment_shader(vertex_shader: &str) -> String {
+    let color = ERROR_COLOR.map(|c| format!("{:.1}", c)).join(", ");
+    let (version, output, assign) = if vertex_shader.trim_start().starts_with("#version 300 es") {
+        (
+            "#version 300 es\n",
+            "out vec4 bob_error_color;\n",
+            "bob_error_color",
+        )
+    } else {
+        ("", "", "gl_FragColor")
+    };
+    format!(
+        "{}precision mediump float;\n{}void main() {{ {} = vec4({}); }}\n",
+        version, output, assign, color
+    )
+}
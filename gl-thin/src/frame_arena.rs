@@ -0,0 +1,307 @@
+//! Memory for things that only live until the frame is submitted: draw lists, projection
+//! views, layer lists.  A [FrameArena] hands out pieces of a few big chunks by bumping an
+//! offset, and [FrameArena::reset] takes them all back at once.  After a frame or two it
+//! has one chunk big enough for a whole frame, and stops asking the heap for anything.
+//!
+//! Like other bump allocators it does not run destructors; [ArenaVec] does, for its
+//! elements, when it is dropped.
+
+use std::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use std::cell::{Cell, UnsafeCell};
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of};
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+/// every chunk is aligned this much, and no allocation may need more
+const MAX_ALIGN: usize = 16;
+const MIN_CHUNK: usize = 4096;
+
+struct Chunk {
+    start: NonNull<u8>,
+    size: usize,
+}
+
+impl Chunk {
+    fn new(size: usize) -> Self {
+        let layout = Self::layout(size);
+        let start =
+            NonNull::new(unsafe { alloc(layout) }).unwrap_or_else(|| handle_alloc_error(layout));
+        Self { start, size }
+    }
+
+    fn layout(size: usize) -> Layout {
+        Layout::from_size_align(size, MAX_ALIGN).expect("frame arena chunk too large")
+    }
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.start.as_ptr(), Self::layout(self.size)) }
+    }
+}
+
+pub struct FrameArena {
+    /// only the last one is bumped into; the others hold what was allocated before it
+    /// filled up
+    chunks: UnsafeCell<Vec<Chunk>>,
+    /// bytes used in the last chunk
+    used: Cell<usize>,
+    /// bytes handed out since the last reset, over all chunks
+    allocated: Cell<usize>,
+}
+
+// it owns its chunks, and sharing is ruled out by the Cells
+unsafe impl Send for FrameArena {}
+
+impl Default for FrameArena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameArena {
+    pub fn new() -> Self {
+        Self {
+            chunks: UnsafeCell::new(vec![]),
+            used: Cell::new(0),
+            allocated: Cell::new(0),
+        }
+    }
+
+    /// Take back everything handed out.  If that took more than one chunk, they are
+    /// replaced by one big enough for all of it.
+    pub fn reset(&mut self) {
+        let chunks = self.chunks.get_mut();
+        if chunks.len() > 1 {
+            let total = chunks.iter().map(|chunk| chunk.size).sum();
+            chunks.clear();
+            chunks.push(Chunk::new(total));
+        }
+        self.used.set(0);
+        self.allocated.set(0);
+    }
+
+    /// bytes handed out since the last [FrameArena::reset], not counting alignment padding
+    pub fn allocated(&self) -> usize {
+        self.allocated.get()
+    }
+
+    /// bytes held from the heap
+    pub fn capacity(&self) -> usize {
+        // no &mut to the Vec can be alive here, see alloc_raw
+        unsafe { &*self.chunks.get() }
+            .iter()
+            .map(|chunk| chunk.size)
+            .sum()
+    }
+
+    /// Move `value` into the arena.  It is never dropped.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T>(&self, value: T) -> &mut T {
+        let ptr = self.alloc_raw::<T>(1);
+        unsafe {
+            ptr.as_ptr().write(value);
+            &mut *ptr.as_ptr()
+        }
+    }
+
+    /// `len` values made by `f` from their index.  They are never dropped.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_fill_with<T>(&self, len: usize, mut f: impl FnMut(usize) -> T) -> &mut [T] {
+        let ptr = self.alloc_raw::<T>(len);
+        for i in 0..len {
+            // a panic in f leaves the rest uninitialized, but nothing ever sees them
+            unsafe { ptr.as_ptr().add(i).write(f(i)) };
+        }
+        unsafe { std::slice::from_raw_parts_mut(ptr.as_ptr(), len) }
+    }
+
+    /// uninitialized room for `count` `T`s
+    fn alloc_raw<T>(&self, count: usize) -> NonNull<T> {
+        assert!(
+            align_of::<T>() <= MAX_ALIGN,
+            "frame arena can't align {}",
+            align_of::<T>()
+        );
+        let bytes = size_of::<T>()
+            .checked_mul(count)
+            .expect("frame arena allocation too large");
+        if bytes == 0 {
+            return NonNull::dangling();
+        }
+        // The only &mut to the Vec, and it ends before this returns; the pointers handed out
+        // point into the chunks, which stay where they are when the Vec grows.
+        let chunks = unsafe { &mut *self.chunks.get() };
+        let offset = self.used.get().next_multiple_of(align_of::<T>());
+        let (start, offset) = match chunks.last() {
+            Some(chunk) if offset + bytes <= chunk.size => (chunk.start, offset),
+            last => {
+                let size = last
+                    .map_or(0, |chunk| 2 * chunk.size)
+                    .max(bytes)
+                    .max(MIN_CHUNK);
+                chunks.push(Chunk::new(size));
+                (chunks[chunks.len() - 1].start, 0)
+            }
+        };
+        self.used.set(offset + bytes);
+        self.allocated.set(self.allocated.get() + bytes);
+        unsafe { NonNull::new_unchecked(start.as_ptr().add(offset).cast()) }
+    }
+}
+
+/// A growable list in a [FrameArena].  Growing copies it to a bigger piece of the arena and
+/// leaves the old one unused until the reset, so reserve what the last frame needed when
+/// that is known.
+pub struct ArenaVec<'a, T> {
+    arena: &'a FrameArena,
+    ptr: NonNull<T>,
+    len: usize,
+    capacity: usize,
+    _owns: PhantomData<T>,
+}
+
+impl<'a, T> ArenaVec<'a, T> {
+    pub fn new_in(arena: &'a FrameArena) -> Self {
+        Self::with_capacity_in(0, arena)
+    }
+
+    pub fn with_capacity_in(capacity: usize, arena: &'a FrameArena) -> Self {
+        let capacity = if size_of::<T>() == 0 {
+            usize::MAX
+        } else {
+            capacity
+        };
+        Self {
+            arena,
+            ptr: arena.alloc_raw(if size_of::<T>() == 0 { 0 } else { capacity }),
+            len: 0,
+            capacity,
+            _owns: PhantomData,
+        }
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.len == self.capacity {
+            let capacity = (2 * self.capacity).max(4);
+            let ptr = self.arena.alloc_raw::<T>(capacity);
+            unsafe { std::ptr::copy_nonoverlapping(self.ptr.as_ptr(), ptr.as_ptr(), self.len) };
+            self.ptr = ptr;
+            self.capacity = capacity;
+        }
+        unsafe { self.ptr.as_ptr().add(self.len).write(value) };
+        self.len += 1;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl<T> Deref for ArenaVec<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> DerefMut for ArenaVec<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> Extend<T> for ArenaVec<'_, T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<T> Drop for ArenaVec<'_, T> {
+    fn drop(&mut self) {
+        unsafe { std::ptr::drop_in_place(&mut **self as *mut [T]) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn arena_vec_grows_across_chunks() {
+        let arena = FrameArena::new();
+        let mut list = ArenaVec::new_in(&arena);
+        // far more than MIN_CHUNK bytes, so the copies land in later chunks
+        for i in 0..10_000u64 {
+            list.push(i);
+        }
+        assert!(list.iter().copied().eq(0..10_000));
+        assert!(list.capacity() >= 10_000);
+        assert!(arena.capacity() > MIN_CHUNK);
+    }
+
+    #[test]
+    fn reset_keeps_one_chunk_big_enough() {
+        let mut arena = FrameArena::new();
+        for _ in 0..3 {
+            arena.alloc_slice_fill_with(MIN_CHUNK, |i| i as u8);
+        }
+        let capacity = arena.capacity();
+        arena.reset();
+        assert_eq!(arena.allocated(), 0);
+        assert_eq!(arena.capacity(), capacity);
+        arena.alloc_slice_fill_with(capacity, |i| i as u8);
+        assert_eq!(arena.capacity(), capacity);
+    }
+
+    #[test]
+    fn arena_vec_drops_its_elements() {
+        let counter = Rc::new(());
+        let arena = FrameArena::new();
+        {
+            let mut list = ArenaVec::with_capacity_in(2, &arena);
+            // past the capacity, so some were moved by growing
+            for _ in 0..5 {
+                list.push(counter.clone());
+            }
+            assert_eq!(Rc::strong_count(&counter), 6);
+        }
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn mixed_types_are_aligned() {
+        #[repr(align(16))]
+        struct Wide(#[allow(dead_code)] u8);
+
+        let arena = FrameArena::new();
+        for _ in 0..100 {
+            let byte = arena.alloc(1u8);
+            let word = arena.alloc(2u32);
+            let wide = arena.alloc(Wide(3));
+            let long = arena.alloc_slice_fill_with(3, |i| i as u64);
+            assert_eq!(*byte, 1);
+            assert_eq!(*word, 2);
+            assert_eq!(word as *mut u32 as usize % align_of::<u32>(), 0);
+            assert_eq!(wide as *mut Wide as usize % 16, 0);
+            assert_eq!(long.as_ptr() as usize % align_of::<u64>(), 0);
+            assert_eq!(long, [0, 1, 2]);
+        }
+    }
+
+    #[test]
+    fn zero_sized_values_take_no_room() {
+        let arena = FrameArena::new();
+        let mut list = ArenaVec::new_in(&arena);
+        for _ in 0..1000 {
+            list.push(());
+        }
+        assert_eq!(list.len(), 1000);
+        assert_eq!(arena.capacity(), 0);
+    }
+}
@@ -0,0 +1,197 @@
+//! What the current context can do.  Most of this crate is written for GLES 3 (or desktop GL
+//! 3.3), but some older headsets only offer GLES 2.  Rather than fail there, the code with a
+//! fallback asks [gl_caps] and degrades:
+//!
+//! * no vertex array objects: [crate::gl_helper::VertexArray] records its attributes and sets
+//!   them all again at every bind
+//! * no instancing: [crate::gl_fancy::BoundBuffers::draw_elements_instanced] issues one draw
+//!   per instance
+//! * no sRGB textures or framebuffers: the swapchain is RGBA8 and a final pass encodes with an
+//!   approximation of the sRGB curve (the example app's PostChain does this)
+//! * no sized texture formats: textures are made with the unsized format instead
+//!
+//! Shaders still have to be GLSL ES 1.00 to compile there; most of bob-shaders is.
+
+use gl::types::GLenum;
+use std::ffi::{c_char, CStr};
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+
+/// set by the first [gl_caps], or by [set_gl_caps]
+static CAPS: Mutex<Option<GlCaps>> = Mutex::new(None);
+
+/// Which set of code paths a [GlCaps] gets
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// everything this crate uses is there
+    Full,
+    /// at least one of the GLES 3 features is missing, so some fallbacks are in use
+    Gles2,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GlCaps {
+    pub gles: bool,
+    pub major: u32,
+    pub minor: u32,
+    /// glGenVertexArrays and friends
+    pub vertex_arrays: bool,
+    /// glDrawElementsInstanced, glVertexAttribDivisor and gl_InstanceID
+    pub instancing: bool,
+    /// SRGB8_ALPHA8 textures, renderbuffers and swapchains
+    pub srgb: bool,
+}
+
+impl GlCaps {
+    /// of the context current on this thread
+    pub fn detect() -> Self {
+        let version = gl_string(gl::VERSION);
+        let (gles, major, minor) = parse_version(&version).unwrap_or_else(|| {
+            log::warn!(
+                "can not make sense of GL_VERSION {:?}, assuming GLES 2",
+                version
+            );
+            (true, 2, 0)
+        });
+        // a driver may hand out GLES 3 entry points to a GLES 2 context, so the version decides
+        let gl3 = major >= 3;
+        Self {
+            gles,
+            major,
+            minor,
+            vertex_arrays: gl3 && gl::GenVertexArrays::is_loaded(),
+            instancing: gl3
+                && gl::DrawElementsInstanced::is_loaded()
+                && gl::VertexAttribDivisor::is_loaded(),
+            srgb: gl3,
+        }
+    }
+
+    /// A plain GLES 2.0 context, for [set_gl_caps] to try the fallbacks on a device that does
+    /// not need them.
+    pub fn gles2() -> Self {
+        Self {
+            gles: true,
+            major: 2,
+            minor: 0,
+            vertex_arrays: false,
+            instancing: false,
+            srgb: false,
+        }
+    }
+
+    pub fn profile(&self) -> Profile {
+        if self.vertex_arrays && self.instancing && self.srgb {
+            Profile::Full
+        } else {
+            Profile::Gles2
+        }
+    }
+}
+
+impl Display for GlCaps {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut missing = vec![];
+        if !self.vertex_arrays {
+            missing.push("vertex arrays");
+        }
+        if !self.instancing {
+            missing.push("instancing");
+        }
+        if !self.srgb {
+            missing.push("sRGB");
+        }
+        write!(
+            f,
+            "{} {}.{}, {:?} profile",
+            if self.gles { "GLES" } else { "GL" },
+            self.major,
+            self.minor,
+            self.profile()
+        )?;
+        if !missing.is_empty() {
+            write!(f, " (no {})", missing.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The capabilities of the context current on this thread, detected on the first call.  There
+/// is only ever one context per app here, so they are kept for everyone.
+pub fn gl_caps() -> GlCaps {
+    let mut caps = CAPS.lock().unwrap();
+    *caps.get_or_insert_with(|| {
+        let detected = GlCaps::detect();
+        log::info!("{}", detected);
+        detected
+    })
+}
+
+/// Use `caps` instead of what [gl_caps] detects; call before creating any GL objects, since
+/// those pick their code path when they are made.
+pub fn set_gl_caps(caps: GlCaps) {
+    log::info!("overriding GL capabilities: {}", caps);
+    *CAPS.lock().unwrap() = Some(caps);
+}
+
+/// `(gles, major, minor)` out of a GL_VERSION string, e.g. "OpenGL ES 3.2 V@0615.0" or
+/// "4.6.0 NVIDIA 535.54"
+fn parse_version(version: &str) -> Option<(bool, u32, u32)> {
+    let (gles, rest) = match version.strip_prefix("OpenGL ES") {
+        // "OpenGL ES-CM 1.1" and the like
+        Some(rest) => (true, rest.trim_start_matches(|c: char| !c.is_ascii_digit())),
+        None => (false, version),
+    };
+    let number = rest.split_whitespace().next()?;
+    let mut parts = number.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts
+        .next()
+        .and_then(|minor| minor.parse().ok())
+        .unwrap_or(0);
+    Some((gles, major, minor))
+}
+
+pub(crate) fn gl_string(name: GLenum) -> String {
+    let ptr = unsafe { gl::GetString(name) };
+    if ptr.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(ptr as *const c_char) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gles_versions() {
+        assert_eq!(parse_version("OpenGL ES 3.2"), Some((true, 3, 2)));
+        assert_eq!(parse_version("OpenGL ES 2.0"), Some((true, 2, 0)));
+        assert_eq!(parse_version("OpenGL ES-CM 1.1"), Some((true, 1, 1)));
+    }
+
+    #[test]
+    fn vendor_suffixes_are_ignored() {
+        assert_eq!(
+            parse_version("OpenGL ES 3.2 V@0615.0 (GIT@3fa6f5e, I2c4a3a4f8b) (Date:04/21/22)"),
+            Some((true, 3, 2))
+        );
+        assert_eq!(
+            parse_version("OpenGL ES 3.1 Mesa 23.0.4"),
+            Some((true, 3, 1))
+        );
+        assert_eq!(parse_version("4.6.0 NVIDIA 535.54"), Some((false, 4, 6)));
+        assert_eq!(parse_version("3.3"), Some((false, 3, 3)));
+    }
+
+    #[test]
+    fn garbage_is_none() {
+        assert_eq!(parse_version(""), None);
+        assert_eq!(parse_version("OpenGL ES"), None);
+        assert_eq!(parse_version("not a version"), None);
+        assert_eq!(parse_version("   "), None);
+    }
+}
@@ -0,0 +1,69 @@
+//! Counts of the GL calls that cost the most per frame: draws, texture binds, program
+//! switches and buffer uploads, so the savings of sorting and batching can be seen on the
+//! device instead of guessed at.  gl-thin's wrappers bump the counters, from whatever
+//! thread they run on, and [crate::gl_fancy::GPUState::end_frame] takes them once a frame.
+//! Raw GL calls made around the wrappers are not seen.
+
+use gl::types::{GLenum, GLsizei, GLuint};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GLCallCounts {
+    /// glDrawElements calls, counting an instanced draw as one
+    pub draw_calls: u32,
+    /// over all instances; lines and points count none
+    pub triangles: u64,
+    pub texture_binds: u32,
+    /// glUseProgram calls with a different program than the one before
+    pub program_switches: u32,
+    /// glBufferData and glBufferSubData calls that send data
+    pub buffer_uploads: u32,
+    pub buffer_upload_bytes: u64,
+}
+
+static DRAW_CALLS: AtomicU32 = AtomicU32::new(0);
+static TRIANGLES: AtomicU64 = AtomicU64::new(0);
+static TEXTURE_BINDS: AtomicU32 = AtomicU32::new(0);
+static PROGRAM_SWITCHES: AtomicU32 = AtomicU32::new(0);
+static BUFFER_UPLOADS: AtomicU32 = AtomicU32::new(0);
+static BUFFER_UPLOAD_BYTES: AtomicU64 = AtomicU64::new(0);
+/// the program of the last glUseProgram, to tell a switch from a repeat
+static CURRENT_PROGRAM: AtomicU32 = AtomicU32::new(0);
+
+/// Everything counted since the last call, resetting the counters.
+pub(crate) fn take_counts() -> GLCallCounts {
+    GLCallCounts {
+        draw_calls: DRAW_CALLS.swap(0, Ordering::Relaxed),
+        triangles: TRIANGLES.swap(0, Ordering::Relaxed),
+        texture_binds: TEXTURE_BINDS.swap(0, Ordering::Relaxed),
+        program_switches: PROGRAM_SWITCHES.swap(0, Ordering::Relaxed),
+        buffer_uploads: BUFFER_UPLOADS.swap(0, Ordering::Relaxed),
+        buffer_upload_bytes: BUFFER_UPLOAD_BYTES.swap(0, Ordering::Relaxed),
+    }
+}
+
+pub(crate) fn record_draw(mode: GLenum, n_indices: GLsizei, instances: GLsizei) {
+    DRAW_CALLS.fetch_add(1, Ordering::Relaxed);
+    let n = n_indices.max(0) as u64;
+    let triangles = match mode {
+        gl::TRIANGLES => n / 3,
+        gl::TRIANGLE_STRIP | gl::TRIANGLE_FAN => n.saturating_sub(2),
+        _ => 0,
+    };
+    TRIANGLES.fetch_add(triangles * instances.max(0) as u64, Ordering::Relaxed);
+}
+
+pub(crate) fn record_texture_bind() {
+    TEXTURE_BINDS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_use_program(program: GLuint) {
+    if CURRENT_PROGRAM.swap(program, Ordering::Relaxed) != program {
+        PROGRAM_SWITCHES.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) fn record_buffer_upload(bytes: u64) {
+    BUFFER_UPLOADS.fetch_add(1, Ordering::Relaxed);
+    BUFFER_UPLOAD_BYTES.fetch_add(bytes, Ordering::Relaxed);
+}
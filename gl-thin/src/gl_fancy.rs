@@ -1,8 +1,9 @@
-use crate::gl_helper;
+use crate::gl_caps::gl_caps;
+use crate::gl_counters::{self, GLCallCounts};
 use crate::gl_helper::{
-    bytes_per_pixel, explode_if_gl_error, gl_offset_for, ArrayBufferType, Buffer, BufferOwnership,
-    BufferTarget, ElementArrayBufferType, GLBufferType, GLErrorWrapper, Program, Texture,
-    VertexArray,
+    bytes_per_pixel, explode_if_gl_error, gl_offset_for, internal_format_for, unbind_vertex_array,
+    ArrayBufferType, Buffer, BufferOwnership, BufferTarget, ElementArrayBufferType, GLBufferType,
+    GLErrorWrapper, Program, Texture, VertexArray,
 };
 use gl::types::{GLenum, GLint, GLsizei, GLuint};
 use std::marker::PhantomData;
@@ -17,12 +18,16 @@ use std::rc::Rc;
 /// * what else?
 pub struct GPUState {
     active_texture_unit: ActiveTextureUnit,
+    stencil: Option<StencilState>,
+    last_frame: GLCallCounts,
 }
 
 impl GPUState {
     pub fn new() -> Self {
         Self {
             active_texture_unit: ActiveTextureUnit(0),
+            stencil: None,
+            last_frame: GLCallCounts::default(),
         }
     }
 
@@ -48,6 +53,84 @@ impl GPUState {
         unsafe { gl::ActiveTexture(self.active_texture_unit.gl_arg()) };
         explode_if_gl_error()
     }
+
+    /// Configure the stencil test and what passing or failing it does, or disable it with
+    /// `None`.  The bound framebuffer needs a stencil attachment, e.g.
+    /// [crate::render_target::RenderTarget::with_stencil], for this to do anything.
+    pub fn set_stencil(&mut self, stencil: Option<StencilState>) -> Result<(), GLErrorWrapper> {
+        self.stencil = stencil;
+        match &stencil {
+            None => unsafe { gl::Disable(gl::STENCIL_TEST) },
+            Some(stencil) => unsafe {
+                gl::Enable(gl::STENCIL_TEST);
+                gl::StencilFunc(stencil.func, stencil.reference, stencil.read_mask);
+                gl::StencilOp(stencil.stencil_fail, stencil.depth_fail, stencil.pass);
+                gl::StencilMask(stencil.write_mask);
+            },
+        }
+        explode_if_gl_error()
+    }
+
+    /// what [GPUState::set_stencil] last set
+    pub fn stencil(&self) -> Option<&StencilState> {
+        self.stencil.as_ref()
+    }
+
+    /// Close the frame's [crate::gl_counters], returning what it cost.  Call it once the
+    /// frame is submitted; anything counted before belongs to the frame.
+    pub fn end_frame(&mut self) -> GLCallCounts {
+        self.last_frame = gl_counters::take_counts();
+        self.last_frame
+    }
+
+    /// what [GPUState::end_frame] last returned
+    pub fn last_frame_counts(&self) -> GLCallCounts {
+        self.last_frame
+    }
+}
+
+/// The arguments of glStencilFunc, glStencilOp and glStencilMask, applied to both faces
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StencilState {
+    /// gl::ALWAYS, gl::EQUAL, ...
+    pub func: GLenum,
+    pub reference: GLint,
+    /// the bits the test compares
+    pub read_mask: GLuint,
+    /// the bits the operations may change
+    pub write_mask: GLuint,
+    pub stencil_fail: GLenum,
+    /// the stencil test passed but the depth test failed
+    pub depth_fail: GLenum,
+    pub pass: GLenum,
+}
+
+impl StencilState {
+    /// set the stencil to `reference` wherever something is drawn
+    pub fn write(reference: GLint) -> Self {
+        Self {
+            func: gl::ALWAYS,
+            reference,
+            read_mask: 0xff,
+            write_mask: 0xff,
+            stencil_fail: gl::KEEP,
+            depth_fail: gl::KEEP,
+            pass: gl::REPLACE,
+        }
+    }
+
+    /// only draw where the stencil is `reference`, leaving it as it is
+    pub fn equal(reference: GLint) -> Self {
+        Self {
+            func: gl::EQUAL,
+            reference,
+            read_mask: 0xff,
+            write_mask: 0,
+            stencil_fail: gl::KEEP,
+            depth_fail: gl::KEEP,
+            pass: gl::KEEP,
+        }
+    }
 }
 
 //
@@ -86,17 +169,13 @@ impl<'a, AT, IT> BoundBuffers<'a, AT, IT> {
         stride: GLsizei,
         offset: GLsizei,
     ) -> Result<(), GLErrorWrapper> {
-        unsafe {
-            gl::VertexAttribPointer(
-                program_attribute_location,
-                attribute_array_width,
-                T::TYPE_CODE,
-                gl::FALSE,
-                stride * size_of::<T>() as GLsizei,
-                gl_helper::gl_offset_for::<T>(offset),
-            );
-        }
-        explode_if_gl_error()
+        self.vertex_array.attribute_pointer(
+            program_attribute_location,
+            attribute_array_width,
+            T::TYPE_CODE,
+            stride * size_of::<T>() as GLsizei,
+            offset as usize * size_of::<T>(),
+        )
     }
 
     pub fn rig_one_attribute_by_name<T: GLBufferType>(
@@ -126,6 +205,36 @@ impl<'a, AT, IT: GLBufferType> BoundBuffers<'a, AT, IT> {
         unsafe {
             gl::DrawElements(mode, n_indices, IT::TYPE_CODE, offset);
         }
+        gl_counters::record_draw(mode, n_indices, 1);
+        explode_if_gl_error()
+    }
+
+    /// Draw `instances` copies, which the vertex shader tells apart by instance id.  Where
+    /// instancing is missing (see [crate::gl_caps]) that is one draw per instance, with the
+    /// `uniform int` at `instance_location` set to the instance first; per-instance attributes
+    /// (glVertexAttribDivisor) are not emulated, so keep per-instance data in uniform arrays.
+    /// bob_shaders::compat::instance_id_preamble declares an `instance_id()` for either path.
+    pub fn draw_elements_instanced(
+        &self,
+        mode: GLenum,
+        n_indices: GLsizei,
+        offset: GLsizei,
+        instances: GLsizei,
+        instance_location: GLint,
+    ) -> Result<(), GLErrorWrapper> {
+        let offset = unsafe { gl_offset_for::<IT>(offset) };
+        if gl_caps().instancing {
+            unsafe { gl::DrawElementsInstanced(mode, n_indices, IT::TYPE_CODE, offset, instances) };
+            gl_counters::record_draw(mode, n_indices, instances);
+        } else {
+            for instance in 0..instances {
+                unsafe {
+                    gl::Uniform1i(instance_location, instance);
+                    gl::DrawElements(mode, n_indices, IT::TYPE_CODE, offset);
+                }
+                gl_counters::record_draw(mode, n_indices, 1);
+            }
+        }
         explode_if_gl_error()
     }
 }
@@ -135,8 +244,8 @@ impl<'a, AT, IT> Drop for BoundBuffers<'a, AT, IT> {
         unsafe {
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-            gl::BindVertexArray(0);
         }
+        unbind_vertex_array();
     }
 }
 
@@ -414,7 +523,7 @@ impl<'g, 't> BoundTexture<'g, 't> {
             gl::TexImage2D(
                 self.target,
                 level,
-                internal_format,
+                internal_format_for(internal_format, format),
                 width,
                 height,
                 border,
@@ -423,7 +532,10 @@ impl<'g, 't> BoundTexture<'g, 't> {
                 std::ptr::null(),
             )
         };
-        explode_if_gl_error()
+        explode_if_gl_error()?;
+        self.tex
+            .record_image(self.target, level, internal_format, width, height);
+        Ok(())
     }
 
     pub fn attach(
@@ -503,7 +615,7 @@ impl<'g, 't> BoundTexture<'g, 't> {
             gl::TexImage2D(
                 self.target,
                 level,
-                internal_format,
+                internal_format_for(internal_format, format),
                 width,
                 height,
                 0,
@@ -512,15 +624,100 @@ impl<'g, 't> BoundTexture<'g, 't> {
                 pixels.as_ptr() as *const _,
             );
         }
-        explode_if_gl_error()
+        explode_if_gl_error()?;
+        self.tex
+            .record_image(self.target, level, internal_format, width, height);
+        Ok(())
+    }
+
+    /// Upload straight (unassociated) RGBA8 `pixels` with their colors multiplied by their
+    /// alpha.  Filtering, blending with `(ONE, ONE_MINUS_SRC_ALPHA)` and the OpenXR
+    /// compositor all expect premultiplied colors; straight ones leave dark fringes around
+    /// translucent edges.
+    pub fn write_premultiplied_rgba(
+        &mut self,
+        level: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        pixels: &[u8],
+    ) -> Result<(), GLErrorWrapper> {
+        let mut premultiplied = pixels.to_vec();
+        premultiply_alpha(&mut premultiplied);
+        self.write_pixels(
+            level,
+            gl::RGBA as GLint,
+            width,
+            height,
+            gl::RGBA,
+            &premultiplied,
+        )
     }
 
     pub fn generate_mipmap(&self) -> Result<(), GLErrorWrapper> {
         unsafe { gl::GenerateMipmap(self.target) };
+        explode_if_gl_error()?;
+        self.tex.record_mipmaps(self.target);
+        Ok(())
+    }
+
+    /// Overwrite a rectangle of an image that was already allocated with [Self::configure] or [Self::write_pixels]
+    #[allow(clippy::too_many_arguments)]
+    pub fn write_sub_pixels<T: GLBufferType>(
+        &mut self,
+        level: GLint,
+        x_offset: GLint,
+        y_offset: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        format: GLenum,
+        pixels: &[T],
+    ) -> Result<(), GLErrorWrapper> {
+        let bpp = bytes_per_pixel::<T>(format)?;
+        let bytes = std::mem::size_of_val(pixels);
+        if (width * height) as usize * bpp != bytes {
+            return Err(GLErrorWrapper::with_message2(format!(
+                "size mismatch : {}*{}*{} bytes != {} bytes",
+                width, height, bpp, bytes
+            )));
+        }
+
+        unsafe {
+            gl::TexSubImage2D(
+                self.target,
+                level,
+                x_offset,
+                y_offset,
+                width,
+                height,
+                format,
+                T::TYPE_CODE,
+                pixels.as_ptr() as *const _,
+            );
+        }
+        explode_if_gl_error()
+    }
+
+    /// wrapper for gl::TexParameteri, e.g. `set_parameter(gl::TEXTURE_MIN_FILTER, gl::LINEAR)`
+    pub fn set_parameter(&self, pname: GLenum, value: GLenum) -> Result<(), GLErrorWrapper> {
+        unsafe { gl::TexParameteri(self.target, pname, value as GLint) };
         explode_if_gl_error()
     }
 }
 
+/// Multiply the color of each RGBA8 pixel by its alpha, in place
+pub fn premultiply_alpha(rgba: &mut [u8]) {
+    for pixel in rgba.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u32;
+        for channel in &mut pixel[..3] {
+            // rounded division by 255
+            let product = *channel as u32 * alpha + 128;
+            *channel = ((product + (product >> 8)) >> 8) as u8;
+        }
+    }
+}
+
+//
+
 /// still experimental
 pub struct BoundVertexArray<'a, 'g, AT> {
     pub vao: &'a VertexArray,
@@ -553,17 +750,13 @@ impl<'a, 'g, AT: GLBufferType> BoundVertexArray<'a, 'g, AT> {
         stride: GLsizei,
         offset: GLsizei,
     ) -> Result<(), GLErrorWrapper> {
-        unsafe {
-            gl::VertexAttribPointer(
-                program_attribute_location,
-                attribute_array_width,
-                AT::TYPE_CODE,
-                gl::FALSE,
-                stride * size_of::<AT>() as GLsizei,
-                gl_helper::gl_offset_for::<AT>(offset),
-            );
-        }
-        explode_if_gl_error()?;
+        self.vao.attribute_pointer(
+            program_attribute_location,
+            attribute_array_width,
+            AT::TYPE_CODE,
+            stride * size_of::<AT>() as GLsizei,
+            offset as usize * size_of::<AT>(),
+        )?;
 
         unsafe { gl::EnableVertexAttribArray(program_attribute_location) };
         explode_if_gl_error()
@@ -602,9 +795,7 @@ impl<'a, 'g, AT: GLBufferType> BoundVertexArray<'a, 'g, AT> {
 
 impl<'a, 'g, AT> Drop for BoundVertexArray<'a, 'g, AT> {
     fn drop(&mut self) {
-        unsafe {
-            gl::BindVertexArray(0);
-        }
+        unbind_vertex_array();
         let _ = explode_if_gl_error();
     }
 }
@@ -1,10 +1,16 @@
+use crate::fallback;
+use crate::gl_caps::gl_caps;
 use crate::gl_fancy::{BoundTexture, BoundVertexArray, GPUState, OneBoundBuffer};
-use gl::types::{GLchar, GLenum, GLfloat, GLint, GLsizei, GLsizeiptr, GLuint, GLushort};
-use std::ffi::{c_void, CString};
+use crate::multiview::{self, MultiviewVariant};
+use crate::{gl_counters, gpu_memory};
+use gl::types::{GLchar, GLenum, GLfloat, GLint, GLintptr, GLsizei, GLsizeiptr, GLuint, GLushort};
+use std::cell::{Cell, RefCell};
+use std::ffi::{c_char, c_void, CStr, CString};
 use std::fmt::{Debug, Display, Formatter};
 use std::marker::PhantomData;
 use std::mem::{size_of, MaybeUninit};
 use std::ptr::null;
+use std::sync::Mutex;
 
 pub fn initialize_gl_using_egli() {
     gl::load_with(|name| {
@@ -30,6 +36,33 @@ pub fn explode_if_gl_error() -> Result<(), GLErrorWrapper> {
     }
 }
 
+/// true for an OpenGL ES context, false for desktop GL
+pub fn is_gles() -> bool {
+    let version = unsafe { gl::GetString(gl::VERSION) };
+    if version.is_null() {
+        return false;
+    }
+    let version = unsafe { CStr::from_ptr(version as *const c_char) };
+    version.to_bytes().starts_with(b"OpenGL ES")
+}
+
+/// whether the current context advertises the extension `name`, e.g. `"GL_EXT_color_buffer_float"`
+pub fn has_extension(name: &str) -> bool {
+    if gl_caps().major < 3 {
+        // no glGetStringi; GLES 2 has them all in one string
+        return crate::gl_caps::gl_string(gl::EXTENSIONS)
+            .split_whitespace()
+            .any(|extension| extension == name);
+    }
+    let mut count: GLint = 0;
+    unsafe { gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count) };
+    (0..count.max(0) as GLuint).any(|i| {
+        let extension = unsafe { gl::GetStringi(gl::EXTENSIONS, i) };
+        !extension.is_null()
+            && unsafe { CStr::from_ptr(extension as *const c_char) }.to_bytes() == name.as_bytes()
+    })
+}
+
 //
 
 #[derive(Clone)]
@@ -123,18 +156,64 @@ impl BufferTarget for ElementArrayBufferType {
 
 //
 
-pub struct VertexArray(GLuint);
+/// A vertex array object.  Where the context has none (see [crate::gl_caps]) it is emulated:
+/// the attribute pointers rigged through it are recorded, and every [VertexArray::bind] sets
+/// them all again.  The emulation does not keep the ELEMENT_ARRAY_BUFFER binding, so bind the
+/// index buffer after the vertex array, as [GPUState::bind_vertex_array_and_buffers] does.
+pub struct VertexArray {
+    /// 0 when emulated
+    name: GLuint,
+    emulated: Option<RefCell<Vec<AttributePointer>>>,
+}
+
+/// the arguments of one glVertexAttribPointer, and the ARRAY_BUFFER it read from
+#[derive(Copy, Clone, Debug)]
+struct AttributePointer {
+    location: GLuint,
+    width: GLint,
+    type_code: GLenum,
+    /// bytes
+    stride: GLsizei,
+    /// bytes
+    offset: usize,
+    buffer: GLuint,
+}
+
+/// the attributes the last emulated [VertexArray::bind] enabled, for [unbind_vertex_array]
+static EMULATED_ENABLED: Mutex<Vec<GLuint>> = Mutex::new(Vec::new());
 
 impl VertexArray {
     pub fn incomplete() -> Result<Self, GLErrorWrapper> {
+        if !gl_caps().vertex_arrays {
+            return Ok(Self {
+                name: 0,
+                emulated: Some(RefCell::new(vec![])),
+            });
+        }
         let mut rval = MaybeUninit::uninit();
         unsafe { gl::GenVertexArrays(1, rval.as_mut_ptr()) };
         explode_if_gl_error()?;
-        Ok(Self(unsafe { rval.assume_init() }))
+        Ok(Self {
+            name: unsafe { rval.assume_init() },
+            emulated: None,
+        })
     }
 
     pub fn bind(&self) -> Result<(), GLErrorWrapper> {
-        unsafe { gl::BindVertexArray(self.0) }
+        let Some(emulated) = &self.emulated else {
+            unsafe { gl::BindVertexArray(self.name) }
+            return explode_if_gl_error();
+        };
+        unbind_vertex_array();
+        let mut enabled = EMULATED_ENABLED.lock().unwrap();
+        for attribute in emulated.borrow().iter() {
+            unsafe {
+                gl::BindBuffer(gl::ARRAY_BUFFER, attribute.buffer);
+                attribute.set();
+                gl::EnableVertexAttribArray(attribute.location);
+            }
+            enabled.push(attribute.location);
+        }
         explode_if_gl_error()
     }
 
@@ -145,14 +224,82 @@ impl VertexArray {
         BoundVertexArray::new(self, gpu_state)
     }
 
+    /// glVertexAttribPointer for `location` out of the bound ARRAY_BUFFER, which this vertex
+    /// array remembers.  `stride` and `offset` are in bytes.  It has to be bound.
+    pub fn attribute_pointer(
+        &self,
+        location: GLuint,
+        width: GLint,
+        type_code: GLenum,
+        stride: GLsizei,
+        offset: usize,
+    ) -> Result<(), GLErrorWrapper> {
+        let mut attribute = AttributePointer {
+            location,
+            width,
+            type_code,
+            stride,
+            offset,
+            buffer: 0,
+        };
+        unsafe { attribute.set() };
+        explode_if_gl_error()?;
+        if let Some(emulated) = &self.emulated {
+            let mut buffer = 0;
+            unsafe { gl::GetIntegerv(gl::ARRAY_BUFFER_BINDING, &mut buffer) };
+            explode_if_gl_error()?;
+            attribute.buffer = buffer as GLuint;
+            let mut emulated = emulated.borrow_mut();
+            emulated.retain(|a| a.location != location);
+            emulated.push(attribute);
+        }
+        Ok(())
+    }
+
+    /// 0 for an emulated one
     pub fn borrow_raw(&self) -> GLuint {
-        self.0
+        self.name
+    }
+
+    pub fn is_emulated(&self) -> bool {
+        self.emulated.is_some()
+    }
+}
+
+impl AttributePointer {
+    unsafe fn set(&self) {
+        gl::VertexAttribPointer(
+            self.location,
+            self.width,
+            self.type_code,
+            gl::FALSE,
+            self.stride,
+            self.offset as *const c_void,
+        );
+    }
+}
+
+/// glBindVertexArray(0), or where vertex arrays are emulated, disable the attributes the last
+/// [VertexArray::bind] enabled
+pub fn unbind_vertex_array() {
+    let mut enabled = EMULATED_ENABLED.lock().unwrap();
+    if enabled.is_empty() {
+        drop(enabled);
+        if gl_caps().vertex_arrays {
+            unsafe { gl::BindVertexArray(0) }
+        }
+    } else {
+        for location in enabled.drain(..) {
+            unsafe { gl::DisableVertexAttribArray(location) }
+        }
     }
 }
 
 impl Drop for VertexArray {
     fn drop(&mut self) {
-        unsafe { gl::DeleteVertexArrays(1, &self.0) }
+        if self.emulated.is_none() {
+            unsafe { gl::DeleteVertexArrays(1, &self.name) }
+        }
     }
 }
 
@@ -217,6 +364,7 @@ impl<'a, B, T> Buffer<'a, B, T> {
 impl<'a, B, T> Drop for Buffer<'a, B, T> {
     fn drop(&mut self) {
         unsafe { gl::DeleteBuffers(1, &self.handle) }
+        gpu_memory::forget_buffer(self.handle);
     }
 }
 
@@ -242,7 +390,10 @@ impl<'a, B: BufferTarget, T> Buffer<'a, B, T> {
                 gl::STATIC_DRAW,
             )
         }
-        explode_if_gl_error()
+        explode_if_gl_error()?;
+        gpu_memory::record_buffer(self.handle, byte_count as u64);
+        gl_counters::record_buffer_upload(byte_count as u64);
+        Ok(())
     }
 
     pub fn load(&mut self, values: &'a [T]) -> Result<(), GLErrorWrapper> {
@@ -257,7 +408,10 @@ impl<'a, B: BufferTarget, T> Buffer<'a, B, T> {
             )
         }
         self.data = BufferOwnership::Reference(values);
-        explode_if_gl_error()
+        explode_if_gl_error()?;
+        gpu_memory::record_buffer(self.handle, byte_count as u64);
+        gl_counters::record_buffer_upload(byte_count as u64);
+        Ok(())
     }
 
     pub fn load_owned(&mut self, values: Vec<T>) -> Result<(), GLErrorWrapper> {
@@ -272,7 +426,40 @@ impl<'a, B: BufferTarget, T> Buffer<'a, B, T> {
             )
         }
         self.data = BufferOwnership::Owned(values);
-        explode_if_gl_error()
+        explode_if_gl_error()?;
+        gpu_memory::record_buffer(self.handle, byte_count as u64);
+        gl_counters::record_buffer_upload(byte_count as u64);
+        Ok(())
+    }
+
+    /// Make room on the GPU for `count` elements that will be rewritten often with
+    /// [Buffer::update].  Keeps no CPU copy, so the caller has to hold on to one.
+    pub fn allocate_dynamic(&self, count: usize) -> Result<(), GLErrorWrapper> {
+        self.bind()?;
+        let byte_count: GLsizeiptr = count as GLsizeiptr * size_of::<T>() as GLsizeiptr;
+        unsafe { gl::BufferData(B::TARGET, byte_count, null(), gl::DYNAMIC_DRAW) }
+        explode_if_gl_error()?;
+        gpu_memory::record_buffer(self.handle, byte_count as u64);
+        Ok(())
+    }
+
+    /// Overwrite the GPU copy of elements `first..first + values.len()`, which must fit
+    /// inside what was allocated.
+    pub fn update(&self, first: usize, values: &[T]) -> Result<(), GLErrorWrapper> {
+        self.bind()?;
+        let element_size = size_of::<T>() as GLsizeiptr;
+        let byte_count = values.len() as GLsizeiptr * element_size;
+        unsafe {
+            gl::BufferSubData(
+                B::TARGET,
+                first as GLintptr * element_size,
+                byte_count,
+                values.as_ptr() as *const c_void,
+            )
+        }
+        explode_if_gl_error()?;
+        gl_counters::record_buffer_upload(byte_count as u64);
+        Ok(())
     }
 
     pub fn bind(&self) -> Result<(), GLErrorWrapper> {
@@ -379,25 +566,84 @@ impl<F> Drop for Shader<F> {
 
 //
 
-pub struct Program(GLuint);
+pub struct Program {
+    handle: GLuint,
+    /// drawn with instead during a multiview pass, see [crate::multiview]
+    multiview: Option<Box<MultiviewVariant>>,
+    /// whether the last [Program::use_] picked [Program::multiview]
+    using_multiview: Cell<bool>,
+    /// see [Program::is_error_shader]
+    error_shader: bool,
+    /// the attributes an error shader optimized out, see [fallback::unused_attribute]
+    missing_attributes: RefCell<Vec<String>>,
+}
 
 impl Program {
     pub fn new_empty() -> Result<Self, GLErrorWrapper> {
         let rval = unsafe { gl::CreateProgram() };
         explode_if_gl_error()?;
-        Ok(Self(rval))
+        Ok(Self::take_ownership(rval))
     }
 
+    /// With [crate::multiview::enable_shader_variants], also compiles a variant that draws
+    /// both eyes at once; if that fails the program is still usable outside multiview passes.
+    ///
+    /// If the vertex shader compiles but the program does not, the error is logged and the
+    /// program comes back with [fallback::error_fragment_shader] instead; see
+    /// [Program::is_error_shader].  [Program::compile_exact] fails instead.
     pub fn compile(
         vertex_shader: impl AsRef<str>,
         fragment_shader: impl AsRef<str>,
     ) -> Result<Self, GLErrorWrapper> {
-        let vertex_shader = Shader::<VertexShader>::compile(vertex_shader.as_ref())?;
-        let fragment_shader = Shader::<FragmentShader>::compile(fragment_shader.as_ref())?;
+        let vertex_shader = vertex_shader.as_ref();
+        match Self::compile_exact(vertex_shader, fragment_shader) {
+            Ok(rval) => Ok(rval),
+            Err(e) => {
+                let error_shader = fallback::error_fragment_shader(vertex_shader);
+                // a vertex shader that does not compile either is beyond help
+                let Ok(mut rval) = Self::compile_exact(vertex_shader, error_shader) else {
+                    return Err(e);
+                };
+                log::error!(
+                    "program {} failed to build, painting it magenta {}",
+                    rval.handle,
+                    e
+                );
+                rval.error_shader = true;
+                Ok(rval)
+            }
+        }
+    }
+
+    /// [Program::compile] without the error shader fallback
+    pub fn compile_exact(
+        vertex_shader: impl AsRef<str>,
+        fragment_shader: impl AsRef<str>,
+    ) -> Result<Self, GLErrorWrapper> {
+        let (vertex_shader, fragment_shader) = (vertex_shader.as_ref(), fragment_shader.as_ref());
+        let mut rval = Self::link(vertex_shader, fragment_shader, |_| {})?;
+        if multiview::shader_variants_enabled() {
+            match MultiviewVariant::compile(&rval, vertex_shader, fragment_shader) {
+                Ok(variant) => rval.multiview = Some(Box::new(variant)),
+                Err(e) => log::warn!("no multiview variant of program {}: {}", rval.handle, e),
+            }
+        }
+        Ok(rval)
+    }
+
+    /// `before_link` gets the program's handle with the shaders attached
+    pub(crate) fn link(
+        vertex_shader: &str,
+        fragment_shader: &str,
+        before_link: impl FnOnce(GLuint),
+    ) -> Result<Self, GLErrorWrapper> {
+        let vertex_shader = Shader::<VertexShader>::compile(vertex_shader)?;
+        let fragment_shader = Shader::<FragmentShader>::compile(fragment_shader)?;
 
         let mut rval = Self::new_empty().unwrap();
         rval.attach(&vertex_shader).unwrap();
         rval.attach(&fragment_shader).unwrap();
+        before_link(rval.borrow());
 
         unsafe { gl::LinkProgram(rval.borrow()) };
         explode_if_gl_error().unwrap();
@@ -416,11 +662,45 @@ impl Program {
     }
 
     pub fn borrow(&self) -> GLuint {
-        self.0
+        self.handle
     }
 
     pub fn take_ownership(handle: GLuint) -> Self {
-        Self(handle)
+        Self {
+            handle,
+            multiview: None,
+            using_multiview: Cell::new(false),
+            error_shader: false,
+            missing_attributes: RefCell::new(vec![]),
+        }
+    }
+
+    /// The fragment shader [Program::compile] was given failed, and this paints
+    /// [fallback::ERROR_COLOR] instead.  Its uniforms and attributes that went with the
+    /// real fragment shader are still found, but setting them does nothing.
+    pub fn is_error_shader(&self) -> bool {
+        self.error_shader
+    }
+
+    pub fn has_multiview_variant(&self) -> bool {
+        self.multiview.is_some()
+    }
+
+    /// the program the last [Program::use_] put in use: this one, or its multiview variant
+    pub fn handle_in_use(&self) -> GLuint {
+        match &self.multiview {
+            Some(variant) if self.using_multiview.get() => variant.program.handle,
+            _ => self.handle,
+        }
+    }
+
+    /// `location` (from [Program::get_uniform_location]) in the program in use; -1, which GL
+    /// ignores, for a uniform the multiview variant optimized out
+    pub fn location_in_use(&self, location: GLint) -> GLint {
+        match &self.multiview {
+            Some(variant) if self.using_multiview.get() => variant.location(location),
+            _ => location,
+        }
     }
 
     fn attach<T>(&mut self, shader: &Shader<T>) -> Result<(), GLErrorWrapper> {
@@ -432,16 +712,26 @@ impl Program {
         unsafe { gl::DetachShader(self.borrow(), shader.borrow()) };
     }
 
+    /// Inside a multiview pass (see [crate::multiview::MultiviewFrameBuffer]) this uses the
+    /// multiview variant, and the setters below translate their locations to it.
     pub fn use_(&self) -> Result<(), GLErrorWrapper> {
-        unsafe { gl::UseProgram(self.0) }
+        self.using_multiview
+            .set(self.multiview.is_some() && multiview::pass_active());
+        let handle = self.handle_in_use();
+        unsafe { gl::UseProgram(handle) }
+        gl_counters::record_use_program(handle);
         explode_if_gl_error()
     }
 
     pub fn get_uniform_location(&self, name: &str) -> Result<GLuint, GLErrorWrapper> {
         let c_name = CString::new(name).unwrap();
-        let rval = unsafe { gl::GetUniformLocation(self.0, c_name.as_ptr() as *const GLchar) };
+        let rval = unsafe { gl::GetUniformLocation(self.handle, c_name.as_ptr() as *const GLchar) };
         explode_if_gl_error()?;
         if rval < 0 {
+            if self.error_shader {
+                // -1, which the setters pass on and GL ignores
+                return Ok(rval as GLuint);
+            }
             return Err(GLErrorWrapper::with_message(
                 CString::new(format!("no attribute named {}", name)).unwrap(),
             ));
@@ -451,9 +741,12 @@ impl Program {
 
     pub fn get_attribute_location(&self, p0: &str) -> Result<GLuint, GLErrorWrapper> {
         let name = CString::new(p0).unwrap();
-        let rval = unsafe { gl::GetAttribLocation(self.0, name.as_ptr()) };
+        let rval = unsafe { gl::GetAttribLocation(self.handle, name.as_ptr()) };
         explode_if_gl_error()?;
-        if rval < 0 {
+        if rval < 0 && self.error_shader {
+            let mut missing = self.missing_attributes.borrow_mut();
+            Ok(fallback::unused_attribute(self.handle, p0, &mut missing))
+        } else if rval < 0 {
             panic!("no attribute named {} on this program", p0)
         } else {
             Ok(rval as GLuint)
@@ -463,12 +756,12 @@ impl Program {
     //
 
     pub fn set_uniform_1i(&self, location: GLint, v0: GLint) -> Result<(), GLErrorWrapper> {
-        unsafe { gl::Uniform1i(location, v0) }
+        unsafe { gl::Uniform1i(self.location_in_use(location), v0) }
         explode_if_gl_error()
     }
 
     pub fn set_uniform_1f(&self, location: GLint, v0: GLfloat) -> Result<(), GLErrorWrapper> {
-        unsafe { gl::Uniform1f(location, v0) }
+        unsafe { gl::Uniform1f(self.location_in_use(location), v0) }
         explode_if_gl_error()
     }
 
@@ -478,7 +771,7 @@ impl Program {
         v0: GLfloat,
         v1: GLfloat,
     ) -> Result<(), GLErrorWrapper> {
-        unsafe { gl::Uniform2f(location, v0, v1) }
+        unsafe { gl::Uniform2f(self.location_in_use(location), v0, v1) }
         explode_if_gl_error()
     }
 
@@ -488,12 +781,24 @@ impl Program {
         val: &[GLfloat; 2],
     ) -> Result<(), GLErrorWrapper> {
         // Uniform2fv has failed me in the past
-        unsafe { gl::Uniform2f(location, val[0], val[1]) }
+        unsafe { gl::Uniform2f(self.location_in_use(location), val[0], val[1]) }
         explode_if_gl_error()
     }
 
     pub fn set_uniform_3f(&self, name: &str, x: f32, y: f32, z: f32) -> Result<(), GLErrorWrapper> {
-        unsafe { gl::Uniform3f(self.get_uniform_location(name)? as GLint, x, y, z) }
+        unsafe {
+            gl::Uniform3f(
+                self.location_in_use(self.get_uniform_location(name)? as GLint),
+                x,
+                y,
+                z,
+            )
+        }
+        explode_if_gl_error()
+    }
+
+    pub fn set_uniform_3fv(&self, location: GLint, val: &[f32; 3]) -> Result<(), GLErrorWrapper> {
+        unsafe { gl::Uniform3f(self.location_in_use(location), val[0], val[1], val[2]) }
         explode_if_gl_error()
     }
 
@@ -505,22 +810,77 @@ impl Program {
         z: f32,
         a: f32,
     ) -> Result<(), GLErrorWrapper> {
-        unsafe { gl::Uniform4f(location, x, y, z, a) }
+        unsafe { gl::Uniform4f(self.location_in_use(location), x, y, z, a) }
         explode_if_gl_error()
     }
 
     pub fn set_uniform_4fv(&self, location: GLint, vec4: &[f32; 4]) -> Result<(), GLErrorWrapper> {
-        unsafe { gl::Uniform4fv(location, 1, vec4.as_ptr()) }
+        unsafe { gl::Uniform4fv(self.location_in_use(location), 1, vec4.as_ptr()) }
+        explode_if_gl_error()
+    }
+
+    /// fills a `uniform vec3 name[N]` from element 0, `location` being that of `name`
+    pub fn set_uniform_3fv_array(
+        &self,
+        location: GLint,
+        values: &[[f32; 3]],
+    ) -> Result<(), GLErrorWrapper> {
+        unsafe {
+            gl::Uniform3fv(
+                self.location_in_use(location),
+                values.len() as GLsizei,
+                values.as_ptr().cast(),
+            )
+        }
+        explode_if_gl_error()
+    }
+
+    /// fills a `uniform vec4 name[N]` from element 0, `location` being that of `name`
+    pub fn set_uniform_4fv_array(
+        &self,
+        location: GLint,
+        values: &[[f32; 4]],
+    ) -> Result<(), GLErrorWrapper> {
+        unsafe {
+            gl::Uniform4fv(
+                self.location_in_use(location),
+                values.len() as GLsizei,
+                values.as_ptr().cast(),
+            )
+        }
+        explode_if_gl_error()
+    }
+
+    /// `val` is column-major, like all the other matrices
+    pub fn set_mat3u(&self, location: GLint, val: &[f32; 9]) -> Result<(), GLErrorWrapper> {
+        unsafe { gl::UniformMatrix3fv(self.location_in_use(location), 1, 0, val.as_ptr()) }
         explode_if_gl_error()
     }
 
     pub fn set_mat4(&self, location: GLint, val: &[[f32; 4]; 4]) -> Result<(), GLErrorWrapper> {
-        unsafe { gl::UniformMatrix4fv(location, 1, 0, val[0].as_ptr()) }
+        unsafe { gl::UniformMatrix4fv(self.location_in_use(location), 1, 0, val[0].as_ptr()) }
         explode_if_gl_error()
     }
 
     pub fn set_mat4u(&self, location: GLint, val: &[f32; 16]) -> Result<(), GLErrorWrapper> {
-        unsafe { gl::UniformMatrix4fv(location, 1, 0, val.as_ptr()) }
+        unsafe { gl::UniformMatrix4fv(self.location_in_use(location), 1, 0, val.as_ptr()) }
+        explode_if_gl_error()
+    }
+
+    /// fills a `uniform mat4 name[N]` from element 0, `location` being that of `name`
+    pub fn set_mat4u_array(
+        &self,
+        location: GLint,
+        values: &[[f32; 16]],
+    ) -> Result<(), GLErrorWrapper> {
+        unsafe {
+            gl::UniformMatrix4fv(
+                self.location_in_use(location),
+                values.len() as GLsizei,
+                0,
+                values.as_ptr().cast(),
+            )
+        }
         explode_if_gl_error()
     }
 
@@ -547,7 +907,7 @@ fn from_glchar_to_u8(src: Vec<GLchar>) -> Vec<u8> {
 
 impl Drop for Program {
     fn drop(&mut self) {
-        unsafe { gl::DeleteProgram(self.0) }
+        unsafe { gl::DeleteProgram(self.handle) }
     }
 }
 
@@ -566,6 +926,44 @@ impl FrameBuffer {
         unsafe { gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.0) }
         explode_if_gl_error()
     }
+
+    /// bind as the source for glReadPixels and glBlitFramebuffer
+    pub fn bind_read(&self) -> Result<(), GLErrorWrapper> {
+        unsafe { gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.0) }
+        explode_if_gl_error()
+    }
+
+    /// Bind for drawing and tell the driver the contents of `attachments` (e.g.
+    /// gl::DEPTH_ATTACHMENT) are no longer needed, so a tiled GPU can skip writing them back
+    /// to memory.  Does nothing where glInvalidateFramebuffer is missing (desktop GL before 4.3).
+    pub fn invalidate(&self, attachments: &[GLenum]) -> Result<(), GLErrorWrapper> {
+        if attachments.is_empty() || !gl::InvalidateFramebuffer::is_loaded() {
+            return Ok(());
+        }
+        self.bind()?;
+        unsafe {
+            gl::InvalidateFramebuffer(
+                gl::DRAW_FRAMEBUFFER,
+                attachments.len() as GLsizei,
+                attachments.as_ptr(),
+            )
+        };
+        explode_if_gl_error()
+    }
+
+    /// check the currently bound `target` (DRAW_FRAMEBUFFER or READ_FRAMEBUFFER) is complete
+    pub fn check_status(target: GLenum) -> Result<(), GLErrorWrapper> {
+        let status = unsafe { gl::CheckFramebufferStatus(target) };
+        explode_if_gl_error()?;
+        if status == gl::FRAMEBUFFER_COMPLETE {
+            Ok(())
+        } else {
+            Err(GLErrorWrapper::with_message2(format!(
+                "framebuffer incomplete 0x{:x}",
+                status
+            )))
+        }
+    }
 }
 
 impl Drop for FrameBuffer {
@@ -576,6 +974,51 @@ impl Drop for FrameBuffer {
 
 //
 
+/// An image only a framebuffer can use: nothing samples it, so the driver is free to keep it
+/// in tile memory, e.g. the samples of a multisampled color or depth buffer.
+pub struct RenderBuffer(GLuint);
+
+impl RenderBuffer {
+    /// `samples` 0 for an ordinary single-sampled buffer
+    pub fn new(
+        samples: GLsizei,
+        internal_format: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) -> Result<Self, GLErrorWrapper> {
+        let mut rval = MaybeUninit::uninit();
+        unsafe { gl::GenRenderbuffers(1, rval.as_mut_ptr()) };
+        explode_if_gl_error()?;
+        let rval = Self(unsafe { rval.assume_init() });
+        unsafe {
+            gl::BindRenderbuffer(gl::RENDERBUFFER, rval.0);
+            gl::RenderbufferStorageMultisample(
+                gl::RENDERBUFFER,
+                samples,
+                internal_format,
+                width,
+                height,
+            );
+        }
+        explode_if_gl_error()?;
+        Ok(rval)
+    }
+
+    /// to the bound `target` framebuffer
+    pub fn attach(&self, target: GLenum, attachment: GLenum) -> Result<(), GLErrorWrapper> {
+        unsafe { gl::FramebufferRenderbuffer(target, attachment, gl::RENDERBUFFER, self.0) };
+        explode_if_gl_error()
+    }
+}
+
+impl Drop for RenderBuffer {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteRenderbuffers(1, &self.0) };
+    }
+}
+
+//
+
 pub struct Texture(pub Ownership<GLuint>);
 
 impl Texture {
@@ -611,6 +1054,42 @@ impl Texture {
         Ok(rval)
     }
 
+    /// Like [Texture::depth_buffer], with 8 stencil bits packed next to the 24 depth bits.
+    /// Attach it at gl::DEPTH_STENCIL_ATTACHMENT.
+    pub fn depth_stencil_buffer(
+        width: i32,
+        height: i32,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let rval = Self::new()?;
+
+        let _bound = rval.bound(gl::TEXTURE_2D, gpu_state)?;
+        // configure() can't express the packed UNSIGNED_INT_24_8 type
+        unsafe {
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::DEPTH24_STENCIL8 as i32,
+                width,
+                height,
+                0,
+                gl::DEPTH_STENCIL,
+                gl::UNSIGNED_INT_24_8,
+                null(),
+            )
+        };
+        explode_if_gl_error()?;
+        rval.record_image(
+            gl::TEXTURE_2D,
+            0,
+            gl::DEPTH24_STENCIL8 as i32,
+            width,
+            height,
+        );
+
+        Ok(rval)
+    }
+
     pub fn bound<'g, 't>(
         &'t self,
         target: GLenum,
@@ -619,6 +1098,34 @@ impl Texture {
         BoundTexture::new(gpu_state, self, target)
     }
 
+    /// the handle, if deleting it is up to this object
+    fn owned_handle(&self) -> Option<GLuint> {
+        match self.0 {
+            Ownership::Owned(handle) => Some(handle),
+            Ownership::Borrowed(_) | Ownership::None => None,
+        }
+    }
+
+    /// count a freshly specified image in [gpu_memory::gpu_memory_totals]
+    pub(crate) fn record_image(
+        &self,
+        target: GLenum,
+        level: GLint,
+        internal_format: GLint,
+        width: GLint,
+        height: GLint,
+    ) {
+        if let Some(handle) = self.owned_handle() {
+            gpu_memory::record_texture_image(handle, target, level, internal_format, width, height);
+        }
+    }
+
+    pub(crate) fn record_mipmaps(&self, target: GLenum) {
+        if let Some(handle) = self.owned_handle() {
+            gpu_memory::record_mipmaps(handle, target);
+        }
+    }
+
     pub fn borrow(&self) -> GLuint {
         match &self.0 {
             Ownership::Borrowed(val) | Ownership::Owned(val) => *val,
@@ -643,7 +1150,7 @@ impl Texture {
             gl::TexImage2D(
                 target,
                 level,
-                internal_format,
+                internal_format_for(internal_format, format),
                 width,
                 height,
                 border,
@@ -653,12 +1160,15 @@ impl Texture {
                 null(),
             )
         };
-        explode_if_gl_error()
+        explode_if_gl_error()?;
+        self.record_image(target, level, internal_format, width, height);
+        Ok(())
     }
 
     /// Consider using BoundTexture instead
     pub fn bind(&self, target: GLenum) -> Result<(), GLErrorWrapper> {
         unsafe { gl::BindTexture(target, *self.0.unwrap()) };
+        gl_counters::record_texture_bind();
         explode_if_gl_error()
     }
 
@@ -762,7 +1272,7 @@ impl Texture {
             gl::TexImage2D(
                 target,
                 level,
-                internal_format,
+                internal_format_for(internal_format, format),
                 width,
                 height,
                 0,
@@ -771,21 +1281,28 @@ impl Texture {
                 pixels.as_ptr() as *const _,
             );
         }
-        explode_if_gl_error()
+        explode_if_gl_error()?;
+        self.record_image(target, level, internal_format, width, height);
+        Ok(())
     }
 
     /// # Safety
     /// did you `bind()` this texture yet?
     pub unsafe fn generate_mipmap(&self, target: GLenum) -> Result<(), GLErrorWrapper> {
         unsafe { gl::GenerateMipmap(target) };
-        explode_if_gl_error()
+        explode_if_gl_error()?;
+        self.record_mipmaps(target);
+        Ok(())
     }
 }
 
 impl Drop for Texture {
     fn drop(&mut self) {
         match self.0 {
-            Ownership::Owned(handle) => unsafe { gl::DeleteTextures(1, &handle) },
+            Ownership::Owned(handle) => {
+                unsafe { gl::DeleteTextures(1, &handle) };
+                gpu_memory::forget_texture(handle);
+            }
             Ownership::Borrowed(_) | Ownership::None => {}
         }
     }
@@ -843,10 +1360,22 @@ pub const unsafe fn gl_offset_for<T>(count: GLsizei) -> *const c_void {
     (count * size_of::<T>() as GLsizei) as *const c_void
 }
 
+/// GLES 2 has no sized internal formats (RGBA8, DEPTH_COMPONENT24, ...); the internal format
+/// has to be `format` itself
+pub(crate) fn internal_format_for(internal_format: GLint, format: GLenum) -> GLint {
+    let caps = gl_caps();
+    if caps.gles && caps.major < 3 {
+        format as GLint
+    } else {
+        internal_format
+    }
+}
+
 pub fn bytes_per_pixel<T: GLBufferType>(format: GLenum) -> Result<usize, GLErrorWrapper> {
     let alpha = match format {
         gl::RGB => 3,
         gl::RED => 1,
+        gl::RG => 2,
         gl::RGBA => 4,
         _ => {
             // there are so many variants I am missing ...
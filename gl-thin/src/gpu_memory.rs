@@ -0,0 +1,234 @@
+//! Accounting of the GPU memory allocated through gl-thin's [crate::gl_helper::Texture]s and
+//! [crate::gl_helper::Buffer]s, and a [GpuBudget] to decide what streamable assets to let go
+//! of when there is too much of it.  The sizes are estimates: drivers pad and compress as they
+//! like, and memory allocated by raw GL calls (or the OpenXR swapchains) is not seen.
+
+use gl::types::{GLenum, GLint, GLuint};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::sync::Mutex;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GpuMemoryTotals {
+    pub texture_bytes: u64,
+    pub buffer_bytes: u64,
+    pub texture_count: usize,
+    pub buffer_count: usize,
+}
+
+impl GpuMemoryTotals {
+    pub fn total_bytes(&self) -> u64 {
+        self.texture_bytes + self.buffer_bytes
+    }
+}
+
+/// (image target, mip level) → bytes; a cube map has six image targets
+type TextureImages = BTreeMap<(GLenum, GLint), u64>;
+
+struct Registry {
+    textures: BTreeMap<GLuint, TextureImages>,
+    buffers: BTreeMap<GLuint, u64>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    textures: BTreeMap::new(),
+    buffers: BTreeMap::new(),
+});
+
+/// what gl-thin currently has allocated
+pub fn gpu_memory_totals() -> GpuMemoryTotals {
+    let registry = REGISTRY.lock().unwrap();
+    GpuMemoryTotals {
+        texture_bytes: registry.textures.values().flat_map(|t| t.values()).sum(),
+        buffer_bytes: registry.buffers.values().sum(),
+        texture_count: registry.textures.len(),
+        buffer_count: registry.buffers.len(),
+    }
+}
+
+/// the bytes of one mip level, after TexImage2D
+pub(crate) fn record_texture_image(
+    texture: GLuint,
+    target: GLenum,
+    level: GLint,
+    internal_format: GLint,
+    width: GLint,
+    height: GLint,
+) {
+    let bytes = width.max(0) as u64 * height.max(0) as u64 * texel_bytes(internal_format);
+    let mut registry = REGISTRY.lock().unwrap();
+    registry
+        .textures
+        .entry(texture)
+        .or_default()
+        .insert((target, level), bytes);
+}
+
+/// after GenerateMipmap, which adds about a third on top of the base level
+pub(crate) fn record_mipmaps(texture: GLuint, target: GLenum) {
+    let mut registry = REGISTRY.lock().unwrap();
+    let Some(levels) = registry.textures.get_mut(&texture) else {
+        return;
+    };
+    let images: Vec<GLenum> = levels
+        .keys()
+        .filter(|(_, level)| *level == 0)
+        .map(|(image, _)| *image)
+        .filter(|image| *image == target || is_cube_face(*image))
+        .collect();
+    for image in images {
+        let base = levels[&(image, 0)];
+        levels.retain(|(i, level), _| *i != image || *level == 0);
+        levels.insert((image, 1), base / 3);
+    }
+}
+
+pub(crate) fn forget_texture(texture: GLuint) {
+    REGISTRY.lock().unwrap().textures.remove(&texture);
+}
+
+pub(crate) fn record_buffer(buffer: GLuint, bytes: u64) {
+    REGISTRY.lock().unwrap().buffers.insert(buffer, bytes);
+}
+
+pub(crate) fn forget_buffer(buffer: GLuint) {
+    REGISTRY.lock().unwrap().buffers.remove(&buffer);
+}
+
+fn is_cube_face(target: GLenum) -> bool {
+    (gl::TEXTURE_CUBE_MAP_POSITIVE_X..=gl::TEXTURE_CUBE_MAP_NEGATIVE_Z).contains(&target)
+}
+
+/// Estimated bytes per texel of a TexImage2D `internal_format`.  Three-channel formats
+/// count as four, because that is how most GPUs store them.
+fn texel_bytes(internal_format: GLint) -> u64 {
+    match internal_format as GLenum {
+        gl::R8 | gl::RED | gl::ALPHA => 1,
+        gl::RG8 | gl::RG | gl::DEPTH_COMPONENT16 | gl::R16F => 2,
+        gl::RGBA16F | gl::RGB16F | gl::RG32F => 8,
+        gl::RGBA32F | gl::RGB32F => 16,
+        // RGBA8, SRGB8_ALPHA8, RGB8, DEPTH_COMPONENT24, DEPTH24_STENCIL8, R32F, ...
+        _ => 4,
+    }
+}
+
+//
+
+/// What a [GpuBudget] reports to its callback
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BudgetEvent<K> {
+    /// `key` should be freed; [GpuBudget::enforce] already forgot it
+    Evicted { key: K, bytes: u64 },
+    /// still over budget after evicting everything that was not in use this frame
+    OverBudget { used: u64, budget: u64 },
+}
+
+type BudgetCallback<K> = Box<dyn FnMut(&BudgetEvent<K>)>;
+
+struct BudgetEntry {
+    bytes: u64,
+    last_used: u64,
+}
+
+/// Least-recently-used eviction of streamable assets, keyed by whatever the owner uses to
+/// find them again (a chunk coordinate, a file name, ...).  The owner registers each asset
+/// with its size, touches the ones it uses every frame, and frees what [GpuBudget::enforce]
+/// returns.  Assets touched in the current frame are never evicted.
+pub struct GpuBudget<K> {
+    pub budget_bytes: u64,
+    entries: HashMap<K, BudgetEntry>,
+    frame: u64,
+    callback: Option<BudgetCallback<K>>,
+}
+
+impl<K: Clone + Eq + Hash> GpuBudget<K> {
+    pub fn new(budget_bytes: u64) -> Self {
+        Self {
+            budget_bytes,
+            entries: HashMap::new(),
+            frame: 0,
+            callback: None,
+        }
+    }
+
+    /// to hear about evictions and overruns, e.g. to drop quality settings
+    pub fn with_callback(mut self, callback: impl FnMut(&BudgetEvent<K>) + 'static) -> Self {
+        self.callback = Some(Box::new(callback));
+        self
+    }
+
+    /// start a new frame for [GpuBudget::touch]
+    pub fn begin_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// a newly loaded asset, counted as used this frame
+    pub fn register(&mut self, key: K, bytes: u64) {
+        let last_used = self.frame;
+        self.entries.insert(key, BudgetEntry { bytes, last_used });
+    }
+
+    pub fn touch(&mut self, key: &K) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.last_used = self.frame;
+        }
+    }
+
+    /// the owner freed it on its own
+    pub fn remove(&mut self, key: &K) {
+        self.entries.remove(key);
+    }
+
+    /// the bytes of the registered assets
+    pub fn used_bytes(&self) -> u64 {
+        self.entries.values().map(|entry| entry.bytes).sum()
+    }
+
+    /// If `used` (e.g. [GpuMemoryTotals::total_bytes], or [GpuBudget::used_bytes]) is over
+    /// [GpuBudget::budget_bytes], pick the least recently used assets to free until it would
+    /// not be.
+    pub fn enforce(&mut self, used: u64) -> Vec<K> {
+        let mut used = used;
+        let mut rval = vec![];
+        if used <= self.budget_bytes {
+            return rval;
+        }
+
+        let mut candidates: Vec<(u64, K)> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.last_used < self.frame)
+            .map(|(key, entry)| (entry.last_used, key.clone()))
+            .collect();
+        candidates.sort_by_key(|(last_used, _)| *last_used);
+
+        for (_, key) in candidates {
+            if used <= self.budget_bytes {
+                break;
+            }
+            let Some(entry) = self.entries.remove(&key) else {
+                continue;
+            };
+            used = used.saturating_sub(entry.bytes);
+            self.notify(BudgetEvent::Evicted {
+                key: key.clone(),
+                bytes: entry.bytes,
+            });
+            rval.push(key);
+        }
+
+        if used > self.budget_bytes {
+            self.notify(BudgetEvent::OverBudget {
+                used,
+                budget: self.budget_bytes,
+            });
+        }
+        rval
+    }
+
+    fn notify(&mut self, event: BudgetEvent<K>) {
+        if let Some(callback) = &mut self.callback {
+            callback(&event);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! The user's hands as skinned meshes.  XR_FB_hand_tracking_mesh hands out a mesh of each
+//! hand, modeled around a bind pose of the 26 XR_EXT_hand_tracking joints; locating the joints
+//! every frame says how to bend it to match the real hand.
+
+use crate::errors::{Wrappable, XrErrorWrapped};
+use crate::linear::{
+    xr_matrix4x4f_create_translation_rotation_scale, xr_matrix4x4f_invert_rigid_body, XrMatrix4x4f,
+    XrVector3f,
+};
+use openxr::{
+    Graphics, Hand, HandJointLocations, HandTracker, Instance, Session, Space, SpaceLocationFlags,
+};
+use openxr_sys::{
+    HandJointEXT, HandTrackingMeshFB, Posef, Time, Vector2f, Vector3f, Vector4f, Vector4sFB,
+};
+use std::ptr::null_mut;
+
+/// One hand's mesh, in the space of its joints' bind poses
+pub struct HandMesh {
+    pub positions: Vec<[f32; 3]>,
+    /// the four joints that move each vertex, as indices into the joint list
+    pub joints: Vec<[u16; 4]>,
+    /// how much each of [HandMesh::joints] moves the vertex, summing to 1
+    pub weights: Vec<[f32; 4]>,
+    pub indices: Vec<u16>,
+    /// from the mesh's space into each joint's, at the bind pose
+    inverse_bind: Vec<XrMatrix4x4f>,
+}
+
+impl HandMesh {
+    /// Ask the runtime for `tracker`'s mesh.  Needs XR_FB_hand_tracking_mesh, see
+    /// [crate::openxr_helpers::OpenXRComponent::supports_hand_meshes].
+    pub fn fetch(instance: &Instance, tracker: &HandTracker) -> Result<Self, XrErrorWrapped> {
+        let Some(ext) = instance.exts().fb_hand_tracking_mesh.as_ref() else {
+            return Err(XrErrorWrapped::simple(
+                "XR_FB_hand_tracking_mesh is not enabled",
+            ));
+        };
+
+        // the usual two calls: how big, then fill
+        let mut mesh = empty_mesh();
+        succeeded(unsafe { (ext.get_hand_mesh)(tracker.as_raw(), &mut mesh) })
+            .annotate_if_err(Some(instance), "failed to size the hand mesh")?;
+
+        let joint_count = mesh.joint_count_output as usize;
+        let vertex_count = mesh.vertex_count_output as usize;
+        let index_count = mesh.index_count_output as usize;
+        let mut bind_poses = vec![Posef::default(); joint_count];
+        let mut radii = vec![0.0f32; joint_count];
+        let mut parents = vec![HandJointEXT::PALM; joint_count];
+        let mut positions = vec![Vector3f::default(); vertex_count];
+        let mut normals = vec![Vector3f::default(); vertex_count];
+        let mut uvs = vec![Vector2f::default(); vertex_count];
+        let mut blend_indices = vec![Vector4sFB::default(); vertex_count];
+        let mut blend_weights = vec![Vector4f::default(); vertex_count];
+        let mut indices = vec![0i16; index_count];
+
+        let mut mesh = HandTrackingMeshFB {
+            joint_capacity_input: joint_count as u32,
+            joint_bind_poses: bind_poses.as_mut_ptr(),
+            joint_radii: radii.as_mut_ptr(),
+            joint_parents: parents.as_mut_ptr(),
+            vertex_capacity_input: vertex_count as u32,
+            vertex_positions: positions.as_mut_ptr(),
+            vertex_normals: normals.as_mut_ptr(),
+            vertex_uvs: uvs.as_mut_ptr(),
+            vertex_blend_indices: blend_indices.as_mut_ptr(),
+            vertex_blend_weights: blend_weights.as_mut_ptr(),
+            index_capacity_input: index_count as u32,
+            indices: indices.as_mut_ptr(),
+            ..empty_mesh()
+        };
+        succeeded(unsafe { (ext.get_hand_mesh)(tracker.as_raw(), &mut mesh) })
+            .annotate_if_err(Some(instance), "failed to get the hand mesh")?;
+
+        Ok(Self {
+            positions: positions.iter().map(|p| [p.x, p.y, p.z]).collect(),
+            joints: blend_indices
+                .iter()
+                .map(|j| [j.x, j.y, j.z, j.w].map(|i| i.max(0) as u16))
+                .collect(),
+            weights: blend_weights.iter().map(|w| [w.x, w.y, w.z, w.w]).collect(),
+            indices: indices.iter().map(|&i| i as u16).collect(),
+            inverse_bind: bind_poses
+                .iter()
+                .map(|pose| xr_matrix4x4f_invert_rigid_body(&pose_matrix(pose)))
+                .collect(),
+        })
+    }
+
+    /// Each joint's move from the bind pose to where `joints` found it, for skinning.
+    /// `None` unless every joint was located.
+    pub fn joint_matrices(&self, joints: &HandJointLocations) -> Option<Vec<XrMatrix4x4f>> {
+        let valid = SpaceLocationFlags::POSITION_VALID | SpaceLocationFlags::ORIENTATION_VALID;
+        joints
+            .iter()
+            .zip(&self.inverse_bind)
+            .map(|(joint, inverse_bind)| {
+                joint
+                    .location_flags
+                    .contains(valid)
+                    .then(|| pose_matrix(&joint.pose) * *inverse_bind)
+            })
+            .collect()
+    }
+}
+
+/// the raw call's result, as the wrapped calls return it
+fn succeeded(result: openxr_sys::Result) -> Result<(), openxr_sys::Result> {
+    if result.into_raw() >= 0 {
+        Ok(())
+    } else {
+        Err(result)
+    }
+}
+
+fn empty_mesh() -> HandTrackingMeshFB {
+    HandTrackingMeshFB {
+        ty: HandTrackingMeshFB::TYPE,
+        next: null_mut(),
+        joint_capacity_input: 0,
+        joint_count_output: 0,
+        joint_bind_poses: null_mut(),
+        joint_radii: null_mut(),
+        joint_parents: null_mut(),
+        vertex_capacity_input: 0,
+        vertex_count_output: 0,
+        vertex_positions: null_mut(),
+        vertex_normals: null_mut(),
+        vertex_uvs: null_mut(),
+        vertex_blend_indices: null_mut(),
+        vertex_blend_weights: null_mut(),
+        index_capacity_input: 0,
+        index_count_output: 0,
+        indices: null_mut(),
+    }
+}
+
+/// from the space of a located `pose` into its base space
+pub(crate) fn pose_matrix(pose: &Posef) -> XrMatrix4x4f {
+    xr_matrix4x4f_create_translation_rotation_scale(
+        &pose.position.into(),
+        &pose.orientation.into(),
+        &XrVector3f::default_scale(),
+    )
+}
+
+//
+
+/// A hand tracker with its mesh
+pub struct HandMeshTracker {
+    pub hand: Hand,
+    pub tracker: HandTracker,
+    pub mesh: HandMesh,
+}
+
+impl HandMeshTracker {
+    pub fn new<G: Graphics>(
+        instance: &Instance,
+        session: &Session<G>,
+        hand: Hand,
+    ) -> Result<Self, XrErrorWrapped> {
+        let tracker = create_hand_tracker(instance, session, hand)?;
+        let mesh = HandMesh::fetch(instance, &tracker)?;
+        Ok(Self {
+            hand,
+            tracker,
+            mesh,
+        })
+    }
+
+    /// the skinning matrices at `time`, relative to `base`; `None` while the hand is not tracked
+    pub fn locate(
+        &self,
+        base: &Space,
+        time: Time,
+    ) -> Result<Option<Vec<XrMatrix4x4f>>, XrErrorWrapped> {
+        let joints = base
+            .locate_hand_joints(&self.tracker, time)
+            .annotate_if_err(None, "failed to locate hand joints")?;
+        Ok(joints.and_then(|joints| self.mesh.joint_matrices(&joints)))
+    }
+}
+
+/// `hand`'s XR_EXT_hand_tracking tracker, for [HandMeshTracker] and
+/// [crate::openxr_helpers::HandJointTracker]
+pub(crate) fn create_hand_tracker<G: Graphics>(
+    instance: &Instance,
+    session: &Session<G>,
+    hand: Hand,
+) -> Result<HandTracker, XrErrorWrapped> {
+    if instance.exts().ext_hand_tracking.is_none() {
+        return Err(XrErrorWrapped::simple(
+            "XR_EXT_hand_tracking is not enabled",
+        ));
+    }
+    let name = if hand == Hand::LEFT { "left" } else { "right" };
+    session.create_hand_tracker(hand).annotate_if_err(
+        Some(instance),
+        format!("failed to create {} hand tracker", name),
+    )
+}
@@ -1,6 +1,40 @@
+pub mod bvh;
+pub mod camera_relative;
+#[cfg(feature = "openxr")]
+pub mod composition_layers;
+#[cfg(feature = "openxr")]
+pub mod depth_layer;
+pub mod editable_mesh;
 pub mod errors;
+pub mod external_image;
+pub mod fallback;
+pub mod frame_arena;
+pub mod gl_caps;
+pub mod gl_counters;
 pub mod gl_fancy;
 pub mod gl_helper;
+pub mod gpu_memory;
+#[cfg(feature = "openxr")]
+pub mod hand_mesh;
 pub mod linear;
+pub mod msaa;
+pub mod multiview;
+pub mod navmesh;
 #[cfg(feature = "openxr")]
 pub mod openxr_helpers;
+#[cfg(feature = "openxr")]
+pub mod performance_settings;
+pub mod raycast;
+pub mod recipes;
+pub mod render_graph;
+pub mod render_queue;
+pub mod render_target;
+#[cfg(feature = "openxr")]
+pub mod space_warp;
+pub mod static_batch;
+pub mod swapchain_readback;
+pub mod texture_atlas;
+pub mod uniform_batch;
+pub mod watchdog;
+pub mod world_scale;
+pub mod yuv;
@@ -149,6 +149,14 @@ impl std::ops::Div<f32> for XrVector3f {
     }
 }
 
+impl std::ops::Mul<f32> for XrVector3f {
+    type Output = XrVector3f;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        XrVector3f::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
 //
 
 #[derive(Copy, Clone, Debug)]
@@ -399,6 +407,118 @@ pub fn xr_matrix4x4f_create_projection(
     }
 }
 
+/// Like [xr_matrix4x4f_create_projection], but the extents of the near plane are given in
+/// meters at `near_z` instead of as tangents, the way glFrustum takes them.  Handy for
+/// off-axis views, where the eye is not centered on what it looks through.
+#[allow(clippy::too_many_arguments)]
+pub fn xr_matrix4x4f_create_projection_frustum(
+    graphics_api: GraphicsAPI,
+    left: f32,
+    right: f32,
+    bottom: f32,
+    top: f32,
+    near_z: f32,
+    far_z: f32,
+) -> XrMatrix4x4f {
+    xr_matrix4x4f_create_projection(
+        graphics_api,
+        left / near_z,
+        right / near_z,
+        top / near_z,
+        bottom / near_z,
+        near_z,
+        far_z,
+    )
+}
+
+/// The view and projection of an eye at `eye` looking through a rectangular screen, given
+/// by three of its corners; the near plane is parallel to the screen however the eye is
+/// placed (Kooima's "generalized perspective projection").  Portals, CAVE walls and
+/// head-tracked displays use this.  Returns the product `projection * view`.
+#[allow(clippy::too_many_arguments)]
+pub fn xr_matrix4x4f_create_projection_screen(
+    graphics_api: GraphicsAPI,
+    eye: &XrVector3f,
+    lower_left: &XrVector3f,
+    lower_right: &XrVector3f,
+    upper_left: &XrVector3f,
+    near_z: f32,
+    far_z: f32,
+) -> XrMatrix4x4f {
+    // the screen's axes
+    let right = xr_vector3f_normalize(&(*lower_right - *lower_left));
+    let up = xr_vector3f_normalize(&(*upper_left - *lower_left));
+    let normal = xr_vector3f_normalize(&xr_vector3f_cross(&right, &up));
+
+    let to_lower_left = *lower_left - *eye;
+    let to_lower_right = *lower_right - *eye;
+    let to_upper_left = *upper_left - *eye;
+    let distance = -xr_vector3f_dot(&to_lower_left, &normal);
+    let scale = near_z / distance;
+    let projection = xr_matrix4x4f_create_projection_frustum(
+        graphics_api,
+        xr_vector3f_dot(&right, &to_lower_left) * scale,
+        xr_vector3f_dot(&right, &to_lower_right) * scale,
+        xr_vector3f_dot(&up, &to_lower_left) * scale,
+        xr_vector3f_dot(&up, &to_upper_left) * scale,
+        near_z,
+        far_z,
+    );
+
+    // world to screen-aligned axes, with the eye at the origin
+    #[rustfmt::skip]
+    let rotation: XrMatrix4x4f = [
+        right.x, up.x, normal.x, 0.0,
+        right.y, up.y, normal.y, 0.0,
+        right.z, up.z, normal.z, 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    ].into();
+    projection * rotation * xr_matrix4x4f_create_translation(-eye.x, -eye.y, -eye.z)
+}
+
+/// An orthographic projection of the box `left..right`, `bottom..top` (in view space) and
+/// `near_z..far_z` in front of the viewer, for shadow maps, minimaps and baking UI.  Like
+/// [xr_matrix4x4f_create_projection], `graphics_api` picks the clip space: Vulkan flips Y
+/// and maps depth to [0,1]; OpenGL (ES) maps it to [-1,1]; D3D / Metal to [0,1].
+#[allow(clippy::too_many_arguments)]
+pub fn xr_matrix4x4f_create_orthographic(
+    graphics_api: GraphicsAPI,
+    left: f32,
+    right: f32,
+    bottom: f32,
+    top: f32,
+    near_z: f32,
+    far_z: f32,
+) -> XrMatrix4x4f {
+    let width = right - left;
+    // positive Y down for Vulkan, up for everybody else
+    let (height, y_sum) = if graphics_api == GraphicsAPI::GraphicsVulkan {
+        (bottom - top, top + bottom)
+    } else {
+        (top - bottom, top + bottom)
+    };
+    // the depth of the near plane in clip space
+    let low_z = if graphics_api == GraphicsAPI::GraphicsOpenGL
+        || graphics_api == GraphicsAPI::GraphicsOpenGLES
+    {
+        -1.0
+    } else {
+        0.0
+    };
+    let depth = far_z - near_z;
+
+    let m0 = 2.0 / width;
+    let m5 = 2.0 / height;
+    let m10 = -(1.0 - low_z) / depth;
+    let m12 = -(right + left) / width;
+    let m13 = -y_sum / height;
+    let m14 = (low_z * depth - (1.0 - low_z) * near_z) / depth;
+    [
+        m0, 0.0, 0.0, 0.0, 0.0, m5, 0.0, 0.0, 0.0, 0.0, m10, 0.0, m12, m13, m14, 1.0,
+    ]
+    .into()
+}
+
 pub fn xr_matrix4x4f_create_translation_rotation_scale(
     translation: &XrVector3f,
     rotation: &XrQuaternionf,
@@ -527,6 +647,44 @@ pub fn xr_matrix4x4f_invert_rigid_body(src: &XrMatrix4x4f) -> XrMatrix4x4f {
     .into()
 }
 
+/// determinant of the 3x3 matrix made of rows `r` and columns `c` of `m`
+fn xr_matrix4x4f_minor(m: &XrMatrix4x4f, r: [usize; 3], c: [usize; 3]) -> f32 {
+    let at = |row: usize, col: usize| m.m[4 * row + col];
+    at(r[0], c[0]) * (at(r[1], c[1]) * at(r[2], c[2]) - at(r[2], c[1]) * at(r[1], c[2]))
+        - at(r[0], c[1]) * (at(r[1], c[0]) * at(r[2], c[2]) - at(r[2], c[0]) * at(r[1], c[2]))
+        + at(r[0], c[2]) * (at(r[1], c[0]) * at(r[2], c[1]) - at(r[2], c[0]) * at(r[1], c[1]))
+}
+
+/// Invert an arbitrary matrix (e.g. a projection).  Prefer [xr_matrix4x4f_invert_rigid_body]
+/// when it applies; it is cheaper and more precise.
+pub fn xr_matrix4x4f_invert(src: &XrMatrix4x4f) -> XrMatrix4x4f {
+    let minor = |r, c| xr_matrix4x4f_minor(src, r, c);
+    let rcp_det = 1.0
+        / (src.m[0] * minor([1, 2, 3], [1, 2, 3]) - src.m[1] * minor([1, 2, 3], [0, 2, 3])
+            + src.m[2] * minor([1, 2, 3], [0, 1, 3])
+            - src.m[3] * minor([1, 2, 3], [0, 1, 2]));
+
+    [
+        minor([1, 2, 3], [1, 2, 3]) * rcp_det,
+        -minor([0, 2, 3], [1, 2, 3]) * rcp_det,
+        minor([0, 1, 3], [1, 2, 3]) * rcp_det,
+        -minor([0, 1, 2], [1, 2, 3]) * rcp_det,
+        -minor([1, 2, 3], [0, 2, 3]) * rcp_det,
+        minor([0, 2, 3], [0, 2, 3]) * rcp_det,
+        -minor([0, 1, 3], [0, 2, 3]) * rcp_det,
+        minor([0, 1, 2], [0, 2, 3]) * rcp_det,
+        minor([1, 2, 3], [0, 1, 3]) * rcp_det,
+        -minor([0, 2, 3], [0, 1, 3]) * rcp_det,
+        minor([0, 1, 3], [0, 1, 3]) * rcp_det,
+        -minor([0, 1, 2], [0, 1, 3]) * rcp_det,
+        -minor([1, 2, 3], [0, 1, 2]) * rcp_det,
+        minor([0, 2, 3], [0, 1, 2]) * rcp_det,
+        -minor([0, 1, 3], [0, 1, 2]) * rcp_det,
+        minor([0, 1, 2], [0, 1, 2]) * rcp_det,
+    ]
+    .into()
+}
+
 pub fn xr_matrix4x4f_transform_vector3f(m: &XrMatrix4x4f, v: &XrVector3f) -> XrVector3f {
     let w = m.m[3] * v.x + m.m[7] * v.y + m.m[11] * v.z + m.m[15];
     if false {
@@ -548,3 +706,49 @@ pub fn xr_matrix4x4f_transform_vector3f(m: &XrMatrix4x4f, v: &XrVector3f) -> XrV
     let z = (m.m[2] * v.x + m.m[6] * v.y + m.m[10] * v.z + m.m[14]) * rcp_w;
     XrVector3f { x, y, z }
 }
+
+pub fn xr_vector3f_dot(a: &XrVector3f, b: &XrVector3f) -> f32 {
+    a.x * b.x + a.y * b.y + a.z * b.z
+}
+
+pub fn xr_vector3f_cross(a: &XrVector3f, b: &XrVector3f) -> XrVector3f {
+    XrVector3f {
+        x: a.y * b.z - a.z * b.y,
+        y: a.z * b.x - a.x * b.z,
+        z: a.x * b.y - a.y * b.x,
+    }
+}
+
+pub fn xr_vector3f_normalize(v: &XrVector3f) -> XrVector3f {
+    let length_rcp = 1.0 / xr_vector3f_dot(v, v).sqrt();
+    XrVector3f {
+        x: v.x * length_rcp,
+        y: v.y * length_rcp,
+        z: v.z * length_rcp,
+    }
+}
+
+pub fn xr_vector3f_lerp(a: &XrVector3f, b: &XrVector3f, fraction: f32) -> XrVector3f {
+    XrVector3f {
+        x: a.x + fraction * (b.x - a.x),
+        y: a.y + fraction * (b.y - a.y),
+        z: a.z + fraction * (b.z - a.z),
+    }
+}
+
+pub fn xr_quaternionf_lerp(a: &XrQuaternionf, b: &XrQuaternionf, fraction: f32) -> XrQuaternionf {
+    let s = a.x * b.x + a.y * b.y + a.z * b.z + a.w * b.w;
+    let fa = 1.0 - fraction;
+    let fb = if s < 0.0 { -fraction } else { fraction };
+    let x = a.x * fa + b.x * fb;
+    let y = a.y * fa + b.y * fb;
+    let z = a.z * fa + b.z * fb;
+    let w = a.w * fa + b.w * fb;
+    let length_rcp = 1.0 / (x * x + y * y + z * z + w * w).sqrt();
+    XrQuaternionf::new(
+        x * length_rcp,
+        y * length_rcp,
+        z * length_rcp,
+        w * length_rcp,
+    )
+}
@@ -0,0 +1,366 @@
+//! Multisampled rendering, and getting the samples into an sRGB swapchain without averaging
+//! them in the wrong space.  The samples of an edge pixel have to be averaged as linear light;
+//! averaging the sRGB-encoded values darkens every antialiased edge (half white over black
+//! comes out 128 instead of 188).  There are two ways to resolve, and which is right depends on
+//! both formats:
+//!
+//! * [ResolveMethod::Blit] with glBlitFramebuffer, which only resolves between identical
+//!   formats.  Between two sRGB buffers GLES 3 decodes the samples, averages and encodes again;
+//!   desktop GL only does with FRAMEBUFFER_SRGB enabled, which [MsaaTarget::resolve_blit]
+//!   takes care of.  It is the cheap path, and on tiled GPUs often free.
+//! * [ResolveMethod::Shader], a full-screen pass that texelFetches every sample of a
+//!   multisampled texture (GLES 3.1).  Fetching from an sRGB texture decodes, so the average is
+//!   linear whatever the source stores, and writing into an sRGB destination encodes.
+//!
+//! [ResolvePlan] picks one and says what space each step works in.  Drivers have gotten the
+//! blit wrong before, so check the result on the device (the example app's MsaaResolver does)
+//! and fall back to [ResolvePlan::shader] if it comes out dark.
+
+use crate::gl_fancy::GPUState;
+use crate::gl_helper::{
+    explode_if_gl_error, is_gles, FrameBuffer, GLErrorWrapper, RenderBuffer, Texture,
+};
+use crate::render_target::ColorFormat;
+use gl::types::{GLenum, GLint, GLsizei};
+use std::fmt::{Display, Formatter};
+
+/// How the values in a buffer are encoded
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// stored as they are; blending and averaging them is correct
+    Linear,
+    /// sRGB-encoded; the GPU decodes them when sampling or blending and encodes when writing
+    Srgb,
+}
+
+impl ColorSpace {
+    /// of a buffer with `internal_format`, e.g. a swapchain's
+    pub fn of(internal_format: GLenum) -> Self {
+        match internal_format {
+            gl::SRGB8_ALPHA8 | gl::SRGB8 => ColorSpace::Srgb,
+            _ => ColorSpace::Linear,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResolveMethod {
+    Blit,
+    Shader,
+}
+
+/// How the samples get from an [MsaaTarget] into the destination, and the space of each step
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ResolvePlan {
+    pub method: ResolveMethod,
+    /// how the samples are stored
+    pub stored: ColorSpace,
+    /// what the samples are averaged as; always [ColorSpace::Linear] for the plans made here
+    pub averaged: ColorSpace,
+    /// how the destination stores the result
+    pub written: ColorSpace,
+}
+
+impl ResolvePlan {
+    /// A blit if `source` is the destination's format, otherwise a shader resolve if the
+    /// device can sample multisampled textures.  Fails if it can not.
+    pub fn choose(source: ColorFormat, destination_format: GLenum) -> Result<Self, GLErrorWrapper> {
+        Self::choose_for(source, destination_format, shader_resolve_supported())
+    }
+
+    /// Resolve with a shader even where a blit would do.  Fails without GLES 3.1 (or
+    /// ARB_texture_multisample).
+    pub fn shader(source: ColorFormat, destination_format: GLenum) -> Result<Self, GLErrorWrapper> {
+        if !shader_resolve_supported() {
+            return Err(unsupported(source, destination_format));
+        }
+        Ok(Self::new(ResolveMethod::Shader, source, destination_format))
+    }
+
+    /// [ResolvePlan::choose] on a device where [shader_resolve_supported] says
+    /// `shader_supported`
+    fn choose_for(
+        source: ColorFormat,
+        destination_format: GLenum,
+        shader_supported: bool,
+    ) -> Result<Self, GLErrorWrapper> {
+        if source.internal_format() == destination_format {
+            Ok(Self::new(ResolveMethod::Blit, source, destination_format))
+        } else if shader_supported {
+            Ok(Self::new(ResolveMethod::Shader, source, destination_format))
+        } else {
+            Err(unsupported(source, destination_format))
+        }
+    }
+
+    fn new(method: ResolveMethod, source: ColorFormat, destination_format: GLenum) -> Self {
+        Self {
+            method,
+            stored: ColorSpace::of(source.internal_format()),
+            averaged: ColorSpace::Linear,
+            written: ColorSpace::of(destination_format),
+        }
+    }
+}
+
+fn unsupported(source: ColorFormat, destination_format: GLenum) -> GLErrorWrapper {
+    GLErrorWrapper::with_message2(format!(
+        "can not resolve {:?} into format 0x{:x}: the formats differ and multisampled textures are not supported",
+        source, destination_format
+    ))
+}
+
+impl Display for ResolvePlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} resolve: samples stored {:?}, averaged {:?}, written {:?}",
+            self.method, self.stored, self.averaged, self.written
+        )
+    }
+}
+
+/// glTexStorage2DMultisample and texelFetch on a sampler2DMS
+pub fn shader_resolve_supported() -> bool {
+    gl::TexStorage2DMultisample::is_loaded()
+}
+
+/// the most samples a color buffer can have
+pub fn max_samples() -> Result<GLint, GLErrorWrapper> {
+    let mut rval = 0;
+    unsafe { gl::GetIntegerv(gl::MAX_SAMPLES, &mut rval) };
+    explode_if_gl_error()?;
+    Ok(rval)
+}
+
+enum MsaaColor {
+    Buffer(RenderBuffer),
+    /// TEXTURE_2D_MULTISAMPLE
+    Texture(Texture),
+}
+
+/// A multisampled color+depth(+stencil) buffer to draw a view into, then resolve into the
+/// single-sampled destination as its [ResolvePlan] says.  The color is a renderbuffer for a
+/// blit and a multisampled texture for the shader to fetch from; the depth is never resolved.
+pub struct MsaaTarget {
+    pub frame_buffer: FrameBuffer,
+    color: MsaaColor,
+    /// DEPTH24_STENCIL8 if [MsaaTarget::stencil], DEPTH_COMPONENT24 otherwise
+    _depth: RenderBuffer,
+    pub stencil: bool,
+    pub samples: GLsizei,
+    pub format: ColorFormat,
+    pub plan: ResolvePlan,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl MsaaTarget {
+    /// `samples` is clamped to [max_samples]
+    pub fn new(
+        width: i32,
+        height: i32,
+        samples: GLsizei,
+        format: ColorFormat,
+        plan: ResolvePlan,
+        stencil: bool,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        if !format.is_renderable() {
+            return Err(GLErrorWrapper::with_message2(format!(
+                "{:?} color buffers are not supported",
+                format
+            )));
+        }
+        let samples = samples.clamp(1, max_samples()?.max(1));
+        let internal_format = format.internal_format();
+
+        let color = match plan.method {
+            ResolveMethod::Blit => {
+                MsaaColor::Buffer(RenderBuffer::new(samples, internal_format, width, height)?)
+            }
+            ResolveMethod::Shader => {
+                let texture = Texture::new()?;
+                let _bound = texture.bound(gl::TEXTURE_2D_MULTISAMPLE, gpu_state)?;
+                unsafe {
+                    gl::TexStorage2DMultisample(
+                        gl::TEXTURE_2D_MULTISAMPLE,
+                        samples,
+                        internal_format,
+                        width,
+                        height,
+                        gl::TRUE,
+                    )
+                };
+                explode_if_gl_error()?;
+                MsaaColor::Texture(texture)
+            }
+        };
+        let (depth_format, depth_attachment) = if stencil {
+            (gl::DEPTH24_STENCIL8, gl::DEPTH_STENCIL_ATTACHMENT)
+        } else {
+            (gl::DEPTH_COMPONENT24, gl::DEPTH_ATTACHMENT)
+        };
+        let depth = RenderBuffer::new(samples, depth_format, width, height)?;
+
+        let frame_buffer = FrameBuffer::new()?;
+        frame_buffer.bind()?;
+        match &color {
+            MsaaColor::Buffer(buffer) => buffer.attach(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0)?,
+            MsaaColor::Texture(texture) => texture.attach(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D_MULTISAMPLE,
+                0,
+            )?,
+        }
+        depth.attach(gl::FRAMEBUFFER, depth_attachment)?;
+        FrameBuffer::check_status(gl::DRAW_FRAMEBUFFER)?;
+
+        Ok(Self {
+            frame_buffer,
+            color,
+            _depth: depth,
+            stencil,
+            samples,
+            format,
+            plan,
+            width,
+            height,
+        })
+    }
+
+    /// bind for drawing and set the viewport to cover the whole target
+    pub fn bind(&self) -> Result<(), GLErrorWrapper> {
+        self.frame_buffer.bind()?;
+
+        unsafe { gl::Viewport(0, 0, self.width as GLsizei, self.height as GLsizei) };
+        explode_if_gl_error()?;
+
+        if gl::DrawBuffer::is_loaded() {
+            unsafe { gl::DrawBuffer(gl::COLOR_ATTACHMENT0) };
+            explode_if_gl_error()?;
+        }
+        Ok(())
+    }
+
+    /// the TEXTURE_2D_MULTISAMPLE color for a [ResolveMethod::Shader] plan to fetch from
+    pub fn color_texture(&self) -> Option<&Texture> {
+        match &self.color {
+            MsaaColor::Texture(texture) => Some(texture),
+            MsaaColor::Buffer(_) => None,
+        }
+    }
+
+    /// Resolve into the bound draw framebuffer, which must be the same size and, for a
+    /// [ResolveMethod::Blit] plan, the same format.
+    pub fn resolve_blit(&self) -> Result<(), GLErrorWrapper> {
+        if self.plan.method != ResolveMethod::Blit {
+            return Err(GLErrorWrapper::with_message2(format!(
+                "can not blit a target planned for a {:?} resolve",
+                self.plan.method
+            )));
+        }
+        self.frame_buffer.bind_read()?;
+        with_srgb_writes(self.plan.written, || {
+            unsafe {
+                gl::BlitFramebuffer(
+                    0,
+                    0,
+                    self.width,
+                    self.height,
+                    0,
+                    0,
+                    self.width,
+                    self.height,
+                    gl::COLOR_BUFFER_BIT,
+                    gl::NEAREST,
+                );
+            }
+            explode_if_gl_error()
+        })
+    }
+
+    /// Once resolved, none of the samples are needed; drop them before a tiled GPU writes
+    /// them back to memory.
+    pub fn invalidate(&self) -> Result<(), GLErrorWrapper> {
+        let depth_attachment = if self.stencil {
+            gl::DEPTH_STENCIL_ATTACHMENT
+        } else {
+            gl::DEPTH_ATTACHMENT
+        };
+        self.frame_buffer
+            .invalidate(&[gl::COLOR_ATTACHMENT0, depth_attachment])
+    }
+}
+
+/// Run `draw` with desktop GL's FRAMEBUFFER_SRGB enabled if `written` is
+/// [ColorSpace::Srgb], so the writes (and a blit's reads) are converted the way GLES always
+/// converts them.  Just runs `draw` on GLES.
+pub fn with_srgb_writes<T>(
+    written: ColorSpace,
+    draw: impl FnOnce() -> Result<T, GLErrorWrapper>,
+) -> Result<T, GLErrorWrapper> {
+    if is_gles() || written != ColorSpace::Srgb {
+        return draw();
+    }
+    unsafe { gl::Enable(gl::FRAMEBUFFER_SRGB) };
+    let result = draw();
+    unsafe { gl::Disable(gl::FRAMEBUFFER_SRGB) };
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_format_blits() {
+        for (source, destination) in [
+            (ColorFormat::Srgb8Alpha8, gl::SRGB8_ALPHA8),
+            (ColorFormat::Rgba8, gl::RGBA8),
+        ] {
+            for shader_supported in [false, true] {
+                let plan = ResolvePlan::choose_for(source, destination, shader_supported).unwrap();
+                assert_eq!(plan.method, ResolveMethod::Blit, "{:?}", source);
+            }
+        }
+        let plan =
+            ResolvePlan::choose_for(ColorFormat::Srgb8Alpha8, gl::SRGB8_ALPHA8, false).unwrap();
+        assert_eq!(plan.stored, ColorSpace::Srgb);
+        assert_eq!(plan.averaged, ColorSpace::Linear);
+        assert_eq!(plan.written, ColorSpace::Srgb);
+    }
+
+    #[test]
+    fn different_formats_resolve_in_a_shader() {
+        let plan = ResolvePlan::choose_for(ColorFormat::Rgba16F, gl::SRGB8_ALPHA8, true).unwrap();
+        assert_eq!(
+            plan,
+            ResolvePlan {
+                method: ResolveMethod::Shader,
+                stored: ColorSpace::Linear,
+                averaged: ColorSpace::Linear,
+                written: ColorSpace::Srgb,
+            }
+        );
+        let plan = ResolvePlan::choose_for(ColorFormat::Srgb8Alpha8, gl::RGBA8, true).unwrap();
+        assert_eq!(plan.method, ResolveMethod::Shader);
+        assert_eq!(plan.stored, ColorSpace::Srgb);
+        assert_eq!(plan.written, ColorSpace::Linear);
+    }
+
+    #[test]
+    fn different_formats_without_shader_support_fail() {
+        let e = ResolvePlan::choose_for(ColorFormat::Rgba8, gl::SRGB8_ALPHA8, false).unwrap_err();
+        assert!(e.to_string().contains("formats differ"), "{}", e);
+    }
+
+    #[test]
+    fn color_space_of_formats() {
+        assert_eq!(ColorSpace::of(gl::SRGB8_ALPHA8), ColorSpace::Srgb);
+        assert_eq!(ColorSpace::of(gl::SRGB8), ColorSpace::Srgb);
+        assert_eq!(ColorSpace::of(gl::RGBA8), ColorSpace::Linear);
+        assert_eq!(ColorSpace::of(gl::RGBA16F), ColorSpace::Linear);
+    }
+}
@@ -0,0 +1,503 @@
+//! GL_OVR_multiview2: both eyes drawn by the same draw calls, into the two layers of a
+//! texture array, instead of the whole scene being submitted once per eye.  That roughly
+//! halves the CPU and driver cost of a frame.
+//!
+//! The shaders are written for one view, so after [enable_shader_variants] every
+//! [Program::compile] also builds a multiview variant from the same sources (see
+//! [vertex_variant]), which [Program::use_] switches to while a [MultiviewFrameBuffer] is
+//! bound.  Each draw still gets the first eye's matrices; the variant moves its
+//! `gl_Position` into the other eye with a correction from [MultiviewFrameBuffer::prepare_to_draw].
+//! Anything a shader works out from the eye position itself (specular highlights, billboards
+//! turning to face the viewer) is as seen from the first eye in both.  Draws that are
+//! already in clip space go through [in_screen_space], which turns the correction off.
+
+use crate::gl_caps::gl_caps;
+use crate::gl_fancy::GPUState;
+use crate::gl_helper::{
+    explode_if_gl_error, has_extension, FrameBuffer, GLErrorWrapper, Program, Texture,
+};
+use crate::linear::{xr_matrix4x4f_identity, xr_matrix4x4f_invert, XrMatrix4x4f};
+use gl::types::{GLchar, GLenum, GLint, GLsizei, GLsizeiptr, GLuint};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::ffi::{c_void, CString};
+use std::mem::{size_of, MaybeUninit};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// eyes drawn by each draw call
+pub const VIEW_COUNT: usize = 2;
+
+/// the uniform buffer binding point the variants read the eye corrections from; GLES 3 has
+/// at least 24
+pub const UNIFORM_BINDING: GLuint = 15;
+
+const BLOCK_NAME: &str = "BobMultiview";
+/// what a fragment shader's gl_FragColor is renamed to
+const FRAG_COLOR: &str = "bob_FragColor";
+/// what a vertex shader's own main() is renamed to, so the variant can wrap it
+const SINGLE_VIEW_MAIN: &str = "bob_single_view_main";
+
+/// see [enable_shader_variants]
+static SHADER_VARIANTS: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// the pass's uniform buffers, between [MultiviewFrameBuffer::prepare_to_draw] and
+    /// [MultiviewFrameBuffer::finish_drawing]
+    static PASS: Cell<Option<PassBuffers>> = const { Cell::new(None) };
+}
+
+/// the `BobMultiview` blocks of a [MultiviewFrameBuffer]
+#[derive(Copy, Clone)]
+struct PassBuffers {
+    corrections: GLuint,
+    /// every eye's correction is the identity, see [in_screen_space]
+    identity: GLuint,
+}
+
+type FramebufferTextureMultiviewFn = unsafe extern "C" fn(
+    target: GLenum,
+    attachment: GLenum,
+    texture: GLuint,
+    level: GLint,
+    base_view_index: GLint,
+    num_views: GLsizei,
+);
+
+/// whether the current context can draw both eyes at once
+pub fn is_supported() -> bool {
+    gl_caps().major >= 3
+        && has_extension("GL_OVR_multiview2")
+        && framebuffer_texture_multiview().is_ok()
+}
+
+/// Compile a multiview variant of every program from now on.  Call before creating the
+/// shaders that draw the eyes; the ones made earlier stay single view.
+pub fn enable_shader_variants() {
+    SHADER_VARIANTS.store(true, Ordering::Relaxed);
+}
+
+pub fn shader_variants_enabled() -> bool {
+    SHADER_VARIANTS.load(Ordering::Relaxed)
+}
+
+/// whether a [MultiviewFrameBuffer] is being drawn into on this thread
+pub fn pass_active() -> bool {
+    PASS.with(Cell::get).is_some()
+}
+
+/// Run `draw` with the eyes' corrections off, for draws whose vertices are already in clip
+/// space (a full-screen camera background and the like) and so must land in the same place in
+/// every eye.  Outside a multiview pass this just runs `draw`.
+pub fn in_screen_space<T>(
+    draw: impl FnOnce() -> Result<T, GLErrorWrapper>,
+) -> Result<T, GLErrorWrapper> {
+    let Some(pass) = PASS.with(Cell::get) else {
+        return draw();
+    };
+    unsafe { gl::BindBufferBase(gl::UNIFORM_BUFFER, UNIFORM_BINDING, pass.identity) };
+    let drawn = draw();
+    unsafe { gl::BindBufferBase(gl::UNIFORM_BUFFER, UNIFORM_BINDING, pass.corrections) };
+    let rval = drawn?;
+    explode_if_gl_error()?;
+    Ok(rval)
+}
+
+/// A GLSL ES 1.00 (or 3.00) vertex shader turned into a GLSL ES 3.00 one that draws
+/// [VIEW_COUNT] views, moving `gl_Position` into each view with the matrices in the
+/// `BobMultiview` uniform block.
+pub fn vertex_variant(source: &str) -> String {
+    let (extensions, body) = essl3(source, true);
+    let body = replace_word(&body, "main", SINGLE_VIEW_MAIN);
+    format!(
+        "#version 300 es
+{extensions}#extension GL_OVR_multiview2 : require
+layout(num_views = {VIEW_COUNT}) in;
+layout(std140) uniform {BLOCK_NAME} {{
+    mat4 bob_eye_from_first[{VIEW_COUNT}];
+}};
+{body}
+void main()
+{{
+    {SINGLE_VIEW_MAIN}();
+    gl_Position = bob_eye_from_first[gl_ViewID_OVR] * gl_Position;
+}}
+"
+    )
+}
+
+/// A GLSL ES 1.00 (or 3.00) fragment shader as GLSL ES 3.00, to link with a [vertex_variant].
+pub fn fragment_variant(source: &str) -> String {
+    let (extensions, body) = essl3(source, false);
+    if !contains_word(&body, FRAG_COLOR) {
+        return format!("#version 300 es\n{}{}", extensions, body);
+    }
+    // after the precision statement, which an `out vec4` needs
+    let main = body
+        .lines()
+        .position(|line| line.trim_start().starts_with("void") && contains_word(line, "main"))
+        .unwrap_or(0);
+    let mut lines: Vec<&str> = body.lines().collect();
+    let declaration = format!("out vec4 {};", FRAG_COLOR);
+    lines.insert(main, &declaration);
+    format!("#version 300 es\n{}{}\n", extensions, lines.join("\n"))
+}
+
+/// The `#extension` lines (which have to come first) and the rest, with the GLSL ES 1.00
+/// spellings changed to their 3.00 ones.
+fn essl3(source: &str, vertex: bool) -> (String, String) {
+    let mut extensions = String::new();
+    let mut body = String::new();
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("#version") {
+            continue;
+        }
+        if let Some(rest) = trimmed.strip_prefix("#extension") {
+            let name = rest.split(':').next().unwrap_or("").trim();
+            match name {
+                // core in GLSL ES 3.00
+                "GL_OES_standard_derivatives"
+                | "GL_EXT_shader_texture_lod"
+                | "GL_EXT_frag_depth" => {}
+                "GL_OES_EGL_image_external" => {
+                    extensions.push_str("#extension GL_OES_EGL_image_external_essl3 : require\n")
+                }
+                _ => {
+                    extensions.push_str(trimmed);
+                    extensions.push('\n');
+                }
+            }
+            continue;
+        }
+        body.push_str(line);
+        body.push('\n');
+    }
+
+    let stage: &[(&str, &str)] = if vertex {
+        &[("attribute", "in"), ("varying", "out")]
+    } else {
+        &[("varying", "in"), ("gl_FragColor", FRAG_COLOR)]
+    };
+    let common = [
+        ("texture2D", "texture"),
+        ("textureCube", "texture"),
+        ("texture2DProj", "textureProj"),
+        ("texture2DLod", "textureLod"),
+        ("texture2DLodEXT", "textureLod"),
+        ("textureCubeLodEXT", "textureLod"),
+        ("gl_FragDepthEXT", "gl_FragDepth"),
+    ];
+    for (from, to) in stage.iter().chain(&common) {
+        body = replace_word(&body, from, to);
+    }
+    (extensions, body)
+}
+
+/// `source` with every identifier `from` replaced by `to`
+fn replace_word(source: &str, from: &str, to: &str) -> String {
+    let mut rval = String::with_capacity(source.len());
+    let mut word = String::new();
+    for c in source.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            word.push(c);
+            continue;
+        }
+        rval.push_str(if word == from { to } else { &word });
+        word.clear();
+        rval.push(c);
+    }
+    rval.push_str(if word == from { to } else { &word });
+    rval
+}
+
+fn contains_word(source: &str, word: &str) -> bool {
+    source
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .any(|w| w == word)
+}
+
+//
+
+/// A [Program]'s multiview twin, and where its uniforms ended up
+pub(crate) struct MultiviewVariant {
+    pub(crate) program: Program,
+    /// the single view program's uniform locations to this one's
+    locations: HashMap<GLint, GLint>,
+}
+
+impl MultiviewVariant {
+    /// from the sources `single` was compiled from; the attributes get the same locations
+    pub(crate) fn compile(
+        single: &Program,
+        vertex_shader: &str,
+        fragment_shader: &str,
+    ) -> Result<Self, GLErrorWrapper> {
+        let vertex_shader = vertex_variant(vertex_shader);
+        let fragment_shader = fragment_variant(fragment_shader);
+        let program = Program::link(&vertex_shader, &fragment_shader, |handle| {
+            bind_attributes_like(single.borrow(), handle)
+        })?;
+        let block_name = CString::new(BLOCK_NAME).unwrap();
+        unsafe {
+            let index = gl::GetUniformBlockIndex(program.borrow(), block_name.as_ptr());
+            if index != gl::INVALID_INDEX {
+                gl::UniformBlockBinding(program.borrow(), index, UNIFORM_BINDING);
+            }
+        }
+        explode_if_gl_error()?;
+        let locations = uniform_locations(single.borrow(), program.borrow());
+        Ok(Self { program, locations })
+    }
+
+    pub(crate) fn location(&self, location: GLint) -> GLint {
+        self.locations.get(&location).copied().unwrap_or(-1)
+    }
+}
+
+fn bind_attributes_like(single: GLuint, variant: GLuint) {
+    for name in active_names(
+        single,
+        gl::ACTIVE_ATTRIBUTES,
+        gl::ACTIVE_ATTRIBUTE_MAX_LENGTH,
+    ) {
+        let name = CString::new(name.0).unwrap();
+        let location = unsafe { gl::GetAttribLocation(single, name.as_ptr()) };
+        if location >= 0 {
+            unsafe { gl::BindAttribLocation(variant, location as GLuint, name.as_ptr()) };
+        }
+    }
+}
+
+/// every element of every uniform outside a block
+fn uniform_locations(single: GLuint, variant: GLuint) -> HashMap<GLint, GLint> {
+    let mut locations = HashMap::new();
+    for (name, size) in active_names(single, gl::ACTIVE_UNIFORMS, gl::ACTIVE_UNIFORM_MAX_LENGTH) {
+        let elements: Vec<String> = match name.strip_suffix("[0]") {
+            Some(base) => (0..size.max(1))
+                .map(|i| format!("{}[{}]", base, i))
+                .chain([base.to_string()])
+                .collect(),
+            None => vec![name],
+        };
+        for element in elements {
+            let element = CString::new(element).unwrap();
+            let from = unsafe { gl::GetUniformLocation(single, element.as_ptr()) };
+            if from >= 0 {
+                let to = unsafe { gl::GetUniformLocation(variant, element.as_ptr()) };
+                locations.insert(from, to);
+            }
+        }
+    }
+    locations
+}
+
+/// the names and array sizes of a program's active attributes or uniforms
+pub(crate) fn active_names(
+    program: GLuint,
+    count: GLenum,
+    max_length: GLenum,
+) -> Vec<(String, GLint)> {
+    let (mut n, mut length) = (0, 0);
+    unsafe {
+        gl::GetProgramiv(program, count, &mut n);
+        gl::GetProgramiv(program, max_length, &mut length);
+    }
+    (0..n.max(0) as GLuint)
+        .map(|i| {
+            let mut name = vec![0u8; length.max(1) as usize];
+            let (mut written, mut size, mut kind) = (0, 0, 0);
+            let name_ptr = name.as_mut_ptr() as *mut GLchar;
+            unsafe {
+                if count == gl::ACTIVE_ATTRIBUTES {
+                    gl::GetActiveAttrib(
+                        program,
+                        i,
+                        length,
+                        &mut written,
+                        &mut size,
+                        &mut kind,
+                        name_ptr,
+                    )
+                } else {
+                    gl::GetActiveUniform(
+                        program,
+                        i,
+                        length,
+                        &mut written,
+                        &mut size,
+                        &mut kind,
+                        name_ptr,
+                    )
+                }
+            };
+            name.truncate(written.max(0) as usize);
+            (String::from_utf8_lossy(&name).into_owned(), size)
+        })
+        .collect()
+}
+
+//
+
+/// Where both eyes are drawn at once: a framebuffer with both layers of the eye swapchain's
+/// texture array attached, and a depth buffer to match.  Like the example app's FrameEnv,
+/// for [crate::openxr_helpers::OpenXRComponent::is_multiview].
+pub struct MultiviewFrameBuffer {
+    pub frame_buffer: FrameBuffer,
+    /// a two layer array, with 8 stencil bits if [MultiviewFrameBuffer::stencil]
+    pub depth_buffer: Texture,
+    pub stencil: bool,
+    /// a uniform buffer of the `BobMultiview` block
+    corrections: GLuint,
+    /// another, of identity matrices, see [in_screen_space]
+    identity: GLuint,
+    framebuffer_texture_multiview: FramebufferTextureMultiviewFn,
+}
+
+impl MultiviewFrameBuffer {
+    /// Fails where [is_supported] is false.
+    pub fn new(
+        width: u32,
+        height: u32,
+        stencil: bool,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let framebuffer_texture_multiview = framebuffer_texture_multiview()?;
+        let depth_buffer = Texture::new()?;
+        {
+            let _bound = depth_buffer.bound(gl::TEXTURE_2D_ARRAY, gpu_state)?;
+            let format = if stencil {
+                gl::DEPTH24_STENCIL8
+            } else {
+                gl::DEPTH_COMPONENT24
+            };
+            unsafe {
+                gl::TexStorage3D(
+                    gl::TEXTURE_2D_ARRAY,
+                    1,
+                    format,
+                    width as GLsizei,
+                    height as GLsizei,
+                    VIEW_COUNT as GLsizei,
+                )
+            };
+            explode_if_gl_error()?;
+        }
+
+        let mut buffers = MaybeUninit::<[GLuint; 2]>::uninit();
+        unsafe { gl::GenBuffers(2, buffers.as_mut_ptr() as *mut GLuint) };
+        explode_if_gl_error()?;
+        let [corrections, identity] = unsafe { buffers.assume_init() };
+        let identities = [xr_matrix4x4f_identity(); VIEW_COUNT];
+        upload_corrections(identity, &identities)?;
+        Ok(Self {
+            frame_buffer: FrameBuffer::new()?,
+            depth_buffer,
+            stencil,
+            corrections,
+            identity,
+            framebuffer_texture_multiview,
+        })
+    }
+
+    /// Bind the frame_buffer with both layers of `color_array` and of the depth buffer
+    /// attached, and start a multiview pass.  Draws get `matrices_pv[0]`; the shaders move
+    /// the other eyes from there to their own.
+    pub fn prepare_to_draw(
+        &self,
+        color_array: &Texture,
+        matrices_pv: &[XrMatrix4x4f; VIEW_COUNT],
+        width: u32,
+        height: u32,
+    ) -> Result<(), GLErrorWrapper> {
+        self.frame_buffer.bind()?;
+        let depth_attachment = self.depth_attachment();
+        unsafe {
+            (self.framebuffer_texture_multiview)(
+                gl::DRAW_FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                color_array.borrow(),
+                0,
+                0,
+                VIEW_COUNT as GLsizei,
+            );
+            (self.framebuffer_texture_multiview)(
+                gl::DRAW_FRAMEBUFFER,
+                depth_attachment,
+                self.depth_buffer.borrow(),
+                0,
+                0,
+                VIEW_COUNT as GLsizei,
+            );
+            gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
+        }
+        explode_if_gl_error()?;
+
+        let first_inverse = xr_matrix4x4f_invert(&matrices_pv[0]);
+        upload_corrections(
+            self.corrections,
+            &matrices_pv.map(|matrix_pv| matrix_pv * first_inverse),
+        )?;
+        unsafe { gl::BindBufferBase(gl::UNIFORM_BUFFER, UNIFORM_BINDING, self.corrections) };
+        explode_if_gl_error()?;
+        PASS.with(|pass| {
+            pass.set(Some(PassBuffers {
+                corrections: self.corrections,
+                identity: self.identity,
+            }))
+        });
+        Ok(())
+    }
+
+    /// End the multiview pass, and drop the depth before it is written back.  Call even if
+    /// drawing failed.
+    pub fn finish_drawing(&self) -> Result<(), GLErrorWrapper> {
+        PASS.with(|pass| pass.set(None));
+        self.frame_buffer.invalidate(&[self.depth_attachment()])
+    }
+
+    fn depth_attachment(&self) -> GLenum {
+        if self.stencil {
+            gl::DEPTH_STENCIL_ATTACHMENT
+        } else {
+            gl::DEPTH_ATTACHMENT
+        }
+    }
+}
+
+impl Drop for MultiviewFrameBuffer {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(2, [self.corrections, self.identity].as_ptr()) };
+    }
+}
+
+/// fill `buffer` with a `BobMultiview` block
+fn upload_corrections(
+    buffer: GLuint,
+    corrections: &[XrMatrix4x4f; VIEW_COUNT],
+) -> Result<(), GLErrorWrapper> {
+    let mut data = [0.0f32; 16 * VIEW_COUNT];
+    for (eye, correction) in corrections.iter().enumerate() {
+        data[16 * eye..16 * (eye + 1)].copy_from_slice(correction.slice());
+    }
+    unsafe {
+        gl::BindBuffer(gl::UNIFORM_BUFFER, buffer);
+        gl::BufferData(
+            gl::UNIFORM_BUFFER,
+            size_of::<[f32; 16 * VIEW_COUNT]>() as GLsizeiptr,
+            data.as_ptr() as *const c_void,
+            gl::DYNAMIC_DRAW,
+        );
+    }
+    explode_if_gl_error()
+}
+
+fn framebuffer_texture_multiview() -> Result<FramebufferTextureMultiviewFn, GLErrorWrapper> {
+    let name = CString::new("glFramebufferTextureMultiviewOVR").unwrap();
+    let address = unsafe { egli::ffi::eglGetProcAddress(name.as_ptr()) } as *mut c_void;
+    if address.is_null() {
+        return Err(GLErrorWrapper::with_message2(
+            "glFramebufferTextureMultiviewOVR is not available".to_string(),
+        ));
+    }
+    Ok(unsafe { std::mem::transmute::<*mut c_void, FramebufferTextureMultiviewFn>(address) })
+}
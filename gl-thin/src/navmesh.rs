@@ -0,0 +1,330 @@
+//! Where the user may stand: a navigation mesh baked by an external tool (Recast, or any
+//! modeler) and loaded from OBJ or JSON.  Queries look for the polygon under or along a
+//! path through a [Bvh] of polygon boxes, then test the point against the polygon seen
+//! from above.
+
+use crate::bvh::{Aabb, Bvh};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// Recast pads its polygons' vertex lists with this index
+const RECAST_NULL_INDEX: u32 = 0xffff;
+
+#[derive(Debug)]
+pub enum NavMeshError {
+    /// a line of an OBJ or a part of a JSON document that makes no sense
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// a polygon refers to a vertex that is not there
+    BadIndex {
+        polygon: usize,
+        index: u32,
+    },
+    Empty,
+}
+
+impl Display for NavMeshError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NavMeshError::Parse { line, message } => {
+                write!(f, "navmesh line {}: {}", line, message)
+            }
+            NavMeshError::BadIndex { polygon, index } => {
+                write!(
+                    f,
+                    "navmesh polygon {} uses missing vertex {}",
+                    polygon, index
+                )
+            }
+            NavMeshError::Empty => write!(f, "navmesh has no polygons"),
+        }
+    }
+}
+
+impl Error for NavMeshError {}
+
+/// where a query met the walkable surface
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NavHit {
+    pub polygon: usize,
+    pub position: [f32; 3],
+    /// along the query's ray, in units of its direction's length
+    pub distance: f32,
+}
+
+pub struct NavMesh {
+    pub vertices: Vec<[f32; 3]>,
+    /// indices into [NavMesh::vertices], at least three each, wound either way
+    pub polygons: Vec<Vec<u32>>,
+    /// each polygon's unit normal, pointing up
+    normals: Vec<[f32; 3]>,
+    tree: Bvh,
+}
+
+impl NavMesh {
+    /// Walls and other polygons too steep to stand on are left out.
+    pub fn new(vertices: Vec<[f32; 3]>, polygons: Vec<Vec<u32>>) -> Result<Self, NavMeshError> {
+        let mut kept = Vec::with_capacity(polygons.len());
+        let mut normals = Vec::with_capacity(polygons.len());
+        for (i, polygon) in polygons.into_iter().enumerate() {
+            if let Some(&index) = polygon.iter().find(|&&v| v as usize >= vertices.len()) {
+                return Err(NavMeshError::BadIndex { polygon: i, index });
+            }
+            if polygon.len() < 3 {
+                continue;
+            }
+            let normal = newell_normal(&vertices, &polygon);
+            // more than about 80° from level
+            if normal[1] < 0.17 {
+                continue;
+            }
+            kept.push(polygon);
+            normals.push(normal);
+        }
+        if kept.is_empty() {
+            return Err(NavMeshError::Empty);
+        }
+        let boxes: Vec<Aabb> = kept
+            .iter()
+            .map(|polygon| Aabb::of_points(polygon.iter().map(|&v| vertices[v as usize])))
+            .collect();
+        Ok(Self {
+            tree: Bvh::build(&boxes),
+            vertices,
+            polygons: kept,
+            normals,
+        })
+    }
+
+    /// `v` and `f` lines, as Recast's demo and most modelers write them; everything else is
+    /// ignored.
+    pub fn from_obj(source: &str) -> Result<Self, NavMeshError> {
+        let mut vertices = vec![];
+        let mut polygons = vec![];
+        for (number, line) in source.lines().enumerate() {
+            let parse_error = |message: String| NavMeshError::Parse {
+                line: number + 1,
+                message,
+            };
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("v") => {
+                    let xyz: Vec<f32> = words
+                        .take(3)
+                        .map(|word| word.parse())
+                        .collect::<Result<_, _>>()
+                        .map_err(|e| parse_error(format!("bad vertex {}", e)))?;
+                    let [x, y, z] = xyz[..] else {
+                        return Err(parse_error("vertex needs x, y and z".to_string()));
+                    };
+                    vertices.push([x, y, z]);
+                }
+                Some("f") => {
+                    let polygon = words
+                        .map(|word| obj_index(word, vertices.len()).map_err(parse_error))
+                        .collect::<Result<_, _>>()?;
+                    polygons.push(polygon);
+                }
+                _ => {}
+            }
+        }
+        Self::new(vertices, polygons)
+    }
+
+    /// `{"vertices": [...], "polygons": [[...], ...]}`: the vertices as a flat or nested list
+    /// of coordinates, three to a vertex, and each polygon a list of vertex numbers.  Recast's
+    /// padding index 0xffff ends a polygon early.
+    pub fn from_recast_json(source: &str) -> Result<Self, NavMeshError> {
+        let parse_error = |message: &str| NavMeshError::Parse {
+            line: 1,
+            message: message.to_string(),
+        };
+        let vertices = json_array(source, "vertices").ok_or(parse_error("no vertices array"))?;
+        let coordinates = json_numbers(vertices).ok_or(parse_error("bad vertices"))?;
+        if coordinates.len() % 3 != 0 {
+            return Err(parse_error("vertex coordinates do not come in threes"));
+        }
+        let vertices = coordinates
+            .chunks_exact(3)
+            .map(|xyz| [xyz[0] as f32, xyz[1] as f32, xyz[2] as f32])
+            .collect();
+
+        let polygons = json_array(source, "polygons").ok_or(parse_error("no polygons array"))?;
+        let polygons = json_sub_arrays(polygons)
+            .map(|polygon| {
+                let indices = json_numbers(polygon).ok_or(parse_error("bad polygon"))?;
+                Ok(indices
+                    .into_iter()
+                    .map(|index| index as u32)
+                    .take_while(|&index| index != RECAST_NULL_INDEX)
+                    .collect())
+            })
+            .collect::<Result<_, NavMeshError>>()?;
+        Self::new(vertices, polygons)
+    }
+
+    /// Where `origin + t * direction`, `0 <= t <= max_t`, first lands on the walkable surface
+    /// coming from above, e.g. the end of a teleport arc.
+    pub fn cast(&self, origin: &[f32; 3], direction: &[f32; 3], max_t: f32) -> Option<NavHit> {
+        let (polygon, distance) = self.tree.cast(origin, direction, max_t, |polygon, limit| {
+            let normal = &self.normals[polygon];
+            let approach = dot(normal, direction);
+            if approach >= 0.0 {
+                return None;
+            }
+            let corner = &self.vertices[self.polygons[polygon][0] as usize];
+            let t = (dot(normal, corner) - dot(normal, origin)) / approach;
+            let position = along(origin, direction, t);
+            ((0.0..=limit).contains(&t) && self.contains_xz(polygon, &position)).then_some(t)
+        })?;
+        Some(NavHit {
+            polygon,
+            position: along(origin, direction, distance),
+            distance,
+        })
+    }
+
+    /// The walkable surface under `point`, looking from `step` above it (a curb stepped onto)
+    /// to `max_drop` below.
+    pub fn ground_below(&self, point: &[f32; 3], step: f32, max_drop: f32) -> Option<NavHit> {
+        let start = [point[0], point[1] + step, point[2]];
+        self.cast(&start, &[0.0, -1.0, 0.0], step + max_drop)
+    }
+
+    /// true if `point` is on the walkable surface, give or take `tolerance` vertically
+    pub fn is_walkable(&self, point: &[f32; 3], tolerance: f32) -> bool {
+        self.ground_below(point, tolerance, tolerance).is_some()
+    }
+
+    /// every polygon edge, once per polygon, e.g. for debug drawing
+    pub fn edges(&self) -> impl Iterator<Item = ([f32; 3], [f32; 3])> + '_ {
+        self.polygons.iter().flat_map(|polygon| {
+            polygon.iter().enumerate().map(|(i, &a)| {
+                let b = polygon[(i + 1) % polygon.len()];
+                (self.vertices[a as usize], self.vertices[b as usize])
+            })
+        })
+    }
+
+    /// crossing-number test of `point` against the polygon seen from above
+    fn contains_xz(&self, polygon: usize, point: &[f32; 3]) -> bool {
+        let polygon = &self.polygons[polygon];
+        let (x, z) = (point[0], point[2]);
+        let mut inside = false;
+        for (i, &a) in polygon.iter().enumerate() {
+            let a = &self.vertices[a as usize];
+            let b = &self.vertices[polygon[(i + 1) % polygon.len()] as usize];
+            if (a[2] > z) != (b[2] > z) {
+                let crossing = a[0] + (z - a[2]) / (b[2] - a[2]) * (b[0] - a[0]);
+                if x < crossing {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    }
+}
+
+/// a polygon's unit normal, flipped to point up; stays sensible for slightly non-planar ones
+fn newell_normal(vertices: &[[f32; 3]], polygon: &[u32]) -> [f32; 3] {
+    let mut n = [0.0f32; 3];
+    for (i, &a) in polygon.iter().enumerate() {
+        let a = &vertices[a as usize];
+        let b = &vertices[polygon[(i + 1) % polygon.len()] as usize];
+        n[0] += (a[1] - b[1]) * (a[2] + b[2]);
+        n[1] += (a[2] - b[2]) * (a[0] + b[0]);
+        n[2] += (a[0] - b[0]) * (a[1] + b[1]);
+    }
+    let length = dot(&n, &n).sqrt();
+    if length == 0.0 {
+        return [0.0; 3];
+    }
+    let sign = if n[1] < 0.0 { -1.0 } else { 1.0 };
+    n.map(|c| sign * c / length)
+}
+
+/// "7", "7/2" or "7/2/5" in a face, counting from 1; negative counts back from the latest
+/// vertex
+fn obj_index(word: &str, vertex_count: usize) -> Result<u32, String> {
+    let index: i64 = word
+        .split('/')
+        .next()
+        .unwrap_or(word)
+        .parse()
+        .map_err(|e| format!("bad face index {}", e))?;
+    match index {
+        0 => Err("face index 0".to_string()),
+        i if i < 0 => Ok((vertex_count as i64 + i).max(0) as u32),
+        i => Ok((i - 1) as u32),
+    }
+}
+
+fn dot(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn along(origin: &[f32; 3], direction: &[f32; 3], t: f32) -> [f32; 3] {
+    [0, 1, 2].map(|axis| origin[axis] + t * direction[axis])
+}
+
+//
+
+/// the `[...]` that follows `"key":` in a JSON document, brackets included
+fn json_array<'a>(source: &'a str, key: &str) -> Option<&'a str> {
+    let quoted = format!("\"{}\"", key);
+    let after_key = &source[source.find(&quoted)? + quoted.len()..];
+    let value = after_key.trim_start().strip_prefix(':')?.trim_start();
+    if !value.starts_with('[') {
+        return None;
+    }
+    let mut depth = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&value[..=i]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// the arrays directly inside `array`
+fn json_sub_arrays(array: &str) -> impl Iterator<Item = &str> {
+    let inner = &array[1..array.len() - 1];
+    let mut depth = 0;
+    let mut start = 0;
+    inner.char_indices().filter_map(move |(i, c)| {
+        match c {
+            '[' => {
+                if depth == 0 {
+                    start = i;
+                }
+                depth += 1;
+            }
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&inner[start..=i]);
+                }
+            }
+            _ => {}
+        }
+        None
+    })
+}
+
+/// every number in `array`, however deeply nested
+fn json_numbers(array: &str) -> Option<Vec<f64>> {
+    array
+        .split(|c: char| c == '[' || c == ']' || c == ',' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.parse().ok())
+        .collect()
+}
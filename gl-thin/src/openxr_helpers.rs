@@ -1,33 +1,280 @@
-use crate::errors::{Wrappable, XrErrorWrapped};
+use crate::composition_layers::{BuiltLayer, LayerStack};
+use crate::depth_layer::{chain_depth, chain_next, DepthLayer};
+use crate::errors::{RuntimeUnavailable, Wrappable, XrErrorWrapped};
+use crate::frame_arena::{ArenaVec, FrameArena};
+use crate::gl_caps::gl_caps;
+use crate::hand_mesh::{create_hand_tracker, pose_matrix};
+use crate::linear::{
+    xr_matrix4x4f_create_from_quaternion, xr_matrix4x4f_transform_vector3f, XrMatrix4x4f,
+    XrVector3f,
+};
+use crate::performance_settings::{
+    PerfSettingsDomainEXT, PerfSettingsLevelEXT, PerformanceNotification, PerformanceSettings,
+};
+use crate::space_warp::{chain_space_warp, SpaceWarp, SpaceWarpImages};
+use crate::watchdog::Heartbeat;
 use gl::types::GLint;
 use itertools::izip;
 use log::{debug, error, info, warn};
 use openxr::sys::{result_to_string, Result as XrResult, MAX_RESULT_STRING_SIZE};
 use openxr::OpenGlEs;
 use openxr::{
-    ActionSet, ApplicationInfo, Binding, CompositionLayerBase, CompositionLayerProjection, Entry,
-    Event, EventDataBuffer, ExtensionSet, FormFactor, FrameState, FrameStream, FrameWaiter,
-    Graphics, Instance, Posef, Quaternionf, ReferenceSpaceType, Session, SessionState, Space,
-    SpaceLocation, Swapchain, SwapchainCreateFlags, SwapchainCreateInfo, SwapchainUsageFlags,
-    SystemId, Version, View, ViewConfigurationType, ViewConfigurationView,
+    Action, ActionSet, ApiLayerProperties, ApplicationInfo, Binding, CompositionLayerBase,
+    CompositionLayerProjection, Entry, Event, EventDataBuffer, ExtensionSet, FormFactor,
+    FrameState, FrameStream, FrameWaiter, Graphics, Hand, HandJointLocations, Instance, Posef,
+    Quaternionf, ReferenceSpaceType, Session, SessionState, Space, SpaceLocation,
+    SpaceLocationFlags, Swapchain, SwapchainCreateFlags, SwapchainCreateInfo, SwapchainUsageFlags,
+    SystemId, Version, View, ViewConfigurationType, ViewConfigurationView, HAND_JOINT_COUNT,
 };
 use openxr_sys::{
-    CompositionLayerFlags, Duration as XrDuration, EnvironmentBlendMode, Extent2Di, Offset2Di,
-    Rect2Di, Time,
+    CompositionLayerFlags, Duration as XrDuration, EnvironmentBlendMode, Extent2Di, HandJointEXT,
+    Offset2Di, Path, Rect2Di, Time,
 };
 use std::ffi::{c_void, CStr};
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
 
 pub type Backend = OpenGlEs;
 
+/// the most [OpenXRComponent::set_prediction_offset] will shift pose prediction either way
+pub const MAX_PREDICTION_OFFSET_NANOS: i64 = 20_000_000;
+
+/// How often instance and system creation are tried before giving up.  Right after boot
+/// the runtime service can take a moment to come up, and a headset can still be waking.
+const STARTUP_ATTEMPTS: u32 = 3;
+const STARTUP_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// the Khronos validation layer, which checks every call against the spec and logs what
+/// it does not like.  On Android it has to be packaged into the APK with its manifest.
+pub const CORE_VALIDATION_LAYER: &str = "XR_APILAYER_LUNARG_core_validation";
+
+/// Choices made when the instance is created, see [OpenXRComponent::new]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct XrConfig {
+    /// API layers to enable, by name.  Ones the loader does not have are logged and skipped,
+    /// so a development setting does not keep the app from starting elsewhere.
+    pub api_layers: Vec<String>,
+    /// Make one texture array swapchain with a layer per eye instead of a swapchain per eye,
+    /// so both can be drawn at once with GL_OVR_multiview2; see
+    /// [OpenXRComponent::paint_vr_multiview].
+    pub multiview: bool,
+}
+
+impl XrConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_api_layer(mut self, name: impl Into<String>) -> Self {
+        self.api_layers.push(name.into());
+        self
+    }
+
+    /// [CORE_VALIDATION_LAYER], for development builds
+    pub fn with_validation(self) -> Self {
+        self.with_api_layer(CORE_VALIDATION_LAYER)
+    }
+
+    pub fn with_multiview(mut self) -> Self {
+        self.multiview = true;
+        self
+    }
+
+    /// the [XrConfig::api_layers] the loader has
+    fn available_api_layers(&self, entry: &Entry) -> Vec<String> {
+        if self.api_layers.is_empty() {
+            return vec![];
+        }
+        let installed = match enumerate_api_layers(entry) {
+            Ok(installed) => installed,
+            Err(e) => {
+                warn!("{}; starting without API layers", e);
+                return vec![];
+            }
+        };
+        self.api_layers
+            .iter()
+            .filter(|name| {
+                let found = installed.iter().any(|layer| &layer.layer_name == *name);
+                if !found {
+                    warn!("API layer {} is not installed, skipping it", name);
+                }
+                found
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// the API layers the loader can enable, logged at debug level
+pub fn enumerate_api_layers(entry: &Entry) -> Result<Vec<ApiLayerProperties>, XrErrorWrapped> {
+    let layers = entry
+        .enumerate_layers()
+        .annotate_if_err(None, "failed to enumerate API layers")?;
+    for layer in &layers {
+        debug!(
+            "API layer {} v{}: {}",
+            layer.layer_name, layer.layer_version, layer.description
+        );
+    }
+    Ok(layers)
+}
+
+/// the runtime OpenXR is talking to, from xrGetInstanceProperties
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuntimeInfo {
+    pub name: String,
+    pub version: Version,
+}
+
+impl RuntimeInfo {
+    pub fn of(instance: &Instance) -> Result<Self, XrErrorWrapped> {
+        let properties = instance
+            .properties()
+            .annotate_if_err(Some(instance), "failed to get instance properties")?;
+        Ok(Self {
+            name: properties.runtime_name,
+            version: properties.runtime_version,
+        })
+    }
+}
+
+impl Display for RuntimeInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}.{}.{}",
+            self.name,
+            self.version.major(),
+            self.version.minor(),
+            self.version.patch()
+        )
+    }
+}
+
+/// the errors that mean there is no runtime to talk to, rather than a broken call
+fn is_runtime_missing(e: XrResult) -> bool {
+    e == XrResult::ERROR_RUNTIME_UNAVAILABLE || e == XrResult::ERROR_RUNTIME_FAILURE
+}
+
+/// [Entry::create_instance], retried while the runtime is not up yet
+fn create_instance_with_retry(
+    entry: &Entry,
+    application_info: &ApplicationInfo,
+    extensions: &ExtensionSet,
+    layers: &[&str],
+) -> Result<Instance, XrErrorWrapped> {
+    let mut attempt = 1;
+    loop {
+        match entry.create_instance(application_info, extensions, layers) {
+            Ok(instance) => return Ok(instance),
+            Err(e) if is_runtime_missing(e) && attempt < STARTUP_ATTEMPTS => {
+                warn!("no OpenXR runtime yet ({}), attempt {}", e, attempt);
+            }
+            Err(e) if is_runtime_missing(e) => {
+                return Err(
+                    XrErrorWrapped::build(e, None, "failed to create XR instance")
+                        .with_unavailable(RuntimeUnavailable::NoRuntime),
+                );
+            }
+            Err(e) => {
+                return Err(XrErrorWrapped::build(
+                    e,
+                    None,
+                    "failed to create XR instance",
+                ))
+            }
+        }
+        attempt += 1;
+        std::thread::sleep(STARTUP_RETRY_DELAY);
+    }
+}
+
+/// [Instance::system], retried while the headset is not ready
+fn find_system_with_retry(
+    instance: &Instance,
+    runtime: &RuntimeInfo,
+) -> Result<SystemId, XrErrorWrapped> {
+    let mut attempt = 1;
+    loop {
+        match instance.system(FormFactor::HEAD_MOUNTED_DISPLAY) {
+            Ok(system_id) => return Ok(system_id),
+            Err(XrResult::ERROR_FORM_FACTOR_UNAVAILABLE) if attempt < STARTUP_ATTEMPTS => {
+                warn!("{} has no headset ready yet, attempt {}", runtime, attempt);
+            }
+            Err(
+                e @ (XrResult::ERROR_FORM_FACTOR_UNAVAILABLE
+                | XrResult::ERROR_FORM_FACTOR_UNSUPPORTED),
+            ) => {
+                return Err(
+                    XrErrorWrapped::build(e, Some(instance), "failed to get system id")
+                        .with_unavailable(RuntimeUnavailable::NoHeadset {
+                            runtime: runtime.to_string(),
+                        }),
+                );
+            }
+            Err(e) => {
+                return Err(XrErrorWrapped::build(
+                    e,
+                    Some(instance),
+                    "failed to get system id",
+                ))
+            }
+        }
+        attempt += 1;
+        std::thread::sleep(STARTUP_RETRY_DELAY);
+    }
+}
+
 pub struct OpenXRComponent<G: Graphics> {
     pub xr_instance: Instance,
+    /// which runtime this is, for bug reports
+    pub runtime: RuntimeInfo,
+    pub system_id: SystemId,
     pub xr_session: Session<G>,
     pub frame_waiter: FrameWaiter,
     pub frame_stream: FrameStream<G>,
     pub xr_space: Space,
     pub xr_swapchain_images: Vec<Vec<G::SwapchainImage>>,
     pub xr_swapchains: Vec<Swapchain<G>>,
+    /// The runtime's views, with the recommended rectangle scaled by the render scale: the
+    /// size [OpenXRComponent::xr_swapchains] were made at.
     pub view_config_views: Vec<ViewConfigurationView>,
+    /// what the eye buffers were created with, and a good choice for layer swapchains
+    pub swapchain_format: G::Format,
+    /// submitted along with the projection layer every frame
+    pub layers: LayerStack<G>,
+    /// `None` if the runtime does not support XR_EXT_performance_settings
+    pub performance_settings: Option<PerformanceSettings>,
+    /// motion vectors submitted with the eye images, see [OpenXRComponent::enable_space_warp]
+    pub space_warp: Option<SpaceWarp<G>>,
+    /// depth submitted with the eye images, see [OpenXRComponent::enable_depth_layer]
+    pub depth_layer: Option<DepthLayer<G>>,
+    /// which part of [OpenXRComponent::paint_vr_multiview] is running, for a
+    /// [crate::watchdog::Watchdog]
+    pub heartbeat: Heartbeat,
+    /// emptied at the start of every [OpenXRComponent::paint_vr_multiview], which lends it
+    /// to each view's painting
+    frame_arena: FrameArena,
+    /// collected by [OpenXRComponent::poll_till_no_events]
+    performance_notifications: Vec<PerformanceNotification>,
+    /// an XrEventDataInteractionProfileChanged arrived since the last check
+    interaction_profile_changed: bool,
+    /// see [OpenXRComponent::session_state]
+    session_state: SessionState,
+    /// see [OpenXRComponent::pose_time]
+    prediction_offset: XrDuration,
+    /// see [OpenXRComponent::set_render_scale]
+    render_scale: f32,
+    /// see [OpenXRComponent::swapchains_stale]
+    swapchains_stale: bool,
+    /// see [OpenXRComponent::swapchain_generation]
+    swapchain_generation: u64,
+    /// see [XrConfig::multiview]
+    multiview: bool,
+    /// where [OpenXRComponent::xr_space]'s origin is in the LOCAL space, see
+    /// [OpenXRComponent::move_user]
+    space_origin: Posef,
 }
 
 impl<G: Graphics> Drop for OpenXRComponent<G> {
@@ -58,7 +305,17 @@ impl<G: Graphics> OpenXRComponent<G> {
         info: &<G as Graphics>::SessionCreateInfo,
         acceptable_format: impl Fn(&G::Format) -> bool,
         pre_session_check: impl Fn(&Instance, SystemId) -> Result<(), XrErrorWrapped>,
+        config: &XrConfig,
     ) -> Result<Self, XrErrorWrapped> {
+        let available_extensions = entry.enumerate_extensions().map_err(|e| {
+            let wrapped = XrErrorWrapped::build(e, None, "failed to enumerate XR extensions");
+            if is_runtime_missing(e) {
+                wrapped.with_unavailable(RuntimeUnavailable::NoRuntime)
+            } else {
+                wrapped
+            }
+        })?;
+
         let instance = {
             let application_info = ApplicationInfo {
                 application_name: "GStreamer OpenXR video sink",
@@ -72,15 +329,36 @@ impl<G: Graphics> OpenXRComponent<G> {
             {
                 enabled_extensions.khr_android_create_instance = true;
             }
-
-            let tmp: Result<Instance, openxr_sys::Result> =
-                entry.create_instance(&application_info, &enabled_extensions, &[]);
-            tmp.annotate_if_err(None, "failed to create XR instance ")?
+            // optional
+            enabled_extensions.ext_performance_settings =
+                available_extensions.ext_performance_settings;
+            enabled_extensions.khr_composition_layer_equirect2 =
+                available_extensions.khr_composition_layer_equirect2;
+            enabled_extensions.khr_composition_layer_cylinder =
+                available_extensions.khr_composition_layer_cylinder;
+            enabled_extensions.khr_composition_layer_cube =
+                available_extensions.khr_composition_layer_cube;
+            enabled_extensions.fb_composition_layer_secure_content =
+                available_extensions.fb_composition_layer_secure_content;
+            enabled_extensions.ext_hand_tracking = available_extensions.ext_hand_tracking;
+            enabled_extensions.fb_hand_tracking_mesh = available_extensions.ext_hand_tracking
+                && available_extensions.fb_hand_tracking_mesh;
+            enabled_extensions.fb_space_warp = available_extensions.fb_space_warp;
+            enabled_extensions.khr_composition_layer_depth =
+                available_extensions.khr_composition_layer_depth;
+
+            let api_layers = config.available_api_layers(entry);
+            if !api_layers.is_empty() {
+                info!("enabling API layers {:?}", api_layers);
+            }
+            let api_layers: Vec<&str> = api_layers.iter().map(String::as_str).collect();
+            create_instance_with_retry(entry, &application_info, &enabled_extensions, &api_layers)?
         };
 
-        let system_id = instance
-            .system(FormFactor::HEAD_MOUNTED_DISPLAY)
-            .annotate_if_err(Some(&instance), "failed to get system id")?;
+        let runtime = RuntimeInfo::of(&instance)?;
+        info!("OpenXR runtime {}", runtime);
+
+        let system_id = find_system_with_retry(&instance, &runtime)?;
 
         let view_config_views = instance
             .enumerate_view_configuration_views(system_id, ViewConfigurationType::PRIMARY_STEREO)
@@ -93,19 +371,17 @@ impl<G: Graphics> OpenXRComponent<G> {
                 .annotate_if_err(Some(&instance), "failed to create session")?
         };
 
+        let space_origin = Posef {
+            orientation: Quaternionf {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+                w: 1.0,
+            },
+            position: Default::default(),
+        };
         let xr_space = xr_session
-            .create_reference_space(
-                ReferenceSpaceType::LOCAL,
-                Posef {
-                    orientation: Quaternionf {
-                        x: 0.0,
-                        y: 0.0,
-                        z: 0.0,
-                        w: 1.0,
-                    },
-                    position: Default::default(),
-                },
-            )
+            .create_reference_space(ReferenceSpaceType::LOCAL, space_origin)
             .annotate_if_err(Some(&instance), "failed to create refrence space")?;
 
         {
@@ -133,57 +409,21 @@ impl<G: Graphics> OpenXRComponent<G> {
             }
         };
 
-        let xr_swapchains = {
-            let mut xr_swapchains = vec![];
-
-            for view_config_i in view_config_views.iter() {
-                debug!(
-                    "view config recommended size {}x{}",
-                    view_config_i.recommended_image_rect_width,
-                    view_config_i.recommended_image_rect_height
-                );
-                let swapchain_create_info = SwapchainCreateInfo::<G> {
-                    create_flags: SwapchainCreateFlags::EMPTY,
-                    usage_flags: SwapchainUsageFlags::SAMPLED
-                        | SwapchainUsageFlags::COLOR_ATTACHMENT,
-                    format: swapchain_format,
-                    sample_count: 1,
-                    width: view_config_i.recommended_image_rect_width,
-                    height: view_config_i.recommended_image_rect_height,
-                    face_count: 1,
-                    array_size: 1,
-                    mip_count: 1,
-                };
-                let swapchain = xr_session
-                    .create_swapchain(&swapchain_create_info)
-                    // .unwrap();
-                    .annotate_if_err(Some(&instance), "failed to create swapchain")?;
-
-                xr_swapchains.push(swapchain);
-            }
+        let (xr_swapchains, xr_swapchain_images) = create_eye_swapchains(
+            &instance,
+            &xr_session,
+            &view_config_views,
+            swapchain_format,
+            config.multiview,
+        )?;
 
-            xr_swapchains
-        };
-
-        debug!(
-            "fetching swapchain images for {} swapchains",
-            xr_swapchains.len()
-        );
-        let xr_swapchain_images = {
-            let mut swapchain_images = vec![];
-            for (i, swapchain) in xr_swapchains.iter().enumerate() {
-                let images = swapchain
-                    .enumerate_images()
-                    .annotate_if_err(Some(&instance), "failed to enumerate swapchain images")?;
-                debug!("swapchain[{}] has {} images", i, images.len());
-                swapchain_images.push(images);
-            }
-
-            swapchain_images
-        };
+        let performance_settings = PerformanceSettings::new(&instance);
+        let layers = LayerStack::new(&instance);
 
         let thing = Self {
             xr_instance: instance,
+            runtime,
+            system_id,
             xr_session,
             frame_waiter,
             frame_stream,
@@ -191,10 +431,37 @@ impl<G: Graphics> OpenXRComponent<G> {
             xr_swapchain_images,
             xr_swapchains,
             view_config_views,
+            swapchain_format,
+            layers,
+            performance_settings,
+            space_warp: None,
+            depth_layer: None,
+            performance_notifications: vec![],
+            interaction_profile_changed: false,
+            session_state: SessionState::READY,
+            prediction_offset: XrDuration::from_nanos(0),
+            heartbeat: Heartbeat::new(),
+            frame_arena: FrameArena::new(),
+            render_scale: 1.0,
+            swapchains_stale: false,
+            swapchain_generation: 0,
+            multiview: config.multiview,
+            space_origin,
         };
         Ok(thing)
     }
 
+    /// whether [crate::hand_mesh::HandMeshTracker]s can be made
+    pub fn supports_hand_meshes(&self) -> bool {
+        self.xr_instance.exts().fb_hand_tracking_mesh.is_some()
+            && self.xr_instance.exts().ext_hand_tracking.is_some()
+    }
+
+    /// whether a [HandJointTracker] can be made
+    pub fn supports_hand_tracking(&self) -> bool {
+        self.xr_instance.exts().ext_hand_tracking.is_some()
+    }
+
     pub fn loop_poll_until_ready(instance: &Instance) -> Result<(), XrErrorWrapped> {
         let mut event_data_buffer2 = Default::default();
         loop {
@@ -223,27 +490,266 @@ impl<G: Graphics> OpenXRComponent<G> {
         }
     }
 
+    /// The runtime's performance notifications since the last call.
+    /// Empty if the runtime does not support XR_EXT_performance_settings.
+    pub fn take_performance_notifications(&mut self) -> Vec<PerformanceNotification> {
+        std::mem::take(&mut self.performance_notifications)
+    }
+
+    /// The latest state [OpenXRComponent::poll_till_no_events] saw.  VISIBLE and FOCUSED mean
+    /// frames are on screen; the runtime leaves them when the headset comes off.
+    pub fn session_state(&self) -> SessionState {
+        self.session_state
+    }
+
+    /// Whether the runtime said the interaction profile of some top-level user path (a
+    /// controller connecting, disconnecting or being swapped) changed since the last call.
+    pub fn take_interaction_profile_changed(&mut self) -> bool {
+        std::mem::take(&mut self.interaction_profile_changed)
+    }
+
+    /// Ask the runtime to clock `domain` at `level`.  Returns `false` (and does nothing)
+    /// if the runtime does not support XR_EXT_performance_settings.
+    pub fn set_performance_level(
+        &self,
+        domain: PerfSettingsDomainEXT,
+        level: PerfSettingsLevelEXT,
+    ) -> Result<bool, XrErrorWrapped> {
+        match &self.performance_settings {
+            Some(settings) => {
+                settings.set_level(&self.xr_instance, &self.xr_session, domain, level)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Locate views and spaces `offset` later (or, if negative, earlier) than the runtime predicts
+    /// the frame will be shown.  Predicting further ahead makes up for latency the runtime does
+    /// not know about, at the cost of jitter.  The offset is clamped to
+    /// [MAX_PREDICTION_OFFSET_NANOS]; this returns what was actually set.
+    pub fn set_prediction_offset(&mut self, offset: XrDuration) -> XrDuration {
+        let max = MAX_PREDICTION_OFFSET_NANOS;
+        self.prediction_offset = XrDuration::from_nanos(offset.as_nanos().clamp(-max, max));
+        self.prediction_offset
+    }
+
+    pub fn prediction_offset(&self) -> XrDuration {
+        self.prediction_offset
+    }
+
+    /// when to locate views and spaces for this frame: its display time plus the prediction offset
+    pub fn pose_time(&self, frame_state: &FrameState) -> Time {
+        Time::from_nanos(
+            frame_state.predicted_display_time.as_nanos() + self.prediction_offset.as_nanos(),
+        )
+    }
+
     pub fn view_count(&self) -> usize {
         self.view_config_views.len()
     }
 
+    /// whether the eyes share one texture array swapchain, see [XrConfig::multiview]
+    pub fn is_multiview(&self) -> bool {
+        self.multiview
+    }
+
+    /// where [OpenXRComponent::xr_space]'s origin is in the LOCAL space; create other spaces
+    /// that have to agree with it at this pose
+    pub fn space_origin(&self) -> Posef {
+        self.space_origin
+    }
+
+    /// Move the user `delta` meters through the scene, e.g. to teleport:
+    /// [OpenXRComponent::xr_space] is created again with its origin moved the other way, so
+    /// the views and controllers located in it from now on are `delta` further along.
+    pub fn move_user(&mut self, delta: &XrVector3f) -> Result<(), XrErrorWrapped> {
+        let rotation = xr_matrix4x4f_create_from_quaternion(&self.space_origin.orientation.into());
+        let shift = xr_matrix4x4f_transform_vector3f(&rotation, delta);
+        let mut origin = self.space_origin;
+        origin.position.x -= shift.x;
+        origin.position.y -= shift.y;
+        origin.position.z -= shift.z;
+        self.set_space_origin(origin)
+    }
+
+    /// Put [OpenXRComponent::xr_space]'s origin at `origin` in the LOCAL space.
+    pub fn set_space_origin(&mut self, origin: Posef) -> Result<(), XrErrorWrapped> {
+        self.xr_space = self
+            .xr_session
+            .create_reference_space(ReferenceSpaceType::LOCAL, origin)
+            .annotate_if_err(
+                Some(&self.xr_instance),
+                "failed to move the reference space",
+            )?;
+        self.space_origin = origin;
+        Ok(())
+    }
+
+    /// Make the eye swapchains `scale` times the runtime's recommended size (within its
+    /// maximum) from the next [OpenXRComponent::recreate_swapchains] on.
+    pub fn set_render_scale(&mut self, scale: f32) {
+        let scale = scale.max(0.1);
+        if scale != self.render_scale {
+            self.render_scale = scale;
+            self.swapchains_stale = true;
+        }
+    }
+
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    /// Whether the eye swapchains no longer match what they should be: the render scale
+    /// changed, the session restarted, or [OpenXRComponent::check_recommended_size] found a
+    /// new recommendation.  Call [OpenXRComponent::recreate_swapchains] between frames.
+    pub fn swapchains_stale(&self) -> bool {
+        self.swapchains_stale
+    }
+
+    /// Counts [OpenXRComponent::recreate_swapchains], so whatever was sized after the old
+    /// swapchains (depth buffers, offscreen targets) can tell it has to follow.
+    pub fn swapchain_generation(&self) -> u64 {
+        self.swapchain_generation
+    }
+
+    /// Ask the runtime for its recommended view sizes again, since a change in the system
+    /// settings does not come with an event.  Returns whether they changed, which also makes
+    /// the swapchains [OpenXRComponent::swapchains_stale].
+    pub fn check_recommended_size(&mut self) -> Result<bool, XrErrorWrapped> {
+        let views = self.scaled_views()?;
+        let changed = views.len() != self.view_config_views.len()
+            || views.iter().zip(&self.view_config_views).any(|(a, b)| {
+                a.recommended_image_rect_width != b.recommended_image_rect_width
+                    || a.recommended_image_rect_height != b.recommended_image_rect_height
+            });
+        if changed {
+            self.swapchains_stale = true;
+        }
+        Ok(changed)
+    }
+
+    /// Replace the eye swapchains with new ones at the runtime's current recommended size
+    /// times the render scale.  Call between frames, never with an image acquired.  The old
+    /// images go away, so framebuffers that had them attached have to attach the new ones,
+    /// and anything sized after them should be made again; see
+    /// [OpenXRComponent::swapchain_generation].  If this fails there are no eye swapchains
+    /// until a later call succeeds.  If only the [OpenXRComponent::depth_layer]'s fail, frames
+    /// go out without depth until then.
+    pub fn recreate_swapchains(&mut self) -> Result<(), XrErrorWrapped> {
+        let views = self.scaled_views()?;
+        // before making the new ones; a runtime may only have memory for one set
+        self.xr_swapchain_images.clear();
+        self.xr_swapchains.clear();
+
+        let (swapchains, images) = create_eye_swapchains(
+            &self.xr_instance,
+            &self.xr_session,
+            &views,
+            self.swapchain_format,
+            self.multiview,
+        )?;
+        self.xr_swapchains = swapchains;
+        self.xr_swapchain_images = images;
+        self.view_config_views = views;
+        self.swapchains_stale = false;
+        self.swapchain_generation += 1;
+        if let Some(depth_layer) = &mut self.depth_layer {
+            // the eye swapchains are fine without it
+            if let Err(e) = depth_layer.recreate(&self.xr_session, &self.view_config_views) {
+                warn!(
+                    "failed to recreate the depth swapchains, submitting without depth {}",
+                    e
+                );
+            }
+        }
+        info!(
+            "recreated {} swapchains at {}x{} (render scale {})",
+            self.xr_swapchains.len(),
+            self.view_config_views[0].recommended_image_rect_width,
+            self.view_config_views[0].recommended_image_rect_height,
+            self.render_scale
+        );
+        Ok(())
+    }
+
+    /// the runtime's current view configuration, scaled by [OpenXRComponent::render_scale]
+    fn scaled_views(&self) -> Result<Vec<ViewConfigurationView>, XrErrorWrapped> {
+        let views = self
+            .xr_instance
+            .enumerate_view_configuration_views(
+                self.system_id,
+                ViewConfigurationType::PRIMARY_STEREO,
+            )
+            .annotate_if_err(
+                Some(&self.xr_instance),
+                "failed to enumerate configuration views",
+            )?;
+        Ok(views
+            .into_iter()
+            .map(|mut view| {
+                let scale = |recommended: u32, max: u32| {
+                    ((recommended as f32 * self.render_scale).round() as u32).clamp(1, max.max(1))
+                };
+                view.recommended_image_rect_width =
+                    scale(view.recommended_image_rect_width, view.max_image_rect_width);
+                view.recommended_image_rect_height = scale(
+                    view.recommended_image_rect_height,
+                    view.max_image_rect_height,
+                );
+                view
+            })
+            .collect())
+    }
+
     pub fn poll_till_no_events(&mut self) -> Result<LoopStatus, XrResult> {
         let openxr_bits = self;
         let mut event_data_buffer = EventDataBuffer::new();
         loop {
             match openxr_bits.xr_instance.poll_event(&mut event_data_buffer) {
-                Ok(Some(evt)) => {
-                    if let Event::SessionStateChanged(ch) = evt {
-                        if let SessionState::STOPPING = ch.state() {
+                Ok(Some(evt)) => match evt {
+                    Event::SessionStateChanged(ch) => {
+                        info!("session state {:?}", ch.state());
+                        openxr_bits.session_state = ch.state();
+                        // the first READY was waited out before the session began, so this
+                        // one is a restart, after which the recommended size may differ
+                        if ch.state() == SessionState::READY {
+                            openxr_bits.swapchains_stale = true;
+                        }
+                        if ch.state() == SessionState::STOPPING {
                             return Ok(LoopStatus::PleaseStop);
                         }
+                        if ch.state() == SessionState::LOSS_PENDING {
+                            return Ok(LoopStatus::RuntimeLost);
+                        }
                     }
-                    info!(
-                        "ignoring event ",
-                        //event_data_buffer.ty.into_raw()
-                    );
-                }
+                    Event::PerfSettingsEXT(perf) => {
+                        let notification = PerformanceNotification {
+                            domain: perf.domain(),
+                            sub_domain: perf.sub_domain(),
+                            from_level: perf.from_level(),
+                            to_level: perf.to_level(),
+                        };
+                        info!("performance notification {:?}", notification);
+                        openxr_bits.performance_notifications.push(notification);
+                    }
+                    Event::InteractionProfileChanged(_) => {
+                        info!("interaction profile changed");
+                        openxr_bits.interaction_profile_changed = true;
+                    }
+                    Event::InstanceLossPending(_) => {
+                        warn!("the runtime is going away");
+                        return Ok(LoopStatus::RuntimeLost);
+                    }
+                    _ => {
+                        info!(
+                            "ignoring event ",
+                            //event_data_buffer.ty.into_raw()
+                        );
+                    }
+                },
                 Ok(None) => return Ok(LoopStatus::Groovy), // EVENT_UNAVAILALBE,
+                Err(XrResult::ERROR_INSTANCE_LOST) => return Ok(LoopStatus::RuntimeLost),
                 Err(result) => return Err(result),
             };
         }
@@ -252,43 +758,94 @@ impl<G: Graphics> OpenXRComponent<G> {
     /// Get the frame state and provide it to the `before_paint` closure to
     /// calculate app-specific data.
     /// Then use the `paint_one_view` closure with that app-specific data to
-    /// render all the camera views needed by the openxr system
+    /// render all the camera views needed by the openxr system, and the view's
+    /// [OpenXRComponent::space_warp] images and [OpenXRComponent::depth_layer] image while
+    /// those are enabled.  Each view gets this
+    /// frame's [FrameArena] for its short-lived lists.
+    /// With [OpenXRComponent::is_multiview], `paint_views_at_once` paints every view into
+    /// the layers of one texture array image instead, without space warp or depth images.
+    #[allow(clippy::type_complexity)]
     pub fn paint_vr_multiview<T>(
         &mut self,
         before_paint: impl FnOnce(&Self, &FrameState) -> T,
-        mut paint_one_view: impl FnMut(&View, &ViewConfigurationView, Time, &G::SwapchainImage, &mut T),
+        mut paint_one_view: impl FnMut(
+            &View,
+            &ViewConfigurationView,
+            Time,
+            &G::SwapchainImage,
+            Option<&SpaceWarpImages<G>>,
+            Option<&G::SwapchainImage>,
+            &FrameArena,
+            &mut T,
+        ),
+        mut paint_views_at_once: impl FnMut(
+            &[View],
+            &ViewConfigurationView,
+            Time,
+            &G::SwapchainImage,
+            &FrameArena,
+            &mut T,
+        ),
         mut after_paint: impl FnMut(&Self, &FrameState, T),
         view_configuration_type: ViewConfigurationType,
     ) -> Result<(), XrErrorWrapped> {
+        self.heartbeat.enter("wait frame");
         let frame_state = self
             .frame_waiter
             .wait()
             .annotate_if_err(None, "failed to wait for frame")?;
         let predicted_display_time: Time = frame_state.predicted_display_time;
+        let pose_time = self.pose_time(&frame_state);
+        self.frame_arena.reset();
 
+        self.heartbeat.enter("begin frame");
         self.frame_stream
             .begin()
             .annotate_if_err(None, "failed to frame_stream.begin")?;
 
         let (_flags, views) = self
             .xr_session
-            .locate_views(
-                view_configuration_type,
-                predicted_display_time,
-                &self.xr_space,
-            )
+            .locate_views(view_configuration_type, pose_time, &self.xr_space)
             .annotate_if_err(None, "failed to locate_views")?;
 
         let mut malfunctions = vec![];
 
+        self.heartbeat.enter("before paint");
         let mut arg = before_paint(self, &frame_state);
 
-        for (swapchain, sci, view_i, vcv) in izip!(
+        if self.multiview {
+            if let (Some(swapchain), Some(images), Some(vcv)) = (
+                self.xr_swapchains.first_mut(),
+                self.xr_swapchain_images.first(),
+                self.view_config_views.first(),
+            ) {
+                let arena = &self.frame_arena;
+                let painted = paint_swapchain_image(swapchain, &mut self.heartbeat, |index| {
+                    paint_views_at_once(
+                        &views,
+                        vcv,
+                        predicted_display_time,
+                        &images[index as usize],
+                        arena,
+                        &mut arg,
+                    )
+                });
+                if let Err(e) = painted {
+                    malfunctions.push(e);
+                }
+            }
+        }
+
+        let per_eye = if self.multiview { 0 } else { views.len() };
+        for (eye, (swapchain, sci, view_i, vcv)) in izip!(
             self.xr_swapchains.iter_mut(),
             &self.xr_swapchain_images,
-            views.iter(),
+            views.iter().take(per_eye),
             self.view_config_views.iter(),
-        ) {
+        )
+        .enumerate()
+        {
+            self.heartbeat.enter("acquire swapchain image");
             let buffer_index = match swapchain.acquire_image() {
                 Ok(x) => x,
                 Err(result) => {
@@ -301,6 +858,7 @@ impl<G: Graphics> OpenXRComponent<G> {
                 }
             };
 
+            self.heartbeat.enter("wait swapchain image");
             if let Err(result) = swapchain.wait_image(XrDuration::INFINITE) {
                 malfunctions.push(XrErrorWrapped::build(
                     result,
@@ -311,8 +869,43 @@ impl<G: Graphics> OpenXRComponent<G> {
             };
 
             let color_buffer = &sci[buffer_index as usize];
-
-            paint_one_view(view_i, vcv, predicted_display_time, color_buffer, &mut arg);
+            let arena = &self.frame_arena;
+            self.heartbeat.enter("paint view");
+
+            let mut paint_with = |images: Option<&SpaceWarpImages<G>>| match &mut self.depth_layer {
+                Some(depth_layer) => depth_layer.paint_eye(eye, |depth| {
+                    paint_one_view(
+                        view_i,
+                        vcv,
+                        predicted_display_time,
+                        color_buffer,
+                        images,
+                        depth,
+                        arena,
+                        &mut arg,
+                    )
+                }),
+                None => {
+                    paint_one_view(
+                        view_i,
+                        vcv,
+                        predicted_display_time,
+                        color_buffer,
+                        images,
+                        None,
+                        arena,
+                        &mut arg,
+                    );
+                    Ok(())
+                }
+            };
+            let painted = match &mut self.space_warp {
+                Some(space_warp) => space_warp.paint_eye(eye, paint_with).and_then(|p| p),
+                None => paint_with(None),
+            };
+            if let Err(e) = painted {
+                malfunctions.push(e);
+            }
 
             if let Err(result) = swapchain.release_image() {
                 malfunctions.push(XrErrorWrapped::build(
@@ -324,6 +917,7 @@ impl<G: Graphics> OpenXRComponent<G> {
             }
         }
 
+        self.heartbeat.enter("after paint");
         after_paint(self, &frame_state, arg);
 
         for err in &malfunctions {
@@ -333,34 +927,72 @@ impl<G: Graphics> OpenXRComponent<G> {
             (Err(err))?;
         }
 
-        let projection_views: Vec<_> = {
-            izip!(
-                views.iter(),
-                self.xr_swapchains.iter(),
-                self.view_config_views.iter()
-            )
-            .map(|(view, swapchain, view_config_view)| {
-                projection_view_for(view, swapchain, view_config_view)
-            })
-            .collect()
+        let arena = &self.frame_arena;
+        // chained onto the projection views, so they must outlive the submission
+        let space_warp_infos = match &self.space_warp {
+            Some(space_warp) => space_warp.layer_infos(arena),
+            None => ArenaVec::new_in(arena),
         };
+        // ahead of the space warp infos in each view's chain
+        let depth_infos = match &self.depth_layer {
+            Some(depth_layer) => {
+                depth_layer.layer_infos(arena, |eye| chain_next(space_warp_infos.get(eye)))
+            }
+            None => ArenaVec::new_in(arena),
+        };
+        let mut projection_views = ArenaVec::with_capacity_in(views.len(), arena);
+        // the one array swapchain for every eye, or one each
+        let swapchains = self.xr_swapchains.iter().cycle().take(if self.multiview {
+            views.len()
+        } else {
+            self.xr_swapchains.len()
+        });
+        projection_views.extend(
+            izip!(views.iter(), swapchains, self.view_config_views.iter())
+                .enumerate()
+                .map(|(eye, (view, swapchain, view_config_view))| {
+                    let layer = if self.multiview { eye as u32 } else { 0 };
+                    let view = projection_view_for_layer(view, swapchain, view_config_view, layer);
+                    match (depth_infos.get(eye), space_warp_infos.get(eye)) {
+                        (Some(depth), _) => chain_depth(view, depth),
+                        (None, Some(info)) => chain_space_warp(view, info),
+                        (None, None) => view,
+                    }
+                }),
+        );
 
         {
+            let (underlays, overlays) = self.layers.build(&self.xr_space, arena);
+            // let the underlays show through wherever the eye buffers are transparent
+            let projection_flags = if underlays.is_empty() {
+                CompositionLayerFlags::EMPTY
+            } else {
+                CompositionLayerFlags::BLEND_TEXTURE_SOURCE_ALPHA
+            };
             let projection_layer = CompositionLayerProjection::new()
-                .layer_flags(CompositionLayerFlags::EMPTY)
+                .layer_flags(projection_flags)
                 .space(&self.xr_space)
-                .views(projection_views.as_slice());
+                .views(&projection_views);
 
-            let projection_layers: Vec<&CompositionLayerBase<G>> = vec![&projection_layer];
+            let mut layers: ArenaVec<&CompositionLayerBase<G>> =
+                ArenaVec::with_capacity_in(underlays.len() + 1 + overlays.len(), arena);
+            layers.extend(underlays.iter().map(BuiltLayer::base));
+            layers.push(&projection_layer);
+            layers.extend(overlays.iter().map(BuiltLayer::base));
 
+            self.heartbeat.enter("end frame");
             self.frame_stream
                 .end(
                     predicted_display_time,
                     EnvironmentBlendMode::OPAQUE,
-                    projection_layers.as_slice(),
+                    &layers,
                 )
                 .annotate_if_err(None, "failed to frame_stream.end")?;
         }
+        if let Some(space_warp) = &mut self.space_warp {
+            space_warp.frame_ended();
+        }
+        self.heartbeat.idle();
 
         Ok(())
     }
@@ -374,6 +1006,44 @@ impl<G: Graphics> OpenXRComponent<G> {
     }
 }
 
+impl OpenXRComponent<OpenGlEs> {
+    /// Create [OpenXRComponent::space_warp], with half-float motion vectors and 24-bit depth.
+    /// Fails if the runtime does not offer XR_FB_space_warp.
+    pub fn enable_space_warp(&mut self) -> Result<(), XrErrorWrapped> {
+        if self.space_warp.is_none() {
+            self.space_warp = Some(SpaceWarp::new(
+                &self.xr_instance,
+                self.system_id,
+                &self.xr_session,
+                self.view_count(),
+                gl::RGBA16F,
+                gl::DEPTH_COMPONENT24,
+            )?);
+        }
+        Ok(())
+    }
+
+    /// Create [OpenXRComponent::depth_layer], with 24-bit depth, and a stencil if `stencil`
+    /// (the eye painting draws into these instead of a depth buffer of its own).  Fails if
+    /// the runtime does not offer XR_KHR_composition_layer_depth.
+    pub fn enable_depth_layer(&mut self, stencil: bool) -> Result<(), XrErrorWrapped> {
+        if self.depth_layer.is_none() {
+            let format = if stencil {
+                gl::DEPTH24_STENCIL8
+            } else {
+                gl::DEPTH_COMPONENT24
+            };
+            self.depth_layer = Some(DepthLayer::new(
+                &self.xr_instance,
+                &self.xr_session,
+                &self.view_config_views,
+                format,
+            )?);
+        }
+        Ok(())
+    }
+}
+
 #[cfg(target_os = "android")]
 impl OpenXRComponent<OpenGlEs> {
     /// # Safety
@@ -393,6 +1063,7 @@ impl OpenXRComponent<OpenGlEs> {
     pub fn new_android(
         gl_display: *mut c_void,
         gl_context: *mut c_void,
+        config: &XrConfig,
     ) -> Result<Self, XrErrorWrapped> {
         let entry: Entry = Entry::linked();
         {
@@ -400,14 +1071,14 @@ impl OpenXRComponent<OpenGlEs> {
                 return Err(XrErrorWrapped::simple(format!(
                     "failed to initialize android loader  : {}",
                     e
-                )));
+                ))
+                .with_unavailable(RuntimeUnavailable::Loader));
             }
         }
 
-        let mut gl_major_version = -1;
-        let mut gl_minor_version = -1;
-        unsafe { gl::GetIntegerv(gl::MAJOR_VERSION, &mut gl_major_version) };
-        unsafe { gl::GetIntegerv(gl::MINOR_VERSION, &mut gl_minor_version) };
+        // GLES 2 has no GL_MAJOR_VERSION to ask for
+        let caps = gl_caps();
+        let (gl_major_version, gl_minor_version) = (caps.major as GLint, caps.minor as GLint);
         let session_pre_check =
             |instance: &Instance, system_id: SystemId| -> Result<(), XrErrorWrapped> {
                 debug!("time to check the version requirements");
@@ -423,15 +1094,68 @@ impl OpenXRComponent<OpenGlEs> {
         };
 
         let acceptable_format = |&fmt: &u32| {
-            fmt == gl::RGBA8
-                || fmt == gl::RGBA8_SNORM
-                || (fmt == gl::SRGB8_ALPHA8 && gl_major_version >= 3)
+            fmt == gl::RGBA8 || fmt == gl::RGBA8_SNORM || (fmt == gl::SRGB8_ALPHA8 && caps.srgb)
         };
 
-        Self::new(&entry, &info, acceptable_format, session_pre_check)
+        Self::new(&entry, &info, acceptable_format, session_pre_check, config)
     }
 }
 
+/// one swapchain per view at its recommended size, and their images
+#[allow(clippy::type_complexity)]
+fn create_eye_swapchains<G: Graphics>(
+    instance: &Instance,
+    session: &Session<G>,
+    view_config_views: &[ViewConfigurationView],
+    swapchain_format: G::Format,
+    multiview: bool,
+) -> Result<(Vec<Swapchain<G>>, Vec<Vec<G::SwapchainImage>>), XrErrorWrapped> {
+    // a multiview swapchain is sized after the first view, with a layer for each
+    let (views, array_size) = if multiview {
+        (&view_config_views[..1], view_config_views.len() as u32)
+    } else {
+        (view_config_views, 1)
+    };
+    let mut xr_swapchains = vec![];
+    for view_config_i in views.iter() {
+        debug!(
+            "view config recommended size {}x{}",
+            view_config_i.recommended_image_rect_width, view_config_i.recommended_image_rect_height
+        );
+        let swapchain_create_info = SwapchainCreateInfo::<G> {
+            create_flags: SwapchainCreateFlags::EMPTY,
+            usage_flags: SwapchainUsageFlags::SAMPLED | SwapchainUsageFlags::COLOR_ATTACHMENT,
+            format: swapchain_format,
+            sample_count: 1,
+            width: view_config_i.recommended_image_rect_width,
+            height: view_config_i.recommended_image_rect_height,
+            face_count: 1,
+            array_size,
+            mip_count: 1,
+        };
+        let swapchain = session
+            .create_swapchain(&swapchain_create_info)
+            .annotate_if_err(Some(instance), "failed to create swapchain")?;
+
+        xr_swapchains.push(swapchain);
+    }
+
+    debug!(
+        "fetching swapchain images for {} swapchains",
+        xr_swapchains.len()
+    );
+    let mut swapchain_images = vec![];
+    for (i, swapchain) in xr_swapchains.iter().enumerate() {
+        let images = swapchain
+            .enumerate_images()
+            .annotate_if_err(Some(instance), "failed to enumerate swapchain images")?;
+        debug!("swapchain[{}] has {} images", i, images.len());
+        swapchain_images.push(images);
+    }
+
+    Ok((xr_swapchains, swapchain_images))
+}
+
 pub fn message_for_error(instance: &openxr_sys::Instance, result: XrResult) -> String {
     let mut msg = [0; MAX_RESULT_STRING_SIZE];
     if XrResult::SUCCESS.into_raw()
@@ -476,6 +1200,16 @@ pub fn projection_view_for<'a, G: Graphics>(
     view: &View,
     swapchain: &'a Swapchain<G>,
     view_config_view: &ViewConfigurationView,
+) -> openxr::CompositionLayerProjectionView<'a, G> {
+    projection_view_for_layer(view, swapchain, view_config_view, 0)
+}
+
+/// like [projection_view_for], showing layer `array_index` of a texture array swapchain
+pub fn projection_view_for_layer<'a, G: Graphics>(
+    view: &View,
+    swapchain: &'a Swapchain<G>,
+    view_config_view: &ViewConfigurationView,
+    array_index: u32,
 ) -> openxr::CompositionLayerProjectionView<'a, G> {
     openxr::CompositionLayerProjectionView::new()
         .pose(view.pose)
@@ -490,88 +1224,107 @@ pub fn projection_view_for<'a, G: Graphics>(
                         height: view_config_view.recommended_image_rect_height as i32,
                     },
                 })
-                .image_array_index(0),
+                .image_array_index(array_index),
         )
 }
 
+/// Acquire an image of `swapchain`, wait for it, let `paint` draw into the image at the
+/// index it gets, and release it.
+fn paint_swapchain_image<G: Graphics>(
+    swapchain: &mut Swapchain<G>,
+    heartbeat: &mut Heartbeat,
+    paint: impl FnOnce(u32),
+) -> Result<(), XrErrorWrapped> {
+    heartbeat.enter("acquire swapchain image");
+    let index = swapchain
+        .acquire_image()
+        .annotate_if_err(None, "failed to acquire swapchain image")?;
+    heartbeat.enter("wait swapchain image");
+    swapchain
+        .wait_image(XrDuration::INFINITE)
+        .annotate_if_err(None, "failed to wait for swapchain image")?;
+    heartbeat.enter("paint view");
+    paint(index);
+    swapchain
+        .release_image()
+        .annotate_if_err(None, "failed to release swapchain image")
+}
+
 //
 
-pub struct RightHandTracker {
+/// One controller's grip pose
+pub struct HandTracker {
+    pub hand: Hand,
+    /// `left_hand_pose` or `right_hand_pose`, see [HandTracker::binding]
+    pub action: Action<Posef>,
     pub space: Space,
 }
 
-impl RightHandTracker {
+impl HandTracker {
+    /// Creates a `left_hand_pose` or `right_hand_pose` action in `action_set`, so one set can
+    /// hold a tracker for each hand.  Its binding is not suggested here: suggesting replaces
+    /// whatever was suggested before for the same interaction profile, so add
+    /// [HandTracker::binding] to the rest of the app's bindings for [GRIP_PROFILES].
     pub fn new<G: Graphics>(
         instance: &Instance,
         xr_session: &Session<G>,
         action_set: &ActionSet,
+        hand: Hand,
     ) -> Result<Self, XrErrorWrapped> {
-        let user_hand_left = instance
-            .string_to_path("/user/hand/left")
-            .annotate_if_err(Some(instance), "failed to ")?;
-        let user_hand_right = instance
-            .string_to_path("/user/hand/right")
-            .annotate_if_err(Some(instance), "failed to ")?;
-        let pose_action = action_set
-            .create_action::<Posef>(
-                "hand_pose",
-                "controller 1",
-                &[user_hand_left, user_hand_right],
-            )
-            .annotate_if_err(Some(instance), "failed to ")?;
-        let left_grip_pose = instance
-            .string_to_path("/user/hand/left/input/grip/pose")
-            .annotate_if_err(Some(instance), "failed to ")?;
-        let right_grip_pose = instance
-            .string_to_path("/user/hand/right/input/grip/pose")
-            .annotate_if_err(Some(instance), "failed to ")?;
-        let bindings = [
-            Binding::new(&pose_action, left_grip_pose),
-            Binding::new(&pose_action, right_grip_pose),
-        ];
-        {
-            let interaction_profile = instance
-                .string_to_path("/interaction_profiles/khr/simple_controller")
-                .annotate_if_err(Some(instance), "failed to ")?;
-
-            instance
-                .suggest_interaction_profile_bindings(interaction_profile, &bindings)
-                .annotate_if_err(Some(instance), "failed to ")?;
-        }
-
-        {
-            let interaction_profile = instance
-                .string_to_path("/interaction_profiles/oculus/touch_controller")
-                .annotate_if_err(Some(instance), "failed to ")?;
-            instance
-                .suggest_interaction_profile_bindings(interaction_profile, &bindings)
-                .annotate_if_err(Some(instance), "failed to ")?;
-        }
+        let (name, localized) = if hand == Hand::LEFT {
+            ("left_hand_pose", "left controller pose")
+        } else {
+            ("right_hand_pose", "right controller pose")
+        };
+        let hand_path = hand_path(instance, hand)?;
+        let action = action_set
+            .create_action::<Posef>(name, localized, &[hand_path])
+            .annotate_if_err(Some(instance), format!("failed to create {} action", name))?;
 
         let mut posef = Posef::default();
         posef.orientation.w = 1.0;
-        let space = pose_action
-            .create_space(xr_session.clone(), user_hand_right, posef)
-            .annotate_if_err(Some(instance), "failed to ")?;
+        let space = action
+            .create_space(xr_session.clone(), hand_path, posef)
+            .annotate_if_err(Some(instance), "failed to create hand space")?;
+        Ok(Self {
+            hand,
+            action,
+            space,
+        })
+    }
 
-        Ok(Self { space })
+    /// The grip pose, which has the same path in each of [GRIP_PROFILES]
+    pub fn binding(&self, instance: &Instance) -> Result<Binding<'_>, XrErrorWrapped> {
+        let grip = if self.hand == Hand::LEFT {
+            "/user/hand/left/input/grip/pose"
+        } else {
+            "/user/hand/right/input/grip/pose"
+        };
+        let grip = instance
+            .string_to_path(grip)
+            .annotate_if_err(Some(instance), "failed to make grip path")?;
+        Ok(Binding::new(&self.action, grip))
     }
 
+    /// an action set of its own, attached to `xr_session` with just this in it and its
+    /// binding the only one suggested
     pub fn action_set_from<G: Graphics>(
         instance: &Instance,
         xr_session: &Session<G>,
+        hand: Hand,
     ) -> Result<(ActionSet, Self), XrErrorWrapped> {
         let action_set = instance
             .create_action_set("pants", "pants", 0)
             .annotate_if_err(Some(instance), "failed to create_action_set")?;
 
-        let right_hand_tracker = Self::new(instance, xr_session, &action_set)?;
+        let hand_tracker = Self::new(instance, xr_session, &action_set, hand)?;
+        suggest_grip_bindings(instance, &[hand_tracker.binding(instance)?])?;
 
         xr_session
             .attach_action_sets(&[&action_set])
             .annotate_if_err(Some(instance), "failed to attach_action_sets")?;
 
-        Ok((action_set, right_hand_tracker))
+        Ok((action_set, hand_tracker))
     }
 
     pub fn locate(&self, base: &Space, time: Time) -> Result<SpaceLocation, XrResult> {
@@ -579,6 +1332,195 @@ impl RightHandTracker {
     }
 }
 
+/// Both controllers' grip poses, e.g. to hold a different model in each
+pub struct BothHands {
+    pub left: HandTracker,
+    pub right: HandTracker,
+}
+
+impl BothHands {
+    /// A [HandTracker] for each hand in `action_set`.  As with [HandTracker::new], add
+    /// [BothHands::bindings] to the bindings the app suggests.
+    pub fn new<G: Graphics>(
+        instance: &Instance,
+        xr_session: &Session<G>,
+        action_set: &ActionSet,
+    ) -> Result<Self, XrErrorWrapped> {
+        Ok(Self {
+            left: HandTracker::new(instance, xr_session, action_set, Hand::LEFT)?,
+            right: HandTracker::new(instance, xr_session, action_set, Hand::RIGHT)?,
+        })
+    }
+
+    /// both grips, see [HandTracker::binding]
+    pub fn bindings(&self, instance: &Instance) -> Result<Vec<Binding<'_>>, XrErrorWrapped> {
+        Ok(vec![
+            self.left.binding(instance)?,
+            self.right.binding(instance)?,
+        ])
+    }
+
+    /// an action set of its own, attached to `xr_session` with just these in it and their
+    /// bindings the only ones suggested
+    pub fn action_set_from<G: Graphics>(
+        instance: &Instance,
+        xr_session: &Session<G>,
+    ) -> Result<(ActionSet, Self), XrErrorWrapped> {
+        let action_set = instance
+            .create_action_set("pants", "pants", 0)
+            .annotate_if_err(Some(instance), "failed to create_action_set")?;
+
+        let hands = Self::new(instance, xr_session, &action_set)?;
+        suggest_grip_bindings(instance, &hands.bindings(instance)?)?;
+
+        xr_session
+            .attach_action_sets(&[&action_set])
+            .annotate_if_err(Some(instance), "failed to attach_action_sets")?;
+
+        Ok((action_set, hands))
+    }
+
+    pub fn get(&self, hand: Hand) -> &HandTracker {
+        if hand == Hand::LEFT {
+            &self.left
+        } else {
+            &self.right
+        }
+    }
+}
+
+/// the interaction profiles [HandTracker::binding] fits
+pub const GRIP_PROFILES: [&str; 2] = [
+    "/interaction_profiles/khr/simple_controller",
+    "/interaction_profiles/oculus/touch_controller",
+];
+
+fn hand_path(instance: &Instance, hand: Hand) -> Result<Path, XrErrorWrapped> {
+    let name = if hand == Hand::LEFT {
+        "/user/hand/left"
+    } else {
+        "/user/hand/right"
+    };
+    instance
+        .string_to_path(name)
+        .annotate_if_err(Some(instance), format!("failed to make path {}", name))
+}
+
+/// suggest `bindings`, and nothing else, for each of [GRIP_PROFILES]
+fn suggest_grip_bindings(instance: &Instance, bindings: &[Binding]) -> Result<(), XrErrorWrapped> {
+    for profile in GRIP_PROFILES {
+        let interaction_profile = instance
+            .string_to_path(profile)
+            .annotate_if_err(Some(instance), "failed to make interaction profile path")?;
+        instance
+            .suggest_interaction_profile_bindings(interaction_profile, bindings)
+            .annotate_if_err(
+                Some(instance),
+                format!("failed to suggest {} bindings", profile),
+            )?;
+    }
+    Ok(())
+}
+
+//
+
+/// One joint of a [HandJoints], where XR_EXT_hand_tracking put it
+#[derive(Copy, Clone, Debug)]
+pub struct HandJoint {
+    pub pose: Posef,
+    /// meters, from the joint's center to the skin
+    pub radius: f32,
+    /// both the position and the orientation are valid; otherwise the pose is stale or zero
+    pub tracked: bool,
+}
+
+impl HandJoint {
+    /// from the joint's space into the base space it was located in
+    pub fn matrix(&self) -> XrMatrix4x4f {
+        pose_matrix(&self.pose)
+    }
+}
+
+/// All [HAND_JOINT_COUNT] joints of one hand at one time, indexed by [HandJointEXT]
+#[derive(Copy, Clone, Debug)]
+pub struct HandJoints {
+    pub joints: [HandJoint; HAND_JOINT_COUNT],
+}
+
+impl HandJoints {
+    pub fn from_locations(locations: &HandJointLocations) -> Self {
+        let valid = SpaceLocationFlags::POSITION_VALID | SpaceLocationFlags::ORIENTATION_VALID;
+        Self {
+            joints: locations.map(|location| HandJoint {
+                pose: location.pose,
+                radius: location.radius,
+                tracked: location.location_flags.contains(valid),
+            }),
+        }
+    }
+
+    pub fn joint(&self, joint: HandJointEXT) -> &HandJoint {
+        &self.joints[joint.into_raw() as usize]
+    }
+
+    pub fn all_tracked(&self) -> bool {
+        self.joints.iter().all(|joint| joint.tracked)
+    }
+}
+
+/// Both hands' joints, for drawing articulated hands.  Unlike [HandTracker] this
+/// follows the fingers, not the controller, so it only reports while the runtime is tracking
+/// bare hands.  Needs XR_EXT_hand_tracking, see [OpenXRComponent::supports_hand_tracking].
+pub struct HandJointTracker {
+    pub left: openxr::HandTracker,
+    pub right: openxr::HandTracker,
+}
+
+impl HandJointTracker {
+    pub fn new<G: Graphics>(
+        instance: &Instance,
+        xr_session: &Session<G>,
+    ) -> Result<Self, XrErrorWrapped> {
+        Ok(Self {
+            left: create_hand_tracker(instance, xr_session, Hand::LEFT)?,
+            right: create_hand_tracker(instance, xr_session, Hand::RIGHT)?,
+        })
+    }
+
+    pub fn tracker(&self, hand: Hand) -> &openxr::HandTracker {
+        if hand == Hand::LEFT {
+            &self.left
+        } else {
+            &self.right
+        }
+    }
+
+    /// `hand`'s joints at `time`, relative to `base`; `None` while the hand is not tracked
+    pub fn locate_hand(
+        &self,
+        hand: Hand,
+        base: &Space,
+        time: Time,
+    ) -> Result<Option<HandJoints>, XrErrorWrapped> {
+        let locations = base
+            .locate_hand_joints(self.tracker(hand), time)
+            .annotate_if_err(None, "failed to locate hand joints")?;
+        Ok(locations.as_ref().map(HandJoints::from_locations))
+    }
+
+    /// both hands' joints at `time`, relative to `base`, left then right; once per frame
+    pub fn locate(
+        &self,
+        base: &Space,
+        time: Time,
+    ) -> Result<[Option<HandJoints>; 2], XrErrorWrapped> {
+        Ok([
+            self.locate_hand(Hand::LEFT, base, time)?,
+            self.locate_hand(Hand::RIGHT, base, time)?,
+        ])
+    }
+}
+
 //
 
 /// the return value for our canned event processing loop
@@ -588,4 +1530,8 @@ pub enum LoopStatus {
     PleaseStop,
     /// Nothing weird happened, carry on
     Groovy,
+    /// The session or instance is lost or about to be (LOSS_PENDING, INSTANCE_LOSS_PENDING,
+    /// ERROR_INSTANCE_LOST).  Nothing will render again; drop the [OpenXRComponent] and
+    /// everything made with it, and create a new one once the runtime is back.
+    RuntimeLost,
 }
@@ -0,0 +1,50 @@
+//! XR_EXT_performance_settings: hints to the runtime about how hard to clock the CPU and GPU,
+//! and notifications from it when one of them is running out of headroom.
+
+use crate::errors::XrErrorWrapped;
+use openxr::{Graphics, Instance, Session};
+pub use openxr_sys::{
+    PerfSettingsDomainEXT, PerfSettingsLevelEXT, PerfSettingsNotificationLevelEXT,
+    PerfSettingsSubDomainEXT,
+};
+
+/// A copy of an `XrEventDataPerfSettingsEXT`, which only lives as long as the event buffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PerformanceNotification {
+    pub domain: PerfSettingsDomainEXT,
+    pub sub_domain: PerfSettingsSubDomainEXT,
+    pub from_level: PerfSettingsNotificationLevelEXT,
+    pub to_level: PerfSettingsNotificationLevelEXT,
+}
+
+pub struct PerformanceSettings {
+    set_performance_level: openxr_sys::pfn::PerfSettingsSetPerformanceLevelEXT,
+}
+
+impl PerformanceSettings {
+    /// `None` unless the instance was created with the extension enabled
+    pub fn new(instance: &Instance) -> Option<Self> {
+        instance.exts().ext_performance_settings.map(|ext| Self {
+            set_performance_level: ext.perf_settings_set_performance_level,
+        })
+    }
+
+    pub fn set_level<G: Graphics>(
+        &self,
+        instance: &Instance,
+        session: &Session<G>,
+        domain: PerfSettingsDomainEXT,
+        level: PerfSettingsLevelEXT,
+    ) -> Result<(), XrErrorWrapped> {
+        let result = unsafe { (self.set_performance_level)(session.as_raw(), domain, level) };
+        if result.into_raw() < 0 {
+            Err(XrErrorWrapped::build(
+                result,
+                Some(instance),
+                format!("failed to set {:?} performance level {:?}", domain, level),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! Ray queries against registered triangle meshes, for picking and line-of-sight tests
+//! such as whether a sound is behind a wall.  A [Bvh] over the meshes' boxes picks out the
+//! few a ray can hit, so scenes with hundreds of them stay cheap; within a mesh it is brute
+//! force, which is plenty for a few thousand triangles.
+
+use crate::bvh::{Aabb, Bvh};
+use crate::gl_helper::GLErrorWrapper;
+use crate::linear::{xr_matrix4x4f_invert, XrMatrix4x4f, XrVector3f};
+use crate::static_batch::{cross, transform_point, VertexLayout};
+use gl::types::GLuint;
+use std::cell::RefCell;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OccluderId(u64);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RayHit {
+    pub occluder: OccluderId,
+    /// along the ray, in units of its direction's length
+    pub distance: f32,
+}
+
+struct Occluder {
+    id: OccluderId,
+    /// mesh space
+    triangles: Vec<[[f32; 3]; 3]>,
+    /// mesh space
+    local_bounds: Aabb,
+    /// world space to mesh space; rays are brought into the mesh rather than the other way
+    /// around, so moving it does not touch the triangles
+    inverse: XrMatrix4x4f,
+}
+
+#[derive(Default)]
+pub struct Raycaster {
+    occluders: Vec<Occluder>,
+    /// each occluder's world space box, in the same order
+    bounds: Vec<Aabb>,
+    /// over [Raycaster::bounds]; dropped by adds and removes and built again by the next
+    /// cast, refitted by [Raycaster::set_transform]
+    tree: RefCell<Option<Bvh>>,
+    next_id: u64,
+}
+
+impl Raycaster {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a mesh, placed in the world by `world`.  The raycaster keeps its own copy
+    /// of the triangles, so later changes to the mesh need a [Raycaster::remove] and a new
+    /// add; moving it only needs [Raycaster::set_transform].
+    pub fn add_mesh<IT: Copy + Into<GLuint>>(
+        &mut self,
+        layout: VertexLayout,
+        vertices: &[f32],
+        indices: &[IT],
+        world: &XrMatrix4x4f,
+    ) -> Result<OccluderId, GLErrorWrapper> {
+        let positions: Vec<[f32; 3]> = vertices
+            .chunks_exact(layout.stride)
+            .map(|v| [0, 1, 2].map(|axis| v[layout.position_offset + axis]))
+            .collect();
+
+        let mut triangles = Vec::with_capacity(indices.len() / 3);
+        for triangle in indices.chunks_exact(3) {
+            let mut corners = [[0.0; 3]; 3];
+            for (corner, index) in corners.iter_mut().zip(triangle) {
+                let index: GLuint = (*index).into();
+                *corner = *positions.get(index as usize).ok_or_else(|| {
+                    GLErrorWrapper::with_message2(format!(
+                        "index {} is out of range for {} vertices",
+                        index,
+                        positions.len()
+                    ))
+                })?;
+            }
+            triangles.push(corners);
+        }
+
+        let local_bounds = Aabb::of_points(positions);
+
+        let id = OccluderId(self.next_id);
+        self.next_id += 1;
+        self.bounds.push(local_bounds.transformed(&world.m));
+        self.occluders.push(Occluder {
+            id,
+            triangles,
+            local_bounds,
+            inverse: xr_matrix4x4f_invert(world),
+        });
+        *self.tree.get_mut() = None;
+        Ok(id)
+    }
+
+    /// Move a mesh to `world`, refitting the tree around it.  False if it is not registered.
+    pub fn set_transform(&mut self, id: OccluderId, world: &XrMatrix4x4f) -> bool {
+        let Some(index) = self.occluders.iter().position(|o| o.id == id) else {
+            return false;
+        };
+        let occluder = &mut self.occluders[index];
+        occluder.inverse = xr_matrix4x4f_invert(world);
+        self.bounds[index] = occluder.local_bounds.transformed(&world.m);
+        if let Some(tree) = self.tree.get_mut() {
+            tree.refit(index, &self.bounds);
+        }
+        true
+    }
+
+    /// Build the tree again at the next cast.  Refitting keeps it correct, but after most
+    /// meshes have moved a long way from where they were, a fresh one is quicker to search.
+    pub fn rebuild(&mut self) {
+        *self.tree.get_mut() = None;
+    }
+
+    pub fn remove(&mut self, id: OccluderId) {
+        if let Some(index) = self.occluders.iter().position(|o| o.id == id) {
+            self.occluders.remove(index);
+            self.bounds.remove(index);
+            *self.tree.get_mut() = None;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.occluders.clear();
+        self.bounds.clear();
+        *self.tree.get_mut() = None;
+    }
+
+    pub fn len(&self) -> usize {
+        self.occluders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.occluders.is_empty()
+    }
+
+    /// The nearest triangle (either side) along `origin + t * direction` for `0 <= t <= max_t`.
+    pub fn cast(&self, origin: &XrVector3f, direction: &XrVector3f, max_t: f32) -> Option<RayHit> {
+        let origin = [origin.x, origin.y, origin.z];
+        let direction = [direction.x, direction.y, direction.z];
+        let mut tree = self.tree.borrow_mut();
+        let tree = tree.get_or_insert_with(|| Bvh::build(&self.bounds));
+        let (index, distance) = tree.cast(&origin, &direction, max_t, |index, limit| {
+            let occluder = &self.occluders[index];
+            // an affine map keeps `t` the same along the ray
+            let local_origin = transform_point(&occluder.inverse, &origin);
+            let tip = [0, 1, 2].map(|axis| origin[axis] + direction[axis]);
+            let local_tip = transform_point(&occluder.inverse, &tip);
+            let local_direction = [0, 1, 2].map(|axis| local_tip[axis] - local_origin[axis]);
+            occluder
+                .local_bounds
+                .ray_entry(&local_origin, &local_direction, limit)?;
+            occluder
+                .triangles
+                .iter()
+                .filter_map(|triangle| ray_triangle(&local_origin, &local_direction, triangle))
+                .filter(|&t| t <= limit)
+                .min_by(f32::total_cmp)
+        })?;
+        Some(RayHit {
+            occluder: self.occluders[index].id,
+            distance,
+        })
+    }
+
+    /// true if anything registered is in the way between `from` and `to`
+    pub fn is_blocked(&self, from: &XrVector3f, to: &XrVector3f) -> bool {
+        self.cast(from, &(*to - *from), 1.0).is_some()
+    }
+}
+
+//
+
+/// Möller–Trumbore; the `t` of the hit, if in front of the origin
+fn ray_triangle(origin: &[f32; 3], direction: &[f32; 3], triangle: &[[f32; 3]; 3]) -> Option<f32> {
+    let sub = |a: &[f32; 3], b: &[f32; 3]| [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    let dot = |a: &[f32; 3], b: &[f32; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+
+    let edge1 = sub(&triangle[1], &triangle[0]);
+    let edge2 = sub(&triangle[2], &triangle[0]);
+    let p = cross(direction, &edge2);
+    let determinant = dot(&edge1, &p);
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let s = sub(origin, &triangle[0]);
+    let u = dot(&s, &p) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = cross(&s, &edge1);
+    let v = dot(direction, &q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = dot(&edge2, &q) * inverse;
+    (t >= 0.0).then_some(t)
+}
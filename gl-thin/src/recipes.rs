@@ -0,0 +1,205 @@
+//! GL objects die with their context, which Android throws away on suspend or when the
+//! driver resets.  A recipe remembers how one resource was made (a texture from some image,
+//! a program from its sources, a mesh from its vertices) so a [RecipeBook] can make all of
+//! them again with a single [RecipeBook::recreate_all].
+
+use crate::fallback;
+use crate::gl_fancy::{GPUState, VertexBufferBundle};
+use crate::gl_helper::{GLErrorWrapper, Program, Texture};
+use gl::types::{GLenum, GLfloat, GLint, GLsizei, GLuint, GLushort};
+use std::cell::{Ref, RefCell};
+use std::rc::{Rc, Weak};
+
+/// How to make one resource from scratch, with the context current.
+pub trait Recipe {
+    type Output;
+
+    fn cook(&self, gpu_state: &mut GPUState) -> Result<Self::Output, GLErrorWrapper>;
+}
+
+/// A program from the source of its two shaders
+pub struct ProgramRecipe {
+    pub vertex_shader: String,
+    pub fragment_shader: String,
+}
+
+impl Recipe for ProgramRecipe {
+    type Output = Program;
+
+    fn cook(&self, _gpu_state: &mut GPUState) -> Result<Program, GLErrorWrapper> {
+        Program::compile(&self.vertex_shader, &self.fragment_shader)
+    }
+}
+
+/// Pixels for [TextureRecipe], e.g. an asset decoded again
+pub struct ImageData {
+    pub width: GLsizei,
+    pub height: GLsizei,
+    /// e.g. gl::RGBA; also the internal format
+    pub format: GLenum,
+    pub pixels: Vec<u8>,
+}
+
+/// A 2D texture from an image that `load` produces on demand, so the pixels need not stay in
+/// memory between uses, e.g. by decoding an asset again.  If `load` fails the texture is
+/// [fallback::white_image].
+pub struct TextureRecipe {
+    pub load: Box<dyn Fn() -> Result<ImageData, GLErrorWrapper>>,
+    pub mipmaps: bool,
+    /// e.g. gl::LINEAR_MIPMAP_LINEAR, or `None` to leave it at the default
+    pub min_filter: Option<GLenum>,
+}
+
+impl Recipe for TextureRecipe {
+    type Output = Texture;
+
+    fn cook(&self, gpu_state: &mut GPUState) -> Result<Texture, GLErrorWrapper> {
+        // a missing or broken image shows up white rather than taking the scene down
+        let image = (self.load)().unwrap_or_else(|e| {
+            log::warn!("failed to load texture, using a white pixel {}", e);
+            fallback::white_image()
+        });
+        let texture = Texture::new()?;
+        {
+            let mut bound = texture.bound(gl::TEXTURE_2D, gpu_state)?;
+            bound.write_pixels(
+                0,
+                image.format as GLint,
+                image.width,
+                image.height,
+                image.format,
+                &image.pixels,
+            )?;
+            if self.mipmaps {
+                bound.generate_mipmap()?;
+            }
+            if let Some(min_filter) = self.min_filter {
+                bound.set_parameter(gl::TEXTURE_MIN_FILTER, min_filter)?;
+            }
+        }
+        Ok(texture)
+    }
+}
+
+/// Vertex and index buffers with their attributes rigged, as for [VertexBufferBundle::new]
+pub struct MeshRecipe {
+    pub vertices: Rc<[GLfloat]>,
+    pub indices: Rc<[GLushort]>,
+    pub stride: GLsizei,
+    /// (attribute location, size, offset) in floats
+    pub attributes: Vec<(GLuint, GLint, GLsizei)>,
+}
+
+impl Recipe for MeshRecipe {
+    type Output = VertexBufferBundle<'static, GLfloat, GLushort>;
+
+    fn cook(&self, gpu_state: &mut GPUState) -> Result<Self::Output, GLErrorWrapper> {
+        VertexBufferBundle::new(
+            gpu_state,
+            self.vertices.to_vec().into(),
+            self.indices.to_vec().into(),
+            self.stride,
+            &self.attributes,
+        )
+    }
+}
+
+//
+
+/// A resource made by a [RecipeBook].  The object inside is replaced by
+/// [RecipeBook::recreate_all], so borrow it for a draw rather than keeping it.
+pub struct Cooked<T> {
+    inner: Rc<RefCell<T>>,
+}
+
+impl<T> Cooked<T> {
+    pub fn get(&self) -> Ref<'_, T> {
+        self.inner.borrow()
+    }
+}
+
+impl<T> Clone for Cooked<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// one recipe and a weak reference to what it made
+trait Entry {
+    /// `false` once every [Cooked] handle is gone, so the entry can be forgotten
+    fn is_alive(&self) -> bool;
+
+    fn recreate(&self, gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper>;
+}
+
+struct RecipeEntry<R: Recipe> {
+    recipe: R,
+    output: Weak<RefCell<R::Output>>,
+}
+
+impl<R: Recipe> Entry for RecipeEntry<R> {
+    fn is_alive(&self) -> bool {
+        self.output.strong_count() > 0
+    }
+
+    fn recreate(&self, gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper> {
+        let Some(output) = self.output.upgrade() else {
+            return Ok(());
+        };
+        let fresh = self.recipe.cook(gpu_state)?;
+        let stale = output.replace(fresh);
+        // its names belong to the old context, where deleting them is meaningless at best;
+        // at worst the new context handed out the same names to something else
+        std::mem::forget(stale);
+        Ok(())
+    }
+}
+
+/// Every resource made with [RecipeBook::cook], in the order they were made.
+#[derive(Default)]
+pub struct RecipeBook {
+    entries: Vec<Box<dyn Entry>>,
+}
+
+impl RecipeBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make a resource now, and remember how for [RecipeBook::recreate_all]
+    pub fn cook<R: Recipe + 'static>(
+        &mut self,
+        recipe: R,
+        gpu_state: &mut GPUState,
+    ) -> Result<Cooked<R::Output>, GLErrorWrapper> {
+        let inner = Rc::new(RefCell::new(recipe.cook(gpu_state)?));
+        self.entries.push(Box::new(RecipeEntry {
+            recipe,
+            output: Rc::downgrade(&inner),
+        }));
+        Ok(Cooked { inner })
+    }
+
+    /// how many resources are still in use
+    pub fn len(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.is_alive()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Make every resource that is still in use again, in the order they were first made,
+    /// after the context was lost and a new one made current.  Pass a fresh [GPUState] too,
+    /// since the old one describes the old context.  The old objects are leaked rather than
+    /// deleted.  Stops at the first failure.
+    pub fn recreate_all(&mut self, gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper> {
+        self.entries.retain(|entry| entry.is_alive());
+        for entry in &self.entries {
+            entry.recreate(gpu_state)?;
+        }
+        Ok(())
+    }
+}
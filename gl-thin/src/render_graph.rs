@@ -0,0 +1,127 @@
+use crate::gl_fancy::GPUState;
+use crate::gl_helper::GLErrorWrapper;
+use crate::render_target::RenderTarget;
+
+/// Names a render target (or any other GPU resource) that passes read and write.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ResourceId(pub &'static str);
+
+type PassFn<'a> = Box<dyn FnMut(&mut GPUState) -> Result<(), GLErrorWrapper> + 'a>;
+
+struct RenderPass<'a> {
+    name: &'static str,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+    /// invalidated once the pass is over
+    targets: Vec<&'a RenderTarget>,
+    run: PassFn<'a>,
+}
+
+/// A list of render passes that declare which resources they read and write.
+/// [RenderGraph::execute] runs each pass after every pass that writes something it reads,
+/// so the code adding passes does not have to know the order they must happen in.
+/// Passes with no dependency between them run in the order they were added.
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    passes: Vec<RenderPass<'a>>,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_pass(
+        &mut self,
+        name: &'static str,
+        reads: &[ResourceId],
+        writes: &[ResourceId],
+        run: impl FnMut(&mut GPUState) -> Result<(), GLErrorWrapper> + 'a,
+    ) -> &mut Self {
+        self.add_pass_into(name, reads, writes, &[], run)
+    }
+
+    /// Like [RenderGraph::add_pass], for a pass that draws into `targets`.  Their
+    /// [crate::render_target::StoreOp::DontCare] attachments are invalidated as soon as it
+    /// finishes.
+    pub fn add_pass_into(
+        &mut self,
+        name: &'static str,
+        reads: &[ResourceId],
+        writes: &[ResourceId],
+        targets: &[&'a RenderTarget],
+        run: impl FnMut(&mut GPUState) -> Result<(), GLErrorWrapper> + 'a,
+    ) -> &mut Self {
+        self.passes.push(RenderPass {
+            name,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            targets: targets.to_vec(),
+            run: Box::new(run),
+        });
+        self
+    }
+
+    /// the names of the passes in the order [RenderGraph::execute] would run them
+    pub fn schedule(&self) -> Result<Vec<&'static str>, GLErrorWrapper> {
+        Ok(self
+            .execution_order()?
+            .into_iter()
+            .map(|i| self.passes[i].name)
+            .collect())
+    }
+
+    fn execution_order(&self) -> Result<Vec<usize>, GLErrorWrapper> {
+        let n = self.passes.len();
+        // dependencies[i] are the passes that must finish before pass i
+        let dependencies: Vec<Vec<usize>> = self
+            .passes
+            .iter()
+            .enumerate()
+            .map(|(i, pass)| {
+                (0..n)
+                    .filter(|&j| {
+                        j != i && self.passes[j].writes.iter().any(|w| pass.reads.contains(w))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut done = vec![false; n];
+        let mut order = Vec::with_capacity(n);
+        while order.len() < n {
+            let ready = (0..n).find(|&i| !done[i] && dependencies[i].iter().all(|&j| done[j]));
+            match ready {
+                Some(i) => {
+                    done[i] = true;
+                    order.push(i);
+                }
+                None => {
+                    let stuck: Vec<_> = (0..n)
+                        .filter(|&i| !done[i])
+                        .map(|i| self.passes[i].name)
+                        .collect();
+                    return Err(GLErrorWrapper::with_message2(format!(
+                        "render graph has a cycle among {:?}",
+                        stuck
+                    )));
+                }
+            }
+        }
+        Ok(order)
+    }
+
+    /// run every pass once, in dependency order
+    pub fn execute(mut self, gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper> {
+        for i in self.execution_order()? {
+            let pass = &mut self.passes[i];
+            (pass.run)(gpu_state).map_err(|e| {
+                GLErrorWrapper::with_message2(format!("render pass {} failed: {}", pass.name, e))
+            })?;
+            for target in &pass.targets {
+                target.invalidate()?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+use crate::frame_arena::{ArenaVec, FrameArena};
+use crate::gl_fancy::GPUState;
+use crate::gl_helper::{explode_if_gl_error, GLErrorWrapper};
+
+/// Broad buckets of draws, run in this order by [RenderQueue::execute], so features that
+/// care about order (the sky first, UI on top, debug last) say so instead of depending on
+/// where their draw call happens to be.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum RenderLayer {
+    /// clearing, the sky, camera backgrounds: things everything else covers
+    Background,
+    /// the world's solid meshes
+    Opaque,
+    /// effects and other blended things that still test against the depth buffer
+    Transparent,
+    /// UI over the world, e.g. a pointer that must never be hidden
+    Overlay,
+    /// diagnostics, over everything including the UI
+    Debug,
+}
+
+/// A draw that can be run through a reference, so it can live in a [FrameArena]
+trait Draw {
+    fn draw(&mut self, gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper>;
+
+    /// drop the closure; it does nothing when run after this
+    fn discard(&mut self);
+}
+
+impl<F: FnMut(&mut GPUState) -> Result<(), GLErrorWrapper>> Draw for Option<F> {
+    fn draw(&mut self, gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper> {
+        match self {
+            Some(draw) => draw(gpu_state),
+            None => Ok(()),
+        }
+    }
+
+    fn discard(&mut self) {
+        self.take();
+    }
+}
+
+enum DrawFn<'a> {
+    Boxed(Box<dyn Draw + 'a>),
+    /// the arena never drops it, so [DrawFn::drop] discards it
+    InArena(&'a mut (dyn Draw + 'a)),
+}
+
+impl DrawFn<'_> {
+    fn run(&mut self, gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper> {
+        match self {
+            DrawFn::Boxed(draw) => draw.draw(gpu_state),
+            DrawFn::InArena(draw) => draw.draw(gpu_state),
+        }
+    }
+}
+
+impl Drop for DrawFn<'_> {
+    fn drop(&mut self) {
+        if let DrawFn::InArena(draw) = self {
+            draw.discard();
+        }
+    }
+}
+
+struct QueuedDraw<'a> {
+    layer: RenderLayer,
+    sort_key: i32,
+    /// when it was added, so an unstable sort (which needs no scratch memory) keeps the
+    /// insertion order among equals
+    order: u32,
+    draw: DrawFn<'a>,
+}
+
+impl QueuedDraw<'_> {
+    fn key(&self) -> (RenderLayer, i32, u32) {
+        (self.layer, self.sort_key, self.order)
+    }
+}
+
+enum Draws<'a> {
+    Heap(Vec<QueuedDraw<'a>>),
+    Arena(ArenaVec<'a, QueuedDraw<'a>>),
+}
+
+impl Default for Draws<'_> {
+    fn default() -> Self {
+        Draws::Heap(vec![])
+    }
+}
+
+impl<'a> Draws<'a> {
+    fn push(&mut self, draw: QueuedDraw<'a>) {
+        match self {
+            Draws::Heap(draws) => draws.push(draw),
+            Draws::Arena(draws) => draws.push(draw),
+        }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [QueuedDraw<'a>] {
+        match self {
+            Draws::Heap(draws) => draws,
+            Draws::Arena(draws) => draws,
+        }
+    }
+
+    /// sorted by [QueuedDraw::key]
+    fn sorted(&mut self) -> &mut [QueuedDraw<'a>] {
+        let draws = self.as_mut_slice();
+        draws.sort_unstable_by_key(QueuedDraw::key);
+        draws
+    }
+}
+
+/// Draws collected from wherever they are decided and run sorted by [RenderLayer], then by
+/// sort key (lowest first).  Draws with the same layer and sort key run in the order they
+/// were added.
+#[derive(Default)]
+pub struct RenderQueue<'a> {
+    draws: Draws<'a>,
+    /// where the draws go instead of the heap, see [RenderQueue::new_in]
+    arena: Option<&'a FrameArena>,
+}
+
+impl<'a> RenderQueue<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A queue whose list and closures live in `arena`, so filling it every frame costs no
+    /// heap allocations once the arena has grown to a frame's worth.
+    pub fn new_in(arena: &'a FrameArena) -> Self {
+        Self {
+            draws: Draws::Arena(ArenaVec::new_in(arena)),
+            arena: Some(arena),
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        layer: RenderLayer,
+        sort_key: i32,
+        draw: impl FnMut(&mut GPUState) -> Result<(), GLErrorWrapper> + 'a,
+    ) -> &mut Self {
+        let draw = match self.arena {
+            Some(arena) => DrawFn::InArena(arena.alloc(Some(draw))),
+            None => DrawFn::Boxed(Box::new(Some(draw))),
+        };
+        let order = self.len() as u32;
+        self.draws.push(QueuedDraw {
+            layer,
+            sort_key,
+            order,
+            draw,
+        });
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.draws {
+            Draws::Heap(draws) => draws.len(),
+            Draws::Arena(draws) => draws.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// run every draw once, in queue order
+    pub fn execute(mut self, gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper> {
+        for draw in self.draws.sorted() {
+            draw.draw.run(gpu_state)?;
+        }
+        Ok(())
+    }
+
+    /// Like [RenderQueue::execute], but right before the [RenderLayer::Opaque] draws, run
+    /// them once with color writes off to lay down their depth.  They then run again testing
+    /// with GL_EQUAL, so each pixel is shaded once however much geometry overlaps it.  Worth it
+    /// when fragment shading costs more than transforming the opaque geometry twice.  Opaque
+    /// draws must write the same depth both times, so anything blended (text, outlines,
+    /// effects) belongs in [RenderLayer::Transparent] or later.
+    pub fn execute_with_depth_prepass(
+        mut self,
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let result = run_with_prepass(self.draws.sorted(), gpu_state);
+        unsafe {
+            gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+            gl::DepthFunc(gl::LESS);
+        }
+        result.and_then(|_| explode_if_gl_error())
+    }
+}
+
+/// `draws`, sorted, with the opaque ones run depth-only before the first draw past the
+/// background
+fn run_with_prepass(
+    draws: &mut [QueuedDraw],
+    gpu_state: &mut GPUState,
+) -> Result<(), GLErrorWrapper> {
+    let opaque_start = draws.partition_point(|draw| draw.layer < RenderLayer::Opaque);
+    let opaque_end = draws.partition_point(|draw| draw.layer <= RenderLayer::Opaque);
+    for draw in &mut draws[..opaque_start] {
+        draw.draw.run(gpu_state)?;
+    }
+    for depth_draw in &mut draws[opaque_start..opaque_end] {
+        // again every time, since draws like hand occlusion turn color back on
+        unsafe { gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE) };
+        depth_draw.draw.run(gpu_state)?;
+    }
+    unsafe {
+        gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+        gl::DepthFunc(gl::EQUAL);
+    }
+    for draw in &mut draws[opaque_start..opaque_end] {
+        draw.draw.run(gpu_state)?;
+    }
+    unsafe { gl::DepthFunc(gl::LESS) };
+    for draw in &mut draws[opaque_end..] {
+        draw.draw.run(gpu_state)?;
+    }
+    Ok(())
+}
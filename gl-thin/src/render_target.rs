@@ -0,0 +1,258 @@
+use crate::gl_fancy::GPUState;
+use crate::gl_helper::{
+    explode_if_gl_error, has_extension, is_gles, FrameBuffer, GLErrorWrapper, Texture,
+};
+use gl::types::{GLenum, GLfloat, GLint, GLsizei};
+
+/// What [RenderTarget::color] stores
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorFormat {
+    Rgba8,
+    /// half-float, for HDR intermediates whose values go past 1.0.
+    /// Check [ColorFormat::is_renderable] first.
+    Rgba16F,
+    /// 8 bits per channel stored sRGB-encoded, like an SRGB8_ALPHA8 swapchain.  On GLES the
+    /// GPU encodes what is drawn into it and decodes it when sampled; desktop GL only encodes
+    /// with FRAMEBUFFER_SRGB enabled.
+    Srgb8Alpha8,
+}
+
+impl ColorFormat {
+    pub fn internal_format(self) -> GLenum {
+        match self {
+            ColorFormat::Rgba8 => gl::RGBA8,
+            ColorFormat::Rgba16F => gl::RGBA16F,
+            ColorFormat::Srgb8Alpha8 => gl::SRGB8_ALPHA8,
+        }
+    }
+
+    /// GLES 3 can sample half-float textures but only draws into them with
+    /// EXT_color_buffer_half_float (or EXT_color_buffer_float); desktop GL always can.
+    pub fn is_renderable(self) -> bool {
+        match self {
+            ColorFormat::Rgba8 | ColorFormat::Srgb8Alpha8 => true,
+            ColorFormat::Rgba16F => {
+                !is_gles()
+                    || has_extension("GL_EXT_color_buffer_half_float")
+                    || has_extension("GL_EXT_color_buffer_float")
+            }
+        }
+    }
+
+    /// whether [RenderTarget::read_pixels_f32] works: GLES needs EXT_color_buffer_float to read
+    /// a color buffer back as GL_FLOAT
+    pub fn float_readback() -> bool {
+        !is_gles() || has_extension("GL_EXT_color_buffer_float")
+    }
+}
+
+/// What happens to an attachment once the pass drawing into it is over, see
+/// [RenderTarget::invalidate].  On tiled GPUs (Adreno, Mali) everything left stored is written
+/// back from tile memory at the end of a pass, which costs bandwidth for nothing if no later
+/// pass reads it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StoreOp {
+    Store,
+    DontCare,
+}
+
+/// An offscreen color+depth(+stencil) buffer you can render into and then sample as a texture
+/// (or read back to the CPU), for views that are not an OpenXR swapchain.
+pub struct RenderTarget {
+    pub frame_buffer: FrameBuffer,
+    /// TEXTURE_2D, see [RenderTarget::format]
+    pub color: Texture,
+    pub format: ColorFormat,
+    /// DEPTH24_STENCIL8 if [RenderTarget::stencil], DEPTH_COMPONENT24 otherwise
+    pub depth: Texture,
+    pub stencil: bool,
+    pub width: i32,
+    pub height: i32,
+    /// [StoreOp::Store] unless only a later part of the same pass reads the color
+    pub color_store: StoreOp,
+    /// [StoreOp::DontCare] unless something samples or copies the depth afterward
+    pub depth_store: StoreOp,
+}
+
+impl RenderTarget {
+    pub fn new(width: i32, height: i32, gpu_state: &mut GPUState) -> Result<Self, GLErrorWrapper> {
+        Self::build(width, height, ColorFormat::Rgba8, false, gpu_state)
+    }
+
+    /// Fails if `format` is not [ColorFormat::is_renderable] on this device
+    pub fn with_format(
+        width: i32,
+        height: i32,
+        format: ColorFormat,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        if !format.is_renderable() {
+            return Err(GLErrorWrapper::with_message2(format!(
+                "{:?} color buffers are not supported",
+                format
+            )));
+        }
+        Self::build(width, height, format, false, gpu_state)
+    }
+
+    /// with a stencil buffer packed into [RenderTarget::depth], see [GPUState::set_stencil]
+    pub fn with_stencil(
+        width: i32,
+        height: i32,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        Self::build(width, height, ColorFormat::Rgba8, true, gpu_state)
+    }
+
+    fn build(
+        width: i32,
+        height: i32,
+        format: ColorFormat,
+        stencil: bool,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let color = Texture::new()?;
+        {
+            let bound = color.bound(gl::TEXTURE_2D, gpu_state)?;
+            let internal_format = format.internal_format() as GLint;
+            match format {
+                ColorFormat::Rgba8 | ColorFormat::Srgb8Alpha8 => {
+                    bound.configure::<u8>(0, internal_format, width, height, 0, gl::RGBA)?
+                }
+                ColorFormat::Rgba16F => {
+                    bound.configure::<GLfloat>(0, internal_format, width, height, 0, gl::RGBA)?
+                }
+            }
+            bound.set_parameter(gl::TEXTURE_MIN_FILTER, gl::LINEAR)?;
+            bound.set_parameter(gl::TEXTURE_MAG_FILTER, gl::LINEAR)?;
+            bound.set_parameter(gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE)?;
+            bound.set_parameter(gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE)?;
+        }
+        let (depth, depth_attachment) = if stencil {
+            (
+                Texture::depth_stencil_buffer(width, height, gpu_state)?,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+            )
+        } else {
+            (
+                Texture::depth_buffer(width, height, gpu_state)?,
+                gl::DEPTH_ATTACHMENT,
+            )
+        };
+        {
+            // sampled by post effects, which want exact depths
+            let bound = depth.bound(gl::TEXTURE_2D, gpu_state)?;
+            bound.set_parameter(gl::TEXTURE_MIN_FILTER, gl::NEAREST)?;
+            bound.set_parameter(gl::TEXTURE_MAG_FILTER, gl::NEAREST)?;
+        }
+
+        let frame_buffer = FrameBuffer::new()?;
+        frame_buffer.bind()?;
+        color.attach(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, 0)?;
+        depth.attach(gl::FRAMEBUFFER, depth_attachment, gl::TEXTURE_2D, 0)?;
+        FrameBuffer::check_status(gl::DRAW_FRAMEBUFFER)?;
+
+        Ok(Self {
+            frame_buffer,
+            color,
+            format,
+            depth,
+            stencil,
+            width,
+            height,
+            color_store: StoreOp::Store,
+            depth_store: StoreOp::DontCare,
+        })
+    }
+
+    /// bind for drawing and set the viewport to cover the whole target
+    pub fn bind(&self) -> Result<(), GLErrorWrapper> {
+        self.frame_buffer.bind()?;
+
+        unsafe { gl::Viewport(0, 0, self.width as GLsizei, self.height as GLsizei) };
+        explode_if_gl_error()?;
+
+        if gl::DrawBuffer::is_loaded() {
+            unsafe { gl::DrawBuffer(gl::COLOR_ATTACHMENT0) };
+            explode_if_gl_error()?;
+        }
+        Ok(())
+    }
+
+    /// Discard the attachments marked [StoreOp::DontCare], once the pass drawing into this
+    /// target is done.  [crate::render_graph::RenderGraph] does this for the targets a pass
+    /// names.
+    pub fn invalidate(&self) -> Result<(), GLErrorWrapper> {
+        let mut attachments = Vec::with_capacity(2);
+        if self.color_store == StoreOp::DontCare {
+            attachments.push(gl::COLOR_ATTACHMENT0);
+        }
+        if self.depth_store == StoreOp::DontCare {
+            attachments.push(if self.stencil {
+                gl::DEPTH_STENCIL_ATTACHMENT
+            } else {
+                gl::DEPTH_ATTACHMENT
+            });
+        }
+        self.frame_buffer.invalidate(&attachments)
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+
+    /// Copy the color buffer to the CPU as tightly packed RGBA rows, bottom row first.
+    /// [ColorFormat::Rgba16F] is clamped to 0..1 on the way.
+    /// This stalls until the GPU has finished drawing, so do not call it every frame unless you mean it.
+    pub fn read_pixels(&self) -> Result<Vec<u8>, GLErrorWrapper> {
+        if self.format == ColorFormat::Rgba16F && ColorFormat::float_readback() {
+            let floats = self.read_pixels_f32()?;
+            return Ok(floats
+                .iter()
+                .map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)
+                .collect());
+        }
+        let mut pixels = vec![0u8; (self.width * self.height * 4) as usize];
+        self.frame_buffer.bind_read()?;
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                self.width,
+                self.height,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut _,
+            );
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+        }
+        explode_if_gl_error()?;
+        Ok(pixels)
+    }
+
+    /// like [RenderTarget::read_pixels], with the values as the GPU stored them.  Fails where
+    /// [ColorFormat::float_readback] is false.
+    pub fn read_pixels_f32(&self) -> Result<Vec<GLfloat>, GLErrorWrapper> {
+        if !ColorFormat::float_readback() {
+            return Err(GLErrorWrapper::with_message2(
+                "reading back floats needs GL_EXT_color_buffer_float".to_string(),
+            ));
+        }
+        let mut pixels = vec![0.0; (self.width * self.height * 4) as usize];
+        self.frame_buffer.bind_read()?;
+        unsafe {
+            gl::ReadPixels(
+                0,
+                0,
+                self.width,
+                self.height,
+                gl::RGBA,
+                gl::FLOAT,
+                pixels.as_mut_ptr() as *mut _,
+            );
+        }
+        explode_if_gl_error()?;
+        Ok(pixels)
+    }
+}
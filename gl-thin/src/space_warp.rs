@@ -0,0 +1,219 @@
+//! XR_FB_space_warp: along with each eye image, hand the runtime a small image of per-pixel
+//! motion vectors and its depth.  The runtime then lets the app render at half the display
+//! rate and synthesizes the frames in between, which roughly halves the GPU load.
+
+use crate::composition_layers::LayerSwapchain;
+use crate::errors::XrErrorWrapped;
+use crate::frame_arena::{ArenaVec, FrameArena};
+use openxr::{
+    CompositionLayerProjectionView, Graphics, Instance, Posef, Quaternionf, Session,
+    SwapchainUsageFlags, SystemId,
+};
+use openxr_sys::{
+    CompositionLayerSpaceWarpInfoFB, CompositionLayerSpaceWarpInfoFlagsFB, SystemProperties,
+    SystemSpaceWarpPropertiesFB,
+};
+use std::ffi::c_void;
+use std::ptr::{null, null_mut};
+
+/// The swapchain images for one eye's motion vectors, acquired while the eye is painted.
+/// Both are [SpaceWarp::width] x [SpaceWarp::height].
+pub struct SpaceWarpImages<'a, G: Graphics> {
+    /// normalized device coordinates now minus last frame in RGB, half-float
+    pub motion: &'a G::SwapchainImage,
+    pub depth: &'a G::SwapchainImage,
+    pub width: u32,
+    pub height: u32,
+}
+
+struct SpaceWarpEye<G: Graphics> {
+    motion: LayerSwapchain<G>,
+    depth: LayerSwapchain<G>,
+}
+
+/// see [crate::openxr_helpers::OpenXRComponent::space_warp]
+pub struct SpaceWarp<G: Graphics> {
+    /// Submit the motion vectors with each frame.  While false the runtime composites as if
+    /// there were no space warp, and no images are acquired.
+    pub enabled: bool,
+    /// How the app moved its tracking space since the previous frame, e.g. by walking the
+    /// user with a thumbstick, so the runtime does not mistake it for motion in the scene.
+    /// Back to identity after every frame.
+    pub app_space_delta: Posef,
+    /// the near and far planes the depth images are drawn with
+    pub near_z: f32,
+    pub far_z: f32,
+    /// the runtime's recommended size for the motion vector images
+    pub width: u32,
+    pub height: u32,
+    eyes: Vec<SpaceWarpEye<G>>,
+}
+
+impl<G: Graphics> SpaceWarp<G> {
+    /// whether the instance was created with XR_FB_space_warp
+    pub fn is_supported(instance: &Instance) -> bool {
+        instance.exts().fb_space_warp.is_some()
+    }
+
+    /// One motion and one depth swapchain for each of `view_count` views, at the size the
+    /// runtime recommends.
+    pub fn new(
+        instance: &Instance,
+        system_id: SystemId,
+        session: &Session<G>,
+        view_count: usize,
+        motion_format: G::Format,
+        depth_format: G::Format,
+    ) -> Result<Self, XrErrorWrapped> {
+        if !Self::is_supported(instance) {
+            return Err(XrErrorWrapped::simple(
+                "the runtime has no XR_FB_space_warp",
+            ));
+        }
+        let (width, height) = recommended_size(instance, system_id)?;
+
+        let mut eyes = Vec::with_capacity(view_count);
+        for _ in 0..view_count {
+            eyes.push(SpaceWarpEye {
+                motion: LayerSwapchain::with_usage(
+                    session,
+                    motion_format,
+                    SwapchainUsageFlags::SAMPLED | SwapchainUsageFlags::COLOR_ATTACHMENT,
+                    width,
+                    height,
+                )?,
+                depth: LayerSwapchain::with_usage(
+                    session,
+                    depth_format,
+                    SwapchainUsageFlags::SAMPLED | SwapchainUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                    width,
+                    height,
+                )?,
+            });
+        }
+
+        Ok(Self {
+            enabled: true,
+            app_space_delta: identity_pose(),
+            near_z: 0.01,
+            far_z: 10_000.0,
+            width,
+            height,
+            eyes,
+        })
+    }
+
+    /// Acquire `eye`'s images, let `paint` fill them (and the eye image), and release them.
+    /// `paint` gets `None` while disabled.
+    pub(crate) fn paint_eye<T>(
+        &mut self,
+        eye: usize,
+        paint: impl FnOnce(Option<&SpaceWarpImages<G>>) -> T,
+    ) -> Result<T, XrErrorWrapped> {
+        let (width, height) = (self.width, self.height);
+        if !self.enabled {
+            return Ok(paint(None));
+        }
+        let Some(SpaceWarpEye { motion, depth }) = self.eyes.get_mut(eye) else {
+            return Ok(paint(None));
+        };
+        motion
+            .paint(|motion| {
+                depth.paint(|depth| {
+                    paint(Some(&SpaceWarpImages {
+                        motion,
+                        depth,
+                        width,
+                        height,
+                    }))
+                })
+            })
+            .and_then(|painted| painted)
+    }
+
+    /// One `XrCompositionLayerSpaceWarpInfoFB` per eye, to chain onto the projection views;
+    /// empty unless every eye's images hold something.
+    pub(crate) fn layer_infos<'a>(
+        &self,
+        arena: &'a FrameArena,
+    ) -> ArenaVec<'a, CompositionLayerSpaceWarpInfoFB> {
+        let mut infos = ArenaVec::with_capacity_in(self.eyes.len(), arena);
+        let ready = self
+            .eyes
+            .iter()
+            .all(|eye| eye.motion.has_image() && eye.depth.has_image());
+        if !self.enabled || !ready {
+            return infos;
+        }
+        infos.extend(self.eyes.iter().map(|eye| CompositionLayerSpaceWarpInfoFB {
+            ty: CompositionLayerSpaceWarpInfoFB::TYPE,
+            next: null(),
+            layer_flags: CompositionLayerSpaceWarpInfoFlagsFB::EMPTY,
+            motion_vector_sub_image: eye.motion.sub_image().into_raw(),
+            app_space_delta_pose: self.app_space_delta,
+            depth_sub_image: eye.depth.sub_image().into_raw(),
+            min_depth: 0.0,
+            max_depth: 1.0,
+            near_z: self.near_z,
+            far_z: self.far_z,
+        }));
+        infos
+    }
+
+    /// after the frame is submitted
+    pub(crate) fn frame_ended(&mut self) {
+        self.app_space_delta = identity_pose();
+    }
+}
+
+/// `view` with `info` chained on, which must outlive the frame's submission
+pub(crate) fn chain_space_warp<'a, G: Graphics>(
+    view: CompositionLayerProjectionView<'a, G>,
+    info: &'a CompositionLayerSpaceWarpInfoFB,
+) -> CompositionLayerProjectionView<'a, G> {
+    let mut raw = view.into_raw();
+    raw.next = info as *const CompositionLayerSpaceWarpInfoFB as *const c_void;
+    unsafe { CompositionLayerProjectionView::from_raw(raw) }
+}
+
+fn recommended_size(
+    instance: &Instance,
+    system_id: SystemId,
+) -> Result<(u32, u32), XrErrorWrapped> {
+    let mut space_warp = SystemSpaceWarpPropertiesFB {
+        ty: SystemSpaceWarpPropertiesFB::TYPE,
+        next: null_mut(),
+        recommended_motion_vector_image_rect_width: 0,
+        recommended_motion_vector_image_rect_height: 0,
+    };
+    // the rest is plain data, filled in by the runtime
+    let mut properties: SystemProperties = unsafe { std::mem::zeroed() };
+    properties.ty = SystemProperties::TYPE;
+    properties.next = &mut space_warp as *mut SystemSpaceWarpPropertiesFB as *mut c_void;
+    let result = unsafe {
+        (instance.fp().get_system_properties)(instance.as_raw(), system_id, &mut properties)
+    };
+    if result.into_raw() < 0 {
+        return Err(XrErrorWrapped::build(
+            result,
+            Some(instance),
+            "failed to get space warp properties",
+        ));
+    }
+    Ok((
+        space_warp.recommended_motion_vector_image_rect_width,
+        space_warp.recommended_motion_vector_image_rect_height,
+    ))
+}
+
+fn identity_pose() -> Posef {
+    Posef {
+        orientation: Quaternionf {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            w: 1.0,
+        },
+        position: Default::default(),
+    }
+}
@@ -0,0 +1,201 @@
+//! Merging many small static meshes into one vertex/index buffer pair per material,
+//! so a field of props costs one draw call per material instead of one per prop.
+
+use crate::gl_fancy::{GPUState, VertexBufferLite};
+use crate::gl_helper::GLErrorWrapper;
+use crate::linear::XrMatrix4x4f;
+use gl::types::{GLfloat, GLuint};
+
+/// Where the position (and optionally the normal) live inside each vertex.
+/// Meshes can only be merged if their layouts match.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct VertexLayout {
+    /// floats per vertex
+    pub stride: usize,
+    pub position_offset: usize,
+    pub normal_offset: Option<usize>,
+}
+
+impl VertexLayout {
+    pub fn new(stride: usize, position_offset: usize, normal_offset: Option<usize>) -> Self {
+        Self {
+            stride,
+            position_offset,
+            normal_offset,
+        }
+    }
+}
+
+struct PendingBatch<K> {
+    material: K,
+    layout: VertexLayout,
+    vertices: Vec<GLfloat>,
+    indices: Vec<GLuint>,
+}
+
+/// Collects the CPU-side copies of the meshes (the data you would otherwise have handed to
+/// [VertexBufferLite::new]) along with their world matrices, then uploads one merged
+/// [VertexBufferLite] per material.
+///
+/// `K` is whatever the caller uses to tell materials apart (a shader+texture handle, an enum, ...).
+pub struct StaticBatchBuilder<K> {
+    batches: Vec<PendingBatch<K>>,
+}
+
+impl<K> Default for StaticBatchBuilder<K> {
+    fn default() -> Self {
+        Self { batches: vec![] }
+    }
+}
+
+impl<K: PartialEq> StaticBatchBuilder<K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a copy of a mesh, with its positions (and normals) pre-transformed by `world`.
+    /// A mirroring `world` matrix does not fix up the triangle winding.
+    pub fn add<IT: Copy + Into<GLuint>>(
+        &mut self,
+        material: K,
+        layout: VertexLayout,
+        vertices: &[GLfloat],
+        indices: &[IT],
+        world: &XrMatrix4x4f,
+    ) -> Result<(), GLErrorWrapper> {
+        if !vertices.chunks_exact(layout.stride).remainder().is_empty() {
+            return Err(GLErrorWrapper::with_message2(format!(
+                "{} floats is not a whole number of {}-float vertices",
+                vertices.len(),
+                layout.stride
+            )));
+        }
+
+        let batch = match self.batches.iter().position(|b| b.material == material) {
+            Some(i) => &mut self.batches[i],
+            None => {
+                self.batches.push(PendingBatch {
+                    material,
+                    layout,
+                    vertices: vec![],
+                    indices: vec![],
+                });
+                self.batches.last_mut().unwrap()
+            }
+        };
+        if batch.layout != layout {
+            return Err(GLErrorWrapper::with_message2(format!(
+                "vertex layout {:?} does not match the {:?} already batched for this material",
+                layout, batch.layout
+            )));
+        }
+
+        let base = (batch.vertices.len() / layout.stride) as GLuint;
+        let vertex_count = (vertices.len() / layout.stride) as GLuint;
+        let normal_matrix = cofactor3(world);
+        for vertex in vertices.chunks_exact(layout.stride) {
+            let start = batch.vertices.len();
+            batch.vertices.extend_from_slice(vertex);
+            let out = &mut batch.vertices[start..];
+
+            let p = &mut out[layout.position_offset..layout.position_offset + 3];
+            let transformed = transform_point(world, p);
+            p.copy_from_slice(&transformed);
+
+            if let Some(offset) = layout.normal_offset {
+                let n = &mut out[offset..offset + 3];
+                let transformed = normalize(multiply3(&normal_matrix, n));
+                n.copy_from_slice(&transformed);
+            }
+        }
+
+        for &index in indices {
+            let index: GLuint = index.into();
+            if index >= vertex_count {
+                return Err(GLErrorWrapper::with_message2(format!(
+                    "index {} is out of range for {} vertices",
+                    index, vertex_count
+                )));
+            }
+            batch.indices.push(base + index);
+        }
+        Ok(())
+    }
+
+    /// Upload every material's merged buffers.  Uses 32-bit indices so that each material is
+    /// always exactly one draw call, no matter how many props were added.
+    pub fn build(self, gpu_state: &mut GPUState) -> Result<Vec<StaticBatch<K>>, GLErrorWrapper> {
+        self.batches
+            .into_iter()
+            .map(|batch| {
+                Ok(StaticBatch {
+                    buffers: VertexBufferLite::new(
+                        gpu_state,
+                        batch.vertices.into(),
+                        batch.indices.into(),
+                    )?,
+                    material: batch.material,
+                    layout: batch.layout,
+                })
+            })
+            .collect()
+    }
+}
+
+/// One material's worth of merged static geometry.
+/// Rig it for a shader with [crate::gl_fancy::VertexBufferBundle::from_buffers] and draw
+/// `buffers.index_count` indices.
+pub struct StaticBatch<K> {
+    pub material: K,
+    pub layout: VertexLayout,
+    pub buffers: VertexBufferLite<'static, GLfloat, GLuint>,
+}
+
+//
+
+pub(crate) fn transform_point(m: &XrMatrix4x4f, p: &[f32]) -> [f32; 3] {
+    let m = &m.m;
+    [
+        m[0] * p[0] + m[4] * p[1] + m[8] * p[2] + m[12],
+        m[1] * p[0] + m[5] * p[1] + m[9] * p[2] + m[13],
+        m[2] * p[0] + m[6] * p[1] + m[10] * p[2] + m[14],
+    ]
+}
+
+/// The cofactor matrix of the upper 3x3 (column-major), sign-corrected.  It is the
+/// inverse-transpose scaled by |determinant|, which is all a normal needs since it gets
+/// renormalized anyway.
+fn cofactor3(m: &XrMatrix4x4f) -> [f32; 9] {
+    let m = &m.m;
+    let c0 = [m[0], m[1], m[2]];
+    let c1 = [m[4], m[5], m[6]];
+    let c2 = [m[8], m[9], m[10]];
+    let [a, b, c] = [cross(&c1, &c2), cross(&c2, &c0), cross(&c0, &c1)];
+    let determinant = c0[0] * a[0] + c0[1] * a[1] + c0[2] * a[2];
+    [a[0], a[1], a[2], b[0], b[1], b[2], c[0], c[1], c[2]].map(|x| x * determinant.signum())
+}
+
+pub(crate) fn cross(a: &[f32; 3], b: &[f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn multiply3(m: &[f32; 9], v: &[f32]) -> [f32; 3] {
+    [
+        m[0] * v[0] + m[3] * v[1] + m[6] * v[2],
+        m[1] * v[0] + m[4] * v[1] + m[7] * v[2],
+        m[2] * v[0] + m[5] * v[1] + m[8] * v[2],
+    ]
+}
+
+pub(crate) fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    if length > 0.0 {
+        v.map(|c| c / length)
+    } else {
+        v
+    }
+}
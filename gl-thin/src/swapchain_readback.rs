@@ -0,0 +1,205 @@
+//! What was actually submitted: the format the eye swapchains were negotiated with, and a
+//! small region of an eye image read back straight after it was drawn, before it is released
+//! to the compositor.  For self-tests that check the negotiation came out as intended, and for
+//! bug reports.
+
+use crate::gl_helper::{explode_if_gl_error, GLErrorWrapper};
+use gl::types::{GLenum, GLfloat};
+use std::fmt::Write;
+
+/// How the compositor will interpret a swapchain format
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FormatInfo {
+    pub format: GLenum,
+    /// e.g. "SRGB8_ALPHA8", or "unknown"
+    pub name: &'static str,
+    /// The values are stored sRGB-encoded and decoded by the compositor; otherwise they
+    /// are taken as linear.
+    pub srgb: bool,
+    /// half-float (or wider) channels, which can go past 1.0
+    pub float: bool,
+    /// red, green, blue, alpha; 0 where unknown
+    pub bits: [u8; 4],
+}
+
+impl FormatInfo {
+    pub fn of(format: GLenum) -> Self {
+        let (name, srgb, float, bits) = match format {
+            gl::RGBA8 => ("RGBA8", false, false, [8, 8, 8, 8]),
+            gl::RGBA8_SNORM => ("RGBA8_SNORM", false, false, [8, 8, 8, 8]),
+            gl::SRGB8_ALPHA8 => ("SRGB8_ALPHA8", true, false, [8, 8, 8, 8]),
+            gl::RGB10_A2 => ("RGB10_A2", false, false, [10, 10, 10, 2]),
+            gl::RGBA16F => ("RGBA16F", false, true, [16, 16, 16, 16]),
+            gl::R11F_G11F_B10F => ("R11F_G11F_B10F", false, true, [11, 11, 10, 0]),
+            _ => ("unknown", false, false, [0; 4]),
+        };
+        Self {
+            format,
+            name,
+            srgb,
+            float,
+            bits,
+        }
+    }
+
+    /// "SRGB8_ALPHA8 (0x8c43), sRGB"
+    pub fn describe(&self) -> String {
+        format!(
+            "{} (0x{:x}), {}",
+            self.name,
+            self.format,
+            if self.srgb { "sRGB" } else { "linear" }
+        )
+    }
+}
+
+/// A rectangle of pixels, from the bottom left as GL counts them
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PixelRegion {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl PixelRegion {
+    /// `size` x `size` pixels (or fewer, for a smaller image) in the middle of a `width` x
+    /// `height` image
+    pub fn centered(width: u32, height: u32, size: i32) -> Self {
+        let (width, height) = (width as i32, height as i32);
+        let (w, h) = (size.min(width), size.min(height));
+        Self {
+            x: (width - w) / 2,
+            y: (height - h) / 2,
+            width: w,
+            height: h,
+        }
+    }
+
+    /// the part of this that lies within a `width` x `height` image
+    pub fn clipped(&self, width: u32, height: u32) -> Self {
+        let x = self.x.clamp(0, width as i32);
+        let y = self.y.clamp(0, height as i32);
+        Self {
+            x,
+            y,
+            width: (self.x + self.width).clamp(x, width as i32) - x,
+            height: (self.y + self.height).clamp(y, height as i32) - y,
+        }
+    }
+}
+
+/// A region of one eye image as it was handed to the compositor
+#[derive(Clone, Debug)]
+pub struct SwapchainSnapshot {
+    pub eye: usize,
+    pub frame_index: u64,
+    pub format: FormatInfo,
+    pub region: PixelRegion,
+    /// Tightly packed RGBA rows, bottom row first, as stored: still sRGB-encoded in an sRGB
+    /// swapchain.  Float formats are clamped to 0..1.
+    pub pixels: Vec<u8>,
+}
+
+impl SwapchainSnapshot {
+    /// Read `region` (clipped to the `width` x `height` image) from the bound read
+    /// framebuffer, whose color attachment is the eye image.  Stalls until the GPU has
+    /// finished drawing it.
+    pub fn read(
+        eye: usize,
+        frame_index: u64,
+        format: FormatInfo,
+        region: PixelRegion,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, GLErrorWrapper> {
+        let region = region.clipped(width, height);
+        let count = (region.width * region.height * 4) as usize;
+        let pixels = if format.float {
+            let mut floats: Vec<GLfloat> = vec![0.0; count];
+            unsafe {
+                gl::ReadPixels(
+                    region.x,
+                    region.y,
+                    region.width,
+                    region.height,
+                    gl::RGBA,
+                    gl::FLOAT,
+                    floats.as_mut_ptr() as *mut _,
+                );
+            }
+            explode_if_gl_error()?;
+            floats
+                .iter()
+                .map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)
+                .collect()
+        } else {
+            let mut pixels = vec![0u8; count];
+            unsafe {
+                gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+                gl::ReadPixels(
+                    region.x,
+                    region.y,
+                    region.width,
+                    region.height,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    pixels.as_mut_ptr() as *mut _,
+                );
+                gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+            }
+            explode_if_gl_error()?;
+            pixels
+        };
+        Ok(Self {
+            eye,
+            frame_index,
+            format,
+            region,
+            pixels,
+        })
+    }
+
+    /// the pixel at `x`, `y` within the region, or `None` outside it
+    pub fn pixel(&self, x: i32, y: i32) -> Option<[u8; 4]> {
+        if x < 0 || y < 0 || x >= self.region.width || y >= self.region.height {
+            return None;
+        }
+        let offset = ((y * self.region.width + x) * 4) as usize;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.pixels[offset..offset + 4]);
+        Some(pixel)
+    }
+
+    /// the mean of each channel over the region, as stored
+    pub fn average(&self) -> [f32; 4] {
+        let mut sum = [0u64; 4];
+        for pixel in self.pixels.chunks_exact(4) {
+            for (total, &value) in sum.iter_mut().zip(pixel) {
+                *total += value as u64;
+            }
+        }
+        let count = (self.pixels.len() / 4).max(1) as f32;
+        sum.map(|total| total as f32 / count)
+    }
+
+    /// a few lines for a log or a bug report
+    pub fn report(&self) -> String {
+        let region = &self.region;
+        let mut report = format!(
+            "eye {} frame {}: {}\n{}x{} at ({}, {}), average RGBA {:.1?}",
+            self.eye,
+            self.frame_index,
+            self.format.describe(),
+            region.width,
+            region.height,
+            region.x,
+            region.y,
+            self.average()
+        );
+        if let Some(pixel) = self.pixel(region.width / 2, region.height / 2) {
+            let _ = write!(report, ", middle {:?}", pixel);
+        }
+        report
+    }
+}
@@ -0,0 +1,248 @@
+//! Packing many small images into one texture, so things like icons and glyphs
+//! can all be drawn without switching textures.
+
+use crate::gl_fancy::GPUState;
+use crate::gl_helper::{GLErrorWrapper, Texture, TextureWithTarget};
+use gl::types::GLint;
+
+/// A rectangle of texels inside an atlas
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AtlasRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// texture coordinates of an [AtlasRect], (u0,v0) being the texel at (x,y)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UvRect {
+    pub u0: f32,
+    pub v0: f32,
+    pub u1: f32,
+    pub v1: f32,
+}
+
+//
+
+#[derive(Copy, Clone, Debug)]
+struct SkylineSegment {
+    x: i32,
+    y: i32,
+    width: i32,
+}
+
+/// Skyline bottom-left rectangle packing.
+/// The packed area is described by the height of its top edge across the width of the atlas;
+/// each new rectangle goes wherever it leaves that skyline lowest.
+/// Good for a stream of similar-sized rectangles arriving one at a time, like glyphs.
+pub struct SkylinePacker {
+    pub width: i32,
+    pub height: i32,
+    skyline: Vec<SkylineSegment>,
+}
+
+impl SkylinePacker {
+    pub fn new(width: i32, height: i32) -> Self {
+        Self {
+            width,
+            height,
+            skyline: vec![SkylineSegment { x: 0, y: 0, width }],
+        }
+    }
+
+    /// forget everything that was packed
+    pub fn clear(&mut self) {
+        self.skyline = vec![SkylineSegment {
+            x: 0,
+            y: 0,
+            width: self.width,
+        }];
+    }
+
+    /// `None` if there is no room left
+    pub fn pack(&mut self, width: i32, height: i32) -> Option<AtlasRect> {
+        if width <= 0 || height <= 0 {
+            return Some(AtlasRect {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+            });
+        }
+
+        // (segment index, y, x) with the lowest top edge, then the leftmost
+        let mut best: Option<(usize, i32, i32)> = None;
+        for i in 0..self.skyline.len() {
+            if let Some(y) = self.fit(i, width, height) {
+                let x = self.skyline[i].x;
+                match best {
+                    Some((_, best_y, _)) if best_y <= y => {}
+                    _ => best = Some((i, y, x)),
+                }
+            }
+        }
+        let (index, y, x) = best?;
+
+        self.skyline.insert(
+            index,
+            SkylineSegment {
+                x,
+                y: y + height,
+                width,
+            },
+        );
+        // trim the segments the new one now covers
+        let right = x + width;
+        let mut i = index + 1;
+        while i < self.skyline.len() && self.skyline[i].x < right {
+            let segment = &mut self.skyline[i];
+            let overlap = right - segment.x;
+            if overlap >= segment.width {
+                self.skyline.remove(i);
+            } else {
+                segment.x += overlap;
+                segment.width -= overlap;
+                i += 1;
+            }
+        }
+        self.merge();
+
+        Some(AtlasRect {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    /// the y a `width`x`height` rectangle would sit at if its left edge were at segment `index`
+    fn fit(&self, index: usize, width: i32, height: i32) -> Option<i32> {
+        let x = self.skyline[index].x;
+        if x + width > self.width {
+            return None;
+        }
+        let mut y = 0;
+        let mut remaining = width;
+        for segment in &self.skyline[index..] {
+            if remaining <= 0 {
+                break;
+            }
+            y = y.max(segment.y);
+            remaining -= segment.width;
+        }
+        (y + height <= self.height).then_some(y)
+    }
+
+    fn merge(&mut self) {
+        let mut i = 0;
+        while i + 1 < self.skyline.len() {
+            if self.skyline[i].y == self.skyline[i + 1].y {
+                self.skyline[i].width += self.skyline[i + 1].width;
+                self.skyline.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+//
+
+/// An RGBA8 texture plus a [SkylinePacker] keeping track of which parts of it are in use.
+pub struct TextureAtlas {
+    pub texture: TextureWithTarget,
+    packer: SkylinePacker,
+    /// empty texels left around each image so linear filtering does not bleed between neighbors
+    pub padding: i32,
+}
+
+impl TextureAtlas {
+    pub fn new(width: i32, height: i32, gpu_state: &mut GPUState) -> Result<Self, GLErrorWrapper> {
+        let texture = Texture::new()?;
+        {
+            let bound = texture.bound(gl::TEXTURE_2D, gpu_state)?;
+            bound.set_parameter(gl::TEXTURE_MIN_FILTER, gl::LINEAR)?;
+            bound.set_parameter(gl::TEXTURE_MAG_FILTER, gl::LINEAR)?;
+            bound.set_parameter(gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE)?;
+            bound.set_parameter(gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE)?;
+        }
+
+        let mut rval = Self {
+            texture: TextureWithTarget::new(texture, gl::TEXTURE_2D),
+            packer: SkylinePacker::new(width, height),
+            padding: 1,
+        };
+        rval.clear(gpu_state)?;
+        Ok(rval)
+    }
+
+    pub fn width(&self) -> i32 {
+        self.packer.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.packer.height
+    }
+
+    /// Reserve room for a `width`x`height` image without writing anything into it.
+    /// `None` if the atlas is full.
+    pub fn allocate(&mut self, width: i32, height: i32) -> Option<AtlasRect> {
+        let padded = self
+            .packer
+            .pack(width + 2 * self.padding, height + 2 * self.padding)?;
+        Some(AtlasRect {
+            x: padded.x + self.padding,
+            y: padded.y + self.padding,
+            width,
+            height,
+        })
+    }
+
+    /// Copy an RGBA8 image into the atlas.  `None` if the atlas is full.
+    pub fn insert_rgba(
+        &mut self,
+        width: i32,
+        height: i32,
+        pixels: &[u8],
+        gpu_state: &mut GPUState,
+    ) -> Result<Option<(AtlasRect, UvRect)>, GLErrorWrapper> {
+        let Some(rect) = self.allocate(width, height) else {
+            return Ok(None);
+        };
+        if width > 0 && height > 0 {
+            self.texture
+                .texture
+                .bound(self.texture.target, gpu_state)?
+                .write_sub_pixels(0, rect.x, rect.y, width, height, gl::RGBA, pixels)?;
+        }
+        Ok(Some((rect, self.uv(&rect))))
+    }
+
+    pub fn uv(&self, rect: &AtlasRect) -> UvRect {
+        let (w, h) = (self.width() as f32, self.height() as f32);
+        UvRect {
+            u0: rect.x as f32 / w,
+            v0: rect.y as f32 / h,
+            u1: (rect.x + rect.width) as f32 / w,
+            v1: (rect.y + rect.height) as f32 / h,
+        }
+    }
+
+    /// Forget every image and zero the texture, so the padding is transparent again.
+    pub fn clear(&mut self, gpu_state: &mut GPUState) -> Result<(), GLErrorWrapper> {
+        self.packer.clear();
+        let (width, height) = (self.width(), self.height());
+        self.texture
+            .texture
+            .bound(self.texture.target, gpu_state)?
+            .write_pixels(
+                0,
+                gl::RGBA8 as GLint,
+                width,
+                height,
+                gl::RGBA,
+                &vec![0u8; (4 * width * height) as usize],
+            )
+    }
+}
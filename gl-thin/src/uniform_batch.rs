@@ -0,0 +1,126 @@
+//! A draw's uniforms, recorded up front and sent together by [UniformBatch::flush].  The
+//! whole lot costs one glGetError instead of one per call, and a value the program already
+//! holds (a view matrix shared by every draw of the frame, a color that never changes) is not
+//! sent again.  It is also the one place a uniform buffer object could later stand in for
+//! the individual glUniform calls.
+
+use crate::gl_helper::{explode_if_gl_error, GLErrorWrapper, Program};
+use gl::types::{GLfloat, GLint, GLuint};
+use std::collections::HashMap;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum UniformValue {
+    Int(GLint),
+    Float(GLfloat),
+    Vec2([GLfloat; 2]),
+    Vec3([GLfloat; 3]),
+    Vec4([GLfloat; 4]),
+    /// column-major, like all the other matrices
+    Mat3([GLfloat; 9]),
+    Mat4([GLfloat; 16]),
+}
+
+impl UniformValue {
+    /// the glUniform call, leaving the error check to the caller
+    unsafe fn send(&self, location: GLint) {
+        match self {
+            UniformValue::Int(v) => gl::Uniform1i(location, *v),
+            UniformValue::Float(v) => gl::Uniform1f(location, *v),
+            UniformValue::Vec2(v) => gl::Uniform2f(location, v[0], v[1]),
+            UniformValue::Vec3(v) => gl::Uniform3f(location, v[0], v[1], v[2]),
+            UniformValue::Vec4(v) => gl::Uniform4f(location, v[0], v[1], v[2], v[3]),
+            UniformValue::Mat3(v) => gl::UniformMatrix3fv(location, 1, gl::FALSE, v.as_ptr()),
+            UniformValue::Mat4(v) => gl::UniformMatrix4fv(location, 1, gl::FALSE, v.as_ptr()),
+        }
+    }
+}
+
+/// Uniform values live in the program object, so keep one batch per [crate::gl_helper::Program]
+/// and only flush it with that program in use; what it remembers sending is only true there.
+#[derive(Default)]
+pub struct UniformBatch {
+    pending: Vec<(GLint, UniformValue)>,
+    /// what each location was last sent
+    sent: HashMap<GLint, UniformValue>,
+    /// which program [UniformBatch::flush_to] last sent to
+    program: Option<GLuint>,
+}
+
+impl UniformBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, location: GLint, value: UniformValue) -> &mut Self {
+        self.pending.push((location, value));
+        self
+    }
+
+    pub fn set_1i(&mut self, location: GLint, v0: GLint) -> &mut Self {
+        self.set(location, UniformValue::Int(v0))
+    }
+
+    pub fn set_1f(&mut self, location: GLint, v0: GLfloat) -> &mut Self {
+        self.set(location, UniformValue::Float(v0))
+    }
+
+    pub fn set_2fv(&mut self, location: GLint, val: &[GLfloat; 2]) -> &mut Self {
+        self.set(location, UniformValue::Vec2(*val))
+    }
+
+    pub fn set_3fv(&mut self, location: GLint, val: &[GLfloat; 3]) -> &mut Self {
+        self.set(location, UniformValue::Vec3(*val))
+    }
+
+    pub fn set_4fv(&mut self, location: GLint, val: &[GLfloat; 4]) -> &mut Self {
+        self.set(location, UniformValue::Vec4(*val))
+    }
+
+    pub fn set_mat3u(&mut self, location: GLint, val: &[GLfloat; 9]) -> &mut Self {
+        self.set(location, UniformValue::Mat3(*val))
+    }
+
+    pub fn set_mat4u(&mut self, location: GLint, val: &[GLfloat; 16]) -> &mut Self {
+        self.set(location, UniformValue::Mat4(*val))
+    }
+
+    /// Send what the program does not already hold, then start over for the next draw.
+    /// Returns how many calls were made.
+    pub fn flush(&mut self) -> Result<usize, GLErrorWrapper> {
+        self.flush_mapped(|location| location)
+    }
+
+    /// Like [UniformBatch::flush], for `program`, which is in use: the locations are
+    /// translated to its multiview variant when that is what [Program::use_] picked, and
+    /// switching between the two starts over.
+    pub fn flush_to(&mut self, program: &Program) -> Result<usize, GLErrorWrapper> {
+        let in_use = program.handle_in_use();
+        if self.program != Some(in_use) {
+            self.forget();
+            self.program = Some(in_use);
+        }
+        self.flush_mapped(|location| program.location_in_use(location))
+    }
+
+    fn flush_mapped(&mut self, map: impl Fn(GLint) -> GLint) -> Result<usize, GLErrorWrapper> {
+        let mut calls = 0;
+        for (location, value) in self.pending.drain(..) {
+            if self.sent.get(&location) == Some(&value) {
+                continue;
+            }
+            unsafe { value.send(map(location)) };
+            self.sent.insert(location, value);
+            calls += 1;
+        }
+        if calls > 0 {
+            explode_if_gl_error()?;
+        }
+        Ok(calls)
+    }
+
+    /// Forget what was sent, so the next flush sends everything: after the program is linked
+    /// again, after the context is lost, or after setting its uniforms some other way.
+    pub fn forget(&mut self) {
+        self.sent.clear();
+    }
+}
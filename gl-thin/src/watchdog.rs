@@ -0,0 +1,163 @@
+//! Notices when the frame loop stops coming back from a call, e.g. `xrWaitFrame` or a
+//! swapchain wait that the runtime never answers.  The frame loop reports which phase it is
+//! in through a [Heartbeat]; a [Watchdog] thread checks on it and, as soon as one phase has
+//! gone on too long, logs and calls back from its own thread, since the frame loop may never
+//! return to say so itself.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// the shortest [Watchdog::threshold]; the thread checks four times per threshold
+pub const MIN_THRESHOLD: Duration = Duration::from_millis(20);
+
+/// one phase that ran past [Watchdog::threshold]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Stall {
+    pub phase: &'static str,
+    /// how long it had gone on when the watchdog noticed
+    pub elapsed: Duration,
+}
+
+#[derive(Default)]
+struct Beat {
+    /// `None` between frames, which does not count as stalling
+    phase: Option<(&'static str, Instant)>,
+    /// noticed, not yet taken by [Watchdog::take_stall]
+    stall: Option<Stall>,
+    /// the phase already complained about, so it is only logged once
+    reported: Option<Instant>,
+}
+
+/// The frame loop's side: cheap to clone, and does nothing but note the time unless a
+/// [Watchdog] is looking.
+#[derive(Clone, Default)]
+pub struct Heartbeat {
+    beat: Arc<Mutex<Beat>>,
+}
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// about to start `phase`, e.g. "wait frame"; the previous one is over
+    pub fn enter(&self, phase: &'static str) {
+        self.lock().phase = Some((phase, Instant::now()));
+    }
+
+    /// between frames, or waiting on purpose
+    pub fn idle(&self) {
+        self.lock().phase = None;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Beat> {
+        // a panic while holding it only leaves stale timing behind
+        self.beat
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+//
+
+/// Watches a [Heartbeat] from a thread of its own until dropped.
+pub struct Watchdog {
+    /// the thread has its own copy, so this is fixed at [Watchdog::spawn]
+    threshold: Duration,
+    heartbeat: Heartbeat,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// `threshold` is how long one phase may take before it counts as a stall, at least
+    /// [MIN_THRESHOLD].  `on_stall` runs on the watchdog thread, once per stalled phase, while
+    /// the frame loop is still stuck: it is the place to end the session or restart.
+    pub fn spawn(
+        heartbeat: Heartbeat,
+        threshold: Duration,
+        on_stall: impl FnMut(Stall) + Send + 'static,
+    ) -> std::io::Result<Self> {
+        if threshold < MIN_THRESHOLD {
+            log::warn!(
+                "watchdog threshold {:?} is too short, using {:?}",
+                threshold,
+                MIN_THRESHOLD
+            );
+        }
+        let threshold = threshold.max(MIN_THRESHOLD);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let heartbeat = heartbeat.clone();
+            let stop = stop.clone();
+            thread::Builder::new()
+                .name("watchdog".to_string())
+                .spawn(move || watch(&heartbeat, threshold, &stop, on_stall))?
+        };
+        Ok(Self {
+            threshold,
+            heartbeat,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// see [Watchdog::spawn]
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    pub fn heartbeat(&self) -> &Heartbeat {
+        &self.heartbeat
+    }
+
+    /// the stall noticed since the last call, once the frame loop is back to ask
+    pub fn take_stall(&self) -> Option<Stall> {
+        self.heartbeat.lock().stall.take()
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            if thread.join().is_err() {
+                log::error!("watchdog thread panicked");
+            }
+        }
+    }
+}
+
+fn watch(
+    heartbeat: &Heartbeat,
+    threshold: Duration,
+    stop: &AtomicBool,
+    mut on_stall: impl FnMut(Stall),
+) {
+    let interval = threshold / 4;
+    while !stop.load(Ordering::Relaxed) {
+        thread::park_timeout(interval);
+        let mut beat = heartbeat.lock();
+        let Some((phase, since)) = beat.phase else {
+            continue;
+        };
+        let elapsed = since.elapsed();
+        if elapsed < threshold || beat.reported == Some(since) {
+            continue;
+        }
+        log::error!(
+            "frame loop stalled in {} for {:.1}s",
+            phase,
+            elapsed.as_secs_f32()
+        );
+        let stall = Stall { phase, elapsed };
+        beat.reported = Some(since);
+        beat.stall = Some(stall);
+        // not holding the lock, so the callback can not wedge a frame loop that comes back
+        drop(beat);
+        on_stall(stall);
+    }
+}
@@ -0,0 +1,102 @@
+//! Being a giant over a miniature world, or an ant in a huge one.  Every tracked position
+//! (eyes, controllers, hands) is stretched away from a pivot by the same factor before it is
+//! used, so walking a real meter crosses `factor` world meters and the eyes end up `factor`
+//! times as far apart; the world then looks `1 / factor` its size.  Rotations are unchanged.
+
+use crate::linear::{
+    xr_matrix4x4f_create_translation_rotation_scale, XrMatrix4x4f, XrQuaternionf, XrVector3f,
+};
+#[cfg(feature = "openxr")]
+use openxr_sys::Posef;
+
+/// Maps tracking-space ("real") coordinates to scene ("world") coordinates:
+/// `world = world_pivot + factor * (real - real_pivot)`.
+#[derive(Copy, Clone, Debug)]
+pub struct WorldScale {
+    /// world meters per real meter; above 1 the user is a giant
+    pub factor: f32,
+    pub real_pivot: XrVector3f,
+    /// where [WorldScale::real_pivot] is in the world
+    pub world_pivot: XrVector3f,
+}
+
+impl Default for WorldScale {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl WorldScale {
+    /// `factor` about the tracking space's origin
+    pub fn new(factor: f32) -> Self {
+        Self {
+            factor,
+            real_pivot: XrVector3f::default(),
+            world_pivot: XrVector3f::default(),
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.factor == 1.0 && {
+            let d = self.world_pivot - self.real_pivot;
+            d.x == 0.0 && d.y == 0.0 && d.z == 0.0
+        }
+    }
+
+    /// The same mapping with a new `factor`, growing or shrinking the world around
+    /// `world_point` (e.g. the floor under the user), which stays where it is.
+    pub fn rescaled(&self, factor: f32, world_point: &XrVector3f) -> Self {
+        Self {
+            factor,
+            real_pivot: self.to_real(world_point),
+            world_pivot: *world_point,
+        }
+    }
+
+    pub fn to_world(&self, real: &XrVector3f) -> XrVector3f {
+        self.world_pivot + self.to_world_vector(&(*real - self.real_pivot))
+    }
+
+    pub fn to_real(&self, world: &XrVector3f) -> XrVector3f {
+        self.real_pivot + self.to_real_vector(&(*world - self.world_pivot))
+    }
+
+    /// a displacement or velocity, which the pivot does not affect
+    pub fn to_world_vector(&self, real: &XrVector3f) -> XrVector3f {
+        *real * self.factor
+    }
+
+    pub fn to_real_vector(&self, world: &XrVector3f) -> XrVector3f {
+        *world / self.factor
+    }
+
+    /// A real length in world meters.  Physics and sound that should feel natural to the
+    /// user (how fast a dropped ball falls, how far a voice carries) run in real units and
+    /// convert, scaling accelerations the same way as distances.
+    pub fn to_world_distance(&self, real: f32) -> f32 {
+        real * self.factor
+    }
+
+    pub fn to_real_distance(&self, world: f32) -> f32 {
+        world / self.factor
+    }
+
+    /// world from real, for drawing things that follow the user's body at their real size,
+    /// e.g. a hand mesh skinned in tracking space
+    pub fn matrix(&self) -> XrMatrix4x4f {
+        let translation = self.world_pivot - self.to_world_vector(&self.real_pivot);
+        xr_matrix4x4f_create_translation_rotation_scale(
+            &translation,
+            &XrQuaternionf::default(),
+            &XrVector3f::scale(self.factor),
+        )
+    }
+
+    #[cfg(feature = "openxr")]
+    pub fn to_world_pose(&self, real: Posef) -> Posef {
+        Posef {
+            orientation: real.orientation,
+            position: (&self.to_world(&real.position.into())).into(),
+        }
+    }
+}
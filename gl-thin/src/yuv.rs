@@ -0,0 +1,175 @@
+use crate::gl_fancy::{ActiveTextureUnit, GPUState};
+use crate::gl_helper::{explode_if_gl_error, GLErrorWrapper, Texture};
+use gl::types::{GLenum, GLint, GLsizei};
+
+/// How the chroma planes of a YUV 4:2:0 image are arranged in memory
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum YuvLayout {
+    /// full-resolution Y plane followed by one half-resolution plane of interleaved U,V pairs.
+    /// This is what most android cameras and MediaCodec decoders produce.
+    Nv12,
+    /// full-resolution Y plane followed by separate half-resolution U and V planes
+    I420,
+}
+
+impl YuvLayout {
+    /// how many textures (and samplers) this layout needs
+    pub fn plane_count(&self) -> usize {
+        match self {
+            YuvLayout::Nv12 => 2,
+            YuvLayout::I420 => 3,
+        }
+    }
+
+    /// the total number of bytes in one frame
+    pub fn frame_size(&self, width: i32, height: i32) -> usize {
+        let (cw, ch) = chroma_size(width, height);
+        (width * height + 2 * cw * ch) as usize
+    }
+}
+
+/// The chroma planes are half resolution, rounded up for odd sizes
+pub fn chroma_size(width: i32, height: i32) -> (i32, i32) {
+    ((width + 1) / 2, (height + 1) / 2)
+}
+
+/// One single-channel texture for the luma, and one or two textures for the chroma.
+/// The conversion to RGB happens in a shader (see bob_shaders::yuv_shader) so we never pay for it on the CPU.
+pub struct YuvTextures {
+    pub layout: YuvLayout,
+    pub width: i32,
+    pub height: i32,
+    /// Y, then UV (NV12) or U, V (I420)
+    pub planes: Vec<Texture>,
+}
+
+impl YuvTextures {
+    /// allocate (but do not fill) the plane textures
+    pub fn new(
+        layout: YuvLayout,
+        width: i32,
+        height: i32,
+        gpu_state: &mut GPUState,
+    ) -> Result<Self, GLErrorWrapper> {
+        let (cw, ch) = chroma_size(width, height);
+
+        let mut planes = vec![];
+        for (i, (internal_format, format)) in Self::plane_formats(layout).into_iter().enumerate() {
+            let (w, h) = if i == 0 { (width, height) } else { (cw, ch) };
+            let texture = Texture::new()?;
+            {
+                let bound = texture.bound(gl::TEXTURE_2D, gpu_state)?;
+                bound.configure::<u8>(0, internal_format as GLint, w, h, 0, format)?;
+                // video frames have no mipmaps, so the default MIN_FILTER would leave the texture incomplete
+                bound.set_parameter(gl::TEXTURE_MIN_FILTER, gl::LINEAR)?;
+                bound.set_parameter(gl::TEXTURE_MAG_FILTER, gl::LINEAR)?;
+                bound.set_parameter(gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE)?;
+                bound.set_parameter(gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE)?;
+            }
+            planes.push(texture);
+        }
+
+        Ok(Self {
+            layout,
+            width,
+            height,
+            planes,
+        })
+    }
+
+    /// (internal_format, format) for each plane
+    fn plane_formats(layout: YuvLayout) -> Vec<(GLenum, GLenum)> {
+        match layout {
+            YuvLayout::Nv12 => vec![(gl::R8, gl::RED), (gl::RG8, gl::RG)],
+            YuvLayout::I420 => vec![(gl::R8, gl::RED), (gl::R8, gl::RED), (gl::R8, gl::RED)],
+        }
+    }
+
+    /// Upload a frame where all the planes are packed back-to-back with no row padding
+    pub fn upload_packed(
+        &mut self,
+        frame: &[u8],
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        let expected = self.layout.frame_size(self.width, self.height);
+        if frame.len() < expected {
+            return Err(GLErrorWrapper::with_message2(format!(
+                "YUV frame too small : {} < {}",
+                frame.len(),
+                expected
+            )));
+        }
+
+        let luma = (self.width * self.height) as usize;
+        let (cw, ch) = chroma_size(self.width, self.height);
+        let chroma = (cw * ch) as usize;
+        let (y, rest) = frame.split_at(luma);
+        match self.layout {
+            YuvLayout::Nv12 => self.upload_planes(&[y, &rest[..2 * chroma]], gpu_state),
+            YuvLayout::I420 => {
+                let (u, v) = rest.split_at(chroma);
+                self.upload_planes(&[y, u, &v[..chroma]], gpu_state)
+            }
+        }
+    }
+
+    /// Upload each plane from its own slice.  Each slice must be tightly packed (stride == width).
+    pub fn upload_planes(
+        &mut self,
+        planes: &[&[u8]],
+        gpu_state: &mut GPUState,
+    ) -> Result<(), GLErrorWrapper> {
+        if planes.len() != self.planes.len() {
+            return Err(GLErrorWrapper::with_message2(format!(
+                "{:?} needs {} planes, got {}",
+                self.layout,
+                self.planes.len(),
+                planes.len()
+            )));
+        }
+
+        // rows of odd-width planes are not 4-byte aligned
+        unsafe { gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1) };
+        explode_if_gl_error()?;
+
+        let (cw, ch) = chroma_size(self.width, self.height);
+        let formats = Self::plane_formats(self.layout);
+        for (i, (texture, pixels)) in self.planes.iter().zip(planes).enumerate() {
+            let (w, h): (GLsizei, GLsizei) = if i == 0 {
+                (self.width, self.height)
+            } else {
+                (cw, ch)
+            };
+            texture.bound(gl::TEXTURE_2D, gpu_state)?.write_sub_pixels(
+                0,
+                0,
+                0,
+                w,
+                h,
+                formats[i].1,
+                pixels,
+            )?;
+        }
+
+        unsafe { gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4) };
+        explode_if_gl_error()
+    }
+
+    /// bind plane `i` to texture unit `first_unit + i`.
+    /// Returns the units used, in plane order, so you can feed them to the shader's samplers.
+    pub fn bind_to_units(
+        &self,
+        first_unit: ActiveTextureUnit,
+        gpu_state: &mut GPUState,
+    ) -> Result<Vec<ActiveTextureUnit>, GLErrorWrapper> {
+        let mut units = vec![];
+        for (i, texture) in self.planes.iter().enumerate() {
+            let unit = ActiveTextureUnit(first_unit.0 + i as u32);
+            gpu_state.set_active_texture(unit)?;
+            texture.bind(gl::TEXTURE_2D)?;
+            units.push(unit);
+        }
+        gpu_state.set_active_texture(first_unit)?;
+        Ok(units)
+    }
+}